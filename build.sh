@@ -74,6 +74,7 @@ RUN apt-get update && apt-get install -y --no-install-recommends \
     curl \
     ca-certificates \
     pkg-config \
+    libclang-dev \
     libssl-dev \
     cmake \
     git \
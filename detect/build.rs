@@ -0,0 +1,11 @@
+use std::env;
+
+fn main() {
+    // libnvbufsurface lives in the DeepStream install, not on the default linker path
+    if env::var("CARGO_FEATURE_DEEPSTREAM").is_ok() {
+        let deepstream_dir = env::var("DEEPSTREAM_DIR")
+            .unwrap_or_else(|_| "/opt/nvidia/deepstream/deepstream".to_string());
+        println!("cargo:rustc-link-search=native={}/lib", deepstream_dir);
+        println!("cargo:rerun-if-env-changed=DEEPSTREAM_DIR");
+    }
+}
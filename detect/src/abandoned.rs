@@ -0,0 +1,240 @@
+use crate::detections::{Detection, FrameDetections, FrameObserver};
+use crate::events::{Event, Events};
+use crate::zones::Zone;
+use std::env;
+
+/// Objects seen in the first seconds of a source are taken as part of the scene
+const LEARN_SECONDS: f64 = 5.0;
+
+/// Candidates that aren't static yet are forgotten after this long unseen
+const CANDIDATE_TIMEOUT_SECONDS: f64 = 2.0;
+
+pub struct LeftBehindConfig {
+    /// Seconds an object must stay put before it counts as static
+    pub stationary_secs: f64,
+    /// Seconds a static object must be missing before it counts as removed
+    pub removed_secs: f64,
+    /// Allowed drift of the box center, as a fraction of the frame size
+    pub tolerance: f32,
+    /// Class names to watch (all classes when empty)
+    pub classes: Vec<String>,
+}
+
+impl LeftBehindConfig {
+    /// Reads LEFT_BEHIND=true / LEFT_BEHIND_SECONDS / REMOVED_SECONDS / LEFT_BEHIND_TOLERANCE
+    /// / LEFT_BEHIND_CLASSES (comma-separated labels).
+    pub fn from_env() -> Option<LeftBehindConfig> {
+        if env::var("LEFT_BEHIND").map(|v| v != "true").unwrap_or(true) {
+            return None;
+        }
+        let stationary_secs = env::var("LEFT_BEHIND_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0.0)
+            .unwrap_or(30.0);
+        let removed_secs = env::var("REMOVED_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0.0)
+            .unwrap_or(10.0);
+        let tolerance = env::var("LEFT_BEHIND_TOLERANCE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0.02);
+        let classes = env::var("LEFT_BEHIND_CLASSES")
+            .map(|v| v.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect())
+            .unwrap_or_default();
+        Some(LeftBehindConfig { stationary_secs, removed_secs, tolerance, classes })
+    }
+}
+
+/// One entry of the background model: a box of one class that hasn't moved.
+struct StaticObject {
+    source_id: u32,
+    class_id: i32,
+    track_id: Option<u64>,
+    // Normalized box the object is anchored at
+    left: f32,
+    top: f32,
+    width: f32,
+    height: f32,
+    first_seen: f64,
+    last_seen: f64,
+    /// Part of the scene since the source started; only its removal is reported
+    known: bool,
+    is_static: bool,
+}
+
+impl StaticObject {
+    fn iou(&self, left: f32, top: f32, width: f32, height: f32) -> f32 {
+        let x1 = self.left.max(left);
+        let y1 = self.top.max(top);
+        let x2 = (self.left + self.width).min(left + width);
+        let y2 = (self.top + self.height).min(top + height);
+        let inter = (x2 - x1).max(0.0) * (y2 - y1).max(0.0);
+        let union = self.width * self.height + width * height - inter;
+        if union <= 0.0 { 0.0 } else { inter / union }
+    }
+
+    fn center(&self) -> (f32, f32) {
+        (self.left + self.width / 2.0, self.top + self.height / 2.0)
+    }
+}
+
+/// Flags new objects that stay put (abandoned bag) and static objects that disappear.
+/// Detections are matched to the model by class and overlap rather than by track ID alone,
+/// since trackers often drop and re-assign IDs of objects that don't move.
+pub struct LeftBehindDetector {
+    config: LeftBehindConfig,
+    class_ids: Vec<i32>,
+    labels: Vec<String>,
+    zones: Vec<Zone>,
+    events: Events,
+    objects: Vec<StaticObject>,
+    // First timestamp per source, for the learning period
+    started: Vec<(u32, f64)>,
+}
+
+impl LeftBehindDetector {
+    pub fn new(config: LeftBehindConfig, labels: Vec<String>, zones: &[Zone], events: Events) -> LeftBehindDetector {
+        let mut class_ids = Vec::new();
+        for name in &config.classes {
+            match labels.iter().position(|l| l.eq_ignore_ascii_case(name)) {
+                Some(id) => class_ids.push(id as i32),
+                None => eprintln!("Warning: LEFT_BEHIND_CLASSES entry '{}' is not in the labels file", name),
+            }
+        }
+        LeftBehindDetector {
+            config,
+            class_ids,
+            labels,
+            zones: zones.to_vec(),
+            events,
+            objects: Vec::new(),
+            started: Vec::new(),
+        }
+    }
+
+    fn watches(&self, det: &Detection) -> bool {
+        self.config.classes.is_empty() || self.class_ids.contains(&det.class_id)
+    }
+
+    fn event(&self, kind: &str, frame: &FrameDetections, object: &StaticObject) -> Event {
+        let label = self
+            .labels
+            .get(object.class_id as usize)
+            .cloned()
+            .unwrap_or_else(|| format!("class{}", object.class_id));
+        let (cx, cy) = object.center();
+        let mut event = Event::new(kind, frame.source_id, frame.frame_num)
+            .with("label", label)
+            .with("left", object.left * frame.pipeline_width as f32)
+            .with("top", object.top * frame.pipeline_height as f32)
+            .with("width", object.width * frame.pipeline_width as f32)
+            .with("height", object.height * frame.pipeline_height as f32)
+            .with("seconds", frame.seconds() - object.first_seen);
+        if let Some(track_id) = object.track_id {
+            event = event.with("track_id", track_id);
+        }
+        if let Some(zone) = self.zones.iter().find(|z| z.contains(cx, cy)) {
+            event = event.with("zone", zone.name.as_str());
+        }
+        event
+    }
+}
+
+impl FrameObserver for LeftBehindDetector {
+    fn on_frame(&mut self, frame: &FrameDetections) {
+        let now = frame.seconds();
+        let started = match self.started.iter().find(|(s, _)| *s == frame.source_id) {
+            Some((_, t)) => *t,
+            None => {
+                self.started.push((frame.source_id, now));
+                now
+            }
+        };
+        let learning = now - started < LEARN_SECONDS;
+        let (width, height) = (frame.pipeline_width.max(1) as f32, frame.pipeline_height.max(1) as f32);
+
+        let watched: Vec<&Detection> = frame.objects.iter().filter(|d| self.watches(d)).collect();
+        for det in watched {
+            let (left, top, w, h) = (det.left / width, det.top / height, det.width / width, det.height / height);
+            let (cx, cy) = (left + w / 2.0, top + h / 2.0);
+            let matched = self
+                .objects
+                .iter_mut()
+                .filter(|o| o.source_id == frame.source_id && o.class_id == det.class_id)
+                .map(|o| {
+                    let iou = o.iou(left, top, w, h);
+                    (o, iou)
+                })
+                .filter(|(_, iou)| *iou > 0.5)
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(o, _)| o);
+            match matched {
+                Some(object) => {
+                    let (ox, oy) = object.center();
+                    if (ox - cx).abs() > self.config.tolerance || (oy - cy).abs() > self.config.tolerance {
+                        // Moved: start over at the new position
+                        *object = StaticObject {
+                            left,
+                            top,
+                            width: w,
+                            height: h,
+                            first_seen: now,
+                            known: false,
+                            is_static: false,
+                            ..*object
+                        };
+                    }
+                    object.last_seen = now;
+                    object.track_id = det.track_id.or(object.track_id);
+                }
+                None => self.objects.push(StaticObject {
+                    source_id: frame.source_id,
+                    class_id: det.class_id,
+                    track_id: det.track_id,
+                    left,
+                    top,
+                    width: w,
+                    height: h,
+                    first_seen: now,
+                    last_seen: now,
+                    known: learning,
+                    is_static: false,
+                }),
+            }
+        }
+
+        let stationary = self.config.stationary_secs;
+        for i in 0..self.objects.len() {
+            let object = &mut self.objects[i];
+            if object.source_id != frame.source_id
+                || object.is_static
+                || object.last_seen != now
+                || now - object.first_seen < stationary
+            {
+                continue;
+            }
+            object.is_static = true;
+            if !object.known {
+                let event = self.event("object_left_behind", frame, &self.objects[i]);
+                self.events.lock().unwrap().emit(event);
+            }
+        }
+
+        let mut kept = Vec::with_capacity(self.objects.len());
+        for object in std::mem::take(&mut self.objects) {
+            let gone = now - object.last_seen;
+            if object.source_id != frame.source_id {
+                kept.push(object);
+            } else if object.is_static && gone >= self.config.removed_secs {
+                let event = self.event("object_removed", frame, &object).with("left_behind", !object.known);
+                self.events.lock().unwrap().emit(event);
+            } else if object.is_static || gone < CANDIDATE_TIMEOUT_SECONDS {
+                kept.push(object);
+            }
+        }
+        self.objects = kept;
+    }
+}
@@ -0,0 +1,122 @@
+use crate::sinks::SinkSpec;
+use gstreamer::prelude::*;
+use gstreamer_rtsp_server::prelude::*;
+use gstreamer_rtsp_server::RTSPMedia;
+use std::sync::{Arc, Mutex};
+
+/// Seconds between bitrate decisions
+const INTERVAL_SECS: u32 = 2;
+/// Receiver-reported loss above which the bitrate steps down, and below which it may step up
+const LOSS_HIGH: f64 = 0.02;
+const LOSS_LOW: f64 = 0.005;
+/// Round trip this many times the best one seen means packets are queuing on the link
+const RTT_GROWTH: f64 = 1.5;
+const STEP_DOWN: f64 = 0.75;
+const STEP_UP: f64 = 1.1;
+
+/// Link quality from the RTCP receiver reports of every client of a mount: the worst loss
+/// fraction and round trip (ms) among them.
+fn link_stats(medias: &[RTSPMedia]) -> Option<(f64, f64)> {
+    let mut worst: Option<(f64, f64)> = None;
+    let sessions = medias.iter().flat_map(|m| (0..m.n_streams()).filter_map(|i| m.stream(i)?.rtpsession()));
+    for session in sessions {
+        let stats = session.property::<gstreamer::Structure>("stats");
+        let Ok(sources) = stats.get::<glib::ValueArray>("source-stats") else { continue };
+        for source in sources.iter().filter_map(|v| v.get::<gstreamer::Structure>().ok()) {
+            // Receiver reports arrive on the clients' (non-internal) sources
+            if source.get::<bool>("internal").unwrap_or(true) || !source.get::<bool>("have-rb").unwrap_or(false) {
+                continue;
+            }
+            let loss = source.get::<u32>("rb-fractionlost").unwrap_or(0) as f64 / 256.0;
+            // 16.16 fixed point seconds
+            let rtt = source.get::<u32>("rb-round-trip").unwrap_or(0) as f64 / 65.536;
+            let (worst_loss, worst_rtt) = worst.unwrap_or((0.0, 0.0));
+            worst = Some((worst_loss.max(loss), worst_rtt.max(rtt)));
+        }
+    }
+    worst
+}
+
+/// Adjusts an rtsp sink's encoder bitrate within its `abr=<min>-<max>` range from what
+/// its clients report over RTCP: down quickly on loss or a growing round trip, back up
+/// slowly while the link is clean. With several clients on a mount the worst one decides,
+/// since they all share one encode.
+pub struct AdaptiveBitrate {
+    mount: String,
+    encoder: gstreamer::Element,
+    min: u32,
+    max: u32,
+    /// Bits/s per unit of the encoder's bitrate property: x264enc/x265enc (dev mode) take kbit/s
+    unit: u32,
+    best_rtt: Option<f64>,
+    /// The mount's prepared media; shared, so usually one
+    medias: Arc<Mutex<Vec<RTSPMedia>>>,
+}
+
+impl AdaptiveBitrate {
+    /// None when the sink has no `abr` option.
+    pub fn new(pipeline: &gstreamer::Element, sink: &SinkSpec) -> Option<AdaptiveBitrate> {
+        let (min, max) = sink.abr_range()?;
+        let Some(encoder) = pipeline.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name(&sink.encoder_name())) else {
+            eprintln!("Warning: Encoder for rtsp sink {} not found, adaptive bitrate disabled", sink.rtsp_mount());
+            return None;
+        };
+        let software = encoder.factory().is_some_and(|f| matches!(f.name().as_str(), "x264enc" | "x265enc"));
+        Some(AdaptiveBitrate {
+            mount: sink.rtsp_mount().to_string(),
+            encoder,
+            min,
+            max,
+            unit: if software { 1000 } else { 1 },
+            best_rtt: None,
+            medias: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Follows the mount's media on `server` and starts adjusting every INTERVAL_SECS.
+    pub fn start(mut self, server: &gstreamer_rtsp_server::RTSPServer) {
+        let (factory, _) = server.mount_points().expect("Could not get mount points").match_(&self.mount);
+        let medias = self.medias.clone();
+        factory.connect_media_configure(move |_factory, media| {
+            let prepared = medias.clone();
+            media.connect_prepared(move |media| prepared.lock().unwrap().push(media.clone()));
+            let unprepared = medias.clone();
+            media.connect_unprepared(move |media| unprepared.lock().unwrap().retain(|m| m != media));
+        });
+        println!("  Adaptive bitrate: {} between {} and {} bps", self.mount, self.min, self.max);
+        glib::timeout_add_seconds(INTERVAL_SECS, move || {
+            self.adjust();
+            glib::Continue(true)
+        });
+    }
+
+    fn adjust(&mut self) {
+        let Some((loss, rtt)) = link_stats(&self.medias.lock().unwrap()) else {
+            return;
+        };
+        if rtt > 0.0 {
+            self.best_rtt = Some(self.best_rtt.map_or(rtt, |best| best.min(rtt)));
+        }
+        let congested = loss > LOSS_HIGH || self.best_rtt.is_some_and(|best| rtt > best * RTT_GROWTH);
+        let current = self.encoder.property::<u32>("bitrate").saturating_mul(self.unit);
+        let target = if congested {
+            (current as f64 * STEP_DOWN) as u32
+        } else if loss < LOSS_LOW {
+            (current as f64 * STEP_UP) as u32
+        } else {
+            current
+        }
+        .clamp(self.min, self.max);
+        if target != current {
+            println!(
+                "Adaptive bitrate {}: {} -> {} bps (loss {:.1}%, rtt {:.0} ms)",
+                self.mount,
+                current,
+                target,
+                loss * 100.0,
+                rtt
+            );
+            self.encoder.set_property("bitrate", target / self.unit);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::sinks::SinkSpec;
+use crate::viewers::{self, Viewers};
+use gstreamer::prelude::*;
+use gstreamer_rtsp_server::gst_rtsp::RTSPStatusCode;
+use gstreamer_rtsp_server::prelude::*;
+use gstreamer_rtsp_server::{RTSPClient, RTSPContext, RTSPServer};
+use std::net::IpAddr;
+
+/// An address or CIDR block, e.g. `10.0.0.0/8` or `fd00::/8`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Network {
+    address: IpAddr,
+    prefix: u32,
+}
+
+impl Network {
+    pub fn parse(value: &str) -> Result<Network, String> {
+        let (address, prefix) = value.split_once('/').unwrap_or((value, ""));
+        let address: IpAddr = address.trim().parse().map_err(|_| format!("'{}' is not an IP address or CIDR block", value))?;
+        let bits = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            "" => bits,
+            prefix => match prefix.parse::<u32>() {
+                Ok(prefix) if prefix <= bits => prefix,
+                _ => return Err(format!("'{}' has an invalid prefix length", value)),
+            },
+        };
+        Ok(Network { address, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // Leading bits shared with the network address, counted in 128 bits
+        let (network, ip, offset) = match (self.address, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => (u32::from(network) as u128, u32::from(ip) as u128, 96),
+            (IpAddr::V6(network), IpAddr::V6(ip)) => (u128::from(network), u128::from(ip), 0),
+            _ => return false,
+        };
+        (network ^ ip).leading_zeros() >= self.prefix + offset
+    }
+}
+
+/// Who may play an rtsp sink's mount, how many at once, and how fast each is sent the stream.
+#[derive(Clone, Debug, Default)]
+pub struct RtspAccess {
+    /// Empty admits every address not denied
+    pub allow: Vec<Network>,
+    pub deny: Vec<Network>,
+    pub max_clients: Option<usize>,
+    /// Bits/s the server sends a client at most
+    pub client_bitrate: Option<u64>,
+}
+
+fn networks(list: Option<String>) -> Result<Vec<Network>, String> {
+    list.map(|l| l.split(',').map(str::trim).filter(|n| !n.is_empty()).map(Network::parse).collect())
+        .unwrap_or(Ok(Vec::new()))
+}
+
+impl RtspAccess {
+    pub fn parse(
+        allow: Option<String>,
+        deny: Option<String>,
+        max_clients: Option<String>,
+        client_bitrate: Option<String>,
+    ) -> Result<RtspAccess, String> {
+        let positive = |key: &str, value: Option<String>| match value {
+            None => Ok(None),
+            Some(v) => match v.trim().parse::<u64>() {
+                Ok(n) if n > 0 => Ok(Some(n)),
+                _ => Err(format!("{} must be a positive integer, got '{}'", key, v)),
+            },
+        };
+        Ok(RtspAccess {
+            allow: networks(allow).map_err(|e| format!("allow: {}", e))?,
+            deny: networks(deny).map_err(|e| format!("deny: {}", e))?,
+            max_clients: positive("max-clients", max_clients)?.map(|n| n as usize),
+            client_bitrate: positive("client-bitrate", client_bitrate)?,
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty() && self.max_clients.is_none() && self.client_bitrate.is_none()
+    }
+
+    /// Deny entries win over allow entries.
+    pub fn admits(&self, ip: IpAddr) -> bool {
+        !self.deny.iter().any(|n| n.contains(ip)) && (self.allow.is_empty() || self.allow.iter().any(|n| n.contains(ip)))
+    }
+}
+
+/// The sink whose mount `ctx` requests, with its access settings.
+fn requested<'a>(ctx: &RTSPContext, mounts: &'a [(String, RtspAccess)]) -> Option<&'a (String, RtspAccess)> {
+    let path = viewers::request_path(ctx)?;
+    mounts.iter().filter(|(mount, _)| path.starts_with(mount.as_str())).max_by_key(|(mount, _)| mount.len())
+}
+
+/// Refuses clients outside a mount's allow list (403) before DESCRIBE and SETUP, and clients
+/// beyond its `max-clients` (503) before PLAY, given the server's port and its viewers.
+fn check(
+    client: &RTSPClient,
+    ctx: &RTSPContext,
+    mounts: &[(String, RtspAccess)],
+    playing: Option<(&str, &Viewers)>,
+) -> RTSPStatusCode {
+    let Some((mount, access)) = requested(ctx, mounts) else { return RTSPStatusCode::Ok };
+    let address = viewers::client_address(client);
+    if !address.parse().is_ok_and(|ip| access.admits(ip)) {
+        eprintln!("Warning: RTSP client {} refused on {}: address not allowed", address, mount);
+        return RTSPStatusCode::Forbidden;
+    }
+    if let (Some(max), Some((port, viewers))) = (access.max_clients, playing) {
+        let key = format!("{}{}", port, mount);
+        if !viewers.is_watching(&key, &address) && viewers.count(&key) >= max {
+            eprintln!("Warning: RTSP client {} refused on {}: {} clients already playing", address, mount, max);
+            return RTSPStatusCode::ServiceUnavailable;
+        }
+    }
+    RTSPStatusCode::Ok
+}
+
+/// Applies the access settings of the rtsp `sinks` served by `server` on `port`: address
+/// lists and client limits per request, and the per-client bitrate on the sinks of each
+/// prepared media, which pace the RTP sent to every client of the mount.
+pub fn enforce(server: &RTSPServer, port: &str, sinks: &[&SinkSpec], viewers: &Viewers) {
+    // Already validated when the sinks were parsed
+    let mounts: Vec<(String, RtspAccess)> = sinks
+        .iter()
+        .map(|s| (s.rtsp_mount().to_string(), s.rtsp_access().expect("Invalid RTSP access options")))
+        .filter(|(_, access)| !access.is_open())
+        .collect();
+    if mounts.is_empty() {
+        return;
+    }
+    let mount_points = server.mount_points().expect("Could not get mount points");
+    for (mount, access) in &mounts {
+        let Some(bitrate) = access.client_bitrate else { continue };
+        let (factory, _) = mount_points.match_(mount);
+        factory.connect_media_configure(move |_factory, media| {
+            media.connect_prepared(move |media| {
+                let top = std::iter::successors(Some(media.element()), |e| e.parent().and_then(|p| p.downcast().ok()))
+                    .last();
+                let Some(bin) = top.and_then(|t| t.downcast::<gstreamer::Bin>().ok()) else { return };
+                // The RTP and RTCP udpsinks, and the appsinks of TCP clients
+                for sink in bin.iterate_recurse().into_iter().flatten() {
+                    if sink.has_property("max-bitrate", Some(u64::static_type())) {
+                        sink.set_property("max-bitrate", bitrate);
+                    }
+                }
+            });
+        });
+    }
+    let port = port.to_string();
+    let viewers = viewers.clone();
+    server.connect_client_connected(move |_server, client| {
+        let (describe, setup) = (mounts.clone(), mounts.clone());
+        client.connect_pre_describe_request(move |client, ctx| check(client, ctx, &describe, None));
+        client.connect_pre_setup_request(move |client, ctx| check(client, ctx, &setup, None));
+        let (play, port, viewers) = (mounts.clone(), port.clone(), viewers.clone());
+        client.connect_pre_play_request(move |client, ctx| check(client, ctx, &play, Some((&port, &viewers))));
+    });
+}
@@ -0,0 +1,255 @@
+use crate::clock::utc_datetime;
+use crate::detections::{FrameDetections, FrameObserver};
+use crate::parquet::{self, Column};
+use crate::zones::Zone;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnalyticsFormat {
+    Csv,
+    Parquet,
+}
+
+pub struct AnalyticsConfig {
+    pub dir: String,
+    pub format: AnalyticsFormat,
+    /// Seconds per aggregation period, aligned to the wall clock
+    pub interval_secs: u64,
+    /// A new file every hour instead of every day
+    pub hourly: bool,
+}
+
+impl AnalyticsConfig {
+    /// Reads ANALYTICS_DIR (required) / ANALYTICS_FORMAT / ANALYTICS_INTERVAL / ANALYTICS_ROTATE.
+    pub fn from_env() -> Option<AnalyticsConfig> {
+        let dir = env::var("ANALYTICS_DIR").ok().filter(|v| !v.trim().is_empty())?;
+        let format = match env::var("ANALYTICS_FORMAT").unwrap_or_else(|_| "csv".to_string()).trim() {
+            "csv" => AnalyticsFormat::Csv,
+            "parquet" => AnalyticsFormat::Parquet,
+            other => {
+                eprintln!("Error: Unknown ANALYTICS_FORMAT '{}' (expected csv or parquet)", other);
+                std::process::exit(2);
+            }
+        };
+        let interval_secs = env::var("ANALYTICS_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(60);
+        let hourly = match env::var("ANALYTICS_ROTATE").unwrap_or_else(|_| "daily".to_string()).trim() {
+            "daily" => false,
+            "hourly" => true,
+            other => {
+                eprintln!("Error: Unknown ANALYTICS_ROTATE '{}' (expected daily or hourly)", other);
+                std::process::exit(2);
+            }
+        };
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            eprintln!("Warning: Could not create ANALYTICS_DIR {}: {}", dir, e);
+        }
+        Some(AnalyticsConfig { dir, format, interval_secs, hourly })
+    }
+
+    /// File the period starting at `timestamp_ms` goes to, e.g. `analytics_2025-10-09.csv`
+    /// or `analytics_2025-10-09T08.parquet`.
+    fn path(&self, timestamp_ms: u64) -> String {
+        let stamp = utc_datetime(timestamp_ms / 1000);
+        let stamp = if self.hourly { &stamp[..13] } else { &stamp[..10] };
+        let extension = match self.format {
+            AnalyticsFormat::Csv => "csv",
+            AnalyticsFormat::Parquet => "parquet",
+        };
+        format!("{}/analytics_{}.{}", self.dir.trim_end_matches('/'), stamp, extension)
+    }
+}
+
+/// Aggregate of one class in one zone of one source over a period.
+#[derive(Default)]
+struct Tally {
+    tracks: HashSet<u64>,
+    /// Most objects seen in a single frame
+    max: u64,
+    /// Objects summed over the source's frames, for the average occupancy
+    total: u64,
+}
+
+/// One exported row.
+struct Row {
+    timestamp_ms: u64,
+    source_id: u32,
+    zone: String,
+    class: String,
+    count: u64,
+    avg_occupancy: f64,
+    max_occupancy: u64,
+}
+
+/// Aggregates detections per period, source, zone and class into rows for BI tools:
+/// `count` is the distinct tracks seen (without a tracker, the most in one frame), the
+/// occupancy columns how many were in the zone at once. The `frame` zone covers the whole
+/// picture, then one row set per ZONES zone by box center. CSV files are appended to;
+/// Parquet files can't be, so the rows of the current file are rewritten every period.
+pub struct AnalyticsExporter {
+    config: AnalyticsConfig,
+    labels: Vec<String>,
+    zones: Vec<Zone>,
+    period: Option<u64>,
+    frames: HashMap<u32, u64>,
+    tallies: BTreeMap<(u32, usize, String), Tally>,
+    /// Parquet rows of the file being written
+    rows: Vec<Row>,
+    /// Rotation path of the current file, and the file actually written
+    path: Option<(String, String)>,
+    csv: Option<File>,
+}
+
+impl AnalyticsExporter {
+    pub fn new(config: AnalyticsConfig, labels: Vec<String>, zones: &[Zone]) -> AnalyticsExporter {
+        let zones = std::iter::once(Zone::full_frame()).chain(zones.iter().cloned()).collect();
+        AnalyticsExporter {
+            config,
+            labels,
+            zones,
+            period: None,
+            frames: HashMap::new(),
+            tallies: BTreeMap::new(),
+            rows: Vec::new(),
+            path: None,
+            csv: None,
+        }
+    }
+
+    /// Period (in interval units since the epoch) of the current wall-clock time.
+    fn current_period(&self) -> u64 {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        now / self.config.interval_secs
+    }
+
+    /// Writes the rows of the finished period to the file its start time rotates into.
+    fn export(&mut self) {
+        let Some(period) = self.period else { return };
+        let timestamp_ms = period * self.config.interval_secs * 1000;
+        let frames = std::mem::take(&mut self.frames);
+        let rows: Vec<Row> = std::mem::take(&mut self.tallies)
+            .into_iter()
+            .map(|((source_id, zone, class), tally)| {
+                let source_frames = frames.get(&source_id).copied().unwrap_or(1).max(1);
+                Row {
+                    timestamp_ms,
+                    source_id,
+                    zone: self.zones[zone].name.clone(),
+                    class,
+                    count: if tally.tracks.is_empty() { tally.max } else { tally.tracks.len() as u64 },
+                    avg_occupancy: tally.total as f64 / source_frames as f64,
+                    max_occupancy: tally.max,
+                }
+            })
+            .collect();
+
+        let rotation = self.config.path(timestamp_ms);
+        if self.path.as_ref().map(|(r, _)| r) != Some(&rotation) {
+            self.rows.clear();
+            self.csv = None;
+            // A Parquet file left by an earlier run in the same hour/day is kept, not rewritten
+            let mut path = rotation.clone();
+            let mut n = 1;
+            while self.config.format == AnalyticsFormat::Parquet && std::path::Path::new(&path).exists() {
+                path = rotation.replace(".parquet", &format!(".{}.parquet", n));
+                n += 1;
+            }
+            self.path = Some((rotation, path));
+        }
+        let path = self.path.as_ref().map(|(_, p)| p.clone()).unwrap_or_default();
+        let result = match self.config.format {
+            AnalyticsFormat::Csv => self.append_csv(&path, &rows),
+            AnalyticsFormat::Parquet => {
+                self.rows.extend(rows);
+                write_parquet(&path, &self.rows, self.config.interval_secs)
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("Warning: Failed to write analytics to {}: {}", path, e);
+        }
+    }
+
+    fn append_csv(&mut self, path: &str, rows: &[Row]) -> std::io::Result<()> {
+        if self.csv.is_none() {
+            let is_new = std::fs::metadata(path).map(|m| m.len() == 0).unwrap_or(true);
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            if is_new {
+                writeln!(file, "timestamp_ms,period_seconds,source_id,zone,class,count,avg_occupancy,max_occupancy")?;
+            }
+            self.csv = Some(file);
+        }
+        let file = self.csv.as_mut().expect("opened above");
+        for row in rows {
+            writeln!(
+                file,
+                "{},{},{},{},{},{},{:.2},{}",
+                row.timestamp_ms,
+                self.config.interval_secs,
+                row.source_id,
+                row.zone,
+                row.class,
+                row.count,
+                row.avg_occupancy,
+                row.max_occupancy
+            )?;
+        }
+        file.flush()
+    }
+}
+
+fn write_parquet(path: &str, rows: &[Row], interval_secs: u64) -> std::io::Result<()> {
+    parquet::write(
+        path,
+        &[
+            Column::Int64("timestamp_ms", rows.iter().map(|r| r.timestamp_ms as i64).collect()),
+            Column::Int64("period_seconds", vec![interval_secs as i64; rows.len()]),
+            Column::Int64("source_id", rows.iter().map(|r| r.source_id as i64).collect()),
+            Column::Text("zone", rows.iter().map(|r| r.zone.clone()).collect()),
+            Column::Text("class", rows.iter().map(|r| r.class.clone()).collect()),
+            Column::Int64("count", rows.iter().map(|r| r.count as i64).collect()),
+            Column::Double("avg_occupancy", rows.iter().map(|r| r.avg_occupancy).collect()),
+            Column::Int64("max_occupancy", rows.iter().map(|r| r.max_occupancy as i64).collect()),
+        ],
+    )
+}
+
+impl FrameObserver for AnalyticsExporter {
+    fn on_frame(&mut self, frame: &FrameDetections) {
+        let period = self.current_period();
+        if self.period.is_some_and(|p| p != period) {
+            self.export();
+        }
+        self.period = Some(period);
+        *self.frames.entry(frame.source_id).or_default() += 1;
+
+        let (width, height) = (frame.pipeline_width.max(1) as f32, frame.pipeline_height.max(1) as f32);
+        let mut in_frame: HashMap<(usize, String), u64> = HashMap::new();
+        for det in &frame.objects {
+            let label = self.labels.get(det.class_id as usize).cloned().unwrap_or_else(|| det.class_id.to_string());
+            let (x, y) = ((det.left + det.width / 2.0) / width, (det.top + det.height / 2.0) / height);
+            for (zone, _) in self.zones.iter().enumerate().filter(|(_, z)| z.contains(x, y)) {
+                *in_frame.entry((zone, label.clone())).or_default() += 1;
+                let tally = self.tallies.entry((frame.source_id, zone, label.clone())).or_default();
+                if let Some(track_id) = det.track_id {
+                    tally.tracks.insert(track_id);
+                }
+            }
+        }
+        for ((zone, label), count) in in_frame {
+            let tally = self.tallies.entry((frame.source_id, zone, label)).or_default();
+            tally.max = tally.max.max(count);
+            tally.total += count;
+        }
+    }
+
+    fn finish(&mut self) {
+        self.export();
+    }
+}
@@ -0,0 +1,268 @@
+use crate::events::{Event, Events};
+use crate::png;
+use crate::processing::{self, MappedFrame};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::time::{Duration, Instant};
+
+const FRAME_ELEMENT: &str = "anpr_frames";
+
+/// Snapshots are downscaled to at most this width
+const MAX_SNAPSHOT_WIDTH: u32 = 1280;
+
+/// Licence plate recognition: a plate detector SGIE on the vehicles, then an OCR
+/// classifier (LPRNet style, custom parser) on the plates, whose result label is the
+/// plate text. Recognized plates are checked against a watchlist.
+pub struct AnprConfig {
+    pub detector_config: String,
+    pub recognizer_config: String,
+    pub detector_id: u32,
+    pub recognizer_id: u32,
+    pub watchlist_path: Option<String>,
+    /// Edit distance (after OCR confusion folding) at or below which a read matches
+    pub max_distance: usize,
+    pub snapshot_dir: Option<String>,
+    /// The same plate isn't alerted again on a source within this time
+    pub alert_interval: Duration,
+}
+
+impl AnprConfig {
+    /// Reads LPD_CONFIG and LPR_CONFIG (both required), LPD_UNIQUE_ID, LPR_UNIQUE_ID,
+    /// ANPR_WATCHLIST, ANPR_MAX_DISTANCE, ANPR_SNAPSHOT_DIR and ANPR_ALERT_INTERVAL (seconds).
+    pub fn from_env() -> Option<AnprConfig> {
+        let detector_config = env::var("LPD_CONFIG").ok();
+        let recognizer_config = env::var("LPR_CONFIG").ok();
+        let (detector_config, recognizer_config) = match (detector_config, recognizer_config) {
+            (Some(detector), Some(recognizer)) => (detector, recognizer),
+            (None, None) => return None,
+            _ => {
+                eprintln!("Warning: Plate recognition needs both LPD_CONFIG and LPR_CONFIG. Disabled.");
+                return None;
+            }
+        };
+        let detector_id = env::var("LPD_UNIQUE_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(6);
+        let recognizer_id = env::var("LPR_UNIQUE_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(7);
+        let watchlist_path = env::var("ANPR_WATCHLIST").ok();
+        let max_distance = env::var("ANPR_MAX_DISTANCE").ok().and_then(|v| v.parse().ok()).unwrap_or(1);
+        let snapshot_dir = env::var("ANPR_SNAPSHOT_DIR").ok();
+        let alert_interval = env::var("ANPR_ALERT_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(60));
+        Some(AnprConfig {
+            detector_config,
+            recognizer_config,
+            detector_id,
+            recognizer_id,
+            watchlist_path,
+            max_distance,
+            snapshot_dir,
+            alert_interval,
+        })
+    }
+
+    /// Plate detector on the PGIE objects, the recognizer on its plates, then mapped
+    /// frames so alerts can save a snapshot.
+    pub fn stage(&self) -> String {
+        format!(
+            "nvinfer name=lpd process-mode=2 config-file-path={} unique-id={} ! \
+             nvinfer name=lpr process-mode=2 infer-on-gie-id={} config-file-path={} unique-id={} ! {}",
+            self.detector_config,
+            self.detector_id,
+            self.detector_id,
+            self.recognizer_config,
+            self.recognizer_id,
+            processing::mappable_stage(FRAME_ELEMENT)
+        )
+    }
+}
+
+/// One watchlist line: `PLATE[,label[,priority]]`.
+pub struct WatchEntry {
+    pub plate: String,
+    pub label: String,
+    pub priority: String,
+    // Folded form used for matching
+    key: String,
+}
+
+const PRIORITIES: [&str; 3] = ["high", "medium", "low"];
+
+/// Plates to alert on; blank lines and lines starting with # are skipped.
+pub fn load_watchlist(path: &str) -> Result<Vec<WatchEntry>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut entries = Vec::new();
+    for (line_no, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split(',').map(str::trim);
+        let plate = normalize(fields.next().unwrap_or_default());
+        if plate.is_empty() {
+            return Err(format!("line {}: missing plate", line_no + 1));
+        }
+        let label = fields.next().filter(|l| !l.is_empty()).unwrap_or("watchlist").to_string();
+        let priority = fields.next().filter(|p| !p.is_empty()).unwrap_or("high").to_lowercase();
+        if !PRIORITIES.contains(&priority.as_str()) {
+            return Err(format!("line {}: priority '{}' is not high, medium or low", line_no + 1, priority));
+        }
+        entries.push(WatchEntry { key: fold(&plate), plate, label, priority });
+    }
+    Ok(entries)
+}
+
+/// Uppercase letters and digits only, so "ab-123 c" and "AB123C" are the same plate.
+fn normalize(plate: &str) -> String {
+    plate.chars().filter(char::is_ascii_alphanumeric).map(|c| c.to_ascii_uppercase()).collect()
+}
+
+/// Maps characters OCR commonly confuses onto one of them.
+fn fold(plate: &str) -> String {
+    plate
+        .chars()
+        .map(|c| match c {
+            'O' | 'Q' | 'D' => '0',
+            'I' | 'L' => '1',
+            'Z' => '2',
+            'S' => '5',
+            'B' => '8',
+            'G' => '6',
+            c => c,
+        })
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Index and distance of the closest watchlist entry within `max_distance` of a normalized read.
+fn best_match(watchlist: &[WatchEntry], read: &str, max_distance: usize) -> Option<(usize, usize)> {
+    let key = fold(read);
+    watchlist
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| (i, if entry.plate == read { 0 } else { edit_distance(&entry.key, &key) }))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .min_by_key(|(_, distance)| *distance)
+}
+
+/// A watchlist hit seen on this frame.
+struct Hit {
+    read: String,
+    confidence: f32,
+    entry: usize,
+    distance: usize,
+    track_id: Option<u64>,
+}
+
+struct AnprMatcher {
+    config: AnprConfig,
+    watchlist: Vec<WatchEntry>,
+    events: Events,
+    // (source, watchlist entry) -> last alert
+    alerted: HashMap<(u32, usize), Instant>,
+}
+
+impl AnprMatcher {
+    fn process(&mut self, frame: &mut MappedFrame) {
+        let mut hits = Vec::new();
+        for obj in frame.meta.objects() {
+            if obj.unique_component_id != self.config.detector_id as i32 {
+                continue;
+            }
+            let Some((text, confidence)) = obj.classifier_result(self.config.recognizer_id) else { continue };
+            let read = normalize(&text);
+            if read.is_empty() {
+                continue;
+            }
+            obj.append_display_text(&format!(" {}", read));
+            let Some((entry, distance)) = best_match(&self.watchlist, &read, self.config.max_distance) else {
+                continue;
+            };
+            obj.append_display_text(&format!(" [{}]", self.watchlist[entry].label));
+            // SAFETY: a plate detected by the secondary detector points at its vehicle object
+            let track_id = unsafe { obj.parent.as_ref() }.and_then(|p| p.track_id());
+            hits.push(Hit { read, confidence, entry, distance, track_id });
+        }
+
+        let (source_id, frame_num) = (frame.meta.source_id, frame.meta.frame_num);
+        let mut snapshot: Option<Option<String>> = None;
+        for hit in hits {
+            let key = (source_id, hit.entry);
+            if self.alerted.get(&key).is_some_and(|at| at.elapsed() < self.config.alert_interval) {
+                continue;
+            }
+            self.alerted.insert(key, Instant::now());
+            let entry = &self.watchlist[hit.entry];
+            let mut event = Event::new("plate_watchlist_match", source_id, frame_num)
+                .with("plate", entry.plate.as_str())
+                .with("read", hit.read.as_str())
+                .with("label", entry.label.as_str())
+                .with("priority", entry.priority.as_str())
+                .with("distance", hit.distance as u64)
+                .with("confidence", hit.confidence);
+            if let Some(track_id) = hit.track_id {
+                event = event.with("track_id", track_id);
+            }
+            // One snapshot per frame, shared by all of its alerts
+            let path = snapshot.get_or_insert_with(|| self.save_snapshot(frame, &entry.plate));
+            if let Some(path) = path {
+                event = event.with("snapshot", path.as_str());
+            }
+            self.events.lock().unwrap().emit(event);
+        }
+    }
+
+    fn save_snapshot(&self, frame: &MappedFrame, plate: &str) -> Option<String> {
+        let dir = self.config.snapshot_dir.as_ref()?;
+        let millis = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let path = format!("{}/anpr_src{}_{}_{}.png", dir, frame.meta.source_id, millis, plate);
+        let (width, height, rgb) = frame.rgb(frame.width.div_ceil(MAX_SNAPSHOT_WIDTH));
+        match png::write_rgb(&path, width, height, &rgb) {
+            Ok(()) => Some(path),
+            Err(e) => {
+                eprintln!("Warning: Failed to write ANPR snapshot {}: {}", path, e);
+                None
+            }
+        }
+    }
+}
+
+/// Labels recognized plates on the OSD and emits plate_watchlist_match events.
+pub fn attach(bin: &gstreamer::Element, config: AnprConfig, events: Events) {
+    let watchlist = match &config.watchlist_path {
+        Some(path) => match load_watchlist(path) {
+            Ok(watchlist) => watchlist,
+            Err(e) => {
+                eprintln!("Warning: Could not load ANPR_WATCHLIST {}: {}. Watchlist alerts disabled.", path, e);
+                Vec::new()
+            }
+        },
+        None => Vec::new(),
+    };
+    if let Some(dir) = &config.snapshot_dir {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("Warning: Could not create ANPR_SNAPSHOT_DIR {}: {}", dir, e);
+        }
+    }
+    let mut matcher = AnprMatcher { config, watchlist, events, alerted: HashMap::new() };
+    processing::attach_at(bin, FRAME_ELEMENT, Box::new(move |frame: &mut MappedFrame| matcher.process(frame)));
+}
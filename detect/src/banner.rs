@@ -0,0 +1,64 @@
+use gstreamer::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Seconds a banner stays up when the command doesn't say
+pub const DEFAULT_SECONDS: u32 = 10;
+
+/// Distance of the banner from the top left corner of every frame, in pixels
+const MARGIN: u32 = 16;
+
+struct Message {
+    text: String,
+    until: Instant,
+}
+
+/// Operator text drawn over every source's video for a while, set through the control API.
+#[derive(Clone, Default)]
+pub struct Banner {
+    message: Arc<Mutex<Option<Message>>>,
+}
+
+impl Banner {
+    pub fn show(&self, text: &str, seconds: u32) {
+        let until = Instant::now() + Duration::from_secs(seconds as u64);
+        *self.message.lock().unwrap() = Some(Message { text: text.to_string(), until });
+    }
+
+    /// Takes the banner down early; false if none was showing.
+    pub fn clear(&self) -> bool {
+        self.message.lock().unwrap().take().is_some_and(|m| m.until > Instant::now())
+    }
+
+    /// The text to draw now, dropping the message once it has expired.
+    fn current(&self) -> Option<String> {
+        let mut message = self.message.lock().unwrap();
+        if message.as_ref().is_some_and(|m| m.until <= Instant::now()) {
+            *message = None;
+        }
+        message.as_ref().map(|m| m.text.clone())
+    }
+}
+
+/// Adds the banner as display-meta text to each frame before nvdsosd, so it shows on every
+/// output drawn with the OSD (not on `osd=false` sinks or the clean recording).
+pub fn attach(bin: &gstreamer::Element, banner: Banner) {
+    let Some(osd) = bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("osd")) else {
+        eprintln!("Warning: nvdsosd element not found, banners disabled");
+        return;
+    };
+    let sink_pad = osd.static_pad("sink").expect("nvdsosd has a sink pad");
+    sink_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        let Some(text) = banner.current() else { return gstreamer::PadProbeReturn::Ok };
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            for frame in batch.frames() {
+                // Readable from across the room on any output size
+                let font_size = (frame.pipeline_height / 24).max(14);
+                let Some(display) = frame.add_display_meta() else { continue };
+                display.text_params[0].set(&text, MARGIN, MARGIN, font_size);
+                display.num_labels = 1;
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
@@ -0,0 +1,246 @@
+use crate::clock::utc_datetime;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+const INDEX_HEADER: &str = "camera,start_unix,end_unix,start_utc,file";
+
+/// One segment file of one camera.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub camera: String,
+    /// Relative to RECORD_DIR, e.g. `cam1/cam1_2026-10-14T08-00-00Z.mp4`
+    pub file: String,
+    pub start: u64,
+    pub end: u64,
+    /// File size once the segment is closed
+    pub bytes: u64,
+}
+
+impl Segment {
+    fn parse(line: &str) -> Option<Segment> {
+        let fields: Vec<&str> = line.split(',').collect();
+        let [camera, start, end, _, file] = fields[..] else { return None };
+        Some(Segment {
+            camera: camera.to_string(),
+            file: file.to_string(),
+            start: start.parse().ok()?,
+            end: end.parse().ok()?,
+            bytes: 0,
+        })
+    }
+
+    fn row(&self) -> String {
+        format!("{},{},{},{},{}", self.camera, self.start, self.end, utc_datetime(self.start), self.file)
+    }
+}
+
+/// How much footage is kept; the oldest segments are deleted first.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Retention {
+    pub seconds: Option<u64>,
+    pub bytes: Option<u64>,
+}
+
+impl Retention {
+    /// Reads RECORD_RETENTION_HOURS and RECORD_RETENTION_GB; unset keeps everything.
+    pub fn from_env() -> Retention {
+        let limit = |key: &str| match env::var(key) {
+            Err(_) => None,
+            Ok(value) => match value.trim().parse::<f64>() {
+                Ok(n) if n > 0.0 => Some(n),
+                _ => {
+                    eprintln!("Error: {} must be a positive number, got '{}'", key, value);
+                    std::process::exit(2);
+                }
+            },
+        };
+        Retention {
+            seconds: limit("RECORD_RETENTION_HOURS").map(|hours| (hours * 3600.0) as u64),
+            bytes: limit("RECORD_RETENTION_GB").map(|gb| (gb * 1e9) as u64),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let hours = |seconds: u64| seconds as f64 / 3600.0;
+        match (self.seconds, self.bytes) {
+            (None, None) => "kept forever".to_string(),
+            (Some(seconds), None) => format!("kept {}h", hours(seconds)),
+            (None, Some(bytes)) => format!("kept up to {} GB", bytes as f64 / 1e9),
+            (Some(seconds), Some(bytes)) => format!("kept {}h, up to {} GB", hours(seconds), bytes as f64 / 1e9),
+        }
+    }
+}
+
+/// Every camera's segments under RECORD_DIR: the ones being written, the closed ones waiting
+/// for the trigger decision, and the kept ones, which are listed in `index.csv`.
+pub struct Archive {
+    dir: String,
+    /// Seconds of footage kept before and after activity; None keeps every segment
+    window: Option<(u64, u64)>,
+    retention: Retention,
+    /// Segment being written per camera
+    open: Vec<Segment>,
+    pending: Vec<Segment>,
+    kept: Vec<Segment>,
+    /// (camera, unix second) of every recent second with activity
+    activity: Vec<(String, u64)>,
+}
+
+impl Archive {
+    /// Picks up the segments kept by earlier runs from `index.csv`, so retention covers them too.
+    pub fn load(dir: &str, window: Option<(u64, u64)>, retention: Retention) -> Archive {
+        let dir = dir.trim_end_matches('/').to_string();
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("Error: Could not create RECORD_DIR {}: {}", dir, e);
+            std::process::exit(2);
+        }
+        let kept = index(&dir)
+            .into_iter()
+            .filter_map(|s| {
+                let bytes = fs::metadata(Path::new(&dir).join(&s.file)).ok()?.len();
+                Some(Segment { bytes, ..s })
+            })
+            .collect();
+        Archive { dir, window, retention, open: Vec::new(), pending: Vec::new(), kept, activity: Vec::new() }
+    }
+
+    /// Starts a new segment of `camera` at `now` and returns the path to write it to; the
+    /// camera's previous segment ends there.
+    pub fn begin(&mut self, camera: &str, now: u64) -> String {
+        self.close(camera, now);
+        let stamp = utc_datetime(now).replace(':', "-");
+        let file = format!("{}/{}_{}.mp4", camera, camera, stamp);
+        let path = format!("{}/{}", self.dir, file);
+        if let Err(e) = fs::create_dir_all(format!("{}/{}", self.dir, camera)) {
+            eprintln!("Warning: Could not create the directory for {}: {}", path, e);
+        }
+        self.open.push(Segment { camera: camera.to_string(), file, start: now, end: now, bytes: 0 });
+        path
+    }
+
+    /// Ends the segment `camera` is writing, e.g. when its pipeline stopped.
+    pub fn close(&mut self, camera: &str, now: u64) {
+        let Some(index) = self.open.iter().position(|s| s.camera == camera) else { return };
+        let mut segment = self.open.remove(index);
+        segment.end = now;
+        self.pending.push(segment);
+    }
+
+    /// Notes activity on `camera` at `now`, keeping the segments around it.
+    pub fn active(&mut self, camera: &str, now: u64) {
+        if !self.activity.iter().any(|(c, t)| c == camera && *t == now) {
+            self.activity.push((camera.to_string(), now));
+        }
+    }
+
+    /// Decides about every closed segment whose window has passed: kept ones go into the
+    /// index, the rest are deleted. Then applies the retention limits.
+    pub fn settle(&mut self, now: u64) {
+        let pending = std::mem::take(&mut self.pending);
+        for mut segment in pending {
+            let keep = match self.window {
+                None => true,
+                Some((pre, _)) if now < segment.end + pre => {
+                    self.pending.push(segment);
+                    continue;
+                }
+                Some((pre, post)) => self.activity.iter().any(|(camera, t)| {
+                    *camera == segment.camera && *t + post >= segment.start && *t <= segment.end + pre
+                }),
+            };
+            if keep {
+                if self.window.is_some() {
+                    println!("{}: kept {} ({}s)", segment.camera, segment.file, segment.end - segment.start);
+                }
+                segment.bytes = fs::metadata(format!("{}/{}", self.dir, segment.file)).map(|m| m.len()).unwrap_or(0);
+                self.append(&segment);
+                self.kept.push(segment);
+            } else if let Err(e) = fs::remove_file(format!("{}/{}", self.dir, segment.file)) {
+                eprintln!("Warning: Could not delete {} without activity: {}", segment.file, e);
+            }
+        }
+        // Activity matters to segments starting up to `post` seconds later
+        let post = self.window.map_or(0, |(_, post)| post);
+        let earliest = |camera: &str| {
+            self.open.iter().chain(&self.pending).filter(|s| s.camera == camera).map(|s| s.start).min().unwrap_or(now)
+        };
+        let activity: Vec<(String, u64)> =
+            self.activity.iter().filter(|(camera, t)| t + post >= earliest(camera)).cloned().collect();
+        self.activity = activity;
+        self.retain(now);
+    }
+
+    /// Ends every segment being written and decides about all of them with the activity
+    /// seen so far, at shutdown.
+    pub fn finish(&mut self, now: u64) {
+        let cameras: Vec<String> = self.open.iter().map(|s| s.camera.clone()).collect();
+        for camera in cameras {
+            self.close(&camera, now);
+        }
+        let pre = self.window.map_or(0, |(pre, _)| pre);
+        self.settle(now + pre);
+    }
+
+    /// Deletes the oldest kept segments beyond RECORD_RETENTION_HOURS / RECORD_RETENTION_GB.
+    fn retain(&mut self, now: u64) {
+        let Retention { seconds, bytes } = self.retention;
+        let mut total: u64 = self.kept.iter().map(|s| s.bytes).sum();
+        let mut expired = 0;
+        for segment in &self.kept {
+            let too_old = seconds.is_some_and(|s| segment.end + s < now);
+            let too_big = bytes.is_some_and(|b| total > b);
+            if !too_old && !too_big {
+                break;
+            }
+            total -= segment.bytes;
+            if let Err(e) = fs::remove_file(format!("{}/{}", self.dir, segment.file)) {
+                eprintln!("Warning: Could not delete expired segment {}: {}", segment.file, e);
+            }
+            expired += 1;
+        }
+        if expired > 0 {
+            self.kept.drain(..expired);
+            self.rewrite();
+        }
+    }
+
+    fn append(&self, segment: &Segment) {
+        let path = format!("{}/index.csv", self.dir);
+        let new = !Path::new(&path).exists();
+        let result = OpenOptions::new().create(true).append(true).open(&path).and_then(|mut file| {
+            if new {
+                writeln!(file, "{}", INDEX_HEADER)?;
+            }
+            writeln!(file, "{}", segment.row())
+        });
+        if let Err(e) = result {
+            eprintln!("Warning: Could not add {} to {}: {}", segment.file, path, e);
+        }
+    }
+
+    /// Replaces `index.csv`, atomically, with the kept segments.
+    fn rewrite(&self) {
+        let path = format!("{}/index.csv", self.dir);
+        let mut contents = format!("{}\n", INDEX_HEADER);
+        for segment in &self.kept {
+            contents.push_str(&segment.row());
+            contents.push('\n');
+        }
+        let temp = format!("{}.tmp", path);
+        if let Err(e) = fs::write(&temp, contents).and_then(|_| fs::rename(&temp, &path)) {
+            eprintln!("Warning: Could not rewrite {}: {}", path, e);
+        }
+    }
+}
+
+/// Segments listed in `<dir>/index.csv`, oldest first; empty without an index.
+pub fn index(dir: &str) -> Vec<Segment> {
+    let Ok(contents) = fs::read_to_string(format!("{}/index.csv", dir.trim_end_matches('/'))) else {
+        return Vec::new();
+    };
+    let mut segments: Vec<Segment> = contents.lines().skip(1).filter_map(Segment::parse).collect();
+    segments.sort_by_key(|s| s.start);
+    segments
+}
@@ -0,0 +1,184 @@
+use crate::archive::{self, Segment};
+use crate::clock::utc_datetime;
+use gstreamer::prelude::*;
+use std::env;
+
+const USAGE: &str =
+    "Usage: record export --from <time> --to <time> --source <camera> --out <file.mp4> [--transcode] [--bitrate <bits/s>]";
+
+/// Unix seconds from `1760428800` or a UTC time like `2026-10-14T08:00:00Z`.
+pub fn parse_time(value: &str) -> Option<u64> {
+    if let Ok(secs) = value.parse() {
+        return Some(secs);
+    }
+    let value = value.trim_end_matches('Z');
+    let (date, time) = value.split_once(['T', ' '])?;
+    let date: Vec<i64> = date.split('-').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    let time: Vec<u64> = time.split(':').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    let (&[year, month, day], &[hour, minute, second]) = (&date[..], &time[..]) else { return None };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    // Days since 1970-01-01 from the civil date (Howard Hinnant's algorithm)
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    u64::try_from(days).ok().map(|days| days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Seconds of recorded footage in `segments` before unix time `at`; gaps between segments
+/// don't count, as the segments play back to back.
+pub fn position(segments: &[Segment], at: u64) -> u64 {
+    segments.iter().map(|s| at.clamp(s.start, s.end) - s.start).sum()
+}
+
+/// RECORD_DIR and the indexed segments of `camera` overlapping `from..to`; exits when there are none.
+pub fn archived(camera: &str, from: u64, to: u64) -> (String, Vec<Segment>) {
+    let dir = env::var("RECORD_DIR").unwrap_or_else(|_| "/recordings".to_string());
+    let dir = dir.trim_end_matches('/').to_string();
+    let segments: Vec<Segment> =
+        archive::index(&dir).into_iter().filter(|s| s.camera == camera && s.end > from && s.start < to).collect();
+    if segments.is_empty() {
+        eprintln!(
+            "Error: No recording of '{}' between {} and {} in {}/index.csv",
+            camera,
+            utc_datetime(from),
+            utc_datetime(to),
+            dir
+        );
+        std::process::exit(2);
+    }
+    (dir, segments)
+}
+
+/// `record export --from <time> --to <time> --source <camera> --out <file.mp4>`: joins the
+/// archived segments of one camera covering the period into one MP4. By default the stream is
+/// copied, starting at the keyframe before `--from`; `--transcode` re-encodes it (NVENC with the
+/// deepstream feature) to cut exactly.
+pub fn command(args: &[String]) -> ! {
+    let usage = || -> ! {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    let (mut from, mut to, mut camera, mut out) = (None, None, None, None);
+    let (mut transcode, mut bitrate) = (false, 4_000_000u64);
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--transcode" => transcode = true,
+            "--from" | "--to" | "--source" | "--out" | "--bitrate" => {
+                let Some(value) = args.next() else { usage() };
+                match flag.as_str() {
+                    "--from" => from = Some(parse_time(value).unwrap_or_else(|| usage())),
+                    "--to" => to = Some(parse_time(value).unwrap_or_else(|| usage())),
+                    "--source" => camera = Some(value.clone()),
+                    "--out" => out = Some(value.clone()),
+                    _ => bitrate = value.parse().ok().filter(|b| *b > 0).unwrap_or_else(|| usage()),
+                }
+            }
+            _ => usage(),
+        }
+    }
+    let (Some(from), Some(to), Some(camera), Some(out)) = (from, to, camera, out) else { usage() };
+    if to <= from {
+        eprintln!("Error: --to ({}) must be after --from ({})", utc_datetime(to), utc_datetime(from));
+        std::process::exit(2);
+    }
+
+    let (dir, segments) = archived(&camera, from, to);
+    let files: Vec<String> = segments.iter().map(|s| format!("{}/{}", dir, s.file)).collect();
+    let (start, stop) = (position(&segments, from), position(&segments, to));
+    match export(&files, start, stop, &out, transcode.then_some(bitrate)) {
+        Ok(()) => {
+            let first = segments[0].start.max(from);
+            let last = segments[segments.len() - 1].end.min(to);
+            println!(
+                "✓ Exported {} from {} to {} ({} segments, {}) to {}",
+                camera,
+                utc_datetime(first),
+                utc_datetime(last),
+                segments.len(),
+                if transcode { "transcoded" } else { "stream copied" },
+                out
+            );
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Error: Export to {} failed: {}", out, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Plays `files` back to back with splitmuxsrc from `start` to `stop` seconds into `out`, copying
+/// the stream or, with a bitrate, re-encoding it.
+fn export(files: &[String], start: u64, stop: u64, out: &str, transcode: Option<u64>) -> Result<(), String> {
+    let chain = match transcode {
+        None => "parsebin".to_string(),
+        Some(bitrate) => format!("decodebin ! {}", h264_encoder(bitrate)),
+    };
+    let pipeline = gstreamer::parse_launch(&format!(
+        "splitmuxsrc name=src ! {} ! mp4mux ! filesink location=\"{}\"",
+        chain, out
+    ))
+    .map_err(|e| e.to_string())?;
+    // Copies have to start on a keyframe; a transcode decodes up to the exact frame
+    let flags = if transcode.is_some() { gstreamer::SeekFlags::ACCURATE } else { gstreamer::SeekFlags::KEY_UNIT };
+    play(&pipeline, files, Some((start, stop, flags)))
+}
+
+/// H.264 encode of decoded frames at `bitrate` bits/s: NVENC with the deepstream feature,
+/// x264enc without.
+pub fn h264_encoder(bitrate: u64) -> String {
+    if cfg!(feature = "deepstream") {
+        format!("nvvideoconvert ! nvv4l2h264enc bitrate={} ! h264parse", bitrate)
+    } else {
+        format!("videoconvert ! x264enc bitrate={} tune=zerolatency ! h264parse", bitrate / 1000)
+    }
+}
+
+/// Runs `pipeline` to EOS with `files` as the locations of its splitmuxsrc `src`, if it has one,
+/// first seeking to `start..stop` seconds. The default main context is dispatched meanwhile.
+pub fn play(
+    pipeline: &gstreamer::Element,
+    files: &[String],
+    range: Option<(u64, u64, gstreamer::SeekFlags)>,
+) -> Result<(), String> {
+    if let Some(src) = pipeline.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("src")) {
+        let locations = files.to_vec();
+        src.connect("format-location", false, move |_| Some(locations.to_value()));
+    }
+    let bus = pipeline.bus().expect("Pipeline should have a bus");
+    pipeline.set_state(gstreamer::State::Paused).map_err(|e| e.to_string())?;
+    let _ = pipeline.state(gstreamer::ClockTime::from_seconds(10));
+    if let Some((start, stop, flags)) = range {
+        pipeline
+            .seek(
+                1.0,
+                gstreamer::SeekFlags::FLUSH | flags,
+                gstreamer::SeekType::Set,
+                gstreamer::ClockTime::from_seconds(start),
+                gstreamer::SeekType::Set,
+                gstreamer::ClockTime::from_seconds(stop),
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    pipeline.set_state(gstreamer::State::Playing).map_err(|e| e.to_string())?;
+    let main_context = glib::MainContext::default();
+    let result = loop {
+        main_context.iteration(false);
+        let Some(msg) = bus.timed_pop(gstreamer::ClockTime::from_mseconds(100)) else { continue };
+        match msg.view() {
+            gstreamer::MessageView::Eos(..) => break Ok(()),
+            gstreamer::MessageView::Error(err) => break Err(err.error().to_string()),
+            _ => {}
+        }
+    };
+    pipeline
+        .set_state(gstreamer::State::Null)
+        .expect("Unable to set the pipeline to the Null state");
+    result
+}
@@ -0,0 +1,286 @@
+use gstreamer::prelude::*;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+mod archive;
+#[path = "../../clock.rs"]
+mod clock;
+mod export;
+#[path = "../../nvbufsurface.rs"]
+mod nvbufsurface;
+#[path = "../../source.rs"]
+#[allow(dead_code)]
+mod source;
+mod timelapse;
+mod trigger;
+
+use archive::{Archive, Retention};
+use trigger::Trigger;
+
+const SIGINT: i32 = 2;
+const DEFAULT_MODEL_CONFIG: &str = "/opt/nvidia/deepstream/deepstream/samples/configs/deepstream-app/config_infer_primary.txt";
+/// Wait before a camera whose pipeline failed is reconnected
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// One entry of RECORD_CAMERAS.
+struct Camera {
+    name: String,
+    uri: String,
+}
+
+/// RECORD_CAMERAS: comma-separated `name=uri` entries; entries without a name are called
+/// cam1, cam2, ... by position. Local files may be given as plain paths.
+fn cameras() -> Vec<Camera> {
+    let Ok(list) = env::var("RECORD_CAMERAS") else {
+        eprintln!("Error: RECORD_CAMERAS is not set (e.g. RECORD_CAMERAS=door=rtsp://10.0.0.5/stream,yard=rtsp://10.0.0.6/stream)");
+        std::process::exit(2);
+    };
+    let mut cameras: Vec<Camera> = Vec::new();
+    for (index, entry) in list.split(',').map(str::trim).filter(|e| !e.is_empty()).enumerate() {
+        // A '=' after the scheme belongs to the URI's query
+        let (name, uri) = match entry.split_once('=') {
+            Some((name, uri)) if !name.contains("://") => (name.trim().to_string(), uri.trim().to_string()),
+            _ => (format!("cam{}", index + 1), entry.to_string()),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            eprintln!("Error: Camera name '{}' in RECORD_CAMERAS may only use letters, digits, '-' and '_'", name);
+            std::process::exit(2);
+        }
+        if cameras.iter().any(|c| c.name == name) {
+            eprintln!("Error: Camera name '{}' appears twice in RECORD_CAMERAS", name);
+            std::process::exit(2);
+        }
+        let uri = if uri.contains("://") { uri } else { format!("file://{}", uri) };
+        cameras.push(Camera { name, uri });
+    }
+    if cameras.is_empty() {
+        eprintln!("Error: RECORD_CAMERAS lists no cameras");
+        std::process::exit(2);
+    }
+    cameras
+}
+
+fn seconds(key: &str, default: u64) -> u64 {
+    match env::var(key) {
+        Err(_) => default,
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            eprintln!("Error: {} must be a whole number of seconds, got '{}'", key, value);
+            std::process::exit(2);
+        }),
+    }
+}
+
+/// Recording settings shared by every camera.
+struct Settings {
+    trigger: Trigger,
+    segment: u64,
+    /// Share of changed pixels that counts as motion
+    threshold: f64,
+    model_config: String,
+}
+
+/// One camera's pipeline, rebuilt after errors.
+struct Recorder {
+    camera: Camera,
+    pipeline: Option<gstreamer::Pipeline>,
+    restart_at: Option<Instant>,
+    /// EOS arrived after the interrupt, or a file input ended
+    finished: bool,
+}
+
+impl Recorder {
+    /// Source, parsed without decoding, teed into splitmuxsink and, when gated, the
+    /// branch the trigger watches.
+    fn description(&self, settings: &Settings) -> String {
+        let source = if self.camera.uri.starts_with("rtsp://") {
+            format!("rtspsrc location={} latency=200", self.camera.uri)
+        } else {
+            format!("urisourcebin uri={}", self.camera.uri)
+        };
+        let mut description = format!(
+            "{} ! parsebin ! capsfilter caps=\"video/x-h264;video/x-h265\" ! tee name=t \
+             t. ! queue ! splitmuxsink name=rec muxer-factory=mp4mux max-size-time={}",
+            source,
+            settings.segment * 1_000_000_000
+        );
+        if let Some(branch) = settings.trigger.branch(&settings.model_config) {
+            description.push_str(&format!(" t. ! queue ! {}", branch));
+        }
+        description
+    }
+
+    fn start(&mut self, settings: &Settings, archive: &Arc<Mutex<Archive>>) {
+        self.restart_at = None;
+        let pipeline = match gstreamer::parse_launch(&self.description(settings)) {
+            Ok(element) => element.downcast::<gstreamer::Pipeline>().expect("parse_launch returns a pipeline"),
+            Err(e) => {
+                eprintln!("Error: Could not build the pipeline for {}: {}", self.camera.name, e);
+                std::process::exit(2);
+            }
+        };
+        let splitmux = pipeline.by_name("rec").expect("splitmuxsink is named rec");
+        let (name, segments) = (self.camera.name.clone(), archive.clone());
+        splitmux.connect("format-location", false, move |_| {
+            Some(segments.lock().unwrap().begin(&name, now()).to_value())
+        });
+        let (name, activity) = (self.camera.name.clone(), archive.clone());
+        settings.trigger.watch(&pipeline, settings.threshold, move || activity.lock().unwrap().active(&name, now()));
+        if pipeline.set_state(gstreamer::State::Playing).is_err() {
+            self.fail(archive, "could not start the pipeline");
+            let _ = pipeline.set_state(gstreamer::State::Null);
+            return;
+        }
+        self.pipeline = Some(pipeline);
+    }
+
+    /// Stops the pipeline, closing its segment, and schedules a reconnect.
+    fn fail(&mut self, archive: &Arc<Mutex<Archive>>, reason: &str) {
+        eprintln!(
+            "Warning: {}: {}; reconnecting in {}s",
+            self.camera.name,
+            source::redact_credentials(reason),
+            RESTART_DELAY.as_secs()
+        );
+        if let Some(pipeline) = self.pipeline.take() {
+            let _ = pipeline.set_state(gstreamer::State::Null);
+        }
+        archive.lock().unwrap().close(&self.camera.name, now());
+        self.restart_at = Some(Instant::now() + RESTART_DELAY);
+    }
+
+    /// Handles the pipeline's bus messages and a due reconnect.
+    fn poll(&mut self, settings: &Settings, archive: &Arc<Mutex<Archive>>, stopping: bool) {
+        if self.finished {
+            return;
+        }
+        if let Some(at) = self.restart_at {
+            if !stopping && Instant::now() >= at {
+                self.start(settings, archive);
+            }
+            return;
+        }
+        let Some(bus) = self.pipeline.as_ref().and_then(|p| p.bus()) else { return };
+        while let Some(msg) = bus.pop() {
+            match msg.view() {
+                // Files end; cameras that send EOS have hung up and are reconnected
+                gstreamer::MessageView::Eos(..) if stopping || !source::is_stream(&self.camera.uri) => {
+                    self.finished = true;
+                    return;
+                }
+                gstreamer::MessageView::Eos(..) => return self.fail(archive, "end of stream"),
+                gstreamer::MessageView::Error(err) => return self.fail(archive, &err.error().to_string()),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn main() {
+    gstreamer::init().expect("Failed to initialize GStreamer");
+
+    // `record export --from <time> --to <time> --source <camera> --out <file>` cuts a clip from the archive
+    if env::args().nth(1).as_deref() == Some("export") {
+        export::command(&env::args().skip(2).collect::<Vec<_>>());
+    }
+
+    // `record timelapse --source <camera or uri> --every <seconds> --out <file>` samples a timelapse
+    if env::args().nth(1).as_deref() == Some("timelapse") {
+        timelapse::command(&env::args().skip(2).collect::<Vec<_>>());
+    }
+
+    let dir = env::var("RECORD_DIR").unwrap_or_else(|_| "/recordings".to_string());
+    let settings = Settings {
+        trigger: Trigger::from_env(),
+        segment: seconds("RECORD_SEGMENT_SECONDS", 60).max(1),
+        threshold: match env::var("RECORD_MOTION_THRESHOLD") {
+            Err(_) => 0.02,
+            Ok(value) => match value.trim().parse::<f64>() {
+                Ok(t) if t > 0.0 && t <= 1.0 => t,
+                _ => {
+                    eprintln!("Error: RECORD_MOTION_THRESHOLD must be a share of pixels in (0, 1], got '{}'", value);
+                    std::process::exit(2);
+                }
+            },
+        },
+        model_config: env::var("MODEL_CONFIG").unwrap_or_else(|_| DEFAULT_MODEL_CONFIG.to_string()),
+    };
+    let window = match settings.trigger {
+        Trigger::Continuous => None,
+        _ => Some((seconds("RECORD_PRE_SECONDS", 10), seconds("RECORD_POST_SECONDS", 20))),
+    };
+    let retention = Retention::from_env();
+    let archive = Arc::new(Mutex::new(Archive::load(&dir, window, retention)));
+
+    let mut recorders: Vec<Recorder> = cameras()
+        .into_iter()
+        .map(|camera| Recorder { camera, pipeline: None, restart_at: None, finished: false })
+        .collect();
+    println!("Recording {} cameras to {}:", recorders.len(), dir);
+    for recorder in &recorders {
+        println!("  {}: {}", recorder.camera.name, source::redact_credentials(&recorder.camera.uri));
+    }
+    let gating = match window {
+        None => String::new(),
+        Some((pre, post)) => format!(", kept {}s before to {}s after activity", pre, post),
+    };
+    println!("  Trigger: {}{}", settings.trigger.name(), gating);
+    println!("  Segments: {}s, {}", settings.segment, retention.describe());
+    for recorder in &mut recorders {
+        recorder.start(&settings, &archive);
+    }
+
+    // Segments are decided on and expired once a second
+    let settling = archive.clone();
+    glib::timeout_add_seconds(1, move || {
+        settling.lock().unwrap().settle(now());
+        glib::Continue(true)
+    });
+
+    // Ctrl+C sends EOS so the last segment of every camera is finalized; a second Ctrl+C force-quits
+    let interrupted = Arc::new(Mutex::new(false));
+    #[cfg(unix)]
+    {
+        let interrupted = interrupted.clone();
+        glib::unix_signal_add(SIGINT, move || {
+            let mut interrupted = interrupted.lock().unwrap();
+            if *interrupted {
+                std::process::exit(130);
+            }
+            println!("\nInterrupt received, finishing segments (press Ctrl+C again to force quit)...");
+            *interrupted = true;
+            glib::Continue(true)
+        });
+    }
+
+    let main_context = glib::MainContext::default();
+    let mut stopping = false;
+    loop {
+        main_context.iteration(false);
+        if !stopping && *interrupted.lock().unwrap() {
+            stopping = true;
+            for pipeline in recorders.iter().filter_map(|r| r.pipeline.as_ref()) {
+                pipeline.send_event(gstreamer::event::Eos::new());
+            }
+        }
+        for recorder in &mut recorders {
+            recorder.poll(&settings, &archive, stopping);
+        }
+        if recorders.iter().all(|r| r.finished || (stopping && r.pipeline.is_none())) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    for recorder in &recorders {
+        if let Some(pipeline) = &recorder.pipeline {
+            let _ = pipeline.set_state(gstreamer::State::Null);
+        }
+    }
+    archive.lock().unwrap().finish(now());
+    println!("✓ Recordings indexed in {}/index.csv", dir.trim_end_matches('/'));
+}
@@ -0,0 +1,188 @@
+use crate::export;
+use gstreamer::prelude::*;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+const USAGE: &str = "Usage: record timelapse --source <camera or uri> --every <seconds> --out <file.mp4> \
+                     [--fps <n>] [--from <time>] [--to <time>] [--overlay] [--bitrate <bits/s>]";
+
+#[derive(Default)]
+struct Sampler {
+    /// Timestamp of the first frame; samples are taken every interval after it
+    base: Option<gstreamer::ClockTime>,
+    /// Interval the next sample is due at
+    next: u64,
+    /// Frames kept so far, which also numbers the next one
+    frames: u64,
+}
+
+/// Keeps the first frame of every `every` of source time on `pad` and retimes the kept ones
+/// to follow each other at `fps`, replacing the segment so they start at zero.
+fn sample(pad: &gstreamer::Pad, every: u64, fps: u64, sampler: Arc<Mutex<Sampler>>) {
+    let every = gstreamer::ClockTime::from_seconds(every);
+    let frame = gstreamer::ClockTime::SECOND / fps;
+    pad.add_probe(
+        gstreamer::PadProbeType::BUFFER | gstreamer::PadProbeType::EVENT_DOWNSTREAM,
+        move |_pad, info| {
+            match &mut info.data {
+                Some(gstreamer::PadProbeData::Event(event)) if event.type_() == gstreamer::EventType::Segment => {
+                    *event = gstreamer::event::Segment::new(&gstreamer::FormattedSegment::<gstreamer::ClockTime>::new());
+                }
+                Some(gstreamer::PadProbeData::Buffer(buffer)) => {
+                    let Some(pts) = buffer.pts() else { return gstreamer::PadProbeReturn::Drop };
+                    let mut sampler = sampler.lock().unwrap();
+                    let base = *sampler.base.get_or_insert(pts);
+                    if pts < base + every * sampler.next {
+                        return gstreamer::PadProbeReturn::Drop;
+                    }
+                    // A stall in the source skips the samples it missed instead of catching up
+                    sampler.next = (pts - base).nseconds() / every.nseconds() + 1;
+                    let buffer = buffer.make_mut();
+                    buffer.set_pts(frame * sampler.frames);
+                    buffer.set_dts(gstreamer::ClockTime::NONE);
+                    buffer.set_duration(frame);
+                    sampler.frames += 1;
+                }
+                _ => {}
+            }
+            gstreamer::PadProbeReturn::Ok
+        },
+    );
+}
+
+/// Writes the number of detected objects into the top left corner of every frame nvdsosd draws.
+fn count_objects(osd: &gstreamer::Element) {
+    let Some(pad) = osd.static_pad("sink") else { return };
+    pad.add_probe(gstreamer::PadProbeType::BUFFER, |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            for frame in batch.frames() {
+                let count = frame.num_obj_meta;
+                let font_size = (frame.pipeline_height / 24).max(14);
+                let Some(display) = frame.add_display_meta() else { continue };
+                let text = if count == 1 { "1 object".to_string() } else { format!("{} objects", count) };
+                display.text_params[0].set(&text, 16, 16, font_size);
+                display.num_labels = 1;
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
+
+/// `record timelapse --source <camera or uri> --every <seconds> --out <file.mp4>`: keeps one
+/// frame every `--every` seconds of an archived camera (between `--from` and `--to`) or a live
+/// source (until it ends or Ctrl+C) and encodes them at `--fps` (default 30). `--overlay` runs
+/// MODEL_CONFIG's detector on the kept frames and draws its boxes and object count.
+pub fn command(args: &[String]) -> ! {
+    let usage = || -> ! {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    let (mut source, mut out, mut every, mut from, mut to) = (None, None, None, None, None);
+    let (mut fps, mut bitrate, mut overlay) = (30u64, 4_000_000u64, false);
+    let positive = |value: &str| value.parse::<u64>().ok().filter(|n| *n > 0).unwrap_or_else(|| usage());
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        if flag == "--overlay" {
+            overlay = true;
+            continue;
+        }
+        let Some(value) = args.next() else { usage() };
+        match flag.as_str() {
+            "--source" => source = Some(value.clone()),
+            "--out" => out = Some(value.clone()),
+            "--every" => every = Some(positive(value)),
+            "--fps" => fps = positive(value),
+            "--bitrate" => bitrate = positive(value),
+            "--from" => from = Some(export::parse_time(value).unwrap_or_else(|| usage())),
+            "--to" => to = Some(export::parse_time(value).unwrap_or_else(|| usage())),
+            _ => usage(),
+        }
+    }
+    let (Some(source), Some(out), Some(every)) = (source, out, every) else { usage() };
+    if overlay && !cfg!(feature = "deepstream") {
+        eprintln!("Error: --overlay needs nvinfer; build with the deepstream feature");
+        std::process::exit(2);
+    }
+
+    // A camera of the archive, played back from its segments, or a live URI
+    let live = source.contains("://");
+    let (input, files, range) = if live {
+        if from.is_some() || to.is_some() {
+            eprintln!("Error: --from and --to only apply to archived cameras, not {}", source);
+            std::process::exit(2);
+        }
+        (format!("uridecodebin uri={} caps=\"video/x-raw(ANY)\" expose-all-streams=false", source), Vec::new(), None)
+    } else {
+        let (from, to) = (from.unwrap_or(0), to.unwrap_or_else(crate::now));
+        let (dir, segments) = export::archived(&source, from, to);
+        let files = segments.iter().map(|s| format!("{}/{}", dir, s.file)).collect();
+        let range = (export::position(&segments, from), export::position(&segments, to), gstreamer::SeekFlags::ACCURATE);
+        ("splitmuxsrc name=src ! decodebin".to_string(), files, Some(range))
+    };
+    let detector = if overlay {
+        let model_config = env::var("MODEL_CONFIG").unwrap_or_else(|_| crate::DEFAULT_MODEL_CONFIG.to_string());
+        format!(
+            "nvvideoconvert ! mux.sink_0 nvstreammux name=mux batch-size=1 width=1280 height=720 ! \
+             nvinfer config-file-path={} ! nvvideoconvert ! nvdsosd name=osd ! ",
+            model_config
+        )
+    } else {
+        String::new()
+    };
+    let pipeline = gstreamer::parse_launch(&format!(
+        "{} ! identity name=sample ! {}{} ! mp4mux ! filesink location=\"{}\"",
+        input,
+        detector,
+        export::h264_encoder(bitrate),
+        out
+    ))
+    .unwrap_or_else(|e| {
+        eprintln!("Error: Could not build the timelapse pipeline: {}", e);
+        std::process::exit(2);
+    });
+    let bin = pipeline.downcast_ref::<gstreamer::Bin>().expect("parse_launch returns a bin");
+    let sampler = Arc::new(Mutex::new(Sampler::default()));
+    let identity = bin.by_name("sample").expect("identity is named sample");
+    sample(&identity.static_pad("src").expect("identity has a src pad"), every, fps, sampler.clone());
+    if let Some(osd) = bin.by_name("osd") {
+        count_objects(&osd);
+    }
+
+    // Ctrl+C ends a live timelapse with EOS so the MP4 is finalized; a second Ctrl+C force-quits
+    #[cfg(unix)]
+    {
+        let interrupt_pipeline = pipeline.clone();
+        let mut eos_sent = false;
+        glib::unix_signal_add(crate::SIGINT, move || {
+            if eos_sent {
+                std::process::exit(130);
+            }
+            println!("\nInterrupt received, finishing the timelapse (press Ctrl+C again to force quit)...");
+            interrupt_pipeline.send_event(gstreamer::event::Eos::new());
+            eos_sent = true;
+            glib::Continue(true)
+        });
+    }
+    if live {
+        println!("Sampling {} every {}s, Ctrl+C to finish", source, every);
+    }
+
+    match export::play(&pipeline, &files, range) {
+        Ok(()) => {
+            let frames = sampler.lock().unwrap().frames;
+            println!(
+                "✓ Timelapse of {} frames, one every {}s, written to {} ({}s at {} fps)",
+                frames,
+                every,
+                out,
+                frames / fps,
+                fps
+            );
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Error: Timelapse to {} failed: {}", out, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use gstreamer::prelude::*;
+use std::env;
+use std::sync::Mutex;
+
+/// Size and rate of the grayscale frames compared for motion
+const MOTION_WIDTH: usize = 160;
+const MOTION_HEIGHT: usize = 90;
+const MOTION_FPS: u32 = 5;
+/// Gray levels a pixel has to change by to count as changed, so sensor noise doesn't
+const PIXEL_DELTA: u8 = 24;
+/// Frames nvinfer skips between inferences in detection mode
+const DETECTION_INTERVAL: u32 = 4;
+
+/// Which segments are kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// All of them
+    Continuous,
+    /// The ones around frame-to-frame changes
+    Motion,
+    /// The ones around frames the detector found objects in
+    Detection,
+}
+
+impl Trigger {
+    /// Reads RECORD_TRIGGER (continuous, motion or detection).
+    pub fn from_env() -> Trigger {
+        match env::var("RECORD_TRIGGER").as_deref() {
+            Err(_) | Ok("continuous") => Trigger::Continuous,
+            Ok("motion") => Trigger::Motion,
+            Ok("detection") if cfg!(feature = "deepstream") => Trigger::Detection,
+            Ok("detection") => {
+                eprintln!("Error: RECORD_TRIGGER=detection needs nvinfer; build with the deepstream feature");
+                std::process::exit(2);
+            }
+            Ok(other) => {
+                eprintln!("Error: Unknown RECORD_TRIGGER '{}' (expected continuous, motion or detection)", other);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Trigger::Continuous => "continuous",
+            Trigger::Motion => "motion",
+            Trigger::Detection => "detection",
+        }
+    }
+
+    /// Branch that decodes the camera's stream to watch it, ending in a fakesink named
+    /// `activity`; None for continuous recording, which never decodes.
+    pub fn branch(&self, model_config: &str) -> Option<String> {
+        let convert = if cfg!(feature = "deepstream") { "nvvideoconvert" } else { "videoconvert ! videoscale" };
+        match self {
+            Trigger::Continuous => None,
+            Trigger::Motion => Some(format!(
+                "decodebin ! {} ! video/x-raw,format=GRAY8,width={},height={} ! \
+                 videorate drop-only=true max-rate={} ! fakesink name=activity sync=false",
+                convert, MOTION_WIDTH, MOTION_HEIGHT, MOTION_FPS
+            )),
+            Trigger::Detection => Some(format!(
+                "decodebin ! mux.sink_0 nvstreammux name=mux batch-size=1 width=1280 height=720 live-source=1 ! \
+                 nvinfer config-file-path={} interval={} ! fakesink name=activity sync=false",
+                model_config, DETECTION_INTERVAL
+            )),
+        }
+    }
+
+    /// Calls `active` for every frame reaching the `activity` sink of `pipeline` that shows
+    /// activity: at least `threshold` of its pixels changed since the previous frame (motion),
+    /// or the detector found an object in it (detection).
+    pub fn watch(&self, pipeline: &gstreamer::Pipeline, threshold: f64, active: impl Fn() + Send + Sync + 'static) {
+        let Some(pad) = pipeline.by_name("activity").and_then(|sink| sink.static_pad("sink")) else { return };
+        match self {
+            Trigger::Continuous => {}
+            Trigger::Motion => {
+                let previous = Mutex::new(Vec::new());
+                pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+                    let Some(gstreamer::PadProbeData::Buffer(buffer)) = &info.data else {
+                        return gstreamer::PadProbeReturn::Ok;
+                    };
+                    let Ok(map) = buffer.map_readable() else { return gstreamer::PadProbeReturn::Ok };
+                    // Rows may be padded; only the first width x height bytes are compared
+                    let frame = &map[..map.len().min(MOTION_WIDTH * MOTION_HEIGHT)];
+                    let mut previous = previous.lock().unwrap();
+                    if changed(&previous, frame) >= threshold {
+                        active();
+                    }
+                    previous.clear();
+                    previous.extend_from_slice(frame);
+                    gstreamer::PadProbeReturn::Ok
+                });
+            }
+            Trigger::Detection => {
+                pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+                    if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+                        if batch.frames().any(|frame| frame.num_obj_meta > 0) {
+                            active();
+                        }
+                    }
+                    gstreamer::PadProbeReturn::Ok
+                });
+            }
+        }
+    }
+}
+
+/// Share of pixels that changed by more than PIXEL_DELTA; 0 without a previous frame.
+fn changed(previous: &[u8], frame: &[u8]) -> f64 {
+    if previous.len() != frame.len() || frame.is_empty() {
+        return 0.0;
+    }
+    let count = previous.iter().zip(frame).filter(|(a, b)| a.abs_diff(**b) > PIXEL_DELTA).count();
+    count as f64 / frame.len() as f64
+}
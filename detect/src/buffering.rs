@@ -0,0 +1,83 @@
+use std::env;
+
+/// Points in the main chain that get a queue, in pipeline order: after each source (before
+/// nvstreammux), before nvinfer, before nvtracker, before the analytics stages and before
+/// nvdsosd. Sink branches have their own queues (SINK_QUEUE, `queue-` sink options).
+pub const STAGES: [&str; 5] = ["source", "infer", "tracker", "analytics", "osd"];
+
+/// Bounded by buffer count only: NVMM buffers are small handles, so byte limits never hit,
+/// and batched timestamps make time limits unreliable
+const DEFAULT_PROPERTIES: &str = "max-size-buffers=8 max-size-bytes=0 max-size-time=0";
+
+/// Queue properties per stage; None leaves that stage without a queue.
+#[derive(Clone, Debug)]
+pub struct StageQueues {
+    properties: Vec<Option<String>>,
+}
+
+impl StageQueues {
+    /// No queues between stages, as before they were configurable.
+    pub fn none() -> StageQueues {
+        StageQueues { properties: vec![None; STAGES.len()] }
+    }
+
+    /// Parses STAGE_QUEUES: `;`-separated `stage: properties` entries that replace the default
+    /// properties of that stage (`*` for every stage), `stage: none` to remove its queue, or
+    /// `none` alone for no queues at all, e.g. `infer: max-size-buffers=2 leaky=downstream; osd: none`.
+    pub fn parse(spec: &str) -> Result<StageQueues, String> {
+        let mut properties = vec![Some(DEFAULT_PROPERTIES.to_string()); STAGES.len()];
+        if spec.trim() == "none" {
+            return Ok(StageQueues::none());
+        }
+        for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (stage, value) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("'{}' must be stage: properties", entry))?;
+            let (stage, value) = (stage.trim(), value.trim());
+            let value = match value {
+                "none" => None,
+                _ => {
+                    if let Some(word) = value.split_whitespace().find(|w| !w.contains('=')) {
+                        return Err(format!("stage '{}': property '{}' must be key=value", stage, word));
+                    }
+                    Some(value.to_string())
+                }
+            };
+            match STAGES.iter().position(|s| *s == stage) {
+                Some(index) => properties[index] = value,
+                None if stage == "*" => properties.iter_mut().for_each(|p| *p = value.clone()),
+                None => return Err(format!("unknown stage '{}' (expected {} or *)", stage, STAGES.join(", "))),
+            }
+        }
+        Ok(StageQueues { properties })
+    }
+
+    /// STAGE_QUEUES, or a queue with the default properties at every stage.
+    pub fn from_env() -> StageQueues {
+        let spec = env::var("STAGE_QUEUES").unwrap_or_default();
+        StageQueues::parse(&spec).unwrap_or_else(|e| {
+            eprintln!("Error: STAGE_QUEUES: {}", e);
+            std::process::exit(2);
+        })
+    }
+
+    /// The queue for `stage` as a launch fragment named `queue_<name>`, if it has one.
+    pub fn fragment(&self, stage: &str, name: &str) -> Option<String> {
+        let index = STAGES.iter().position(|s| *s == stage).expect("known stage");
+        let properties = self.properties[index].as_ref()?;
+        Some(format!("queue name=queue_{} {}", name, properties).trim_end().to_string())
+    }
+
+    pub fn describe(&self) -> String {
+        let stages: Vec<String> = STAGES
+            .iter()
+            .zip(&self.properties)
+            .filter_map(|(stage, properties)| properties.as_ref().map(|p| format!("{} ({})", stage, p)))
+            .collect();
+        if stages.is_empty() {
+            "none".to_string()
+        } else {
+            stages.join(", ")
+        }
+    }
+}
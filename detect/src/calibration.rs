@@ -0,0 +1,109 @@
+use std::env;
+
+/// (normalized image point, ground point in meters)
+pub type PointPair = ((f64, f64), (f64, f64));
+
+/// Image-to-ground-plane mapping from point correspondences. Image points are
+/// normalized (0-1) like zones; ground points are in meters on a flat plane.
+#[derive(Clone, Debug)]
+pub struct Calibration {
+    /// Row-major 3x3 homography with h[8] = 1
+    h: [f64; 9],
+}
+
+impl Calibration {
+    /// Fits a homography to 4 or more `(image, ground)` pairs, least squares when over-determined.
+    pub fn from_points(pairs: &[PointPair]) -> Result<Calibration, String> {
+        if pairs.len() < 4 {
+            return Err("needs at least 4 point pairs".to_string());
+        }
+        // Normal equations of the direct linear transform with h[8] fixed to 1
+        let mut ata = [[0.0f64; 8]; 8];
+        let mut atb = [0.0f64; 8];
+        for &((x, y), (gx, gy)) in pairs {
+            let rows = [
+                ([x, y, 1.0, 0.0, 0.0, 0.0, -x * gx, -y * gx], gx),
+                ([0.0, 0.0, 0.0, x, y, 1.0, -x * gy, -y * gy], gy),
+            ];
+            for (row, b) in rows {
+                for i in 0..8 {
+                    for j in 0..8 {
+                        ata[i][j] += row[i] * row[j];
+                    }
+                    atb[i] += row[i] * b;
+                }
+            }
+        }
+        let solution = solve(ata, atb).ok_or("points are degenerate (three or more on a line?)")?;
+        let mut h = [1.0; 9];
+        h[..8].copy_from_slice(&solution);
+        Ok(Calibration { h })
+    }
+
+    /// Ground position in meters of a normalized image point.
+    pub fn to_ground(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let h = &self.h;
+        let w = h[6] * x + h[7] * y + h[8];
+        if w.abs() < 1e-12 {
+            return None;
+        }
+        Some(((h[0] * x + h[1] * y + h[2]) / w, (h[3] * x + h[4] * y + h[5]) / w))
+    }
+}
+
+/// Gaussian elimination with partial pivoting.
+fn solve(mut a: [[f64; 8]; 8], mut b: [f64; 8]) -> Option<[f64; 8]> {
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col];
+        for row in col + 1..8 {
+            let factor = a[row][col] / pivot_row[col];
+            for (value, pivot) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = [0.0; 8];
+    for row in (0..8).rev() {
+        let sum: f64 = (row + 1..8).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Parses `x,y=X,Y` pairs separated by `;`: normalized image point = ground point in meters.
+pub fn parse_calibration(spec: &str) -> Result<Calibration, String> {
+    let pair = |text: &str| -> Result<(f64, f64), String> {
+        let (a, b) = text
+            .split_once(',')
+            .ok_or_else(|| format!("'{}' must be x,y", text))?;
+        let parse = |v: &str| v.trim().parse::<f64>().map_err(|_| format!("'{}' is not a number", v.trim()));
+        Ok((parse(a)?, parse(b)?))
+    };
+    let mut pairs = Vec::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (image, ground) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' must be x,y=X,Y", entry))?;
+        pairs.push((pair(image)?, pair(ground)?));
+    }
+    Calibration::from_points(&pairs)
+}
+
+/// Reads CALIBRATION; exits on a malformed spec like the other startup configuration.
+pub fn calibration_from_env() -> Option<Calibration> {
+    let spec = env::var("CALIBRATION").ok()?;
+    match parse_calibration(&spec) {
+        Ok(calibration) => Some(calibration),
+        Err(e) => {
+            eprintln!("Error: CALIBRATION: {}", e);
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use gstreamer::prelude::*;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Writes a copy of `base_config` whose [class-attrs-*] thresholds only let `class_ids`
+/// through, to `path`.
+pub fn create_filtered_config(base_config: &str, class_ids: &[i32], path: &str) -> Result<String, std::io::Error> {
+    // Read the base config
+    let config_content = fs::read_to_string(base_config)?;
+
+    let mut new_config = String::new();
+
+    // Copy everything except [class-attrs-all] section
+    let mut in_class_attrs = false;
+    for line in config_content.lines() {
+        if line.trim().starts_with("[class-attrs-all]") {
+            in_class_attrs = true;
+            continue;
+        } else if in_class_attrs && line.trim().starts_with("[") {
+            in_class_attrs = false;
+        }
+
+        if !in_class_attrs {
+            new_config.push_str(line);
+            new_config.push('\n');
+        }
+    }
+
+    // Add class-specific filtering: high threshold for non-target classes
+    for class_id in class_ids {
+        new_config.push_str(&format!("\n[class-attrs-{}]\n", class_id));
+        new_config.push_str("pre-cluster-threshold=0.25\n");
+    }
+    new_config.push_str("\n[class-attrs-all]\n");
+    new_config.push_str("pre-cluster-threshold=1.0\n"); // Impossible threshold to hide other classes
+
+    let mut file = fs::File::create(path)?;
+    file.write_all(new_config.as_bytes())?;
+
+    Ok(path.to_string())
+}
+
+/// Where filtered configs go: slot 0 is the one written at startup, runtime changes take
+/// turns between slots 1 and 2. Named after the process so two detects don't share one.
+pub fn filtered_config_path(slot: u32) -> String {
+    let name = format!("config_infer_filtered_{}_{}.txt", std::process::id(), slot);
+    env::temp_dir().join(name).to_string_lossy().into_owned()
+}
+
+/// How non-target classes are hidden.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterMode {
+    /// A copy of the model config with an impossible threshold for every other class
+    Config,
+    /// A probe after nvinfer drops the other classes' object meta; the model config is used as is
+    Metadata,
+}
+
+impl FilterMode {
+    /// Reads CLASS_FILTER (config or metadata, default config).
+    pub fn from_env() -> FilterMode {
+        match std::env::var("CLASS_FILTER").unwrap_or_default().to_lowercase().as_str() {
+            "" | "config" => FilterMode::Config,
+            "metadata" | "meta" | "probe" => FilterMode::Metadata,
+            other => {
+                eprintln!("Error: Invalid CLASS_FILTER '{}' (expected config or metadata)", other);
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+/// The classes detect currently reports, changeable at runtime with the `classes` command.
+/// In config mode every change writes a new filtered config, which nvinfer loads when its
+/// config-file-path is set while playing (DeepStream's on-the-fly model update). In
+/// metadata mode the probe reads the shared class list, so a change takes effect on the
+/// next frame.
+pub struct ClassFilter {
+    pub mode: FilterMode,
+    base_config: String,
+    labels: Vec<String>,
+    /// Empty when every class is shown
+    current: Arc<Mutex<Vec<i32>>>,
+    generation: u32,
+}
+
+impl ClassFilter {
+    pub fn new(mode: FilterMode, base_config: &str, labels: Vec<String>, current: Vec<i32>) -> ClassFilter {
+        ClassFilter {
+            mode,
+            base_config: base_config.to_string(),
+            labels,
+            current: Arc::new(Mutex::new(current)),
+            generation: 0,
+        }
+    }
+
+    /// Class IDs for a comma-separated list of label names or numeric IDs; `all` is empty.
+    pub fn resolve(&self, spec: &str) -> Result<Vec<i32>, String> {
+        if spec.trim() == "all" {
+            return Ok(Vec::new());
+        }
+        let mut ids = Vec::new();
+        for name in spec.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let id = match name.parse::<i32>() {
+                Ok(id) => id,
+                Err(_) => self
+                    .labels
+                    .iter()
+                    .position(|label| label == name)
+                    .map(|idx| idx as i32)
+                    .ok_or_else(|| format!("unknown class '{}'", name))?,
+            };
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        if ids.is_empty() {
+            return Err("no classes given (names, IDs or all)".to_string());
+        }
+        Ok(ids)
+    }
+
+    /// Label names of the current classes, e.g. "person, car" (or "all").
+    pub fn describe(&self) -> String {
+        let current = self.current.lock().unwrap();
+        if current.is_empty() {
+            return "all".to_string();
+        }
+        let names: Vec<String> = current
+            .iter()
+            .map(|id| self.labels.get(*id as usize).cloned().unwrap_or_else(|| id.to_string()))
+            .collect();
+        names.join(", ")
+    }
+
+    /// The current classes as `resolve` takes them: comma-separated IDs, or `all`.
+    pub fn spec(&self) -> String {
+        let current = self.current.lock().unwrap();
+        if current.is_empty() {
+            return "all".to_string();
+        }
+        current.iter().map(i32::to_string).collect::<Vec<_>>().join(",")
+    }
+
+    /// Switches to `ids`. In config mode, returns the config nvinfer has to load: the file
+    /// nvinfer isn't using, so it always sees a different config-file-path (two files in
+    /// all, however often the classes change), and the base config when every class is
+    /// shown again.
+    pub fn update(&mut self, ids: Vec<i32>) -> Result<Option<String>, std::io::Error> {
+        let path = match self.mode {
+            FilterMode::Metadata => None,
+            FilterMode::Config if ids.is_empty() => Some(self.base_config.clone()),
+            FilterMode::Config => {
+                self.generation += 1;
+                let path = filtered_config_path(1 + self.generation % 2);
+                Some(create_filtered_config(&self.base_config, &ids, &path)?)
+            }
+        };
+        *self.current.lock().unwrap() = ids;
+        Ok(path)
+    }
+
+    /// Metadata mode: drops the objects of other classes on nvinfer's (`pgie`) src pad, before
+    /// the tracker, analytics, OSD and event outputs see them.
+    pub fn attach(&self, bin: &gstreamer::Element) {
+        if self.mode != FilterMode::Metadata {
+            return;
+        }
+        let Some(pgie) = bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("pgie")) else {
+            eprintln!("Warning: nvinfer not found, class filtering disabled");
+            return;
+        };
+        let current = self.current.clone();
+        let src_pad = pgie.static_pad("src").expect("nvinfer has a src pad");
+        src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+            let classes = current.lock().unwrap();
+            if classes.is_empty() {
+                return gstreamer::PadProbeReturn::Ok;
+            }
+            if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+                for frame in batch.frames() {
+                    frame.retain_objects(|obj| classes.contains(&obj.class_id));
+                }
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runtime_changes_take_turns_between_two_files() {
+        let base = env::temp_dir().join(format!("detect-classes-{}.txt", std::process::id()));
+        fs::write(&base, "[property]\nnum-detected-classes=3\n\n[class-attrs-all]\npre-cluster-threshold=0.2\n").unwrap();
+        let base = base.to_string_lossy().into_owned();
+        let labels = vec!["person".to_string(), "bicycle".to_string(), "car".to_string()];
+        let mut filter = ClassFilter::new(FilterMode::Config, &base, labels, vec![0]);
+
+        let paths: Vec<Option<String>> = [vec![2], vec![1, 2], vec![0], vec![]]
+            .into_iter()
+            .map(|ids| filter.update(ids).unwrap())
+            .collect();
+        let (first, second) = (filtered_config_path(2), filtered_config_path(1));
+        assert_eq!(paths, [Some(first.clone()), Some(second.clone()), Some(first.clone()), Some(base)]);
+        assert!(first.starts_with(&*env::temp_dir().to_string_lossy()));
+
+        // The last write to each file wins
+        let config = fs::read_to_string(&first).unwrap();
+        assert!(config.contains("[class-attrs-0]\npre-cluster-threshold=0.25\n"));
+        assert!(config.ends_with("[class-attrs-all]\npre-cluster-threshold=1.0\n"));
+        assert!(fs::read_to_string(&second).unwrap().contains("[class-attrs-1]"));
+    }
+}
@@ -0,0 +1,87 @@
+use gstreamer::prelude::*;
+use std::collections::HashMap;
+use std::env;
+
+/// Model labels renamed or merged into a stable taxonomy, e.g. PeopleNet's `pedestrian` as
+/// `person` and `truck` and `bus` both as `vehicle`. The taxonomy is labels.txt plus the
+/// mapped names it doesn't have yet, appended in order. Objects are renamed by their label
+/// before the analytics stages, the OSD and the detection outputs, so every model's
+/// objects end up with the taxonomy's class IDs and names. The class filter, size filters
+/// and the A/B and shadow models still see the model's own classes.
+pub struct ClassMap {
+    /// Model label → taxonomy name
+    renames: HashMap<String, String>,
+    /// Class names by ID after mapping; what the detection outputs are given as labels
+    pub taxonomy: Vec<String>,
+    /// labels.txt, for objects whose model has no labelfile
+    model_labels: Vec<String>,
+}
+
+impl ClassMap {
+    /// Reads CLASS_MAP: `;`-separated `<model label,...>:<name>` entries, e.g.
+    /// `pedestrian:person;truck,bus:vehicle`. None when unset.
+    pub fn from_env(labels: &[String]) -> Option<ClassMap> {
+        let spec = env::var("CLASS_MAP").ok().filter(|v| !v.trim().is_empty())?;
+        let mut renames = HashMap::new();
+        let mut taxonomy = labels.to_vec();
+        for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((from, to)) = entry.split_once(':').map(|(f, t)| (f, t.trim())).filter(|(_, t)| !t.is_empty()) else {
+                eprintln!("Error: Invalid CLASS_MAP entry '{}' (expected <label,...>:<name>)", entry);
+                std::process::exit(2);
+            };
+            for label in from.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+                if renames.insert(label.to_string(), to.to_string()).is_some() {
+                    eprintln!("Error: '{}' is mapped more than once in CLASS_MAP", label);
+                    std::process::exit(2);
+                }
+            }
+            if !taxonomy.iter().any(|name| name == to) {
+                taxonomy.push(to.to_string());
+            }
+        }
+        Some(ClassMap { renames, taxonomy, model_labels: labels.to_vec() })
+    }
+
+    pub fn describe(&self) -> String {
+        let mut pairs: Vec<String> = self.renames.iter().map(|(from, to)| format!("{}→{}", from, to)).collect();
+        pairs.sort();
+        pairs.join(", ")
+    }
+
+    /// Taxonomy class ID and name for an object labelled `label` (or, without a label,
+    /// of class `class_id` in labels.txt). None when the taxonomy doesn't know it.
+    fn resolve(&self, label: &str, class_id: i32) -> Option<(i32, &str)> {
+        let label = if label.is_empty() { self.model_labels.get(class_id as usize)?.as_str() } else { label };
+        let name = self.renames.get(label).map_or(label, String::as_str);
+        let id = self.taxonomy.iter().position(|known| known == name)?;
+        Some((id as i32, self.taxonomy[id].as_str()))
+    }
+}
+
+/// Renames the objects on the pause valve's sink pad: after every primary model and the
+/// tracker, ahead of everything that reports objects.
+pub fn attach(pipeline: &gstreamer::Element, map: ClassMap) {
+    let Some(pad) = pipeline
+        .downcast_ref::<gstreamer::Bin>()
+        .and_then(|b| b.by_name("pause_valve"))
+        .and_then(|valve| valve.static_pad("sink"))
+    else {
+        eprintln!("Warning: pause_valve not found, CLASS_MAP not applied");
+        return;
+    };
+    pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            for frame in batch.frames() {
+                for obj in frame.objects() {
+                    let label = obj.label();
+                    let Some((class_id, name)) = map.resolve(&label, obj.class_id) else { continue };
+                    obj.class_id = class_id;
+                    if name != label {
+                        obj.set_label(name);
+                    }
+                }
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
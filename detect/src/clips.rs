@@ -0,0 +1,82 @@
+use gstreamer::prelude::*;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+/// Event clips from nvurisrcbin smart record: each RTSP source keeps the last seconds of
+/// its encoded stream cached, so a clip can start before the event that triggered it.
+pub struct ClipConfig {
+    pub dir: String,
+    /// Seconds before the trigger included in a clip
+    pub pre_secs: u32,
+    /// Seconds after the trigger included in a clip
+    pub post_secs: u32,
+}
+
+impl ClipConfig {
+    /// Reads CLIP_DIR (required) / CLIP_PRE_SECONDS / CLIP_POST_SECONDS.
+    pub fn from_env() -> Option<ClipConfig> {
+        let dir = env::var("CLIP_DIR").ok()?;
+        let pre_secs = env::var("CLIP_PRE_SECONDS").ok().and_then(|v| v.parse().ok()).unwrap_or(5);
+        let post_secs = env::var("CLIP_POST_SECONDS").ok().and_then(|v| v.parse().ok()).unwrap_or(10);
+        Some(ClipConfig { dir, pre_secs, post_secs })
+    }
+
+    /// Smart-record properties for the nvurisrcbin of source `index`, which is named so
+    /// clips can be triggered on it.
+    pub fn source_properties(&self, index: usize) -> String {
+        format!(
+            " name={} smart-record=2 smart-rec-dir-path={} smart-rec-file-prefix=clip_src{} \
+             smart-rec-cache={} smart-rec-default-duration={}",
+            source_name(index),
+            self.dir,
+            index,
+            self.pre_secs,
+            self.pre_secs + self.post_secs
+        )
+    }
+}
+
+fn source_name(index: usize) -> String {
+    format!("clip_src{}", index)
+}
+
+/// Starts clips on the smart-record sources of the pipeline.
+pub struct ClipRecorder {
+    config: ClipConfig,
+    // Indexed by source_id; None for sources that can't record (not RTSP). Filled by attach
+    // once the pipeline exists.
+    sources: Mutex<Vec<Option<gstreamer::Element>>>,
+}
+
+pub type Clips = Arc<ClipRecorder>;
+
+impl ClipRecorder {
+    pub fn new(config: ClipConfig) -> ClipRecorder {
+        ClipRecorder { config, sources: Mutex::new(Vec::new()) }
+    }
+
+    pub fn attach(&self, bin: &gstreamer::Element, source_count: usize) {
+        if let Err(e) = std::fs::create_dir_all(&self.config.dir) {
+            eprintln!("Warning: Could not create CLIP_DIR {}: {}", self.config.dir, e);
+        }
+        let bin = bin.downcast_ref::<gstreamer::Bin>();
+        *self.sources.lock().unwrap() = (0..source_count)
+            .map(|index| bin.and_then(|b| b.by_name(&source_name(index))))
+            .collect();
+    }
+
+    /// Records a clip of source `source_id` around now; returns the directory it goes to.
+    /// A trigger while that source is already recording is ignored by smart record.
+    pub fn trigger(&self, source_id: u32) -> Option<&str> {
+        let source = self.sources.lock().unwrap().get(source_id as usize)?.clone()?;
+        let mut session_id: u32 = 0;
+        let session_ptr = &mut session_id as *mut u32 as glib::Pointer;
+        let duration = self.config.pre_secs + self.config.post_secs;
+        // start-sr(session id out, seconds back from now, duration, user data)
+        source.emit_by_name::<()>(
+            "start-sr",
+            &[&session_ptr, &self.config.pre_secs, &duration, &std::ptr::null_mut::<std::ffi::c_void>()],
+        );
+        Some(&self.config.dir)
+    }
+}
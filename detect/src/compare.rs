@@ -0,0 +1,292 @@
+use crate::detections::{Detection, FrameDetections, FrameObserver};
+use crate::json::Value;
+use gstreamer::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// gie-unique-id of the candidate model, telling its objects apart from pgie's
+pub const CANDIDATE_UNIQUE_ID: u32 = 30;
+/// Boxes of the same class overlapping this much count as the same detection
+const IOU_THRESHOLD: f32 = 0.5;
+/// How often the differential report is printed and rewritten
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A/B comparison of a candidate model against MODEL_CONFIG on live inputs. Every input is
+/// teed into the batch twice: inputs 0..N are the current model's, N..2N the candidate's
+/// copies of the same frames. The candidate runs right after pgie; a probe after it keeps
+/// each model's objects on its own copies and matches them up for the differential report.
+pub struct CompareConfig {
+    /// nvinfer config of the candidate (COMPARE_MODEL)
+    pub model_config: String,
+    /// Where the JSON report is written (COMPARE_REPORT)
+    pub report_path: Option<String>,
+    /// Real inputs; the batch holds twice as many frames
+    pub sources: usize,
+}
+
+impl CompareConfig {
+    /// Reads COMPARE_MODEL and COMPARE_REPORT; None without COMPARE_MODEL.
+    pub fn from_env(sources: usize) -> Option<CompareConfig> {
+        let model_config = env::var("COMPARE_MODEL").ok().filter(|v| !v.trim().is_empty())?;
+        if !Path::new(&model_config).exists() {
+            eprintln!("Error: COMPARE_MODEL config {} not found", model_config);
+            std::process::exit(2);
+        }
+        let report_path = env::var("COMPARE_REPORT").ok().filter(|v| !v.trim().is_empty());
+        Some(CompareConfig { model_config, report_path, sources })
+    }
+
+    /// The candidate's nvinfer, placed after pgie.
+    pub fn stage(&self) -> String {
+        format!(
+            "nvinfer name=compare_model config-file-path={} unique-id={}",
+            self.model_config, CANDIDATE_UNIQUE_ID
+        )
+    }
+
+    /// Tiler columns and rows: the two copies of a single input side by side, otherwise the
+    /// current model's inputs in the top row and the candidate's below.
+    pub fn grid(sources: usize) -> (usize, usize) {
+        if sources == 1 {
+            (2, 1)
+        } else {
+            (sources, 2)
+        }
+    }
+}
+
+#[derive(Default)]
+struct ClassAgreement {
+    both: u64,
+    only_current: u64,
+    only_candidate: u64,
+    current_confidence: f64,
+    candidate_confidence: f64,
+}
+
+impl ClassAgreement {
+    /// Share of all detections that both models made
+    fn agreement(&self) -> f64 {
+        let total = self.both + self.only_current + self.only_candidate;
+        if total == 0 { 1.0 } else { self.both as f64 / total as f64 }
+    }
+}
+
+/// How often two models' detections on the same frames match, per class.
+#[derive(Default)]
+pub struct Agreement {
+    frames: u64,
+    classes: BTreeMap<i32, ClassAgreement>,
+}
+
+fn iou(a: &Detection, b: &Detection) -> f32 {
+    let x1 = a.left.max(b.left);
+    let y1 = a.top.max(b.top);
+    let x2 = (a.left + a.width).min(b.left + b.width);
+    let y2 = (a.top + a.height).min(b.top + b.height);
+    let inter = (x2 - x1).max(0.0) * (y2 - y1).max(0.0);
+    let union = a.width * a.height + b.width * b.height - inter;
+    if union <= 0.0 { 0.0 } else { inter / union }
+}
+
+impl Agreement {
+    /// Matches one frame's detections greedily by overlap, same class only.
+    pub fn add(&mut self, current: &[Detection], candidate: &[Detection]) {
+        self.frames += 1;
+        let mut pairs: Vec<(f32, usize, usize)> = Vec::new();
+        for (i, a) in current.iter().enumerate() {
+            for (j, b) in candidate.iter().enumerate() {
+                let overlap = iou(a, b);
+                if a.class_id == b.class_id && overlap >= IOU_THRESHOLD {
+                    pairs.push((overlap, i, j));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mut current_matched = vec![false; current.len()];
+        let mut candidate_matched = vec![false; candidate.len()];
+        for (_, i, j) in pairs {
+            if current_matched[i] || candidate_matched[j] {
+                continue;
+            }
+            current_matched[i] = true;
+            candidate_matched[j] = true;
+            let class = self.classes.entry(current[i].class_id).or_default();
+            class.both += 1;
+            class.current_confidence += current[i].confidence as f64;
+            class.candidate_confidence += candidate[j].confidence as f64;
+        }
+        for (det, _) in current.iter().zip(&current_matched).filter(|(_, m)| !**m) {
+            let class = self.classes.entry(det.class_id).or_default();
+            class.only_current += 1;
+            class.current_confidence += det.confidence as f64;
+        }
+        for (det, _) in candidate.iter().zip(&candidate_matched).filter(|(_, m)| !**m) {
+            let class = self.classes.entry(det.class_id).or_default();
+            class.only_candidate += 1;
+            class.candidate_confidence += det.confidence as f64;
+        }
+    }
+
+    fn totals(&self) -> ClassAgreement {
+        let mut total = ClassAgreement::default();
+        for class in self.classes.values() {
+            total.both += class.both;
+            total.only_current += class.only_current;
+            total.only_candidate += class.only_candidate;
+        }
+        total
+    }
+
+    /// One line per class, for the console.
+    pub fn summary(&self, labels: &[String]) -> Vec<String> {
+        let mut lines = vec![format!("{} frames, agreement {:.3}", self.frames, self.totals().agreement())];
+        for (class_id, class) in &self.classes {
+            lines.push(format!(
+                "{:<16} both {:>6}  only current {:>6}  only candidate {:>6}  agreement {:.3}",
+                class_name(labels, *class_id),
+                class.both,
+                class.only_current,
+                class.only_candidate,
+                class.agreement()
+            ));
+        }
+        lines
+    }
+
+    pub fn to_json(&self, labels: &[String]) -> Value {
+        let mean = |sum: f64, count: u64| Value::from(if count == 0 { 0.0 } else { sum / count as f64 });
+        let classes = self
+            .classes
+            .iter()
+            .map(|(class_id, class)| {
+                Value::Object(vec![
+                    ("class_id".to_string(), Value::from(*class_id as i64)),
+                    ("name".to_string(), Value::from(class_name(labels, *class_id))),
+                    ("both".to_string(), Value::from(class.both)),
+                    ("only_current".to_string(), Value::from(class.only_current)),
+                    ("only_candidate".to_string(), Value::from(class.only_candidate)),
+                    ("agreement".to_string(), Value::from(class.agreement())),
+                    ("current_confidence".to_string(), mean(class.current_confidence, class.both + class.only_current)),
+                    ("candidate_confidence".to_string(), mean(class.candidate_confidence, class.both + class.only_candidate)),
+                ])
+            })
+            .collect();
+        Value::Object(vec![
+            ("frames".to_string(), Value::from(self.frames)),
+            ("iou_threshold".to_string(), Value::from(IOU_THRESHOLD)),
+            ("agreement".to_string(), Value::from(self.totals().agreement())),
+            ("classes".to_string(), Value::Array(classes)),
+        ])
+    }
+}
+
+fn class_name(labels: &[String], class_id: i32) -> String {
+    labels.get(class_id as usize).cloned().unwrap_or_else(|| class_id.to_string())
+}
+
+/// Copies an object out of its meta for matching.
+pub fn detection(obj: &nvds::NvDsObjectMeta) -> Detection {
+    let rect = &obj.rect_params;
+    Detection {
+        class_id: obj.class_id,
+        track_id: None,
+        confidence: obj.confidence,
+        left: rect.left,
+        top: rect.top,
+        width: rect.width,
+        height: rect.height,
+        keypoints: Vec::new(),
+    }
+}
+
+/// The running comparison; also a frame observer so the final report is written at shutdown.
+#[derive(Clone)]
+pub struct Comparison {
+    config: Arc<CompareConfig>,
+    labels: Arc<Vec<String>>,
+    agreement: Arc<Mutex<Agreement>>,
+}
+
+impl Comparison {
+    pub fn new(config: CompareConfig, labels: Vec<String>) -> Comparison {
+        Comparison { config: Arc::new(config), labels: Arc::new(labels), agreement: Arc::default() }
+    }
+
+    fn report(&self) {
+        let agreement = self.agreement.lock().unwrap();
+        println!("A/B comparison with {}:", self.config.model_config);
+        for line in agreement.summary(&self.labels) {
+            println!("  {}", line);
+        }
+        if let Some(path) = &self.config.report_path {
+            if let Err(e) = fs::write(path, agreement.to_json(&self.labels).to_json()) {
+                eprintln!("Warning: Failed to write comparison report {}: {}", path, e);
+            }
+        }
+    }
+
+    /// Sorts the objects onto their model's copies after the candidate's nvinfer and reports
+    /// every REPORT_INTERVAL.
+    pub fn attach(&self, pipeline: &gstreamer::Element) {
+        let Some(candidate) = pipeline.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("compare_model")) else {
+            eprintln!("Warning: compare_model element not found, A/B comparison disabled");
+            return;
+        };
+        let sources = self.config.sources as u32;
+        let agreement = self.agreement.clone();
+        let pad = candidate.static_pad("src").expect("nvinfer has a src pad");
+        pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+            if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+                let mut current: HashMap<u32, Vec<Detection>> = HashMap::new();
+                let mut candidates: HashMap<u32, Vec<Detection>> = HashMap::new();
+                for frame in batch.frames() {
+                    let (copy, source) = if frame.source_id < sources {
+                        (false, frame.source_id)
+                    } else {
+                        (true, frame.source_id - sources)
+                    };
+                    let mut kept = Vec::new();
+                    frame.retain_objects(|obj| {
+                        let keep = (obj.unique_component_id == CANDIDATE_UNIQUE_ID as i32) == copy;
+                        if keep {
+                            kept.push(detection(obj));
+                        }
+                        keep
+                    });
+                    if copy { candidates.insert(source, kept) } else { current.insert(source, kept) };
+                }
+                // Copies that didn't make the same batch (a dropped frame) aren't compared
+                let mut agreement = agreement.lock().unwrap();
+                for (source, current) in &current {
+                    if let Some(candidate) = candidates.get(source) {
+                        agreement.add(current, candidate);
+                    }
+                }
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+
+        let comparison = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(REPORT_INTERVAL);
+            comparison.report();
+        });
+    }
+}
+
+impl FrameObserver for Comparison {
+    fn on_frame(&mut self, _frame: &FrameDetections) {}
+
+    fn finish(&mut self) {
+        self.report();
+        if let Some(path) = &self.config.report_path {
+            println!("✓ Wrote comparison report: {}", path);
+        }
+    }
+}
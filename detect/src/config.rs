@@ -0,0 +1,502 @@
+use crate::json::{self, Value};
+use std::env;
+use std::fs;
+
+/// How a setting's value is written in APP_CONFIG_JSON.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    Number,
+    /// `true`/`false`
+    Flag,
+    /// Array of entries joined with the separator the setting's parser splits on
+    List(char),
+}
+
+/// Every setting detect reads, for validating APP_CONFIG_JSON. GST_* variables are
+/// passed through to GStreamer unchecked.
+const SCHEMA: &[(&str, Kind)] = &[
+    ("ANALYTICS_DIR", Kind::Text),
+    ("ANALYTICS_FORMAT", Kind::Text),
+    ("ANALYTICS_INTERVAL", Kind::Number),
+    ("ANALYTICS_ROTATE", Kind::Text),
+    ("ANPR_ALERT_INTERVAL", Kind::Number),
+    ("ANPR_MAX_DISTANCE", Kind::Number),
+    ("ANPR_SNAPSHOT_DIR", Kind::Text),
+    ("ANPR_WATCHLIST", Kind::Text),
+    ("ARAVIS_EXPOSURE", Kind::Number),
+    ("ARAVIS_FEATURES", Kind::Text),
+    ("ARAVIS_GAIN", Kind::Number),
+    ("ARAVIS_TRIGGER", Kind::Text),
+    ("BAYER", Kind::Text),
+    ("CALIBRATION", Kind::List(';')),
+    ("CLASS_FILTER", Kind::Text),
+    ("CLASS_MAP", Kind::List(';')),
+    ("CLIP_DIR", Kind::Text),
+    ("CLIP_POST_SECONDS", Kind::Number),
+    ("CLIP_PRE_SECONDS", Kind::Number),
+    ("COMPARE_MODEL", Kind::Text),
+    ("COMPARE_REPORT", Kind::Text),
+    ("CONTROL_PORT", Kind::Number),
+    ("CONTROL_SOCKET", Kind::Text),
+    ("CONVERT_OUTPUT_BUFFERS", Kind::Number),
+    ("CUDA_STAGE", Kind::Text),
+    ("CUSTOM_LIB", Kind::Text),
+    ("CUSTOM_POSITION", Kind::Text),
+    ("CUSTOM_PROPS", Kind::List(';')),
+    ("CUSTOM_STAGE", Kind::Text),
+    ("DATASET_DIR", Kind::Text),
+    ("DATASET_FORMAT", Kind::Text),
+    ("DATASET_INTERVAL", Kind::Number),
+    ("DATASET_SKIP_EMPTY", Kind::Flag),
+    ("DECODER_MAX_SESSIONS", Kind::Number),
+    ("DECODER_OVERFLOW", Kind::Text),
+    ("DENSITY_CONFIG", Kind::Text),
+    ("DENSITY_INTERVAL", Kind::Number),
+    ("DENSITY_LIMIT", Kind::Number),
+    ("DENSITY_SCALE", Kind::Number),
+    ("DENSITY_UNIQUE_ID", Kind::Number),
+    ("DETECTION_HOLD_FRAMES", Kind::Number),
+    ("DETECTION_MIN_FRAMES", Kind::Number),
+    ("DETECT_CONFIG", Kind::Text),
+    ("DETECT_OBJECT", Kind::Text),
+    ("DIAGNOSTICS_DIR", Kind::Text),
+    ("DIAGNOSTICS_MESSAGES", Kind::Number),
+    ("ENCODER_MAX_SESSIONS", Kind::Number),
+    ("ENCODER_OVERFLOW", Kind::Text),
+    ("ENCODER_TUNING", Kind::Text),
+    ("ENGINE_CACHE_DIR", Kind::Text),
+    ("ERROR_POLICY", Kind::List(';')),
+    ("EVAL_GT", Kind::Text),
+    ("EVAL_REPORT", Kind::Text),
+    ("EVENT_PLUGINS", Kind::List(';')),
+    ("EVENT_ROUTES", Kind::List(';')),
+    ("EVENT_RULES", Kind::List(';')),
+    ("EVENTS_FILE", Kind::Text),
+    ("EVENTS_MQTT", Kind::Text),
+    ("EVENTS_REMOTE_KINDS", Kind::List(',')),
+    ("EVENTS_WEBHOOK", Kind::Text),
+    ("EXPORT_DIR", Kind::Text),
+    ("EXPORT_FORMAT", Kind::List(',')),
+    ("FACE_DETECT_CONFIG", Kind::Text),
+    ("FACE_DETECT_ID", Kind::Number),
+    ("FACE_EMBED_CONFIG", Kind::Text),
+    ("FACE_EMBED_ID", Kind::Number),
+    ("FACE_GALLERY", Kind::Text),
+    ("FACE_THRESHOLD", Kind::Number),
+    ("FALL_ANGLE", Kind::Number),
+    ("FALL_CONFIRM_SECONDS", Kind::Number),
+    ("FALL_DETECT", Kind::Flag),
+    ("FALL_DROP", Kind::Number),
+    ("FALL_PERSON_LABEL", Kind::Text),
+    ("FALL_WINDOW", Kind::Number),
+    ("FILTER_CLASS_ID", Kind::Number),
+    ("FRAME_HOOK", Kind::Text),
+    ("GPS_BAUD", Kind::Number),
+    ("GPS_MAX_AGE", Kind::Number),
+    ("GPS_SOURCE", Kind::Text),
+    ("GPU_BUDGET", Kind::Number),
+    ("GPU_ID", Kind::Number),
+    ("GST_DEVICE", Kind::List(',')),
+    ("HEALTH_BITRATE_DROP", Kind::Number),
+    ("HEALTH_BLACK_LEVEL", Kind::Number),
+    ("HEALTH_MONITOR", Kind::Flag),
+    ("HEALTH_SECONDS", Kind::Number),
+    ("HEATMAP_CELL_SIZE", Kind::Number),
+    ("HEATMAP_DECAY", Kind::Number),
+    ("HEATMAP_EXPORT_DIR", Kind::Text),
+    ("HEATMAP_EXPORT_INTERVAL", Kind::Number),
+    ("HEATMAP_SCALE", Kind::Number),
+    ("IDLE_AFTER", Kind::Number),
+    ("IDLE_BITRATE", Kind::Number),
+    ("IDLE_INFER_INTERVAL", Kind::Number),
+    ("INGEST_JITTER", Kind::Number),
+    ("INGEST_MODE", Kind::Text),
+    ("LANES", Kind::List(';')),
+    ("LANE_CLASSES", Kind::List(',')),
+    ("LANE_COUNTS_FILE", Kind::Text),
+    ("LANE_INTERVAL", Kind::Number),
+    ("LANE_MIN_FRAMES", Kind::Number),
+    ("LATENCY_TEST_SECONDS", Kind::Number),
+    ("LEFT_BEHIND", Kind::Flag),
+    ("LEFT_BEHIND_CLASSES", Kind::List(',')),
+    ("LEFT_BEHIND_SECONDS", Kind::Number),
+    ("LEFT_BEHIND_TOLERANCE", Kind::Number),
+    ("LPD_CONFIG", Kind::Text),
+    ("LPD_UNIQUE_ID", Kind::Number),
+    ("LPR_CONFIG", Kind::Text),
+    ("LPR_UNIQUE_ID", Kind::Number),
+    ("MAP_CALIBRATION", Kind::List('|')),
+    ("MAP_DIR", Kind::Text),
+    ("MAP_IMAGE", Kind::Text),
+    ("MAP_INTERVAL", Kind::Number),
+    ("METRICS_PORT", Kind::Number),
+    ("MODEL_CONFIG", Kind::Text),
+    ("MODEL_ENGINE", Kind::Text),
+    ("MOTION_DETECT", Kind::Flag),
+    ("MOTION_MIN_FRAMES", Kind::Number),
+    ("MOTION_THRESHOLD", Kind::Number),
+    ("MUX_BATCH_TIMEOUT", Kind::Number),
+    ("MUX_BUFFER_POOL_SIZE", Kind::Number),
+    ("NDI_BANDWIDTH", Kind::Text),
+    ("NVBUF_MEMORY_TYPE", Kind::Text),
+    ("OUTPUT_HEIGHT", Kind::Number),
+    ("OUTPUT_WIDTH", Kind::Number),
+    ("PARKING_CLASSES", Kind::List(',')),
+    ("PARKING_OVERLAP", Kind::Number),
+    ("PARKING_OVERVIEW_DIR", Kind::Text),
+    ("PARKING_OVERVIEW_INTERVAL", Kind::Number),
+    ("PARKING_SMOOTHING", Kind::Number),
+    ("PARKING_SPOTS", Kind::List(';')),
+    ("PIPELINE_BUILDER", Kind::Text),
+    ("PPE_CONFIG", Kind::Text),
+    ("PPE_MIN_FRAMES", Kind::Number),
+    ("PPE_PERSON_LABEL", Kind::Text),
+    ("PPE_REQUIRED", Kind::List(',')),
+    ("PPE_UNIQUE_ID", Kind::Number),
+    ("PRESET", Kind::Text),
+    ("PROFILE", Kind::Text),
+    ("QUEUE_INTERVAL", Kind::Number),
+    ("QUEUE_MAX_LENGTH", Kind::Number),
+    ("QUEUE_MAX_WAIT", Kind::Number),
+    ("QUEUE_PERSON_LABEL", Kind::Text),
+    ("QUEUE_ZONES", Kind::List(';')),
+    ("RECORD_BITRATE", Kind::Number),
+    ("RECORD_DIR", Kind::Text),
+    ("RECORD_MODE", Kind::Text),
+    ("RECORD_PREFIX", Kind::Text),
+    ("REID_CONFIG", Kind::Text),
+    ("REID_MAX_AGE", Kind::Number),
+    ("REID_THRESHOLD", Kind::Number),
+    ("REID_UNIQUE_ID", Kind::Number),
+    ("REMOVED_SECONDS", Kind::Number),
+    ("REPLAY", Kind::Flag),
+    ("REPLAY_DUMP_DIR", Kind::Text),
+    ("RTSP_ALLOW", Kind::List(',')),
+    ("RTSP_CLIENT_BITRATE", Kind::Number),
+    ("RTSP_DENY", Kind::List(',')),
+    ("RTSP_DROP_ON_LATENCY", Kind::Flag),
+    ("RTSP_IDLE_LINGER", Kind::Number),
+    ("RTSP_LATENCY", Kind::Number),
+    ("RTSP_MAX_CLIENTS", Kind::Number),
+    ("RTSP_MTU", Kind::Number),
+    ("RTSP_MULTICAST", Kind::Text),
+    ("RTSP_MULTICAST_IFACE", Kind::Text),
+    ("RTSP_MULTICAST_TTL", Kind::Number),
+    ("RTSP_OUTPUT", Kind::Text),
+    ("RTSP_OUTPUT_PORT", Kind::Number),
+    ("RTSP_PASSWORD", Kind::Text),
+    ("RTSP_PROTOCOL", Kind::Text),
+    ("RTSP_PROTOCOLS", Kind::Text),
+    ("RTSP_RTP_PORTS", Kind::Text),
+    ("RTSP_URL", Kind::Text),
+    ("RTSP_USER", Kind::Text),
+    ("SAMPLES_DIR", Kind::Text),
+    ("SCRIPT", Kind::Text),
+    ("SCRIPT_SNAPSHOT_DIR", Kind::Text),
+    ("SHADOW_LOG", Kind::Text),
+    ("SHADOW_MODEL", Kind::Text),
+    ("SHADOW_REPORT", Kind::Text),
+    ("SHOW_DISPLAY", Kind::Flag),
+    ("SINKS", Kind::List(';')),
+    ("SINK_QUEUE", Kind::Text),
+    ("SINK_SYNC", Kind::Flag),
+    ("SIZE_FILTERS", Kind::List(';')),
+    ("SNAPSHOT_INTERVAL", Kind::Number),
+    ("SOFTWARE_PIPELINE", Kind::Flag),
+    ("SOURCE_MAX_INTERVAL", Kind::Number),
+    ("SOURCE_MODELS", Kind::List(';')),
+    ("SOURCE_OFFSETS", Kind::List(',')),
+    ("SOURCE_PRIORITY", Kind::List(',')),
+    ("SOURCE_STARTUP_RETRY", Kind::Number),
+    ("SOURCE_STARTUP_TIMEOUT", Kind::Number),
+    ("SOURCE_TIMEOUT", Kind::Number),
+    ("SPEED_LIMIT", Kind::Number),
+    ("SPEED_SMOOTHING", Kind::Number),
+    ("SPEED_WINDOW", Kind::Number),
+    ("STAGE_QUEUES", Kind::List(';')),
+    ("STATE_FILE", Kind::Text),
+    ("STATE_SAVE_INTERVAL", Kind::Number),
+    ("SYNC_PLAYBACK", Kind::Flag),
+    ("TAMPER_BLUR", Kind::Number),
+    ("TAMPER_DETECT", Kind::Flag),
+    ("TAMPER_LEARN_SECONDS", Kind::Number),
+    ("TAMPER_SCENE_CHANGE", Kind::Number),
+    ("TAMPER_SECONDS", Kind::Number),
+    ("TEST_SOURCE_FRAMERATE", Kind::Number),
+    ("TEST_SOURCE_HEIGHT", Kind::Number),
+    ("TEST_SOURCE_OBJECTS", Kind::Number),
+    ("TEST_SOURCE_PATTERN", Kind::Text),
+    ("TEST_SOURCE_WIDTH", Kind::Number),
+    ("THERMAL", Kind::Flag),
+    ("THERMAL_ALERT_PIXELS", Kind::Number),
+    ("THERMAL_ALERT_TEMP", Kind::Number),
+    ("THERMAL_OFFSET", Kind::Number),
+    ("THERMAL_PALETTE", Kind::Text),
+    ("THERMAL_RANGE", Kind::List(',')),
+    ("THERMAL_SCALE", Kind::Number),
+    ("TRACKER_CONFIG", Kind::Text),
+    ("TRACKER_LIB", Kind::Text),
+    ("TRAJECTORIES", Kind::Flag),
+    ("TRAJECTORY_LENGTH", Kind::Number),
+    ("TRAJECTORY_WIDTH", Kind::Number),
+    ("ZONES", Kind::List(';')),
+];
+
+/// Loads the optional DETECT_CONFIG file: `KEY=VALUE` lines using the same names
+/// as the environment variables, `#` comments allowed. Variables already set in
+/// the environment take precedence, so a config file can be overridden per run.
+///
+/// Must run before anything else reads the environment.
+pub fn load_config_file() {
+    let path = match env::var("DETECT_CONFIG") {
+        Ok(path) => path,
+        Err(_) => return,
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("Error: Could not read DETECT_CONFIG {}: {}", path, e);
+            std::process::exit(2);
+        }
+    };
+
+    for (line_no, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            eprintln!("Warning: {}:{}: expected KEY=VALUE, ignoring '{}'", path, line_no + 1, line);
+            continue;
+        };
+        let key = key.trim().trim_start_matches("export ").trim();
+        let value = value.trim().trim_matches('"');
+        if env::var_os(key).is_none() {
+            env::set_var(key, value);
+        }
+    }
+    println!("Loaded configuration from {}", path);
+}
+
+/// Loads APP_CONFIG_JSON, a JSON document holding the whole configuration (e.g. one
+/// Kubernetes ConfigMap value). Keys are the environment variable names, either flat
+/// (`"RTSP_LATENCY": 200`) or grouped by prefix (`"rtsp": {"latency": 200}`); lists
+/// such as SINKS or GST_DEVICE can be arrays. Like DETECT_CONFIG, variables already
+/// set in the environment take precedence.
+///
+/// Must run before anything else reads the environment.
+pub fn load_json_config() {
+    let Ok(document) = env::var("APP_CONFIG_JSON") else { return };
+    let parsed = match json::parse(&document) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Error: APP_CONFIG_JSON is not valid JSON ({})", e);
+            std::process::exit(2);
+        }
+    };
+    let settings = match flatten_json(&parsed) {
+        Ok(settings) => settings,
+        Err(errors) => {
+            eprintln!("Error: Invalid APP_CONFIG_JSON:");
+            for error in errors {
+                eprintln!("  - {}", error);
+            }
+            std::process::exit(2);
+        }
+    };
+    let count = settings.len();
+    for (key, value) in settings {
+        if env::var_os(&key).is_none() {
+            env::set_var(key, value);
+        }
+    }
+    println!("Loaded {} settings from APP_CONFIG_JSON", count);
+}
+
+/// Checks a configuration document against the schema and turns it into
+/// environment variable assignments; errors name the offending path, e.g. `$.rtsp.latency`.
+fn flatten_json(document: &Value) -> Result<Vec<(String, String)>, Vec<String>> {
+    let mut settings = Vec::new();
+    let mut errors = Vec::new();
+    match document {
+        Value::Object(entries) => flatten_object(entries, "", "$", &mut settings, &mut errors),
+        _ => errors.push("$: expected an object of settings".to_string()),
+    }
+    if errors.is_empty() {
+        Ok(settings)
+    } else {
+        Err(errors)
+    }
+}
+
+fn flatten_object(
+    entries: &[(String, Value)],
+    prefix: &str,
+    path: &str,
+    settings: &mut Vec<(String, String)>,
+    errors: &mut Vec<String>,
+) {
+    for (key, value) in entries {
+        let path = format!("{}.{}", path, key);
+        let name = format!("{}{}", prefix, key.to_uppercase().replace('-', "_"));
+        if let Value::Object(group) = value {
+            flatten_object(group, &format!("{}_", name), &path, settings, errors);
+            continue;
+        }
+        if *value == Value::Null {
+            continue;
+        }
+        let kind = match SCHEMA.iter().find(|(n, _)| *n == name) {
+            Some((_, kind)) => *kind,
+            None if name.starts_with("GST_") => Kind::Text,
+            None => {
+                let hint = closest_setting(&name).map(|n| format!(" (did you mean {}?)", n)).unwrap_or_default();
+                errors.push(format!("{}: unknown setting {}{}", path, name, hint));
+                continue;
+            }
+        };
+        match setting_value(value, kind) {
+            Ok(text) => settings.push((name, text)),
+            Err(expected) => errors.push(format!("{}: {} expects {}, got {}", path, name, expected, value.to_json())),
+        }
+    }
+}
+
+/// The environment variable value for one setting, or what was expected instead.
+fn setting_value(value: &Value, kind: Kind) -> Result<String, &'static str> {
+    let scalar = |value: &Value| match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) if n.fract() == 0.0 => Some(format!("{}", *n as i64)),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    };
+    match (kind, value) {
+        (Kind::Flag, Value::Bool(b)) => Ok(b.to_string()),
+        (Kind::Flag, Value::String(s)) if s == "true" || s == "false" => Ok(s.clone()),
+        (Kind::Flag, _) => Err("true or false"),
+        (Kind::Number, Value::Number(_)) => Ok(scalar(value).unwrap_or_default()),
+        (Kind::Number, Value::String(s)) if s.trim().parse::<f64>().is_ok() => Ok(s.trim().to_string()),
+        (Kind::Number, _) => Err("a number"),
+        (Kind::List(separator), Value::Array(items)) => {
+            let entries: Option<Vec<String>> =
+                items.iter().map(|item| if let Value::Bool(_) = item { None } else { scalar(item) }).collect();
+            let joined = if separator == ';' { "; " } else { "," };
+            entries.map(|e| e.join(joined)).ok_or("a string or an array of strings")
+        }
+        (Kind::List(_), Value::String(s)) => Ok(s.clone()),
+        (Kind::List(_), _) => Err("a string or an array of strings"),
+        (Kind::Text, Value::String(_) | Value::Number(_)) => Ok(scalar(value).unwrap_or_default()),
+        (Kind::Text, _) => Err("a string"),
+    }
+}
+
+/// Known setting within two edits of a misspelled one.
+fn closest_setting(name: &str) -> Option<&'static str> {
+    SCHEMA
+        .iter()
+        .map(|(known, _)| (edit_distance(name, known), *known))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flatten(document: &str) -> Result<Vec<(String, String)>, Vec<String>> {
+        flatten_json(&json::parse(document).unwrap())
+    }
+
+    fn settings(document: &str) -> Vec<(String, String)> {
+        flatten(document).unwrap()
+    }
+
+    fn errors(document: &str) -> Vec<String> {
+        flatten(document).unwrap_err()
+    }
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn flat_and_grouped_keys_become_variables() {
+        assert_eq!(
+            settings(r#"{"CONTROL_PORT": 9000, "trajectory": {"length": "30", "width": 2.5}, "gst-debug": 3, "CLIP_DIR": null}"#),
+            [pair("CONTROL_PORT", "9000"), pair("TRAJECTORY_LENGTH", "30"), pair("TRAJECTORY_WIDTH", "2.5"), pair("GST_DEBUG", "3")]
+        );
+    }
+
+    #[test]
+    fn lists_join_with_their_separator() {
+        assert_eq!(
+            settings(r#"{"ZONES": ["door:0,0,10,10", "desk:5,5,9,9"], "CLASS_MAP": "0=person"}"#),
+            [pair("ZONES", "door:0,0,10,10; desk:5,5,9,9"), pair("CLASS_MAP", "0=person")]
+        );
+    }
+
+    #[test]
+    fn flags_accept_booleans_and_their_strings() {
+        assert_eq!(settings(r#"{"TRAJECTORIES": true}"#), [pair("TRAJECTORIES", "true")]);
+        assert_eq!(settings(r#"{"TRAJECTORIES": "false"}"#), [pair("TRAJECTORIES", "false")]);
+    }
+
+    #[test]
+    fn type_mismatches_name_the_expected_type() {
+        assert_eq!(errors(r#"{"TRAJECTORIES": 1}"#), ["$.TRAJECTORIES: TRAJECTORIES expects true or false, got 1"]);
+        assert_eq!(errors(r#"{"TRAJECTORIES": "yes"}"#), [r#"$.TRAJECTORIES: TRAJECTORIES expects true or false, got "yes""#]);
+        assert_eq!(errors(r#"{"CONTROL_PORT": "many"}"#), [r#"$.CONTROL_PORT: CONTROL_PORT expects a number, got "many""#]);
+        assert_eq!(errors(r#"{"CONTROL_PORT": [1]}"#), ["$.CONTROL_PORT: CONTROL_PORT expects a number, got [1]"]);
+        assert_eq!(errors(r#"{"ZONES": 3}"#), ["$.ZONES: ZONES expects a string or an array of strings, got 3"]);
+        assert_eq!(errors(r#"{"ZONES": ["a", true]}"#), [r#"$.ZONES: ZONES expects a string or an array of strings, got ["a",true]"#]);
+        assert_eq!(errors(r#"{"CLIP_DIR": false}"#), ["$.CLIP_DIR: CLIP_DIR expects a string, got false"]);
+    }
+
+    #[test]
+    fn errors_give_the_nested_path() {
+        assert_eq!(
+            errors(r#"{"trajectory": {"width": "wide", "colour": "red"}}"#),
+            [
+                r#"$.trajectory.width: TRAJECTORY_WIDTH expects a number, got "wide""#,
+                "$.trajectory.colour: unknown setting TRAJECTORY_COLOUR",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_settings_suggest_a_close_name() {
+        assert_eq!(errors(r#"{"CONTROL_PROT": 1}"#), ["$.CONTROL_PROT: unknown setting CONTROL_PROT (did you mean CONTROL_PORT?)"]);
+        assert_eq!(errors(r#"{"clip": {"dirs": "x"}}"#), ["$.clip.dirs: unknown setting CLIP_DIRS (did you mean CLIP_DIR?)"]);
+        assert_eq!(errors(r#"{"NOTHING_LIKE_IT": 1}"#), ["$.NOTHING_LIKE_IT: unknown setting NOTHING_LIKE_IT"]);
+    }
+
+    #[test]
+    fn the_document_must_be_an_object() {
+        assert_eq!(errors("[1]"), ["$: expected an object of settings"]);
+    }
+
+    #[test]
+    fn edit_distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("ZONES", "ZONES"), 0);
+        assert_eq!(edit_distance("ZONE", "ZONES"), 1);
+        assert_eq!(edit_distance("ZOENS", "ZONES"), 2);
+        assert_eq!(edit_distance("", "ABC"), 3);
+    }
+}
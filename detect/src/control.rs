@@ -0,0 +1,507 @@
+use crate::banner::{self, Banner};
+use crate::classes::ClassFilter;
+use crate::snapshot::Snapshots;
+use crate::state::SharedState;
+use crate::viewers::Viewers;
+use gstreamer::prelude::*;
+use std::env;
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Operator commands, accepted one per line from the keyboard and the control socket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Pause,
+    Resume,
+    Toggle,
+    Flush,
+    /// Advance a paused non-live pipeline by this many batches
+    Step(u32),
+    Status,
+    /// Detect only these classes (comma-separated names or IDs, `all`); empty shows the current ones
+    Classes(String),
+    /// Make every encoder send an IDR frame now
+    Keyframe,
+    /// Draw this text over the video for this many seconds; no text takes the banner down
+    Banner { seconds: u32, text: String },
+    /// RTSP clients per mount and their addresses
+    Viewers,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Option<Command> {
+        // Banner text keeps its case: `banner [seconds] text`, `banner clear`
+        let raw = line.trim();
+        let banner = raw.get(..6).filter(|word| word.eq_ignore_ascii_case("banner")).map(|_| &raw[6..]);
+        if let Some(rest) = banner.filter(|rest| rest.is_empty() || rest.starts_with(' ')) {
+            let rest = rest.trim();
+            if rest.is_empty() || rest.eq_ignore_ascii_case("clear") {
+                return Some(Command::Banner { seconds: 0, text: String::new() });
+            }
+            let (seconds, text) = match rest.split_once(' ').map(|(n, text)| (n.parse::<u32>(), text.trim())) {
+                Some((Ok(seconds), text)) if seconds > 0 && !text.is_empty() => (seconds, text),
+                _ => (banner::DEFAULT_SECONDS, rest),
+            };
+            return Some(Command::Banner { seconds, text: text.to_string() });
+        }
+        let line = raw.to_lowercase();
+        if let Some(count) = line.strip_prefix("step ").or_else(|| line.strip_prefix("n ")) {
+            return count.trim().parse().ok().filter(|n| *n > 0).map(Command::Step);
+        }
+        if let Some(classes) = line.strip_prefix("classes").or_else(|| line.strip_prefix("class")) {
+            if classes.is_empty() || classes.starts_with(' ') {
+                return Some(Command::Classes(classes.trim().to_string()));
+            }
+        }
+        match line.as_str() {
+            "p" | "pause" => Some(Command::Pause),
+            "r" | "resume" | "play" => Some(Command::Resume),
+            "" | "t" | "toggle" => Some(Command::Toggle),
+            "f" | "flush" => Some(Command::Flush),
+            "n" | "step" | "next" => Some(Command::Step(1)),
+            "s" | "status" => Some(Command::Status),
+            "k" | "keyframe" | "idr" => Some(Command::Keyframe),
+            "v" | "viewers" | "clients" => Some(Command::Viewers),
+            "q" | "quit" | "stop" => Some(Command::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// A command plus where to send the one-line reply (None for the keyboard, which prints it).
+pub struct Request {
+    pub command: Command,
+    pub reply: Option<mpsc::Sender<String>>,
+}
+
+const HELP: &str =
+    "commands: pause (p), resume (r), toggle (Enter), flush (f), step [N] (n), status (s), classes [names|all], keyframe (k), banner [seconds] text|clear, viewers (v), quit (q)";
+
+/// Keyboard controls: reads commands from stdin when it is a terminal.
+pub fn spawn_keyboard(requests: mpsc::Sender<Request>) {
+    if !std::io::stdin().is_terminal() {
+        return;
+    }
+    println!("Keyboard controls: {}", HELP);
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            match Command::parse(&line) {
+                Some(command) => {
+                    if requests.send(Request { command, reply: None }).is_err() {
+                        break;
+                    }
+                }
+                None => println!("Unknown command '{}' - {}", line.trim(), HELP),
+            }
+        }
+    });
+}
+
+/// Control API: a Unix socket at CONTROL_SOCKET taking one command per line and
+/// answering each with one line, e.g. `echo pause | nc -U /tmp/detect.sock`.
+#[cfg(unix)]
+pub fn spawn_socket(requests: mpsc::Sender<Request>) {
+    let Ok(path) = env::var("CONTROL_SOCKET") else { return };
+    // A stale socket from a previous run would make bind fail
+    let _ = std::fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Warning: Could not open control socket {}: {}", path, e);
+            return;
+        }
+    };
+    println!("Control socket: {}", path);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let requests = requests.clone();
+            thread::spawn(move || {
+                let Ok(mut writer) = stream.try_clone() else { return };
+                for line in BufReader::new(stream).lines() {
+                    let Ok(line) = line else { break };
+                    let response = match Command::parse(&line) {
+                        Some(command) => {
+                            let (reply, answer) = mpsc::channel();
+                            if requests.send(Request { command, reply: Some(reply) }).is_err() {
+                                break;
+                            }
+                            answer.recv().unwrap_or_else(|_| "error: pipeline stopped".to_string())
+                        }
+                        None => format!("error: unknown command '{}' ({})", line.trim(), HELP),
+                    };
+                    if writeln!(writer, "{}", response).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Largest request body the HTTP API reads; a class list or banner text is far smaller.
+const MAX_HTTP_BODY: usize = 16 * 1024;
+
+/// How long a silent HTTP client may hold the (single) control connection.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Address the HTTP API listens on: CONTROL_BIND (default 127.0.0.1, this machine only)
+/// and CONTROL_PORT.
+pub fn http_address(port: &str) -> String {
+    let bind = env::var("CONTROL_BIND").unwrap_or_else(|_| "127.0.0.1".to_string());
+    if bind.contains(':') {
+        format!("[{}]:{}", bind, port)
+    } else {
+        format!("{}:{}", bind, port)
+    }
+}
+
+/// Reads the request line, headers and body of one HTTP request. Err is the status to
+/// answer with, or None when the client went away or timed out.
+fn read_http_request(reader: &mut impl BufRead) -> Result<(String, Vec<u8>), Option<&'static str>> {
+    let mut request = String::new();
+    if reader.read_line(&mut request).is_err() {
+        return Err(None);
+    }
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).is_err() || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().map_err(|_| Some("400 Bad Request"))?;
+            }
+        }
+    }
+    if length > MAX_HTTP_BODY {
+        return Err(Some("413 Payload Too Large"));
+    }
+    let mut body = vec![0; length];
+    if reader.read_exact(&mut body).is_err() {
+        return Err(None);
+    }
+    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+    Ok((path, body))
+}
+
+fn respond(mut stream: &TcpStream, status: &str, response: &str) {
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}\n",
+        status,
+        response.len() + 1,
+        response
+    );
+}
+
+/// REST flavor of the control API on CONTROL_PORT: the path is the command and the body
+/// its argument, e.g. `curl -X POST -d person,car localhost:8090/classes`. Replies are
+/// the same lines as on the socket. There is no authentication, so it only listens on
+/// the loopback interface unless CONTROL_BIND says otherwise.
+pub fn spawn_http(requests: mpsc::Sender<Request>, snapshots: Snapshots) {
+    let Ok(port) = env::var("CONTROL_PORT") else { return };
+    let address = http_address(&port);
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Warning: Could not open control port {}: {}", address, e);
+            return;
+        }
+    };
+    println!("Control API: http://{}/<command>", address);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = stream.set_read_timeout(Some(HTTP_TIMEOUT));
+            let _ = stream.set_write_timeout(Some(HTTP_TIMEOUT));
+            let (path, body) = match read_http_request(&mut BufReader::new(&stream)) {
+                Ok(request) => request,
+                Err(Some(status)) => {
+                    respond(&stream, status, &format!("error: {}", status));
+                    continue;
+                }
+                Err(None) => continue,
+            };
+            let path = path.as_str();
+            if let Some(query) = path.strip_prefix("/snapshot.jpg") {
+                serve_snapshot(&stream, &snapshots, query);
+                continue;
+            }
+            let line = format!("{} {}", path.trim_start_matches('/').replace('/', " "), String::from_utf8_lossy(&body));
+            // An empty command would toggle the pause
+            let (status, response) = match Command::parse(&line).filter(|_| !line.trim().is_empty()) {
+                Some(command) => {
+                    let (reply, answer) = mpsc::channel();
+                    if requests.send(Request { command, reply: Some(reply) }).is_err() {
+                        break;
+                    }
+                    let response = answer.recv().unwrap_or_else(|_| "error: pipeline stopped".to_string());
+                    (if response.starts_with("error") { "409 Conflict" } else { "200 OK" }, response)
+                }
+                None => ("404 Not Found", format!("error: unknown command '{}' ({})", line.trim(), HELP)),
+            };
+            respond(&stream, status, &response);
+        }
+    });
+}
+
+/// Answers `GET /snapshot.jpg[?source=N]` with the latest JPEG of the output or of input N.
+fn serve_snapshot(mut stream: &TcpStream, snapshots: &Snapshots, query: &str) {
+    let source = query.strip_prefix('?').unwrap_or_default().split('&').find_map(|p| p.strip_prefix("source="));
+    let result = match source.map(|s| s.parse::<usize>()) {
+        None => snapshots.latest(None),
+        Some(Ok(index)) => snapshots.latest(Some(index)),
+        Some(Err(_)) => Err(("400 Bad Request", "error: source must be an input number".to_string())),
+    };
+    let (status, kind, body) = match result {
+        Ok(jpeg) => ("200 OK", "image/jpeg", jpeg),
+        Err((status, message)) => (status, "text/plain", format!("{}\n", message).into_bytes()),
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        kind,
+        body.len()
+    );
+    let _ = stream.write_all(&body);
+}
+
+/// Asks every video encoder in `pipeline` for an IDR frame (with SPS/PPS) via an upstream
+/// force-key-unit event, so viewers joining mid-stream get a picture without waiting for
+/// the next scheduled keyframe. Returns how many encoders were asked.
+pub fn force_keyframe(pipeline: &gstreamer::Element) -> usize {
+    let Some(bin) = pipeline.downcast_ref::<gstreamer::Bin>() else { return 0 };
+    let encoders = bin.iterate_recurse().into_iter().flatten().filter(|element| {
+        element
+            .factory()
+            .and_then(|f| f.metadata("klass").map(|klass| klass.contains("Encoder") && klass.contains("Video")))
+            .unwrap_or(false)
+    });
+    let mut count = 0;
+    for encoder in encoders {
+        let Some(src) = encoder.static_pad("src") else { continue };
+        // What gst_video_event_new_upstream_force_key_unit builds
+        let structure = gstreamer::Structure::builder("GstForceKeyUnit")
+            .field("running-time", u64::MAX)
+            .field("all-headers", true)
+            .field("count", 0u32)
+            .build();
+        if src.send_event(gstreamer::event::CustomUpstream::new(structure)) {
+            count += 1;
+        }
+    }
+    count
+}
+
+#[cfg(not(unix))]
+pub fn spawn_socket(_requests: mpsc::Sender<Request>) {
+    if env::var("CONTROL_SOCKET").is_ok() {
+        eprintln!("Warning: CONTROL_SOCKET needs Unix sockets; use the keyboard controls on this platform");
+    }
+}
+
+/// Applies commands to the running pipeline.
+///
+/// Pausing a live source makes cameras drop the session or pile up latency, so live
+/// inputs keep running and the `pause_valve` after inference drops frames instead;
+/// the display freezes on the last frame and recordings simply have a gap.
+pub struct Controller {
+    pipeline: gstreamer::Element,
+    live: bool,
+    paused: bool,
+    classes: ClassFilter,
+    banner: Banner,
+    viewers: Viewers,
+    /// Where pause and class changes are persisted (STATE_FILE)
+    state: Option<SharedState>,
+}
+
+impl Controller {
+    /// `paused` tells the controller the pipeline was started paused (replay mode).
+    pub fn new(
+        pipeline: gstreamer::Element,
+        live: bool,
+        paused: bool,
+        classes: ClassFilter,
+        banner: Banner,
+        viewers: Viewers,
+    ) -> Controller {
+        Controller { pipeline, live, paused, classes, banner, viewers, state: None }
+    }
+
+    /// Applies the pause state and classes saved by the last run (`control.paused`,
+    /// `control.classes`) and keeps saving later changes to `state`.
+    pub fn restore(&mut self, state: SharedState) {
+        let (paused, classes) = {
+            let state = state.lock().unwrap();
+            (state.get("control.paused") == Some("true"), state.get("control.classes").map(str::to_string))
+        };
+        self.state = Some(state);
+        if let Some(spec) = classes.filter(|spec| spec != "all") {
+            let response = self.set_classes(&spec);
+            if response.starts_with("error") {
+                eprintln!("Warning: Could not restore classes {}: {}", spec, response);
+            }
+        }
+        if paused {
+            let response = self.set_paused(true);
+            if response.starts_with("error") {
+                eprintln!("Warning: Could not restore the paused state: {}", response);
+            }
+        }
+    }
+
+    fn persist(&self, key: &str, value: impl ToString) {
+        if let Some(state) = &self.state {
+            state.lock().unwrap().set(key, value);
+        }
+    }
+
+    pub fn handle(&mut self, command: Command) -> String {
+        match command {
+            Command::Pause => self.set_paused(true),
+            Command::Resume => self.set_paused(false),
+            Command::Toggle => self.set_paused(!self.paused),
+            Command::Flush => self.flush(),
+            Command::Step(count) => self.step(count),
+            Command::Status => format!(
+                "ok: {} ({} source, classes: {})",
+                if self.paused { "paused" } else { "playing" },
+                if self.live { "live" } else { "non-live" },
+                self.classes.describe()
+            ),
+            Command::Classes(spec) if spec.is_empty() => format!("ok: classes {}", self.classes.describe()),
+            Command::Classes(spec) => self.set_classes(&spec),
+            Command::Keyframe => match force_keyframe(&self.pipeline) {
+                0 => "error: no encoder took the keyframe request".to_string(),
+                count => format!("ok: keyframe requested from {} encoder{}", count, if count == 1 { "" } else { "s" }),
+            },
+            Command::Banner { text, .. } if text.is_empty() => {
+                if self.banner.clear() { "ok: banner cleared" } else { "ok: no banner showing" }.to_string()
+            }
+            Command::Banner { seconds, text } => {
+                self.banner.show(&text, seconds);
+                println!("Banner for {}s: {}", seconds, text);
+                format!("ok: banner shown for {}s", seconds)
+            }
+            Command::Viewers => format!("ok: {}", self.viewers.describe()),
+            Command::Quit => {
+                self.pipeline.send_event(gstreamer::event::Eos::new());
+                "ok: stopping".to_string()
+            }
+        }
+    }
+
+    fn set_paused(&mut self, paused: bool) -> String {
+        if paused == self.paused {
+            return format!("ok: already {}", if paused { "paused" } else { "playing" });
+        }
+        if self.live {
+            match self.by_name("pause_valve") {
+                Some(valve) => valve.set_property("drop", paused),
+                None => return "error: pause valve not found".to_string(),
+            }
+        } else {
+            let state = if paused { gstreamer::State::Paused } else { gstreamer::State::Playing };
+            if let Err(e) = self.pipeline.set_state(state) {
+                return format!("error: could not change state: {}", e);
+            }
+        }
+        self.paused = paused;
+        self.persist("control.paused", paused);
+        let state = if paused { "paused" } else { "playing" };
+        println!("Pipeline {}", state);
+        format!("ok: {}", state)
+    }
+
+    /// Steps the paused pipeline by `count` buffers at the sinks. Each buffer is one
+    /// nvstreammux batch, i.e. one frame of every source.
+    fn step(&mut self, count: u32) -> String {
+        if self.live {
+            return "error: live sources can't be stepped".to_string();
+        }
+        if !self.paused {
+            let response = self.set_paused(true);
+            if response.starts_with("error") {
+                return response;
+            }
+        }
+        let step = gstreamer::event::Step::new(gstreamer::format::Buffers::from_u64(count as u64), 1.0, true, false);
+        if !self.pipeline.send_event(step) {
+            return "error: step was not handled".to_string();
+        }
+        format!("ok: stepped {} frame{}", count, if count == 1 { "" } else { "s" })
+    }
+
+    /// Flushes everything downstream of nvstreammux, which unsticks a wedged encoder or sink.
+    /// Running time is kept so live sources and recordings stay in sync.
+    fn flush(&self) -> String {
+        let Some(mux) = self.by_name("m") else {
+            return "error: nvstreammux not found".to_string();
+        };
+        let pad = mux.static_pad("src").expect("nvstreammux has a src pad");
+        pad.push_event(gstreamer::event::FlushStart::new());
+        pad.push_event(gstreamer::event::FlushStop::new(false));
+        println!("Pipeline flushed");
+        "ok: flushed".to_string()
+    }
+
+    /// Regenerates the filtered nvinfer config and points the running nvinfer at it.
+    fn set_classes(&mut self, spec: &str) -> String {
+        let ids = match self.classes.resolve(spec) {
+            Ok(ids) => ids,
+            Err(e) => return format!("error: {}", e),
+        };
+        let Some(pgie) = self.by_name("pgie") else {
+            return "error: nvinfer not found".to_string();
+        };
+        match self.classes.update(ids) {
+            Ok(Some(path)) => pgie.set_property("config-file-path", &path),
+            Ok(None) => {}
+            Err(e) => return format!("error: could not write filtered config: {}", e),
+        }
+        self.persist("control.classes", self.classes.spec());
+        println!("Detecting classes: {}", self.classes.describe());
+        format!("ok: classes {}", self.classes.describe())
+    }
+
+    fn by_name(&self, name: &str) -> Option<gstreamer::Element> {
+        self.pipeline.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(text: &str) -> Result<(String, Vec<u8>), Option<&'static str>> {
+        read_http_request(&mut text.as_bytes())
+    }
+
+    #[test]
+    fn http_requests_give_their_path_and_body() {
+        let (path, body) = request("POST /classes HTTP/1.1\r\nHost: x\r\nContent-Length: 10\r\n\r\nperson,car").unwrap();
+        assert_eq!((path.as_str(), body.as_slice()), ("/classes", b"person,car".as_slice()));
+        let (path, body) = request("GET /status HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!((path.as_str(), body.len()), ("/status", 0));
+    }
+
+    #[test]
+    fn oversized_or_bad_lengths_are_refused_before_reading_the_body() {
+        assert_eq!(request("POST /classes HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n"), Err(Some("413 Payload Too Large")));
+        assert_eq!(request("POST /classes HTTP/1.1\r\ncontent-length: -1\r\n\r\n"), Err(Some("400 Bad Request")));
+        // The client hung up before sending the whole body
+        assert_eq!(request("POST /classes HTTP/1.1\r\nContent-Length: 5\r\n\r\nab"), Err(None));
+    }
+}
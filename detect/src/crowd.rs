@@ -0,0 +1,152 @@
+use crate::events::{Event, Events};
+use crate::zones::{GridCells, Zone};
+use gstreamer::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+/// Crowd counting with a density-estimation model (CSRNet style) for scenes too dense
+/// for per-person boxes. The model runs as a second nvinfer exporting its raw output;
+/// the density map is integrated over each zone.
+pub struct CrowdConfig {
+    /// nvinfer config of the density model (network-type=100, whole frame, no aspect padding)
+    pub model_config: String,
+    pub unique_id: u32,
+    /// Multiplier for the map sum, for models trained on scaled density maps
+    pub scale: f32,
+    /// Frames between crowd_count events per source
+    pub interval: u32,
+    /// Count above which a crowd_over_limit event fires
+    pub limit: Option<f32>,
+}
+
+impl CrowdConfig {
+    /// Reads DENSITY_CONFIG / DENSITY_UNIQUE_ID / DENSITY_SCALE / DENSITY_INTERVAL / DENSITY_LIMIT.
+    pub fn from_env() -> Option<CrowdConfig> {
+        let model_config = env::var("DENSITY_CONFIG").ok()?;
+        let unique_id = env::var("DENSITY_UNIQUE_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(2);
+        let scale = env::var("DENSITY_SCALE").ok().and_then(|v| v.parse().ok()).unwrap_or(1.0);
+        let interval = env::var("DENSITY_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(30);
+        let limit = env::var("DENSITY_LIMIT").ok().and_then(|v| v.parse().ok());
+        Some(CrowdConfig { model_config, unique_id, scale, interval, limit })
+    }
+
+    /// The unique-id set here overrides the model config so the tensor meta can be found.
+    pub fn stage(&self) -> String {
+        format!(
+            "nvinfer name=density config-file-path={} unique-id={} output-tensor-meta=true",
+            self.model_config, self.unique_id
+        )
+    }
+}
+
+struct CrowdCounter {
+    config: CrowdConfig,
+    zones: Vec<Zone>,
+    events: Events,
+    cells: Option<GridCells>,
+    // Zones over the limit per (source, zone)
+    over_limit: HashMap<(u32, usize), bool>,
+}
+
+impl CrowdCounter {
+    fn counts(&mut self, dims: &nvds::NvDsInferDims, map: &[f32]) -> Option<Vec<f32>> {
+        // CHW or HW; with several channels only the first one is used
+        let n = dims.numDims as usize;
+        if !(2..=nvds::NVDS_INFER_MAX_DIMS).contains(&n) {
+            return None;
+        }
+        let (rows, cols) = (dims.d[n - 2] as usize, dims.d[n - 1] as usize);
+        if rows * cols == 0 || map.len() < rows * cols {
+            return None;
+        }
+        let scale = self.config.scale;
+        Some(
+            GridCells::for_grid(&mut self.cells, &self.zones, rows, cols)
+                .iter()
+                .map(|zone| zone.iter().map(|&i| map[i]).sum::<f32>() * scale)
+                .collect(),
+        )
+    }
+
+    fn on_frame(&mut self, frame: &mut nvds::NvDsFrameMeta) {
+        let Some((dims, map)) = frame.tensor_meta(self.config.unique_id).and_then(|t| t.float_output(0)) else {
+            return;
+        };
+        let dims = *dims;
+        let Some(counts) = self.counts(&dims, map) else { return };
+        let (source_id, frame_num) = (frame.source_id, frame.frame_num);
+        let (width, height) = (frame.pipeline_width as f32, frame.pipeline_height as f32);
+
+        let per_meta = nvds::MAX_ELEMENTS_IN_DISPLAY_META;
+        for (zones, batch) in self.zones.chunks(per_meta).zip(counts.chunks(per_meta)) {
+            let Some(display) = frame.add_display_meta() else { break };
+            for ((zone, count), text) in zones.iter().zip(batch).zip(display.text_params.iter_mut()) {
+                let (x, y) = zone.origin();
+                let label = format!("{}: {:.0}", zone.name, count);
+                text.set(&label, (x * width) as u32 + 4, (y * height) as u32 + 4, 14);
+            }
+            display.num_labels = zones.len() as u32;
+        }
+
+        let mut events = self.events.lock().unwrap();
+        if (frame_num.max(0) as u32).is_multiple_of(self.config.interval) {
+            for (zone, count) in self.zones.iter().zip(&counts) {
+                events.emit(
+                    Event::new("crowd_count", source_id, frame_num)
+                        .with("zone", zone.name.as_str())
+                        .with("count", *count),
+                );
+            }
+        }
+        if let Some(limit) = self.config.limit {
+            for (index, (zone, count)) in self.zones.iter().zip(&counts).enumerate() {
+                let over = self.over_limit.entry((source_id, index)).or_default();
+                if *count > limit && !*over {
+                    events.emit(
+                        Event::new("crowd_over_limit", source_id, frame_num)
+                            .with("zone", zone.name.as_str())
+                            .with("count", *count)
+                            .with("limit", limit),
+                    );
+                }
+                *over = *count > limit;
+            }
+        }
+    }
+}
+
+/// Integrates the density map after the density nvinfer, draws the count per zone
+/// (the whole frame when no ZONES are set) and emits crowd events.
+pub fn attach(bin: &gstreamer::Element, config: CrowdConfig, zones: &[Zone], events: Events) {
+    let density = match bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("density")) {
+        Some(density) => density,
+        None => {
+            eprintln!("Warning: density nvinfer not found, crowd counting disabled");
+            return;
+        }
+    };
+    let src_pad = density.static_pad("src").expect("nvinfer has a src pad");
+    let zones = if zones.is_empty() { vec![Zone::full_frame()] } else { zones.to_vec() };
+    let counter = Mutex::new(CrowdCounter {
+        config,
+        zones,
+        events,
+        cells: None,
+        over_limit: HashMap::new(),
+    });
+
+    src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            let mut counter = counter.lock().unwrap();
+            for frame in batch.frames() {
+                counter.on_frame(frame);
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
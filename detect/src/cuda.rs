@@ -0,0 +1,329 @@
+// Custom CUDA kernel stage (cargo feature `cuda`). Kernels run directly on the NVMM
+// device surfaces, so frames never leave the GPU. cudarc loads libcuda and libnvrtc at
+// runtime; device buffers are CudaSlices, freed when dropped.
+
+use crate::nvbufsurface as nvbuf;
+use cudarc::driver::{CudaContext, CudaFunction, CudaModule, CudaSlice, CudaStream, LaunchConfig, PushKernelArg};
+use cudarc::nvrtc::{self, CompileError, CompileOptions};
+use gstreamer::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+fn check<T, E: Debug>(result: Result<T, E>, what: &str) -> Result<T, String> {
+    result.map_err(|e| format!("{} failed ({:?})", what, e))
+}
+
+/// Compiles `source` with NVRTC and loads it on GPU `gpu_id`'s primary context, with a
+/// stream of its own to launch on.
+fn load_module(source: &str, gpu_id: i32) -> Result<(Arc<CudaStream>, Arc<CudaModule>), String> {
+    let options = CompileOptions { use_fast_math: Some(true), ..Default::default() };
+    let ptx = match nvrtc::compile_ptx_with_opts(source, options) {
+        Ok(ptx) => ptx,
+        Err(CompileError::CompileError { log, .. }) => {
+            return Err(format!("kernel compilation failed:\n{}", log.to_string_lossy()))
+        }
+        Err(e) => return Err(format!("NVRTC failed ({:?})", e)),
+    };
+    // DeepStream runs on the primary context, which CudaContext retains, so surfaces stay addressable
+    let context = check(CudaContext::new(gpu_id.max(0) as usize), "CUDA context")?;
+    let module = check(context.load_module(ptx), "module load")?;
+    let stream = check(context.new_stream(), "stream creation")?;
+    Ok((stream, module))
+}
+
+/// A grid of BLOCK x BLOCK thread blocks covering `width` x `height` pixels.
+fn grid(width: u32, height: u32) -> LaunchConfig {
+    LaunchConfig {
+        grid_dim: (width.div_ceil(BLOCK), height.div_ceil(BLOCK), 1),
+        block_dim: (BLOCK, BLOCK, 1),
+        shared_mem_bytes: 0,
+    }
+}
+
+const HEATMAP_SOURCE: &str = r#"
+extern "C" __global__ void accumulate(float* heat, int width, int x0, int y0, int x1, int y1) {
+    int x = x0 + blockIdx.x * blockDim.x + threadIdx.x;
+    int y = y0 + blockIdx.y * blockDim.y + threadIdx.y;
+    if (x < x1 && y < y1) heat[y * width + x] += 1.0f;
+}
+
+extern "C" __global__ void decay_and_overlay(float* heat, unsigned char* rgba, int width, int height,
+                                             int pitch, float decay, float scale) {
+    int x = blockIdx.x * blockDim.x + threadIdx.x;
+    int y = blockIdx.y * blockDim.y + threadIdx.y;
+    if (x >= width || y >= height) return;
+    float h = heat[y * width + x] * decay;
+    heat[y * width + x] = h;
+    float a = fminf(h * scale, 0.6f);
+    unsigned char* p = rgba + y * pitch + x * 4;
+    p[0] = (unsigned char)(p[0] * (1.0f - a) + 255.0f * a);
+    p[1] = (unsigned char)(p[1] * (1.0f - a));
+    p[2] = (unsigned char)(p[2] * (1.0f - a));
+}
+"#;
+
+const BLOCK: u32 = 16;
+
+/// Detection heatmap: an exponentially decaying per-pixel count of boxes, blended
+/// in red over the frame. Each source has its own heat buffer, which stays on the GPU
+/// between frames.
+struct Heatmap {
+    stream: Arc<CudaStream>,
+    accumulate: CudaFunction,
+    overlay: CudaFunction,
+    /// Heat and frame size by source_id
+    heat: HashMap<u32, (CudaSlice<f32>, (u32, u32))>,
+    decay: f32,
+    scale: f32,
+}
+
+impl Heatmap {
+    fn new(gpu_id: i32) -> Result<Heatmap, String> {
+        let (stream, module) = load_module(HEATMAP_SOURCE, gpu_id)?;
+        let accumulate = check(module.load_function("accumulate"), "accumulate lookup")?;
+        let overlay = check(module.load_function("decay_and_overlay"), "decay_and_overlay lookup")?;
+        let decay = env::var("HEATMAP_DECAY").ok().and_then(|v| v.parse().ok()).unwrap_or(0.98);
+        let scale = env::var("HEATMAP_SCALE").ok().and_then(|v| v.parse().ok()).unwrap_or(0.05);
+        Ok(Heatmap { stream, accumulate, overlay, heat: HashMap::new(), decay, scale })
+    }
+
+    /// # Safety
+    /// `params` must describe an RGBA surface in CUDA device-accessible memory.
+    unsafe fn process(&mut self, params: &nvbuf::NvBufSurfaceParams, meta: &mut nvds::NvDsFrameMeta) -> Result<(), String> {
+        let (width, height) = (params.width, params.height);
+        let heat = match self.heat.remove(&meta.source_id) {
+            Some((heat, size)) if size == (width, height) => heat,
+            // New source or resolution change: start from a cold map
+            _ => check(self.stream.alloc_zeros::<f32>((width * height) as usize), "heat allocation")?,
+        };
+        let (heat, _) = self.heat.entry(meta.source_id).or_insert((heat, (width, height)));
+
+        let (w, h) = (width as i32, height as i32);
+        for obj in meta.objects() {
+            let rect = &obj.rect_params;
+            let x0 = (rect.left.max(0.0) as i32).min(w);
+            let y0 = (rect.top.max(0.0) as i32).min(h);
+            let x1 = ((rect.left + rect.width) as i32).clamp(0, w);
+            let y1 = ((rect.top + rect.height) as i32).clamp(0, h);
+            if x1 <= x0 || y1 <= y0 {
+                continue;
+            }
+            let mut launch = self.stream.launch_builder(&self.accumulate);
+            launch.arg(&mut *heat).arg(&w).arg(&x0).arg(&y0).arg(&x1).arg(&y1);
+            check(launch.launch(grid((x1 - x0) as u32, (y1 - y0) as u32)), "accumulate launch")?;
+        }
+
+        // The surface isn't a cudarc allocation; its device address goes in as a plain pointer argument
+        let rgba = params.data_ptr as u64;
+        let pitch = params.plane_params.pitch[0] as i32;
+        let mut launch = self.stream.launch_builder(&self.overlay);
+        launch.arg(&mut *heat).arg(&rgba).arg(&w).arg(&h).arg(&pitch).arg(&self.decay).arg(&self.scale);
+        check(launch.launch(grid(width, height)), "overlay launch")?;
+        // The buffer must not move downstream while the kernel is still writing it
+        check(self.stream.synchronize(), "stream synchronize")
+    }
+}
+
+const CUDA_ELEMENT: &str = "cuda_stage";
+
+pub struct CudaStage {
+    heatmap: Heatmap,
+}
+
+impl CudaStage {
+    /// Reads CUDA_STAGE (currently `heatmap`). Kernel compilation happens here so errors
+    /// show up before the pipeline starts.
+    pub fn from_env() -> Option<CudaStage> {
+        let name = env::var("CUDA_STAGE").ok()?;
+        if name != "heatmap" {
+            eprintln!("Warning: Unknown CUDA_STAGE '{}' (expected heatmap). CUDA stage disabled.", name);
+            return None;
+        }
+        if cfg!(target_arch = "aarch64") {
+            eprintln!("Warning: CUDA_STAGE needs dGPU device memory surfaces; not supported on Jetson yet.");
+            return None;
+        }
+        let gpu_id = env::var("GPU_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(0);
+        match Heatmap::new(gpu_id) {
+            Ok(heatmap) => Some(CudaStage { heatmap }),
+            Err(e) => {
+                eprintln!("Warning: CUDA stage disabled: {}", e);
+                None
+            }
+        }
+    }
+
+    /// RGBA surfaces in CUDA device memory, so kernels can address them directly.
+    pub fn stage() -> String {
+        crate::processing::surface_stage(CUDA_ELEMENT, nvbuf::NVBUF_MEM_CUDA_DEVICE)
+    }
+
+    pub fn attach(self, bin: &gstreamer::Element) {
+        let element = match bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name(CUDA_ELEMENT)) {
+            Some(element) => element,
+            None => {
+                eprintln!("Warning: CUDA stage element not found, CUDA stage disabled");
+                return;
+            }
+        };
+        let src_pad = element.static_pad("src").expect("identity has a src pad");
+        let heatmap = Mutex::new(self.heatmap);
+
+        src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+            if let Some((buffer, Some(batch))) = nvds::probe_buffer(info) {
+                let Ok(map) = buffer.map_readable() else {
+                    return gstreamer::PadProbeReturn::Ok;
+                };
+                let surface = map.as_ptr() as *const nvbuf::NvBufSurface;
+                let mut heatmap = heatmap.lock().unwrap();
+                for meta in batch.frames() {
+                    // SAFETY: the surface is the NVMM descriptor of this buffer; batch_id is bounds-checked
+                    unsafe {
+                        if meta.batch_id >= (*surface).num_filled {
+                            continue;
+                        }
+                        let params = &*(*surface).surface_list.add(meta.batch_id as usize);
+                        if params.color_format != nvbuf::NVBUF_COLOR_FORMAT_RGBA {
+                            continue;
+                        }
+                        if let Err(e) = heatmap.process(params, meta) {
+                            eprintln!("Warning: CUDA stage: {}", e);
+                        }
+                    }
+                }
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+    }
+}
+
+const DEBAYER_SOURCE: &str = r#"
+// nvvideoconvert turned the GRAY8 mosaic into gray RGBA, so any channel holds the raw value
+extern "C" __global__ void extract(const unsigned char* rgba, int pitch, unsigned char* raw, int width, int height) {
+    int x = blockIdx.x * blockDim.x + threadIdx.x;
+    int y = blockIdx.y * blockDim.y + threadIdx.y;
+    if (x < width && y < height) raw[y * width + x] = rgba[y * pitch + x * 4];
+}
+
+__device__ float at(const unsigned char* raw, int width, int height, int x, int y) {
+    x = min(max(x, 0), width - 1);
+    y = min(max(y, 0), height - 1);
+    return raw[y * width + x];
+}
+
+// Bilinear demosaicing; (rx, ry) is the red pixel's position in the 2x2 block
+extern "C" __global__ void demosaic(const unsigned char* raw, unsigned char* rgba, int width, int height,
+                                    int pitch, int rx, int ry) {
+    int x = blockIdx.x * blockDim.x + threadIdx.x;
+    int y = blockIdx.y * blockDim.y + threadIdx.y;
+    if (x >= width || y >= height) return;
+    float c = at(raw, width, height, x, y);
+    float cross = (at(raw, width, height, x - 1, y) + at(raw, width, height, x + 1, y) +
+                   at(raw, width, height, x, y - 1) + at(raw, width, height, x, y + 1)) * 0.25f;
+    float diag = (at(raw, width, height, x - 1, y - 1) + at(raw, width, height, x + 1, y - 1) +
+                  at(raw, width, height, x - 1, y + 1) + at(raw, width, height, x + 1, y + 1)) * 0.25f;
+    float horiz = (at(raw, width, height, x - 1, y) + at(raw, width, height, x + 1, y)) * 0.5f;
+    float vert = (at(raw, width, height, x, y - 1) + at(raw, width, height, x, y + 1)) * 0.5f;
+    bool red_row = (y & 1) == ry, red_col = (x & 1) == rx;
+    float r, g, b;
+    if (red_row && red_col) { r = c; g = cross; b = diag; }
+    else if (!red_row && !red_col) { r = diag; g = cross; b = c; }
+    else if (red_row) { r = horiz; g = c; b = vert; }
+    else { r = vert; g = c; b = horiz; }
+    unsigned char* p = rgba + y * pitch + x * 4;
+    p[0] = (unsigned char)r;
+    p[1] = (unsigned char)g;
+    p[2] = (unsigned char)b;
+    p[3] = 255;
+}
+"#;
+
+/// Rebuilds color from a raw Bayer mosaic that arrived on the GPU as gray RGBA. The raw
+/// plane is copied out first, since demosaicing reads neighbours the output overwrites.
+struct Debayer {
+    stream: Arc<CudaStream>,
+    extract: CudaFunction,
+    demosaic: CudaFunction,
+    /// Raw plane and the frame size it was allocated for
+    raw: Option<(CudaSlice<u8>, (u32, u32))>,
+    red: (i32, i32),
+}
+
+impl Debayer {
+    fn new(gpu_id: i32, red: (i32, i32)) -> Result<Debayer, String> {
+        let (stream, module) = load_module(DEBAYER_SOURCE, gpu_id)?;
+        let extract = check(module.load_function("extract"), "extract lookup")?;
+        let demosaic = check(module.load_function("demosaic"), "demosaic lookup")?;
+        Ok(Debayer { stream, extract, demosaic, raw: None, red })
+    }
+
+    /// # Safety
+    /// `params` must describe an RGBA surface in CUDA device memory.
+    unsafe fn process(&mut self, params: &nvbuf::NvBufSurfaceParams) -> Result<(), String> {
+        let (width, height) = (params.width, params.height);
+        let raw = match self.raw.take() {
+            Some((raw, size)) if size == (width, height) => raw,
+            // Uninitialized is fine: extract writes every pixel before demosaic reads it
+            _ => check(self.stream.alloc::<u8>((width * height) as usize), "raw allocation")?,
+        };
+        let (raw, _) = self.raw.insert((raw, (width, height)));
+
+        let rgba = params.data_ptr as u64;
+        let (w, h) = (width as i32, height as i32);
+        let pitch = params.plane_params.pitch[0] as i32;
+        let (rx, ry) = self.red;
+        let mut launch = self.stream.launch_builder(&self.extract);
+        launch.arg(&rgba).arg(&pitch).arg(&mut *raw).arg(&w).arg(&h);
+        check(launch.launch(grid(width, height)), "extract launch")?;
+        let mut launch = self.stream.launch_builder(&self.demosaic);
+        launch.arg(&*raw).arg(&rgba).arg(&w).arg(&h).arg(&pitch).arg(&rx).arg(&ry);
+        check(launch.launch(grid(width, height)), "demosaic launch")?;
+        check(self.stream.synchronize(), "stream synchronize")
+    }
+}
+
+/// Demosaics the Bayer inputs on the `debayer<N>` identities that BayerConfig::stage puts
+/// after each camera's nvvideoconvert. These sit before nvstreammux, so every buffer is a
+/// single surface without batch meta.
+pub fn attach_debayer(bin: &gstreamer::Element, sources: usize, bayer: &crate::source::BayerConfig) {
+    let gpu_id = env::var("GPU_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(0);
+    for index in 0..sources {
+        // Only camera inputs get a debayer stage
+        let name = format!("debayer{}", index);
+        let Some(element) = bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name(&name)) else {
+            continue;
+        };
+        let debayer = match Debayer::new(gpu_id, bayer.red_offset()) {
+            Ok(debayer) => Mutex::new(debayer),
+            Err(e) => {
+                eprintln!("Error: BAYER needs the CUDA debayer kernel: {}", e);
+                std::process::exit(2);
+            }
+        };
+        let src_pad = element.static_pad("src").expect("identity has a src pad");
+        src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+            if let Some(gstreamer::PadProbeData::Buffer(buffer)) = &info.data {
+                let Ok(map) = buffer.map_readable() else {
+                    return gstreamer::PadProbeReturn::Ok;
+                };
+                let surface = map.as_ptr() as *const nvbuf::NvBufSurface;
+                // SAFETY: the surface is the NVMM descriptor of this buffer; num_filled is checked
+                unsafe {
+                    if (*surface).num_filled == 0 {
+                        return gstreamer::PadProbeReturn::Ok;
+                    }
+                    let params = &*(*surface).surface_list;
+                    if params.color_format != nvbuf::NVBUF_COLOR_FORMAT_RGBA {
+                        return gstreamer::PadProbeReturn::Ok;
+                    }
+                    if let Err(e) = debayer.lock().unwrap().process(params) {
+                        eprintln!("Warning: Debayer: {}", e);
+                    }
+                }
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+    }
+}
@@ -0,0 +1,82 @@
+use std::env;
+
+/// Where a custom GPU stage is linked into the pipeline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StagePosition {
+    /// Between nvstreammux and nvinfer, e.g. for preprocessing
+    PreInfer,
+    /// After nvinfer (and nvtracker), with object metadata available
+    PostInfer,
+    /// After nvdsosd, seeing the annotated frames
+    PostOsd,
+}
+
+impl StagePosition {
+    fn parse(value: &str) -> Option<StagePosition> {
+        match value.trim().to_lowercase().as_str() {
+            "pre-infer" | "preinfer" => Some(StagePosition::PreInfer),
+            "post-infer" | "postinfer" => Some(StagePosition::PostInfer),
+            "post-osd" | "postosd" => Some(StagePosition::PostOsd),
+            _ => None,
+        }
+    }
+}
+
+/// A user-supplied DeepStream plugin hosted by this pipeline, e.g. nvdsvideotemplate
+/// with a proprietary custom library, or dsexample.
+pub struct CustomStage {
+    pub element: String,
+    pub position: StagePosition,
+}
+
+impl CustomStage {
+    /// Reads CUSTOM_STAGE (a full element description such as `dsexample full-frame=0`),
+    /// or CUSTOM_LIB plus `;`-separated `key:value` CUSTOM_PROPS for nvdsvideotemplate.
+    /// CUSTOM_POSITION picks the insertion point (pre-infer, post-infer, post-osd).
+    pub fn from_env() -> Option<CustomStage> {
+        let element = match (env::var("CUSTOM_STAGE"), env::var("CUSTOM_LIB")) {
+            (Ok(stage), _) if !stage.trim().is_empty() => stage.trim().to_string(),
+            (_, Ok(lib)) if !lib.trim().is_empty() => {
+                let mut element = format!("nvdsvideotemplate customlib-name={}", lib.trim());
+                // Each customlib-props assignment adds one key:value pair
+                for prop in env::var("CUSTOM_PROPS").unwrap_or_default().split(';').map(str::trim) {
+                    if prop.is_empty() {
+                        continue;
+                    }
+                    if !prop.contains(':') {
+                        eprintln!("Warning: Ignoring CUSTOM_PROPS entry '{}' (expected key:value)", prop);
+                        continue;
+                    }
+                    element.push_str(&format!(" customlib-props=\"{}\"", prop));
+                }
+                element
+            }
+            _ => return None,
+        };
+
+        let position = match env::var("CUSTOM_POSITION") {
+            Ok(value) => StagePosition::parse(&value).unwrap_or_else(|| {
+                eprintln!(
+                    "Warning: Unknown CUSTOM_POSITION '{}' (expected pre-infer, post-infer or post-osd), using post-infer",
+                    value
+                );
+                StagePosition::PostInfer
+            }),
+            Err(_) => StagePosition::PostInfer,
+        };
+        Some(CustomStage { element, position })
+    }
+
+    /// The stage linked in when it sits at `position`, with a trailing `! ` for chaining.
+    pub fn at(stage: &Option<CustomStage>, position: StagePosition) -> String {
+        match stage {
+            Some(stage) if stage.position == position => format!("{} ! ", stage.element),
+            _ => String::new(),
+        }
+    }
+
+    /// GStreamer element name, for checking the plugin is installed.
+    pub fn factory_name(&self) -> &str {
+        self.element.split_whitespace().next().unwrap_or_default()
+    }
+}
@@ -0,0 +1,283 @@
+use crate::detections::FrameDetections;
+use crate::export::input_stem;
+use std::env;
+use std::fs;
+use std::io;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatasetFormat {
+    /// images/ + labels/*.txt (class cx cy w h, normalized) + data.yaml
+    Yolo,
+    /// JPEGImages/ + Annotations/*.xml
+    Voc,
+}
+
+pub struct DatasetConfig {
+    pub format: DatasetFormat,
+    pub dir: String,
+    pub stem: String,
+    /// Keep one frame out of every `interval`
+    pub interval: u64,
+    pub skip_empty: bool,
+}
+
+impl DatasetConfig {
+    /// Reads DATASET_FORMAT (yolo|voc), DATASET_DIR, DATASET_INTERVAL and DATASET_SKIP_EMPTY.
+    /// Exits with several inputs: the image branch comes after the tiler, so its JPEGs would
+    /// be mosaics that no one source's labels line up with.
+    pub fn from_env(devices: &[String]) -> Option<DatasetConfig> {
+        let format_str = env::var("DATASET_FORMAT").ok()?;
+        if devices.len() > 1 {
+            eprintln!("Error: DATASET_FORMAT needs a single input, {} were given", devices.len());
+            std::process::exit(2);
+        }
+        let format = match format_str.trim().to_lowercase().as_str() {
+            "yolo" => DatasetFormat::Yolo,
+            "voc" => DatasetFormat::Voc,
+            other => {
+                eprintln!("Warning: Unknown DATASET_FORMAT '{}' (expected yolo or voc). Dataset output disabled.", other);
+                return None;
+            }
+        };
+        let dir = env::var("DATASET_DIR").unwrap_or_else(|_| "/dataset".to_string());
+        let interval = env::var("DATASET_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(30);
+        let skip_empty = env::var("DATASET_SKIP_EMPTY").map(|v| v == "true").unwrap_or(false);
+        let stem = input_stem(&devices[0]);
+
+        Some(DatasetConfig { format, dir, stem, interval, skip_empty })
+    }
+
+    fn dir(&self) -> String {
+        self.dir.trim_end_matches('/').to_string()
+    }
+
+    pub fn image_dir(&self) -> String {
+        match self.format {
+            DatasetFormat::Yolo => format!("{}/images", self.dir()),
+            DatasetFormat::Voc => format!("{}/JPEGImages", self.dir()),
+        }
+    }
+
+    pub fn label_dir(&self) -> String {
+        match self.format {
+            DatasetFormat::Yolo => format!("{}/labels", self.dir()),
+            DatasetFormat::Voc => format!("{}/Annotations", self.dir()),
+        }
+    }
+
+    /// Branch saving the sampled frames as JPEG; hangs off the pre-OSD tee so
+    /// training images don't contain boxes. The `dataset_q` src pad is where the
+    /// sampling probe goes; multifilesink numbers files in the same order.
+    pub fn image_branch(&self) -> String {
+        format!(
+            "queue name=dataset_q ! nvvideoconvert ! video/x-raw,format=I420 ! \
+             jpegenc quality=95 ! multifilesink location={}/{}_%06d.jpg",
+            self.image_dir(),
+            self.stem
+        )
+    }
+}
+
+pub struct DatasetWriter {
+    config: DatasetConfig,
+    labels: Vec<String>,
+    frames_seen: u64,
+    next_index: u64,
+}
+
+impl DatasetWriter {
+    pub fn new(config: DatasetConfig, labels: Vec<String>) -> io::Result<DatasetWriter> {
+        fs::create_dir_all(config.image_dir())?;
+        fs::create_dir_all(config.label_dir())?;
+
+        if config.format == DatasetFormat::Yolo {
+            fs::write(format!("{}/classes.txt", config.dir()), labels.join("\n") + "\n")?;
+            let names: Vec<String> = labels
+                .iter()
+                .enumerate()
+                .map(|(id, name)| format!("  {}: {}", id, name))
+                .collect();
+            fs::write(
+                format!("{}/data.yaml", config.dir()),
+                format!("path: {}\ntrain: images\nval: images\nnames:\n{}\n", config.dir(), names.join("\n")),
+            )?;
+        }
+
+        Ok(DatasetWriter { config, labels, frames_seen: 0, next_index: 0 })
+    }
+
+    /// Decides whether this frame is sampled and, if so, writes its label file.
+    /// Returns false when the buffer should be dropped from the image branch.
+    pub fn sample(&mut self, frame: &FrameDetections) -> bool {
+        let seen = self.frames_seen;
+        self.frames_seen += 1;
+        if !seen.is_multiple_of(self.config.interval) {
+            return false;
+        }
+        if self.config.skip_empty && frame.objects.is_empty() {
+            return false;
+        }
+
+        let name = format!("{}_{:06}", self.config.stem, self.next_index);
+        self.next_index += 1;
+
+        let result = match self.config.format {
+            DatasetFormat::Yolo => fs::write(format!("{}/{}.txt", self.config.label_dir(), name), self.yolo_labels(frame)),
+            DatasetFormat::Voc => fs::write(format!("{}/{}.xml", self.config.label_dir(), name), self.voc_annotation(frame, &name)),
+        };
+        if let Err(e) = result {
+            eprintln!("Warning: Failed to write dataset labels for {}: {}", name, e);
+        }
+        true
+    }
+
+    // Saved images are taken after nvstreammux, so boxes stay in pipeline coordinates
+    fn yolo_labels(&self, frame: &FrameDetections) -> String {
+        let (w, h) = (frame.pipeline_width.max(1) as f32, frame.pipeline_height.max(1) as f32);
+        let mut out = String::new();
+        for det in &frame.objects {
+            let cx = ((det.left + det.width / 2.0) / w).clamp(0.0, 1.0);
+            let cy = ((det.top + det.height / 2.0) / h).clamp(0.0, 1.0);
+            out.push_str(&format!(
+                "{} {:.6} {:.6} {:.6} {:.6}\n",
+                det.class_id,
+                cx,
+                cy,
+                (det.width / w).clamp(0.0, 1.0),
+                (det.height / h).clamp(0.0, 1.0)
+            ));
+        }
+        out
+    }
+
+    fn voc_annotation(&self, frame: &FrameDetections, name: &str) -> String {
+        let (w, h) = (frame.pipeline_width, frame.pipeline_height);
+        let mut out = format!(
+            "<annotation>\n  <folder>JPEGImages</folder>\n  <filename>{}.jpg</filename>\n  \
+             <size><width>{}</width><height>{}</height><depth>3</depth></size>\n  <segmented>0</segmented>\n",
+            name, w, h
+        );
+        for det in &frame.objects {
+            let class_name = self
+                .labels
+                .get(det.class_id as usize)
+                .cloned()
+                .unwrap_or_else(|| det.class_id.to_string());
+            let xmin = det.left.max(0.0).round() as u32;
+            let ymin = det.top.max(0.0).round() as u32;
+            let xmax = ((det.left + det.width).round() as u32).min(w);
+            let ymax = ((det.top + det.height).round() as u32).min(h);
+            out.push_str(&format!(
+                "  <object>\n    <name>{}</name>\n    <pose>Unspecified</pose>\n    <truncated>0</truncated>\n    \
+                 <difficult>0</difficult>\n    <bndbox><xmin>{}</xmin><ymin>{}</ymin><xmax>{}</xmax><ymax>{}</ymax></bndbox>\n  </object>\n",
+                xml_escape(&class_name),
+                xmin,
+                ymin,
+                xmax,
+                ymax
+            ));
+        }
+        out.push_str("</annotation>\n");
+        out
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detections::Detection;
+    use std::path::Path;
+
+    fn writer(name: &str, format: DatasetFormat, interval: u64, skip_empty: bool) -> DatasetWriter {
+        let dir = env::temp_dir().join(format!("detect-dataset-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        let dir = dir.to_string_lossy().into_owned();
+        let config = DatasetConfig { format, dir, stem: "clip".to_string(), interval, skip_empty };
+        DatasetWriter::new(config, vec!["person".to_string(), "a&b".to_string()]).unwrap()
+    }
+
+    /// A 100x50 pipeline frame with (class, left, top, width, height) boxes.
+    fn frame(boxes: &[(i32, f32, f32, f32, f32)]) -> FrameDetections {
+        FrameDetections {
+            source_id: 0,
+            frame_num: 0,
+            pts: 0,
+            source_width: 1920,
+            source_height: 1080,
+            pipeline_width: 100,
+            pipeline_height: 50,
+            objects: boxes
+                .iter()
+                .map(|&(class_id, left, top, width, height)| Detection {
+                    class_id,
+                    track_id: None,
+                    confidence: 0.9,
+                    left,
+                    top,
+                    width,
+                    height,
+                    keypoints: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn label_files(writer: &DatasetWriter) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(writer.config.label_dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn yolo_boxes_are_normalized_centers_clamped_to_the_image() {
+        let writer = writer("yolo", DatasetFormat::Yolo, 1, false);
+        let labels = writer.yolo_labels(&frame(&[(0, 10.0, 5.0, 20.0, 10.0), (1, -10.0, -10.0, 200.0, 20.0)]));
+        assert_eq!(labels, "0 0.200000 0.200000 0.200000 0.200000\n1 0.900000 0.000000 1.000000 0.400000\n");
+
+        let dir = writer.config.dir();
+        assert_eq!(fs::read_to_string(format!("{}/classes.txt", dir)).unwrap(), "person\na&b\n");
+        let yaml = fs::read_to_string(format!("{}/data.yaml", dir)).unwrap();
+        assert!(yaml.contains("train: images\n"));
+        assert!(yaml.ends_with("names:\n  0: person\n  1: a&b\n"));
+    }
+
+    #[test]
+    fn voc_annotations_have_the_image_size_and_clipped_corners() {
+        let writer = writer("voc", DatasetFormat::Voc, 1, false);
+        let xml = writer.voc_annotation(&frame(&[(1, -3.0, 4.4, 50.0, 60.0)]), "clip_000000");
+        assert!(xml.starts_with("<annotation>\n  <folder>JPEGImages</folder>\n  <filename>clip_000000.jpg</filename>\n"));
+        assert!(xml.contains("<size><width>100</width><height>50</height><depth>3</depth></size>"));
+        assert!(xml.contains("<name>a&amp;b</name>"));
+        assert!(xml.contains("<bndbox><xmin>0</xmin><ymin>4</ymin><xmax>47</xmax><ymax>50</ymax></bndbox>"));
+        assert!(xml.ends_with("</annotation>\n"));
+        assert!(!Path::new(&format!("{}/classes.txt", writer.config.dir())).exists());
+    }
+
+    #[test]
+    fn every_nth_frame_is_kept_and_numbered_in_order() {
+        let mut writer = writer("interval", DatasetFormat::Yolo, 3, false);
+        let kept: Vec<bool> = (0..7).map(|_| writer.sample(&frame(&[]))).collect();
+        assert_eq!(kept, [true, false, false, true, false, false, true]);
+        assert_eq!(label_files(&writer), ["clip_000000.txt", "clip_000001.txt", "clip_000002.txt"]);
+    }
+
+    #[test]
+    fn empty_frames_can_be_skipped_without_a_gap_in_the_numbering() {
+        let mut writer = writer("skip", DatasetFormat::Voc, 1, true);
+        let person = frame(&[(0, 10.0, 5.0, 20.0, 10.0)]);
+        let kept: Vec<bool> = [&person, &frame(&[]), &person].iter().map(|f| writer.sample(f)).collect();
+        assert_eq!(kept, [true, false, true]);
+        assert_eq!(label_files(&writer), ["clip_000000.xml", "clip_000001.xml"]);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::encoders;
+use crate::source;
+use std::env;
+
+/// 1080p30 H.264 streams one NVDEC engine keeps up with, for the capacity estimate
+const STREAMS_PER_ENGINE: usize = 16;
+
+/// NVDEC engines of the data center GPUs DeepStream is usually deployed on, by model name
+const ENGINES: [(&str, usize); 7] =
+    [("H100", 7), ("A100", 5), ("A30", 4), ("L4", 4), ("L40", 3), ("L40S", 3), ("T4", 2)];
+
+/// What happens to inputs beyond the GPU's NVDEC capacity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Decode them on the CPU and upload the frames
+    Software,
+    /// Leave them out of the run
+    Reject,
+    /// Decode them on NVDEC anyway, which slows every stream down
+    Warn,
+}
+
+/// How one input is decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decode {
+    Nvdec,
+    Software,
+    Rejected,
+    /// Cameras, NDI and the test pattern arrive raw
+    Raw,
+}
+
+/// NVDEC capacity (DECODER_MAX_SESSIONS or estimated from the GPU) and the overflow policy.
+pub struct DecoderConfig {
+    /// None when the capacity isn't known (Jetson, GPUs not in the table)
+    pub max_sessions: Option<usize>,
+    /// Where the capacity came from, for the startup summary
+    pub source: String,
+    pub overflow: Overflow,
+}
+
+/// Whether `device` is compressed video that nvurisrcbin decodes on NVDEC.
+pub fn uses_nvdec(device: &str) -> bool {
+    source::is_stream(device) || [".mp4", ".avi", ".mkv"].iter().any(|ext| device.ends_with(ext))
+}
+
+/// Estimated concurrent streams for a GPU model; None for models not in ENGINES.
+fn capacity(name: &str) -> Option<usize> {
+    let words: Vec<&str> = name.split([' ', '-']).collect();
+    let (_, engines) = ENGINES.iter().find(|(model, _)| words.contains(model))?;
+    Some(engines * STREAMS_PER_ENGINE)
+}
+
+impl DecoderConfig {
+    /// Reads DECODER_MAX_SESSIONS (0 for no limit) and DECODER_OVERFLOW (software, reject or
+    /// warn); without DECODER_MAX_SESSIONS the capacity is estimated from GPU_ID's model.
+    pub fn from_env() -> DecoderConfig {
+        let overflow = match env::var("DECODER_OVERFLOW").as_deref() {
+            Err(_) | Ok("software") => Overflow::Software,
+            Ok("reject") => Overflow::Reject,
+            Ok("warn") => Overflow::Warn,
+            Ok(other) => {
+                eprintln!("Error: Unknown DECODER_OVERFLOW '{}' (expected software, reject or warn)", other);
+                std::process::exit(2);
+            }
+        };
+        if let Ok(value) = env::var("DECODER_MAX_SESSIONS") {
+            let Ok(max) = value.trim().parse::<usize>() else {
+                eprintln!("Error: DECODER_MAX_SESSIONS must be a number of streams, got '{}'", value);
+                std::process::exit(2);
+            };
+            let max_sessions = Some(max).filter(|n| *n > 0);
+            return DecoderConfig { max_sessions, source: "DECODER_MAX_SESSIONS".to_string(), overflow };
+        }
+        if cfg!(target_arch = "aarch64") {
+            return DecoderConfig { max_sessions: None, source: "Jetson".to_string(), overflow };
+        }
+        match encoders::query_gpu() {
+            Some((name, _)) => DecoderConfig { max_sessions: capacity(&name), source: name, overflow },
+            None => DecoderConfig { max_sessions: None, source: "GPU not queried".to_string(), overflow },
+        }
+    }
+
+    /// Decides how each input is decoded: NVDEC in input order until the capacity is used up,
+    /// then the overflow policy, with a warning per input over the limit.
+    pub fn assign(&self, devices: &[String], describe: impl Fn(&str) -> String) -> Vec<Decode> {
+        let mut left = self.max_sessions;
+        devices
+            .iter()
+            .enumerate()
+            .map(|(index, device)| {
+                if !uses_nvdec(device) {
+                    return Decode::Raw;
+                }
+                match &mut left {
+                    None => return Decode::Nvdec,
+                    Some(0) => {}
+                    Some(n) => {
+                        *n -= 1;
+                        return Decode::Nvdec;
+                    }
+                }
+                let max = self.max_sessions.unwrap_or_default();
+                let (decode, outcome) = match self.overflow {
+                    Overflow::Software => (Decode::Software, "decoding it on the CPU"),
+                    Overflow::Reject => (Decode::Rejected, "leaving it out"),
+                    Overflow::Warn => (Decode::Nvdec, "decoding it on NVDEC anyway, expect dropped frames"),
+                };
+                eprintln!(
+                    "Warning: Input {} ({}) is over the NVDEC capacity of {} streams ({}); {}",
+                    index,
+                    describe(device),
+                    max,
+                    self.source,
+                    outcome
+                );
+                decode
+            })
+            .collect()
+    }
+
+    pub fn describe(&self, decodes: &[Decode]) -> String {
+        let count = |decode| decodes.iter().filter(|d| **d == decode).count();
+        let capacity = self.max_sessions.map_or("capacity unknown".to_string(), |max| format!("about {} streams", max));
+        let mut out = format!("{} NVDEC", count(Decode::Nvdec));
+        if count(Decode::Software) > 0 {
+            out.push_str(&format!(", {} software", count(Decode::Software)));
+        }
+        if count(Decode::Rejected) > 0 {
+            out.push_str(&format!(", {} rejected", count(Decode::Rejected)));
+        }
+        format!("{} ({}: {})", out, self.source, capacity)
+    }
+}
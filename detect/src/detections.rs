@@ -0,0 +1,105 @@
+/// One detected object, copied out of NvDsObjectMeta so it can outlive the probe.
+#[derive(Clone, Debug)]
+pub struct Detection {
+    pub class_id: i32,
+    pub track_id: Option<u64>,
+    pub confidence: f32,
+    // Bounding box in nvstreammux (pipeline) coordinates
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Pose keypoints as (x, y, confidence) in pipeline coordinates; empty for plain detectors
+    pub keypoints: Vec<[f32; 3]>,
+}
+
+/// All detections for one source frame of a batch.
+#[derive(Clone, Debug)]
+pub struct FrameDetections {
+    pub source_id: u32,
+    pub frame_num: i32,
+    /// Buffer PTS in nanoseconds
+    pub pts: u64,
+    pub source_width: u32,
+    pub source_height: u32,
+    pub pipeline_width: u32,
+    pub pipeline_height: u32,
+    pub objects: Vec<Detection>,
+}
+
+impl FrameDetections {
+    /// Stream time of the frame; falls back to frame count at 30 fps when buffers carry no timestamps.
+    pub fn seconds(&self) -> f64 {
+        pts_seconds(self.pts, self.frame_num)
+    }
+
+    /// Maps a box from muxer coordinates back onto the original source frame.
+    /// nvstreammux stretches without padding, so each axis scales independently.
+    pub fn to_source_coords(&self, det: &Detection) -> (f32, f32, f32, f32) {
+        if self.pipeline_width == 0 || self.pipeline_height == 0 || self.source_width == 0 || self.source_height == 0 {
+            return (det.left, det.top, det.width, det.height);
+        }
+        let sx = self.source_width as f32 / self.pipeline_width as f32;
+        let sy = self.source_height as f32 / self.pipeline_height as f32;
+        (det.left * sx, det.top * sy, det.width * sx, det.height * sy)
+    }
+}
+
+/// Consumer of per-frame detections, fed from the pad probe on the OSD sink pad.
+pub trait FrameObserver: Send {
+    fn on_frame(&mut self, frame: &FrameDetections);
+
+    /// Called once at shutdown (EOS, error or Ctrl+C) to flush outputs.
+    fn finish(&mut self) {}
+}
+
+/// Stream time of a frame in seconds; assumes 30 fps when the buffer has no timestamp.
+pub fn pts_seconds(pts: u64, frame_num: i32) -> f64 {
+    // GStreamer marks missing timestamps with u64::MAX
+    if pts == u64::MAX {
+        frame_num as f64 / 30.0
+    } else {
+        pts as f64 / 1e9
+    }
+}
+
+/// Copies the object metadata of every frame in the batch.
+/// `mux_width`/`mux_height` are used when the frame meta doesn't carry the pipeline size.
+pub fn collect(batch: &mut nvds::BatchMeta, mux_width: u32, mux_height: u32) -> Vec<FrameDetections> {
+    batch
+        .frames()
+        .map(|frame| {
+            let pipeline_width = if frame.pipeline_width > 0 { frame.pipeline_width } else { mux_width };
+            let pipeline_height = if frame.pipeline_height > 0 { frame.pipeline_height } else { mux_height };
+            let (source_id, frame_num, pts) = (frame.source_id, frame.frame_num, frame.buf_pts);
+            let (source_width, source_height) = (frame.source_frame_width, frame.source_frame_height);
+
+            // Objects of secondary detectors (faces, plates, ...) hang off a primary object
+            let objects = frame
+                .objects()
+                .filter(|obj| obj.parent.is_null())
+                .map(|obj| Detection {
+                    class_id: obj.class_id,
+                    track_id: obj.track_id(),
+                    confidence: obj.confidence,
+                    left: obj.rect_params.left,
+                    top: obj.rect_params.top,
+                    width: obj.rect_params.width,
+                    height: obj.rect_params.height,
+                    keypoints: obj.keypoints().chunks_exact(3).map(|k| [k[0], k[1], k[2]]).collect(),
+                })
+                .collect();
+
+            FrameDetections {
+                source_id,
+                frame_num,
+                pts,
+                source_width,
+                source_height,
+                pipeline_width,
+                pipeline_height,
+                objects,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,203 @@
+use crate::negotiation;
+use crate::png;
+use crate::source::redact_credentials;
+use gstreamer::prelude::*;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Environment variables whose names contain one of these have their values masked
+const SECRET_NAMES: &[&str] = &["PASSWORD", "PASS", "TOKEN", "SECRET", "KEY", "CREDENTIAL"];
+
+/// Crash diagnostics: the most recent bus messages are kept in memory, and when the
+/// pipeline fails a zip with everything needed for a bug report is written.
+pub struct Diagnostics {
+    dir: String,
+    capacity: usize,
+    started: Instant,
+    messages: VecDeque<String>,
+}
+
+impl Diagnostics {
+    /// Reads DIAGNOSTICS_DIR / DIAGNOSTICS_MESSAGES (bus messages kept, default 500).
+    pub fn from_env() -> Option<Diagnostics> {
+        let dir = env::var("DIAGNOSTICS_DIR").ok()?;
+        let capacity = env::var("DIAGNOSTICS_MESSAGES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(500);
+        Some(Diagnostics { dir, capacity, started: Instant::now(), messages: VecDeque::new() })
+    }
+
+    pub fn dir(&self) -> &str {
+        &self.dir
+    }
+
+    /// Remembers one bus message, dropping the oldest once the buffer is full.
+    pub fn record(&mut self, msg: &gstreamer::Message) {
+        use gstreamer::MessageView;
+
+        let source = msg.src().map(|s| s.path_string().to_string()).unwrap_or_else(|| "-".to_string());
+        let details = match msg.view() {
+            MessageView::Error(err) => format!("{} ({:?})", err.error(), err.debug()),
+            MessageView::Warning(warning) => format!("{} ({:?})", warning.error(), warning.debug()),
+            MessageView::Info(info) => format!("{} ({:?})", info.error(), info.debug()),
+            MessageView::StateChanged(changed) => format!("{:?} -> {:?}", changed.old(), changed.current()),
+            _ => msg.structure().map(|s| s.to_string()).unwrap_or_default(),
+        };
+        let line = format!(
+            "[{:>10.3}s] {:?} from {}: {}",
+            self.started.elapsed().as_secs_f64(),
+            msg.type_(),
+            source,
+            details
+        );
+        if self.messages.len() == self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(redact_credentials(&line));
+    }
+
+    /// Writes detect_diagnostics_<unix time>.zip with the bus messages, the pipeline
+    /// graph, element states, negotiated caps, configuration files and environment.
+    pub fn write_bundle(&self, pipeline: &gstreamer::Element, reason: &str) -> io::Result<String> {
+        fs::create_dir_all(&self.dir)?;
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let path = format!("{}/detect_diagnostics_{}.zip", self.dir.trim_end_matches('/'), secs);
+
+        let mut files: Vec<(String, Vec<u8>)> = vec![
+            ("reason.txt".to_string(), format!("{}\n", redact_credentials(reason)).into_bytes()),
+            ("bus_messages.log".to_string(), self.messages.iter().map(|m| format!("{}\n", m)).collect::<String>().into_bytes()),
+        ];
+        if let Some(bin) = pipeline.downcast_ref::<gstreamer::Bin>() {
+            let dot = gstreamer::debug_bin_to_dot_data(bin, gstreamer::DebugGraphDetails::all());
+            files.push(("pipeline.dot".to_string(), redact_credentials(&dot).into_bytes()));
+            let (states, caps) = element_report(bin);
+            files.push(("element_states.txt".to_string(), states.into_bytes()));
+            files.push(("pad_caps.txt".to_string(), caps.into_bytes()));
+            files.push(("negotiation.txt".to_string(), negotiation::explain_pipeline(bin).into_bytes()));
+        }
+        files.push(("environment.txt".to_string(), environment().into_bytes()));
+        for (name, config) in config_files() {
+            files.push((format!("config/{}", name), config));
+        }
+
+        fs::write(&path, zip(&files))?;
+        Ok(path)
+    }
+}
+
+/// Current/pending state of every element, and the caps negotiated on every linked pad.
+fn element_report(bin: &gstreamer::Bin) -> (String, String) {
+    let mut states = String::new();
+    let mut caps = String::new();
+    let mut elements: Vec<gstreamer::Element> = bin.iterate_recurse().into_iter().flatten().collect();
+    elements.sort_by_key(|e| e.path_string().to_string());
+    for element in elements {
+        let path = element.path_string();
+        let factory = element.factory().map(|f| f.name().to_string()).unwrap_or_default();
+        states.push_str(&format!(
+            "{} ({}): {:?} (pending {:?})\n",
+            path,
+            factory,
+            element.current_state(),
+            element.pending_state()
+        ));
+        for pad in element.pads() {
+            let peer = pad.peer().map(|p| p.path_string().to_string()).unwrap_or_else(|| "unlinked".to_string());
+            let negotiated = pad.current_caps().map(|c| c.to_string()).unwrap_or_else(|| "not negotiated".to_string());
+            caps.push_str(&format!("{}:{} -> {}\n    {}\n", path, pad.name(), peer, negotiated));
+        }
+    }
+    (states, caps)
+}
+
+/// All environment variables, sorted, with credentials in URLs and secret-looking values masked.
+fn environment() -> String {
+    let mut vars: Vec<(String, String)> = env::vars().collect();
+    vars.sort();
+    vars.iter()
+        .map(|(key, value)| {
+            let upper = key.to_uppercase();
+            // APP_CONFIG_JSON holds every setting, secrets included
+            if SECRET_NAMES.iter().any(|s| upper.contains(s)) || key == "APP_CONFIG_JSON" {
+                format!("{}=***\n", key)
+            } else {
+                format!("{}={}\n", key, redact_credentials(value))
+            }
+        })
+        .collect()
+}
+
+/// Files referenced from *_CONFIG / *_FILE variables (model, tracker, DETECT_CONFIG, ...).
+fn config_files() -> Vec<(String, Vec<u8>)> {
+    let mut files = Vec::new();
+    let mut vars: Vec<(String, String)> = env::vars().filter(|(k, _)| k.ends_with("_CONFIG") || k.ends_with("_FILE")).collect();
+    vars.sort();
+    for (key, value) in vars {
+        let path = Path::new(&value);
+        if !path.is_file() {
+            continue;
+        }
+        if let Ok(contents) = fs::read(path) {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            files.push((format!("{}_{}", key, name), contents));
+        }
+    }
+    files
+}
+
+/// Minimal zip writer; entries are stored uncompressed, so no compression library is needed.
+fn zip(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in files {
+        let offset = out.len() as u32;
+        let crc = png::crc32(data);
+        let size = data.len() as u32;
+        // Version 2.0, no flags, stored, dated 1980-01-01 00:00 (DOS time, then date)
+        let common = |out: &mut Vec<u8>| {
+            out.extend_from_slice(&20u16.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(&0x0021u16.to_le_bytes());
+            out.extend_from_slice(&crc.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+        };
+
+        out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        common(&mut out);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes());
+        common(&mut central);
+        // Comment length, disk number, internal/external attributes, local header offset
+        central.extend_from_slice(&0u16.to_le_bytes());
+        central.extend_from_slice(&0u16.to_le_bytes());
+        central.extend_from_slice(&0u16.to_le_bytes());
+        central.extend_from_slice(&0u32.to_le_bytes());
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&central_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out
+}
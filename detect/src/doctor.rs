@@ -0,0 +1,232 @@
+use crate::custom::CustomStage;
+use crate::sinks::{sinks_from_env, SinkKind};
+use std::env;
+use std::fs;
+use std::net::{TcpListener, UdpSocket};
+use std::path::{Path, PathBuf};
+
+const DEEPSTREAM_DIR: &str = "/opt/nvidia/deepstream/deepstream";
+
+/// Collects pass/fail lines so the summary can count them.
+struct Report {
+    failures: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn pass(&mut self, what: &str) {
+        println!("  ✓ {}", what);
+    }
+
+    fn fail(&mut self, what: &str) {
+        println!("  ✗ {}", what);
+        self.failures += 1;
+    }
+
+    fn warn(&mut self, what: &str) {
+        println!("  ! {}", what);
+        self.warnings += 1;
+    }
+
+    fn check(&mut self, ok: bool, what: &str) {
+        if ok {
+            self.pass(what)
+        } else {
+            self.fail(what)
+        }
+    }
+}
+
+/// Reads the `[property]` keys of an nvinfer config file as `(key, value)` pairs.
+pub fn infer_properties(contents: &str) -> Vec<(String, String)> {
+    let mut in_property = false;
+    let mut out = Vec::new();
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_property = line == "[property]";
+        } else if in_property && !line.starts_with('#') {
+            if let Some((key, value)) = line.split_once('=') {
+                out.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+    }
+    out
+}
+
+/// nvinfer resolves relative paths against the config file's directory.
+pub fn resolve(config_path: &str, value: &str) -> PathBuf {
+    let path = Path::new(value);
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    Path::new(config_path).parent().unwrap_or(Path::new(".")).join(path)
+}
+
+fn count_labels(path: &Path) -> Option<usize> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|contents| contents.lines().filter(|l| !l.trim().is_empty()).count())
+}
+
+/// First line of the installed DeepStream's version file, e.g. "Version: 7.1".
+pub fn deepstream_version() -> Option<String> {
+    fs::read_to_string(format!("{}/version", DEEPSTREAM_DIR))
+        .ok()
+        .map(|version| version.lines().next().unwrap_or("").trim().to_string())
+}
+
+fn check_install(report: &mut Report) {
+    println!("Installation");
+    match gstreamer::init() {
+        Ok(()) => report.pass(&format!("GStreamer {}", gstreamer::version_string())),
+        Err(e) => {
+            report.fail(&format!("GStreamer failed to initialize: {}", e));
+            return;
+        }
+    }
+
+    match deepstream_version() {
+        Some(version) => report.pass(&format!("DeepStream: {}", version)),
+        None => report.fail(&format!("DeepStream not found in {}", DEEPSTREAM_DIR)),
+    }
+
+    match fs::read_to_string("/proc/driver/nvidia/version") {
+        Ok(version) => report.pass(&format!("NVIDIA driver: {}", version.lines().next().unwrap_or("").trim())),
+        Err(_) => report.fail("NVIDIA driver not loaded (/proc/driver/nvidia/version missing; is the container run with --gpus?)"),
+    }
+}
+
+fn check_plugins(report: &mut Report) {
+    println!("Plugins");
+    let mut required = vec![
+        "nvurisrcbin",
+        "nvstreammux",
+        "nvinfer",
+        "nvvideoconvert",
+        "nvdsosd",
+        "nvv4l2h264enc",
+        "h264parse",
+        "mp4mux",
+        "rtph264pay",
+        "udpsink",
+        "udpsrc",
+        "tee",
+        "queue",
+    ];
+    if env::var("TRACKER_CONFIG").is_ok() {
+        required.push("nvtracker");
+    }
+    let custom_stage = CustomStage::from_env();
+    if let Some(stage) = &custom_stage {
+        required.push(stage.factory_name());
+    }
+    for name in required {
+        if gstreamer::ElementFactory::find(name).is_some() {
+            report.pass(name);
+        } else {
+            report.fail(&format!("{} missing", name));
+        }
+    }
+}
+
+fn check_model(report: &mut Report, model_config: &str, labels_path: &str) {
+    println!("Model");
+    let contents = match fs::read_to_string(model_config) {
+        Ok(contents) => {
+            report.pass(&format!("Config {}", model_config));
+            contents
+        }
+        Err(e) => {
+            report.fail(&format!("Config {}: {}", model_config, e));
+            return;
+        }
+    };
+    let properties = infer_properties(&contents);
+    let get = |key: &str| properties.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+
+    // An engine is built from the ONNX file on first run, so either one is enough
+    let engine = get("model-engine-file").map(|v| resolve(model_config, v));
+    let onnx = get("onnx-file").map(|v| resolve(model_config, v));
+    match (&engine, &onnx) {
+        (Some(engine), _) if engine.exists() => report.pass(&format!("Engine {}", engine.display())),
+        (_, Some(onnx)) if onnx.exists() => {
+            report.pass(&format!("ONNX {}", onnx.display()));
+            if let Some(engine) = &engine {
+                report.warn(&format!("Engine {} not built yet; the first run will build it", engine.display()));
+            }
+        }
+        _ => report.fail("Neither model-engine-file nor onnx-file exists"),
+    }
+    if let Some(lib) = get("custom-lib-path") {
+        let lib = resolve(model_config, lib);
+        report.check(lib.exists(), &format!("Custom parser {}", lib.display()));
+    }
+
+    let num_classes = get("num-detected-classes").and_then(|v| v.parse::<usize>().ok());
+    let config_labels = get("labelfile-path").map(|v| resolve(model_config, v));
+    let mut label_files = vec![PathBuf::from(labels_path)];
+    if let Some(path) = config_labels {
+        if path != Path::new(labels_path) {
+            label_files.push(path);
+        }
+    }
+    for path in label_files {
+        match count_labels(&path) {
+            Some(count) => match num_classes {
+                Some(n) if n != count => report.fail(&format!(
+                    "{} has {} labels but num-detected-classes={}",
+                    path.display(),
+                    count,
+                    n
+                )),
+                _ => report.pass(&format!("Labels {} ({} classes)", path.display(), count)),
+            },
+            None => report.fail(&format!("Labels {} not readable", path.display())),
+        }
+    }
+}
+
+fn check_ports(report: &mut Report) {
+    println!("Network");
+    let sinks = match sinks_from_env() {
+        Ok(sinks) => sinks,
+        Err(e) => {
+            report.fail(&format!("Sinks: {}", e));
+            return;
+        }
+    };
+    let rtsp_sinks: Vec<_> = sinks.iter().filter(|s| s.kind == SinkKind::Rtsp).collect();
+    if rtsp_sinks.is_empty() {
+        report.pass("No RTSP output configured");
+    }
+    for sink in rtsp_sinks {
+        let port = sink.rtsp_port();
+        match TcpListener::bind(format!("0.0.0.0:{}", port)) {
+            Ok(_) => report.pass(&format!("RTSP port {} is free", port)),
+            Err(e) => report.fail(&format!("RTSP port {}: {}", port, e)),
+        }
+        let udp_port = sink.rtsp_udp_port();
+        match UdpSocket::bind(format!("127.0.0.1:{}", udp_port)) {
+            Ok(_) => report.pass(&format!("Loopback RTP port {} is free", udp_port)),
+            Err(e) => report.fail(&format!("Loopback RTP port {}: {}", udp_port, e)),
+        }
+    }
+}
+
+/// `detect doctor`: checks the environment and exits non-zero if anything required is missing.
+pub fn run(model_config: &str, labels_path: &str) -> ! {
+    let mut report = Report { failures: 0, warnings: 0 };
+    println!("DeepStream Detect - self test\n");
+    check_install(&mut report);
+    check_plugins(&mut report);
+    check_model(&mut report, model_config, labels_path);
+    check_ports(&mut report);
+
+    println!();
+    if report.failures == 0 {
+        println!("✓ All checks passed ({} warnings)", report.warnings);
+        std::process::exit(0);
+    }
+    println!("✗ {} checks failed, {} warnings", report.failures, report.warnings);
+    std::process::exit(1);
+}
@@ -0,0 +1,171 @@
+use crate::metrics::Metrics;
+use gstreamer::prelude::*;
+use std::env;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// What happens to encodes beyond the GPU's NVENC session limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Encode them on the CPU with x264enc/x265enc
+    Software,
+    /// Refuse to start, naming the outputs over the limit
+    Fail,
+}
+
+/// NVENC session budget (ENCODER_MAX_SESSIONS or detected from the GPU) and the overflow policy.
+pub struct EncoderConfig {
+    /// None when the GPU doesn't cap sessions (data center, workstation and Jetson GPUs)
+    pub max_sessions: Option<usize>,
+    /// Where the limit came from, for the startup summary
+    pub source: String,
+    pub overflow: Overflow,
+}
+
+/// Name and driver version of GPU_ID from nvidia-smi; None without a driver.
+pub fn query_gpu() -> Option<(String, String)> {
+    let gpu = env::var("GPU_ID").unwrap_or_else(|_| "0".to_string());
+    let output = Command::new("nvidia-smi")
+        .args(["-i", &gpu, "--query-gpu=name,driver_version", "--format=csv,noheader"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (name, driver) = stdout.lines().next()?.split_once(',')?;
+    Some((name.trim().to_string(), driver.trim().to_string()))
+}
+
+/// NVENC sessions GeForce drivers allow at once: raised from 3 to 5 in driver 530 and to 8 in 551.
+fn consumer_limit(name: &str, driver: &str) -> Option<usize> {
+    if !name.contains("GeForce") && !name.contains("TITAN") {
+        return None;
+    }
+    let major: u32 = driver.split('.').next()?.trim().parse().ok()?;
+    Some(match major {
+        551.. => 8,
+        530.. => 5,
+        _ => 3,
+    })
+}
+
+impl EncoderConfig {
+    /// Reads ENCODER_MAX_SESSIONS (0 for no limit) and ENCODER_OVERFLOW (software or fail);
+    /// without ENCODER_MAX_SESSIONS the limit is looked up with nvidia-smi for GPU_ID.
+    pub fn from_env() -> EncoderConfig {
+        let overflow = match env::var("ENCODER_OVERFLOW").as_deref() {
+            Err(_) | Ok("software") => Overflow::Software,
+            Ok("fail") => Overflow::Fail,
+            Ok(other) => {
+                eprintln!("Error: Unknown ENCODER_OVERFLOW '{}' (expected software or fail)", other);
+                std::process::exit(2);
+            }
+        };
+        if let Ok(value) = env::var("ENCODER_MAX_SESSIONS") {
+            let Ok(max) = value.trim().parse::<usize>() else {
+                eprintln!("Error: ENCODER_MAX_SESSIONS must be a number of sessions, got '{}'", value);
+                std::process::exit(2);
+            };
+            let max_sessions = Some(max).filter(|n| *n > 0);
+            return EncoderConfig { max_sessions, source: "ENCODER_MAX_SESSIONS".to_string(), overflow };
+        }
+        // Jetson's encoder engine has no per-process session cap
+        if cfg!(target_arch = "aarch64") {
+            return EncoderConfig { max_sessions: None, source: "Jetson".to_string(), overflow };
+        }
+        match query_gpu() {
+            Some((name, driver)) => EncoderConfig {
+                max_sessions: consumer_limit(&name, &driver),
+                source: format!("{}, driver {}", name, driver),
+                overflow,
+            },
+            None => EncoderConfig { max_sessions: None, source: "GPU not queried".to_string(), overflow },
+        }
+    }
+
+    /// Hands out NVENC sessions to `outputs` in order and returns, for each, whether it gets
+    /// one. Outputs that asked for `encoder=software` (`Some(false)`) don't use a session and
+    /// `encoder=hardware` ones (`Some(true)`) always get one; the rest share what is left.
+    /// Exits when the overflow policy is `fail` and anything is over the limit.
+    pub fn assign(&self, outputs: &[(String, Option<bool>)]) -> Vec<bool> {
+        let forced = outputs.iter().filter(|(_, choice)| *choice == Some(true)).count();
+        let mut left = self.max_sessions.map(|max| max.saturating_sub(forced));
+        let mut over = Vec::new();
+        let hardware: Vec<bool> = outputs
+            .iter()
+            .map(|(name, choice)| match (choice, &mut left) {
+                (Some(choice), _) => *choice,
+                (None, None) => true,
+                (None, Some(0)) => {
+                    over.push(name.clone());
+                    false
+                }
+                (None, Some(n)) => {
+                    *n -= 1;
+                    true
+                }
+            })
+            .collect();
+        if !over.is_empty() {
+            let max = self.max_sessions.unwrap_or_default();
+            if self.overflow == Overflow::Fail {
+                eprintln!(
+                    "Error: {} encodes need NVENC but {} allows {} sessions; over the limit: {} \
+                     (ENCODER_OVERFLOW=software encodes them on the CPU)",
+                    outputs.len(),
+                    self.source,
+                    max,
+                    over.join(", ")
+                );
+                std::process::exit(2);
+            }
+            for name in &over {
+                eprintln!("Warning: No NVENC session left ({} allowed on {}); {} encodes on the CPU", max, self.source, name);
+            }
+        }
+        hardware
+    }
+}
+
+/// Counts the frames each encoder puts out and sets `detect_encoder_fps{encoder,kind}` once a
+/// second, plus `detect_encoder_sessions{kind}` for NVENC and CPU encoders.
+pub fn watch(pipeline: &gstreamer::Element, metrics: Metrics) {
+    let Some(bin) = pipeline.downcast_ref::<gstreamer::Bin>() else { return };
+    {
+        let mut metrics = metrics.lock().unwrap();
+        metrics.describe("detect_encoder_sessions", "gauge", "Video encoders running, by kind (nvenc or software)");
+        metrics.describe("detect_encoder_fps", "gauge", "Frames per second put out per video encoder");
+    }
+    let (mut nvenc, mut software) = (0, 0);
+    for encoder in bin.iterate_recurse().into_iter().flatten() {
+        let Some(factory) = encoder.factory().map(|f| f.name().to_string()) else { continue };
+        let kind = match factory.as_str() {
+            "nvv4l2h264enc" | "nvv4l2h265enc" => "nvenc",
+            "x264enc" | "x265enc" => "software",
+            _ => continue,
+        };
+        if kind == "nvenc" {
+            nvenc += 1;
+        } else {
+            software += 1;
+        }
+        let Some(src) = encoder.static_pad("src") else { continue };
+        let name = encoder.name().to_string();
+        let metrics = metrics.clone();
+        let counted = Mutex::new((Instant::now(), 0u64));
+        src.add_probe(gstreamer::PadProbeType::BUFFER, move |_, _| {
+            let mut counted = counted.lock().unwrap();
+            counted.1 += 1;
+            let elapsed = counted.0.elapsed().as_secs_f64();
+            if elapsed >= 1.0 {
+                let fps = counted.1 as f64 / elapsed;
+                metrics.lock().unwrap().set("detect_encoder_fps", &[("encoder", &name), ("kind", kind)], fps);
+                *counted = (Instant::now(), 0);
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+    }
+    let mut metrics = metrics.lock().unwrap();
+    metrics.set("detect_encoder_sessions", &[("kind", "nvenc")], nvenc as f64);
+    metrics.set("detect_encoder_sessions", &[("kind", "software")], software as f64);
+}
@@ -0,0 +1,342 @@
+use crate::doctor;
+use crate::encoders;
+use crate::json::{self, Value};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// nvinfer `[property]` keys that change the engine TensorRT builds
+const BUILD_KEYS: &[&str] = &[
+    "batch-size",
+    "network-mode",
+    "infer-dims",
+    "workspace-size",
+    "gpu-id",
+    "output-blob-names",
+    "force-implicit-batch-dim",
+    "engine-create-func-name",
+    "layer-device-precision",
+    "int8-calib-file",
+];
+/// Model files whose contents go into the key
+const MODEL_KEYS: &[&str] = &["onnx-file", "model-file", "tlt-encoded-model", "uff-file"];
+/// Where TensorRT's version is defined
+const TRT_HEADERS: &[&str] = &[
+    "/usr/include/x86_64-linux-gnu/NvInferVersion.h",
+    "/usr/include/aarch64-linux-gnu/NvInferVersion.h",
+    "/usr/include/NvInferVersion.h",
+];
+const TRT_LIB_DIRS: &[&str] = &["/usr/lib/x86_64-linux-gnu", "/usr/lib/aarch64-linux-gnu", "/usr/lib"];
+
+/// FNV-1a, stable across builds and Rust versions unlike std's hasher.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// GPU model: nvidia-smi's name, or the board on Jetson.
+fn gpu_name() -> String {
+    encoders::query_gpu()
+        .map(|(name, _)| name)
+        .or_else(|| fs::read_to_string("/proc/device-tree/model").ok().map(|m| m.trim_end_matches('\0').trim().to_string()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// TensorRT version from its header, or from the name of the installed libnvinfer.
+fn tensorrt_version() -> String {
+    for header in TRT_HEADERS {
+        let Ok(contents) = fs::read_to_string(header) else { continue };
+        let define = |name: &str| {
+            contents.lines().find_map(|line| {
+                let mut words = line.split_whitespace();
+                (words.next() == Some("#define") && words.next() == Some(name)).then(|| words.next().map(str::to_string))?
+            })
+        };
+        let parts: Option<Vec<String>> = ["MAJOR", "MINOR", "PATCH"]
+            .iter()
+            .map(|part| define(&format!("NV_TENSORRT_{}", part)).or_else(|| define(&format!("TRT_{}_ENTERPRISE", part))))
+            .collect();
+        if let Some(parts) = parts {
+            return parts.join(".");
+        }
+    }
+    TRT_LIB_DIRS
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.strip_prefix("libnvinfer.so.").map(str::to_string))
+        .max_by_key(|version| version.len())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// What an engine was built for; a cached engine is only used when all of it matches.
+struct BuildKey {
+    model: String,
+    gpu: String,
+    tensorrt: String,
+    hash: u64,
+}
+
+impl BuildKey {
+    /// None when the config names no model file to build from.
+    fn of(model_config: &str, properties: &[(String, String)], gpu: &str, tensorrt: &str) -> Option<BuildKey> {
+        let get = |key: &str| properties.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        let model = MODEL_KEYS.iter().find_map(|key| get(key)).map(|v| doctor::resolve(model_config, v))?;
+        let contents = fs::read(&model).ok()?;
+        let mut hash = fnv1a(0xcbf29ce484222325, &contents);
+        for key in BUILD_KEYS {
+            hash = fnv1a(hash, format!("{}={};", key, get(key).unwrap_or_default()).as_bytes());
+        }
+        hash = fnv1a(hash, gpu.as_bytes());
+        hash = fnv1a(hash, tensorrt.as_bytes());
+        let stem = model.file_stem().and_then(|s| s.to_str()).unwrap_or("model").to_string();
+        Some(BuildKey { model: stem, gpu: gpu.to_string(), tensorrt: tensorrt.to_string(), hash })
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}-{:016x}.engine", self.model, self.hash)
+    }
+}
+
+/// `value` from the config at `config_path` as an absolute path.
+fn absolute(config_path: &str, value: &str) -> PathBuf {
+    let path = doctor::resolve(config_path, value);
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+/// A cache entry: the engine and the metadata written next to it.
+pub struct Entry {
+    pub engine: PathBuf,
+    pub model: String,
+    pub gpu: String,
+    pub tensorrt: String,
+    pub created: u64,
+}
+
+impl Entry {
+    fn metadata_path(engine: &Path) -> PathBuf {
+        engine.with_extension("json")
+    }
+
+    fn read(engine: PathBuf) -> Entry {
+        let metadata = fs::read_to_string(Self::metadata_path(&engine)).ok().and_then(|m| json::parse(&m).ok());
+        let text = |key: &str| match metadata.as_ref().and_then(|m| m.get(key)) {
+            Some(Value::String(s)) => s.clone(),
+            _ => "unknown".to_string(),
+        };
+        let created = metadata.as_ref().and_then(|m| m.get("created")).and_then(Value::as_i64).unwrap_or(0) as u64;
+        Entry { model: text("model"), gpu: text("gpu"), tensorrt: text("tensorrt"), created, engine }
+    }
+}
+
+/// Built TensorRT engines kept in ENGINE_CACHE_DIR, keyed by a hash of the model file, the
+/// config keys that shape the build, the GPU and the TensorRT version. nvinfer is pointed at
+/// the entry for the current key: an engine built for another model version, GPU or TensorRT
+/// is never loaded, and once nvinfer has built the new one it is stored under the new key and
+/// the model's stale entries are removed.
+pub struct EngineCache {
+    pub dir: PathBuf,
+    gpu: String,
+    tensorrt: String,
+}
+
+/// An nvinfer config prepared to use the cache.
+pub struct Prepared {
+    /// Config for nvinfer: a copy pointing model-engine-file at the cache entry
+    pub config: String,
+    /// Whether the entry exists; otherwise nvinfer builds the engine and `store` files it
+    pub hit: bool,
+    engine: PathBuf,
+    /// Where nvinfer serializes an engine it builds: `<model file>_b<batch>_gpu<id>_<precision>.engine`
+    built_prefix: PathBuf,
+    key: BuildKey,
+    started: SystemTime,
+}
+
+impl EngineCache {
+    /// Reads ENGINE_CACHE_DIR; None when unset.
+    pub fn from_env() -> Option<EngineCache> {
+        let dir = PathBuf::from(env::var("ENGINE_CACHE_DIR").ok().filter(|d| !d.trim().is_empty())?);
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("Warning: Could not create ENGINE_CACHE_DIR {}: {}. Engine cache disabled.", dir.display(), e);
+            return None;
+        }
+        Some(EngineCache { dir, gpu: gpu_name(), tensorrt: tensorrt_version() })
+    }
+
+    /// Writes a copy of `config` (whose relative paths are resolved against `original`, the
+    /// config it came from) that loads the cache entry for the current build key. None when
+    /// the config has no model file to key on.
+    pub fn prepare(&self, config: &str, original: &str) -> Option<Prepared> {
+        let contents = fs::read_to_string(config).ok()?;
+        let properties = doctor::infer_properties(&contents);
+        let key = BuildKey::of(original, &properties, &self.gpu, &self.tensorrt)?;
+        let engine = self.dir.join(key.file_name());
+        let model = MODEL_KEYS
+            .iter()
+            .find_map(|k| properties.iter().find(|(key, _)| key == k))
+            .map(|(_, v)| absolute(original, v))?;
+        let mut copy = String::new();
+        let mut in_property = false;
+        for line in contents.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                in_property = trimmed == "[property]";
+                copy.push_str(line);
+                copy.push('\n');
+                if in_property {
+                    copy.push_str(&format!("model-engine-file={}\n", engine.display()));
+                }
+                continue;
+            }
+            match trimmed.split_once('=').filter(|_| in_property && !trimmed.starts_with('#')) {
+                Some((key, _)) if key.trim() == "model-engine-file" => {}
+                // The copy lives elsewhere, so file paths can't stay relative
+                Some((key, value)) if (key.trim().ends_with("-file") || key.trim().ends_with("-path")) && !value.trim().is_empty() => {
+                    copy.push_str(&format!("{}={}\n", key.trim(), absolute(original, value.trim()).display()));
+                }
+                _ => {
+                    copy.push_str(line);
+                    copy.push('\n');
+                }
+            }
+        }
+        let path = self.dir.join(format!("{}-{:016x}.txt", key.model, key.hash));
+        if let Err(e) = fs::write(&path, copy) {
+            eprintln!("Warning: Could not write {}: {}. Engine cache not used.", path.display(), e);
+            return None;
+        }
+        Some(Prepared {
+            config: path.display().to_string(),
+            hit: engine.exists(),
+            engine,
+            built_prefix: PathBuf::from(format!("{}_b", model.display())),
+            key,
+            started: SystemTime::now(),
+        })
+    }
+
+    /// Files the engine nvinfer built for `prepared` (found next to the model file) as its
+    /// cache entry, and removes the model's entries for other keys.
+    pub fn store(&self, prepared: &Prepared) {
+        if prepared.hit {
+            return;
+        }
+        let dir = prepared.built_prefix.parent().unwrap_or(Path::new("."));
+        let prefix = prepared.built_prefix.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let built = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                name.starts_with(prefix) && name.ends_with(".engine")
+            })
+            .filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+            .filter(|(modified, _)| *modified >= prepared.started)
+            .max();
+        let Some((_, built)) = built else {
+            eprintln!(
+                "Warning: No engine built by nvinfer found next to {} (is its directory writable?), not cached",
+                prepared.built_prefix.display()
+            );
+            return;
+        };
+        if let Err(e) = fs::copy(&built, &prepared.engine) {
+            eprintln!("Warning: Could not cache engine {}: {}", built.display(), e);
+            return;
+        }
+        let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let metadata = Value::Object(vec![
+            ("model".to_string(), Value::from(prepared.key.model.as_str())),
+            ("gpu".to_string(), Value::from(prepared.key.gpu.as_str())),
+            ("tensorrt".to_string(), Value::from(prepared.key.tensorrt.as_str())),
+            ("source".to_string(), Value::from(built.display().to_string())),
+            ("created".to_string(), Value::from(created)),
+        ]);
+        let _ = fs::write(Entry::metadata_path(&prepared.engine), metadata.to_json());
+        println!("✓ Cached engine {}", prepared.engine.display());
+        for entry in self.entries() {
+            if entry.model == prepared.key.model && entry.engine != prepared.engine {
+                println!("  Removing stale engine {} ({}, TensorRT {})", entry.engine.display(), entry.gpu, entry.tensorrt);
+                remove(&entry);
+            }
+        }
+    }
+
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut engines: Vec<PathBuf> = fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|e| e == "engine"))
+            .collect();
+        engines.sort();
+        engines.into_iter().map(Entry::read).collect()
+    }
+
+    /// Whether `entry` was built for this machine's GPU and TensorRT.
+    fn current(&self, entry: &Entry) -> bool {
+        entry.gpu == self.gpu && entry.tensorrt == self.tensorrt
+    }
+}
+
+fn remove(entry: &Entry) {
+    let _ = fs::remove_file(&entry.engine);
+    let _ = fs::remove_file(Entry::metadata_path(&entry.engine));
+    let _ = fs::remove_file(entry.engine.with_extension("txt"));
+}
+
+/// `detect cache [list]` lists the cached engines, `detect cache clear [stale | <model>]`
+/// removes all of them, the ones built for another GPU or TensorRT, or one model's.
+pub fn command(args: &[String]) -> ! {
+    let Some(cache) = EngineCache::from_env() else {
+        eprintln!("Error: ENGINE_CACHE_DIR is not set");
+        std::process::exit(2);
+    };
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] | ["list"] => {
+            println!("Engine cache {} (GPU {}, TensorRT {})", cache.dir.display(), cache.gpu, cache.tensorrt);
+            let entries = cache.entries();
+            if entries.is_empty() {
+                println!("  (empty)");
+            }
+            for entry in entries {
+                let size = fs::metadata(&entry.engine).map(|m| m.len()).unwrap_or(0);
+                println!(
+                    "  {} {:.1} MB, {}, TensorRT {}, created {}{}",
+                    entry.engine.file_name().and_then(|n| n.to_str()).unwrap_or_default(),
+                    size as f64 / 1e6,
+                    entry.gpu,
+                    entry.tensorrt,
+                    entry.created,
+                    if cache.current(&entry) { "" } else { " (stale)" }
+                );
+            }
+        }
+        ["clear", filter @ ..] => {
+            let entries = cache.entries();
+            let selected: Vec<&Entry> = entries
+                .iter()
+                .filter(|entry| match filter {
+                    [] => true,
+                    ["stale"] => !cache.current(entry),
+                    [model] => entry.model == *model,
+                    _ => false,
+                })
+                .collect();
+            for entry in &selected {
+                remove(entry);
+            }
+            println!("Removed {} cached engine(s)", selected.len());
+        }
+        _ => {
+            eprintln!("Usage: detect cache [list | clear [stale | <model>]]");
+            std::process::exit(2);
+        }
+    }
+    std::process::exit(0);
+}
@@ -0,0 +1,523 @@
+use crate::detections::{FrameDetections, FrameObserver};
+use crate::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+
+pub struct EvalConfig {
+    pub gt_path: String,
+    pub report_path: Option<String>,
+    pub target_class: Option<i32>,
+}
+
+impl EvalConfig {
+    /// Reads EVAL_GT (MOT txt or COCO json) and EVAL_REPORT (optional JSON report path).
+    pub fn from_env(target_class: Option<i32>) -> Option<EvalConfig> {
+        let gt_path = env::var("EVAL_GT").ok()?;
+        let report_path = env::var("EVAL_REPORT").ok();
+        Some(EvalConfig { gt_path, report_path, target_class })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BoxF {
+    left: f32,
+    top: f32,
+    width: f32,
+    height: f32,
+}
+
+impl BoxF {
+    fn iou(&self, other: &BoxF) -> f32 {
+        let x1 = self.left.max(other.left);
+        let y1 = self.top.max(other.top);
+        let x2 = (self.left + self.width).min(other.left + other.width);
+        let y2 = (self.top + self.height).min(other.top + other.height);
+        let inter = (x2 - x1).max(0.0) * (y2 - y1).max(0.0);
+        let union = self.width * self.height + other.width * other.height - inter;
+        if union <= 0.0 { 0.0 } else { inter / union }
+    }
+}
+
+struct GroundTruth {
+    // frame index (1-based) -> (class, box)
+    boxes: HashMap<i64, Vec<(i32, BoxF)>>,
+    // Predictions on frames outside this set are not scored
+    annotated_frames: HashSet<i64>,
+    class_agnostic: bool,
+}
+
+/// MOTChallenge gt.txt: frame,id,left,top,width,height,consider,class,visibility.
+/// MOT class ids don't map onto the detector's labels, so every box is taken as the
+/// target class (or class-agnostic when no target is set). Rows with consider=0 are skipped.
+fn load_mot(contents: &str, target_class: Option<i32>) -> Result<GroundTruth, String> {
+    let mut boxes: HashMap<i64, Vec<(i32, BoxF)>> = HashMap::new();
+    let mut max_frame = 0;
+    for (line_no, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<f32> = line
+            .split(',')
+            .map(|f| f.trim().parse::<f32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("line {}: expected comma-separated numbers", line_no + 1))?;
+        if fields.len() < 6 {
+            return Err(format!("line {}: expected at least 6 fields, found {}", line_no + 1, fields.len()));
+        }
+        let frame = fields[0] as i64;
+        max_frame = max_frame.max(frame);
+        if fields.len() > 6 && fields[6] == 0.0 {
+            continue;
+        }
+        let bbox = BoxF { left: fields[2], top: fields[3], width: fields[4], height: fields[5] };
+        boxes.entry(frame).or_default().push((target_class.unwrap_or(-1), bbox));
+    }
+    Ok(GroundTruth {
+        boxes,
+        annotated_frames: (1..=max_frame).collect(),
+        class_agnostic: target_class.is_none(),
+    })
+}
+
+/// COCO detection JSON. Images map to frames via `frame_index` (as written by our
+/// exporter) and fall back to the image id.
+fn load_coco(contents: &str, target_class: Option<i32>) -> Result<GroundTruth, String> {
+    let doc = json::parse(contents).map_err(|e| e.to_string())?;
+    let images = doc.get("images").and_then(|v| v.as_array()).ok_or("missing \"images\" array")?;
+    let annotations = doc
+        .get("annotations")
+        .and_then(|v| v.as_array())
+        .ok_or("missing \"annotations\" array")?;
+
+    let mut frame_of_image = HashMap::new();
+    for (idx, image) in images.iter().enumerate() {
+        let id = image.get("id").and_then(|v| v.as_i64()).ok_or(format!("images[{}]: missing \"id\"", idx))?;
+        let frame = image.get("frame_index").and_then(|v| v.as_i64()).unwrap_or(id);
+        frame_of_image.insert(id, frame);
+    }
+
+    let mut boxes: HashMap<i64, Vec<(i32, BoxF)>> = HashMap::new();
+    for (idx, ann) in annotations.iter().enumerate() {
+        let image_id = ann
+            .get("image_id")
+            .and_then(|v| v.as_i64())
+            .ok_or(format!("annotations[{}]: missing \"image_id\"", idx))?;
+        let class = ann
+            .get("category_id")
+            .and_then(|v| v.as_i64())
+            .ok_or(format!("annotations[{}]: missing \"category_id\"", idx))? as i32;
+        let bbox: Vec<f32> = ann
+            .get("bbox")
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().filter_map(|n| n.as_f64()).map(|n| n as f32).collect())
+            .unwrap_or_default();
+        if bbox.len() != 4 {
+            return Err(format!("annotations[{}]: \"bbox\" must be [x, y, width, height]", idx));
+        }
+        if target_class.is_some_and(|target| target != class) {
+            continue;
+        }
+        let frame = *frame_of_image
+            .get(&image_id)
+            .ok_or(format!("annotations[{}]: unknown image_id {}", idx, image_id))?;
+        boxes.entry(frame).or_default().push((
+            class,
+            BoxF { left: bbox[0], top: bbox[1], width: bbox[2], height: bbox[3] },
+        ));
+    }
+
+    Ok(GroundTruth {
+        boxes,
+        annotated_frames: frame_of_image.values().copied().collect(),
+        class_agnostic: false,
+    })
+}
+
+struct Prediction {
+    frame: i64,
+    confidence: f32,
+    bbox: BoxF,
+}
+
+struct ClassResult {
+    class_id: i32,
+    gt: usize,
+    predictions: usize,
+    tp: usize,
+    fp: usize,
+    ap50: f32,
+    ap50_95: f32,
+}
+
+impl ClassResult {
+    fn precision(&self) -> f32 {
+        if self.predictions == 0 { 0.0 } else { self.tp as f32 / self.predictions as f32 }
+    }
+
+    fn recall(&self) -> f32 {
+        if self.gt == 0 { 0.0 } else { self.tp as f32 / self.gt as f32 }
+    }
+}
+
+/// Greedy matching of confidence-sorted predictions at one IoU threshold.
+/// Returns (AP with all-point interpolation, true positives, false positives).
+fn score_class(predictions: &[Prediction], gt: &HashMap<i64, Vec<BoxF>>, num_gt: usize, threshold: f32) -> (f32, usize, usize) {
+    let mut matched: HashMap<i64, Vec<bool>> = gt.iter().map(|(frame, boxes)| (*frame, vec![false; boxes.len()])).collect();
+    let mut tp = 0;
+    let mut fp = 0;
+    let mut curve = Vec::with_capacity(predictions.len());
+
+    for pred in predictions {
+        let mut best: Option<(usize, f32)> = None;
+        if let Some(boxes) = gt.get(&pred.frame) {
+            let used = &matched[&pred.frame];
+            for (idx, gt_box) in boxes.iter().enumerate() {
+                let iou = pred.bbox.iou(gt_box);
+                if !used[idx] && iou >= threshold && best.is_none_or(|(_, b)| iou > b) {
+                    best = Some((idx, iou));
+                }
+            }
+        }
+        match best {
+            Some((idx, _)) => {
+                matched.get_mut(&pred.frame).unwrap()[idx] = true;
+                tp += 1;
+            }
+            None => fp += 1,
+        }
+        let recall = if num_gt == 0 { 0.0 } else { tp as f32 / num_gt as f32 };
+        curve.push((recall, tp as f32 / (tp + fp) as f32));
+    }
+
+    let mut recalls = vec![0.0];
+    let mut precisions = vec![0.0];
+    for (r, p) in &curve {
+        recalls.push(*r);
+        precisions.push(*p);
+    }
+    recalls.push(1.0);
+    precisions.push(0.0);
+    for i in (0..precisions.len() - 1).rev() {
+        precisions[i] = precisions[i].max(precisions[i + 1]);
+    }
+    let mut ap = 0.0;
+    for i in 0..recalls.len() - 1 {
+        if recalls[i + 1] != recalls[i] {
+            ap += (recalls[i + 1] - recalls[i]) * precisions[i + 1];
+        }
+    }
+    (ap, tp, fp)
+}
+
+pub struct Evaluator {
+    gt: GroundTruth,
+    labels: Vec<String>,
+    target_class: Option<i32>,
+    report_path: Option<String>,
+    predictions: BTreeMap<i32, Vec<Prediction>>,
+}
+
+impl Evaluator {
+    pub fn new(config: &EvalConfig, labels: Vec<String>) -> Result<Evaluator, String> {
+        let contents = fs::read_to_string(&config.gt_path).map_err(|e| format!("{}: {}", config.gt_path, e))?;
+        let gt = if config.gt_path.ends_with(".json") {
+            load_coco(&contents, config.target_class)
+        } else {
+            load_mot(&contents, config.target_class)
+        }
+        .map_err(|e| format!("{}: {}", config.gt_path, e))?;
+
+        Ok(Evaluator {
+            gt,
+            labels,
+            target_class: config.target_class,
+            report_path: config.report_path.clone(),
+            predictions: BTreeMap::new(),
+        })
+    }
+
+    fn class_name(&self, class_id: i32) -> String {
+        if class_id < 0 {
+            return "all".to_string();
+        }
+        self.labels.get(class_id as usize).cloned().unwrap_or_else(|| class_id.to_string())
+    }
+
+    fn results(&mut self) -> Vec<ClassResult> {
+        let mut gt_by_class: BTreeMap<i32, HashMap<i64, Vec<BoxF>>> = BTreeMap::new();
+        for (frame, boxes) in &self.gt.boxes {
+            for (class, bbox) in boxes {
+                gt_by_class.entry(*class).or_default().entry(*frame).or_default().push(*bbox);
+            }
+        }
+        let mut classes: Vec<i32> = gt_by_class.keys().copied().collect();
+        classes.extend(self.predictions.keys().filter(|c| !gt_by_class.contains_key(c)));
+        classes.sort();
+
+        let empty_gt = HashMap::new();
+        let mut results = Vec::new();
+        for class in classes {
+            let gt = gt_by_class.get(&class).unwrap_or(&empty_gt);
+            let num_gt = gt.values().map(|b| b.len()).sum();
+            let preds = self.predictions.entry(class).or_default();
+            preds.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+            let (ap50, tp, fp) = score_class(preds, gt, num_gt, 0.5);
+            let ap50_95 = (0..10)
+                .map(|i| score_class(preds, gt, num_gt, 0.5 + 0.05 * i as f32).0)
+                .sum::<f32>()
+                / 10.0;
+            results.push(ClassResult { class_id: class, gt: num_gt, predictions: preds.len(), tp, fp, ap50, ap50_95 });
+        }
+        results
+    }
+}
+
+impl FrameObserver for Evaluator {
+    fn on_frame(&mut self, frame: &FrameDetections) {
+        // The ground truth belongs to the first input; other sources' frames share its
+        // frame numbers but not its boxes
+        if frame.source_id != 0 {
+            return;
+        }
+        // Same 1-based frame numbering as the MOT/COCO exporters
+        let frame_index = frame.frame_num as i64 + 1;
+        if !self.gt.annotated_frames.contains(&frame_index) {
+            return;
+        }
+        for det in &frame.objects {
+            if self.target_class.is_some_and(|target| target != det.class_id) {
+                continue;
+            }
+            let class = if self.gt.class_agnostic { -1 } else { det.class_id };
+            let (left, top, width, height) = frame.to_source_coords(det);
+            self.predictions.entry(class).or_default().push(Prediction {
+                frame: frame_index,
+                confidence: det.confidence,
+                bbox: BoxF { left, top, width, height },
+            });
+        }
+    }
+
+    fn finish(&mut self) {
+        let results = self.results();
+        // Classes with no ground truth only contribute false positives, not to the mean AP
+        let scored: Vec<&ClassResult> = results.iter().filter(|r| r.gt > 0).collect();
+        let mean = |f: fn(&ClassResult) -> f32| {
+            if scored.is_empty() { 0.0 } else { scored.iter().map(|r| f(r)).sum::<f32>() / scored.len() as f32 }
+        };
+        let (map50, map50_95) = (mean(|r| r.ap50), mean(|r| r.ap50_95));
+        let total_gt: usize = results.iter().map(|r| r.gt).sum();
+        let total_tp: usize = results.iter().map(|r| r.tp).sum();
+        let total_pred: usize = results.iter().map(|r| r.predictions).sum();
+        let precision = if total_pred == 0 { 0.0 } else { total_tp as f32 / total_pred as f32 };
+        let recall = if total_gt == 0 { 0.0 } else { total_tp as f32 / total_gt as f32 };
+
+        println!("\nEvaluation against {} frames of ground truth (IoU 0.5):", self.gt.annotated_frames.len());
+        println!("  {:<16} {:>6} {:>6} {:>6} {:>6} {:>9} {:>7} {:>7} {:>9}", "class", "gt", "pred", "tp", "fp", "precision", "recall", "AP50", "AP50-95");
+        for r in &results {
+            println!(
+                "  {:<16} {:>6} {:>6} {:>6} {:>6} {:>9.3} {:>7.3} {:>7.3} {:>9.3}",
+                self.class_name(r.class_id),
+                r.gt,
+                r.predictions,
+                r.tp,
+                r.fp,
+                r.precision(),
+                r.recall(),
+                r.ap50,
+                r.ap50_95
+            );
+        }
+        println!("  Overall: precision {:.3}, recall {:.3}, mAP50 {:.3}, mAP50-95 {:.3}", precision, recall, map50, map50_95);
+
+        if let Some(path) = &self.report_path {
+            let classes: Vec<String> = results
+                .iter()
+                .map(|r| {
+                    format!(
+                        "{{\"class_id\":{},\"name\":{},\"gt\":{},\"predictions\":{},\"tp\":{},\"fp\":{},\"fn\":{},\"precision\":{},\"recall\":{},\"ap50\":{},\"ap50_95\":{}}}",
+                        r.class_id,
+                        json::string(&self.class_name(r.class_id)),
+                        r.gt,
+                        r.predictions,
+                        r.tp,
+                        r.fp,
+                        r.gt - r.tp,
+                        json::number(r.precision()),
+                        json::number(r.recall()),
+                        json::number(r.ap50),
+                        json::number(r.ap50_95)
+                    )
+                })
+                .collect();
+            let report = format!(
+                "{{\"frames\":{},\"precision\":{},\"recall\":{},\"map50\":{},\"map50_95\":{},\"classes\":[\n{}\n]}}\n",
+                self.gt.annotated_frames.len(),
+                json::number(precision),
+                json::number(recall),
+                json::number(map50),
+                json::number(map50_95),
+                classes.join(",\n")
+            );
+            match fs::write(path, report) {
+                Ok(()) => println!("✓ Wrote evaluation report: {}", path),
+                Err(e) => eprintln!("Warning: Failed to write evaluation report {}: {}", path, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detections::Detection;
+
+    fn evaluator(gt: &str) -> Evaluator {
+        Evaluator {
+            gt: load_mot(gt, None).unwrap(),
+            labels: Vec::new(),
+            target_class: None,
+            report_path: None,
+            predictions: BTreeMap::new(),
+        }
+    }
+
+    /// A frame with detections given as (confidence, left, top, width, height).
+    fn frame(source_id: u32, frame_num: i32, boxes: &[(f32, f32, f32, f32, f32)]) -> FrameDetections {
+        FrameDetections {
+            source_id,
+            frame_num,
+            pts: 0,
+            source_width: 0,
+            source_height: 0,
+            pipeline_width: 0,
+            pipeline_height: 0,
+            objects: boxes
+                .iter()
+                .map(|&(confidence, left, top, width, height)| Detection {
+                    class_id: 0,
+                    track_id: None,
+                    confidence,
+                    left,
+                    top,
+                    width,
+                    height,
+                    keypoints: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn only_the_first_source_is_scored() {
+        let mut eval = evaluator("1,1,10,10,20,20,1,1,1\n");
+        eval.on_frame(&frame(0, 0, &[(0.9, 10.0, 10.0, 20.0, 20.0)]));
+        eval.on_frame(&frame(1, 0, &[(0.9, 100.0, 100.0, 20.0, 20.0), (0.9, 10.0, 10.0, 20.0, 20.0)]));
+        let results = eval.results();
+        assert_eq!(results.len(), 1);
+        assert_eq!((results[0].predictions, results[0].tp, results[0].fp), (1, 1, 0));
+        assert_eq!(results[0].ap50, 1.0);
+    }
+
+    /// (predictions, tp, fp, AP50, AP50-95) of the only class.
+    fn score(gt: &str, frames: &[FrameDetections]) -> (usize, usize, usize, f32, f32) {
+        let mut eval = evaluator(gt);
+        for f in frames {
+            eval.on_frame(f);
+        }
+        let results = eval.results();
+        assert_eq!(results.len(), 1);
+        let r = &results[0];
+        (r.predictions, r.tp, r.fp, r.ap50, r.ap50_95)
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn a_perfect_match_has_ap_one() {
+        let (predictions, tp, fp, ap50, ap50_95) = score("1,1,0,0,10,10\n", &[frame(0, 0, &[(0.9, 0.0, 0.0, 10.0, 10.0)])]);
+        assert_eq!((predictions, tp, fp), (1, 1, 0));
+        assert_near(ap50, 1.0);
+        assert_near(ap50_95, 1.0);
+    }
+
+    #[test]
+    fn a_false_positive_ranked_first_halves_ap() {
+        // Recall reaches 1 only at the second prediction, where precision is 1/2
+        let detections = [(0.9, 50.0, 50.0, 10.0, 10.0), (0.8, 0.0, 0.0, 10.0, 10.0)];
+        let (_, tp, fp, ap50, _) = score("1,1,0,0,10,10\n", &[frame(0, 0, &detections)]);
+        assert_eq!((tp, fp), (1, 1));
+        assert_near(ap50, 0.5);
+
+        // Ranked below the true positive it costs nothing: the envelope keeps precision 1 up to recall 1
+        let detections = [(0.9, 0.0, 0.0, 10.0, 10.0), (0.8, 50.0, 50.0, 10.0, 10.0)];
+        let (_, _, _, ap50, _) = score("1,1,0,0,10,10\n", &[frame(0, 0, &detections)]);
+        assert_near(ap50, 1.0);
+    }
+
+    #[test]
+    fn unmatched_ground_truth_caps_recall() {
+        let gt = "1,1,0,0,10,10\n1,2,50,50,10,10\n";
+        let (_, tp, fp, ap50, _) = score(gt, &[frame(0, 0, &[(0.9, 0.0, 0.0, 10.0, 10.0)])]);
+        assert_eq!((tp, fp), (1, 0));
+        assert_near(ap50, 0.5);
+    }
+
+    #[test]
+    fn predictions_compete_for_one_box_in_confidence_order() {
+        // IoU 0.72: the more confident box wins the match up to threshold 0.7 and the
+        // duplicate is a false positive; from 0.75 only the exact box matches, ranked second
+        let detections = [(0.9, 0.0, 0.0, 10.0, 7.2), (0.8, 0.0, 0.0, 10.0, 10.0)];
+        let (_, tp, fp, ap50, ap50_95) = score("1,1,0,0,10,10\n", &[frame(0, 0, &detections)]);
+        assert_eq!((tp, fp), (1, 1));
+        assert_near(ap50, 1.0);
+        assert_near(ap50_95, (5.0 * 1.0 + 5.0 * 0.5) / 10.0);
+    }
+
+    #[test]
+    fn ap50_95_averages_ten_thresholds() {
+        // IoU 0.72 matches at 0.5, 0.55, 0.6, 0.65 and 0.7
+        let (_, _, _, ap50, ap50_95) = score("1,1,0,0,10,10\n", &[frame(0, 0, &[(0.9, 0.0, 0.0, 10.0, 7.2)])]);
+        assert_near(ap50, 1.0);
+        assert_near(ap50_95, 0.5);
+    }
+
+    #[test]
+    fn mot_ground_truth_skips_ignored_rows_and_covers_every_frame_up_to_the_last() {
+        let gt = load_mot("# comment\n1,1,0,0,10,10,1,1,1\n1,2,20,20,10,10,0,1,1\n\n4,1,5,5,10,10\n", Some(2)).unwrap();
+        assert_eq!(gt.boxes[&1].len(), 1);
+        assert_eq!(gt.boxes[&1][0].0, 2);
+        assert_eq!(gt.boxes[&4].len(), 1);
+        assert!(!gt.boxes.contains_key(&2));
+        let mut frames: Vec<i64> = gt.annotated_frames.iter().copied().collect();
+        frames.sort();
+        assert_eq!(frames, [1, 2, 3, 4]);
+        assert!(!gt.class_agnostic);
+
+        // A frame whose only row is ignored still counts as annotated
+        let gt = load_mot("3,1,0,0,10,10,0,1,1\n", None).unwrap();
+        assert!(gt.boxes.is_empty());
+        assert_eq!(gt.annotated_frames.len(), 3);
+        assert!(gt.class_agnostic);
+
+        assert_eq!(load_mot("1,1,0,0,10\n", None).err().unwrap(), "line 1: expected at least 6 fields, found 5");
+        assert_eq!(load_mot("1,1,0,0,10,x\n", None).err().unwrap(), "line 1: expected comma-separated numbers");
+    }
+
+    #[test]
+    fn predictions_outside_the_annotated_frames_are_not_scored() {
+        // Frame 2 is annotated (no boxes) so its prediction is a false positive; frame 3 is past the ground truth
+        let frames = [
+            frame(0, 0, &[(0.9, 0.0, 0.0, 10.0, 10.0)]),
+            frame(0, 1, &[(0.9, 0.0, 0.0, 10.0, 10.0)]),
+            frame(0, 2, &[(0.9, 0.0, 0.0, 10.0, 10.0)]),
+        ];
+        let (predictions, tp, fp, _, _) = score("1,1,0,0,10,10\n2,1,0,0,10,10,0,1,1\n", &frames);
+        assert_eq!((predictions, tp, fp), (2, 1, 1));
+    }
+}
@@ -0,0 +1,370 @@
+use crate::gps::Gps;
+use crate::json::Value;
+use crate::plugins;
+use crate::routing::{self, Rules, Severity};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Events queued for a remote sink before new ones are dropped
+const REMOTE_QUEUE: usize = 256;
+
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Something the analytics noticed, e.g. motion starting in a zone.
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub kind: String,
+    pub source_id: u32,
+    pub frame_num: i32,
+    pub timestamp_ms: u64,
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Event {
+    pub fn new(kind: &str, source_id: u32, frame_num: i32) -> Event {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Event {
+            kind: kind.to_string(),
+            source_id,
+            frame_num,
+            timestamp_ms,
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Event {
+        self.fields.push((key.to_string(), value.into()));
+        self
+    }
+
+    /// One JSON object; the fixed keys come first so lines are easy to grep.
+    pub fn to_json(&self) -> String {
+        let mut entries = vec![
+            ("event".to_string(), Value::from(self.kind.as_str())),
+            ("source_id".to_string(), Value::from(self.source_id as u64)),
+            ("frame".to_string(), Value::from(self.frame_num as i64)),
+            ("timestamp_ms".to_string(), Value::from(self.timestamp_ms)),
+        ];
+        entries.extend(self.fields.iter().cloned());
+        Value::Object(entries).to_json()
+    }
+}
+
+/// A destination for events.
+pub trait EventSink: Send {
+    fn send(&mut self, event: &Event);
+}
+
+struct StdoutSink;
+
+impl EventSink for StdoutSink {
+    fn send(&mut self, event: &Event) {
+        println!("Event: {}", event.to_json());
+    }
+}
+
+/// Appends one JSON object per line, flushed per event so `tail -f` works.
+struct JsonLinesSink {
+    path: String,
+    file: File,
+}
+
+impl EventSink for JsonLinesSink {
+    fn send(&mut self, event: &Event) {
+        if let Err(e) = writeln!(self.file, "{}", event.to_json()).and_then(|_| self.file.flush()) {
+            eprintln!("Warning: Failed to write event to {}: {}", self.path, e);
+        }
+    }
+}
+
+/// Hands events to a worker thread, so a slow or unreachable endpoint never
+/// stalls the streaming thread. Events are dropped while the queue is full.
+struct RemoteSink {
+    name: String,
+    queue: SyncSender<Event>,
+    /// Event kinds to forward (all when empty)
+    kinds: Vec<String>,
+}
+
+impl RemoteSink {
+    fn spawn(name: String, kinds: Vec<String>, mut deliver: impl FnMut(&Event) -> Result<(), String> + Send + 'static) -> RemoteSink {
+        let (queue, received) = mpsc::sync_channel::<Event>(REMOTE_QUEUE);
+        let worker_name = name.clone();
+        thread::spawn(move || {
+            for event in received {
+                if let Err(e) = deliver(&event) {
+                    eprintln!("Warning: Failed to send {} event to {}: {}", event.kind, worker_name, e);
+                }
+            }
+        });
+        RemoteSink { name, queue, kinds }
+    }
+}
+
+impl EventSink for RemoteSink {
+    fn send(&mut self, event: &Event) {
+        if !self.kinds.is_empty() && !self.kinds.contains(&event.kind) {
+            return;
+        }
+        if let Err(TrySendError::Full(event)) = self.queue.try_send(event.clone()) {
+            eprintln!("Warning: {} is not keeping up, dropped {} event", self.name, event.kind);
+        }
+    }
+}
+
+/// An EVENT_ROUTES destination: only events of its severity and above are passed on.
+struct SeverityRoute {
+    floor: Severity,
+    sink: RemoteSink,
+}
+
+impl EventSink for SeverityRoute {
+    fn send(&mut self, event: &Event) {
+        if Severity::of(event) >= self.floor {
+            self.sink.send(event);
+        }
+    }
+}
+
+/// Parts of `scheme://[user:password@]host[:port]/path`.
+struct RemoteUrl {
+    credentials: Option<(String, String)>,
+    /// host:port, with the scheme's default port filled in
+    host: String,
+    path: String,
+}
+
+fn split_url(url: &str, scheme: &str, default_port: u16) -> Option<RemoteUrl> {
+    let rest = url.strip_prefix(scheme)?.strip_prefix("://")?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (credentials, host) = match authority.rsplit_once('@') {
+        Some((userinfo, host)) => {
+            let (user, password) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+            (Some((user.to_string(), password.to_string())), host)
+        }
+        None => (None, authority),
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let host = if host.contains(':') { host.to_string() } else { format!("{}:{}", host, default_port) };
+    Some(RemoteUrl { credentials, host, path: path.to_string() })
+}
+
+fn connect(host: &str) -> std::io::Result<TcpStream> {
+    let addr = host
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "host not found"))?;
+    let stream = TcpStream::connect_timeout(&addr, REMOTE_TIMEOUT)?;
+    stream.set_read_timeout(Some(REMOTE_TIMEOUT))?;
+    stream.set_write_timeout(Some(REMOTE_TIMEOUT))?;
+    Ok(stream)
+}
+
+/// POSTs each event as JSON. Plain http is spoken directly; https goes through curl.
+fn webhook_sink(url: &str, kinds: Vec<String>) -> Result<RemoteSink, String> {
+    if url.starts_with("https://") {
+        let name = format!("webhook {}", url);
+        let url = url.to_string();
+        return Ok(RemoteSink::spawn(name, kinds, move |event| {
+            let mut child = Command::new("curl")
+                .args(["-fsS", "-m", "5", "-H", "Content-Type: application/json", "--data-binary", "@-", &url])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .spawn()
+                .map_err(|e| format!("curl: {}", e))?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(event.to_json().as_bytes()).map_err(|e| e.to_string())?;
+            }
+            match child.wait() {
+                Ok(status) if status.success() => Ok(()),
+                Ok(status) => Err(format!("curl exited with {}", status)),
+                Err(e) => Err(e.to_string()),
+            }
+        }));
+    }
+    let RemoteUrl { host, path, .. } = split_url(url, "http", 80).ok_or("expected http://host[:port]/path or https://...")?;
+    Ok(RemoteSink::spawn(format!("webhook {}", url), kinds, move |event| {
+        let body = event.to_json();
+        let mut stream = connect(&host).map_err(|e| e.to_string())?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            path,
+            host,
+            body.len(),
+            body
+        )
+        .map_err(|e| e.to_string())?;
+        let mut status = [0u8; 12];
+        stream.read_exact(&mut status).map_err(|e| e.to_string())?;
+        // "HTTP/1.1 2xx"
+        match status[9] {
+            b'2' => Ok(()),
+            _ => Err(format!("HTTP status {}", String::from_utf8_lossy(&status[9..12]))),
+        }
+    }))
+}
+
+/// MQTT 3.1.1 remaining length: 7 bits per byte, high bit set on all but the last.
+fn mqtt_packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![kind];
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn mqtt_string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn mqtt_connect(host: &str, credentials: Option<(&str, &str)>) -> Result<TcpStream, String> {
+    let mut stream = connect(host).map_err(|e| e.to_string())?;
+    let mut body = Vec::new();
+    mqtt_string(&mut body, "MQTT");
+    body.push(4); // protocol level 3.1.1
+    // Clean session, username and password when given; keep alive disabled
+    body.push(if credentials.is_some() { 0xc2 } else { 0x02 });
+    body.extend_from_slice(&[0, 0]);
+    mqtt_string(&mut body, &format!("detect-{}", std::process::id()));
+    if let Some((user, password)) = credentials {
+        mqtt_string(&mut body, user);
+        mqtt_string(&mut body, password);
+    }
+    stream.write_all(&mqtt_packet(0x10, &body)).map_err(|e| e.to_string())?;
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack).map_err(|e| e.to_string())?;
+    if connack[0] != 0x20 || connack[3] != 0 {
+        return Err(format!("broker refused the connection (code {})", connack[3]));
+    }
+    Ok(stream)
+}
+
+/// Publishes each event with QoS 0 to `<topic>/<event kind>`, reconnecting after errors.
+fn mqtt_sink(url: &str, kinds: Vec<String>) -> Result<RemoteSink, String> {
+    let RemoteUrl { credentials, host, path } =
+        split_url(url, "mqtt", 1883).ok_or("expected mqtt://[user:password@]host[:port]/topic")?;
+    let topic = path.trim_matches('/').to_string();
+    let topic = if topic.is_empty() { "detect".to_string() } else { topic };
+    let mut connection: Option<TcpStream> = None;
+    Ok(RemoteSink::spawn(format!("MQTT {}", host), kinds, move |event| {
+        let mut body = Vec::new();
+        mqtt_string(&mut body, &format!("{}/{}", topic, event.kind));
+        body.extend_from_slice(event.to_json().as_bytes());
+        let packet = mqtt_packet(0x30, &body);
+        let mut stream = match connection.take() {
+            Some(stream) => stream,
+            None => mqtt_connect(&host, credentials.as_ref().map(|(u, p)| (u.as_str(), p.as_str())))?,
+        };
+        stream.write_all(&packet).map_err(|e| e.to_string())?;
+        connection = Some(stream);
+        Ok(())
+    }))
+}
+
+/// Fans every event out to the configured sinks.
+#[derive(Default)]
+pub struct EventBus {
+    sinks: Vec<Box<dyn EventSink>>,
+    gps: Option<Gps>,
+    rules: Option<Rules>,
+}
+
+pub type Events = Arc<Mutex<EventBus>>;
+
+impl EventBus {
+    /// Reads EVENTS_FILE (JSON lines, appended); without it events are printed to stdout.
+    /// EVENTS_WEBHOOK and EVENTS_MQTT forward events to a URL as well, limited to the
+    /// comma-separated EVENTS_REMOTE_KINDS when set. With EVENT_RULES every event gets a
+    /// `severity`, and EVENT_ROUTES sends each severity to its own destinations. Every
+    /// EVENT_PLUGINS library gets all events.
+    pub fn from_env() -> EventBus {
+        let mut bus = EventBus::default();
+        match env::var("EVENTS_FILE") {
+            Ok(path) => match OpenOptions::new().create(true).append(true).open(&path) {
+                Ok(file) => bus.add(Box::new(JsonLinesSink { path, file })),
+                Err(e) => {
+                    eprintln!("Warning: Could not open EVENTS_FILE {}: {}. Printing events instead.", path, e);
+                    bus.add(Box::new(StdoutSink));
+                }
+            },
+            Err(_) => bus.add(Box::new(StdoutSink)),
+        }
+        let kinds: Vec<String> = env::var("EVENTS_REMOTE_KINDS")
+            .map(|v| v.split(',').map(|k| k.trim().to_string()).filter(|k| !k.is_empty()).collect())
+            .unwrap_or_default();
+        if let Ok(url) = env::var("EVENTS_WEBHOOK") {
+            match webhook_sink(&url, kinds.clone()) {
+                Ok(sink) => bus.add(Box::new(sink)),
+                Err(e) => eprintln!("Warning: Invalid EVENTS_WEBHOOK '{}': {}", url, e),
+            }
+        }
+        if let Ok(url) = env::var("EVENTS_MQTT") {
+            match mqtt_sink(&url, kinds) {
+                Ok(sink) => bus.add(Box::new(sink)),
+                Err(e) => eprintln!("Warning: Invalid EVENTS_MQTT '{}': {}", url, e),
+            }
+        }
+        bus.rules = Rules::from_env();
+        for (floor, url) in routing::routes_from_env() {
+            let sink = if url.starts_with("mqtt://") { mqtt_sink(&url, Vec::new()) } else { webhook_sink(&url, Vec::new()) };
+            match sink {
+                Ok(sink) => bus.add(Box::new(SeverityRoute { floor, sink })),
+                Err(e) => eprintln!("Warning: Invalid EVENT_ROUTES destination '{}': {}", url, e),
+            }
+        }
+        // Plugins get their own worker like the remote sinks, so a slow SDK can't stall the pipeline
+        for mut plugin in plugins::from_env() {
+            let name = format!("event plugin {}", plugin.name);
+            bus.add(Box::new(RemoteSink::spawn(name, Vec::new(), move |event| plugin.send(event))));
+        }
+        bus
+    }
+
+    pub fn add(&mut self, sink: Box<dyn EventSink>) {
+        self.sinks.push(sink);
+    }
+
+    /// Tags every event with the current position as a GeoJSON `location` point.
+    pub fn set_gps(&mut self, gps: Gps) {
+        self.gps = Some(gps);
+    }
+
+    pub fn emit(&mut self, mut event: Event) {
+        if let Some(fix) = self.gps.as_ref().and_then(Gps::current) {
+            event.fields.push(("location".to_string(), fix.geometry()));
+        }
+        if let Some(rules) = &self.rules {
+            event.fields.push(("severity".to_string(), Value::from(rules.severity(&event).name())));
+        }
+        for sink in self.sinks.iter_mut() {
+            sink.send(&event);
+        }
+    }
+}
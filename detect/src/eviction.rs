@@ -0,0 +1,96 @@
+use crate::events::{Event, Events};
+use gstreamer::prelude::*;
+use std::env;
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the inputs are checked for stalls
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// SOURCE_TIMEOUT: with several inputs, one that delivers no frame for this long is taken out
+/// of the batch, so nvstreammux stops waiting for it and the others stay live.
+pub struct EvictionConfig {
+    pub timeout: Duration,
+}
+
+impl EvictionConfig {
+    /// Reads SOURCE_TIMEOUT (seconds); None when unset or with a single input.
+    pub fn from_env(sources: usize) -> Option<EvictionConfig> {
+        let value = env::var("SOURCE_TIMEOUT").ok()?;
+        let timeout = match value.trim().parse::<f64>() {
+            Ok(seconds) if seconds > 0.0 => Duration::from_secs_f64(seconds),
+            _ => {
+                eprintln!("Error: SOURCE_TIMEOUT must be a positive number of seconds, got '{}'", value);
+                std::process::exit(2);
+            }
+        };
+        if sources < 2 {
+            eprintln!("Warning: SOURCE_TIMEOUT only applies to several inputs, ignored");
+            return None;
+        }
+        Some(EvictionConfig { timeout })
+    }
+}
+
+struct Input {
+    last_buffer: Instant,
+    evicted: bool,
+}
+
+/// Watches the buffers going into each nvstreammux pad. A stalled input gets an EOS on its
+/// pad, which the muxer takes as the end of that stream and batches without it; the input's
+/// buffers are dropped before the muxer from then on. Its first buffer after that clears the
+/// EOS with a flush-stop, resends its caps and segment and goes through again.
+/// `source_evicted` / `source_restored` events report both.
+pub fn attach(pipeline: &gstreamer::Element, config: EvictionConfig, sources: usize, events: Events) {
+    let bin = pipeline.downcast_ref::<gstreamer::Bin>().expect("pipeline is a bin");
+    let Some(mux) = bin.by_name("m") else { return };
+    let mut watched = Vec::new();
+    for index in 0..sources {
+        let Some(mux_pad) = mux.static_pad(&format!("sink_{}", index)) else { continue };
+        let Some(upstream) = mux_pad.peer() else { continue };
+        let input = Arc::new(Mutex::new(Input { last_buffer: Instant::now(), evicted: false }));
+
+        // The same lock is held while the checker decides, so a buffer either counts as fresh
+        // or is dropped, and never runs into the EOS
+        let state = input.clone();
+        let target = mux_pad.clone();
+        let restored = events.clone();
+        upstream.add_probe(gstreamer::PadProbeType::BUFFER | gstreamer::PadProbeType::BUFFER_LIST, move |pad, _info| {
+            let mut input = state.lock().unwrap();
+            input.last_buffer = Instant::now();
+            if input.evicted {
+                input.evicted = false;
+                target.send_event(gstreamer::event::FlushStop::new(false));
+                pad.sticky_events_foreach(|event| {
+                    if event.type_() != gstreamer::EventType::Eos {
+                        target.send_event(event.clone());
+                    }
+                    ControlFlow::Continue(gstreamer::EventForeachAction::Keep)
+                });
+                println!("Input {} is delivering again, back in the batch", index);
+                restored.lock().unwrap().emit(Event::new("source_restored", index as u32, 0));
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+        watched.push((index, mux_pad, input));
+    }
+
+    let timeout = config.timeout;
+    thread::spawn(move || loop {
+        thread::sleep(CHECK_INTERVAL);
+        for (index, mux_pad, input) in &watched {
+            let mut input = input.lock().unwrap();
+            // Inputs that ended on their own are left alone
+            if input.evicted || input.last_buffer.elapsed() < timeout || mux_pad.pad_flags().contains(gstreamer::PadFlags::EOS) {
+                continue;
+            }
+            input.evicted = true;
+            mux_pad.send_event(gstreamer::event::Eos::new());
+            println!("Input {} delivered nothing for {:?}, taken out of the batch", index, timeout);
+            events.lock().unwrap().emit(Event::new("source_evicted", *index as u32, 0).with("seconds", timeout.as_secs_f64()));
+        }
+    });
+}
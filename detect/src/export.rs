@@ -0,0 +1,292 @@
+use crate::detections::{FrameDetections, FrameObserver};
+use crate::json;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// MOTChallenge results txt: frame,id,left,top,width,height,conf,-1,-1,-1
+    Mot,
+    /// COCO detection JSON (images/annotations/categories) with a track_id extension field
+    Coco,
+    /// GeoJSON-style FeatureCollection with one LineString per track, in source pixels
+    Trajectories,
+}
+
+pub struct ExportConfig {
+    pub formats: Vec<ExportFormat>,
+    pub dir: String,
+    /// File name stem per input, derived from it so each processed file gets its own export
+    pub source_stems: Vec<String>,
+}
+
+impl ExportConfig {
+    /// Reads EXPORT_FORMAT (comma-separated: mot, coco, trajectories) and EXPORT_DIR.
+    pub fn from_env(devices: &[String]) -> Option<ExportConfig> {
+        let formats_str = env::var("EXPORT_FORMAT").ok()?;
+        let mut formats = Vec::new();
+        for name in formats_str.split(',').map(|s| s.trim().to_lowercase()) {
+            match name.as_str() {
+                "mot" => formats.push(ExportFormat::Mot),
+                "coco" => formats.push(ExportFormat::Coco),
+                "trajectories" => formats.push(ExportFormat::Trajectories),
+                "" => {}
+                other => eprintln!(
+                    "Warning: Unknown EXPORT_FORMAT entry '{}' (expected mot, coco or trajectories)",
+                    other
+                ),
+            }
+        }
+        if formats.is_empty() {
+            return None;
+        }
+
+        let dir = env::var("EXPORT_DIR").unwrap_or_else(|_| "/exports".to_string());
+        let source_stems = devices.iter().map(|d| input_stem(d)).collect();
+
+        Some(ExportConfig { formats, dir, source_stems })
+    }
+
+    /// Every input gets its own file per format, named after it (with the source index when
+    /// two inputs share a name, e.g. two streams), so each file describes one video.
+    pub fn path(&self, format: ExportFormat, source_id: usize) -> String {
+        let ext = match format {
+            ExportFormat::Mot => "mot.txt",
+            ExportFormat::Coco => "coco.json",
+            ExportFormat::Trajectories => "trajectories.geojson",
+        };
+        let stem = &self.source_stems[source_id];
+        let name = if self.source_stems.iter().filter(|s| *s == stem).count() > 1 {
+            format!("{}_{}", stem, source_id)
+        } else {
+            stem.clone()
+        };
+        format!("{}/{}.{}", self.dir.trim_end_matches('/'), name, ext)
+    }
+}
+
+/// File-name stem for per-input outputs: the file name for local files, "stream" otherwise.
+pub fn input_stem(device: &str) -> String {
+    Path::new(device)
+        .file_stem()
+        .filter(|_| !device.contains("://") && device != "test")
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "stream".to_string())
+}
+
+/// Accumulates COCO entries; the document can only be written once all frames are known.
+struct CocoWriter {
+    path: String,
+    images: Vec<String>,
+    annotations: Vec<String>,
+    next_image_id: u64,
+    next_annotation_id: u64,
+}
+
+/// Path of one track: the bottom center of its box per frame.
+struct TrackPath {
+    class_id: i32,
+    first_frame: i64,
+    last_frame: i64,
+    points: Vec<(f32, f32)>,
+}
+
+struct TrajectoryWriter {
+    path: String,
+    tracks: BTreeMap<u64, TrackPath>,
+}
+
+/// Writers per source ID
+pub struct Exporter {
+    mot: BTreeMap<u32, BufWriter<File>>,
+    coco: BTreeMap<u32, CocoWriter>,
+    trajectories: BTreeMap<u32, TrajectoryWriter>,
+    labels: Vec<String>,
+}
+
+impl Exporter {
+    pub fn new(config: &ExportConfig, labels: Vec<String>) -> io::Result<Exporter> {
+        fs::create_dir_all(&config.dir)?;
+
+        let (mut mot, mut coco, mut trajectories) = (BTreeMap::new(), BTreeMap::new(), BTreeMap::new());
+        for source_id in 0..config.source_stems.len() {
+            let key = source_id as u32;
+            if config.formats.contains(&ExportFormat::Mot) {
+                let path = config.path(ExportFormat::Mot, source_id);
+                mot.insert(key, BufWriter::new(File::create(path)?));
+            }
+            if config.formats.contains(&ExportFormat::Coco) {
+                coco.insert(
+                    key,
+                    CocoWriter {
+                        path: config.path(ExportFormat::Coco, source_id),
+                        images: Vec::new(),
+                        annotations: Vec::new(),
+                        next_image_id: 1,
+                        next_annotation_id: 1,
+                    },
+                );
+            }
+            if config.formats.contains(&ExportFormat::Trajectories) {
+                trajectories.insert(
+                    key,
+                    TrajectoryWriter {
+                        path: config.path(ExportFormat::Trajectories, source_id),
+                        tracks: BTreeMap::new(),
+                    },
+                );
+            }
+        }
+
+        Ok(Exporter { mot, coco, trajectories, labels })
+    }
+
+    fn add_frame(&mut self, frame: &FrameDetections) {
+        // MOT and COCO frame indices are 1-based
+        let frame_index = frame.frame_num as i64 + 1;
+
+        if let Some(mot) = self.mot.get_mut(&frame.source_id) {
+            for det in &frame.objects {
+                let (left, top, width, height) = frame.to_source_coords(det);
+                let id = det.track_id.map(|id| id as i64).unwrap_or(-1);
+                if let Err(e) = writeln!(
+                    mot,
+                    "{},{},{:.2},{:.2},{:.2},{:.2},{:.4},-1,-1,-1",
+                    frame_index, id, left, top, width, height, det.confidence
+                ) {
+                    eprintln!("Warning: MOT export write failed: {}", e);
+                }
+            }
+        }
+
+        if let Some(coco) = self.coco.get_mut(&frame.source_id) {
+            let image_id = coco.next_image_id;
+            coco.next_image_id += 1;
+            coco.images.push(format!(
+                "{{\"id\":{},\"file_name\":{},\"width\":{},\"height\":{},\"frame_index\":{}}}",
+                image_id,
+                json::string(&format!("frame_{:06}.jpg", frame_index)),
+                frame.source_width,
+                frame.source_height,
+                frame_index
+            ));
+
+            for det in &frame.objects {
+                let (left, top, width, height) = frame.to_source_coords(det);
+                let track_id = det.track_id.map(|id| id.to_string()).unwrap_or_else(|| "null".to_string());
+                coco.annotations.push(format!(
+                    "{{\"id\":{},\"image_id\":{},\"category_id\":{},\"bbox\":[{},{},{},{}],\"area\":{},\"iscrowd\":0,\"score\":{},\"track_id\":{}}}",
+                    coco.next_annotation_id,
+                    image_id,
+                    det.class_id,
+                    json::number(left),
+                    json::number(top),
+                    json::number(width),
+                    json::number(height),
+                    json::number(width * height),
+                    json::number(det.confidence),
+                    track_id
+                ));
+                coco.next_annotation_id += 1;
+            }
+        }
+
+        if let Some(trajectories) = self.trajectories.get_mut(&frame.source_id) {
+            for det in &frame.objects {
+                let Some(track_id) = det.track_id else { continue };
+                let (left, top, width, height) = frame.to_source_coords(det);
+                let track = trajectories
+                    .tracks
+                    .entry(track_id)
+                    .or_insert_with(|| TrackPath {
+                        class_id: det.class_id,
+                        first_frame: frame_index,
+                        last_frame: frame_index,
+                        points: Vec::new(),
+                    });
+                track.last_frame = frame_index;
+                track.points.push((left + width / 2.0, top + height));
+            }
+        }
+    }
+
+    /// Flushes the MOT files and writes the COCO and trajectory documents. Safe to call more than once.
+    fn write_all(&mut self) -> io::Result<()> {
+        for mot in self.mot.values_mut() {
+            mot.flush()?;
+        }
+
+        for coco in self.coco.values() {
+            let categories: Vec<String> = self
+                .labels
+                .iter()
+                .enumerate()
+                .map(|(id, name)| format!("{{\"id\":{},\"name\":{}}}", id, json::string(name)))
+                .collect();
+
+            let mut file = BufWriter::new(File::create(&coco.path)?);
+            writeln!(file, "{{")?;
+            writeln!(file, "\"images\":[\n{}\n],", coco.images.join(",\n"))?;
+            writeln!(file, "\"annotations\":[\n{}\n],", coco.annotations.join(",\n"))?;
+            writeln!(file, "\"categories\":[{}]", categories.join(","))?;
+            writeln!(file, "}}")?;
+            file.flush()?;
+            println!(
+                "✓ Wrote COCO export: {} ({} images, {} annotations)",
+                coco.path,
+                coco.images.len(),
+                coco.annotations.len()
+            );
+        }
+
+        for trajectories in self.trajectories.values() {
+            let features: Vec<String> = trajectories
+                .tracks
+                .iter()
+                .map(|(track_id, track)| {
+                    let coordinates: Vec<String> = track
+                        .points
+                        .iter()
+                        .map(|(x, y)| format!("[{},{}]", json::number(*x), json::number(*y)))
+                        .collect();
+                    let label = self
+                        .labels
+                        .get(track.class_id as usize)
+                        .cloned()
+                        .unwrap_or_else(|| format!("class{}", track.class_id));
+                    format!(
+                        "{{\"type\":\"Feature\",\"geometry\":{{\"type\":\"LineString\",\"coordinates\":[{}]}},\
+                         \"properties\":{{\"track_id\":{},\"class_id\":{},\"label\":{},\"first_frame\":{},\"last_frame\":{}}}}}",
+                        coordinates.join(","),
+                        track_id,
+                        track.class_id,
+                        json::string(&label),
+                        track.first_frame,
+                        track.last_frame
+                    )
+                })
+                .collect();
+
+            let mut file = BufWriter::new(File::create(&trajectories.path)?);
+            writeln!(file, "{{\"type\":\"FeatureCollection\",\"features\":[\n{}\n]}}", features.join(",\n"))?;
+            file.flush()?;
+            println!("✓ Wrote trajectory export: {} ({} tracks)", trajectories.path, features.len());
+        }
+        Ok(())
+    }
+}
+
+impl FrameObserver for Exporter {
+    fn on_frame(&mut self, frame: &FrameDetections) {
+        self.add_frame(frame);
+    }
+
+    fn finish(&mut self) {
+        if let Err(e) = self.write_all() {
+            eprintln!("Warning: Failed to write detection export: {}", e);
+        }
+    }
+}
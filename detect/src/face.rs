@@ -0,0 +1,262 @@
+use crate::events::{Event, Events};
+use crate::json::{self, Value};
+use crate::reid::{cosine, normalized};
+use gstreamer::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The same face isn't reported again on a source within this time
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Face recognition: a face detector followed by an embedding SGIE on its faces,
+/// matched against a gallery of enrolled embeddings.
+pub struct FaceConfig {
+    pub detector_config: String,
+    pub embedder_config: String,
+    pub detector_id: u32,
+    pub embedder_id: u32,
+    pub gallery_path: String,
+    /// Cosine similarity at or above which a face is recognized
+    pub threshold: f32,
+}
+
+impl FaceConfig {
+    /// Reads FACE_DETECT_CONFIG and FACE_EMBED_CONFIG (both required), FACE_DETECT_ID,
+    /// FACE_EMBED_ID, FACE_GALLERY and FACE_THRESHOLD.
+    pub fn from_env() -> Option<FaceConfig> {
+        let detector_config = env::var("FACE_DETECT_CONFIG").ok();
+        let embedder_config = env::var("FACE_EMBED_CONFIG").ok();
+        let (detector_config, embedder_config) = match (detector_config, embedder_config) {
+            (Some(detector), Some(embedder)) => (detector, embedder),
+            (None, None) => return None,
+            _ => {
+                eprintln!("Warning: Face recognition needs both FACE_DETECT_CONFIG and FACE_EMBED_CONFIG. Disabled.");
+                return None;
+            }
+        };
+        let detector_id = env::var("FACE_DETECT_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(4);
+        let embedder_id = env::var("FACE_EMBED_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(5);
+        let gallery_path = env::var("FACE_GALLERY").unwrap_or_else(|_| "/models/faces.gallery".to_string());
+        let threshold = env::var("FACE_THRESHOLD").ok().and_then(|v| v.parse().ok()).unwrap_or(0.5);
+        Some(FaceConfig { detector_config, embedder_config, detector_id, embedder_id, gallery_path, threshold })
+    }
+
+    /// Face detector on the frame, then the embedder on the detector's objects only.
+    pub fn stage(&self) -> String {
+        format!(
+            "nvinfer name=face_detect config-file-path={} unique-id={} ! \
+             nvinfer name=face_embed process-mode=2 infer-on-gie-id={} config-file-path={} unique-id={} output-tensor-meta=true",
+            self.detector_config, self.detector_id, self.detector_id, self.embedder_config, self.embedder_id
+        )
+    }
+}
+
+/// Enrolled embeddings, one JSON object per line: {"name": ..., "embedding": [...]}.
+/// A name may be enrolled several times (different angles, lighting).
+pub struct Gallery {
+    entries: Vec<(String, Vec<f32>)>,
+}
+
+impl Gallery {
+    pub fn load(path: &str) -> Result<Gallery, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.to_string()),
+        };
+        let mut entries = Vec::new();
+        for (line_no, line) in contents.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let value = json::parse(line).map_err(|e| format!("line {}: {}", line_no + 1, e))?;
+            let name = match value.get("name") {
+                Some(Value::String(name)) => name.clone(),
+                _ => return Err(format!("line {}: missing name", line_no + 1)),
+            };
+            let embedding: Vec<f32> = value
+                .get("embedding")
+                .and_then(Value::as_array)
+                .map(|values| values.iter().filter_map(Value::as_f64).map(|v| v as f32).collect())
+                .unwrap_or_default();
+            if embedding.is_empty() {
+                return Err(format!("line {}: missing embedding", line_no + 1));
+            }
+            entries.push((name, normalized(&embedding)));
+        }
+        Ok(Gallery { entries })
+    }
+
+    pub fn append(path: &str, name: &str, embedding: &[f32]) -> std::io::Result<()> {
+        let entry = Value::Object(vec![
+            ("name".to_string(), Value::from(name)),
+            (
+                "embedding".to_string(),
+                Value::Array(embedding.iter().map(|v| Value::from(*v)).collect()),
+            ),
+        ]);
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", entry.to_json())
+    }
+
+    /// Best matching name at or above `threshold`.
+    fn best_match(&self, embedding: &[f32], threshold: f32) -> Option<(&str, f32)> {
+        self.entries
+            .iter()
+            .filter(|(_, e)| e.len() == embedding.len())
+            .map(|(name, e)| (name.as_str(), cosine(e, embedding)))
+            .filter(|(_, similarity)| *similarity >= threshold)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+/// Calls `f` with the source, frame number, object meta and embedding of every face in the batch.
+fn for_each_face(
+    batch: &mut nvds::NvDsBatchMeta,
+    embedder_id: u32,
+    mut f: impl FnMut(u32, i32, &mut nvds::NvDsObjectMeta, Vec<f32>),
+) {
+    for frame in batch.frames() {
+        let (source_id, frame_num) = (frame.source_id, frame.frame_num);
+        for obj in frame.objects() {
+            let embedding = obj.tensor_meta(embedder_id).and_then(|t| t.float_output(0)).map(|(_, v)| normalized(v));
+            if let Some(embedding) = embedding {
+                f(source_id, frame_num, obj, embedding);
+            }
+        }
+    }
+}
+
+/// Labels recognized faces on the OSD and emits face_recognized events.
+pub fn attach(bin: &gstreamer::Element, config: FaceConfig, events: Events) {
+    let gallery = match Gallery::load(&config.gallery_path) {
+        Ok(gallery) => gallery,
+        Err(e) => {
+            eprintln!("Warning: Could not load face gallery {}: {}. Face recognition disabled.", config.gallery_path, e);
+            return;
+        }
+    };
+    if gallery.entries.is_empty() {
+        eprintln!("Warning: Face gallery {} is empty; enroll faces with `detect enroll`", config.gallery_path);
+    }
+    let embed = match bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("face_embed")) {
+        Some(embed) => embed,
+        None => {
+            eprintln!("Warning: face embedding nvinfer not found, face recognition disabled");
+            return;
+        }
+    };
+    let src_pad = embed.static_pad("src").expect("nvinfer has a src pad");
+    let reported: Mutex<HashMap<(u32, String), Instant>> = Mutex::new(HashMap::new());
+
+    src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            let mut reported = reported.lock().unwrap();
+            for_each_face(batch, config.embedder_id, |source_id, frame_num, face, embedding| {
+                let Some((name, similarity)) = gallery.best_match(&embedding, config.threshold) else {
+                    return;
+                };
+                face.append_display_text(&format!(" {}", name));
+
+                let key = (source_id, name.to_string());
+                if reported.get(&key).is_some_and(|at| at.elapsed() < REPORT_INTERVAL) {
+                    return;
+                }
+                reported.insert(key, Instant::now());
+                let mut event = Event::new("face_recognized", source_id, frame_num)
+                    .with("name", name)
+                    .with("similarity", similarity);
+                // SAFETY: a face detected by a secondary detector points at its person object
+                if let Some(track_id) = unsafe { face.parent.as_ref() }.and_then(|p| p.track_id()) {
+                    event = event.with("track_id", track_id);
+                }
+                events.lock().unwrap().emit(event);
+            });
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
+
+/// `detect enroll <name> <image>...`: runs the face models over still images and adds the
+/// largest face of each image to the gallery under `name`, averaged into one entry.
+pub fn enroll_command(args: &[String]) -> ! {
+    if args.len() < 2 {
+        eprintln!("Usage: detect enroll <name> <image.jpg|png>...");
+        std::process::exit(2);
+    }
+    let Some(config) = FaceConfig::from_env() else {
+        eprintln!("Error: enroll needs FACE_DETECT_CONFIG and FACE_EMBED_CONFIG");
+        std::process::exit(2);
+    };
+    let (name, images) = (&args[0], &args[1..]);
+
+    let mut sum: Vec<f32> = Vec::new();
+    for image in images {
+        let Some(embedding) = embed_image(&config, image) else {
+            eprintln!("Error: No face found in {}", image);
+            std::process::exit(1);
+        };
+        if sum.is_empty() {
+            sum = embedding;
+        } else if sum.len() == embedding.len() {
+            sum.iter_mut().zip(&embedding).for_each(|(s, e)| *s += e);
+        }
+    }
+    if let Err(e) = Gallery::append(&config.gallery_path, name, &normalized(&sum)) {
+        eprintln!("Error: Failed to write face gallery {}: {}", config.gallery_path, e);
+        std::process::exit(1);
+    }
+    println!("✓ Enrolled {} from {} image(s) into {}", name, images.len(), config.gallery_path);
+    std::process::exit(0);
+}
+
+/// Embedding of the largest face in a still image.
+fn embed_image(config: &FaceConfig, image: &str) -> Option<Vec<f32>> {
+    let pipeline_str = format!(
+        "filesrc location={} ! decodebin ! videoconvert ! nvvideoconvert ! \
+         video/x-raw(memory:NVMM),format=NV12 ! m.sink_0 nvstreammux name=m width=1280 height=720 batch-size=1 ! \
+         {} ! fakesink",
+        image,
+        config.stage()
+    );
+    let pipeline = gstreamer::parse_launch(&pipeline_str).expect("Failed to create enroll pipeline");
+    let embed = pipeline
+        .downcast_ref::<gstreamer::Bin>()
+        .and_then(|b| b.by_name("face_embed"))
+        .expect("face embedding nvinfer");
+    let found: Arc<Mutex<Option<Vec<f32>>>> = Arc::new(Mutex::new(None));
+    let probe_found = found.clone();
+    let embedder_id = config.embedder_id;
+    embed
+        .static_pad("src")
+        .expect("nvinfer has a src pad")
+        .add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+            if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+                let mut found = probe_found.lock().unwrap();
+                let mut largest = 0.0;
+                for_each_face(batch, embedder_id, |_, _, face, embedding| {
+                    let area = face.rect_params.width * face.rect_params.height;
+                    if area > largest {
+                        largest = area;
+                        *found = Some(embedding);
+                    }
+                });
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+
+    pipeline.set_state(gstreamer::State::Playing).expect("Unable to start the enroll pipeline");
+    let bus = pipeline.bus().expect("Pipeline without bus");
+    if let Some(msg) = bus.timed_pop_filtered(
+        gstreamer::ClockTime::NONE,
+        &[gstreamer::MessageType::Eos, gstreamer::MessageType::Error],
+    ) {
+        if let gstreamer::MessageView::Error(err) = msg.view() {
+            eprintln!("Error: {}: {}", image, err.error());
+        }
+    }
+    pipeline.set_state(gstreamer::State::Null).expect("Unable to stop the enroll pipeline");
+    let embedding = found.lock().unwrap().take();
+    embedding
+}
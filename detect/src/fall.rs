@@ -0,0 +1,180 @@
+use crate::clips::Clips;
+use crate::detections::{Detection, FrameDetections, FrameObserver};
+use crate::events::{Event, Events};
+use std::collections::{HashMap, VecDeque};
+use std::env;
+
+/// Tracks not seen for this long are forgotten
+const TRACK_TIMEOUT_SECONDS: f64 = 5.0;
+
+/// COCO keypoint indices of the shoulders and hips
+const SHOULDERS: [usize; 2] = [5, 6];
+const HIPS: [usize; 2] = [11, 12];
+
+/// Keypoints below this confidence are ignored
+const MIN_KEYPOINT_CONFIDENCE: f32 = 0.3;
+
+pub struct FallConfig {
+    /// Drop of the body center, as a fraction of the person's height, that counts as a fall
+    pub drop: f32,
+    /// Seconds the drop must happen within
+    pub window: f64,
+    /// Seconds a person must stay down before the fall is reported
+    pub confirm_secs: f64,
+    /// Torso angle from vertical (degrees) above which a person is lying
+    pub lying_angle: f32,
+    pub person_label: String,
+}
+
+impl FallConfig {
+    /// Reads FALL_DETECT=true / FALL_DROP / FALL_WINDOW / FALL_CONFIRM_SECONDS / FALL_ANGLE
+    /// / FALL_PERSON_LABEL.
+    pub fn from_env() -> Option<FallConfig> {
+        if env::var("FALL_DETECT").map(|v| v != "true").unwrap_or(true) {
+            return None;
+        }
+        let var = |name: &str, default: f64| env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        Some(FallConfig {
+            drop: var("FALL_DROP", 0.3) as f32,
+            window: var("FALL_WINDOW", 1.0),
+            confirm_secs: var("FALL_CONFIRM_SECONDS", 2.0),
+            lying_angle: var("FALL_ANGLE", 60.0) as f32,
+            person_label: env::var("FALL_PERSON_LABEL").unwrap_or_else(|_| "person".to_string()),
+        })
+    }
+}
+
+/// Body center y, person height and torso angle from vertical in degrees. Uses the
+/// shoulder and hip keypoints of pose models, otherwise the box shape (a box wider than
+/// tall reads as lying).
+fn posture(det: &Detection) -> (f32, f32, f32) {
+    let mean = |indices: [usize; 2]| -> Option<(f32, f32)> {
+        let points: Vec<&[f32; 3]> = indices.iter().filter_map(|&i| det.keypoints.get(i)).collect();
+        if points.len() < 2 || points.iter().any(|k| k[2] < MIN_KEYPOINT_CONFIDENCE) {
+            return None;
+        }
+        Some(((points[0][0] + points[1][0]) / 2.0, (points[0][1] + points[1][1]) / 2.0))
+    };
+    let height = det.height.max(det.width);
+    match (mean(SHOULDERS), mean(HIPS)) {
+        (Some((sx, sy)), Some((hx, hy))) => (hy, height, (hx - sx).abs().atan2((hy - sy).abs()).to_degrees()),
+        _ => (det.top + det.height / 2.0, height, det.width.atan2(det.height).to_degrees()),
+    }
+}
+
+enum FallState {
+    Upright,
+    /// Dropped and lying since the given time, not confirmed yet
+    Falling(f64),
+    /// Fall reported; `since` is when the person went down, `up_since` when they started getting up
+    Fallen { since: f64, up_since: Option<f64> },
+}
+
+struct PersonTrack {
+    // (time, body center y, height, lying)
+    history: VecDeque<(f64, f32, f32, bool)>,
+    state: FallState,
+    last_seen: f64,
+}
+
+/// Flags tracked people whose body center drops quickly and who then stay lying down.
+/// Emits fall_detected (with a clip when CLIP_DIR is set) and fall_recovered.
+pub struct FallDetector {
+    config: FallConfig,
+    person_class: Option<i32>,
+    events: Events,
+    clips: Option<Clips>,
+    tracks: HashMap<(u32, u64), PersonTrack>,
+}
+
+impl FallDetector {
+    pub fn new(config: FallConfig, labels: &[String], events: Events, clips: Option<Clips>) -> FallDetector {
+        let person_class = labels.iter().position(|l| l.eq_ignore_ascii_case(&config.person_label)).map(|i| i as i32);
+        if person_class.is_none() {
+            eprintln!("Warning: FALL_PERSON_LABEL '{}' is not in the labels file", config.person_label);
+        }
+        FallDetector { config, person_class, events, clips, tracks: HashMap::new() }
+    }
+
+    fn update(&self, track: &mut PersonTrack, now: f64, center: f32, height: f32, lying: bool) -> Option<(&'static str, f64)> {
+        track.history.push_back((now, center, height, lying));
+        while track.history.front().is_some_and(|(t, ..)| now - t > self.config.window) {
+            track.history.pop_front();
+        }
+        match track.state {
+            FallState::Upright => {
+                // A fast drop from an upright posture into a lying one
+                let dropped = track
+                    .history
+                    .iter()
+                    .any(|&(_, c, h, was_lying)| !was_lying && center - c > self.config.drop * h);
+                if lying && dropped {
+                    track.state = FallState::Falling(now);
+                }
+                None
+            }
+            FallState::Falling(_) if !lying => {
+                track.state = FallState::Upright;
+                None
+            }
+            FallState::Falling(since) => {
+                if now - since >= self.config.confirm_secs {
+                    track.state = FallState::Fallen { since, up_since: None };
+                    return Some(("fall_detected", now - since));
+                }
+                None
+            }
+            FallState::Fallen { since, up_since } => {
+                if lying {
+                    track.state = FallState::Fallen { since, up_since: None };
+                    return None;
+                }
+                let up_since = up_since.unwrap_or(now);
+                if now - up_since >= self.config.confirm_secs {
+                    track.state = FallState::Upright;
+                    return Some(("fall_recovered", up_since - since));
+                }
+                track.state = FallState::Fallen { since, up_since: Some(up_since) };
+                None
+            }
+        }
+    }
+}
+
+impl FrameObserver for FallDetector {
+    fn on_frame(&mut self, frame: &FrameDetections) {
+        let now = frame.seconds();
+        for det in &frame.objects {
+            let Some(track_id) = det.track_id else { continue };
+            if Some(det.class_id) != self.person_class {
+                continue;
+            }
+            let (center, height, angle) = posture(det);
+            let lying = angle > self.config.lying_angle;
+            let mut track = self.tracks.remove(&(frame.source_id, track_id)).unwrap_or(PersonTrack {
+                history: VecDeque::new(),
+                state: FallState::Upright,
+                last_seen: now,
+            });
+            track.last_seen = now;
+            let change = self.update(&mut track, now, center, height, lying);
+            self.tracks.insert((frame.source_id, track_id), track);
+
+            let Some((kind, seconds)) = change else { continue };
+            let mut event = Event::new(kind, frame.source_id, frame.frame_num)
+                .with("track_id", track_id)
+                .with("pose", !det.keypoints.is_empty());
+            if kind == "fall_recovered" {
+                event = event.with("fallen_seconds", seconds);
+            } else {
+                event = event.with("down_seconds", seconds).with("angle", angle);
+                if let Some(dir) = self.clips.as_ref().and_then(|c| c.trigger(frame.source_id)) {
+                    event = event.with("clip_dir", dir);
+                }
+            }
+            self.events.lock().unwrap().emit(event);
+        }
+        self.tracks
+            .retain(|(source, _), t| *source != frame.source_id || now - t.last_seen <= TRACK_TIMEOUT_SECONDS);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::json::Value;
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Wait before reopening a GPS feed that failed or ended
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+const GPSD_PORT: u16 = 2947;
+
+/// Where NMEA sentences come from.
+#[derive(Clone, Debug)]
+pub enum GpsSource {
+    /// A serial device (or FIFO) that prints NMEA, e.g. /dev/ttyUSB0
+    Serial { device: String, baud: u32 },
+    /// gpsd, asked to relay the raw NMEA of its receiver
+    Gpsd { host: String },
+}
+
+pub struct GpsConfig {
+    pub source: GpsSource,
+    /// A fix older than this is not attached to anything
+    pub max_age: Duration,
+}
+
+impl GpsConfig {
+    /// Reads GPS_SOURCE (`/dev/tty...` or `gpsd://host[:port]`), GPS_BAUD and GPS_MAX_AGE.
+    pub fn from_env() -> Option<GpsConfig> {
+        let spec = env::var("GPS_SOURCE").ok().filter(|v| !v.trim().is_empty())?;
+        let spec = spec.trim();
+        let source = match spec.strip_prefix("gpsd://") {
+            Some(rest) => {
+                let host = rest.trim_end_matches('/');
+                let host = if host.is_empty() { "localhost" } else { host };
+                let host = if host.contains(':') { host.to_string() } else { format!("{}:{}", host, GPSD_PORT) };
+                GpsSource::Gpsd { host }
+            }
+            None if spec.contains("://") => {
+                eprintln!("Error: Unknown GPS_SOURCE '{}' (expected a serial device or gpsd://host[:port])", spec);
+                std::process::exit(2);
+            }
+            None => {
+                let baud = env::var("GPS_BAUD").ok().and_then(|v| v.parse().ok()).unwrap_or(9600);
+                GpsSource::Serial { device: spec.to_string(), baud }
+            }
+        };
+        let max_age = env::var("GPS_MAX_AGE")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| *v > 0.0)
+            .unwrap_or(5.0);
+        Some(GpsConfig { source, max_age: Duration::from_secs_f64(max_age) })
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.source {
+            GpsSource::Serial { device, baud } => format!("{} at {} baud", device, baud),
+            GpsSource::Gpsd { host } => format!("gpsd at {}", host),
+        }
+    }
+}
+
+/// Latest position from the receiver.
+#[derive(Clone, Debug)]
+pub struct Fix {
+    pub lat: f64,
+    pub lon: f64,
+    pub altitude_m: Option<f64>,
+    pub speed_kmh: Option<f64>,
+    /// Degrees clockwise from true north
+    pub course: Option<f64>,
+    pub at: Instant,
+}
+
+impl Fix {
+    /// GeoJSON Point geometry; coordinates are longitude first.
+    pub fn geometry(&self) -> Value {
+        let mut coordinates = vec![Value::from(self.lon), Value::from(self.lat)];
+        coordinates.extend(self.altitude_m.map(Value::from));
+        Value::Object(vec![
+            ("type".to_string(), Value::from("Point")),
+            ("coordinates".to_string(), Value::Array(coordinates)),
+        ])
+    }
+}
+
+/// Shared between the reader thread and everything that tags its output with the position.
+#[derive(Clone)]
+pub struct Gps {
+    fix: Arc<Mutex<Option<Fix>>>,
+    max_age: Duration,
+}
+
+impl Gps {
+    /// The latest fix, unless the receiver lost it or went quiet for longer than GPS_MAX_AGE.
+    pub fn current(&self) -> Option<Fix> {
+        self.fix.lock().unwrap().clone().filter(|fix| fix.at.elapsed() <= self.max_age)
+    }
+}
+
+/// `ddmm.mmmm` + hemisphere to signed decimal degrees.
+fn coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    let raw: f64 = value.parse().ok()?;
+    let degrees = (raw / 100.0).trunc();
+    let decimal = degrees + (raw - degrees * 100.0) / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(decimal),
+        "S" | "W" => Some(-decimal),
+        _ => None,
+    }
+}
+
+/// Fields of a sentence whose `*hh` checksum matches, without the `$` and talker-independent
+/// (`$GPRMC` and `$GNRMC` both give `RMC` as the first field).
+fn fields(line: &str) -> Option<Vec<&str>> {
+    let body = line.trim().strip_prefix('$')?;
+    let (body, checksum) = body.split_once('*')?;
+    let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+    if body.bytes().fold(0, |sum, b| sum ^ b) != expected {
+        return None;
+    }
+    let mut fields: Vec<&str> = body.split(',').collect();
+    fields[0] = fields[0].get(2..)?;
+    Some(fields)
+}
+
+/// Updates `fix` from an RMC (position, speed, course) or GGA (position, altitude) sentence.
+/// Sentences without a valid fix clear it.
+fn apply(fix: &mut Option<Fix>, line: &str) {
+    let Some(f) = fields(line) else { return };
+    let field = |i: usize| f.get(i).copied().unwrap_or("");
+    let number = |i: usize| field(i).parse::<f64>().ok();
+    let (valid, lat, lon) = match f[0] {
+        "RMC" => (field(2) == "A", coordinate(field(3), field(4)), coordinate(field(5), field(6))),
+        "GGA" => (!matches!(field(6), "" | "0"), coordinate(field(2), field(3)), coordinate(field(4), field(5))),
+        _ => return,
+    };
+    let (Some(lat), Some(lon), true) = (lat, lon, valid) else {
+        *fix = None;
+        return;
+    };
+    let previous = fix.take();
+    let mut next = Fix {
+        lat,
+        lon,
+        altitude_m: previous.as_ref().and_then(|p| p.altitude_m),
+        speed_kmh: previous.as_ref().and_then(|p| p.speed_kmh),
+        course: previous.as_ref().and_then(|p| p.course),
+        at: Instant::now(),
+    };
+    if f[0] == "RMC" {
+        next.speed_kmh = number(7).map(|knots| knots * 1.852);
+        next.course = number(8);
+    } else {
+        next.altitude_m = number(9);
+    }
+    *fix = Some(next);
+}
+
+/// An open NMEA stream.
+fn open(source: &GpsSource) -> Result<Box<dyn Read + Send>, String> {
+    match source {
+        GpsSource::Serial { device, baud } => {
+            // Raw mode so the tty doesn't echo or rewrite line endings; harmless on a FIFO
+            let configured = Command::new("stty").args(["-F", device, &baud.to_string(), "raw", "-echo"]).status();
+            if !configured.is_ok_and(|s| s.success()) {
+                eprintln!("Warning: Could not set {} to {} baud with stty", device, baud);
+            }
+            let file = File::open(device).map_err(|e| e.to_string())?;
+            Ok(Box::new(file))
+        }
+        GpsSource::Gpsd { host } => {
+            let addr = host
+                .to_socket_addrs()
+                .map_err(|e| e.to_string())?
+                .next()
+                .ok_or("host not found")?;
+            let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(5)).map_err(|e| e.to_string())?;
+            // JSON status lines come too; only the `$` lines are parsed
+            stream
+                .write_all(b"?WATCH={\"enable\":true,\"nmea\":true};\n")
+                .map_err(|e| e.to_string())?;
+            Ok(Box::new(stream))
+        }
+    }
+}
+
+/// Starts a thread that keeps the position up to date, reopening the feed whenever it fails.
+pub fn spawn(config: GpsConfig) -> Gps {
+    let gps = Gps { fix: Arc::new(Mutex::new(None)), max_age: config.max_age };
+    let fix = gps.fix.clone();
+    let source = config.source;
+    thread::spawn(move || loop {
+        match open(&source) {
+            Ok(stream) => {
+                for line in BufReader::new(stream).lines() {
+                    match line {
+                        Ok(line) => apply(&mut fix.lock().unwrap(), &line),
+                        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => continue,
+                        Err(e) => {
+                            eprintln!("Warning: GPS feed failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => eprintln!("Warning: Could not open GPS feed: {}", e),
+        }
+        thread::sleep(RETRY_DELAY);
+    });
+    gps
+}
@@ -0,0 +1,800 @@
+use gstreamer::prelude::*;
+use std::collections::HashSet;
+
+/// Elements that only take and produce GPU (NVMM) surfaces
+const NVMM_ELEMENTS: &[&str] = &[
+    "nvstreammux",
+    "nvinfer",
+    "nvinferserver",
+    "nvtracker",
+    "nvdsosd",
+    "nvmultistreamtiler",
+    "nvdsanalytics",
+    "nvdspreprocess",
+    "nvof",
+    "nvofvisual",
+    "nvdsvideotemplate",
+    "nvdewarper",
+    "nvsegvisual",
+];
+
+/// Elements that can't read NVMM surfaces
+const SYSTEM_MEMORY_ELEMENTS: &[&str] =
+    &["videoconvert", "videoscale", "x264enc", "x265enc", "jpegenc", "pngenc", "ximagesink", "xvimagesink"];
+
+/// Elements that pass buffers through untouched
+const PASSTHROUGH_ELEMENTS: &[&str] = &["queue", "tee", "valve", "identity", "capsfilter"];
+
+/// Encoders and the codec they produce
+const ENCODERS: &[(&str, &str)] = &[
+    ("nvv4l2h264enc", "h264"),
+    ("nvv4l2h265enc", "h265"),
+    ("x264enc", "h264"),
+    ("x265enc", "h265"),
+    ("jpegenc", "jpeg"),
+    ("nvjpegenc", "jpeg"),
+];
+
+/// Elements that need one codec's bitstream
+const CODEC_CONSUMERS: &[(&str, &str)] = &[
+    ("h264parse", "h264"),
+    ("h265parse", "h265"),
+    ("rtph264pay", "h264"),
+    ("rtph265pay", "h265"),
+    ("rtpjpegpay", "jpeg"),
+    ("hlssink2", "h264"),
+];
+
+/// Stock GStreamer stand-ins for NVIDIA elements in software (dev mode) pipelines.
+/// NVMM-only elements not listed here become `identity`, so probes still find them by name.
+const SOFTWARE_ELEMENTS: &[(&str, &str)] = &[
+    ("nvstreammux", "compositor"),
+    ("nvvideoconvert", "videoconvert"),
+    ("nvurisrcbin", "uridecodebin"),
+    ("nvv4l2decoder", "decodebin"),
+    ("nvv4l2h264enc", "x264enc"),
+    ("nvv4l2h265enc", "x265enc"),
+    ("nvjpegenc", "jpegenc"),
+    ("nveglglessink", "autovideosink"),
+];
+
+/// Properties that mean the same on the software stand-in; everything else is dropped
+const SOFTWARE_PROPERTIES: &[&str] = &["name", "uri", "location", "device"];
+
+/// One element with its properties, e.g. `nvinfer name=pgie config-file-path=...`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+    pub factory: String,
+    pub properties: Vec<(String, String)>,
+}
+
+impl Element {
+    pub fn new(factory: &str) -> Element {
+        Element { factory: factory.to_string(), properties: Vec::new() }
+    }
+
+    pub fn prop(mut self, key: &str, value: impl ToString) -> Element {
+        self.properties.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties.iter().rev().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Software stand-in for an NVIDIA element; other elements are returned unchanged.
+    fn software(&self) -> Element {
+        let factory = match software_factory(&self.factory) {
+            Some(factory) => factory,
+            None => return self.clone(),
+        };
+        let mut element = Element::new(factory);
+        element.properties =
+            self.properties.iter().filter(|(key, _)| SOFTWARE_PROPERTIES.contains(&key.as_str())).cloned().collect();
+        if factory == "x264enc" || factory == "x265enc" {
+            element = element.prop("tune", "zerolatency");
+        }
+        element
+    }
+
+    fn render(&self) -> String {
+        let mut out = self.factory.clone();
+        for (key, value) in &self.properties {
+            out.push_str(&format!(" {}={}", key, value));
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Element(Element),
+    /// Caps filter, e.g. `video/x-raw(memory:NVMM),format=RGBA`
+    Caps(String),
+    /// Launch fragment kept as-is (pad references, quoted values); opaque to validation
+    Raw(String),
+    /// Tee feeding each branch; a chain continuing after it does so through a queue
+    Tee { name: String, branches: Vec<Chain> },
+}
+
+/// Linear run of nodes linked one after the other.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chain {
+    pub nodes: Vec<Node>,
+}
+
+impl Chain {
+    pub fn new() -> Chain {
+        Chain::default()
+    }
+
+    /// Parses a parse_launch fragment (`a k=v ! caps ! b`) into nodes. Fragments that
+    /// reference pads by name or quote values are kept whole as a Raw node.
+    pub fn parse(fragment: &str) -> Chain {
+        let fragment = fragment.trim().trim_end_matches('!').trim();
+        if fragment.is_empty() {
+            return Chain::new();
+        }
+        let opaque = fragment.contains('"')
+            || fragment.contains('\'')
+            || fragment.split_whitespace().any(|token| token.contains('.') && !token.contains('='));
+        if opaque {
+            return Chain { nodes: vec![Node::Raw(fragment.to_string())] };
+        }
+        let nodes = fragment
+            .split('!')
+            .map(str::trim)
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                let mut tokens = segment.split_whitespace();
+                let first = tokens.next().unwrap_or_default();
+                if first.contains('/') {
+                    return Node::Caps(segment.to_string());
+                }
+                let mut element = Element::new(first);
+                for token in tokens {
+                    match token.split_once('=') {
+                        Some((key, value)) => element.properties.push((key.to_string(), value.to_string())),
+                        None => return Node::Raw(segment.to_string()),
+                    }
+                }
+                Node::Element(element)
+            })
+            .collect();
+        Chain { nodes }
+    }
+
+    /// Appends a parse_launch fragment; empty fragments are skipped.
+    pub fn then(mut self, fragment: &str) -> Chain {
+        self.nodes.extend(Chain::parse(fragment).nodes);
+        self
+    }
+
+    pub fn element(mut self, element: Element) -> Chain {
+        self.nodes.push(Node::Element(element));
+        self
+    }
+
+    pub fn append(mut self, other: Chain) -> Chain {
+        self.nodes.extend(other.nodes);
+        self
+    }
+
+    /// Tees off side branches, continuing on the main path. No-op without branches.
+    pub fn tee(mut self, name: &str, branches: Vec<Chain>) -> Chain {
+        if !branches.is_empty() {
+            self.nodes.push(Node::Tee { name: name.to_string(), branches });
+        }
+        self
+    }
+
+    /// Ends the chain in every branch; a single branch is linked directly.
+    pub fn fan_out(self, name: &str, mut branches: Vec<Chain>) -> Chain {
+        if branches.len() == 1 {
+            return self.append(branches.remove(0));
+        }
+        self.tee(name, branches)
+    }
+
+    /// The same chain without NVIDIA elements or NVMM caps.
+    pub fn software(&self) -> Chain {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| match node {
+                Node::Element(element) => Node::Element(element.software()),
+                Node::Caps(caps) => Node::Caps(caps.replace("(memory:NVMM)", "")),
+                Node::Raw(raw) => Node::Raw(software_fragment(raw)),
+                Node::Tee { name, branches } => {
+                    Node::Tee { name: name.clone(), branches: branches.iter().map(Chain::software).collect() }
+                }
+            })
+            .collect();
+        Chain { nodes }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut continuing_tee: Option<&str> = None;
+        for node in &self.nodes {
+            let text = match node {
+                Node::Element(element) => element.render(),
+                Node::Caps(caps) | Node::Raw(caps) => caps.clone(),
+                Node::Tee { name, branches } => {
+                    let mut text = format!("tee name={}", name);
+                    for branch in branches {
+                        text.push_str(&format!(" {}. ! {}", name, branch.render()));
+                    }
+                    text
+                }
+            };
+            if let Some(name) = continuing_tee.take() {
+                out.push_str(&format!(" {}. ! queue", name));
+            }
+            if !out.is_empty() {
+                out.push_str(" ! ");
+            }
+            out.push_str(&text);
+            if let Node::Tee { name, .. } = node {
+                continuing_tee = Some(name);
+            }
+        }
+        out
+    }
+}
+
+/// Whole pipeline: one chain per source into nvstreammux, then the main path.
+pub struct Graph {
+    pub sources: Vec<Chain>,
+    pub mux: Element,
+    pub main: Chain,
+}
+
+/// What flows on a link, as far as validation can tell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Memory {
+    Nvmm,
+    System,
+    Encoded(&'static str),
+    Unknown,
+}
+
+impl Graph {
+    fn mux_name(&self) -> &str {
+        self.mux.get("name").unwrap_or("m")
+    }
+
+    /// parse_launch description of the whole pipeline.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (index, source) in self.sources.iter().enumerate() {
+            out.push_str(&format!("{} ! {}.sink_{} ", source.render(), self.mux_name(), index));
+        }
+        out.push_str(&self.mux.render());
+        let main = self.main.render();
+        if !main.is_empty() {
+            out.push_str(&format!(" ! {}", main));
+        }
+        out
+    }
+
+    /// Checks the graph before it is built: GPU memory (NVMM) continuity, encoders
+    /// matching their parsers/payloaders, request pad counts and unique names.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        for (index, source) in self.sources.iter().enumerate() {
+            let context = format!("source {}", index);
+            if check_chain(source, Memory::Unknown, &context, &mut errors) == Memory::System {
+                errors.push(format!(
+                    "{}: nvstreammux needs NVMM input - end the source with nvvideoconvert",
+                    context
+                ));
+            }
+        }
+        check_chain(&self.main, Memory::Nvmm, "pipeline", &mut errors);
+
+        if let Some(batch) = self.mux.get("batch-size").and_then(|b| b.parse::<usize>().ok()) {
+            if batch != self.sources.len() {
+                errors.push(format!("nvstreammux batch-size={} but {} sources are linked", batch, self.sources.len()));
+            }
+        }
+
+        let mut names = HashSet::new();
+        let mut check_name = |name: &str| {
+            if !names.insert(name.to_string()) {
+                errors.push(format!("element name '{}' is used twice", name));
+            }
+        };
+        if let Some(name) = self.mux.get("name") {
+            check_name(name);
+        }
+        for chain in self.sources.iter().chain(std::iter::once(&self.main)) {
+            for name in chain_names(chain) {
+                check_name(&name);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The same pipeline from stock GStreamer elements, for machines without DeepStream.
+    /// Sources are composited instead of batched and inference is a pass-through, so
+    /// frames flow end to end but carry no detections.
+    pub fn software(&self) -> Graph {
+        Graph {
+            sources: self.sources.iter().map(Chain::software).collect(),
+            mux: self.mux.software(),
+            main: self.main.software(),
+        }
+    }
+
+    /// Builds the pipeline element by element instead of through parse_launch.
+    pub fn build(&self) -> Result<gstreamer::Pipeline, String> {
+        let pipeline = gstreamer::Pipeline::new(None);
+        let mux = make(&self.mux)?;
+        pipeline.add(&mux).map_err(|e| e.to_string())?;
+        let mux_name = self.mux_name();
+        for (index, source) in self.sources.iter().enumerate() {
+            let last = build_chain(&pipeline, source, None)?
+                .ok_or_else(|| format!("source {} is empty", index))?;
+            link(&last, &mux, Some(&format!("sink_{}", index)))
+                .map_err(|_| format!("could not link {} to {}", last.name(), mux_name))?;
+        }
+        build_chain(&pipeline, &self.main, Some(mux))?;
+        Ok(pipeline)
+    }
+}
+
+fn software_factory(factory: &str) -> Option<&'static str> {
+    match SOFTWARE_ELEMENTS.iter().find(|(f, _)| *f == factory) {
+        Some((_, software)) => Some(software),
+        None if factory_in(factory, NVMM_ELEMENTS) => Some("identity"),
+        None => None,
+    }
+}
+
+/// Raw fragments are converted token by token: NVIDIA factories are replaced and their
+/// properties dropped the same way as for parsed elements.
+fn software_fragment(fragment: &str) -> String {
+    let mut tokens = Vec::new();
+    let mut dropping = false;
+    for token in fragment.split_whitespace() {
+        if let Some(factory) = software_factory(token) {
+            tokens.push(factory.to_string());
+            if factory == "x264enc" || factory == "x265enc" {
+                tokens.push("tune=zerolatency".to_string());
+            }
+            dropping = true;
+            continue;
+        }
+        match token.split_once('=') {
+            Some((key, _)) if dropping => {
+                if SOFTWARE_PROPERTIES.contains(&key) {
+                    tokens.push(token.to_string());
+                }
+            }
+            _ => {
+                dropping = false;
+                tokens.push(token.replace("(memory:NVMM)", ""));
+            }
+        }
+    }
+    tokens.join(" ")
+}
+
+fn factory_in(factory: &str, list: &[&str]) -> bool {
+    list.contains(&factory)
+}
+
+fn codec_of(factory: &str, table: &[(&str, &'static str)]) -> Option<&'static str> {
+    table.iter().find(|(f, _)| *f == factory).map(|(_, codec)| *codec)
+}
+
+/// Walks a chain tracking what memory flows on each link; returns the state at its end.
+fn check_chain(chain: &Chain, mut memory: Memory, context: &str, errors: &mut Vec<String>) -> Memory {
+    let mut after_converter = false;
+    for node in &chain.nodes {
+        match node {
+            Node::Element(element) => {
+                let factory = element.factory.as_str();
+                if factory_in(factory, NVMM_ELEMENTS) || factory.starts_with("nvv4l2h26") {
+                    if memory == Memory::System {
+                        errors.push(format!(
+                            "{}: {} needs NVMM input but gets system memory - insert nvvideoconvert ! video/x-raw(memory:NVMM) before it",
+                            context, factory
+                        ));
+                    }
+                } else if factory_in(factory, SYSTEM_MEMORY_ELEMENTS) && memory == Memory::Nvmm {
+                    errors.push(format!(
+                        "{}: {} can't read NVMM surfaces - insert nvvideoconvert ! video/x-raw before it",
+                        context, factory
+                    ));
+                }
+                if let Some(codec) = codec_of(factory, CODEC_CONSUMERS) {
+                    match memory {
+                        Memory::Encoded(produced) if produced != codec => errors.push(format!(
+                            "{}: {} expects {} but the encoder before it produces {}",
+                            context, factory, codec, produced
+                        )),
+                        Memory::Nvmm | Memory::System => errors.push(format!(
+                            "{}: {} expects {} but gets raw video - an encoder is missing",
+                            context, factory, codec
+                        )),
+                        _ => {}
+                    }
+                }
+
+                after_converter = factory == "nvvideoconvert";
+                memory = if let Some(codec) = codec_of(factory, ENCODERS) {
+                    Memory::Encoded(codec)
+                } else if factory_in(factory, NVMM_ELEMENTS) {
+                    Memory::Nvmm
+                } else if factory == "videoconvert" || factory == "videoscale" {
+                    Memory::System
+                } else if factory_in(factory, PASSTHROUGH_ELEMENTS) || codec_of(factory, CODEC_CONSUMERS).is_some() {
+                    memory
+                } else {
+                    // nvvideoconvert negotiates whatever follows; anything else is unknown
+                    Memory::Unknown
+                };
+            }
+            Node::Caps(caps) => {
+                let nvmm = caps.contains("memory:NVMM");
+                let raw = caps.starts_with("video/x-raw");
+                if !after_converter && raw {
+                    let mismatch = (nvmm && memory == Memory::System) || (!nvmm && memory == Memory::Nvmm);
+                    if mismatch {
+                        errors.push(format!(
+                            "{}: caps {} don't match the memory flowing into them - convert with nvvideoconvert first",
+                            context, caps
+                        ));
+                    }
+                }
+                if raw {
+                    memory = if nvmm { Memory::Nvmm } else { Memory::System };
+                }
+                after_converter = false;
+            }
+            Node::Raw(_) => {
+                memory = Memory::Unknown;
+                after_converter = false;
+            }
+            Node::Tee { name, branches } => {
+                if branches.is_empty() {
+                    errors.push(format!("{}: tee {} has no branches", context, name));
+                }
+                for branch in branches {
+                    check_chain(branch, memory, &format!("{} branch of {}", context, name), errors);
+                }
+                after_converter = false;
+            }
+        }
+    }
+    memory
+}
+
+/// Every `name=` in a chain, including inside tee branches and raw fragments.
+fn chain_names(chain: &Chain) -> Vec<String> {
+    let mut names = Vec::new();
+    for node in &chain.nodes {
+        match node {
+            Node::Element(element) => names.extend(element.get("name").map(str::to_string)),
+            Node::Raw(raw) => names.extend(
+                raw.split_whitespace().filter_map(|t| t.strip_prefix("name=")).map(str::to_string),
+            ),
+            Node::Tee { name, branches } => {
+                names.push(name.clone());
+                for branch in branches {
+                    names.extend(chain_names(branch));
+                }
+            }
+            Node::Caps(_) => {}
+        }
+    }
+    names
+}
+
+fn make(element: &Element) -> Result<gstreamer::Element, String> {
+    let mut builder = gstreamer::ElementFactory::make(&element.factory);
+    if let Some(name) = element.get("name") {
+        builder = builder.name(name);
+    }
+    let made = builder.build().map_err(|_| format!("no such element \"{}\"", element.factory))?;
+    for (key, value) in element.properties.iter().filter(|(k, _)| k != "name") {
+        if made.find_property(key).is_none() {
+            return Err(format!("no property \"{}\" in element \"{}\"", key, element.factory));
+        }
+        made.set_property_from_str(key, value);
+    }
+    Ok(made)
+}
+
+/// Links two elements, waiting for the pad when upstream only has sometimes pads
+/// (nvurisrcbin, decoders). `sink` names a request pad such as nvstreammux's sink_0.
+fn link(upstream: &gstreamer::Element, downstream: &gstreamer::Element, sink: Option<&str>) -> Result<(), String> {
+    if upstream.link_pads(None, downstream, sink).is_ok() {
+        return Ok(());
+    }
+    let sometimes = upstream
+        .pad_template_list()
+        .iter()
+        .any(|t| t.direction() == gstreamer::PadDirection::Src && t.presence() == gstreamer::PadPresence::Sometimes);
+    if !sometimes {
+        return Err(format!("could not link {} to {}", upstream.name(), downstream.name()));
+    }
+    let downstream = downstream.downgrade();
+    let sink = sink.map(str::to_string);
+    upstream.connect_pad_added(move |upstream, pad| {
+        let Some(downstream) = downstream.upgrade() else { return };
+        if upstream.link_pads(Some(&pad.name()), &downstream, sink.as_deref()).is_err() {
+            eprintln!("Warning: Could not link {}:{} to {}", upstream.name(), pad.name(), downstream.name());
+        }
+    });
+    Ok(())
+}
+
+/// Adds a chain's elements to the pipeline and links them after `upstream`. Returns
+/// the last element, or None when the chain ends in a tee.
+fn build_chain(
+    pipeline: &gstreamer::Pipeline,
+    chain: &Chain,
+    mut upstream: Option<gstreamer::Element>,
+) -> Result<Option<gstreamer::Element>, String> {
+    let mut after_tee = false;
+    for node in &chain.nodes {
+        // The main path continues after a tee through its own queue, as in the launch string
+        if std::mem::take(&mut after_tee) {
+            let queue = make(&Element::new("queue"))?;
+            pipeline.add(&queue).map_err(|e| e.to_string())?;
+            if let Some(tee) = &upstream {
+                link(tee, &queue, None)?;
+            }
+            upstream = Some(queue);
+        }
+        let element = match node {
+            Node::Element(element) => make(element)?,
+            Node::Caps(caps) => {
+                let caps: gstreamer::Caps = caps.parse().map_err(|_| format!("invalid caps {}", caps))?;
+                gstreamer::ElementFactory::make("capsfilter")
+                    .property("caps", caps)
+                    .build()
+                    .map_err(|e| e.to_string())?
+            }
+            Node::Raw(raw) => {
+                // A chain starting at an element built earlier, e.g. `t. ! queue` for a tee's branch
+                let (reference, rest) = raw.split_once(" ! ").unwrap_or((raw, ""));
+                let named = reference.strip_suffix('.').filter(|_| upstream.is_none()).and_then(|n| pipeline.by_name(n));
+                let description = match named {
+                    Some(named) => {
+                        upstream = Some(named);
+                        rest
+                    }
+                    None => raw.as_str(),
+                };
+                gstreamer::parse_bin_from_description(description, true).map_err(|e| e.to_string())?.upcast()
+            }
+            Node::Tee { name, .. } => make(&Element::new("tee").prop("name", name))?,
+        };
+        pipeline.add(&element).map_err(|e| e.to_string())?;
+        if let Some(upstream) = &upstream {
+            link(upstream, &element, None)?;
+        }
+        if let Node::Tee { branches, .. } = node {
+            for branch in branches {
+                build_chain(pipeline, branch, Some(element.clone()))?;
+            }
+            after_tee = true;
+        }
+        upstream = Some(element);
+    }
+    if after_tee {
+        return Ok(None);
+    }
+    Ok(upstream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(main: &str) -> Graph {
+        Graph {
+            sources: vec![Chain::parse("videotestsrc ! nvvideoconvert")],
+            mux: Element::new("nvstreammux").prop("name", "m").prop("batch-size", 1),
+            main: Chain::parse(main),
+        }
+    }
+
+    fn errors(graph: &Graph) -> Vec<String> {
+        graph.validate().err().unwrap_or_default()
+    }
+
+    #[test]
+    fn parse_splits_elements_and_caps() {
+        let chain = Chain::parse("nvvideoconvert ! video/x-raw(memory:NVMM),format=RGBA ! identity name=hook silent=true ! ");
+        assert_eq!(
+            chain.nodes,
+            vec![
+                Node::Element(Element::new("nvvideoconvert")),
+                Node::Caps("video/x-raw(memory:NVMM),format=RGBA".to_string()),
+                Node::Element(Element::new("identity").prop("name", "hook").prop("silent", "true")),
+            ]
+        );
+        assert!(Chain::parse("").nodes.is_empty());
+        assert!(Chain::parse(" ! ").nodes.is_empty());
+    }
+
+    #[test]
+    fn pad_references_and_quotes_stay_raw() {
+        let fragment = "tee name=t t. ! queue ! fakesink t. ! queue";
+        assert_eq!(Chain::parse(fragment).nodes, vec![Node::Raw(fragment.to_string())]);
+        let quoted = "filesink location=\"/my recordings/out.mp4\"";
+        assert_eq!(Chain::parse(quoted).nodes, vec![Node::Raw(quoted.to_string())]);
+        let request_pad = "videotestsrc ! mix.sink_1 compositor name=mix";
+        assert_eq!(Chain::parse(request_pad).nodes, vec![Node::Raw(request_pad.to_string())]);
+        // A trailing dot inside a property value is not a pad reference
+        assert!(matches!(Chain::parse("filesink location=/tmp/out.").nodes[0], Node::Element(_)));
+    }
+
+    #[test]
+    fn parse_render_round_trip() {
+        let fragment = "queue leaky=downstream ! nvvideoconvert ! video/x-raw,format=I420 ! jpegenc quality=95 ! multifilesink location=/d/%06d.jpg";
+        assert_eq!(Chain::parse(fragment).render(), fragment);
+    }
+
+    #[test]
+    fn tee_renders_branches_and_continues_through_a_queue() {
+        let chain = Chain::parse("valve")
+            .tee("t", vec![Chain::parse("queue ! fakesink")])
+            .element(Element::new("nvdsosd"));
+        assert_eq!(chain.render(), "valve ! tee name=t t. ! queue ! fakesink t. ! queue ! nvdsosd");
+    }
+
+    #[test]
+    fn fan_out_links_a_single_branch_directly() {
+        let one = Chain::parse("nvdsosd").fan_out("out", vec![Chain::parse("queue ! fakesink")]);
+        assert_eq!(one.render(), "nvdsosd ! queue ! fakesink");
+        let two = Chain::parse("nvdsosd").fan_out("out", vec![Chain::parse("fakesink"), Chain::parse("fakesink name=b")]);
+        assert_eq!(two.render(), "nvdsosd ! tee name=out out. ! fakesink out. ! fakesink name=b");
+        // No branches at all leaves the chain as it was
+        assert_eq!(Chain::parse("nvdsosd").tee("pre", Vec::new()).render(), "nvdsosd");
+    }
+
+    #[test]
+    fn graph_render_links_sources_to_mux_pads() {
+        let mut graph = graph("nvinfer ! fakesink");
+        graph.sources.push(Chain::parse("videotestsrc ! nvvideoconvert"));
+        graph.mux = Element::new("nvstreammux").prop("name", "m").prop("batch-size", 2);
+        assert_eq!(
+            graph.render(),
+            "videotestsrc ! nvvideoconvert ! m.sink_0 videotestsrc ! nvvideoconvert ! m.sink_1 \
+             nvstreammux name=m batch-size=2 ! nvinfer ! fakesink"
+        );
+        assert_eq!(graph.validate(), Ok(()));
+    }
+
+    #[test]
+    fn valid_pipelines_pass() {
+        let graph = graph(
+            "nvinfer name=pgie ! nvdsosd ! queue ! nvvideoconvert ! video/x-raw(memory:NVMM),format=I420 ! \
+             nvv4l2h264enc ! h264parse ! rtph264pay ! udpsink",
+        );
+        assert_eq!(graph.validate(), Ok(()));
+    }
+
+    #[test]
+    fn system_memory_into_nvmm_element_is_rejected() {
+        let errors = errors(&graph("nvvideoconvert ! video/x-raw,format=RGBA ! nvdsosd"));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("nvdsosd needs NVMM input"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn nvmm_into_cpu_element_is_rejected() {
+        let errors = errors(&graph("nvdsosd ! videoconvert ! ximagesink"));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("videoconvert can't read NVMM"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn caps_without_converter_must_match_memory() {
+        let errors = errors(&graph("nvdsosd ! video/x-raw,format=RGBA ! fakesink"));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("don't match the memory"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn sources_must_reach_the_mux_in_nvmm() {
+        let mut graph = graph("fakesink");
+        graph.sources = vec![Chain::parse("videotestsrc ! videoconvert")];
+        let errors = errors(&graph);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("source 0"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn encoder_payloader_mismatch_is_rejected() {
+        let errors = errors(&graph("nvvideoconvert ! nvv4l2h264enc ! rtph265pay ! fakesink"));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("rtph265pay expects h265"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn payloader_without_encoder_is_rejected() {
+        let errors = errors(&graph("nvdsosd ! queue ! rtph264pay ! fakesink"));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("an encoder is missing"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn branches_are_validated_too() {
+        let main = Chain::parse("nvdsosd").fan_out(
+            "out",
+            vec![Chain::parse("queue ! nvvideoconvert ! ximagesink"), Chain::parse("queue ! jpegenc ! fakesink")],
+        );
+        let graph = Graph { main, ..graph("") };
+        let errors = errors(&graph);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("branch of out") && errors[0].contains("jpegenc"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn batch_size_must_match_sources() {
+        let mut graph = graph("fakesink");
+        graph.mux = Element::new("nvstreammux").prop("name", "m").prop("batch-size", 4);
+        let errors = errors(&graph);
+        assert_eq!(errors, vec!["nvstreammux batch-size=4 but 1 sources are linked".to_string()]);
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        let errors = errors(&graph("nvinfer name=pgie ! nvinfer name=pgie ! fakesink name=m"));
+        assert_eq!(
+            errors,
+            vec!["element name 'pgie' is used twice".to_string(), "element name 'm' is used twice".to_string()]
+        );
+    }
+
+    #[test]
+    fn software_graph_has_no_nvidia_elements() {
+        let graph = Graph {
+            sources: vec![Chain::parse("nvurisrcbin uri=file:///a.mp4 rtsp-reconnect-interval=5 ! nvvideoconvert interpolation-method=5")],
+            mux: Element::new("nvstreammux").prop("name", "m").prop("width", 1920).prop("batch-size", 1),
+            main: Chain::parse("nvinfer name=pgie config-file-path=/m.txt ! valve name=pause_valve drop=false ! nvdsosd")
+                .fan_out(
+                    "out",
+                    vec![
+                        Chain::parse("queue ! nvvideoconvert ! video/x-raw(memory:NVMM),format=I420 ! nvv4l2h264enc bitrate=4000000 ! h264parse ! fakesink"),
+                        Chain::parse("queue ! nveglglessink sync=false"),
+                    ],
+                ),
+        };
+        assert_eq!(
+            graph.software().render(),
+            "uridecodebin uri=file:///a.mp4 ! videoconvert ! m.sink_0 compositor name=m ! identity name=pgie ! \
+             valve name=pause_valve drop=false ! identity ! tee name=out \
+             out. ! queue ! videoconvert ! video/x-raw,format=I420 ! x264enc tune=zerolatency ! h264parse ! fakesink \
+             out. ! queue ! autovideosink"
+        );
+    }
+
+    #[test]
+    fn software_raw_fragments() {
+        let chain = Chain::parse("tee name=t t. ! queue ! nvvideoconvert interpolation-method=5 ! video/x-raw(memory:NVMM),format=I420 ! nvv4l2h265enc name=enc ! fakesink t. ! queue");
+        assert_eq!(
+            chain.software().render(),
+            "tee name=t t. ! queue ! videoconvert ! video/x-raw,format=I420 ! x265enc tune=zerolatency name=enc ! fakesink t. ! queue"
+        );
+    }
+
+    #[test]
+    fn raw_fragments_are_opaque() {
+        // Nothing is known about what comes out of a raw fragment, so no memory errors
+        assert_eq!(graph("nvdsosd ! tee name=t t. ! queue ! fakesink t. ! queue ! videoconvert ! fakesink").validate(), Ok(()));
+    }
+}
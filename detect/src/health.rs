@@ -0,0 +1,278 @@
+use crate::events::{Event, Events};
+use crate::json::Value;
+use crate::metrics::Metrics;
+use crate::processing::{self, MappedFrame};
+use gstreamer::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const HEALTH_ELEMENT: &str = "health_frames";
+
+/// Pixels sampled per frame in each direction for the checksum and brightness
+const SAMPLE_GRID: u32 = 32;
+
+/// Weight of each second's bitrate in the long-term baseline
+const BASELINE_WEIGHT: f64 = 0.02;
+
+pub struct HealthConfig {
+    /// How long a condition must last before it counts
+    pub window: Duration,
+    /// Mean luma (0-255) below which a frame counts as black
+    pub black_level: f64,
+    /// Fraction of the baseline bitrate below which the input counts as degraded
+    pub bitrate_drop: f64,
+}
+
+impl HealthConfig {
+    /// Enabled by HEALTH_MONITOR=true; reads HEALTH_SECONDS, HEALTH_BLACK_LEVEL and HEALTH_BITRATE_DROP.
+    pub fn from_env() -> Option<HealthConfig> {
+        if env::var("HEALTH_MONITOR").map(|v| v != "true").unwrap_or(true) {
+            return None;
+        }
+        let number = |name: &str, default: f64| env::var(name).ok().and_then(|v| v.parse::<f64>().ok()).filter(|v| *v > 0.0).unwrap_or(default);
+        Some(HealthConfig {
+            window: Duration::from_secs_f64(number("HEALTH_SECONDS", 10.0)),
+            black_level: number("HEALTH_BLACK_LEVEL", 16.0),
+            bitrate_drop: number("HEALTH_BITRATE_DROP", 0.3).min(1.0),
+        })
+    }
+
+    /// Mapped RGBA frames, for the checksums and brightness.
+    pub fn stage(&self) -> String {
+        processing::mappable_stage(HEALTH_ELEMENT)
+    }
+}
+
+/// What is wrong with a source that still delivers frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Issue {
+    Frozen,
+    Black,
+    LowBitrate,
+}
+
+impl Issue {
+    fn name(&self) -> &'static str {
+        match self {
+            Issue::Frozen => "frozen",
+            Issue::Black => "black",
+            Issue::LowBitrate => "low_bitrate",
+        }
+    }
+
+    /// Points taken off the 0-100 health score
+    fn penalty(&self) -> f64 {
+        match self {
+            Issue::Frozen | Issue::Black => 60.0,
+            Issue::LowBitrate => 30.0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct SourceHealth {
+    last_frame: Option<Instant>,
+    /// Frames since the last check
+    frames: u64,
+    checksum: u64,
+    /// Since when the checksum hasn't changed / frames have been black
+    unchanged_since: Option<Instant>,
+    black_since: Option<Instant>,
+    /// Compressed bytes per second, long-term and over the last window
+    baseline: Option<f64>,
+    recent: Vec<(Instant, u64)>,
+    low: bool,
+    bytes_seen: u64,
+    offline: bool,
+    issues: Vec<Issue>,
+}
+
+/// Watches every input for failures that don't end the stream: frozen frames (an unchanged
+/// checksum of sampled pixels), black frames and bitrate drops against the source's own
+/// long-term average. `camera_degraded` / `camera_recovered` events report those, apart from
+/// `camera_offline` / `camera_online` when a source stops delivering frames altogether, and
+/// `detect_stream_health` scores each source from 0 to 100.
+pub struct HealthMonitor {
+    config: HealthConfig,
+    sources: Vec<SourceHealth>,
+    /// Compressed bytes entering each source's decoder, counted on the streaming threads
+    bytes: Arc<Vec<AtomicU64>>,
+    events: Events,
+    metrics: Metrics,
+    started: Instant,
+}
+
+pub type Health = Arc<Mutex<HealthMonitor>>;
+
+impl HealthMonitor {
+    pub fn new(config: HealthConfig, sources: usize, events: Events, metrics: Metrics) -> HealthMonitor {
+        {
+            let mut metrics = metrics.lock().unwrap();
+            metrics.describe("detect_stream_health", "gauge", "Stream health score per source (100 healthy, 0 offline)");
+            metrics.describe("detect_stream_fps", "gauge", "Frames per second delivered per source");
+            metrics.describe("detect_stream_bitrate_bps", "gauge", "Compressed input bitrate per source");
+        }
+        HealthMonitor {
+            config,
+            sources: (0..sources).map(|_| SourceHealth::default()).collect(),
+            bytes: Arc::new((0..sources).map(|_| AtomicU64::new(0)).collect()),
+            events,
+            metrics,
+            started: Instant::now(),
+        }
+    }
+
+    fn on_frame(&mut self, frame: &MappedFrame) {
+        let Some(source) = self.sources.get_mut(frame.meta.source_id as usize) else { return };
+        let now = Instant::now();
+        // FNV-1a over a grid of pixels, plus their mean luma
+        let (mut hash, mut luma) = (0xcbf29ce484222325u64, 0.0);
+        let (step_x, step_y) = ((frame.width / SAMPLE_GRID).max(1), (frame.height / SAMPLE_GRID).max(1));
+        let mut samples = 0;
+        for y in (step_y / 2..frame.height).step_by(step_y as usize) {
+            for x in (step_x / 2..frame.width).step_by(step_x as usize) {
+                let i = (y * frame.pitch + x * 4) as usize;
+                let (r, g, b) = (frame.pixels[i], frame.pixels[i + 1], frame.pixels[i + 2]);
+                for byte in [r, g, b] {
+                    hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+                }
+                luma += 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+                samples += 1;
+            }
+        }
+        let luma = luma / samples.max(1) as f64;
+        if source.last_frame.is_none() || hash != source.checksum {
+            source.unchanged_since = None;
+        } else {
+            source.unchanged_since.get_or_insert(now);
+        }
+        source.checksum = hash;
+        if luma < self.config.black_level {
+            source.black_since.get_or_insert(now);
+        } else {
+            source.black_since = None;
+        }
+        source.frames += 1;
+        source.last_frame = Some(now);
+    }
+
+    /// Once a second: updates each source's issues and score, and reports the changes.
+    pub fn check(&mut self) {
+        let now = Instant::now();
+        let window = self.config.window;
+        let lasted = |since: Option<Instant>| since.is_some_and(|at| now.duration_since(at) >= window);
+        for (index, source) in self.sources.iter_mut().enumerate() {
+            let source_id = index.to_string();
+            let labels = [("source", source_id.as_str())];
+
+            // Bitrate over the last window, against an average that only learns from healthy periods
+            let bytes = self.bytes[index].load(Ordering::Relaxed);
+            source.recent.push((now, bytes));
+            source.recent.retain(|(at, _)| now.duration_since(*at) <= window);
+            let (start, start_bytes) = source.recent[0];
+            let elapsed = now.duration_since(start).as_secs_f64();
+            let rate = if elapsed > 0.0 { (bytes - start_bytes) as f64 / elapsed } else { 0.0 };
+            let measured = bytes > 0 && elapsed >= window.as_secs_f64() * 0.9;
+            source.low = measured && source.baseline.is_some_and(|baseline| rate < baseline * self.config.bitrate_drop);
+            if measured && !source.low {
+                let baseline = source.baseline.get_or_insert(rate);
+                *baseline += (rate - *baseline) * BASELINE_WEIGHT;
+            }
+            let per_second = bytes - source.bytes_seen;
+            source.bytes_seen = bytes;
+
+            // A source that never delivered a frame is offline once the first window has passed
+            let offline = now.duration_since(source.last_frame.unwrap_or(self.started)) >= window;
+            if offline != source.offline {
+                source.offline = offline;
+                let kind = if offline { "camera_offline" } else { "camera_online" };
+                self.events.lock().unwrap().emit(Event::new(kind, index as u32, 0));
+            }
+            let mut issues = Vec::new();
+            if !offline {
+                if lasted(source.unchanged_since) {
+                    issues.push(Issue::Frozen);
+                }
+                if lasted(source.black_since) {
+                    issues.push(Issue::Black);
+                }
+                if source.low {
+                    issues.push(Issue::LowBitrate);
+                }
+            }
+            if issues != source.issues {
+                let event = if issues.is_empty() {
+                    Event::new("camera_recovered", index as u32, 0)
+                } else {
+                    let names = issues.iter().map(|i| Value::from(i.name())).collect();
+                    let event = Event::new("camera_degraded", index as u32, 0).with("issues", Value::Array(names));
+                    match source.baseline.filter(|_| issues.contains(&Issue::LowBitrate)) {
+                        Some(baseline) => event.with("bitrate_bps", rate * 8.0).with("baseline_bps", baseline * 8.0),
+                        None => event,
+                    }
+                };
+                source.issues = issues;
+                self.events.lock().unwrap().emit(event);
+            }
+            let score = if offline { 0.0 } else { (100.0 - source.issues.iter().map(Issue::penalty).sum::<f64>()).max(0.0) };
+
+            let mut metrics = self.metrics.lock().unwrap();
+            metrics.set("detect_stream_health", &labels, score);
+            metrics.set("detect_stream_bitrate_bps", &labels, per_second as f64 * 8.0);
+            metrics.set("detect_stream_fps", &labels, source.frames as f64);
+            source.frames = 0;
+        }
+    }
+}
+
+/// Feeds the mapped frames of the health stage to the monitor, and counts the compressed bytes
+/// entering each source's decoder as it is created. Sources that aren't decoded (cameras,
+/// test patterns) have no bitrate check.
+pub fn attach(pipeline: &gstreamer::Element, health: Health, sources: usize) {
+    let frames = health.clone();
+    processing::attach_at(
+        pipeline,
+        HEALTH_ELEMENT,
+        Box::new(move |frame: &mut MappedFrame| frames.lock().unwrap().on_frame(frame)),
+    );
+
+    // Walk upstream from each muxer pad to the element the source starts with
+    let bin = pipeline.downcast_ref::<gstreamer::Bin>().expect("pipeline is a bin");
+    let mut tops: HashMap<String, usize> = HashMap::new();
+    if let Some(mux) = bin.by_name("m") {
+        for index in 0..sources {
+            let mut pad = mux.static_pad(&format!("sink_{}", index));
+            while let Some(element) = pad.and_then(|p| p.peer()).and_then(|p| p.parent_element()) {
+                pad = element.sink_pads().into_iter().next();
+                if pad.is_none() {
+                    tops.insert(element.name().to_string(), index);
+                }
+            }
+        }
+    }
+    let bytes = health.lock().unwrap().bytes.clone();
+    let pipeline_ref = pipeline.downgrade();
+    bin.connect_deep_element_added(move |_, _, element| {
+        let is_decoder = element.factory().is_some_and(|f| f.klass().contains("Decoder"));
+        let Some(pipeline) = pipeline_ref.upgrade().filter(|_| is_decoder) else { return };
+        let mut top = element.clone();
+        while let Some(parent) = top.parent().and_then(|p| p.downcast::<gstreamer::Element>().ok()) {
+            if parent == pipeline {
+                break;
+            }
+            top = parent;
+        }
+        let Some(&index) = tops.get(top.name().as_str()) else { return };
+        let Some(pad) = element.static_pad("sink") else { return };
+        let bytes = bytes.clone();
+        pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_, info| {
+            if let Some(gstreamer::PadProbeData::Buffer(buffer)) = &info.data {
+                bytes[index].fetch_add(buffer.size() as u64, Ordering::Relaxed);
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+    });
+}
@@ -0,0 +1,203 @@
+use crate::png;
+use crate::processing::{self, MappedFrame};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const HEATMAP_ELEMENT: &str = "heatmap_frames";
+
+/// Exported images are downscaled to at most this width
+const MAX_EXPORT_WIDTH: u32 = 640;
+
+pub struct HeatmapConfig {
+    pub dir: String,
+    pub interval_secs: u32,
+    /// Grid cell size in pipeline pixels
+    pub cell: u32,
+}
+
+impl HeatmapConfig {
+    /// Reads HEATMAP_EXPORT_DIR, HEATMAP_EXPORT_INTERVAL and HEATMAP_CELL_SIZE.
+    pub fn from_env() -> Option<HeatmapConfig> {
+        let dir = env::var("HEATMAP_EXPORT_DIR").ok()?;
+        let interval_secs = env::var("HEATMAP_EXPORT_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(60);
+        let cell = env::var("HEATMAP_CELL_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(16);
+        Some(HeatmapConfig { dir, interval_secs, cell })
+    }
+
+    pub fn path(&self, source_id: u32) -> String {
+        format!("{}/heatmap_src{}.png", self.dir, source_id)
+    }
+
+    /// Mapped RGBA frames, for the background image and the detections on it.
+    pub fn stage(&self) -> String {
+        processing::mappable_stage(HEATMAP_ELEMENT)
+    }
+}
+
+/// Counts per grid cell for one source, plus a recent frame to draw them over.
+struct SourceHeatmap {
+    width: u32,
+    height: u32,
+    cols: usize,
+    rows: usize,
+    counts: Vec<u32>,
+    scale: u32,
+    background: Vec<u8>,
+    background_at: Option<Instant>,
+}
+
+impl SourceHeatmap {
+    fn new(width: u32, height: u32, cell: u32) -> SourceHeatmap {
+        let cols = width.div_ceil(cell) as usize;
+        let rows = height.div_ceil(cell) as usize;
+        let scale = width.div_ceil(MAX_EXPORT_WIDTH).max(1);
+        SourceHeatmap {
+            width,
+            height,
+            cols,
+            rows,
+            counts: vec![0; cols * rows],
+            scale,
+            background: Vec::new(),
+            background_at: None,
+        }
+    }
+
+    fn export_size(&self) -> (u32, u32) {
+        (self.width / self.scale, self.height / self.scale)
+    }
+
+    fn capture_background(&mut self, frame: &MappedFrame) {
+        self.background = frame.rgb(self.scale).2;
+        self.background_at = Some(Instant::now());
+    }
+
+    /// 3x3 box blur of the counts, so sparse cells read as areas rather than dots.
+    fn smoothed(&self) -> Vec<f32> {
+        let mut out = vec![0.0; self.counts.len()];
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                let mut sum = 0.0;
+                let mut n = 0.0;
+                for rr in r.saturating_sub(1)..(r + 2).min(self.rows) {
+                    for cc in c.saturating_sub(1)..(c + 2).min(self.cols) {
+                        sum += self.counts[rr * self.cols + cc] as f32;
+                        n += 1.0;
+                    }
+                }
+                out[r * self.cols + c] = sum / n;
+            }
+        }
+        out
+    }
+
+    fn render(&self, cell: u32) -> Vec<u8> {
+        let (out_w, out_h) = self.export_size();
+        let heat = self.smoothed();
+        let max = heat.iter().cloned().fold(0.0f32, f32::max);
+        let mut rgb = if self.background.len() == (out_w * out_h * 3) as usize {
+            self.background.clone()
+        } else {
+            vec![64; (out_w * out_h * 3) as usize]
+        };
+        if max <= 0.0 {
+            return rgb;
+        }
+        for y in 0..out_h {
+            let row = ((y * self.scale / cell) as usize).min(self.rows - 1);
+            for x in 0..out_w {
+                let col = ((x * self.scale / cell) as usize).min(self.cols - 1);
+                let t = heat[row * self.cols + col] / max;
+                if t < 0.02 {
+                    continue;
+                }
+                let alpha = 0.25 + 0.45 * t;
+                let i = ((y * out_w + x) * 3) as usize;
+                for (channel, color) in colormap(t).iter().enumerate() {
+                    let base = rgb[i + channel] as f32;
+                    rgb[i + channel] = (base * (1.0 - alpha) + color * 255.0 * alpha) as u8;
+                }
+            }
+        }
+        rgb
+    }
+}
+
+/// Blue (cold) through green and yellow to red (hot).
+fn colormap(t: f32) -> [f32; 3] {
+    let band = |center: f32| (1.5 - (4.0 * t - center).abs()).clamp(0.0, 1.0);
+    [band(3.0), band(2.0), band(1.0)]
+}
+
+/// Accumulates where objects stand (bottom center of each box) per source and
+/// writes the result blended over a recent frame, for foot-traffic style analytics.
+/// Counts are cumulative for the run; each export overwrites the previous image.
+pub struct HeatmapExporter {
+    config: HeatmapConfig,
+    maps: HashMap<u32, SourceHeatmap>,
+}
+
+pub type Heatmaps = Arc<Mutex<HeatmapExporter>>;
+
+impl HeatmapExporter {
+    pub fn new(config: HeatmapConfig) -> io::Result<HeatmapExporter> {
+        fs::create_dir_all(&config.dir)?;
+        Ok(HeatmapExporter { config, maps: HashMap::new() })
+    }
+
+    fn on_frame(&mut self, frame: &mut MappedFrame) {
+        let (width, height, cell) = (frame.width, frame.height, self.config.cell);
+        let map = self
+            .maps
+            .entry(frame.meta.source_id)
+            .or_insert_with(|| SourceHeatmap::new(width, height, cell));
+        if map.width != width || map.height != height {
+            *map = SourceHeatmap::new(width, height, cell);
+        }
+        // Refresh the background once per export interval so lighting changes show up
+        let interval = Duration::from_secs(self.config.interval_secs as u64);
+        if map.background_at.is_none_or(|at| at.elapsed() >= interval) {
+            map.capture_background(frame);
+        }
+        for obj in frame.meta.objects() {
+            let rect = &obj.rect_params;
+            let x = (rect.left + rect.width / 2.0).max(0.0) as u32 / cell;
+            let y = (rect.top + rect.height).max(0.0) as u32 / cell;
+            let col = (x as usize).min(map.cols - 1);
+            let row = (y as usize).min(map.rows - 1);
+            map.counts[row * map.cols + col] += 1;
+        }
+    }
+
+    /// Writes one PNG per source seen so far.
+    pub fn export(&self) {
+        for (source_id, map) in &self.maps {
+            let path = self.config.path(*source_id);
+            let (width, height) = map.export_size();
+            if let Err(e) = png::write_rgb(&path, width, height, &map.render(self.config.cell)) {
+                eprintln!("Warning: Failed to write heatmap {}: {}", path, e);
+            }
+        }
+    }
+}
+
+/// Feeds the mapped frames of the heatmap stage into the exporter.
+pub fn attach(bin: &gstreamer::Element, heatmaps: Heatmaps) {
+    processing::attach_at(
+        bin,
+        HEATMAP_ELEMENT,
+        Box::new(move |frame: &mut MappedFrame| heatmaps.lock().unwrap().on_frame(frame)),
+    );
+}
@@ -0,0 +1,153 @@
+use crate::events::{Event, EventSink, Events};
+use gstreamer::prelude::*;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often the idle state is re-evaluated; also the longest a wake-up waits
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// Name prefix of the valves `idle=pause` sinks get at the start of their branch
+pub const IDLE_VALVE_PREFIX: &str = "idle_pause";
+
+/// Energy saving for battery and solar installs: after IDLE_AFTER seconds without detections
+/// or motion, pgie only infers every IDLE_INFER_INTERVAL-th batch, encoders drop to
+/// IDLE_BITRATE percent of their bitrate and sinks with `idle=pause` stop taking frames.
+/// The first detection or motion_start brings everything back.
+pub struct IdleConfig {
+    pub after: Duration,
+    /// pgie interval (batches skipped between inferences) while idle
+    pub infer_interval: u32,
+    /// Encoder bitrate while idle, in percent of the configured one
+    pub bitrate_percent: u32,
+}
+
+impl IdleConfig {
+    /// Reads IDLE_AFTER (seconds), IDLE_INFER_INTERVAL (default 10) and IDLE_BITRATE (default 25);
+    /// None without IDLE_AFTER.
+    pub fn from_env() -> Option<IdleConfig> {
+        let value = env::var("IDLE_AFTER").ok()?;
+        let after = match value.trim().parse::<f64>() {
+            Ok(seconds) if seconds > 0.0 => Duration::from_secs_f64(seconds),
+            _ => {
+                eprintln!("Error: IDLE_AFTER must be a positive number of seconds, got '{}'", value);
+                std::process::exit(2);
+            }
+        };
+        let number = |key: &str, default: u32| {
+            env::var(key).ok().map_or(default, |v| match v.trim().parse::<u32>() {
+                Ok(n) => n,
+                Err(_) => {
+                    eprintln!("Error: {} must be a whole number, got '{}'", key, v);
+                    std::process::exit(2);
+                }
+            })
+        };
+        Some(IdleConfig {
+            after,
+            infer_interval: number("IDLE_INFER_INTERVAL", 10),
+            bitrate_percent: number("IDLE_BITRATE", 25).clamp(1, 100),
+        })
+    }
+}
+
+/// Counts motion_start events as activity.
+struct MotionWake(Arc<Mutex<Instant>>);
+
+impl EventSink for MotionWake {
+    fn send(&mut self, event: &Event) {
+        if event.kind == "motion_start" {
+            *self.0.lock().unwrap() = Instant::now();
+        }
+    }
+}
+
+/// What idle mode turns down, with the values to restore.
+struct Saving {
+    pgie: Option<(gstreamer::Element, u32)>,
+    /// Encoders and their configured bitrate
+    encoders: Vec<(gstreamer::Element, u32)>,
+    valves: Vec<gstreamer::Element>,
+}
+
+impl Saving {
+    fn find(bin: &gstreamer::Bin) -> Saving {
+        // Dev mode's stand-in for nvinfer has no interval
+        let pgie = bin.by_name("pgie").filter(|pgie| pgie.find_property("interval").is_some()).map(|pgie| {
+            let interval = pgie.property::<u32>("interval");
+            (pgie, interval)
+        });
+        // Adaptive-bitrate encoders are left to their own controller
+        let encoders = bin
+            .iterate_recurse()
+            .into_iter()
+            .flatten()
+            .filter(|e| e.factory().is_some_and(|f| f.klass().contains("Encoder") && f.klass().contains("Video")))
+            .filter(|e| !e.name().starts_with("abr_enc") && e.find_property("bitrate").is_some())
+            .map(|e| {
+                let bitrate = e.property::<u32>("bitrate");
+                (e, bitrate)
+            })
+            .collect();
+        let valves = bin
+            .iterate_recurse()
+            .into_iter()
+            .flatten()
+            .filter(|e| e.name().starts_with(IDLE_VALVE_PREFIX))
+            .collect();
+        Saving { pgie, encoders, valves }
+    }
+
+    fn apply(&self, idle: bool, config: &IdleConfig) {
+        if let Some((pgie, interval)) = &self.pgie {
+            pgie.set_property("interval", if idle { config.infer_interval.max(*interval) } else { *interval });
+        }
+        for (encoder, bitrate) in &self.encoders {
+            let target = if idle { (*bitrate as u64 * config.bitrate_percent as u64 / 100).max(1) as u32 } else { *bitrate };
+            encoder.set_property("bitrate", target);
+        }
+        for valve in &self.valves {
+            valve.set_property("drop", idle);
+        }
+    }
+}
+
+/// Marks activity from the objects reaching the OSD and from motion events, and switches
+/// idle mode on and off from the main loop. `idle_start` / `idle_end` events report it.
+pub fn attach(pipeline: &gstreamer::Element, config: IdleConfig, events: Events) {
+    let bin = pipeline.downcast_ref::<gstreamer::Bin>().expect("pipeline is a bin");
+    let last_activity = Arc::new(Mutex::new(Instant::now()));
+    events.lock().unwrap().add(Box::new(MotionWake(last_activity.clone())));
+
+    match bin.by_name("osd").and_then(|osd| osd.static_pad("sink")) {
+        Some(pad) => {
+            let activity = last_activity.clone();
+            pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+                if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+                    if batch.frames().any(|frame| frame.objects().next().is_some()) {
+                        *activity.lock().unwrap() = Instant::now();
+                    }
+                }
+                gstreamer::PadProbeReturn::Ok
+            });
+        }
+        None => eprintln!("Warning: nvdsosd element not found, idle mode only wakes on motion"),
+    }
+
+    let saving = Saving::find(bin);
+    let mut idle = false;
+    glib::timeout_add(CHECK_INTERVAL, move || {
+        let quiet = last_activity.lock().unwrap().elapsed();
+        if (quiet >= config.after) != idle {
+            idle = !idle;
+            saving.apply(idle, &config);
+            if idle {
+                println!("Idle for {:?}: saving energy until the next detection or motion", config.after);
+                events.lock().unwrap().emit(Event::new("idle_start", 0, 0));
+            } else {
+                println!("Activity: back to full rate");
+                events.lock().unwrap().emit(Event::new("idle_end", 0, 0));
+            }
+        }
+        glib::Continue(true)
+    });
+}
@@ -0,0 +1,188 @@
+use crate::doctor::deepstream_version;
+use crate::presets::{PRESETS, TRACKER};
+use crate::samples::SAMPLES;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+/// Reads one answer; the default on an empty line or end of input.
+fn ask(question: &str, default: &str) -> String {
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(n) if n > 0 && !line.trim().is_empty() => line.trim().to_string(),
+        _ => default.to_string(),
+    }
+}
+
+fn confirm(question: &str, default: bool) -> bool {
+    let answer = ask(&format!("{} (y/n)", question), if default { "y" } else { "n" });
+    matches!(answer.to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Numbered menu; returns the index picked, asking again on anything else.
+fn choose(question: &str, options: &[String], default: usize) -> usize {
+    println!("{}", question);
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}) {}", i + 1, option);
+    }
+    loop {
+        match ask("Choice", &(default + 1).to_string()).parse::<usize>() {
+            Ok(n) if (1..=options.len()).contains(&n) => return n - 1,
+            _ => println!("Please enter a number from 1 to {}", options.len()),
+        }
+    }
+}
+
+/// Sorted paths in `dir` whose file name satisfies `matches`.
+fn list(dir: &str, matches: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut found: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| matches(&e.file_name().to_string_lossy()))
+                .map(|e| e.path().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    found.sort();
+    found
+}
+
+/// `detect init [file]`: asks about the input, model, outputs and alerts, after probing
+/// the DeepStream install, V4L2 cameras and the models in /models, and writes the answers
+/// as a DETECT_CONFIG file (default detect.conf).
+pub fn command(args: &[String], labels_path: &str, default_model_config: &str) -> ! {
+    let path = args.first().cloned().unwrap_or_else(|| "detect.conf".to_string());
+    println!("DeepStream Detect - setup\n");
+
+    let cameras = list("/dev", |name| name.starts_with("video"));
+    let models = list("/models", |name| name.starts_with("config_infer_") && name.ends_with(".txt"));
+    let labels: Vec<String> = fs::read_to_string(labels_path)
+        .map(|contents| contents.lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect())
+        .unwrap_or_default();
+    println!("Environment");
+    match deepstream_version() {
+        Some(version) => println!("  ✓ DeepStream: {}", version),
+        None => println!("  ! DeepStream not found - the config will need SOFTWARE_PIPELINE=true to run here"),
+    }
+    println!("  Cameras: {}", if cameras.is_empty() { "none found".to_string() } else { cameras.join(", ") });
+    println!("  Models: {}", if models.is_empty() { "none in /models".to_string() } else { models.join(", ") });
+    println!();
+
+    if Path::new(&path).exists() && !confirm(&format!("{} exists. Overwrite it?", path), false) {
+        println!("Nothing written");
+        std::process::exit(1);
+    }
+
+    let mut settings: Vec<(&str, String)> = Vec::new();
+    let mut presets: Vec<String> = vec!["None - answer everything below".to_string()];
+    presets.extend(PRESETS.iter().map(|p| format!("{} - {}", p.name, p.description)));
+    let preset = match choose("Start from a preset?", &presets, 0) {
+        0 => None,
+        n => Some(&PRESETS[n - 1]),
+    };
+    if let Some(preset) = preset {
+        settings.push(("PRESET", preset.name.to_string()));
+    }
+    let preset_value = |key: &str| preset.and_then(|p| p.settings.iter().find(|(k, _)| *k == key)).map(|(_, v)| v.to_string());
+
+    println!();
+    let sources = [
+        "USB / V4L2 camera".to_string(),
+        "RTSP camera".to_string(),
+        "Video file".to_string(),
+        "Sample clip (downloaded with `detect samples fetch`)".to_string(),
+        "Test pattern".to_string(),
+    ];
+    let device = match choose("What is the input?", &sources, if cameras.is_empty() { 1 } else { 0 }) {
+        0 => ask("Camera device(s), comma-separated", cameras.first().map(String::as_str).unwrap_or("/dev/video0")),
+        1 => {
+            let urls = ask("RTSP URL(s), comma-separated", "rtsp://camera/stream");
+            let user = ask("RTSP user (empty if the URL has none or none is needed)", "");
+            if !user.is_empty() {
+                settings.push(("RTSP_USER", user));
+                settings.push(("RTSP_PASSWORD", ask("RTSP password (stored in plain text in the file)", "")));
+            }
+            let latency = preset_value("RTSP_LATENCY").unwrap_or_else(|| "200".to_string());
+            settings.push(("RTSP_LATENCY", ask("Jitterbuffer latency in ms", &latency)));
+            urls
+        }
+        2 => ask("Video file path(s), comma-separated", "/videos/input.mp4"),
+        3 => {
+            let names: Vec<String> = SAMPLES.iter().map(|(name, description, _)| format!("{} - {}", name, description)).collect();
+            format!("sample:{}", SAMPLES[choose("Which sample?", &names, 0)].0)
+        }
+        _ => "test".to_string(),
+    };
+    settings.push(("GST_DEVICE", device));
+
+    println!();
+    let default_model = preset_value("MODEL_CONFIG")
+        .or_else(|| models.iter().find(|m| m.contains("yolo11s")).cloned())
+        .or_else(|| models.first().cloned())
+        .unwrap_or_else(|| default_model_config.to_string());
+    settings.push(("MODEL_CONFIG", ask("nvinfer model config", &default_model)));
+    let default_object = preset_value("DETECT_OBJECT").unwrap_or_else(|| "person".to_string());
+    let object = loop {
+        let object = ask("Object to detect", &default_object);
+        if labels.is_empty() || labels.contains(&object) {
+            break object;
+        }
+        println!("'{}' is not in {}; e.g. {}", object, labels_path, labels.iter().take(8).cloned().collect::<Vec<_>>().join(", "));
+    };
+    settings.push(("DETECT_OBJECT", object));
+    if preset_value("TRACKER_CONFIG").is_none() && confirm("Track objects across frames (IDs, counting)?", true) {
+        settings.push(("TRACKER_CONFIG", TRACKER.to_string()));
+    }
+
+    println!();
+    let mut sinks = Vec::new();
+    if confirm("Show the annotated video on a local display?", false) {
+        sinks.push("display".to_string());
+    }
+    if confirm("Restream the annotated video over RTSP?", true) {
+        sinks.push(format!("rtsp bitrate={}", ask("RTSP bitrate in bits/s", "4000000")));
+    }
+    if confirm("Record the annotated video to a file?", false) {
+        sinks.push(format!("file location={}", ask("Recording path (.mp4 or .mkv)", "/recordings/detect.mp4")));
+    }
+    if sinks.is_empty() {
+        sinks.push("fakesink".to_string());
+    }
+    settings.push(("SINKS", sinks.join("; ")));
+
+    println!();
+    let events_file = ask("Append detection events to a file (empty to print them)", "");
+    if !events_file.is_empty() {
+        settings.push(("EVENTS_FILE", events_file));
+    }
+    let webhook = ask("POST events to a webhook URL (empty for none)", "");
+    if !webhook.is_empty() {
+        settings.push(("EVENTS_WEBHOOK", webhook));
+    }
+    let mqtt = ask("Publish events to MQTT, mqtt://host[:port]/topic (empty for none)", "");
+    if !mqtt.is_empty() {
+        settings.push(("EVENTS_MQTT", mqtt));
+    }
+
+    // Preset settings that were asked about anyway are written out, overriding the preset
+    let mut contents = String::from("# Written by `detect init`; run with DETECT_CONFIG=<this file>\n");
+    for (key, value) in &settings {
+        contents.push_str(&format!("{}={}\n", key, value));
+    }
+    if let Err(e) = fs::write(&path, contents) {
+        eprintln!("Error: Could not write {}: {}", path, e);
+        std::process::exit(1);
+    }
+    println!();
+    println!("✓ Wrote {} ({} settings)", path, settings.len());
+    println!("  Check it:  DETECT_CONFIG={} ./target/release/detect doctor", path);
+    println!("  Run it:    DETECT_CONFIG={} ./target/release/detect", path);
+    std::process::exit(0);
+}
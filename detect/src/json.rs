@@ -0,0 +1,386 @@
+// Minimal JSON helpers; documents are small and flat enough that pulling in
+// a serialization crate isn't worth it.
+
+/// Quotes and escapes `s` as a JSON string literal.
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Formats a float with limited precision; NaN/inf (not valid JSON) become 0.
+pub fn number(v: f32) -> String {
+    if v.is_finite() {
+        let s = format!("{:.3}", v);
+        let s = s.trim_end_matches('0').trim_end_matches('.');
+        if s.is_empty() || s == "-" || s == "-0" { "0".to_string() } else { s.to_string() }
+    } else {
+        "0".to_string()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    // Kept as a list to preserve document order for error messages
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_f64().filter(|n| n.fract() == 0.0).map(|n| n as i64)
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Serializes to compact JSON.
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::Null => "null".to_string(),
+            Value::Bool(b) => b.to_string(),
+            // Integers (timestamps, IDs) are written exactly; f32 would round them
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => format!("{}", *n as i64),
+            Value::Number(n) => number(*n as f32),
+            Value::String(s) => string(s),
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(Value::to_json).collect();
+                format!("[{}]", items.join(","))
+            }
+            Value::Object(entries) => {
+                let entries: Vec<String> = entries.iter().map(|(k, v)| format!("{}:{}", string(k), v.to_json())).collect();
+                format!("{{{}}}", entries.join(","))
+            }
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(n)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(n: f32) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Parses a complete JSON document.
+pub fn parse(input: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser { chars: input.chars().collect(), pos: 0 };
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> ParseError {
+        let consumed = &self.chars[..self.pos.min(self.chars.len())];
+        let line = consumed.iter().filter(|&&c| c == '\n').count() + 1;
+        let column = consumed.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+        ParseError { line, column, message: message.to_string() }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, ParseError> {
+        for c in word.chars() {
+            if self.peek() != Some(c) {
+                return Err(self.error(&format!("invalid literal, expected '{}'", word)));
+            }
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Value::String),
+            Some('t') => self.literal("true", Value::Bool(true)),
+            Some('f') => self.literal("false", Value::Bool(false)),
+            Some('n') => self.literal("null", Value::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, ParseError> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            entries.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(entries));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let hex: String = self.chars.iter().skip(self.pos + 1).take(4).collect();
+                            let code = u32::from_str_radix(&hex, 16)
+                                .map_err(|_| self.error("invalid \\u escape"))?;
+                            self.pos += 4;
+                            char::from_u32(code).unwrap_or('\u{fffd}')
+                        }
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    out.push(escaped);
+                    self.pos += 1;
+                }
+                Some(c) => {
+                    out.push(c);
+                    self.pos += 1;
+                }
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| self.error(&format!("invalid number '{}'", text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: &str) -> (usize, usize, String) {
+        let e = parse(input).unwrap_err();
+        (e.line, e.column, e.message)
+    }
+
+    #[test]
+    fn documents_parse_in_order() {
+        let value = parse(r#" {"b": [1, true, null], "a": {"c": "d"}} "#).unwrap();
+        assert_eq!(
+            value,
+            Value::Object(vec![
+                ("b".to_string(), Value::Array(vec![Value::Number(1.0), Value::Bool(true), Value::Null])),
+                ("a".to_string(), Value::Object(vec![("c".to_string(), Value::from("d"))])),
+            ])
+        );
+        assert_eq!(parse("[]").unwrap(), Value::Array(Vec::new()));
+        assert_eq!(parse("{ }").unwrap(), Value::Object(Vec::new()));
+    }
+
+    #[test]
+    fn escapes_and_unicode() {
+        let value = parse(r#""q\" b\\ s\/ \b\f\n\r\t""#).unwrap();
+        assert_eq!(value, Value::from("q\" b\\ s/ \u{8}\u{c}\n\r\t"));
+        assert_eq!(parse(r#""café é""#).unwrap(), Value::from("café é"));
+        assert_eq!(parse("\"naïve ✓\"").unwrap(), Value::from("naïve ✓"));
+        // A lone surrogate can't be a char
+        assert_eq!(parse(r#""\ud800""#).unwrap(), Value::from("\u{fffd}"));
+        assert_eq!(parse(&string("a\"b\\c\n\u{1}")).unwrap(), Value::from("a\"b\\c\n\u{1}"));
+    }
+
+    #[test]
+    fn numbers() {
+        for (text, expected) in [("0", 0.0), ("-12", -12.0), ("3.25", 3.25), ("1e3", 1000.0), ("-2.5E-2", -0.025)] {
+            assert_eq!(parse(text).unwrap(), Value::Number(expected), "{}", text);
+        }
+        assert_eq!(parse("1700000000123").unwrap().as_i64(), Some(1_700_000_000_123));
+        assert_eq!(parse("1.5").unwrap().as_i64(), None);
+        assert_eq!(error("[1, 2.3.4]"), (1, 10, "invalid number '2.3.4'".to_string()));
+        assert_eq!(error("-"), (1, 2, "invalid number '-'".to_string()));
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_character() {
+        assert_eq!(error(r#"{"a": x}"#), (1, 7, "unexpected character".to_string()));
+        assert_eq!(error("{\n  \"a\": 1,\n  \"b\" 2\n}"), (3, 7, "expected ':'".to_string()));
+        assert_eq!(error("[1, 2"), (1, 6, "expected ',' or ']'".to_string()));
+        assert_eq!(error(r#"{"a": 1 "b": 2}"#), (1, 9, "expected ',' or '}'".to_string()));
+        assert_eq!(error(r#""abc"#), (1, 5, "unterminated string".to_string()));
+        assert_eq!(error(r#""\x""#), (1, 3, "invalid escape sequence".to_string()));
+        assert_eq!(error(r#""\u12g4""#), (1, 3, "invalid \\u escape".to_string()));
+        assert_eq!(error("tru"), (1, 4, "invalid literal, expected 'true'".to_string()));
+        assert_eq!(error("{} {}"), (1, 4, "unexpected trailing characters".to_string()));
+        assert_eq!(error(""), (1, 1, "unexpected end of input".to_string()));
+        assert_eq!(parse("[1,\n 2,\n ?]").unwrap_err().to_string(), "line 3, column 2: unexpected character");
+    }
+
+    #[test]
+    fn values_serialize_back() {
+        let text = r#"{"id":1700000000123,"score":0.5,"tags":["a","b\n"],"ok":false,"none":null}"#;
+        assert_eq!(parse(text).unwrap().to_json(), text);
+        assert_eq!(number(f32::NAN), "0");
+        assert_eq!(number(-0.0001), "0");
+        assert_eq!(number(2.50), "2.5");
+    }
+}
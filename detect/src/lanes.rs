@@ -0,0 +1,201 @@
+use crate::detections::{FrameDetections, FrameObserver};
+use crate::json::Value;
+use crate::metrics::Metrics;
+use crate::zones::{self, Zone};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Tracks not seen for this many frames are forgotten
+const TRACK_TIMEOUT_FRAMES: i32 = 150;
+
+pub struct LaneConfig {
+    pub lanes: Vec<Zone>,
+    /// Labels counted, lowercase
+    pub classes: Vec<String>,
+    /// Consecutive frames a track must be inside a lane to be counted
+    pub min_frames: u32,
+    /// Seconds between exported counts
+    pub interval: Duration,
+    /// JSON lines, or CSV when the name ends in .csv
+    pub output: Option<String>,
+}
+
+impl LaneConfig {
+    /// Reads LANES (same syntax as ZONES) / LANE_CLASSES / LANE_MIN_FRAMES / LANE_INTERVAL
+    /// / LANE_COUNTS_FILE. Exits on a malformed LANES like the other startup configuration.
+    pub fn from_env() -> Option<LaneConfig> {
+        let spec = env::var("LANES").ok()?;
+        let lanes = zones::parse_zones(&spec).unwrap_or_else(|e| {
+            eprintln!("Error: LANES: {}", e);
+            std::process::exit(2);
+        });
+        let classes = env::var("LANE_CLASSES")
+            .unwrap_or_else(|_| "car,bus,truck".to_string())
+            .split(',')
+            .map(|c| c.trim().to_lowercase())
+            .filter(|c| !c.is_empty())
+            .collect();
+        let min_frames = env::var("LANE_MIN_FRAMES").ok().and_then(|v| v.parse().ok()).unwrap_or(3);
+        let interval = env::var("LANE_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(60));
+        let output = env::var("LANE_COUNTS_FILE").ok();
+        Some(LaneConfig { lanes, classes, min_frames, interval, output })
+    }
+}
+
+#[derive(Default)]
+struct TrackInLane {
+    lane: Option<usize>,
+    frames: u32,
+    last_seen: i32,
+}
+
+/// Counts each tracked vehicle once per lane it drives through, by class.
+pub struct LaneCounter {
+    config: LaneConfig,
+    labels: Vec<String>,
+    metrics: Metrics,
+    file: Option<File>,
+    tracks: HashMap<(u32, u64), TrackInLane>,
+    counted: HashSet<(u32, u64, usize)>,
+    // (source, lane, label) -> count since the last export
+    period: HashMap<(u32, usize, String), u64>,
+    period_start: Instant,
+}
+
+impl LaneCounter {
+    pub fn new(config: LaneConfig, labels: Vec<String>, metrics: Metrics) -> std::io::Result<LaneCounter> {
+        let file = match &config.output {
+            Some(path) => {
+                let is_new = std::fs::metadata(path).map(|m| m.len() == 0).unwrap_or(true);
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                if is_new && path.ends_with(".csv") {
+                    writeln!(file, "timestamp_ms,period_seconds,source_id,lane,class,count")?;
+                }
+                Some(file)
+            }
+            None => None,
+        };
+        metrics.lock().unwrap().describe("detect_lane_vehicles_total", "counter", "Vehicles counted per lane and class");
+        Ok(LaneCounter {
+            config,
+            labels,
+            metrics,
+            file,
+            tracks: HashMap::new(),
+            counted: HashSet::new(),
+            period: HashMap::new(),
+            period_start: Instant::now(),
+        })
+    }
+
+    fn count(&mut self, source_id: u32, lane: usize, label: &str) {
+        *self.period.entry((source_id, lane, label.to_string())).or_default() += 1;
+        let source = source_id.to_string();
+        let labels = [("source", source.as_str()), ("lane", self.config.lanes[lane].name.as_str()), ("class", label)];
+        self.metrics.lock().unwrap().add("detect_lane_vehicles_total", &labels, 1.0);
+    }
+
+    /// Writes the counts of the finished period and starts a new one.
+    fn export(&mut self) {
+        let seconds = self.period_start.elapsed().as_secs();
+        self.period_start = Instant::now();
+        let counts = std::mem::take(&mut self.period);
+        let Some(file) = self.file.as_mut() else { return };
+        let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        let csv = self.config.output.as_deref().is_some_and(|p| p.ends_with(".csv"));
+
+        let mut rows: Vec<_> = counts.into_iter().collect();
+        rows.sort();
+        let mut result = Ok(());
+        if csv {
+            for ((source_id, lane, label), count) in &rows {
+                let name = &self.config.lanes[*lane].name;
+                result = result.and_then(|_| {
+                    writeln!(file, "{},{},{},{},{},{}", timestamp_ms, seconds, source_id, name, label, count)
+                });
+            }
+        } else {
+            // One object per source and lane: {"lane": ..., "counts": {"car": 3, ...}}
+            let mut grouped = BTreeMap::new();
+            for ((source_id, lane, label), count) in rows {
+                grouped.entry((source_id, lane)).or_insert_with(Vec::new).push((label, count));
+            }
+            for ((source_id, lane), classes) in grouped {
+                let total: u64 = classes.iter().map(|(_, count)| count).sum();
+                let counts = classes.into_iter().map(|(label, count)| (label, Value::from(count))).collect();
+                let entry = Value::Object(vec![
+                    ("timestamp_ms".to_string(), Value::from(timestamp_ms as u64)),
+                    ("period_seconds".to_string(), Value::from(seconds)),
+                    ("source_id".to_string(), Value::from(source_id as u64)),
+                    ("lane".to_string(), Value::from(self.config.lanes[lane].name.as_str())),
+                    ("total".to_string(), Value::from(total)),
+                    ("counts".to_string(), Value::Object(counts)),
+                ]);
+                result = result.and_then(|_| writeln!(file, "{}", entry.to_json()));
+            }
+        }
+        if let Err(e) = result.and_then(|_| file.flush()) {
+            eprintln!("Warning: Failed to write lane counts: {}", e);
+        }
+    }
+}
+
+impl FrameObserver for LaneCounter {
+    fn on_frame(&mut self, frame: &FrameDetections) {
+        let (width, height) = (frame.pipeline_width.max(1) as f32, frame.pipeline_height.max(1) as f32);
+        for det in &frame.objects {
+            let Some(track_id) = det.track_id else { continue };
+            let label = match self.labels.get(det.class_id as usize) {
+                Some(label) => label.to_lowercase(),
+                None => continue,
+            };
+            if !self.config.classes.contains(&label) {
+                continue;
+            }
+            // The bottom center is where the vehicle touches the road
+            let (x, y) = ((det.left + det.width / 2.0) / width, (det.top + det.height) / height);
+            let lane = self.config.lanes.iter().position(|l| l.contains(x, y));
+
+            let track = self.tracks.entry((frame.source_id, track_id)).or_default();
+            track.last_seen = frame.frame_num;
+            if track.lane == lane {
+                track.frames += 1;
+            } else {
+                track.lane = lane;
+                track.frames = 1;
+            }
+            let Some(lane) = lane else { continue };
+            if track.frames >= self.config.min_frames && self.counted.insert((frame.source_id, track_id, lane)) {
+                self.count(frame.source_id, lane, &label);
+            }
+        }
+
+        let (source_id, frame_num) = (frame.source_id, frame.frame_num);
+        let expired: Vec<(u32, u64)> = self
+            .tracks
+            .iter()
+            .filter(|((source, _), t)| *source == source_id && frame_num - t.last_seen > TRACK_TIMEOUT_FRAMES)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            self.tracks.remove(&key);
+            self.counted.retain(|(source, track, _)| (*source, *track) != key);
+        }
+
+        if self.period_start.elapsed() >= self.config.interval {
+            self.export();
+        }
+    }
+
+    fn finish(&mut self) {
+        self.export();
+    }
+}
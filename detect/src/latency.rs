@@ -0,0 +1,241 @@
+use crate::sinks::{parse_sinks, SinkSpec};
+use crate::source::RtspSourceConfig;
+use gstreamer::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Test frame size; the frame counter is drawn as a row of CODE_BITS blocks in the top-left corner
+const WIDTH: usize = 640;
+const HEIGHT: usize = 360;
+const BLOCK: usize = 16;
+/// 24-bit frame counter followed by an 8-bit check, so frames with smeared blocks are skipped
+const CODE_BITS: usize = 32;
+/// Frames received before measuring starts, while the encoder and jitterbuffer settle
+const WARMUP_FRAMES: usize = 30;
+
+fn encode(counter: u32) -> u32 {
+    let counter = counter & 0xff_ffff;
+    let check = (counter ^ (counter >> 8) ^ (counter >> 16)) & 0xff;
+    (counter << 8) | check
+}
+
+/// The frame counter, if the code is intact; counting starts at 1, so blank frames don't match.
+fn decode(code: u32) -> Option<u32> {
+    let counter = code >> 8;
+    (counter != 0 && encode(counter) == code).then_some(counter)
+}
+
+/// Paints the code into an RGBA frame: white blocks for 1 bits, black for 0.
+fn stamp(frame: &mut [u8], stride: usize, code: u32) {
+    for bit in 0..CODE_BITS {
+        let value = if code & (1 << (CODE_BITS - 1 - bit)) != 0 { 255 } else { 0 };
+        for y in 0..BLOCK {
+            let row = y * stride + bit * BLOCK * 4;
+            frame[row..row + BLOCK * 4].fill(value);
+        }
+    }
+}
+
+/// Reads the code back from the block centers.
+fn read(frame: &[u8], stride: usize) -> u32 {
+    (0..CODE_BITS).fold(0, |code, bit| {
+        let center = (BLOCK / 2) * stride + (bit * BLOCK + BLOCK / 2) * 4;
+        (code << 1) | u32::from(frame[center] > 127)
+    })
+}
+
+/// Frames of one run, after the warmup.
+#[derive(Clone, Default)]
+struct Measurement {
+    /// Counters of the first and last frame received
+    first: Option<u32>,
+    last: u32,
+    /// Milliseconds from stamping to decode, per received frame
+    latencies: Vec<f64>,
+}
+
+impl Measurement {
+    fn summary(&self) -> String {
+        if self.latencies.is_empty() {
+            return "no frames came back".to_string();
+        }
+        let mut sorted = self.latencies.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        let avg = sorted.iter().sum::<f64>() / sorted.len() as f64;
+        format!(
+            "min {:.0} ms, avg {:.0} ms, p95 {:.0} ms, max {:.0} ms ({} of {} frames)",
+            sorted[0],
+            avg,
+            percentile(0.95),
+            sorted[sorted.len() - 1],
+            sorted.len(),
+            self.first.map(|first| self.last - first + 1).unwrap_or(0)
+        )
+    }
+}
+
+/// Sends the stamped test pattern through `sink`'s encode branch and the RTSP server,
+/// receives it back through nvurisrcbin and times every frame from stamping to decode.
+fn measure(sink: &SinkSpec, rtsp: &RtspSourceConfig, duration: Duration) -> Measurement {
+    let sender = gstreamer::parse_launch(&format!(
+        "videotestsrc is-live=true pattern=black ! video/x-raw,format=RGBA,width={},height={},framerate=30/1 ! \
+         identity name=latency_stamp ! {}",
+        WIDTH,
+        HEIGHT,
+        sink.branch(0)
+    ))
+    .expect("Failed to create latency test sender");
+    let receiver = gstreamer::parse_launch(&format!(
+        "nvurisrcbin uri=rtsp://127.0.0.1:{}{}{} ! nvvideoconvert ! \
+         video/x-raw,format=RGBA,width={},height={} ! identity name=latency_read ! fakesink sync=false",
+        sink.rtsp_port(),
+        sink.rtsp_mount(),
+        rtsp.properties(),
+        WIDTH,
+        HEIGHT
+    ))
+    .expect("Failed to create latency test receiver");
+
+    let sent: Arc<Mutex<HashMap<u32, Instant>>> = Arc::new(Mutex::new(HashMap::new()));
+    let measurement: Arc<Mutex<Measurement>> = Arc::new(Mutex::new(Measurement::default()));
+    let by_name = |pipeline: &gstreamer::Element, name: &str| {
+        pipeline
+            .downcast_ref::<gstreamer::Bin>()
+            .and_then(|b| b.by_name(name))
+            .expect("latency test element")
+    };
+
+    let stamp_sent = sent.clone();
+    let counter = AtomicU32::new(0);
+    by_name(&sender, "latency_stamp")
+        .static_pad("src")
+        .expect("identity has a src pad")
+        .add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+            if let Some(gstreamer::PadProbeData::Buffer(buffer)) = &mut info.data {
+                let buffer = buffer.make_mut();
+                if let Ok(mut map) = buffer.map_writable() {
+                    let counter = counter.fetch_add(1, Ordering::Relaxed) + 1;
+                    stamp(map.as_mut_slice(), WIDTH * 4, encode(counter));
+                    stamp_sent.lock().unwrap().insert(counter, Instant::now());
+                }
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+
+    let read_sent = sent.clone();
+    let read_measurement = measurement.clone();
+    let received = AtomicUsize::new(0);
+    by_name(&receiver, "latency_read")
+        .static_pad("src")
+        .expect("identity has a src pad")
+        .add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+            if let Some(gstreamer::PadProbeData::Buffer(buffer)) = &info.data {
+                if let Ok(map) = buffer.map_readable() {
+                    let Some(counter) = decode(read(map.as_slice(), WIDTH * 4)) else {
+                        return gstreamer::PadProbeReturn::Ok;
+                    };
+                    let received = received.fetch_add(1, Ordering::Relaxed) + 1;
+                    let mut sent = read_sent.lock().unwrap();
+                    let stamped = sent.remove(&counter);
+                    // Anything older didn't make it
+                    sent.retain(|c, _| *c > counter);
+                    if let (Some(at), true) = (stamped, received > WARMUP_FRAMES) {
+                        let mut measurement = read_measurement.lock().unwrap();
+                        measurement.first.get_or_insert(counter);
+                        measurement.last = counter;
+                        measurement.latencies.push(at.elapsed().as_secs_f64() * 1000.0);
+                    }
+                }
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+
+    for pipeline in [&sender, &receiver] {
+        pipeline
+            .set_state(gstreamer::State::Playing)
+            .expect("Unable to set the latency test pipeline to the Playing state");
+    }
+    let main_context = glib::MainContext::default();
+    let deadline = Instant::now() + duration;
+    let buses = [sender.bus().expect("Pipeline should have a bus"), receiver.bus().expect("Pipeline should have a bus")];
+    'run: while Instant::now() < deadline {
+        main_context.iteration(false);
+        for bus in &buses {
+            if let Some(msg) = bus.timed_pop(gstreamer::ClockTime::from_mseconds(10)) {
+                if let gstreamer::MessageView::Error(err) = msg.view() {
+                    eprintln!("Error from {:?}: {} ({:?})", err.src().map(|s| s.path_string()), err.error(), err.debug());
+                    break 'run;
+                }
+            }
+        }
+    }
+    for pipeline in [&receiver, &sender] {
+        pipeline
+            .set_state(gstreamer::State::Null)
+            .expect("Unable to set the latency test pipeline to the Null state");
+    }
+
+    let result = measurement.lock().unwrap().clone();
+    result
+}
+
+/// `detect latency-test [rtsp sink options]...`: measures encode -> RTSP -> decode latency for
+/// each set of rtsp sink options (one run with the defaults when none are given), e.g.
+/// `detect latency-test "bitrate=4000000" "bitrate=1000000 iframeinterval=30"`. The receive side
+/// uses RTSP_LATENCY / RTSP_PROTOCOL like GST_DEVICE inputs; LATENCY_TEST_SECONDS sets how long
+/// each run lasts (default 10).
+pub fn command(args: &[String]) -> ! {
+    let variants: Vec<String> = if args.is_empty() { vec![String::new()] } else { args.to_vec() };
+    let spec: Vec<String> = variants
+        .iter()
+        .enumerate()
+        .map(|(i, options)| format!("rtsp mount=/latency-test{} {}", i, options))
+        .collect();
+    let sinks = match parse_sinks(&spec.join(";")) {
+        Ok(sinks) => sinks,
+        Err(e) => {
+            eprintln!("Error: Invalid latency test options: {}", e);
+            eprintln!("Usage: detect latency-test [\"<rtsp sink options>\"]...");
+            std::process::exit(2);
+        }
+    };
+    let seconds = env::var("LATENCY_TEST_SECONDS").ok().and_then(|v| v.parse().ok()).unwrap_or(10);
+    let rtsp = RtspSourceConfig::from_env();
+
+    let mut ports: Vec<String> = sinks.iter().map(|s| s.rtsp_port()).collect();
+    ports.sort();
+    ports.dedup();
+    let _servers: Vec<_> = ports
+        .iter()
+        .map(|port| {
+            let port_sinks: Vec<&SinkSpec> = sinks.iter().filter(|s| s.rtsp_port() == *port).collect();
+            use gstreamer_rtsp_server::prelude::*;
+            let server = crate::setup_rtsp_server(port, &port_sinks);
+            server.attach(None).expect("Failed to attach RTSP server");
+            server
+        })
+        .collect();
+
+    let receive = if rtsp.properties().is_empty() { " nvurisrcbin defaults".to_string() } else { rtsp.properties() };
+    println!("Latency test: {}s per run, receiving with{}", seconds, receive);
+    let mut results = Vec::new();
+    for (options, sink) in variants.iter().zip(&sinks) {
+        let name = if options.trim().is_empty() { "defaults" } else { options.trim() };
+        println!("Measuring {} ...", name);
+        let measurement = measure(sink, &rtsp, Duration::from_secs(seconds));
+        println!("✓ {}: {}", name, measurement.summary());
+        results.push((name, measurement));
+    }
+    if results.len() > 1 {
+        println!();
+        println!("Summary");
+        for (name, measurement) in &results {
+            println!("  {:<40} {}", name, measurement.summary());
+        }
+    }
+    std::process::exit(if results.iter().all(|(_, m)| !m.latencies.is_empty()) { 0 } else { 1 });
+}
@@ -1,48 +1,275 @@
 use gstreamer::prelude::*;
 use gstreamer_rtsp_server::prelude::*;
 use std::env;
-use std::path::Path;
 use std::fs;
-use std::io::Write;
+use std::sync::{Arc, Mutex};
 
-fn create_filtered_config(base_config: &str, target_class_id: i32) -> Result<String, std::io::Error> {
-    // Read the base config
-    let config_content = fs::read_to_string(base_config)?;
-    
-    // Create a temporary config with class filtering
-    let temp_config_path = "/tmp/config_infer_filtered.txt";
-    let mut new_config = String::new();
-    
-    // Copy everything except [class-attrs-all] section
-    let mut in_class_attrs = false;
-    for line in config_content.lines() {
-        if line.trim().starts_with("[class-attrs-all]") {
-            in_class_attrs = true;
-            continue;
-        } else if in_class_attrs && line.trim().starts_with("[") {
-            in_class_attrs = false;
+mod abandoned;
+mod access;
+mod abr;
+mod analytics;
+mod anpr;
+mod banner;
+mod buffering;
+mod calibration;
+mod classes;
+mod classmap;
+mod clips;
+mod clock;
+mod compare;
+mod config;
+mod control;
+#[cfg(feature = "cuda")]
+mod cuda;
+mod crowd;
+mod custom;
+mod dataset;
+mod decoders;
+mod detections;
+mod diagnostics;
+mod doctor;
+mod encoders;
+mod enginecache;
+mod eval;
+mod events;
+mod eviction;
+mod export;
+mod face;
+mod fall;
+mod gps;
+mod graph;
+mod health;
+mod heatmap;
+mod idle;
+mod init;
+mod json;
+mod lanes;
+mod latency;
+mod launch;
+mod mapview;
+mod metrics;
+mod motion;
+mod negotiation;
+mod nvbufsurface;
+mod parking;
+mod parquet;
+mod playback;
+mod plugins;
+mod png;
+mod pools;
+mod ppe;
+mod presets;
+mod processing;
+mod profile;
+mod query;
+mod queues;
+mod recording;
+mod recovery;
+mod reid;
+mod replay;
+mod routing;
+mod samples;
+mod scheduler;
+#[cfg(feature = "scripting")]
+mod script;
+mod sinks;
+mod shadow;
+mod sizefilter;
+mod smoothing;
+mod snapshot;
+mod source;
+mod sourcemodels;
+mod speed;
+mod state;
+mod tamper;
+mod thermal;
+mod trajectory;
+mod viewers;
+mod warmup;
+mod zones;
+
+use abandoned::{LeftBehindConfig, LeftBehindDetector};
+use analytics::{AnalyticsConfig, AnalyticsExporter};
+use anpr::AnprConfig;
+use banner::Banner;
+use buffering::StageQueues;
+use control::Controller;
+use classes::{ClassFilter, FilterMode};
+use classmap::ClassMap;
+use clips::{ClipConfig, ClipRecorder, Clips};
+use compare::{CompareConfig, Comparison};
+use crowd::CrowdConfig;
+use custom::{CustomStage, StagePosition};
+use dataset::{DatasetConfig, DatasetWriter};
+use decoders::{Decode, DecoderConfig};
+use detections::FrameObserver;
+use diagnostics::Diagnostics;
+use encoders::EncoderConfig;
+use enginecache::EngineCache;
+use eval::{EvalConfig, Evaluator};
+use events::{EventBus, Events};
+use eviction::EvictionConfig;
+use export::{ExportConfig, Exporter};
+use face::FaceConfig;
+use fall::{FallConfig, FallDetector};
+use gps::{Gps, GpsConfig};
+use health::{Health, HealthConfig, HealthMonitor};
+use idle::IdleConfig;
+use heatmap::{HeatmapConfig, HeatmapExporter, Heatmaps};
+use lanes::{LaneConfig, LaneCounter};
+use launch::PipelineSpec;
+use mapview::{MapConfig, MapView};
+use metrics::{Metrics, MetricsRegistry};
+use motion::MotionConfig;
+use parking::ParkingConfig;
+use playback::PlaybackConfig;
+use pools::PoolConfig;
+use queues::{QueueConfig, QueueMonitor};
+use ppe::PpeConfig;
+use recording::RecordConfig;
+use recovery::{BusHandler, Policy, RecoveryConfig};
+use reid::ReidConfig;
+use replay::{MetadataDump, ReplayConfig};
+use scheduler::SchedulerConfig;
+use shadow::{Shadow, ShadowConfig};
+use sinks::{sinks_from_env, SinkKind, SinkSpec};
+use sizefilter::SizeFilterConfig;
+use smoothing::SmoothingConfig;
+use snapshot::{SnapshotConfig, Snapshots};
+use source::{redact_credentials, SourceConfig, StartupWait};
+use sourcemodels::SourceModelConfig;
+use speed::{SpeedConfig, SpeedEstimator};
+use state::{RuntimeState, SharedState, StateConfig, StateRecorder};
+use tamper::TamperConfig;
+use thermal::ThermalConfig;
+use trajectory::TrajectoryConfig;
+use viewers::Viewers;
+use warmup::Readiness;
+
+// Not exposed by glib's safe API; value is the same on every Unix we target
+#[cfg(unix)]
+const SIGINT: i32 = 2;
+
+const LABELS_PATH: &str = "/models/labels.txt";
+const DEFAULT_MODEL_CONFIG: &str = "/opt/nvidia/deepstream/deepstream/samples/configs/deepstream-app/config_infer_primary.txt";
+
+type Observers = Arc<Mutex<Vec<Box<dyn FrameObserver>>>>;
+
+/// Installs a buffer probe on the OSD sink pad that hands each frame's detections to the observers.
+/// `bin` is the pipeline containing the `osd` element; `track_id_offset` keeps track IDs
+/// unique across restarts when state persistence is on.
+fn attach_detection_probe(
+    bin: &gstreamer::Element,
+    observers: Observers,
+    mux_width: u32,
+    mux_height: u32,
+    track_id_offset: u64,
+) {
+    let osd = match bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("osd")) {
+        Some(osd) => osd,
+        None => {
+            eprintln!("Warning: nvdsosd element not found, detection outputs disabled");
+            return;
         }
-        
-        if !in_class_attrs {
-            new_config.push_str(line);
-            new_config.push('\n');
+    };
+    let sink_pad = osd.static_pad("sink").expect("nvdsosd has a sink pad");
+
+    sink_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            let mut frames = detections::collect(batch, mux_width, mux_height);
+            if track_id_offset > 0 {
+                for det in frames.iter_mut().flat_map(|f| f.objects.iter_mut()) {
+                    det.track_id = det.track_id.map(|id| id + track_id_offset);
+                }
+            }
+            let mut observers = observers.lock().unwrap();
+            for frame in &frames {
+                for observer in observers.iter_mut() {
+                    observer.on_frame(frame);
+                }
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
+
+/// Samples frames for the dataset branch: dropped buffers never reach the JPEG
+/// encoder, so multifilesink's running index matches the writer's label index.
+fn attach_dataset_probe(bin: &gstreamer::Element, writer: Arc<Mutex<DatasetWriter>>, mux_width: u32, mux_height: u32) {
+    let queue = match bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("dataset_q")) {
+        Some(queue) => queue,
+        None => {
+            eprintln!("Warning: dataset branch not found, dataset output disabled");
+            return;
+        }
+    };
+    let src_pad = queue.static_pad("src").expect("queue has a src pad");
+
+    src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        let frames = nvds::BatchMeta::from_probe(info)
+            .map(|batch| detections::collect(batch, mux_width, mux_height))
+            .unwrap_or_default();
+        let keep = match frames.first() {
+            Some(frame) => writer.lock().unwrap().sample(frame),
+            None => false,
+        };
+        if !keep {
+            return gstreamer::PadProbeReturn::Drop;
         }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
+
+fn finish_observers(observers: &Observers) {
+    for observer in observers.lock().unwrap().iter_mut() {
+        observer.finish();
     }
-    
-    // Add class-specific filtering: high threshold for non-target classes
-    new_config.push_str(&format!("\n[class-attrs-{}]\n", target_class_id));
-    new_config.push_str("pre-cluster-threshold=0.25\n");
-    new_config.push_str("\n[class-attrs-all]\n");
-    new_config.push_str("pre-cluster-threshold=1.0\n");  // Impossible threshold to hide other classes
-    
-    // Write to temp file
-    let mut file = fs::File::create(temp_config_path)?;
-    file.write_all(new_config.as_bytes())?;
-    
-    Ok(temp_config_path.to_string())
 }
 
-fn setup_rtsp_server(pipeline_str: &str, port: &str, mount_point: &str) -> gstreamer_rtsp_server::RTSPServer {
+/// Applies the sink's transport options (protocols, RTP port range, multicast group).
+fn configure_transport(factory: &gstreamer_rtsp_server::RTSPMediaFactory, sink: &SinkSpec) {
+    use gstreamer_rtsp_server::gst_rtsp::RTSPLowerTrans;
+
+    // Already validated when the sinks were parsed
+    let transport = sink.rtsp_transport().expect("Invalid RTSP transport options");
+    let mut protocols = RTSPLowerTrans::empty();
+    if transport.tcp {
+        protocols |= RTSPLowerTrans::TCP;
+    }
+    if transport.udp {
+        protocols |= RTSPLowerTrans::UDP;
+    }
+    if transport.multicast {
+        protocols |= RTSPLowerTrans::UDP_MCAST;
+    }
+    factory.set_protocols(protocols);
+
+    if transport.rtp_ports.is_none() && transport.multicast_range.is_none() {
+        return;
+    }
+    let pool = gstreamer_rtsp_server::RTSPAddressPool::new();
+    let (min_port, max_port) = transport.rtp_ports.unwrap_or((5000, 5010));
+    if transport.rtp_ports.is_some() {
+        let any = gstreamer_rtsp_server::RTSP_ADDRESS_POOL_ANY_IPV4.as_str();
+        pool.add_range(any, any, min_port, max_port, 0)
+            .expect("Failed to add RTP port range");
+    }
+    if let Some((min_address, max_address)) = &transport.multicast_range {
+        pool.add_range(min_address, max_address, min_port, max_port, transport.ttl)
+            .expect("Failed to add multicast address range");
+        // Clients can't ask for a wider scope than configured
+        factory.set_property("max-mcast-ttl", transport.ttl as u32);
+        if let Some(iface) = &transport.multicast_iface {
+            factory.set_multicast_iface(Some(iface));
+        }
+    }
+    factory.set_address_pool(Some(&pool));
+}
+
+/// Serves every RTSP sink sharing `port`. Each mount only wraps a udpsrc reading the
+/// RTP that sink's branch sends to localhost, so inference and encoding run once in the
+/// main pipeline no matter how many clients or mounts there are.
+fn setup_rtsp_server(port: &str, rtsp_sinks: &[&SinkSpec]) -> gstreamer_rtsp_server::RTSPServer {
     use gstreamer_rtsp_server::prelude::*;
     
     let server = gstreamer_rtsp_server::RTSPServer::new();
@@ -52,27 +279,35 @@ fn setup_rtsp_server(pipeline_str: &str, port: &str, mount_point: &str) -> gstre
     server.set_address("0.0.0.0");
     server.set_service(port);
     
-    // Create and configure the media factory
-    let factory = gstreamer_rtsp_server::RTSPMediaFactory::new();
-    
-    println!("DEBUG: Setting pipeline: {}", pipeline_str);
-    factory.set_launch(pipeline_str);
-    factory.set_shared(true);
-    
-    // Connect to factory signals for debugging
-    factory.connect_media_constructed(|_factory, media| {
-        println!("DEBUG: Media constructed");
-        media.connect_new_stream(|_media, stream| {
-            println!("DEBUG: New stream created: {:?}", stream);
-        });
-        media.connect_prepared(|_media| {
-            println!("DEBUG: Media prepared");
-        });
-    });
-    
-    // Get mount points and add the factory
+    // Get mount points and add one factory per sink
     let mounts = server.mount_points().expect("Could not get mount points");
-    mounts.add_factory(mount_point, factory);
+    for sink in rtsp_sinks {
+        let factory = gstreamer_rtsp_server::RTSPMediaFactory::new();
+        let launch = format!(
+            "( udpsrc name=pay0 port={} buffer-size=524288 \
+             caps=\"application/x-rtp, media=video, clock-rate=90000, encoding-name={}, payload=96\" )",
+            sink.rtsp_udp_port(),
+            sink.codec().encoding_name()
+        );
+        println!("DEBUG: Setting pipeline: {}", launch);
+        factory.set_launch(&launch);
+        factory.set_shared(true);
+        configure_transport(&factory, sink);
+        
+        // Connect to factory signals for debugging
+        factory.connect_media_constructed(|_factory, media| {
+            println!("DEBUG: Media constructed");
+            media.connect_new_stream(|_media, stream| {
+                println!("DEBUG: New stream created: {:?}", stream);
+            });
+            media.connect_prepared(|_media| {
+                println!("DEBUG: Media prepared");
+            });
+        });
+        
+        mounts.add_factory(sink.rtsp_mount(), factory);
+        println!("DEBUG: Mount point: {}", sink.rtsp_mount());
+    }
     
     // Connect to server signals
     server.connect_client_connected(|_server, client| {
@@ -80,33 +315,116 @@ fn setup_rtsp_server(pipeline_str: &str, port: &str, mount_point: &str) -> gstre
     });
     
     println!("DEBUG: RTSP server configured for {}", address);
-    println!("DEBUG: Mount point: {}", mount_point);
     
     server
 }
 
 fn main() {
+    // Settings from APP_CONFIG_JSON and DETECT_CONFIG become environment defaults for
+    // everything below
+    config::load_json_config();
+    config::load_config_file();
+    // `--preset <name>` / PRESET fills in whatever is still unset, then `--profile <name>` / PROFILE
+    presets::apply();
+    profile::apply();
+
+    // `detect doctor` checks the installation and configuration instead of running
+    if env::args().nth(1).as_deref() == Some("doctor") {
+        let model_config = env::var("MODEL_CONFIG").unwrap_or_else(|_| DEFAULT_MODEL_CONFIG.to_string());
+        doctor::run(&model_config, LABELS_PATH);
+    }
+
+    // `detect init [file]` asks a few questions and writes a DETECT_CONFIG file
+    if env::args().nth(1).as_deref() == Some("init") {
+        init::command(&env::args().skip(2).collect::<Vec<_>>(), LABELS_PATH, DEFAULT_MODEL_CONFIG);
+    }
+
+    // `detect presets` lists the named presets
+    if env::args().nth(1).as_deref() == Some("presets") {
+        presets::command();
+    }
+
+    // `detect samples [list | fetch [name...]]` downloads sample clips for GST_DEVICE=sample:<name>
+    if env::args().nth(1).as_deref() == Some("samples") {
+        samples::command(&env::args().skip(2).collect::<Vec<_>>());
+    }
+
     // Initialize GStreamer
     gstreamer::init().expect("Failed to initialize GStreamer");
 
-    // Input device can be passed via env GST_DEVICE or RTSP_URL
+    // `detect enroll <name> <image>...` adds a face to the recognition gallery
+    if env::args().nth(1).as_deref() == Some("enroll") {
+        face::enroll_command(&env::args().skip(2).collect::<Vec<_>>());
+    }
+
+    // `detect latency-test [rtsp sink options]...` times frames through encode, RTSP and decode
+    if env::args().nth(1).as_deref() == Some("latency-test") {
+        latency::command(&env::args().skip(2).collect::<Vec<_>>());
+    }
+
+    // `detect warmup [config...]` builds the TensorRT engines ahead of the first run
+    if env::args().nth(1).as_deref() == Some("warmup") {
+        let model_config = env::var("MODEL_CONFIG").unwrap_or_else(|_| DEFAULT_MODEL_CONFIG.to_string());
+        warmup::command(&env::args().skip(2).collect::<Vec<_>>(), &model_config);
+    }
+
+    // `detect cache [list | clear [stale | <model>]]` manages the engines in ENGINE_CACHE_DIR
+    if env::args().nth(1).as_deref() == Some("cache") {
+        enginecache::command(&env::args().skip(2).collect::<Vec<_>>());
+    }
+
+    // `detect query <label> [export <dir>]` finds detections in the recordings of RECORD_DIR
+    if env::args().nth(1).as_deref() == Some("query") {
+        query::command(&env::args().skip(2).collect::<Vec<_>>());
+    }
+
+    // Persisted runtime state (STATE_FILE), restored before anything depends on it
+    let state_config = StateConfig::from_env();
+    let state: Option<SharedState> = state_config
+        .as_ref()
+        .map(|config| Arc::new(Mutex::new(RuntimeState::load(&config.path))));
+
+    // Input device can be passed via env GST_DEVICE or RTSP_URL, else the one from the last run
     let device = env::var("GST_DEVICE")
         .or_else(|_| env::var("RTSP_URL"))
-        .unwrap_or_else(|_| "test".to_string());
+        .ok()
+        .or_else(|| state.as_ref().and_then(|s| s.lock().unwrap().get("source").map(str::to_string)))
+        .unwrap_or_else(|| "test".to_string());
+    if let Some(state) = &state {
+        state.lock().unwrap().set("source", &device);
+    }
+    // Several inputs can be given comma-separated; they are batched into one nvstreammux.
+    // sample:<name> inputs are clips downloaded with `detect samples`
+    let devices: Vec<String> = device
+        .split(',')
+        .map(|d| samples::resolve(d.trim()))
+        .filter(|d| !d.is_empty())
+        .collect();
+    let devices = if devices.is_empty() { vec!["test".to_string()] } else { devices };
+    // Inputs that aren't up yet are waited for (SOURCE_STARTUP_TIMEOUT) instead of failing the start
+    if let Some(startup) = StartupWait::from_env() {
+        startup.wait(&devices);
+    }
+
+    // NVDEC capacity: inputs beyond it decode on the CPU or are left out (DECODER_MAX_SESSIONS,
+    // DECODER_OVERFLOW)
+    let decoder_config = DecoderConfig::from_env();
+    let decodes = decoder_config.assign(&devices, redact_credentials);
+    let decoder_summary = decodes.iter().any(|d| *d != Decode::Raw).then(|| decoder_config.describe(&decodes));
+    let (devices, decodes): (Vec<String>, Vec<Decode>) =
+        devices.into_iter().zip(decodes).filter(|(_, decode)| *decode != Decode::Rejected).unzip();
     
     // Object to detect (e.g., "person", "car", "dog", etc.)
     let target_object = env::var("DETECT_OBJECT").unwrap_or_else(|_| "person".to_string());
     
     // Find the class ID for the target object from labels.txt
-    let labels_path = "/models/labels.txt";
-    let target_class_id = if let Ok(contents) = fs::read_to_string(labels_path) {
-        contents.lines()
-            .enumerate()
-            .find(|(_, line)| line.trim() == target_object)
-            .map(|(idx, _)| idx as i32)
-    } else {
-        None
-    };
+    let labels: Vec<String> = fs::read_to_string(LABELS_PATH)
+        .map(|contents| contents.lines().map(|line| line.trim().to_string()).collect())
+        .unwrap_or_default();
+    let target_class_id = labels
+        .iter()
+        .position(|label| *label == target_object)
+        .map(|idx| idx as i32);
     
     let filter_class_id = env::var("FILTER_CLASS_ID").ok()
         .and_then(|v| v.parse::<i32>().ok())
@@ -114,14 +432,20 @@ fn main() {
     
     // Model configuration
     let model_engine = env::var("MODEL_ENGINE").unwrap_or_else(|_| "".to_string());
-    let model_config = env::var("MODEL_CONFIG").unwrap_or_else(|_| "/opt/nvidia/deepstream/deepstream/samples/configs/deepstream-app/config_infer_primary.txt".to_string());
+    let model_config = env::var("MODEL_CONFIG").unwrap_or_else(|_| DEFAULT_MODEL_CONFIG.to_string());
     
-    // Create filtered config if class filtering is requested
-    let final_config = if let Some(class_id) = filter_class_id {
+    // Create filtered config if class filtering is requested; CLASS_FILTER=metadata drops
+    // the other classes' objects after inference instead and leaves the model config alone
+    let filter_mode = FilterMode::from_env();
+    let final_config = if let (Some(class_id), FilterMode::Metadata) = (filter_class_id, filter_mode) {
+        println!("Target object '{}' (class ID: {})", target_object, class_id);
+        println!("Class filtering: ENABLED (metadata) - Only showing '{}' detections", target_object);
+        model_config.clone()
+    } else if let Some(class_id) = filter_class_id {
         println!("Target object '{}' (class ID: {})", target_object, class_id);
         println!("Class filtering: ENABLED - Only showing '{}' detections", target_object);
         
-        match create_filtered_config(&model_config, class_id) {
+        match classes::create_filtered_config(&model_config, &[class_id], &classes::filtered_config_path(0)) {
             Ok(filtered_config) => {
                 println!("✓ Created filtered config: {}", filtered_config);
                 filtered_config
@@ -136,164 +460,1019 @@ fn main() {
         println!("Class filtering: DISABLED - Showing all detections");
         model_config.clone()
     };
+    // Engines from ENGINE_CACHE_DIR, only for the model, GPU and TensorRT they were built for
+    let engine_cache = EngineCache::from_env();
+    let cached_engine = engine_cache.as_ref().and_then(|cache| cache.prepare(&final_config, &model_config));
+    let final_config = cached_engine.as_ref().map_or(final_config, |cached| cached.config.clone());
+    // The `classes` control command swaps the filtered classes while running
+    let class_filter = ClassFilter::new(filter_mode, &model_config, labels.clone(), filter_class_id.into_iter().collect());
+
+    // Per-class box size and aspect limits applied after inference (SIZE_FILTERS)
+    let size_filter = SizeFilterConfig::from_env(&labels);
+
+    // Model labels renamed and merged into a stable taxonomy (CLASS_MAP); everything that
+    // reports objects from here on gets the taxonomy's names
+    let class_map = ClassMap::from_env(&labels);
+    let labels = class_map.as_ref().map_or(labels, |map| map.taxonomy.clone());
+
+    // Tracks must persist before they are reported, and are drawn a while after they vanish
+    let smoothing_config = SmoothingConfig::from_env();
+
+    // Other primary models for some of the inputs (SOURCE_MODELS)
+    let source_models = SourceModelConfig::from_env(devices.len());
+    // Candidate model compared side by side with MODEL_CONFIG (COMPARE_MODEL)
+    let compare_config = CompareConfig::from_env(devices.len());
+    // Candidate model inferring silently for rollout validation (SHADOW_MODEL)
+    let shadow_config = ShadowConfig::from_env();
     
-    // Display options
-    let show_display = env::var("SHOW_DISPLAY").unwrap_or_else(|_| "true".to_string()) == "true";
-    
-    // RTSP output options
-    let rtsp_output = env::var("RTSP_OUTPUT").ok();
-    let rtsp_port = env::var("RTSP_OUTPUT_PORT").unwrap_or_else(|_| "8555".to_string());
-    
+    // Output sink graph (SINKS, or the legacy RTSP_OUTPUT / SHOW_DISPLAY switches)
+    let mut sinks = match sinks_from_env() {
+        Ok(sinks) => sinks,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // Recording options (RECORD_MODE=annotated|clean|dual)
+    let mut record_config = RecordConfig::from_env(devices.len());
+
+    // NVENC sessions for the recordings and encoding sinks, the rest encode on the CPU
+    // (ENCODER_MAX_SESSIONS, ENCODER_OVERFLOW)
+    let encoder_config = EncoderConfig::from_env();
+    let mut encodes: Vec<(String, Option<bool>)> = Vec::new();
+    let mut copies = Vec::new();
+    if let Some(rec) = &record_config {
+        if rec.mode.records_clean() {
+            copies.push("clean");
+        }
+        if rec.mode.records_annotated() {
+            copies.push("annotated");
+        }
+    }
+    encodes.extend(copies.iter().map(|c| (format!("recording ({})", c), None)));
+    for sink in sinks.iter().filter(|s| s.encodes()) {
+        encodes.push((sink.describe(), sink.encoder_choice().map(|c| c == "hardware")));
+    }
+    let hardware = encoder_config.assign(&encodes);
+    if let Some(rec) = &mut record_config {
+        rec.software = copies.iter().zip(&hardware).filter(|(_, hw)| !**hw).map(|(c, _)| *c).collect();
+    }
+    let sink_hardware = hardware[copies.len()..].iter();
+    for (sink, hw) in sinks.iter_mut().filter(|s| s.encodes()).zip(sink_hardware) {
+        if !hw && sink.encoder_choice().is_none() {
+            sink.use_software_encoder();
+        }
+    }
+    let rtsp_sinks: Vec<&SinkSpec> = sinks.iter().filter(|s| s.kind == SinkKind::Rtsp).collect();
+
+    // Auto-labeling dataset output (DATASET_FORMAT=yolo|voc)
+    let dataset_config = DatasetConfig::from_env(&devices);
+
+    // Annotation export (EXPORT_FORMAT=mot,coco)
+    let export_config = ExportConfig::from_env(&devices);
+
+    // Accuracy evaluation against a ground-truth file (EVAL_GT)
+    let eval_config = EvalConfig::from_env(filter_class_id);
+
+    // Optional multi-object tracker; needed for persistent track IDs in exports
+    let tracker_config = env::var("TRACKER_CONFIG").ok();
+    let tracker_lib = env::var("TRACKER_LIB").unwrap_or_else(|_| "/opt/nvidia/deepstream/deepstream/lib/libnvds_nvmultiobjecttracker.so".to_string());
+
+    // Optional user GPU stage (CUSTOM_STAGE or CUSTOM_LIB for nvdsvideotemplate)
+    let custom_stage = CustomStage::from_env();
+
+    // Analytics zones (ZONES) and the event outputs they report to (EVENTS_FILE)
+    let zones = zones::zones_from_env();
+    let events: Events = Arc::new(Mutex::new(EventBus::from_env()));
+
+    // Position of a mobile camera from NMEA (GPS_SOURCE), attached to events and recordings
+    let gps_config = GpsConfig::from_env();
+
+    // Cross-camera re-identification with a ReID embedding model (REID_CONFIG)
+    let reid_config = ReidConfig::from_env();
+
+    // Face recognition against an enrolled gallery (FACE_DETECT_CONFIG + FACE_EMBED_CONFIG)
+    let face_config = FaceConfig::from_env();
+
+    // Licence plate recognition and watchlist alerts (LPD_CONFIG + LPR_CONFIG)
+    let anpr_config = AnprConfig::from_env();
+
+    // PPE compliance of people in zones with a secondary helmet/vest model (PPE_CONFIG)
+    let ppe_config = PpeConfig::from_env();
+
+    // Crowd counting per zone with a density-estimation model (DENSITY_CONFIG)
+    let crowd_config = CrowdConfig::from_env();
+
+    // Motion in zones from nvof optical flow (MOTION_DETECT=true)
+    let motion_config = MotionConfig::from_env();
+
+    // Lower inference rate and bitrate while nothing happens (IDLE_AFTER)
+    let idle_config = IdleConfig::from_env();
+
+    // Real-world speed of tracked objects from a ground-plane calibration (CALIBRATION)
+    let speed_config = SpeedConfig::from_env();
+
+    // Abandoned / removed object alerts (LEFT_BEHIND=true)
+    let left_behind_config = LeftBehindConfig::from_env();
+
+    // Per-lane vehicle counts by class (LANES), exported to LANE_COUNTS_FILE and METRICS_PORT
+    let lane_config = LaneConfig::from_env();
+
+    // What to do when an element fails (ERROR_POLICY); output branches restart by default
+    let source_config = SourceConfig::from_env();
+    let recovery_config = RecoveryConfig::from_env(source_config.ingest.drone);
+
+    // Per-minute class counts and zone occupancy as CSV/Parquet files (ANALYTICS_DIR)
+    let analytics_config = AnalyticsConfig::from_env();
+
+    // Tracked objects plotted on a floorplan through per-camera homographies (MAP_DIR)
+    let map_config = MapConfig::from_env();
+
+    // Parking spot occupancy from vehicle boxes (PARKING_SPOTS)
+    let parking_config = ParkingConfig::from_env();
+
+    // Queue length and wait time estimates per queue polygon (QUEUE_ZONES)
+    let queue_config = QueueConfig::from_env();
+
+    // Fall detection from pose keypoints or person boxes (FALL_DETECT=true)
+    let fall_config = FallConfig::from_env();
+
+    // 16-bit thermal cameras: normalization, false color and hot spot alerts (THERMAL=true)
+    let thermal_config = ThermalConfig::from_env();
+
+    // Recorded files aligned by start offsets and batched in lockstep (SOURCE_OFFSETS / SYNC_PLAYBACK)
+    let playback_config = PlaybackConfig::from_env(devices.len());
+
+    // Diagnostics zip for bug reports when the pipeline fails (DIAGNOSTICS_DIR)
+    let mut diagnostics = Diagnostics::from_env();
+
+    // Frame-by-frame replay of files from the control API with a metadata dump (REPLAY=true)
+    let replay_config = ReplayConfig::from_env(&devices);
+    // Replay always batches in lockstep so every run feeds the tracker the same batches
+    let playback_config = playback_config.or_else(|| replay_config.as_ref().map(|_| PlaybackConfig::lockstep(devices.len())));
+
+    // Event clips with pre-roll from RTSP sources via smart record (CLIP_DIR)
+    let clip_config = ClipConfig::from_env();
+
+    // Fading trails behind tracked objects on the OSD (TRAJECTORIES=true)
+    let trajectory_config = TrajectoryConfig::from_env();
+
+    // Frozen / black / low-bitrate input detection and health scores (HEALTH_MONITOR=true)
+    let health_config = HealthConfig::from_env();
+
+    // Stalled inputs taken out of the batch until they deliver again (SOURCE_TIMEOUT)
+    let eviction_config = EvictionConfig::from_env(devices.len());
+
+    // Per-input inference intervals kept within a GPU load budget (SOURCE_PRIORITY / GPU_BUDGET)
+    let scheduler_config = SchedulerConfig::from_env(devices.len());
+
+    // Camera moved / blocked / defocused alerts from image statistics (TAMPER_DETECT=true)
+    let tamper_config = TamperConfig::from_env();
+
+    // Detection heatmap images per source (HEATMAP_EXPORT_DIR)
+    let heatmap_config = HeatmapConfig::from_env();
+
+    // Optional Rust frame processor on mapped surfaces (FRAME_HOOK)
+    let frame_hook = processing::from_env();
+
+    // Optional CUDA kernel stage on device surfaces (CUDA_STAGE, needs the cuda feature)
+    #[cfg(feature = "cuda")]
+    let cuda_stage = cuda::CudaStage::from_env();
+    #[cfg(not(feature = "cuda"))]
+    if env::var("CUDA_STAGE").is_ok() {
+        eprintln!("Warning: CUDA_STAGE is set but this build lacks the cuda feature (cargo build --features cuda)");
+    }
+
+    // Optional Rhai script run on every frame (SCRIPT, needs the scripting feature)
+    #[cfg(feature = "scripting")]
+    let script = script::Script::from_env();
+    #[cfg(not(feature = "scripting"))]
+    if env::var("SCRIPT").is_ok() {
+        eprintln!("Warning: SCRIPT is set but this build lacks the scripting feature (cargo build --features scripting)");
+    }
+
     // Output dimensions (optional)
     let output_width = env::var("OUTPUT_WIDTH").unwrap_or_else(|_| "1920".to_string());
     let output_height = env::var("OUTPUT_HEIGHT").unwrap_or_else(|_| "1080".to_string());
 
+    // Latest annotated frame for GET /snapshot.jpg (SNAPSHOT_INTERVAL)
+    let snapshot_config = SnapshotConfig::from_env(
+        devices.len(),
+        output_width.parse().unwrap_or(1920),
+        output_height.parse().unwrap_or(1080),
+    );
+
     println!("DeepStream Object Detection Pipeline");
-    println!("  Input: {}", device);
+    let software_decoded = |index: usize| if decodes[index] == Decode::Software { " (software decode)" } else { "" };
+    if devices.len() == 1 {
+        println!("  Input: {}{}", redact_credentials(&devices[0]), software_decoded(0));
+    } else {
+        for (index, input) in devices.iter().enumerate() {
+            println!("  Input {}: {}{}", index, redact_credentials(input), software_decoded(index));
+        }
+    }
+    if let Some(summary) = &decoder_summary {
+        println!("  Decoders: {}", summary);
+    }
     println!("  Target Object: {}", target_object);
+    if let Some(class_map) = &class_map {
+        println!("  Class Map: {}", class_map.describe());
+    }
+    if let Some(size_filter) = &size_filter {
+        println!("  Size Filters: {}", size_filter.describe(&labels));
+    }
+    if let Some(source_models) = &source_models {
+        for model in &source_models.models {
+            let sources: Vec<String> = model.sources.iter().map(|s| s.to_string()).collect();
+            println!("  Source Model: {} on source {}", model.model_config, sources.join(", "));
+        }
+    }
+    if let Some(compare) = &compare_config {
+        println!(
+            "  A/B Comparison: {} on inputs {}..{}{}",
+            compare.model_config,
+            compare.sources,
+            compare.sources * 2 - 1,
+            compare.report_path.as_ref().map(|p| format!(", report {}", p)).unwrap_or_default()
+        );
+    }
+    if let Some(shadow) = &shadow_config {
+        println!("  Shadow Model: {} (detections to {})", shadow.model_config, shadow.log_path);
+    }
+    if let Some(smoothing) = &smoothing_config {
+        println!(
+            "  Detection Smoothing: shown after {} frames, held for {} frames",
+            smoothing.min_frames, smoothing.hold_frames
+        );
+        if tracker_config.is_none() {
+            println!("  Note: No TRACKER_CONFIG set - smoothing needs track IDs");
+        }
+    }
     println!("  Model Engine: {}", model_engine);
+    if let (Some(cache), Some(cached)) = (&engine_cache, &cached_engine) {
+        let state = if cached.hit { "cached engine" } else { "no engine for this model, GPU and TensorRT yet, building" };
+        println!("  Engine Cache: {} ({})", cache.dir.display(), state);
+    }
     println!("  Model Config: {}", final_config);
-    println!("  Display: {}", if show_display { "enabled" } else { "disabled" });
-    if rtsp_output.is_some() {
-        println!("  RTSP Stream: rtsp://localhost:{}/ds-detect", rtsp_port);
-    }
-
-    // Build output sink based on configuration
-    // IMPORTANT: nvdsosd outputs video/x-raw(memory:NVMM) - keep it in GPU memory!
-    let output_sink = if rtsp_output.is_some() {
-        // RTSP output - encode to H.264 and pay for RTP
-        // The RTSP server will handle the streaming
-        let rtsp_sink = "nvvideoconvert ! video/x-raw(memory:NVMM),format=I420 ! \
-                         nvv4l2h264enc bitrate=4000000 insert-sps-pps=true ! \
-                         h264parse ! rtph264pay name=pay0 pt=96".to_string();
-        
-        if show_display {
-            // Use tee to split for both RTSP and display
-            format!(
-                "nvvideoconvert ! video/x-raw(memory:NVMM),format=I420 ! tee name=t \
-                 t. ! queue ! nvv4l2h264enc bitrate=4000000 insert-sps-pps=true ! h264parse ! rtph264pay name=pay0 pt=96 \
-                 t. ! queue ! nvvideoconvert ! ximagesink sync=false"
-            )
-        } else {
-            rtsp_sink
-        }
-    } else if show_display {
-        // Display only - convert from GPU to CPU for X11
-        "nvvideoconvert ! ximagesink sync=false".to_string()
-    } else {
-        "fakesink sync=false".to_string()
+    for sink in &sinks {
+        println!("  Sink: {}", sink.describe());
+    }
+    let stage_queues = StageQueues::from_env();
+    println!("  Stage Queues: {}", stage_queues.describe());
+    let pool_config = PoolConfig::from_env();
+    if !pool_config.is_default() {
+        println!("  Buffer Pools: {}", pool_config.describe());
+    }
+    let nvenc = hardware.iter().filter(|hw| **hw).count();
+    if !hardware.is_empty() {
+        let sessions = encoder_config.max_sessions.map_or("no session limit".to_string(), |max| format!("{} sessions", max));
+        println!(
+            "  Encoders: {} NVENC, {} software ({}: {})",
+            nvenc,
+            hardware.len() - nvenc,
+            encoder_config.source,
+            sessions
+        );
+    }
+    for rtsp in &rtsp_sinks {
+        println!("  RTSP Stream: rtsp://localhost:{}{}", rtsp.rtsp_port(), rtsp.rtsp_mount());
+    }
+    match &tracker_config {
+        Some(config) => println!("  Tracker: {}", config),
+        None => println!("  Tracker: disabled"),
+    }
+    if let Some(export) = &export_config {
+        for source_id in 0..export.source_stems.len() {
+            for format in &export.formats {
+                println!("  Export: {}", export.path(*format, source_id));
+            }
+        }
+        if tracker_config.is_none() {
+            println!("  Note: No TRACKER_CONFIG set - exported track IDs will be -1/null");
+        }
+    }
+    if let Some(eval) = &eval_config {
+        println!("  Evaluation: ground truth {}", eval.gt_path);
+        if devices.len() > 1 {
+            println!("  Note: Only the first input is scored against EVAL_GT");
+        }
+    }
+    if let Some(stage) = &custom_stage {
+        println!("  Custom Stage ({:?}): {}", stage.position, stage.element);
+    }
+    if frame_hook.is_some() {
+        println!("  Frame Hook: {}", env::var("FRAME_HOOK").unwrap_or_default());
+    }
+    #[cfg(feature = "scripting")]
+    if let Some(script) = &script {
+        println!("  Script: {}", script.describe());
+    }
+    for zone in &zones {
+        println!("  Zone: {} ({} points)", zone.name, zone.points.len());
+    }
+    if let Some(reid) = &reid_config {
+        println!("  ReID Model: {} (match at similarity {})", reid.model_config, reid.threshold);
+        if tracker_config.is_none() {
+            println!("  Note: No TRACKER_CONFIG set - re-identification needs track IDs");
+        }
+    }
+    if let Some(face) = &face_config {
+        println!("  Face Recognition: gallery {} (match at similarity {})", face.gallery_path, face.threshold);
+    }
+    if let Some(anpr) = &anpr_config {
+        match &anpr.watchlist_path {
+            Some(path) => println!("  Plate Recognition: watchlist {} (up to {} OCR edits)", path, anpr.max_distance),
+            None => println!("  Plate Recognition: no ANPR_WATCHLIST, plates are only labelled"),
+        }
+    }
+    if let Some(ppe) = &ppe_config {
+        println!("  PPE Compliance: {} required ({})", ppe.required.join(", "), ppe.model_config);
+        if tracker_config.is_none() {
+            println!("  Note: No TRACKER_CONFIG set - PPE compliance needs track IDs");
+        }
+    }
+    if let Some(crowd) = &crowd_config {
+        println!("  Crowd Density Model: {}", crowd.model_config);
+    }
+    if let Some(motion) = &motion_config {
+        println!("  Motion Detection: threshold {} px/frame over {} frames", motion.threshold, motion.min_frames);
+    }
+    if let Some(idle) = &idle_config {
+        println!(
+            "  Idle Mode: after {:?} without detections or motion, inferring every {} batches at {}% bitrate",
+            idle.after,
+            idle.infer_interval + 1,
+            idle.bitrate_percent
+        );
+    }
+    if let Some(speed) = &speed_config {
+        match speed.limit_kmh {
+            Some(limit) => println!("  Speed Estimation: speeding above {} km/h", limit),
+            None => println!("  Speed Estimation: enabled"),
+        }
+        if tracker_config.is_none() {
+            println!("  Note: No TRACKER_CONFIG set - speed estimation needs track IDs");
+        }
+    }
+    if let Some(left_behind) = &left_behind_config {
+        println!(
+            "  Left-Behind Detection: static after {}s, removed after {}s",
+            left_behind.stationary_secs, left_behind.removed_secs
+        );
+    }
+    if let Some(lane) = &lane_config {
+        let names: Vec<&str> = lane.lanes.iter().map(|l| l.name.as_str()).collect();
+        println!("  Lane Counting: {} ({}) every {}s", names.join(", "), lane.classes.join(", "), lane.interval.as_secs());
+        if tracker_config.is_none() {
+            println!("  Note: No TRACKER_CONFIG set - lane counting needs track IDs");
+        }
+    }
+    if source_config.ingest.drone {
+        println!(
+            "  Ingest: drone ({} ms jitter buffer, inputs scaled to {}x{} before batching)",
+            source_config.ingest.jitter_ms, output_width, output_height
+        );
+    }
+    println!("  Error Policy: {}", recovery_config.describe());
+    if let Some(config) = &gps_config {
+        println!("  GPS: {} (fixes older than {:.0}s are not used)", config.describe(), config.max_age.as_secs_f64());
+    }
+    if let Some(analytics) = &analytics_config {
+        println!(
+            "  Analytics Export: {:?} to {} every {}s, {} files",
+            analytics.format,
+            analytics.dir,
+            analytics.interval_secs,
+            if analytics.hourly { "hourly" } else { "daily" }
+        );
+    }
+    if let Some(map) = &map_config {
+        println!(
+            "  Map View: {} and {} every {:.1}s ({} camera{} calibrated)",
+            map.image_path(),
+            map.positions_path(),
+            map.interval.as_secs_f64(),
+            map.calibrations.len(),
+            if map.calibrations.len() == 1 { "" } else { "s" }
+        );
+    }
+    if let Some(parking) = &parking_config {
+        println!("  Parking Occupancy: {} spots ({})", parking.spots.len(), parking.classes.join(", "));
+        if let Some(dir) = &parking.overview_dir {
+            println!("  Parking Overview: {}/parking_src0.png", dir);
+        }
+    }
+    if let Some(queue) = &queue_config {
+        let names: Vec<&str> = queue.queues.iter().map(|q| q.name.as_str()).collect();
+        println!("  Queue Analytics: {}", names.join(", "));
+        if tracker_config.is_none() {
+            println!("  Note: No TRACKER_CONFIG set - queue wait times need track IDs");
+        }
+    }
+    if let Some(fall) = &fall_config {
+        println!("  Fall Detection: down for {}s after a drop of {}x body height", fall.confirm_secs, fall.drop);
+        if tracker_config.is_none() {
+            println!("  Note: No TRACKER_CONFIG set - fall detection needs track IDs");
+        }
+    }
+    if let Some(thermal) = &thermal_config {
+        match thermal.alert_temp {
+            Some(temp) => println!("  Thermal Input: {:?} palette, hot spot above {}°", thermal.palette, temp),
+            None => println!("  Thermal Input: {:?} palette", thermal.palette),
+        }
+        if !devices.iter().any(|d| d.starts_with("/dev/video")) {
+            println!("  Note: THERMAL only applies to /dev/video* inputs");
+        }
+    }
+    if let Some(diagnostics) = &diagnostics {
+        println!("  Diagnostics: bundle written to {} on errors", diagnostics.dir());
+    }
+    if let Some(replay) = &replay_config {
+        match &replay.dump_dir {
+            Some(dir) => println!("  Replay: starts paused, step from the controls; metadata dumped to {}", dir),
+            None => println!("  Replay: starts paused, step from the controls"),
+        }
+    }
+    // Replay's implicit lockstep batching isn't worth a line of its own
+    if let Some(playback) = &playback_config {
+        if replay_config.is_none() || playback.skips.iter().any(|s| *s > 0.0) {
+            let skips: Vec<String> = playback.skips.iter().map(|s| format!("{:.2}s", s)).collect();
+            println!("  Synchronized Playback: skipping {} at the start", skips.join(", "));
+            if devices.iter().any(|d| d.contains("://")) {
+                println!("  Note: Synchronized playback is meant for local files - live inputs can't be aligned");
+            }
+        }
+    }
+    if let Some(clip) = &clip_config {
+        println!("  Event Clips: {} ({}s before, {}s after)", clip.dir, clip.pre_secs, clip.post_secs);
+        if !devices.iter().any(|d| d.starts_with("rtsp://")) {
+            println!("  Note: Event clips are only recorded from rtsp:// inputs");
+        }
+    }
+    if let Some(trajectory) = &trajectory_config {
+        println!("  Trajectories: last {} frames", trajectory.length);
+        if tracker_config.is_none() {
+            println!("  Note: No TRACKER_CONFIG set - trajectories need track IDs and won't be drawn");
+        }
+    }
+    if let Some(heatmap) = &heatmap_config {
+        println!("  Heatmap: {} (every {}s)", heatmap.path(0), heatmap.interval_secs);
+    }
+    if let Some(tamper) = &tamper_config {
+        println!(
+            "  Tamper Detection: alerts after {:.0}s (learning for {:.0}s)",
+            tamper.duration.as_secs_f32(),
+            tamper.learn.as_secs_f32()
+        );
+    }
+    if let Some(eviction) = &eviction_config {
+        println!("  Stalled Inputs: out of the batch after {:?} without frames", eviction.timeout);
+    }
+    if let Some(scheduler) = &scheduler_config {
+        println!(
+            "  Source Priority: {} (GPU budget {:.0}%, down to 1/{} frames)",
+            scheduler.describe(),
+            scheduler.budget,
+            scheduler.max_interval
+        );
+    }
+    if let Some(health) = &health_config {
+        println!(
+            "  Stream Health: frozen/black after {:.0}s, bitrate below {:.0}% of normal",
+            health.window.as_secs_f64(),
+            health.bitrate_drop * 100.0
+        );
+    }
+    #[cfg(feature = "cuda")]
+    if cuda_stage.is_some() {
+        println!("  CUDA Stage: {}", env::var("CUDA_STAGE").unwrap_or_default());
+    }
+    if let (Some(config), Some(state)) = (&state_config, &state) {
+        let restored = if state.lock().unwrap().get("frames").is_some() { "restored" } else { "new" };
+        println!("  State: {} ({}, saved every {}s)", config.path, restored, config.interval_secs);
+    }
+    if let Some(dataset) = &dataset_config {
+        println!(
+            "  Dataset: {:?} in {} (every {} frames)",
+            dataset.format, dataset.dir, dataset.interval
+        );
+    }
+    if let Some(snapshot) = &snapshot_config {
+        let port = env::var("CONTROL_PORT").unwrap_or_else(|_| "<CONTROL_PORT>".to_string());
+        println!("  Snapshots: http://{}/snapshot.jpg (every {:?})", control::http_address(&port), snapshot.interval);
+    }
+    if let Some(rec) = &record_config {
+        if rec.mode.records_clean() {
+            println!("  Recording (clean): {}", rec.clean_path());
+        }
+        if rec.mode.records_annotated() {
+            println!("  Recording (annotated): {}", rec.annotated_path());
+        }
+    }
+
+    if let Some(bayer) = &source_config.bayer {
+        let demosaic = if cfg!(feature = "cuda") { "CUDA kernel" } else { "bayer2rgb on the CPU" };
+        println!("  Bayer: {} ({})", bayer.pattern, demosaic);
+    }
+    let mut pre_osd_branches = Vec::new();
+    let mut post_osd_branches = Vec::new();
+    if let Some(rec) = &record_config {
+        if rec.mode.records_clean() {
+            pre_osd_branches.push(rec.file_branch("clean"));
+        }
+        if rec.mode.records_annotated() {
+            post_osd_branches.push(rec.file_branch("annotated"));
+        }
+    }
+    for (index, sink) in sinks.iter().enumerate() {
+        let branches = if sink.osd() { &mut post_osd_branches } else { &mut pre_osd_branches };
+        branches.push(sink.branch(index));
+    }
+    if let Some(dataset) = &dataset_config {
+        pre_osd_branches.push(dataset.image_branch());
+    }
+    if let Some(snapshot) = &snapshot_config {
+        post_osd_branches.extend(snapshot.branches());
+    }
+    let mut stages = Vec::new();
+    stages.extend(reid_config.as_ref().map(|r| r.stage()));
+    stages.extend(face_config.as_ref().map(|f| f.stage()));
+    stages.extend(anpr_config.as_ref().map(|a| a.stage()));
+    stages.extend(ppe_config.as_ref().map(|p| p.stage()));
+    stages.extend(crowd_config.as_ref().map(|c| c.stage()));
+    stages.extend(motion_config.as_ref().map(|m| m.stage()));
+    stages.extend(parking_config.as_ref().map(|p| p.stage()));
+    if frame_hook.is_some() {
+        stages.push(processing::stage());
+    }
+    // After the frame hook, so heatmap backgrounds show e.g. redacted frames
+    stages.extend(heatmap_config.as_ref().map(|h| h.stage()));
+    stages.extend(health_config.as_ref().map(|h| h.stage()));
+    stages.extend(tamper_config.as_ref().map(|t| t.stage()));
+    #[cfg(feature = "cuda")]
+    if cuda_stage.is_some() {
+        stages.push(cuda::CudaStage::stage());
+    }
+    let spec = PipelineSpec {
+        sources: devices
+            .iter()
+            .enumerate()
+            .map(|(index, input)| match decodes[index] {
+                Decode::Software => launch::software_source_element(input, &source_config),
+                _ => launch::source_element(input, index, &source_config, clip_config.as_ref(), thermal_config.as_ref()),
+            })
+            .collect(),
+        width: output_width.clone(),
+        height: output_height.clone(),
+        model_config: final_config.clone(),
+        source_models: source_models.as_ref().map(|m| m.stages()).unwrap_or_default(),
+        compare: compare_config.as_ref().map(|c| c.stage()),
+        shadow: shadow_config.as_ref().map(|c| c.stage()),
+        tracker: tracker_config.as_ref().map(|config| (tracker_lib.clone(), config.clone())),
+        lockstep: playback_config.as_ref().is_some_and(|p| p.sync),
+        batch_timeout: env::var("MUX_BATCH_TIMEOUT").ok().and_then(|v| v.parse().ok()),
+        fixed_source_size: source_config.ingest.drone,
+        pre_infer: CustomStage::at(&custom_stage, StagePosition::PreInfer),
+        post_infer: CustomStage::at(&custom_stage, StagePosition::PostInfer),
+        post_osd: CustomStage::at(&custom_stage, StagePosition::PostOsd),
+        stages,
+        queues: stage_queues,
+        pools: pool_config,
+        pre_osd_branches,
+        post_osd_branches,
     };
 
-    // Build the DeepStream pipeline with nvinfer for object detection
-    // Pipeline stays in GPU memory (NVMM) throughout: nvstreammux → nvinfer → nvdsosd
-    let source_pipeline = if device.starts_with("rtsp://") || device.starts_with("http://") {
-        // Network stream with object detection
-        format!(
-            "nvurisrcbin uri={} ! \
-             nvvideoconvert interpolation-method=5 ! \
-             m.sink_0 nvstreammux name=m width={} height={} batch-size=1 ! \
-             nvinfer config-file-path={} ! \
-             nvdsosd",
-            device, output_width, output_height, final_config
-        )
-    } else if device.ends_with(".mp4") || device.ends_with(".avi") || device.ends_with(".mkv") {
-        // Video file with object detection
-        format!(
-            "nvurisrcbin uri=file://{} ! \
-             nvvideoconvert interpolation-method=5 ! \
-             m.sink_0 nvstreammux name=m width={} height={} batch-size=1 ! \
-             nvinfer config-file-path={} ! \
-             nvdsosd",
-            device, output_width, output_height, final_config
-        )
-    } else if Path::new(&device).exists() && device.starts_with("/dev/video") {
-        // Local camera with object detection
-        format!(
-            "v4l2src device={} ! \
-             nvvideoconvert interpolation-method=5 ! \
-             m.sink_0 nvstreammux name=m width={} height={} batch-size=1 ! \
-             nvinfer config-file-path={} ! \
-             nvdsosd",
-            device, output_width, output_height, final_config
-        )
+    // Live inputs are "paused" by dropping frames at the pause valve rather than pausing the source
+    let live_source = devices
+        .iter()
+        .any(|d| source::is_stream(d) || d.starts_with("/dev/video") || d.starts_with("aravis://") || d.starts_with("ndi://"));
+    let graph = launch::graph(&spec);
+    if let Err(errors) = graph.validate() {
+        eprintln!("Error: Invalid pipeline:");
+        for error in errors {
+            eprintln!("  - {}", error);
+        }
+        std::process::exit(1);
+    }
+    // Builds without DeepStream (or SOFTWARE_PIPELINE=true) run the same layout from stock
+    // elements, without detections
+    let graph = if cfg!(not(feature = "deepstream")) || env::var("SOFTWARE_PIPELINE").map(|v| v == "true").unwrap_or(false) {
+        println!("  Dev mode: software elements instead of DeepStream (no inference)");
+        graph.software()
     } else {
-        // Default to test pattern
-        format!(
-            "videotestsrc ! \
-             nvvideoconvert interpolation-method=5 ! \
-             m.sink_0 nvstreammux name=m width={} height={} batch-size=1 ! \
-             nvinfer config-file-path={} ! \
-             nvdsosd",
-            output_width, output_height, final_config
-        )
+        graph
     };
-    
-    let pipeline_str = format!("{} ! {}", source_pipeline, output_sink);
+    let pipeline_str = graph.render();
 
-    println!("  Pipeline: {}", pipeline_str);
-    println!("  Output: {}", output_sink);
+    println!("  Pipeline: {}", redact_credentials(&pipeline_str));
     println!("\nNote: This uses DeepStream's nvinfer element for GPU-accelerated inference");
     println!("      nvdsosd draws bounding boxes and labels on detected objects");
     println!("      You can customize the model by setting MODEL_CONFIG environment variable");
     
-    // Handle RTSP server if RTSP output is enabled
-    if rtsp_output.is_some() {
-        println!("      RTSP stream available at rtsp://localhost:{}/ds-detect", rtsp_port);
-        println!("      View with: ffplay rtsp://localhost:{}/ds-detect", rtsp_port);
-        println!("\nStarting RTSP server...");
-        
-        // Create RTSP server with the detection pipeline
-        // Note: Do NOT wrap in ( ) for RTSP server - it expects a raw pipeline string
-        let server = setup_rtsp_server(&pipeline_str, &rtsp_port, "/ds-detect");
-        
-        // Attach the server to the default main context
-        // This actually starts the server listening on the port  
-        let _server_id = server.attach(None);
-        
-        println!("RTSP server started on port {}", rtsp_port);
-        println!("Server bound to 0.0.0.0:{}", rtsp_port);
-        println!("Waiting for RTSP clients to connect...");
-        println!("Press Ctrl+C to stop the server");
-        
-        // Create a main loop to keep the server running
-        let main_loop = glib::MainLoop::new(None, false);
-        main_loop.run();
-        
-        return; // Exit here - RTSP server handles everything
+    let gps: Option<Gps> = gps_config.map(gps::spawn);
+    if let Some(gps) = &gps {
+        events.lock().unwrap().set_gps(gps.clone());
     }
 
-    // Parse and create the pipeline (only if not using RTSP server)
-    let pipeline = gstreamer::parse_launch(&pipeline_str)
-        .expect("Failed to create pipeline");
+    // Detection consumers are shared between the pad probe and main for the final flush
+    let clips: Option<Clips> = clip_config.map(|config| Arc::new(ClipRecorder::new(config)));
+    let mut frame_observers: Vec<Box<dyn FrameObserver>> = Vec::new();
+    if let Some(dir) = replay_config.as_ref().and_then(|r| r.dump_dir.as_ref()) {
+        match MetadataDump::new(dir, labels.clone()) {
+            Ok(dump) => frame_observers.push(Box::new(dump)),
+            Err(e) => eprintln!("Warning: Failed to set up the replay metadata dump in {}: {}", dir, e),
+        }
+    }
+    if let Some(config) = &export_config {
+        match Exporter::new(config, labels.clone()) {
+            Ok(exporter) => frame_observers.push(Box::new(exporter)),
+            Err(e) => eprintln!("Warning: Failed to set up detection export in {}: {}", config.dir, e),
+        }
+    }
+    if let Some(config) = &eval_config {
+        match Evaluator::new(config, labels.clone()) {
+            Ok(evaluator) => frame_observers.push(Box::new(evaluator)),
+            Err(e) => eprintln!("Warning: Evaluation disabled, could not load ground truth: {}", e),
+        }
+    }
+    // Observed only for the final report at shutdown
+    let comparison = compare_config.map(|config| Comparison::new(config, labels.clone()));
+    if let Some(comparison) = &comparison {
+        frame_observers.push(Box::new(comparison.clone()));
+    }
+    let shadow = shadow_config.map(|config| Shadow::new(config, labels.clone()));
+    if let Some(shadow) = &shadow {
+        frame_observers.push(Box::new(shadow.clone()));
+    }
+    if let Some(config) = speed_config {
+        frame_observers.push(Box::new(SpeedEstimator::new(config, labels.clone(), events.clone())));
+    }
+    let metrics: Metrics = Arc::new(Mutex::new(MetricsRegistry::default()));
+    if let Some(config) = lane_config {
+        let output = config.output.clone();
+        match LaneCounter::new(config, labels.clone(), metrics.clone()) {
+            Ok(counter) => frame_observers.push(Box::new(counter)),
+            Err(e) => eprintln!("Warning: Could not open LANE_COUNTS_FILE {}: {}", output.unwrap_or_default(), e),
+        }
+    }
+    if let Some(config) = analytics_config {
+        frame_observers.push(Box::new(AnalyticsExporter::new(config, labels.clone(), &zones)));
+    }
+    if let Some(config) = map_config {
+        let dir = config.dir.clone();
+        match MapView::new(config, labels.clone()) {
+            Ok(view) => frame_observers.push(Box::new(view)),
+            Err(e) => eprintln!("Warning: Failed to set up the map view in {}: {}", dir, e),
+        }
+    }
+    if let Some(config) = queue_config {
+        frame_observers.push(Box::new(QueueMonitor::new(config, &labels, events.clone(), metrics.clone())));
+    }
+    // Ready (GET /ready, sd_notify) once the first inference is through
+    let readiness = Readiness::default();
+    metrics::serve_from_env(metrics.clone(), readiness.clone());
+    if let Some(config) = fall_config {
+        frame_observers.push(Box::new(FallDetector::new(config, &labels, events.clone(), clips.clone())));
+    }
+    if let Some(config) = left_behind_config {
+        frame_observers.push(Box::new(LeftBehindDetector::new(config, labels.clone(), &zones, events.clone())));
+    }
+    // Read before the recorder starts raising it
+    let track_id_offset = state.as_ref().map(|s| s.lock().unwrap().get_u64("tracker.next_id")).unwrap_or(0);
+    if let (Some(config), Some(state)) = (&state_config, &state) {
+        frame_observers.push(Box::new(StateRecorder::new(state.clone(), &config.path, labels.clone())));
+
+        let save_state = state.clone();
+        let save_path = config.path.clone();
+        glib::timeout_add_seconds(config.interval_secs, move || {
+            if let Err(e) = save_state.lock().unwrap().save(&save_path) {
+                eprintln!("Warning: Failed to save state to {}: {}", save_path, e);
+            }
+            glib::Continue(true)
+        });
+    }
+    let has_observers = !frame_observers.is_empty();
+    let observers: Observers = Arc::new(Mutex::new(frame_observers));
+    let dataset_writer = dataset_config.and_then(|config| {
+        let dir = config.dir.clone();
+        match DatasetWriter::new(config, labels.clone()) {
+            Ok(writer) => Some(Arc::new(Mutex::new(writer))),
+            Err(e) => {
+                eprintln!("Warning: Failed to set up dataset output in {}: {}", dir, e);
+                None
+            }
+        }
+    });
+    let heatmaps: Option<Heatmaps> = heatmap_config.and_then(|config| {
+        let dir = config.dir.clone();
+        let interval_secs = config.interval_secs;
+        match HeatmapExporter::new(config) {
+            Ok(exporter) => {
+                let heatmaps = Arc::new(Mutex::new(exporter));
+                let export = heatmaps.clone();
+                glib::timeout_add_seconds(interval_secs, move || {
+                    export.lock().unwrap().export();
+                    glib::Continue(true)
+                });
+                Some(heatmaps)
+            }
+            Err(e) => {
+                eprintln!("Warning: Failed to set up heatmap export in {}: {}", dir, e);
+                None
+            }
+        }
+    });
+    let health: Option<Health> = health_config.map(|config| {
+        let health = Arc::new(Mutex::new(HealthMonitor::new(config, devices.len(), events.clone(), metrics.clone())));
+        let check = health.clone();
+        glib::timeout_add_seconds(1, move || {
+            check.lock().unwrap().check();
+            glib::Continue(true)
+        });
+        health
+    });
+    let mux_width: u32 = output_width.parse().unwrap_or(1920);
+    let mux_height: u32 = output_height.parse().unwrap_or(1080);
+
+    // Parse and create the pipeline
+    // PIPELINE_BUILDER=programmatic creates and links the elements one by one instead
+    let built = if env::var("PIPELINE_BUILDER").map(|v| v == "programmatic").unwrap_or(false) {
+        graph.build().map(|p| p.upcast::<gstreamer::Element>()).map_err(|e| glib::Error::new(gstreamer::ParseError::Link, &e))
+    } else {
+        gstreamer::parse_launch(&pipeline_str)
+    };
+    let pipeline = match built {
+        Ok(pipeline) => pipeline,
+        Err(e) => {
+            eprintln!("Error: Failed to create pipeline: {}", e);
+            if let Some(explanation) = negotiation::explain_link_error(&pipeline_str, e.message()) {
+                eprintln!("Caps negotiation:\n{}", explanation);
+            }
+            std::process::exit(1);
+        }
+    };
+
+    readiness.attach(&pipeline, cached_engine.as_ref().map_or(&model_config, |cached| &cached.config), metrics.clone());
+    if let (Some(cache), Some(cached)) = (engine_cache, cached_engine.filter(|cached| !cached.hit)) {
+        // nvinfer has written the engine it built once the first inference is through
+        let readiness = readiness.clone();
+        std::thread::spawn(move || {
+            while !readiness.is_ready() {
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+            cache.store(&cached);
+        });
+    }
+    if let Some(rec) = &record_config {
+        recording::use_utc_clock(&pipeline);
+        recording::attach_index(&pipeline, rec, labels.clone(), mux_width, mux_height, gps.clone());
+    }
+    if let Some(clips) = &clips {
+        clips.attach(&pipeline, devices.len());
+    }
+    class_filter.attach(&pipeline);
+    if let Some(map) = class_map {
+        classmap::attach(&pipeline, map);
+    }
+    if let Some(comparison) = &comparison {
+        comparison.attach(&pipeline);
+    }
+    if let Some(shadow) = &shadow {
+        shadow.attach(&pipeline);
+    }
+    if let Some(config) = source_models {
+        sourcemodels::attach(&pipeline, config);
+    }
+    if let Some(config) = size_filter {
+        sizefilter::attach(&pipeline, config);
+    }
+    if let Some(config) = smoothing_config {
+        smoothing::attach(&pipeline, config);
+    }
+    if let Some(config) = &playback_config {
+        playback::attach(&pipeline, config);
+    }
+    if let Some(config) = thermal_config {
+        thermal::attach(&pipeline, config, devices.len(), events.clone());
+    }
+    if has_observers {
+        attach_detection_probe(&pipeline, observers.clone(), mux_width, mux_height, track_id_offset);
+    }
+    if let Some(writer) = &dataset_writer {
+        attach_dataset_probe(&pipeline, writer.clone(), mux_width, mux_height);
+    }
+    let snapshots = Snapshots::default();
+    if let Some(config) = &snapshot_config {
+        snapshots.attach(&pipeline, config);
+    }
+    if let Some(processor) = frame_hook {
+        processing::attach(&pipeline, processor);
+    }
+    #[cfg(feature = "scripting")]
+    if let Some(script) = script {
+        script.attach(&pipeline, labels.clone(), events.clone(), clips.clone(), snapshots.clone());
+    }
+    if let Some(config) = trajectory_config {
+        trajectory::attach(&pipeline, config);
+    }
+    pools::watch(&pipeline, metrics.clone());
+    encoders::watch(&pipeline, metrics.clone());
+    let banner = Banner::default();
+    banner::attach(&pipeline, banner.clone());
+    if let Some(health) = &health {
+        health::attach(&pipeline, health.clone(), devices.len());
+    }
+    if let Some(config) = eviction_config {
+        eviction::attach(&pipeline, config, devices.len(), events.clone());
+    }
+    if let Some(config) = scheduler_config {
+        scheduler::attach(&pipeline, config, metrics.clone());
+    }
+    if let Some(config) = tamper_config {
+        tamper::attach(&pipeline, config, events.clone(), metrics.clone());
+    }
+    if let Some(heatmaps) = &heatmaps {
+        heatmap::attach(&pipeline, heatmaps.clone());
+    }
+    if let Some(config) = reid_config {
+        reid::attach(&pipeline, config, events.clone());
+    }
+    if let Some(config) = face_config {
+        face::attach(&pipeline, config, events.clone());
+    }
+    if let Some(config) = anpr_config {
+        anpr::attach(&pipeline, config, events.clone());
+    }
+    if let Some(config) = ppe_config {
+        ppe::attach(&pipeline, config, &labels, &zones, events.clone());
+    }
+    if let Some(config) = parking_config {
+        parking::attach(&pipeline, config, &labels, events.clone(), metrics.clone());
+    }
+    if let Some(config) = crowd_config {
+        crowd::attach(&pipeline, config, &zones, events.clone());
+    }
+    if let Some(config) = motion_config {
+        motion::attach(&pipeline, config, &zones, events.clone());
+    }
+    if let Some(config) = idle_config {
+        idle::attach(&pipeline, config, events.clone());
+    }
+    #[cfg(feature = "cuda")]
+    if let Some(stage) = cuda_stage {
+        stage.attach(&pipeline);
+    }
+    #[cfg(feature = "cuda")]
+    if let Some(bayer) = &source_config.bayer {
+        cuda::attach_debayer(&pipeline, devices.len(), bayer);
+    }
+    if source_config.ingest.drone {
+        source::watch_resolution(&pipeline, devices.len(), &output_width, &output_height);
+    }
+
+    // Ctrl+C sends EOS so muxers and exporters can finalize; a second Ctrl+C force-quits
+    #[cfg(unix)]
+    {
+        let interrupt_pipeline = pipeline.clone();
+        let mut eos_sent = false;
+        glib::unix_signal_add(SIGINT, move || {
+            if eos_sent {
+                std::process::exit(130);
+            }
+            println!("\nInterrupt received, sending EOS (press Ctrl+C again to force quit)...");
+            interrupt_pipeline.send_event(gstreamer::event::Eos::new());
+            eos_sent = true;
+            glib::Continue(true)
+        });
+    }
+
+    // RTSP servers re-serve the RTP the rtsp sink branches send to localhost; one
+    // server per distinct port, all dispatched from the main context below
+    let mut rtsp_ports: Vec<String> = rtsp_sinks.iter().map(|s| s.rtsp_port()).collect();
+    rtsp_ports.sort();
+    rtsp_ports.dedup();
+    let mut rtsp_servers = Vec::new();
+    let viewers = Viewers::default();
+    for port in &rtsp_ports {
+        let port_sinks: Vec<&SinkSpec> = rtsp_sinks.iter().copied().filter(|s| s.rtsp_port() == *port).collect();
+        let server = setup_rtsp_server(port, &port_sinks);
+        // New viewers get an IDR right away instead of waiting for the next keyframe
+        let keyframe_pipeline = pipeline.clone();
+        server.connect_client_connected(move |_server, client| {
+            let pipeline = keyframe_pipeline.clone();
+            client.connect_play_request(move |_client, _ctx| {
+                control::force_keyframe(&pipeline);
+            });
+        });
+        viewers.track(&server, port, &port_sinks, &pipeline);
+        access::enforce(&server, port, &port_sinks, &viewers);
+        server.attach(None).expect("Failed to attach RTSP server");
+        println!("RTSP server started on port {}", port);
+        for sink in &port_sinks {
+            println!("      View with: ffplay rtsp://localhost:{}{}", port, sink.rtsp_mount());
+            let transport = sink.rtsp_transport().expect("Invalid RTSP transport options");
+            if let Some((group, last)) = transport.multicast_range.filter(|_| transport.multicast) {
+                let groups = if group == last { group } else { format!("{}-{}", group, last) };
+                println!("      Multicast: {} (ffplay -rtsp_transport udp_multicast)", groups);
+            }
+            if let Some(abr) = abr::AdaptiveBitrate::new(&pipeline, sink) {
+                abr.start(&server);
+            }
+        }
+        rtsp_servers.push(server);
+    }
+    if !rtsp_servers.is_empty() {
+        viewers.start(&rtsp_servers, metrics.clone());
+    }
 
     // Get the pipeline bus for messages
     let bus = pipeline
         .bus()
         .expect("Pipeline should have a bus");
 
-    // Start playing
-    pipeline
-        .set_state(gstreamer::State::Playing)
-        .expect("Unable to set the pipeline to the Playing state");
+    // Start playing; replay prerolls the first frame and then waits to be stepped
+    if replay_config.is_some() {
+        pipeline
+            .set_state(gstreamer::State::Paused)
+            .expect("Unable to set the pipeline to the Paused state");
+        println!("Replay paused - send 'step [N]' or 'resume' via the keyboard or CONTROL_SOCKET");
+    } else {
+        pipeline
+            .set_state(gstreamer::State::Playing)
+            .expect("Unable to set the pipeline to the Playing state");
+    }
+
+    // Pause/resume/step/flush/classes from the keyboard, the CONTROL_SOCKET control API and CONTROL_PORT
+    let (control_tx, control_rx) = std::sync::mpsc::channel();
+    control::spawn_keyboard(control_tx.clone());
+    control::spawn_http(control_tx.clone(), snapshots);
+    control::spawn_socket(control_tx);
+    let mut controller = Controller::new(pipeline.clone(), live_source, replay_config.is_some(), class_filter, banner, viewers);
+    if let Some(state) = &state {
+        controller.restore(state.clone());
+    }
+
+    let mut bus_handler = BusHandler::new(recovery_config, &pipeline);
 
-    // Wait for error or EOS
-    for msg in bus.iter_timed(gstreamer::ClockTime::NONE) {
+    // Wait for error or EOS, dispatching the default main context so the
+    // signal handler above gets a chance to run
+    let main_context = glib::MainContext::default();
+    loop {
         use gstreamer::MessageView;
 
+        main_context.iteration(false);
+        bus_handler.rebuild_due();
+        while let Ok(request) = control_rx.try_recv() {
+            let response = controller.handle(request.command);
+            match request.reply {
+                Some(reply) => {
+                    let _ = reply.send(response);
+                }
+                None => println!("{}", response),
+            }
+        }
+        let msg = match bus.timed_pop(gstreamer::ClockTime::from_mseconds(100)) {
+            Some(msg) => msg,
+            None => continue,
+        };
+        if let Some(diagnostics) = &mut diagnostics {
+            diagnostics.record(&msg);
+        }
+
         match msg.view() {
             MessageView::Eos(..) => {
                 println!("End of stream");
                 break;
             }
+            MessageView::Warning(warning) => bus_handler.warning(warning),
             MessageView::Error(err) => {
-                eprintln!(
+                // Reported (deduplicated, credentials redacted) by the handler; its policy may
+                // restart the failed branch or ignore the error instead of stopping
+                if bus_handler.error(err) != Policy::Fail {
+                    continue;
+                }
+                let message = format!(
                     "Error from {:?}: {} ({:?})",
                     err.src().map(|s| s.path_string()),
                     err.error(),
                     err.debug()
                 );
+                if negotiation::is_not_negotiated(err) {
+                    if let Some(bin) = pipeline.downcast_ref::<gstreamer::Bin>() {
+                        eprintln!("Caps negotiation failed:\n{}", negotiation::explain_pipeline(bin));
+                    }
+                }
+                // Collected before shutdown, while element states and caps still show the failure
+                if let Some(diagnostics) = &diagnostics {
+                    match diagnostics.write_bundle(&pipeline, &message) {
+                        Ok(path) => eprintln!("Diagnostics bundle written to {}", path),
+                        Err(e) => eprintln!("Warning: Failed to write diagnostics bundle: {}", e),
+                    }
+                }
                 break;
             }
+            #[allow(clippy::collapsible_match)]
             MessageView::StateChanged(state_changed) => {
                 if state_changed
                     .src()
@@ -315,4 +1494,9 @@ fn main() {
     pipeline
         .set_state(gstreamer::State::Null)
         .expect("Unable to set the pipeline to the Null state");
+
+    finish_observers(&observers);
+    if let Some(heatmaps) = &heatmaps {
+        heatmaps.lock().unwrap().export();
+    }
 }
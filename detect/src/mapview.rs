@@ -0,0 +1,266 @@
+use crate::calibration::{self, Calibration};
+use crate::detections::{FrameDetections, FrameObserver};
+use crate::json;
+use crate::png;
+use gstreamer::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Canvas used when no MAP_IMAGE is given
+const BLANK_SIZE: (u32, u32) = (800, 600);
+/// Positions kept per track for its trail
+const TRAIL_POINTS: usize = 30;
+/// Objects of a source that stopped sending frames are dropped after this long
+const STALE_SECS: u64 = 2;
+const DOT_RADIUS: i64 = 6;
+/// One color per camera, so overlapping views can be told apart
+const SOURCE_COLORS: [[u8; 3]; 6] = [[0, 200, 0], [255, 150, 0], [0, 200, 255], [255, 0, 255], [255, 255, 0], [255, 50, 50]];
+
+pub struct MapConfig {
+    pub dir: String,
+    pub image: Option<String>,
+    /// Image-to-map homography per source; map coordinates are pixels of the map image
+    pub calibrations: HashMap<u32, Calibration>,
+    pub interval: Duration,
+}
+
+impl MapConfig {
+    /// Enabled by MAP_DIR; reads MAP_CALIBRATION (required), MAP_IMAGE and MAP_INTERVAL.
+    ///
+    /// MAP_CALIBRATION holds one CALIBRATION-style spec per camera separated by `|`, each
+    /// optionally prefixed with its GST_DEVICE position (`1:x,y=X,Y;...`), else numbered in order.
+    pub fn from_env() -> Option<MapConfig> {
+        let dir = env::var("MAP_DIR").ok().filter(|v| !v.trim().is_empty())?;
+        let Some(spec) = env::var("MAP_CALIBRATION").ok().filter(|v| !v.trim().is_empty()) else {
+            eprintln!("Error: MAP_DIR needs MAP_CALIBRATION (image-to-map point pairs per camera)");
+            std::process::exit(2);
+        };
+        let mut calibrations = HashMap::new();
+        for (i, entry) in spec.split('|').map(str::trim).filter(|e| !e.is_empty()).enumerate() {
+            let (source_id, pairs) = match entry.split_once(':') {
+                Some((source, pairs)) if source.trim().parse::<u32>().is_ok() => (source.trim().parse().unwrap_or(0), pairs),
+                _ => (i as u32, entry),
+            };
+            match calibration::parse_calibration(pairs) {
+                Ok(calibration) => calibrations.insert(source_id, calibration),
+                Err(e) => {
+                    eprintln!("Error: MAP_CALIBRATION source {}: {}", source_id, e);
+                    std::process::exit(2);
+                }
+            };
+        }
+        let image = env::var("MAP_IMAGE").ok().filter(|v| !v.trim().is_empty());
+        let interval = env::var("MAP_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| *v > 0.0)
+            .unwrap_or(1.0);
+        Some(MapConfig { dir, image, calibrations, interval: Duration::from_secs_f64(interval) })
+    }
+
+    pub fn image_path(&self) -> String {
+        format!("{}/map.png", self.dir.trim_end_matches('/'))
+    }
+
+    pub fn positions_path(&self) -> String {
+        format!("{}/map.jsonl", self.dir.trim_end_matches('/'))
+    }
+}
+
+/// Decodes any image GStreamer can read into packed RGB.
+fn load_image(path: &str) -> Result<(u32, u32, Vec<u8>), String> {
+    let pipeline = gstreamer::parse_launch(&format!(
+        "filesrc location=\"{}\" ! decodebin ! videoconvert ! video/x-raw,format=RGB ! fakesink name=sink enable-last-sample=true",
+        path
+    ))
+    .map_err(|e| e.to_string())?;
+    pipeline.set_state(gstreamer::State::Paused).map_err(|e| e.to_string())?;
+    let (result, _, _) = pipeline.state(gstreamer::ClockTime::from_seconds(10));
+    let sample = result.ok().and_then(|_| {
+        let sink = pipeline.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("sink"))?;
+        sink.property::<Option<gstreamer::Sample>>("last-sample")
+    });
+    pipeline
+        .set_state(gstreamer::State::Null)
+        .expect("Unable to set the map image pipeline to the Null state");
+    let sample = sample.ok_or("could not decode it")?;
+    let structure = sample.caps().and_then(|c| c.structure(0).map(|s| s.to_owned())).ok_or("no caps")?;
+    let width = structure.get::<i32>("width").map_err(|e| e.to_string())? as usize;
+    let height = structure.get::<i32>("height").map_err(|e| e.to_string())? as usize;
+    let buffer = sample.buffer().ok_or("no buffer")?;
+    let map = buffer.map_readable().map_err(|e| e.to_string())?;
+    // Raw video rows are padded to 4 bytes
+    let stride = (width * 3).div_ceil(4) * 4;
+    if map.len() < stride * height {
+        return Err("unexpected frame size".to_string());
+    }
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for row in map.chunks(stride).take(height) {
+        rgb.extend_from_slice(&row[..width * 3]);
+    }
+    Ok((width as u32, height as u32, rgb))
+}
+
+/// One object's position on the map.
+struct Placed {
+    track_id: Option<u64>,
+    label: String,
+    x: f64,
+    y: f64,
+}
+
+struct SourceView {
+    objects: Vec<Placed>,
+    seen: Instant,
+}
+
+/// Projects the ground point (bottom center of each box) of every camera through its
+/// MAP_CALIBRATION homography onto one floorplan and, every MAP_INTERVAL, writes the plan
+/// with a dot per object and a trail per track (`map.png`) plus the positions as one JSON
+/// line (`map.jsonl`). The same object seen by two cameras shows twice; there is no fusion.
+pub struct MapView {
+    config: MapConfig,
+    labels: Vec<String>,
+    width: u32,
+    height: u32,
+    background: Vec<u8>,
+    sources: HashMap<u32, SourceView>,
+    trails: HashMap<(u32, u64), VecDeque<(f64, f64)>>,
+    positions: Option<File>,
+    last_render: Option<Instant>,
+}
+
+impl MapView {
+    pub fn new(config: MapConfig, labels: Vec<String>) -> std::io::Result<MapView> {
+        fs::create_dir_all(&config.dir)?;
+        let positions = OpenOptions::new().create(true).append(true).open(config.positions_path())?;
+        let (width, height, background) = match &config.image {
+            Some(path) => match load_image(path) {
+                Ok(image) => image,
+                Err(e) => {
+                    eprintln!("Warning: Could not load MAP_IMAGE {}: {}; drawing on a blank map", path, e);
+                    blank()
+                }
+            },
+            None => blank(),
+        };
+        Ok(MapView {
+            config,
+            labels,
+            width,
+            height,
+            background,
+            sources: HashMap::new(),
+            trails: HashMap::new(),
+            positions: Some(positions),
+            last_render: None,
+        })
+    }
+
+    fn render(&mut self) {
+        self.sources.retain(|_, view| view.seen.elapsed() < Duration::from_secs(STALE_SECS));
+        let (w, h) = (self.width, self.height);
+        let mut rgb = self.background.clone();
+        for ((source_id, _), trail) in &self.trails {
+            let color = dim(SOURCE_COLORS[*source_id as usize % SOURCE_COLORS.len()]);
+            for &(x, y) in trail {
+                dot(&mut rgb, (w, h), (x, y), 2, color);
+            }
+        }
+        let mut objects = Vec::new();
+        let mut ids: Vec<&u32> = self.sources.keys().collect();
+        ids.sort();
+        for source_id in ids {
+            let color = SOURCE_COLORS[*source_id as usize % SOURCE_COLORS.len()];
+            for placed in &self.sources[source_id].objects {
+                dot(&mut rgb, (w, h), (placed.x, placed.y), DOT_RADIUS, color);
+                objects.push(format!(
+                    "{{\"source_id\":{},\"track_id\":{},\"label\":{},\"x\":{},\"y\":{}}}",
+                    source_id,
+                    placed.track_id.map_or("null".to_string(), |id| id.to_string()),
+                    json::string(&placed.label),
+                    json::number(placed.x as f32),
+                    json::number(placed.y as f32)
+                ));
+            }
+        }
+
+        // Written aside and renamed so dashboards never load a half-written image
+        let path = self.config.image_path();
+        let tmp = format!("{}.tmp", path);
+        if let Err(e) = png::write_rgb(&tmp, w, h, &rgb).and_then(|_| fs::rename(&tmp, &path)) {
+            eprintln!("Warning: Failed to write map view {}: {}", path, e);
+        }
+        let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        let line = format!("{{\"timestamp_ms\":{},\"objects\":[{}]}}", timestamp_ms, objects.join(","));
+        if let Some(file) = &mut self.positions {
+            if let Err(e) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+                eprintln!("Warning: Failed to write map positions, stopping: {}", e);
+                self.positions = None;
+            }
+        }
+    }
+}
+
+fn blank() -> (u32, u32, Vec<u8>) {
+    let (w, h) = BLANK_SIZE;
+    (w, h, vec![40; (w * h * 3) as usize])
+}
+
+fn dim(color: [u8; 3]) -> [u8; 3] {
+    color.map(|c| c / 2)
+}
+
+/// Fills a circle into a packed RGB image; points off the image are skipped.
+fn dot(rgb: &mut [u8], (w, h): (u32, u32), (x, y): (f64, f64), radius: i64, color: [u8; 3]) {
+    if !x.is_finite() || !y.is_finite() {
+        return;
+    }
+    let (cx, cy) = (x.round() as i64, y.round() as i64);
+    for py in cy - radius..=cy + radius {
+        for px in cx - radius..=cx + radius {
+            let inside = (px - cx).pow(2) + (py - cy).pow(2) <= radius * radius;
+            if inside && px >= 0 && py >= 0 && (px as u32) < w && (py as u32) < h {
+                let i = (py as usize * w as usize + px as usize) * 3;
+                rgb[i..i + 3].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+impl FrameObserver for MapView {
+    fn on_frame(&mut self, frame: &FrameDetections) {
+        let Some(calibration) = self.config.calibrations.get(&frame.source_id) else { return };
+        let (width, height) = (frame.pipeline_width.max(1) as f64, frame.pipeline_height.max(1) as f64);
+        let mut objects = Vec::new();
+        for det in &frame.objects {
+            let foot = ((det.left + det.width / 2.0) as f64 / width, (det.top + det.height) as f64 / height);
+            let Some((x, y)) = calibration.to_ground(foot.0, foot.1) else { continue };
+            let label = self.labels.get(det.class_id as usize).cloned().unwrap_or_else(|| det.class_id.to_string());
+            if let Some(track_id) = det.track_id {
+                let trail = self.trails.entry((frame.source_id, track_id)).or_default();
+                trail.push_back((x, y));
+                if trail.len() > TRAIL_POINTS {
+                    trail.pop_front();
+                }
+            }
+            objects.push(Placed { track_id: det.track_id, label, x, y });
+        }
+        // Trails end with their track
+        self.trails
+            .retain(|(source_id, track_id), _| *source_id != frame.source_id || objects.iter().any(|o| o.track_id == Some(*track_id)));
+        self.sources.insert(frame.source_id, SourceView { objects, seen: Instant::now() });
+
+        if self.last_render.is_none_or(|at| at.elapsed() >= self.config.interval) {
+            self.last_render = Some(Instant::now());
+            self.render();
+        }
+    }
+
+    fn finish(&mut self) {
+        self.render();
+    }
+}
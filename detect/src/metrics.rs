@@ -0,0 +1,125 @@
+use crate::warmup::Readiness;
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// One metric and its samples, one per label set.
+struct Family {
+    name: String,
+    kind: &'static str,
+    help: String,
+    // Rendered label set ("" or {k="v",...}) -> value
+    samples: Vec<(String, f64)>,
+}
+
+/// Counters and gauges the analytics update, served in the Prometheus text format.
+#[derive(Default)]
+pub struct MetricsRegistry {
+    families: Vec<Family>,
+}
+
+pub type Metrics = Arc<Mutex<MetricsRegistry>>;
+
+fn label_set(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+impl MetricsRegistry {
+    /// Registers a metric; `kind` is "counter" or "gauge". Repeated calls are ignored.
+    pub fn describe(&mut self, name: &str, kind: &'static str, help: &str) {
+        if self.families.iter().all(|f| f.name != name) {
+            self.families.push(Family { name: name.to_string(), kind, help: help.to_string(), samples: Vec::new() });
+        }
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)]) -> &mut f64 {
+        let index = match self.families.iter().position(|f| f.name == name) {
+            Some(index) => index,
+            None => {
+                self.describe(name, "gauge", "");
+                self.families.len() - 1
+            }
+        };
+        let samples = &mut self.families[index].samples;
+        let labels = label_set(labels);
+        let position = match samples.iter().position(|(l, _)| *l == labels) {
+            Some(position) => position,
+            None => {
+                samples.push((labels, 0.0));
+                samples.len() - 1
+            }
+        };
+        &mut samples[position].1
+    }
+
+    pub fn add(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        *self.sample(name, labels) += value;
+    }
+
+    pub fn set(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        *self.sample(name, labels) = value;
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for family in &self.families {
+            if !family.help.is_empty() {
+                out.push_str(&format!("# HELP {} {}\n", family.name, family.help));
+            }
+            out.push_str(&format!("# TYPE {} {}\n", family.name, family.kind));
+            for (labels, value) in &family.samples {
+                out.push_str(&format!("{}{} {}\n", family.name, labels, value));
+            }
+        }
+        out
+    }
+}
+
+/// Reads METRICS_PORT; serves GET /metrics and GET /ready (200 once `readiness` is set, 503
+/// before) on it from a background thread.
+pub fn serve_from_env(metrics: Metrics, readiness: Readiness) {
+    let Ok(port) = env::var("METRICS_PORT") else { return };
+    let listener = match TcpListener::bind(format!("0.0.0.0:{}", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Warning: Could not open metrics port {}: {}", port, e);
+            return;
+        }
+    };
+    println!("Metrics: http://0.0.0.0:{}/metrics", port);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut request = String::new();
+            if BufReader::new(&stream).read_line(&mut request).is_err() {
+                continue;
+            }
+            let path = request.split_whitespace().nth(1).unwrap_or("/");
+            let (status, body) = if path == "/metrics" {
+                ("200 OK", metrics.lock().unwrap().render())
+            } else if path == "/ready" && readiness.is_ready() {
+                ("200 OK", "ready\n".to_string())
+            } else if path == "/ready" {
+                ("503 Service Unavailable", "warming up\n".to_string())
+            } else {
+                ("404 Not Found", "not found\n".to_string())
+            };
+            let mut stream = stream;
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+        }
+    });
+}
@@ -0,0 +1,132 @@
+use crate::events::{Event, Events};
+use crate::zones::{GridCells, Zone};
+use gstreamer::prelude::*;
+use std::collections::HashMap;
+use std::env;
+
+/// Motion-in-zone detection from nvof optical flow. Works for anything that moves,
+/// including classes the detector doesn't know.
+pub struct MotionConfig {
+    /// Mean flow magnitude (pixels/frame) above which a zone counts as moving
+    pub threshold: f32,
+    /// Consecutive frames above/below the threshold before an event fires
+    pub min_frames: u32,
+}
+
+impl MotionConfig {
+    /// Reads MOTION_DETECT=true / MOTION_THRESHOLD / MOTION_MIN_FRAMES.
+    pub fn from_env() -> Option<MotionConfig> {
+        if env::var("MOTION_DETECT").map(|v| v != "true").unwrap_or(true) {
+            return None;
+        }
+        let threshold = env::var("MOTION_THRESHOLD").ok().and_then(|v| v.parse().ok()).unwrap_or(1.0);
+        let min_frames = env::var("MOTION_MIN_FRAMES").ok().and_then(|v| v.parse().ok()).unwrap_or(5);
+        Some(MotionConfig { threshold, min_frames })
+    }
+
+    /// nvof attaches a flow grid to every frame of the batch.
+    pub fn stage(&self) -> String {
+        "nvof name=nvof ! queue".to_string()
+    }
+}
+
+#[derive(Default)]
+struct ZoneState {
+    active: bool,
+    run: u32,
+    started_frame: i32,
+    peak: f32,
+}
+
+struct MotionAnalyzer {
+    config: MotionConfig,
+    zones: Vec<Zone>,
+    events: Events,
+    masks: Option<GridCells>,
+    states: HashMap<(u32, usize), ZoneState>,
+}
+
+impl MotionAnalyzer {
+    fn on_flow(&mut self, source_id: u32, frame_num: i32, rows: usize, cols: usize, vectors: &[nvds::NvOFFlowVector]) {
+        let magnitudes: Vec<f32> = GridCells::for_grid(&mut self.masks, &self.zones, rows, cols)
+            .iter()
+            .map(|cells| {
+                if cells.is_empty() {
+                    return 0.0;
+                }
+                let sum: f32 = cells
+                    .iter()
+                    .map(|&i| {
+                        let v = vectors[i];
+                        // S10.5 fixed point
+                        ((v.flowx as f32 / 32.0).powi(2) + (v.flowy as f32 / 32.0).powi(2)).sqrt()
+                    })
+                    .sum();
+                sum / cells.len() as f32
+            })
+            .collect();
+
+        for (index, magnitude) in magnitudes.into_iter().enumerate() {
+            let state = self.states.entry((source_id, index)).or_default();
+            let moving = magnitude >= self.config.threshold;
+            // Count frames that disagree with the current state; flip once enough have accumulated
+            state.run = if moving != state.active { state.run + 1 } else { 0 };
+            if state.active {
+                state.peak = state.peak.max(magnitude);
+            }
+            if state.run < self.config.min_frames {
+                continue;
+            }
+            state.run = 0;
+            state.active = moving;
+            let zone = &self.zones[index].name;
+            let event = if moving {
+                state.started_frame = frame_num;
+                state.peak = magnitude;
+                Event::new("motion_start", source_id, frame_num)
+                    .with("zone", zone.as_str())
+                    .with("magnitude", magnitude)
+            } else {
+                Event::new("motion_end", source_id, frame_num)
+                    .with("zone", zone.as_str())
+                    .with("peak_magnitude", state.peak)
+                    .with("duration_frames", (frame_num - state.started_frame) as i64)
+            };
+            self.events.lock().unwrap().emit(event);
+        }
+    }
+}
+
+/// Reads the flow meta after nvof and emits motion_start / motion_end per zone
+/// (the whole frame when no ZONES are set).
+pub fn attach(bin: &gstreamer::Element, config: MotionConfig, zones: &[Zone], events: Events) {
+    let nvof = match bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("nvof")) {
+        Some(nvof) => nvof,
+        None => {
+            eprintln!("Warning: nvof element not found, motion detection disabled");
+            return;
+        }
+    };
+    let src_pad = nvof.static_pad("src").expect("nvof has a src pad");
+    let zones = if zones.is_empty() { vec![Zone::full_frame()] } else { zones.to_vec() };
+    let analyzer = std::sync::Mutex::new(MotionAnalyzer {
+        config,
+        zones,
+        events,
+        masks: None,
+        states: HashMap::new(),
+    });
+
+    src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            let mut analyzer = analyzer.lock().unwrap();
+            for frame in batch.frames() {
+                let (source_id, frame_num) = (frame.source_id, frame.frame_num);
+                if let Some((rows, cols, vectors)) = frame.optical_flow() {
+                    analyzer.on_flow(source_id, frame_num, rows, cols, vectors);
+                }
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
@@ -0,0 +1,187 @@
+use gstreamer::prelude::*;
+
+/// Summary of one side of a link: which memory and formats it can do.
+struct CapsSummary {
+    media_types: Vec<String>,
+    nvmm: bool,
+    system_memory: bool,
+    formats: Vec<String>,
+    any: bool,
+}
+
+impl CapsSummary {
+    fn of(caps: &gstreamer::Caps) -> CapsSummary {
+        let mut summary = CapsSummary {
+            media_types: Vec::new(),
+            nvmm: false,
+            system_memory: false,
+            formats: Vec::new(),
+            any: caps.is_any(),
+        };
+        for (structure, features) in caps.iter_with_features() {
+            let name = structure.name().to_string();
+            if !summary.media_types.contains(&name) {
+                summary.media_types.push(name);
+            }
+            if features.contains("memory:NVMM") {
+                summary.nvmm = true;
+            } else {
+                summary.system_memory = true;
+            }
+            let formats = match structure.get::<String>("format") {
+                Ok(format) => vec![format],
+                Err(_) => structure
+                    .get::<gstreamer::List>("format")
+                    .map(|list| list.as_slice().iter().filter_map(|v| v.get::<String>().ok()).collect())
+                    .unwrap_or_default(),
+            };
+            for format in formats {
+                if !summary.formats.contains(&format) {
+                    summary.formats.push(format);
+                }
+            }
+        }
+        summary
+    }
+}
+
+/// Likely fixes for an upstream side producing `proposed` into a downstream side
+/// accepting `accepted`.
+fn suggest(proposed: &CapsSummary, accepted: &CapsSummary) -> Vec<String> {
+    let mut fixes = Vec::new();
+    if proposed.any || accepted.any {
+        return fixes;
+    }
+    let raw = |s: &CapsSummary| s.media_types.iter().any(|m| m == "video/x-raw");
+    if !proposed.media_types.iter().any(|m| accepted.media_types.contains(m)) {
+        if raw(proposed) && !raw(accepted) {
+            fixes.push(format!(
+                "downstream expects {} - an encoder (e.g. nvv4l2h264enc) is missing",
+                accepted.media_types.join(" / ")
+            ));
+        } else if !raw(proposed) && raw(accepted) {
+            fixes.push(format!(
+                "upstream produces {} - a parser/decoder (e.g. h264parse ! nvv4l2decoder) is missing",
+                proposed.media_types.join(" / ")
+            ));
+        } else {
+            fixes.push(format!(
+                "media types don't match ({} vs {})",
+                proposed.media_types.join(" / "),
+                accepted.media_types.join(" / ")
+            ));
+        }
+        return fixes;
+    }
+    if proposed.nvmm && !proposed.system_memory && !accepted.nvmm {
+        fixes.push("upstream outputs GPU memory (NVMM) but downstream needs system memory - insert nvvideoconvert before it".to_string());
+    } else if !proposed.nvmm && accepted.nvmm && !accepted.system_memory {
+        fixes.push(
+            "downstream needs GPU memory (NVMM) - insert nvvideoconvert ! video/x-raw(memory:NVMM) before it".to_string(),
+        );
+    }
+    if !proposed.formats.is_empty()
+        && !accepted.formats.is_empty()
+        && !proposed.formats.iter().any(|f| accepted.formats.contains(f))
+    {
+        fixes.push(format!(
+            "no common pixel format ({} vs {}) - convert with nvvideoconvert ! video/x-raw(memory:NVMM),format={}",
+            proposed.formats.join(","),
+            accepted.formats.join(","),
+            accepted.formats[0]
+        ));
+    }
+    if fixes.is_empty() {
+        fixes.push("a caps filter (size, framerate, format) between them is likely too strict".to_string());
+    }
+    fixes
+}
+
+/// True for bus errors caused by failed caps negotiation at runtime.
+pub fn is_not_negotiated(err: &gstreamer::message::Error) -> bool {
+    err.error().matches(gstreamer::StreamError::Format)
+        || err.debug().map(|d| d.contains("not-negotiated")).unwrap_or(false)
+}
+
+/// Walks a constructed pipeline and explains every link that failed to agree on caps:
+/// what the upstream pad proposes, what the downstream pad allows and likely fixes.
+pub fn explain_pipeline(bin: &gstreamer::Bin) -> String {
+    let mut out = String::new();
+    for element in bin.iterate_recurse().into_iter().flatten() {
+        for pad in element.src_pads() {
+            let Some(peer) = pad.peer() else { continue };
+            if pad.current_caps().is_some() {
+                continue;
+            }
+            let proposed = pad.query_caps(None);
+            let accepted = peer.query_caps(None);
+            // Links whose upstream hasn't received anything yet are just waiting, not broken
+            let upstream_negotiated = element.sink_pads().iter().all(|p| p.current_caps().is_some());
+            if proposed.can_intersect(&accepted) && !upstream_negotiated {
+                continue;
+            }
+            out.push_str(&format!("  {} -> {}\n", pad.path_string(), peer.path_string()));
+            out.push_str(&format!("    proposed: {}\n", proposed));
+            out.push_str(&format!("    allowed:  {}\n", accepted));
+            for fix in suggest(&CapsSummary::of(&proposed), &CapsSummary::of(&accepted)) {
+                out.push_str(&format!("    fix: {}\n", fix));
+            }
+        }
+    }
+    if out.is_empty() {
+        "  every link has negotiated caps; the failure happened inside an element\n".to_string()
+    } else {
+        out
+    }
+}
+
+/// Factory of an element in a parse_launch description: `name=` lookups first, then
+/// GStreamer's auto names (factory name plus an index, e.g. nvvideoconvert0).
+fn factory_for(pipeline: &str, element: &str) -> Option<gstreamer::ElementFactory> {
+    let named = pipeline
+        .split('!')
+        .find(|segment| segment.split_whitespace().any(|token| token == format!("name={}", element)))
+        .and_then(|segment| segment.split_whitespace().find(|token| !token.contains('=')));
+    if let Some(factory) = named.and_then(gstreamer::ElementFactory::find) {
+        return Some(factory);
+    }
+    gstreamer::ElementFactory::find(element.trim_end_matches(|c: char| c.is_ascii_digit()))
+}
+
+fn template_caps(factory: &gstreamer::ElementFactory, direction: gstreamer::PadDirection) -> gstreamer::Caps {
+    let mut caps = gstreamer::Caps::new_empty();
+    for template in factory.static_pad_templates().iter().filter(|t| t.direction() == direction) {
+        caps.merge(template.caps());
+    }
+    caps
+}
+
+/// Explains parse_launch's "could not link A to B" from the two factories' pad templates.
+pub fn explain_link_error(pipeline: &str, message: &str) -> Option<String> {
+    let rest = message.split("could not link ").nth(1)?;
+    let (upstream, downstream) = rest.split_once(" to ")?;
+    let downstream = downstream.split([',', ' ']).next().unwrap_or(downstream);
+    let upstream_factory = factory_for(pipeline, upstream)?;
+    let downstream_factory = factory_for(pipeline, downstream)?;
+    let proposed = template_caps(&upstream_factory, gstreamer::PadDirection::Src);
+    // Links through a caps filter quote the filter ("... can't handle caps video/x-raw,...")
+    let accepted = message
+        .split("handle caps ")
+        .nth(1)
+        .and_then(|caps| caps.parse::<gstreamer::Caps>().ok())
+        .unwrap_or_else(|| template_caps(&downstream_factory, gstreamer::PadDirection::Sink));
+
+    let mut out = format!(
+        "  {} ({}) -> {} ({})\n    output:   {}\n    accepts:  {}\n",
+        upstream,
+        upstream_factory.name(),
+        downstream,
+        downstream_factory.name(),
+        proposed,
+        accepted
+    );
+    for fix in suggest(&CapsSummary::of(&proposed), &CapsSummary::of(&accepted)) {
+        out.push_str(&format!("    fix: {}\n", fix));
+    }
+    Some(out)
+}
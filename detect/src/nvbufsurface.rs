@@ -0,0 +1,116 @@
+// Hand-rolled FFI for the NvBufSurface API, used to reach frame pixels from probes.
+// Layouts mirror nvbufsurface.h from DeepStream 6.x-8.0; like the nvds crate, field names
+// are snake_cased from the C header.
+#![allow(non_camel_case_types, dead_code)]
+
+use std::os::raw::{c_int, c_void};
+
+pub const NVBUF_MAX_PLANES: usize = 4;
+const STRUCTURE_PADDING: usize = 4;
+
+pub type NvBufSurfaceColorFormat = u32;
+pub const NVBUF_COLOR_FORMAT_NV12: NvBufSurfaceColorFormat = 6;
+pub const NVBUF_COLOR_FORMAT_RGBA: NvBufSurfaceColorFormat = 19;
+
+pub type NvBufSurfaceMemType = u32;
+pub const NVBUF_MEM_DEFAULT: NvBufSurfaceMemType = 0;
+pub const NVBUF_MEM_CUDA_PINNED: NvBufSurfaceMemType = 1;
+pub const NVBUF_MEM_CUDA_DEVICE: NvBufSurfaceMemType = 2;
+pub const NVBUF_MEM_CUDA_UNIFIED: NvBufSurfaceMemType = 3;
+pub const NVBUF_MEM_SURFACE_ARRAY: NvBufSurfaceMemType = 4;
+
+pub type NvBufSurfaceMemMapFlags = u32;
+pub const NVBUF_MAP_READ: NvBufSurfaceMemMapFlags = 0;
+pub const NVBUF_MAP_WRITE: NvBufSurfaceMemMapFlags = 1;
+pub const NVBUF_MAP_READ_WRITE: NvBufSurfaceMemMapFlags = 2;
+
+#[repr(C)]
+pub struct NvBufSurfacePlaneParams {
+    pub num_planes: u32,
+    pub width: [u32; NVBUF_MAX_PLANES],
+    pub height: [u32; NVBUF_MAX_PLANES],
+    pub pitch: [u32; NVBUF_MAX_PLANES],
+    pub offset: [u32; NVBUF_MAX_PLANES],
+    pub psize: [u32; NVBUF_MAX_PLANES],
+    pub bytes_per_pix: [u32; NVBUF_MAX_PLANES],
+    pub reserved: [*mut c_void; STRUCTURE_PADDING * NVBUF_MAX_PLANES],
+}
+
+#[repr(C)]
+pub struct NvBufSurfaceMappedAddr {
+    pub addr: [*mut c_void; NVBUF_MAX_PLANES],
+    pub egl_image: *mut c_void,
+    pub reserved: [*mut c_void; STRUCTURE_PADDING],
+}
+
+#[repr(C)]
+pub struct NvBufSurfaceParams {
+    pub width: u32,
+    pub height: u32,
+    pub pitch: u32,
+    pub color_format: NvBufSurfaceColorFormat,
+    pub layout: u32,
+    pub buffer_desc: u64,
+    pub data_size: u32,
+    pub data_ptr: *mut c_void,
+    pub plane_params: NvBufSurfacePlaneParams,
+    pub mapped_addr: NvBufSurfaceMappedAddr,
+    pub paramex: *mut c_void,
+    pub reserved: [*mut c_void; STRUCTURE_PADDING - 1],
+}
+
+#[repr(C)]
+pub struct NvBufSurface {
+    pub gpu_id: u32,
+    pub batch_size: u32,
+    pub num_filled: u32,
+    pub is_contiguous: bool,
+    pub mem_type: NvBufSurfaceMemType,
+    pub surface_list: *mut NvBufSurfaceParams,
+    pub is_imported_buf: bool,
+    pub reserved: [*mut c_void; STRUCTURE_PADDING],
+}
+
+#[cfg(feature = "deepstream")]
+#[link(name = "nvbufsurface")]
+extern "C" {
+    pub fn NvBufSurfaceMap(surf: *mut NvBufSurface, index: c_int, plane: c_int, map_type: NvBufSurfaceMemMapFlags) -> c_int;
+    pub fn NvBufSurfaceUnMap(surf: *mut NvBufSurface, index: c_int, plane: c_int) -> c_int;
+    pub fn NvBufSurfaceSyncForCpu(surf: *mut NvBufSurface, index: c_int, plane: c_int) -> c_int;
+    pub fn NvBufSurfaceSyncForDevice(surf: *mut NvBufSurface, index: c_int, plane: c_int) -> c_int;
+}
+
+// Without the deepstream feature there are no NVMM surfaces; every call fails as the
+// real library does for a buffer that isn't one.
+
+/// # Safety
+/// Always safe; kept unsafe to match the DeepStream signature.
+#[cfg(not(feature = "deepstream"))]
+#[allow(non_snake_case)]
+pub unsafe fn NvBufSurfaceMap(_surf: *mut NvBufSurface, _index: c_int, _plane: c_int, _map_type: NvBufSurfaceMemMapFlags) -> c_int {
+    -1
+}
+
+/// # Safety
+/// Always safe; kept unsafe to match the DeepStream signature.
+#[cfg(not(feature = "deepstream"))]
+#[allow(non_snake_case)]
+pub unsafe fn NvBufSurfaceUnMap(_surf: *mut NvBufSurface, _index: c_int, _plane: c_int) -> c_int {
+    -1
+}
+
+/// # Safety
+/// Always safe; kept unsafe to match the DeepStream signature.
+#[cfg(not(feature = "deepstream"))]
+#[allow(non_snake_case)]
+pub unsafe fn NvBufSurfaceSyncForCpu(_surf: *mut NvBufSurface, _index: c_int, _plane: c_int) -> c_int {
+    -1
+}
+
+/// # Safety
+/// Always safe; kept unsafe to match the DeepStream signature.
+#[cfg(not(feature = "deepstream"))]
+#[allow(non_snake_case)]
+pub unsafe fn NvBufSurfaceSyncForDevice(_surf: *mut NvBufSurface, _index: c_int, _plane: c_int) -> c_int {
+    -1
+}
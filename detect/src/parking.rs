@@ -0,0 +1,299 @@
+use crate::detections::pts_seconds;
+use crate::events::{Event, Events};
+use crate::metrics::Metrics;
+use crate::png;
+use crate::processing::{self, MappedFrame};
+use crate::zones::{self, Zone};
+use std::env;
+use std::time::{Duration, Instant};
+
+const FRAME_ELEMENT: &str = "parking_frames";
+
+/// Sample points per side of a spot's bounding box used to estimate coverage
+const SAMPLES_PER_SIDE: usize = 12;
+
+/// Overview images are downscaled to at most this width
+const MAX_OVERVIEW_WIDTH: u32 = 1280;
+
+const FREE_COLOR: [u8; 3] = [0, 200, 0];
+const OCCUPIED_COLOR: [u8; 3] = [230, 0, 0];
+
+pub struct ParkingConfig {
+    pub spots: Vec<Zone>,
+    /// Labels that occupy a spot, lowercase
+    pub classes: Vec<String>,
+    /// Fraction of a spot that must be covered by a vehicle box
+    pub overlap: f32,
+    /// Seconds a new state must hold before the spot changes
+    pub smoothing: f64,
+    pub overview_dir: Option<String>,
+    /// Minimum time between overview images of a source
+    pub overview_interval: Duration,
+}
+
+impl ParkingConfig {
+    /// Reads PARKING_SPOTS (same syntax as ZONES) / PARKING_CLASSES / PARKING_OVERLAP /
+    /// PARKING_SMOOTHING / PARKING_OVERVIEW_DIR / PARKING_OVERVIEW_INTERVAL. Exits on a
+    /// malformed PARKING_SPOTS like the other startup configuration.
+    pub fn from_env() -> Option<ParkingConfig> {
+        let spec = env::var("PARKING_SPOTS").ok()?;
+        let spots = zones::parse_zones(&spec).unwrap_or_else(|e| {
+            eprintln!("Error: PARKING_SPOTS: {}", e);
+            std::process::exit(2);
+        });
+        let classes = env::var("PARKING_CLASSES")
+            .unwrap_or_else(|_| "car,bus,truck".to_string())
+            .split(',')
+            .map(|c| c.trim().to_lowercase())
+            .filter(|c| !c.is_empty())
+            .collect();
+        let overlap = env::var("PARKING_OVERLAP").ok().and_then(|v| v.parse().ok()).unwrap_or(0.4);
+        let smoothing = env::var("PARKING_SMOOTHING").ok().and_then(|v| v.parse().ok()).unwrap_or(5.0);
+        let overview_dir = env::var("PARKING_OVERVIEW_DIR").ok();
+        let overview_interval = env::var("PARKING_OVERVIEW_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(10));
+        Some(ParkingConfig { spots, classes, overlap, smoothing, overview_dir, overview_interval })
+    }
+
+    /// Mapped frames so overview images can be saved.
+    pub fn stage(&self) -> String {
+        processing::mappable_stage(FRAME_ELEMENT)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SpotState {
+    occupied: bool,
+    since: f64,
+    /// Time the opposite state was first seen, while it isn't confirmed yet
+    pending: Option<f64>,
+}
+
+struct SourceLot {
+    source_id: u32,
+    spots: Vec<SpotState>,
+    last_overview: Option<Instant>,
+    overview_due: bool,
+}
+
+struct ParkingMonitor {
+    config: ParkingConfig,
+    class_ids: Vec<i32>,
+    // Normalized sample points inside each spot
+    samples: Vec<Vec<(f32, f32)>>,
+    events: Events,
+    metrics: Metrics,
+    lots: Vec<SourceLot>,
+}
+
+fn sample_points(spot: &Zone) -> Vec<(f32, f32)> {
+    let (x0, y0) = spot.origin();
+    let x1 = spot.points.iter().map(|p| p.0).fold(0.0, f32::max);
+    let y1 = spot.points.iter().map(|p| p.1).fold(0.0, f32::max);
+    let mut points = Vec::new();
+    for i in 0..SAMPLES_PER_SIDE {
+        for j in 0..SAMPLES_PER_SIDE {
+            let x = x0 + (x1 - x0) * (j as f32 + 0.5) / SAMPLES_PER_SIDE as f32;
+            let y = y0 + (y1 - y0) * (i as f32 + 0.5) / SAMPLES_PER_SIDE as f32;
+            if spot.contains(x, y) {
+                points.push((x, y));
+            }
+        }
+    }
+    points
+}
+
+/// Draws a 2 px wide line into a packed RGB image.
+fn draw_line(rgb: &mut [u8], (w, h): (u32, u32), (x0, y0): (f32, f32), (x1, y1): (f32, f32), color: [u8; 3]) {
+    let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as usize;
+    for step in 0..=steps {
+        let t = step as f32 / steps as f32;
+        let (x, y) = ((x0 + (x1 - x0) * t) as i64, (y0 + (y1 - y0) * t) as i64);
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let (px, py) = (x + dx, y + dy);
+            if px >= 0 && py >= 0 && (px as u32) < w && (py as u32) < h {
+                let i = (py as usize * w as usize + px as usize) * 3;
+                rgb[i..i + 3].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+impl ParkingMonitor {
+    fn lot(&mut self, source_id: u32, now: f64) -> &mut SourceLot {
+        let index = match self.lots.iter().position(|l| l.source_id == source_id) {
+            Some(index) => index,
+            None => {
+                let free = SpotState { occupied: false, since: now, pending: None };
+                self.lots.push(SourceLot {
+                    source_id,
+                    spots: vec![free; self.config.spots.len()],
+                    last_overview: None,
+                    overview_due: true,
+                });
+                self.lots.len() - 1
+            }
+        };
+        &mut self.lots[index]
+    }
+
+    fn process(&mut self, frame: &mut MappedFrame) {
+        let meta = &mut *frame.meta;
+        let (source_id, frame_num) = (meta.source_id, meta.frame_num);
+        let now = pts_seconds(meta.buf_pts, frame_num);
+        let (width, height) = (meta.pipeline_width.max(1) as f32, meta.pipeline_height.max(1) as f32);
+        let boxes: Vec<(f32, f32, f32, f32)> = meta
+            .objects()
+            .filter(|obj| obj.parent.is_null() && self.class_ids.contains(&obj.class_id))
+            .map(|obj| {
+                let r = &obj.rect_params;
+                (r.left / width, r.top / height, (r.left + r.width) / width, (r.top + r.height) / height)
+            })
+            .collect();
+
+        let coverage: Vec<f32> = self
+            .samples
+            .iter()
+            .map(|points| {
+                let covered = points
+                    .iter()
+                    .filter(|(x, y)| boxes.iter().any(|b| *x >= b.0 && *x <= b.2 && *y >= b.1 && *y <= b.3))
+                    .count();
+                covered as f32 / points.len().max(1) as f32
+            })
+            .collect();
+
+        let (overlap, smoothing) = (self.config.overlap, self.config.smoothing);
+        let mut changes = Vec::new();
+        let lot = self.lot(source_id, now);
+        for (index, (spot, coverage)) in lot.spots.iter_mut().zip(&coverage).enumerate() {
+            let occupied = *coverage >= overlap;
+            if occupied == spot.occupied {
+                spot.pending = None;
+                continue;
+            }
+            let pending = *spot.pending.get_or_insert(now);
+            if now - pending >= smoothing {
+                changes.push((index, occupied, pending - spot.since));
+                *spot = SpotState { occupied, since: pending, pending: None };
+            }
+        }
+        if !changes.is_empty() {
+            lot.overview_due = true;
+        }
+        let states: Vec<bool> = lot.spots.iter().map(|s| s.occupied).collect();
+
+        let source = source_id.to_string();
+        {
+            let mut metrics = self.metrics.lock().unwrap();
+            for (spot, occupied) in self.config.spots.iter().zip(&states) {
+                let value = if *occupied { 1.0 } else { 0.0 };
+                metrics.set("detect_parking_spot_occupied", &[("source", &source), ("spot", &spot.name)], value);
+            }
+            let free = states.iter().filter(|o| !**o).count();
+            metrics.set("detect_parking_free_spots", &[("source", &source)], free as f64);
+        }
+        let mut events = self.events.lock().unwrap();
+        for (index, occupied, previous_secs) in &changes {
+            let kind = if *occupied { "parking_spot_occupied" } else { "parking_spot_freed" };
+            events.emit(
+                Event::new(kind, source_id, frame_num)
+                    .with("spot", self.config.spots[*index].name.as_str())
+                    .with("previous_seconds", *previous_secs),
+            );
+        }
+        drop(events);
+
+        self.draw_osd(frame.meta, &states);
+        self.save_overview(frame, &states);
+    }
+
+    /// Spot outlines on the OSD: green when free, red when occupied.
+    fn draw_osd(&self, meta: &mut nvds::NvDsFrameMeta, states: &[bool]) {
+        let (width, height) = (meta.pipeline_width as f32, meta.pipeline_height as f32);
+        let mut lines = Vec::new();
+        for (spot, occupied) in self.config.spots.iter().zip(states) {
+            let color = if *occupied { OCCUPIED_COLOR } else { FREE_COLOR };
+            let color = nvds::NvOSD_ColorParams {
+                red: color[0] as f64 / 255.0,
+                green: color[1] as f64 / 255.0,
+                blue: color[2] as f64 / 255.0,
+                alpha: 1.0,
+            };
+            let n = spot.points.len();
+            for i in 0..n {
+                let ((x1, y1), (x2, y2)) = (spot.points[i], spot.points[(i + 1) % n]);
+                lines.push(((x1 * width, y1 * height), (x2 * width, y2 * height), color));
+            }
+        }
+        for batch in lines.chunks(nvds::MAX_ELEMENTS_IN_DISPLAY_META) {
+            let Some(display) = meta.add_display_meta() else { return };
+            for (line, ((x1, y1), (x2, y2), color)) in display.line_params.iter_mut().zip(batch) {
+                line.x1 = *x1 as u32;
+                line.y1 = *y1 as u32;
+                line.x2 = *x2 as u32;
+                line.y2 = *y2 as u32;
+                line.line_width = 3;
+                line.line_color = *color;
+            }
+            display.num_lines = batch.len() as u32;
+        }
+    }
+
+    /// `parking_src{N}.png`: the frame with the spots outlined by state, after changes.
+    fn save_overview(&mut self, frame: &MappedFrame, states: &[bool]) {
+        let Some(dir) = self.config.overview_dir.clone() else { return };
+        let interval = self.config.overview_interval;
+        let source_id = frame.meta.source_id;
+        let Some(lot) = self.lots.iter_mut().find(|l| l.source_id == source_id) else { return };
+        if !lot.overview_due || lot.last_overview.is_some_and(|at| at.elapsed() < interval) {
+            return;
+        }
+        lot.overview_due = false;
+        lot.last_overview = Some(Instant::now());
+
+        let (w, h, mut rgb) = frame.rgb(frame.width.div_ceil(MAX_OVERVIEW_WIDTH));
+        for (spot, occupied) in self.config.spots.iter().zip(states) {
+            let color = if *occupied { OCCUPIED_COLOR } else { FREE_COLOR };
+            let n = spot.points.len();
+            for i in 0..n {
+                let ((x0, y0), (x1, y1)) = (spot.points[i], spot.points[(i + 1) % n]);
+                let (w_f, h_f) = (w as f32, h as f32);
+                draw_line(&mut rgb, (w, h), (x0 * w_f, y0 * h_f), (x1 * w_f, y1 * h_f), color);
+            }
+        }
+        let path = format!("{}/parking_src{}.png", dir, source_id);
+        if let Err(e) = png::write_rgb(&path, w, h, &rgb) {
+            eprintln!("Warning: Failed to write parking overview {}: {}", path, e);
+        }
+    }
+}
+
+/// Marks spots occupied or free from the vehicle boxes, with `smoothing` seconds of
+/// hysteresis. Emits parking_spot_occupied / parking_spot_freed, updates the parking
+/// metrics, outlines the spots on the OSD and saves overview images.
+pub fn attach(bin: &gstreamer::Element, config: ParkingConfig, labels: &[String], events: Events, metrics: Metrics) {
+    let mut class_ids = Vec::new();
+    for name in &config.classes {
+        match labels.iter().position(|l| l.eq_ignore_ascii_case(name)) {
+            Some(id) => class_ids.push(id as i32),
+            None => eprintln!("Warning: PARKING_CLASSES entry '{}' is not in the labels file", name),
+        }
+    }
+    if let Some(dir) = &config.overview_dir {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("Warning: Could not create PARKING_OVERVIEW_DIR {}: {}", dir, e);
+        }
+    }
+    {
+        let mut metrics = metrics.lock().unwrap();
+        metrics.describe("detect_parking_spot_occupied", "gauge", "1 while the parking spot is occupied");
+        metrics.describe("detect_parking_free_spots", "gauge", "Free parking spots per source");
+    }
+    let samples = config.spots.iter().map(sample_points).collect();
+    let mut monitor = ParkingMonitor { config, class_ids, samples, events, metrics, lots: Vec::new() };
+    processing::attach_at(bin, FRAME_ELEMENT, Box::new(move |frame: &mut MappedFrame| monitor.process(frame)));
+}
@@ -0,0 +1,294 @@
+use std::fs;
+use std::io;
+
+/// Thrift compact protocol type ids
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn binary(out: &mut Vec<u8>, bytes: &[u8]) {
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// A list element holding an i32 (or enum) value.
+fn int_element(value: i32) -> Vec<u8> {
+    let mut out = Vec::new();
+    varint(&mut out, zigzag(value as i64));
+    out
+}
+
+/// A list element holding a string.
+fn string_element(value: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    binary(&mut out, value.as_bytes());
+    out
+}
+
+/// One thrift struct in the compact protocol. Fields must be added in id order; each
+/// header carries the delta to the previous id.
+#[derive(Default)]
+struct Struct {
+    out: Vec<u8>,
+    last: i16,
+}
+
+impl Struct {
+    fn field(&mut self, id: i16, kind: u8) {
+        let delta = id - self.last;
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | kind);
+        } else {
+            self.out.push(kind);
+            varint(&mut self.out, zigzag(id as i64));
+        }
+        self.last = id;
+    }
+
+    fn i32(mut self, id: i16, value: i32) -> Struct {
+        self.field(id, I32);
+        varint(&mut self.out, zigzag(value as i64));
+        self
+    }
+
+    fn i64(mut self, id: i16, value: i64) -> Struct {
+        self.field(id, I64);
+        varint(&mut self.out, zigzag(value));
+        self
+    }
+
+    fn string(mut self, id: i16, value: &str) -> Struct {
+        self.field(id, BINARY);
+        binary(&mut self.out, value.as_bytes());
+        self
+    }
+
+    fn structure(mut self, id: i16, value: Struct) -> Struct {
+        self.field(id, STRUCT);
+        self.out.extend(value.finish());
+        self
+    }
+
+    /// `items` are already encoded elements of type `kind`.
+    fn list(mut self, id: i16, kind: u8, items: Vec<Vec<u8>>) -> Struct {
+        self.field(id, LIST);
+        if items.len() < 15 {
+            self.out.push((items.len() as u8) << 4 | kind);
+        } else {
+            self.out.push(0xf0 | kind);
+            varint(&mut self.out, items.len() as u64);
+        }
+        for item in items {
+            self.out.extend(item);
+        }
+        self
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.out.push(0);
+        self.out
+    }
+}
+
+/// A required column of a table.
+pub enum Column {
+    Int64(&'static str, Vec<i64>),
+    Double(&'static str, Vec<f64>),
+    Text(&'static str, Vec<String>),
+}
+
+impl Column {
+    fn name(&self) -> &str {
+        match self {
+            Column::Int64(name, _) | Column::Double(name, _) | Column::Text(name, _) => name,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Column::Int64(_, values) => values.len(),
+            Column::Double(_, values) => values.len(),
+            Column::Text(_, values) => values.len(),
+        }
+    }
+
+    /// Parquet physical type: INT64, DOUBLE or BYTE_ARRAY
+    fn physical_type(&self) -> i32 {
+        match self {
+            Column::Int64(..) => 2,
+            Column::Double(..) => 5,
+            Column::Text(..) => 6,
+        }
+    }
+
+    /// PLAIN encoding: little-endian numbers, length-prefixed strings.
+    fn plain(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Column::Int64(_, values) => values.iter().for_each(|v| out.extend_from_slice(&v.to_le_bytes())),
+            Column::Double(_, values) => values.iter().for_each(|v| out.extend_from_slice(&v.to_le_bytes())),
+            Column::Text(_, values) => values.iter().for_each(|v| {
+                out.extend_from_slice(&(v.len() as u32).to_le_bytes());
+                out.extend_from_slice(v.as_bytes());
+            }),
+        }
+        out
+    }
+}
+
+/// Minimal Parquet writer: one row group with one uncompressed, PLAIN-encoded data page
+/// per column and no statistics, so files are larger than a library would write but
+/// need no Arrow or compression dependency. Every column must have the same length.
+pub fn write(path: &str, columns: &[Column]) -> io::Result<()> {
+    let rows = columns.first().map_or(0, Column::len);
+    if let Some(column) = columns.iter().find(|c| c.len() != rows) {
+        let message = format!("Parquet column {} has {} rows, expected {}", column.name(), column.len(), rows);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    }
+
+    let mut file = b"PAR1".to_vec();
+    let mut chunks = Vec::new();
+    let mut total_size = 0;
+    for column in columns {
+        let data = column.plain();
+        // DATA_PAGE with PLAIN values; required columns have no definition/repetition levels
+        let page = Struct::default().i32(1, rows as i32).i32(2, 0).i32(3, 3).i32(4, 3);
+        let header = Struct::default()
+            .i32(1, 0)
+            .i32(2, data.len() as i32)
+            .i32(3, data.len() as i32)
+            .structure(5, page)
+            .finish();
+        let offset = file.len() as i64;
+        let size = (header.len() + data.len()) as i64;
+        file.extend(header);
+        file.extend(data);
+        total_size += size;
+
+        let meta = Struct::default()
+            .i32(1, column.physical_type())
+            .list(2, I32, vec![int_element(0), int_element(3)])
+            .list(3, BINARY, vec![string_element(column.name())])
+            .i32(4, 0)
+            .i64(5, rows as i64)
+            .i64(6, size)
+            .i64(7, size)
+            .i64(9, offset);
+        chunks.push(Struct::default().i64(2, offset).structure(3, meta).finish());
+    }
+
+    let mut schema = vec![Struct::default().string(4, "schema").i32(5, columns.len() as i32).finish()];
+    for column in columns {
+        let element = Struct::default().i32(1, column.physical_type()).i32(3, 0).string(4, column.name());
+        // Strings are annotated UTF8 so readers show text instead of bytes
+        let element = if matches!(column, Column::Text(..)) { element.i32(6, 0) } else { element };
+        schema.push(element.finish());
+    }
+    let row_group = Struct::default().list(1, STRUCT, chunks).i64(2, total_size).i64(3, rows as i64).finish();
+    let footer = Struct::default()
+        .i32(1, 1)
+        .list(2, STRUCT, schema)
+        .i64(3, rows as i64)
+        .list(4, STRUCT, vec![row_group])
+        .string(6, &format!("detect version {}", env!("CARGO_PKG_VERSION")))
+        .finish();
+    file.extend_from_slice(&footer);
+    file.extend_from_slice(&(footer.len() as u32).to_le_bytes());
+    file.extend_from_slice(b"PAR1");
+
+    // Written aside and renamed so readers never see a half-written file
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, file)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::Field;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("detect_parquet_{}_{}", std::process::id(), name)).display().to_string()
+    }
+
+    fn columns(rows: usize) -> Vec<Column> {
+        vec![
+            Column::Int64("timestamp", (0..rows as i64).map(|i| 1_700_000_000_000 + i).collect()),
+            Column::Double("score", (0..rows).map(|i| i as f64 / 4.0).collect()),
+            Column::Text("label", (0..rows).map(|i| format!("object-{}", i)).collect()),
+        ]
+    }
+
+    #[test]
+    fn files_are_framed_by_magic_and_footer_length() {
+        let path = temp_path("framing.parquet");
+        write(&path, &columns(3)).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(&bytes[..4], b"PAR1");
+        assert_eq!(&bytes[bytes.len() - 4..], b"PAR1");
+        let footer_len = u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap()) as usize;
+        assert!(footer_len > 0 && footer_len + 12 < bytes.len());
+        // The footer is a FileMetaData struct: field 1 (version) comes first and the
+        // struct ends with a stop byte
+        let footer = &bytes[bytes.len() - 8 - footer_len..bytes.len() - 8];
+        assert_eq!(footer[0], 0x10 | I32);
+        assert_eq!(footer[footer_len - 1], 0);
+    }
+
+    #[test]
+    fn a_reader_gets_the_rows_back() {
+        let path = temp_path("rows.parquet");
+        write(&path, &columns(20)).unwrap();
+        let reader = SerializedFileReader::new(fs::File::open(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 20);
+        assert_eq!(metadata.num_row_groups(), 1);
+        let names: Vec<&str> = metadata.file_metadata().schema_descr().columns().iter().map(|c| c.name()).collect();
+        assert_eq!(names, ["timestamp", "score", "label"]);
+
+        let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), 20);
+        let fields: Vec<&Field> = rows[5].get_column_iter().map(|(_, field)| field).collect();
+        assert_eq!(fields[0], &Field::Long(1_700_000_000_005));
+        assert_eq!(fields[1], &Field::Double(1.25));
+        assert_eq!(fields[2], &Field::Str("object-5".to_string()));
+    }
+
+    #[test]
+    fn an_empty_table_is_still_readable() {
+        let path = temp_path("empty.parquet");
+        write(&path, &columns(0)).unwrap();
+        let reader = SerializedFileReader::new(fs::File::open(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 0);
+    }
+
+    #[test]
+    fn columns_of_different_lengths_are_an_error() {
+        let path = temp_path("ragged.parquet");
+        let ragged = [Column::Int64("a", vec![1, 2]), Column::Double("b", vec![1.0])];
+        let error = write(&path, &ragged).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "Parquet column b has 1 rows, expected 2");
+        assert!(!std::path::Path::new(&path).exists());
+    }
+}
@@ -0,0 +1,86 @@
+use gstreamer::prelude::*;
+use std::env;
+
+/// Synchronized playback of several recorded files, for developing multi-camera analytics
+/// against datasets. Each file can be given a start offset so recordings that started
+/// at different times line up, and batching waits for a frame from every file so runs
+/// are repeatable.
+pub struct PlaybackConfig {
+    /// Seconds to skip at the start of each input, normalized so the smallest is 0
+    pub skips: Vec<f64>,
+    /// Wait for every source before pushing a batch
+    pub sync: bool,
+}
+
+impl PlaybackConfig {
+    /// Reads SOURCE_OFFSETS (comma-separated seconds, one per GST_DEVICE entry: the time each
+    /// recording started relative to the others) and SYNC_PLAYBACK=true. Exits on a
+    /// malformed SOURCE_OFFSETS like the other startup configuration.
+    pub fn from_env(source_count: usize) -> Option<PlaybackConfig> {
+        let sync = env::var("SYNC_PLAYBACK").map(|v| v == "true").unwrap_or(false);
+        let offsets = match env::var("SOURCE_OFFSETS") {
+            Ok(spec) => {
+                let offsets = spec
+                    .split(',')
+                    .map(|v| v.trim().parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .unwrap_or_else(|_| {
+                        eprintln!("Error: SOURCE_OFFSETS must be comma-separated seconds, got '{}'", spec);
+                        std::process::exit(2);
+                    });
+                if offsets.len() != source_count {
+                    eprintln!(
+                        "Error: SOURCE_OFFSETS has {} values for {} inputs in GST_DEVICE",
+                        offsets.len(),
+                        source_count
+                    );
+                    std::process::exit(2);
+                }
+                offsets
+            }
+            Err(_) if sync => vec![0.0; source_count],
+            Err(_) => return None,
+        };
+        // A recording that started later (larger offset) is already "behind", so the
+        // earlier ones skip ahead to meet it
+        let latest = offsets.iter().cloned().fold(f64::MIN, f64::max);
+        let skips = offsets.iter().map(|o| latest - o).collect();
+        Some(PlaybackConfig { skips, sync: sync || source_count > 1 })
+    }
+
+    /// Lockstep batching without offsets, as used by replay mode.
+    pub fn lockstep(source_count: usize) -> PlaybackConfig {
+        PlaybackConfig { skips: vec![0.0; source_count], sync: true }
+    }
+}
+
+/// Drops the skipped start of every input at its nvstreammux sink pad and shifts the
+/// remaining timestamps so all inputs start at 0.
+pub fn attach(bin: &gstreamer::Element, config: &PlaybackConfig) {
+    let Some(mux) = bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("m")) else {
+        eprintln!("Warning: nvstreammux not found, source offsets not applied");
+        return;
+    };
+    for (index, skip) in config.skips.iter().enumerate() {
+        if *skip <= 0.0 {
+            continue;
+        }
+        let Some(pad) = mux.static_pad(&format!("sink_{}", index)) else {
+            eprintln!("Warning: nvstreammux has no sink_{}, offset not applied", index);
+            continue;
+        };
+        let skip = gstreamer::ClockTime::from_nseconds((skip * 1e9) as u64);
+        pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+            if let Some(gstreamer::PadProbeData::Buffer(buffer)) = &mut info.data {
+                let Some(pts) = buffer.pts() else {
+                    return gstreamer::PadProbeReturn::Ok;
+                };
+                if pts < skip {
+                    return gstreamer::PadProbeReturn::Drop;
+                }
+                buffer.make_mut().set_pts(pts - skip);
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+    }
+}
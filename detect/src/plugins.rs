@@ -0,0 +1,123 @@
+// Event sinks from shared libraries (EVENT_PLUGINS), for integrations that can't live in
+// this crate, e.g. a VMS SDK or a SCADA gateway. The ABI is plain C so plugins can be
+// written in any language:
+//
+//   void *detect_sink_open(const char *config);               // NULL when it can't start
+//   int   detect_sink_send(void *sink, const char *event_json); // 0 on success
+//   void  detect_sink_close(void *sink);                       // optional
+//
+// open and close are called once; send is called for every event, always from the same
+// worker thread, with the JSON that EVENTS_FILE would get.
+
+use crate::events::Event;
+use std::env;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::{c_char, c_int};
+
+const RTLD_NOW: c_int = 2;
+
+extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlerror() -> *mut c_char;
+}
+
+type OpenFn = unsafe extern "C" fn(config: *const c_char) -> *mut c_void;
+type SendFn = unsafe extern "C" fn(sink: *mut c_void, event_json: *const c_char) -> c_int;
+type CloseFn = unsafe extern "C" fn(sink: *mut c_void);
+
+fn last_error() -> String {
+    // SAFETY: dlerror returns NULL or a NUL-terminated string valid until the next dl* call
+    let error = unsafe { dlerror() };
+    if error.is_null() {
+        "unknown error".to_string()
+    } else {
+        // SAFETY: checked non-null above
+        unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned()
+    }
+}
+
+/// One loaded plugin with its open sink. The library is never unloaded; closing the sink
+/// is all that happens when it's dropped.
+pub struct Plugin {
+    pub name: String,
+    sink: *mut c_void,
+    send: SendFn,
+    close: Option<CloseFn>,
+}
+
+// SAFETY: the sink handle is only ever used from the thread that owns the Plugin
+unsafe impl Send for Plugin {}
+
+impl Plugin {
+    fn load(path: &str, config: &str) -> Result<Plugin, String> {
+        let filename = CString::new(path).map_err(|_| "path contains a NUL byte".to_string())?;
+        let config = CString::new(config).map_err(|_| "config contains a NUL byte".to_string())?;
+        // SAFETY: filename is NUL-terminated; loading runs the library's initializers,
+        // which is what asking for a plugin means
+        let library = unsafe { dlopen(filename.as_ptr(), RTLD_NOW) };
+        if library.is_null() {
+            return Err(last_error());
+        }
+        let symbol = |name: &CStr| {
+            // SAFETY: library is a live handle and name is NUL-terminated
+            let address = unsafe { dlsym(library, name.as_ptr()) };
+            (!address.is_null()).then_some(address)
+        };
+        let missing = |name: &str| format!("does not export {}", name);
+        let open = symbol(c"detect_sink_open").ok_or_else(|| missing("detect_sink_open"))?;
+        let send = symbol(c"detect_sink_send").ok_or_else(|| missing("detect_sink_send"))?;
+        let close = symbol(c"detect_sink_close");
+        // SAFETY: the symbols are declared with these signatures by the plugin ABI above
+        let (open, send, close) = unsafe {
+            (
+                std::mem::transmute::<*mut c_void, OpenFn>(open),
+                std::mem::transmute::<*mut c_void, SendFn>(send),
+                close.map(|close| std::mem::transmute::<*mut c_void, CloseFn>(close)),
+            )
+        };
+        // SAFETY: config is NUL-terminated and outlives the call
+        let sink = unsafe { open(config.as_ptr()) };
+        if sink.is_null() {
+            return Err("detect_sink_open failed".to_string());
+        }
+        let name = std::path::Path::new(path).file_name().map_or(path.to_string(), |n| n.to_string_lossy().into_owned());
+        Ok(Plugin { name, sink, send, close })
+    }
+
+    pub fn send(&mut self, event: &Event) -> Result<(), String> {
+        let json = CString::new(event.to_json()).map_err(|_| "event contains a NUL byte".to_string())?;
+        // SAFETY: sink came from this plugin's open and json is NUL-terminated for the call
+        match unsafe { (self.send)(self.sink, json.as_ptr()) } {
+            0 => Ok(()),
+            code => Err(format!("detect_sink_send returned {}", code)),
+        }
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        if let Some(close) = self.close {
+            // SAFETY: sink came from this plugin's open and is not used again
+            unsafe { close(self.sink) };
+        }
+    }
+}
+
+/// Loads EVENT_PLUGINS: `;`-separated `<library.so>[:<config>]` entries, the config string
+/// being passed to the plugin's open as is.
+pub fn from_env() -> Vec<Plugin> {
+    let Ok(spec) = env::var("EVENT_PLUGINS") else { return Vec::new() };
+    let mut plugins = Vec::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (path, config) = entry.split_once(':').unwrap_or((entry, ""));
+        match Plugin::load(path.trim(), config) {
+            Ok(plugin) => plugins.push(plugin),
+            Err(e) => {
+                eprintln!("Error: Could not load event plugin {}: {}", path, e);
+                std::process::exit(2);
+            }
+        }
+    }
+    plugins
+}
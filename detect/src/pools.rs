@@ -0,0 +1,150 @@
+use crate::metrics::Metrics;
+use crate::nvbufsurface as nvbuf;
+use gstreamer::prelude::*;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A conversion taking longer than this was waiting for a free output buffer
+const STALL_THRESHOLD: Duration = Duration::from_millis(100);
+
+/// Repeated stalls of one element are reported (not counted) at most this often
+const WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+const MEMORY_TYPES: [(&str, nvbuf::NvBufSurfaceMemType); 5] = [
+    ("default", nvbuf::NVBUF_MEM_DEFAULT),
+    ("pinned", nvbuf::NVBUF_MEM_CUDA_PINNED),
+    ("device", nvbuf::NVBUF_MEM_CUDA_DEVICE),
+    ("unified", nvbuf::NVBUF_MEM_CUDA_UNIFIED),
+    ("surface-array", nvbuf::NVBUF_MEM_SURFACE_ARRAY),
+];
+
+/// NVMM memory and buffer pool sizes of the source converters and nvstreammux, the
+/// elements whose pools run dry first with many streams. Unset values keep DeepStream's
+/// defaults (4 buffers each, default memory).
+#[derive(Clone, Debug, Default)]
+pub struct PoolConfig {
+    pub memory_type: Option<nvbuf::NvBufSurfaceMemType>,
+    /// nvvideoconvert `output-buffers` per source
+    pub convert_buffers: Option<u32>,
+    /// nvstreammux `buffer-pool-size`
+    pub mux_buffers: Option<u32>,
+}
+
+impl PoolConfig {
+    /// Reads NVBUF_MEMORY_TYPE (default, pinned, device, unified or surface-array),
+    /// CONVERT_OUTPUT_BUFFERS and MUX_BUFFER_POOL_SIZE.
+    pub fn from_env() -> PoolConfig {
+        let memory_type = env::var("NVBUF_MEMORY_TYPE").ok().filter(|v| !v.trim().is_empty()).map(|name| {
+            match MEMORY_TYPES.iter().find(|(n, _)| *n == name.trim()) {
+                Some((_, memory_type)) => *memory_type,
+                None => {
+                    let names: Vec<&str> = MEMORY_TYPES.iter().map(|(n, _)| *n).collect();
+                    eprintln!("Error: Unknown NVBUF_MEMORY_TYPE '{}' (expected one of {})", name, names.join(", "));
+                    std::process::exit(2);
+                }
+            }
+        });
+        let count = |name: &str| {
+            env::var(name).ok().map(|v| match v.trim().parse::<u32>() {
+                Ok(n) if n > 0 => n,
+                _ => {
+                    eprintln!("Error: {} must be a positive number of buffers, got '{}'", name, v);
+                    std::process::exit(2);
+                }
+            })
+        };
+        PoolConfig {
+            memory_type,
+            convert_buffers: count("CONVERT_OUTPUT_BUFFERS"),
+            mux_buffers: count("MUX_BUFFER_POOL_SIZE"),
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.memory_type.is_none() && self.convert_buffers.is_none() && self.mux_buffers.is_none()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(memory_type) = self.memory_type {
+            let name = MEMORY_TYPES.iter().find(|(_, t)| *t == memory_type).map_or("?", |(n, _)| n);
+            parts.push(format!("{} memory", name));
+        }
+        if let Some(n) = self.convert_buffers {
+            parts.push(format!("{} buffers per source converter", n));
+        }
+        if let Some(n) = self.mux_buffers {
+            parts.push(format!("{} muxer buffers", n));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Whether `element` is a source converter: nvstreammux is at most a caps filter and a queue
+/// downstream of it.
+fn feeds_muxer(element: &gstreamer::Element) -> bool {
+    let mut element = element.clone();
+    for _ in 0..3 {
+        let next = element.static_pad("src").and_then(|p| p.peer()).and_then(|p| p.parent_element());
+        match next {
+            Some(next) if next.name() == "m" => return true,
+            Some(next) => element = next,
+            None => return false,
+        }
+    }
+    false
+}
+
+/// Times every nvvideoconvert from buffer in to buffer out. One that waits longer than
+/// STALL_THRESHOLD has no free output buffer, i.e. its pool is exhausted because everything
+/// downstream still holds buffers. Stalls are reported with what to change and counted in
+/// `detect_buffer_pool_stalls_total`.
+pub fn watch(pipeline: &gstreamer::Element, metrics: Metrics) {
+    let Some(bin) = pipeline.downcast_ref::<gstreamer::Bin>() else { return };
+    metrics.lock().unwrap().describe(
+        "detect_buffer_pool_stalls_total",
+        "counter",
+        "Conversions that waited over 100 ms for a free NVMM output buffer",
+    );
+    let converters = bin
+        .iterate_recurse()
+        .into_iter()
+        .flatten()
+        .filter(|e| e.factory().is_some_and(|f| f.name() == "nvvideoconvert"));
+    for converter in converters {
+        let (Some(sink), Some(src)) = (converter.static_pad("sink"), converter.static_pad("src")) else { continue };
+        let name = converter.name().to_string();
+        let advice = if feeds_muxer(&converter) {
+            "raise CONVERT_OUTPUT_BUFFERS or MUX_BUFFER_POOL_SIZE"
+        } else {
+            "shorten the queues after it (STAGE_QUEUES, SINK_QUEUE)"
+        };
+        let entered: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
+        let sink_entered = entered.clone();
+        sink.add_probe(gstreamer::PadProbeType::BUFFER, move |_, _| {
+            *sink_entered.lock().unwrap() = Some(Instant::now());
+            gstreamer::PadProbeReturn::Ok
+        });
+        let metrics = metrics.clone();
+        let warned: Mutex<Option<Instant>> = Mutex::new(None);
+        src.add_probe(gstreamer::PadProbeType::BUFFER, move |_, _| {
+            let Some(entered) = entered.lock().unwrap().take() else { return gstreamer::PadProbeReturn::Ok };
+            let waited = entered.elapsed();
+            if waited >= STALL_THRESHOLD {
+                metrics.lock().unwrap().add("detect_buffer_pool_stalls_total", &[("element", &name)], 1.0);
+                let mut warned = warned.lock().unwrap();
+                if warned.is_none_or(|at| at.elapsed() >= WARNING_INTERVAL) {
+                    *warned = Some(Instant::now());
+                    eprintln!(
+                        "Warning: {} waited {} ms for an output buffer - its pool is exhausted; {}",
+                        name,
+                        waited.as_millis(),
+                        advice
+                    );
+                }
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+    }
+}
@@ -0,0 +1,164 @@
+use crate::events::{Event, Events};
+use crate::json::Value;
+use crate::zones::Zone;
+use gstreamer::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+/// Tracks not seen for this many frames are forgotten
+const TRACK_TIMEOUT_FRAMES: i32 = 300;
+
+/// Safety-compliance checks: a secondary model on the person objects reports the PPE a
+/// person wears, either as classifier labels or as detected items inside the person box.
+pub struct PpeConfig {
+    /// nvinfer config of the PPE classifier or detector (process-mode=2 on the persons)
+    pub model_config: String,
+    pub unique_id: u32,
+    /// Labels of the PPE every person must wear, lowercase
+    pub required: Vec<String>,
+    /// PGIE label of the objects checked
+    pub person_label: String,
+    /// Frames a person must be missing (or wearing) PPE before the state changes
+    pub min_frames: u32,
+}
+
+impl PpeConfig {
+    /// Reads PPE_CONFIG / PPE_UNIQUE_ID / PPE_REQUIRED (comma-separated) / PPE_PERSON_LABEL
+    /// / PPE_MIN_FRAMES.
+    pub fn from_env() -> Option<PpeConfig> {
+        let model_config = env::var("PPE_CONFIG").ok()?;
+        let unique_id = env::var("PPE_UNIQUE_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(8);
+        let required: Vec<String> = env::var("PPE_REQUIRED")
+            .unwrap_or_else(|_| "helmet,vest".to_string())
+            .split(',')
+            .map(|l| l.trim().to_lowercase())
+            .filter(|l| !l.is_empty())
+            .collect();
+        if required.is_empty() {
+            eprintln!("Warning: PPE_REQUIRED is empty. Compliance checks disabled.");
+            return None;
+        }
+        let person_label = env::var("PPE_PERSON_LABEL").unwrap_or_else(|_| "person".to_string());
+        let min_frames = env::var("PPE_MIN_FRAMES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(15);
+        Some(PpeConfig { model_config, unique_id, required, person_label, min_frames })
+    }
+
+    pub fn stage(&self) -> String {
+        format!(
+            "nvinfer name=ppe process-mode=2 config-file-path={} unique-id={}",
+            self.model_config, self.unique_id
+        )
+    }
+}
+
+#[derive(Default)]
+struct PersonState {
+    /// Consecutive frames with the current `missing` set, or compliant
+    frames: u32,
+    missing: Vec<String>,
+    /// Missing set last reported, empty while compliant
+    reported: Vec<String>,
+    last_seen: i32,
+}
+
+struct PpeChecker {
+    config: PpeConfig,
+    person_class: Option<i32>,
+    zones: Vec<Zone>,
+    events: Events,
+    people: HashMap<(u32, u64), PersonState>,
+}
+
+impl PpeChecker {
+    fn on_frame(&mut self, frame: &mut nvds::NvDsFrameMeta) {
+        let (source_id, frame_num) = (frame.source_id, frame.frame_num);
+        let (width, height) = (frame.pipeline_width.max(1) as f32, frame.pipeline_height.max(1) as f32);
+        let unique_id = self.config.unique_id;
+
+        // Items found by a PPE detector, keyed by the person object they belong to
+        let mut items: HashMap<usize, Vec<String>> = HashMap::new();
+        for obj in frame.objects() {
+            if obj.unique_component_id == unique_id as i32 && !obj.parent.is_null() {
+                items.entry(obj.parent as usize).or_default().push(obj.label().to_lowercase());
+            }
+        }
+
+        for person in frame.objects() {
+            if !person.parent.is_null() || Some(person.class_id) != self.person_class {
+                continue;
+            }
+            let Some(track_id) = person.track_id() else { continue };
+            let rect = &person.rect_params;
+            let (x, y) = ((rect.left + rect.width / 2.0) / width, (rect.top + rect.height) / height);
+            let Some(zone) = self.zones.iter().find(|z| z.contains(x, y)) else { continue };
+
+            let mut worn = person.classifier_labels(unique_id);
+            worn.iter_mut().for_each(|l| *l = l.to_lowercase());
+            if let Some(found) = items.get(&(person as *mut nvds::NvDsObjectMeta as usize)) {
+                worn.extend(found.iter().cloned());
+            }
+            let missing: Vec<String> = self.config.required.iter().filter(|r| !worn.contains(r)).cloned().collect();
+
+            let state = self.people.entry((source_id, track_id)).or_default();
+            state.last_seen = frame_num;
+            if state.missing == missing {
+                state.frames += 1;
+            } else {
+                state.missing = missing;
+                state.frames = 1;
+            }
+            if state.frames >= self.config.min_frames && state.missing != state.reported {
+                let mut event = if state.missing.is_empty() {
+                    Event::new("ppe_compliant", source_id, frame_num)
+                } else {
+                    let missing = Value::Array(state.missing.iter().map(|m| Value::from(m.as_str())).collect());
+                    Event::new("ppe_violation", source_id, frame_num).with("missing", missing)
+                };
+                event = event.with("track_id", track_id).with("zone", zone.name.as_str());
+                state.reported = state.missing.clone();
+                self.events.lock().unwrap().emit(event);
+            }
+            if !state.reported.is_empty() {
+                person.rect_params.border_color = nvds::NvOSD_ColorParams { red: 1.0, green: 0.0, blue: 0.0, alpha: 1.0 };
+            }
+        }
+
+        self.people
+            .retain(|(source, _), state| *source != source_id || frame_num - state.last_seen <= TRACK_TIMEOUT_FRAMES);
+    }
+}
+
+/// Checks every tracked person inside a zone (the whole frame when no ZONES are set) after
+/// the PPE nvinfer. Emits ppe_violation when required PPE stays missing for min_frames,
+/// and ppe_compliant once it is worn again. Violators get a red box. Needs TRACKER_CONFIG.
+pub fn attach(bin: &gstreamer::Element, config: PpeConfig, labels: &[String], zones: &[Zone], events: Events) {
+    let ppe = match bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("ppe")) {
+        Some(ppe) => ppe,
+        None => {
+            eprintln!("Warning: ppe nvinfer not found, compliance checks disabled");
+            return;
+        }
+    };
+    let person_class = labels.iter().position(|l| l.eq_ignore_ascii_case(&config.person_label)).map(|i| i as i32);
+    if person_class.is_none() {
+        eprintln!("Warning: PPE_PERSON_LABEL '{}' is not in the labels file", config.person_label);
+    }
+    let src_pad = ppe.static_pad("src").expect("nvinfer has a src pad");
+    let zones = if zones.is_empty() { vec![Zone::full_frame()] } else { zones.to_vec() };
+    let checker = Mutex::new(PpeChecker { config, person_class, zones, events, people: HashMap::new() });
+
+    src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            let mut checker = checker.lock().unwrap();
+            for frame in batch.frames() {
+                checker.on_frame(frame);
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
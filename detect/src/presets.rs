@@ -0,0 +1,115 @@
+use crate::profile::PROFILES;
+use std::env;
+
+pub const TRACKER: &str = "/opt/nvidia/deepstream/deepstream/samples/configs/deepstream-app/config_tracker_NvDCF_perf.yml";
+
+/// A named starting configuration: settings applied as environment defaults.
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub settings: &'static [(&'static str, &'static str)],
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "people-counter",
+        description: "Tracked people with per-minute counts and occupancy, restreamed over RTSP",
+        settings: &[
+            ("DETECT_OBJECT", "person"),
+            ("MODEL_CONFIG", "/models/config_infer_yolo11s.txt"),
+            ("TRACKER_CONFIG", TRACKER),
+            ("DETECTION_MIN_FRAMES", "3"),
+            ("DETECTION_HOLD_FRAMES", "5"),
+            ("ANALYTICS_DIR", "/analytics"),
+            ("ANALYTICS_INTERVAL", "60"),
+            ("SINKS", "rtsp bitrate=2000000"),
+        ],
+    },
+    Preset {
+        name: "traffic",
+        description: "Tracked cars with per-minute counts, small boxes filtered out and an annotated recording",
+        settings: &[
+            ("DETECT_OBJECT", "car"),
+            ("MODEL_CONFIG", "/models/config_infer_yolo11s.txt"),
+            ("TRACKER_CONFIG", TRACKER),
+            ("DETECTION_MIN_FRAMES", "3"),
+            ("SIZE_FILTERS", "*:min_area=400"),
+            ("ANALYTICS_DIR", "/analytics"),
+            ("ANALYTICS_INTERVAL", "60"),
+            ("RECORD_MODE", "annotated"),
+            ("SINKS", "rtsp bitrate=4000000"),
+        ],
+    },
+    Preset {
+        name: "restream-lowlatency",
+        description: "Fastest model, short RTSP jitterbuffer on the inputs and frequent keyframes on the output",
+        settings: &[
+            ("MODEL_CONFIG", "/models/config_infer_yolo11n.txt"),
+            ("RTSP_LATENCY", "100"),
+            ("RTSP_DROP_ON_LATENCY", "true"),
+            ("SINKS", "rtsp bitrate=3000000 iframeinterval=15"),
+        ],
+    },
+];
+
+/// The value of `<flag> <value>` / `<flag>=<value>` on the command line, else of `var`.
+pub fn selected(flag: &str, var: &str) -> Option<String> {
+    let args: Vec<String> = env::args().collect();
+    let from_args = args.iter().enumerate().find_map(|(i, arg)| match arg.strip_prefix(flag) {
+        Some("") => args.get(i + 1).cloned(),
+        Some(value) => value.strip_prefix('=').map(str::to_string),
+        None => None,
+    });
+    from_args.or_else(|| env::var(var).ok()).filter(|v| !v.trim().is_empty())
+}
+
+/// Sets each variable not set yet; returns the ones that were, and so keep their value.
+pub fn set_defaults(settings: &[(&'static str, &'static str)]) -> Vec<&'static str> {
+    let mut overridden = Vec::new();
+    for (key, value) in settings {
+        if env::var_os(key).is_none() {
+            env::set_var(key, value);
+        } else {
+            overridden.push(*key);
+        }
+    }
+    overridden
+}
+
+/// Applies the selected preset's settings to every variable not set yet, so the
+/// environment, APP_CONFIG_JSON and DETECT_CONFIG all override it.
+///
+/// Must run after the configuration files and before anything else reads the environment.
+pub fn apply() {
+    let Some(name) = selected("--preset", "PRESET") else { return };
+    let Some(preset) = PRESETS.iter().find(|p| p.name == name.trim()) else {
+        let names: Vec<&str> = PRESETS.iter().map(|p| p.name).collect();
+        eprintln!("Error: Unknown preset '{}' (expected one of {})", name, names.join(", "));
+        std::process::exit(2);
+    };
+    let overridden = set_defaults(preset.settings);
+    if overridden.is_empty() {
+        println!("Using preset {}", preset.name);
+    } else {
+        println!("Using preset {} (overridden: {})", preset.name, overridden.join(", "));
+    }
+}
+
+/// `detect presets`: lists the presets and pipeline profiles and what they set.
+pub fn command() -> ! {
+    for preset in PRESETS {
+        println!("{} - {}", preset.name, preset.description);
+        for (key, value) in preset.settings {
+            println!("    {}={}", key, value);
+        }
+    }
+    println!();
+    println!("Profiles (--profile / PROFILE):");
+    for profile in PROFILES {
+        println!("{} - {}", profile.name, profile.description);
+        for (key, value) in profile.settings {
+            println!("    {}={}", key, value);
+        }
+    }
+    std::process::exit(0);
+}
@@ -0,0 +1,163 @@
+use crate::nvbufsurface as nvbuf;
+use gstreamer::prelude::*;
+use std::env;
+use std::sync::Mutex;
+
+/// One frame of the batch, mapped for CPU access. `pixels` is RGBA, `pitch` bytes per row;
+/// changes to it and to `meta` (e.g. object rect or label) are seen by everything downstream.
+pub struct MappedFrame<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub pitch: u32,
+    pub pixels: &'a mut [u8],
+    pub meta: &'a mut nvds::NvDsFrameMeta,
+}
+
+impl MappedFrame<'_> {
+    /// Packed RGB copy keeping every `scale`-th pixel in both directions, e.g. for PNG snapshots.
+    pub fn rgb(&self, scale: u32) -> (u32, u32, Vec<u8>) {
+        let scale = scale.max(1) as usize;
+        let (out_w, out_h) = (self.width as usize / scale, self.height as usize / scale);
+        let mut rgb = Vec::with_capacity(out_w * out_h * 3);
+        for y in 0..out_h {
+            let row = y * scale * self.pitch as usize;
+            for x in 0..out_w {
+                let i = row + x * scale * 4;
+                rgb.extend_from_slice(&self.pixels[i..i + 3]);
+            }
+        }
+        (out_w as u32, out_h as u32, rgb)
+    }
+}
+
+/// Custom per-frame processing in Rust, run on the streaming thread right after inference.
+/// Any `FnMut(&mut MappedFrame) + Send` closure works.
+pub trait FrameProcessor: Send {
+    fn process(&mut self, frame: &mut MappedFrame);
+}
+
+impl<F> FrameProcessor for F
+where
+    F: FnMut(&mut MappedFrame) + Send,
+{
+    fn process(&mut self, frame: &mut MappedFrame) {
+        self(frame)
+    }
+}
+
+const HOOK_ELEMENT: &str = "frame_hook";
+
+/// Pipeline segment the hook probe hangs on.
+pub fn stage() -> String {
+    mappable_stage(HOOK_ELEMENT)
+}
+
+/// Frames are converted to RGBA in memory the CPU can map: CUDA unified memory on dGPU
+/// (device memory can't be mapped), the default surface array on Jetson.
+pub fn mappable_stage(name: &str) -> String {
+    let memory_type = if cfg!(target_arch = "aarch64") {
+        nvbuf::NVBUF_MEM_DEFAULT
+    } else {
+        nvbuf::NVBUF_MEM_CUDA_UNIFIED
+    };
+    surface_stage(name, memory_type)
+}
+
+/// RGBA conversion into `memory_type` surfaces followed by an identity named `name` for probing.
+pub fn surface_stage(name: &str, memory_type: nvbuf::NvBufSurfaceMemType) -> String {
+    format!(
+        "nvvideoconvert nvbuf-memory-type={} ! video/x-raw(memory:NVMM),format=RGBA ! identity name={} silent=true",
+        memory_type, name
+    )
+}
+
+/// Built-in processors selected with FRAME_HOOK. Register your own closure here.
+pub fn from_env() -> Option<Box<dyn FrameProcessor>> {
+    let name = env::var("FRAME_HOOK").ok()?;
+    match name.as_str() {
+        // Privacy filter: paints every detected box solid black before the OSD draws it
+        "redact" => Some(Box::new(|frame: &mut MappedFrame| {
+            let (width, height, pitch) = (frame.width as usize, frame.height as usize, frame.pitch as usize);
+            for obj in frame.meta.objects() {
+                let rect = &obj.rect_params;
+                let x0 = (rect.left.max(0.0) as usize).min(width);
+                let y0 = (rect.top.max(0.0) as usize).min(height);
+                let x1 = ((rect.left + rect.width).max(0.0) as usize).min(width);
+                let y1 = ((rect.top + rect.height).max(0.0) as usize).min(height);
+                for y in y0..y1 {
+                    let row = &mut frame.pixels[y * pitch + x0 * 4..y * pitch + x1 * 4];
+                    for pixel in row.chunks_exact_mut(4) {
+                        pixel.copy_from_slice(&[0, 0, 0, 255]);
+                    }
+                }
+            }
+        })),
+        _ => {
+            eprintln!("Warning: Unknown FRAME_HOOK '{}' (expected redact). Frame hook disabled.", name);
+            None
+        }
+    }
+}
+
+/// Maps each frame's surface for read/write, runs the processor, and syncs the
+/// result back to the device before the buffer moves on.
+pub fn attach(bin: &gstreamer::Element, processor: Box<dyn FrameProcessor>) {
+    attach_at(bin, HOOK_ELEMENT, processor);
+}
+
+/// Same as `attach`, for the identity of a `mappable_stage(name)`.
+pub fn attach_at(bin: &gstreamer::Element, name: &str, processor: Box<dyn FrameProcessor>) {
+    let hook = match bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name(name)) {
+        Some(hook) => hook,
+        None => {
+            eprintln!("Warning: {} element not found, frame processing disabled", name);
+            return;
+        }
+    };
+    let src_pad = hook.static_pad("src").expect("identity has a src pad");
+    let processor = Mutex::new(processor);
+
+    src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some((buffer, Some(batch))) = nvds::probe_buffer(info) {
+            let Ok(map) = buffer.map_readable() else {
+                return gstreamer::PadProbeReturn::Ok;
+            };
+            // NVMM buffers carry an NvBufSurface descriptor instead of pixels
+            let surface = map.as_ptr() as *mut nvbuf::NvBufSurface;
+            let mut processor = processor.lock().unwrap();
+            for meta in batch.frames() {
+                // SAFETY: `surface` is the descriptor of this NVMM buffer and batch_id indexes
+                // its surface list; the mapping is released before the next frame
+                unsafe { process_frame(surface, meta, processor.as_mut()) };
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
+
+/// # Safety
+/// `surface` must point to the NvBufSurface of the buffer `meta` belongs to.
+unsafe fn process_frame(surface: *mut nvbuf::NvBufSurface, meta: &mut nvds::NvDsFrameMeta, processor: &mut dyn FrameProcessor) {
+    let index = meta.batch_id as i32;
+    if meta.batch_id >= (*surface).num_filled {
+        return;
+    }
+    let params = &*(*surface).surface_list.add(meta.batch_id as usize);
+    if params.color_format != nvbuf::NVBUF_COLOR_FORMAT_RGBA {
+        return;
+    }
+    if nvbuf::NvBufSurfaceMap(surface, index, 0, nvbuf::NVBUF_MAP_READ_WRITE) != 0 {
+        return;
+    }
+    nvbuf::NvBufSurfaceSyncForCpu(surface, index, 0);
+
+    let (width, height, pitch) = (params.width, params.height, params.plane_params.pitch[0]);
+    let data = params.mapped_addr.addr[0] as *mut u8;
+    if !data.is_null() {
+        let pixels = std::slice::from_raw_parts_mut(data, pitch as usize * height as usize);
+        let mut frame = MappedFrame { width, height, pitch, pixels, meta };
+        processor.process(&mut frame);
+        nvbuf::NvBufSurfaceSyncForDevice(surface, index, 0);
+    }
+    nvbuf::NvBufSurfaceUnMap(surface, index, 0);
+}
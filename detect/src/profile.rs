@@ -0,0 +1,59 @@
+use crate::presets::{self, Preset};
+
+/// Pipeline-wide tuning between latency and quality. Each profile sets the input
+/// jitterbuffer, the muxer batch timeout, the stage and branch queues, the encoder tuning and the
+/// display clock sync together, as environment defaults like a preset.
+pub const PROFILES: &[Preset] = &[
+    Preset {
+        name: "latency",
+        description: "Short jitterbuffer, partial batches after 10 ms, short leaky queues, fastest encoder preset",
+        settings: &[
+            ("RTSP_LATENCY", "100"),
+            ("RTSP_DROP_ON_LATENCY", "true"),
+            ("MUX_BATCH_TIMEOUT", "10000"),
+            ("STAGE_QUEUES", "*: max-size-buffers=2 max-size-bytes=0 max-size-time=0 leaky=downstream"),
+            ("SINK_QUEUE", "leaky=downstream max-size-buffers=1"),
+            ("ENCODER_TUNING", "latency"),
+            ("SINK_SYNC", "false"),
+        ],
+    },
+    Preset {
+        name: "balanced",
+        description: "Moderate jitterbuffer, one frame time to fill a batch, queues that only drop when far behind",
+        settings: &[
+            ("RTSP_LATENCY", "500"),
+            ("MUX_BATCH_TIMEOUT", "33000"),
+            ("SINK_QUEUE", "leaky=downstream max-size-buffers=30"),
+            ("SINK_SYNC", "false"),
+        ],
+    },
+    Preset {
+        name: "quality",
+        description: "Long jitterbuffer, full batches, 2 s of queueing without drops, slowest encoder preset, clock-synced displays",
+        settings: &[
+            ("RTSP_LATENCY", "2000"),
+            ("MUX_BATCH_TIMEOUT", "100000"),
+            ("STAGE_QUEUES", "*: max-size-buffers=30 max-size-bytes=0 max-size-time=0"),
+            ("SINK_QUEUE", "max-size-buffers=0 max-size-bytes=0 max-size-time=2000000000"),
+            ("ENCODER_TUNING", "quality"),
+            ("SINK_SYNC", "true"),
+        ],
+    },
+];
+
+/// Applies `--profile <name>` / PROFILE to every variable still unset, i.e. after the
+/// preset, so a preset's own choices (e.g. its RTSP_LATENCY) win over the profile's.
+pub fn apply() {
+    let Some(name) = presets::selected("--profile", "PROFILE") else { return };
+    let Some(profile) = PROFILES.iter().find(|p| p.name == name.trim()) else {
+        let names: Vec<&str> = PROFILES.iter().map(|p| p.name).collect();
+        eprintln!("Error: Unknown profile '{}' (expected one of {})", name, names.join(", "));
+        std::process::exit(2);
+    };
+    let overridden = presets::set_defaults(profile.settings);
+    if overridden.is_empty() {
+        println!("Using profile {}", profile.name);
+    } else {
+        println!("Using profile {} (overridden: {})", profile.name, overridden.join(", "));
+    }
+}
@@ -0,0 +1,164 @@
+use crate::clock::utc_datetime;
+use gstreamer::prelude::*;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Hits further apart than this start a new span
+const GAP_SECS: f64 = 2.0;
+/// Seconds of context kept before and after a span when it is exported
+const PAD_SECS: f64 = 1.0;
+
+/// A run of frames in one recording where a queried label was seen.
+struct Span {
+    recording: String,
+    start: f64,
+    end: f64,
+    utc_ms: u64,
+    hits: usize,
+    best: f32,
+}
+
+/// Spans of `labels` in one detections sidecar, in file order.
+fn spans(sidecar: &Path, recording: &str, labels: &[&str]) -> Vec<Span> {
+    let Ok(text) = fs::read_to_string(sidecar) else {
+        return Vec::new();
+    };
+    let mut spans: Vec<Span> = Vec::new();
+    // frame,file_seconds,utc_ms,source,label,confidence,...
+    for line in text.lines().skip(1) {
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() < 6 || !labels.contains(&fields[4]) {
+            continue;
+        }
+        let (Ok(seconds), Ok(utc_ms)) = (fields[1].parse::<f64>(), fields[2].parse::<u64>()) else {
+            continue;
+        };
+        let confidence = fields[5].parse::<f32>().unwrap_or(0.0);
+        match spans.last_mut() {
+            Some(span) if seconds - span.end <= GAP_SECS => {
+                span.end = span.end.max(seconds);
+                span.hits += 1;
+                span.best = span.best.max(confidence);
+            }
+            _ => spans.push(Span {
+                recording: recording.to_string(),
+                start: seconds,
+                end: seconds,
+                utc_ms,
+                hits: 1,
+                best: confidence,
+            }),
+        }
+    }
+    spans
+}
+
+/// Copies `start..end` seconds of an MP4 recording into `out` without re-encoding; the clip
+/// starts at the keyframe before `start`.
+fn export(recording: &str, start: f64, end: f64, out: &str) -> Result<(), String> {
+    let pipeline = gstreamer::parse_launch(&format!(
+        "filesrc location=\"{}\" ! qtdemux ! h264parse ! mp4mux ! filesink location=\"{}\"",
+        recording, out
+    ))
+    .map_err(|e| e.to_string())?;
+    let bus = pipeline.bus().expect("Pipeline should have a bus");
+    pipeline.set_state(gstreamer::State::Paused).map_err(|e| e.to_string())?;
+    let _ = pipeline.state(gstreamer::ClockTime::from_seconds(10));
+    pipeline
+        .seek(
+            1.0,
+            gstreamer::SeekFlags::FLUSH | gstreamer::SeekFlags::KEY_UNIT,
+            gstreamer::SeekType::Set,
+            gstreamer::ClockTime::from_mseconds((start * 1000.0) as u64),
+            gstreamer::SeekType::Set,
+            gstreamer::ClockTime::from_mseconds((end * 1000.0) as u64),
+        )
+        .map_err(|e| e.to_string())?;
+    pipeline.set_state(gstreamer::State::Playing).map_err(|e| e.to_string())?;
+    let result = loop {
+        let Some(msg) = bus.timed_pop(gstreamer::ClockTime::NONE) else { continue };
+        match msg.view() {
+            gstreamer::MessageView::Eos(..) => break Ok(()),
+            gstreamer::MessageView::Error(err) => break Err(err.error().to_string()),
+            _ => {}
+        }
+    };
+    pipeline
+        .set_state(gstreamer::State::Null)
+        .expect("Unable to set the export pipeline to the Null state");
+    result
+}
+
+/// `detect query <label>[,<label>...] [export <dir>]`: lists the spans of the recordings in
+/// RECORD_DIR (default /recordings) where one of the labels was detected, from the
+/// `<prefix>_<copy>.detections.csv` sidecars written while recording. With `export`, each span
+/// (plus a second either side) is cut into its own MP4 in `<dir>`.
+pub fn command(args: &[String]) -> ! {
+    let usage = || {
+        eprintln!("Usage: detect query <label>[,<label>...] [export <dir>]");
+        std::process::exit(2);
+    };
+    let Some(labels) = args.first() else { usage() };
+    let labels: Vec<&str> = labels.split(',').map(str::trim).filter(|l| !l.is_empty()).collect();
+    let export_dir = match (args.get(1).map(String::as_str), args.get(2)) {
+        (None, _) => None,
+        (Some("export"), Some(dir)) => Some(dir.clone()),
+        _ => usage(),
+    };
+
+    let dir = env::var("RECORD_DIR").unwrap_or_else(|_| "/recordings".to_string());
+    let mut sidecars: Vec<_> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.to_string_lossy().ends_with(".detections.csv"))
+            .collect(),
+        Err(e) => {
+            eprintln!("Error: Could not read RECORD_DIR {}: {}", dir, e);
+            std::process::exit(2);
+        }
+    };
+    sidecars.sort();
+    let found: Vec<Span> = sidecars
+        .iter()
+        .flat_map(|sidecar| {
+            let recording = sidecar.to_string_lossy().replace(".detections.csv", ".mp4");
+            spans(sidecar, &recording, &labels)
+        })
+        .collect();
+    if found.is_empty() {
+        println!("No {} in the recordings of {}", labels.join(", "), dir);
+        std::process::exit(1);
+    }
+
+    if let Some(out_dir) = &export_dir {
+        if let Err(e) = fs::create_dir_all(out_dir) {
+            eprintln!("Error: Could not create export directory {}: {}", out_dir, e);
+            std::process::exit(2);
+        }
+    }
+    let mut failed = 0;
+    for span in &found {
+        println!(
+            "{}  {:.1}s-{:.1}s  {}  ({} detections, best {:.2})",
+            span.recording,
+            span.start,
+            span.end,
+            utc_datetime(span.utc_ms / 1000),
+            span.hits,
+            span.best
+        );
+        let Some(out_dir) = &export_dir else { continue };
+        let stem = Path::new(&span.recording).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let out = format!("{}/{}_{}.mp4", out_dir.trim_end_matches('/'), stem, (span.start * 1000.0) as u64);
+        match export(&span.recording, (span.start - PAD_SECS).max(0.0), span.end + PAD_SECS, &out) {
+            Ok(()) => println!("✓ Exported {}", out),
+            Err(e) => {
+                eprintln!("Warning: Could not export {}: {}", out, e);
+                failed += 1;
+            }
+        }
+    }
+    println!("{} span{} of {}", found.len(), if found.len() == 1 { "" } else { "s" }, labels.join(", "));
+    std::process::exit(if failed == 0 { 0 } else { 1 });
+}
@@ -0,0 +1,157 @@
+use crate::detections::{FrameDetections, FrameObserver};
+use crate::events::{Event, Events};
+use crate::metrics::Metrics;
+use crate::zones::{self, Zone};
+use std::collections::{HashMap, VecDeque};
+use std::env;
+
+/// People who left the queue within this many most recent exits make up the wait estimate
+const WAIT_HISTORY: usize = 20;
+
+/// Tracks must be missing this long before they count as having left the queue
+const EXIT_GRACE_SECONDS: f64 = 2.0;
+
+/// Exits after less than this long in the queue are people passing through
+const MIN_DWELL_SECONDS: f64 = 3.0;
+
+pub struct QueueConfig {
+    pub queues: Vec<Zone>,
+    pub person_label: String,
+    /// Seconds between queue_status events
+    pub interval: f64,
+    pub max_length: Option<usize>,
+    pub max_wait: Option<f64>,
+}
+
+impl QueueConfig {
+    /// Reads QUEUE_ZONES (same syntax as ZONES) / QUEUE_PERSON_LABEL / QUEUE_INTERVAL /
+    /// QUEUE_MAX_LENGTH / QUEUE_MAX_WAIT. Exits on a malformed QUEUE_ZONES like the other
+    /// startup configuration.
+    pub fn from_env() -> Option<QueueConfig> {
+        let spec = env::var("QUEUE_ZONES").ok()?;
+        let queues = zones::parse_zones(&spec).unwrap_or_else(|e| {
+            eprintln!("Error: QUEUE_ZONES: {}", e);
+            std::process::exit(2);
+        });
+        let person_label = env::var("QUEUE_PERSON_LABEL").unwrap_or_else(|_| "person".to_string());
+        let interval = env::var("QUEUE_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0.0)
+            .unwrap_or(10.0);
+        let max_length = env::var("QUEUE_MAX_LENGTH").ok().and_then(|v| v.parse().ok());
+        let max_wait = env::var("QUEUE_MAX_WAIT").ok().and_then(|v| v.parse().ok());
+        Some(QueueConfig { queues, person_label, interval, max_length, max_wait })
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    // Track ID -> (entered, last seen inside)
+    waiting: HashMap<u64, (f64, f64)>,
+    /// Dwell times of the most recent exits
+    recent_waits: VecDeque<f64>,
+    over_limit: bool,
+    last_status: Option<f64>,
+}
+
+impl QueueState {
+    /// Mean wait of the recent exits; while nobody has left yet, the longest current wait.
+    fn estimated_wait(&self, now: f64) -> f64 {
+        if self.recent_waits.is_empty() {
+            return self.waiting.values().map(|(entered, _)| now - entered).fold(0.0, f64::max);
+        }
+        self.recent_waits.iter().sum::<f64>() / self.recent_waits.len() as f64
+    }
+}
+
+/// Queue length and estimated wait per queue zone, from track dwell times, with
+/// periodic queue_status events and queue_over_limit / queue_normal alerts.
+pub struct QueueMonitor {
+    config: QueueConfig,
+    person_class: Option<i32>,
+    events: Events,
+    metrics: Metrics,
+    // (source, queue index) -> state
+    states: HashMap<(u32, usize), QueueState>,
+}
+
+impl QueueMonitor {
+    pub fn new(config: QueueConfig, labels: &[String], events: Events, metrics: Metrics) -> QueueMonitor {
+        let person_class = labels.iter().position(|l| l.eq_ignore_ascii_case(&config.person_label)).map(|i| i as i32);
+        if person_class.is_none() {
+            eprintln!("Warning: QUEUE_PERSON_LABEL '{}' is not in the labels file", config.person_label);
+        }
+        {
+            let mut metrics = metrics.lock().unwrap();
+            metrics.describe("detect_queue_length", "gauge", "People in the queue zone");
+            metrics.describe("detect_queue_wait_seconds", "gauge", "Estimated wait in the queue zone");
+        }
+        QueueMonitor { config, person_class, events, metrics, states: HashMap::new() }
+    }
+}
+
+impl FrameObserver for QueueMonitor {
+    fn on_frame(&mut self, frame: &FrameDetections) {
+        let now = frame.seconds();
+        let (width, height) = (frame.pipeline_width.max(1) as f32, frame.pipeline_height.max(1) as f32);
+        let source = frame.source_id.to_string();
+
+        for (index, queue) in self.config.queues.iter().enumerate() {
+            let state = self.states.entry((frame.source_id, index)).or_default();
+            for det in &frame.objects {
+                let Some(track_id) = det.track_id else { continue };
+                if Some(det.class_id) != self.person_class {
+                    continue;
+                }
+                let (x, y) = ((det.left + det.width / 2.0) / width, (det.top + det.height) / height);
+                if queue.contains(x, y) {
+                    state.waiting.entry(track_id).or_insert((now, now)).1 = now;
+                }
+            }
+
+            let left: Vec<u64> = state
+                .waiting
+                .iter()
+                .filter(|(_, (_, seen))| now - seen > EXIT_GRACE_SECONDS)
+                .map(|(id, _)| *id)
+                .collect();
+            for id in left {
+                let (entered, seen) = state.waiting.remove(&id).unwrap_or_default();
+                if seen - entered >= MIN_DWELL_SECONDS {
+                    state.recent_waits.push_back(seen - entered);
+                    if state.recent_waits.len() > WAIT_HISTORY {
+                        state.recent_waits.pop_front();
+                    }
+                }
+            }
+
+            let length = state.waiting.len();
+            let wait = state.estimated_wait(now);
+            {
+                let mut metrics = self.metrics.lock().unwrap();
+                let labels = [("source", source.as_str()), ("queue", queue.name.as_str())];
+                metrics.set("detect_queue_length", &labels, length as f64);
+                metrics.set("detect_queue_wait_seconds", &labels, wait);
+            }
+
+            let mut events = self.events.lock().unwrap();
+            let event = |kind: &str| {
+                Event::new(kind, frame.source_id, frame.frame_num)
+                    .with("queue", queue.name.as_str())
+                    .with("length", length as u64)
+                    .with("wait_seconds", wait)
+            };
+            if state.last_status.is_none_or(|at| now - at >= self.config.interval) {
+                state.last_status = Some(now);
+                events.emit(event("queue_status"));
+            }
+            let over = self.config.max_length.is_some_and(|max| length > max)
+                || self.config.max_wait.is_some_and(|max| wait > max);
+            if over != state.over_limit {
+                state.over_limit = over;
+                events.emit(event(if over { "queue_over_limit" } else { "queue_normal" }));
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use crate::clock::utc_datetime;
+use crate::detections;
+use crate::gps::Gps;
+use crate::sinks::{Codec, EncodeSettings, Tuning};
+use gstreamer::prelude::*;
+use std::env;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Which copies of the stream get written to disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordMode {
+    /// Only the OSD output (boxes and labels burned in)
+    Annotated,
+    /// Only the inference input, without any overlay graphics
+    Clean,
+    /// Both copies, teed from the same buffers so they stay frame-aligned
+    Dual,
+}
+
+impl RecordMode {
+    fn parse(value: &str) -> Option<RecordMode> {
+        match value.trim().to_lowercase().as_str() {
+            "annotated" => Some(RecordMode::Annotated),
+            "clean" | "raw" => Some(RecordMode::Clean),
+            "dual" | "both" => Some(RecordMode::Dual),
+            _ => None,
+        }
+    }
+
+    pub fn records_clean(&self) -> bool {
+        matches!(self, RecordMode::Clean | RecordMode::Dual)
+    }
+
+    pub fn records_annotated(&self) -> bool {
+        matches!(self, RecordMode::Annotated | RecordMode::Dual)
+    }
+}
+
+pub struct RecordConfig {
+    pub mode: RecordMode,
+    pub dir: String,
+    pub prefix: String,
+    pub bitrate: u32,
+    /// Wall-clock start (unix seconds), written into the files' metadata
+    pub started: u64,
+    /// Copies encoded on the CPU because NVENC had no session left for them
+    pub software: Vec<&'static str>,
+}
+
+impl RecordConfig {
+    /// Reads RECORD_MODE / RECORD_DIR / RECORD_PREFIX / RECORD_BITRATE.
+    /// Returns None when recording is disabled (RECORD_MODE unset or "off"). Exits with
+    /// several inputs: the copies are taken after the tiler, and a tiled "clean" file is
+    /// no camera's own recording.
+    pub fn from_env(inputs: usize) -> Option<RecordConfig> {
+        let mode_str = env::var("RECORD_MODE").ok()?;
+        if mode_str.trim().is_empty() || mode_str == "off" {
+            return None;
+        }
+        if inputs > 1 {
+            eprintln!("Error: RECORD_MODE needs a single input, {} were given (run one detect per camera)", inputs);
+            std::process::exit(2);
+        }
+        let mode = match RecordMode::parse(&mode_str) {
+            Some(mode) => mode,
+            None => {
+                eprintln!(
+                    "Warning: Unknown RECORD_MODE '{}' (expected annotated, clean or dual). Recording disabled.",
+                    mode_str
+                );
+                return None;
+            }
+        };
+
+        let dir = env::var("RECORD_DIR").unwrap_or_else(|_| "/recordings".to_string());
+        // Both files of a dual recording share one prefix so they can be paired later
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let prefix = env::var("RECORD_PREFIX").unwrap_or_else(|_| format!("detect_{}", started));
+        let bitrate = env::var("RECORD_BITRATE")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(8000000);
+
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            eprintln!("Warning: Could not create recording directory {}: {}", dir, e);
+        }
+
+        Some(RecordConfig { mode, dir, prefix, bitrate, started, software: Vec::new() })
+    }
+
+    /// Recording of one copy (`clean` or `annotated`).
+    pub fn path(&self, copy: &str) -> String {
+        format!("{}/{}_{}.mp4", self.dir.trim_end_matches('/'), self.prefix, copy)
+    }
+
+    pub fn clean_path(&self) -> String {
+        self.path("clean")
+    }
+
+    pub fn annotated_path(&self) -> String {
+        self.path("annotated")
+    }
+
+    /// Frame index next to the recording: one `frame,file_seconds,utc_ms` line per frame.
+    pub fn index_path(&self, copy: &str) -> String {
+        format!("{}/{}_{}.index.csv", self.dir.trim_end_matches('/'), self.prefix, copy)
+    }
+
+    /// Detections seen in the recording: one
+    /// `frame,file_seconds,utc_ms,source,label,confidence,track_id,left,top,width,height` line per object,
+    /// read back by `detect query`.
+    pub fn detections_path(&self, copy: &str) -> String {
+        format!("{}/{}_{}.detections.csv", self.dir.trim_end_matches('/'), self.prefix, copy)
+    }
+
+    /// Route of a mobile camera during the recording: one
+    /// `frame,file_seconds,utc_ms,lat,lon,altitude_m,speed_kmh,course` line per GPS update.
+    pub fn gps_path(&self, copy: &str) -> String {
+        format!("{}/{}_{}.gps.csv", self.dir.trim_end_matches('/'), self.prefix, copy)
+    }
+
+    /// Encoder + muxer branch for one copy ending in a filesink, meant to hang off a tee pad.
+    /// Fragmented MP4 keeps the file playable if the process is killed mid-recording. The
+    /// start time goes into the MP4 date tag, and `record_index_<copy>` feeds the frame index.
+    pub fn file_branch(&self, copy: &str) -> String {
+        let encoder = EncodeSettings {
+            tuning: Tuning::from_env(),
+            software: self.software.contains(&copy),
+            ..EncodeSettings::new(Codec::H264, &self.bitrate.to_string())
+        };
+        format!(
+            "queue ! identity name=record_index_{} silent=true ! {} ! \
+             taginject tags=\"datetime=(datetime){}\" ! mp4mux fragment-duration=1000 ! filesink location={} sync=false",
+            copy,
+            encoder.chain(),
+            utc_datetime(self.started),
+            self.path(copy)
+        )
+    }
+}
+
+/// Runs the pipeline on the realtime system clock, so base time + running time is UTC
+/// (as accurate as the host's NTP/PTP sync) and recordings line up with event timestamps.
+pub fn use_utc_clock(pipeline: &gstreamer::Element) {
+    let clock = glib::Object::builder::<gstreamer::SystemClock>()
+        .property("clock-type", gstreamer::ClockType::Realtime)
+        .build();
+    if let Some(pipeline) = pipeline.downcast_ref::<gstreamer::Pipeline>() {
+        pipeline.use_clock(Some(&clock));
+    }
+}
+
+fn create(path: &str, header: &str) -> Option<LineWriter<File>> {
+    match File::create(path) {
+        Ok(file) => {
+            let mut file = LineWriter::new(file);
+            let _ = writeln!(file, "{}", header);
+            Some(file)
+        }
+        Err(e) => {
+            eprintln!("Warning: Could not create recording index {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Writes each recorded copy's frame index: the frame's position in the file and its UTC
+/// capture time, taken from the frames entering the copy's encoder. The objects on those
+/// frames go to the copy's detections sidecar with the same times, so they line up exactly
+/// with the file, and so do the GPS positions when GPS_SOURCE is set.
+pub fn attach_index(
+    pipeline: &gstreamer::Element,
+    config: &RecordConfig,
+    labels: Vec<String>,
+    mux_width: u32,
+    mux_height: u32,
+    gps: Option<Gps>,
+) {
+    let copies = [("clean", config.mode.records_clean()), ("annotated", config.mode.records_annotated())];
+    for (copy, _) in copies.iter().filter(|(_, recorded)| *recorded) {
+        let name = format!("record_index_{}", copy);
+        let Some(element) = pipeline.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name(&name)) else {
+            continue;
+        };
+        let Some(file) = create(&config.index_path(copy), "frame,file_seconds,utc_ms") else {
+            continue;
+        };
+        let sidecar = create(
+            &config.detections_path(copy),
+            "frame,file_seconds,utc_ms,source,label,confidence,track_id,left,top,width,height",
+        );
+        // (writer, frames so far, running time of the first frame)
+        let state = Mutex::new((file, 0u64, None::<gstreamer::ClockTime>));
+        let detections = Mutex::new(sidecar);
+        // (writer, time of the last fix written)
+        let route = gps.clone().and_then(|gps| {
+            let file = create(&config.gps_path(copy), "frame,file_seconds,utc_ms,lat,lon,altitude_m,speed_kmh,course")?;
+            Some((gps, Mutex::new((file, None::<std::time::Instant>))))
+        });
+        let labels = labels.clone();
+        let pipeline = pipeline.clone();
+        let src_pad = element.static_pad("src").expect("identity has a src pad");
+        src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |pad, info| {
+            let Some((buffer, batch)) = nvds::probe_buffer(info) else {
+                return gstreamer::PadProbeReturn::Ok;
+            };
+            let Some(segment) = pad.sticky_event::<gstreamer::event::Segment>(0) else {
+                return gstreamer::PadProbeReturn::Ok;
+            };
+            let Some(segment) = segment.segment().downcast_ref::<gstreamer::ClockTime>() else {
+                return gstreamer::PadProbeReturn::Ok;
+            };
+            let (Some(running_time), Some(base_time)) = (segment.to_running_time(buffer.pts()), pipeline.base_time()) else {
+                return gstreamer::PadProbeReturn::Ok;
+            };
+            let mut state = state.lock().unwrap();
+            let first = *state.2.get_or_insert(running_time);
+            let frame = state.1;
+            state.1 += 1;
+            let file_seconds = running_time.saturating_sub(first).nseconds() as f64 / 1e9;
+            let utc_ms = (base_time + running_time).mseconds();
+            let _ = writeln!(state.0, "{},{:.3},{}", frame, file_seconds, utc_ms);
+            if let Some((gps, route)) = &route {
+                let mut route = route.lock().unwrap();
+                if let Some(fix) = gps.current().filter(|fix| route.1 != Some(fix.at)) {
+                    route.1 = Some(fix.at);
+                    let optional = |v: Option<f64>| v.map(|v| format!("{:.1}", v)).unwrap_or_default();
+                    let _ = writeln!(
+                        route.0,
+                        "{},{:.3},{},{:.7},{:.7},{},{},{}",
+                        frame,
+                        file_seconds,
+                        utc_ms,
+                        fix.lat,
+                        fix.lon,
+                        optional(fix.altitude_m),
+                        optional(fix.speed_kmh),
+                        optional(fix.course)
+                    );
+                }
+            }
+            if let (Some(sidecar), Some(batch)) = (detections.lock().unwrap().as_mut(), batch) {
+                for frame_detections in detections::collect(batch, mux_width, mux_height) {
+                    for det in &frame_detections.objects {
+                        let label = labels.get(det.class_id as usize).cloned().unwrap_or_else(|| det.class_id.to_string());
+                        let track = det.track_id.map(|id| id.to_string()).unwrap_or_default();
+                        let _ = writeln!(
+                            sidecar,
+                            "{},{:.3},{},{},{},{:.3},{},{:.0},{:.0},{:.0},{:.0}",
+                            frame,
+                            file_seconds,
+                            utc_ms,
+                            frame_detections.source_id,
+                            label,
+                            det.confidence,
+                            track,
+                            det.left,
+                            det.top,
+                            det.width,
+                            det.height
+                        );
+                    }
+                }
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+    }
+}
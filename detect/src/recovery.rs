@@ -0,0 +1,448 @@
+use crate::source::redact_credentials;
+use gstreamer::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Repeats of the same message within this window are counted instead of printed
+const REPORT_INTERVAL: Duration = Duration::from_secs(30);
+/// Restarts allowed per element within RESTART_WINDOW before its errors become fatal
+const MAX_RESTARTS: usize = 5;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+/// Longest output branch followed downstream from a tee
+const MAX_BRANCH_ELEMENTS: usize = 16;
+/// Seconds between attempts to bring back a rebuilt branch that can't start, doubling up to the max
+const FIRST_RETRY_SECS: u32 = 2;
+const MAX_RETRY_SECS: u32 = 60;
+
+/// What kind of element an error came from, by the factory klass of it or its nearest bin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Source,
+    Decoder,
+    Sink,
+    Other,
+}
+
+impl Category {
+    fn of(element: &gstreamer::Element) -> Category {
+        let klass = element.factory().map(|f| f.klass().to_string()).unwrap_or_default();
+        if klass.contains("Decoder") {
+            Category::Decoder
+        } else if klass.contains("Sink") {
+            Category::Sink
+        } else if klass.contains("Source") {
+            Category::Source
+        } else {
+            Category::Other
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Category::Source => "source",
+            Category::Decoder => "decoder",
+            Category::Sink => "sink",
+            Category::Other => "other",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// Rebuild the failed output branch, or set the failed element to NULL and back, while the
+    /// rest keeps running
+    Restart,
+    /// Report the error and carry on
+    Ignore,
+    /// Stop the pipeline, as without a policy
+    Fail,
+}
+
+impl Policy {
+    fn parse(value: &str) -> Option<Policy> {
+        match value.trim() {
+            "restart" => Some(Policy::Restart),
+            "ignore" => Some(Policy::Ignore),
+            "fail" => Some(Policy::Fail),
+            _ => None,
+        }
+    }
+}
+
+/// Per-category (`source`, `decoder`, `sink`, `other`) or per-element-name recovery rules.
+pub struct RecoveryConfig {
+    pub rules: Vec<(String, Policy)>,
+}
+
+impl RecoveryConfig {
+    /// Reads ERROR_POLICY: `;`-separated `<category or element name>=<restart|ignore|fail>`,
+    /// e.g. `sink=restart;decoder=fail;rtmp_out=ignore`. Output branches restart by default,
+    /// and with `restart_sources` (drone ingest) so do sources; anything without a rule fails.
+    pub fn from_env(restart_sources: bool) -> RecoveryConfig {
+        let mut rules = Vec::new();
+        for entry in env::var("ERROR_POLICY").unwrap_or_default().split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once('=').and_then(|(key, policy)| Some((key.trim(), Policy::parse(policy)?))) {
+                Some((key, policy)) => rules.push((key.to_string(), policy)),
+                None => {
+                    eprintln!("Error: Invalid ERROR_POLICY entry '{}' (expected <category or element>=restart|ignore|fail)", entry);
+                    std::process::exit(2);
+                }
+            }
+        }
+        let defaults = if restart_sources { &["sink", "source"][..] } else { &["sink"][..] };
+        for key in defaults {
+            if !rules.iter().any(|(k, _)| k == key) {
+                rules.push((key.to_string(), Policy::Restart));
+            }
+        }
+        RecoveryConfig { rules }
+    }
+
+    /// The rule of the first element (innermost first) named in the rules, else of the category.
+    fn policy(&self, names: &[String], category: Category) -> Policy {
+        let rule = |key: &str| self.rules.iter().find(|(k, _)| k == key).map(|(_, p)| *p);
+        names.iter().find_map(|name| rule(name)).or_else(|| rule(category.name())).unwrap_or(Policy::Fail)
+    }
+
+    pub fn describe(&self) -> String {
+        let rules: Vec<String> = self.rules.iter().map(|(key, policy)| format!("{}={:?}", key, policy).to_lowercase()).collect();
+        rules.join(", ")
+    }
+}
+
+struct Seen {
+    suppressed: u64,
+    last_report: Instant,
+}
+
+/// Handles error and warning messages from the bus: categorizes each error by the element
+/// that posted it, applies its policy, and prints each distinct message once per
+/// REPORT_INTERVAL with a count of the repeats in between.
+pub struct BusHandler {
+    config: RecoveryConfig,
+    pipeline: gstreamer::Element,
+    branches: Branches,
+    pending: Vec<Pending>,
+    /// Branches rebuilt so far, numbering the files rebuilt file outputs continue in
+    rebuilds: usize,
+    seen: HashMap<String, Seen>,
+    restarts: HashMap<String, Vec<Instant>>,
+}
+
+impl BusHandler {
+    /// Finds the output branches of `pipeline`'s tees and guards them: as soon as an element of
+    /// a branch posts an error (on the bus's sync handler, still in the failing thread), the
+    /// branch stops taking buffers, so the tee doesn't pass the flow error on to the main path.
+    pub fn new(config: RecoveryConfig, pipeline: &gstreamer::Element) -> BusHandler {
+        let branches: Branches = Arc::new(Mutex::new(Vec::new()));
+        let bin = pipeline.downcast_ref::<gstreamer::Bin>().expect("pipeline is a bin");
+        for tee in bin.children().iter().filter(|e| e.factory().is_some_and(|f| f.name() == "tee")) {
+            for pad in tee.src_pads() {
+                if let Some(elements) = follow(&pad) {
+                    branches.lock().unwrap().push(Branch::guard(tee.clone(), elements));
+                }
+            }
+        }
+        let failing = branches.clone();
+        pipeline.bus().expect("Pipeline should have a bus").set_sync_handler(move |_bus, msg| {
+            if let gstreamer::MessageView::Error(err) = msg.view() {
+                let mut object = err.src().cloned();
+                while let Some(current) = object {
+                    if let Some(branch) = failing.lock().unwrap().iter().find(|b| b.contains(&current)) {
+                        branch.failed.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                    object = current.parent();
+                }
+            }
+            gstreamer::BusSyncReply::Pass
+        });
+        BusHandler {
+            config,
+            pipeline: pipeline.clone(),
+            branches,
+            pending: Vec::new(),
+            rebuilds: 0,
+            seen: HashMap::new(),
+            restarts: HashMap::new(),
+        }
+    }
+
+    fn bin(&self) -> gstreamer::Bin {
+        self.pipeline.clone().downcast::<gstreamer::Bin>().expect("pipeline is a bin")
+    }
+
+    /// Builds the torn-down branches whose retry is due. One that can't start (e.g. its display
+    /// is gone) is tried again later, with the wait doubling up to MAX_RETRY_SECS. Called from
+    /// the bus loop.
+    pub fn rebuild_due(&mut self) {
+        let now = Instant::now();
+        let bin = self.bin();
+        for mut pending in std::mem::take(&mut self.pending) {
+            if pending.due > now {
+                self.pending.push(pending);
+                continue;
+            }
+            match build(&bin, &pending.tee, &pending.templates, pending.attempt) {
+                Ok(elements) => {
+                    let name = elements.last().map(|e| e.name().to_string()).unwrap_or_default();
+                    println!("✓ Output branch {} is running again", name);
+                    self.branches.lock().unwrap().push(Branch::guard(pending.tee, elements));
+                }
+                Err(e) => {
+                    pending.delay = (pending.delay * 2).clamp(FIRST_RETRY_SECS, MAX_RETRY_SECS);
+                    pending.due = now + Duration::from_secs(pending.delay as u64);
+                    eprintln!("Warning: Could not rebuild output branch ({}), retrying in {}s", e, pending.delay);
+                    self.pending.push(pending);
+                }
+            }
+        }
+    }
+
+    /// Prints `message` unless it was printed within REPORT_INTERVAL.
+    fn report(&mut self, key: String, message: &str) {
+        let now = Instant::now();
+        if let Some(seen) = self.seen.get_mut(&key) {
+            if now.duration_since(seen.last_report) < REPORT_INTERVAL {
+                seen.suppressed += 1;
+                return;
+            }
+            if seen.suppressed > 0 {
+                eprintln!("{} (repeated {} times)", redact_credentials(message), seen.suppressed);
+            } else {
+                eprintln!("{}", redact_credentials(message));
+            }
+            seen.suppressed = 0;
+            seen.last_report = now;
+            return;
+        }
+        eprintln!("{}", redact_credentials(message));
+        self.seen.insert(key, Seen { suppressed: 0, last_report: now });
+    }
+
+    pub fn warning(&mut self, warning: &gstreamer::message::Warning) {
+        let path = warning.src().map(|s| s.path_string().to_string()).unwrap_or_default();
+        let message = format!("Warning from {}: {} ({:?})", path, warning.error(), warning.debug());
+        self.report(format!("{}|{}", path, warning.error()), &message);
+    }
+
+    /// Reports the error and returns what the pipeline should do about it; Restart has been
+    /// carried out already when returned.
+    pub fn error(&mut self, err: &gstreamer::message::Error) -> Policy {
+        let pipeline = self.pipeline.clone();
+        let path = err.src().map(|s| s.path_string().to_string()).unwrap_or_default();
+        let message = format!("Error from {:?}: {} ({:?})", err.src().map(|s| s.path_string()), err.error(), err.debug());
+        self.report(format!("{}|{}", path, err.error()), &message);
+
+        // The posting element and the bins around it, innermost first
+        let mut chain: Vec<gstreamer::Element> = Vec::new();
+        let mut object = err.src().cloned();
+        while let Some(current) = object {
+            if &current == pipeline.upcast_ref::<gstreamer::Object>() {
+                break;
+            }
+            if let Some(element) = current.downcast_ref::<gstreamer::Element>() {
+                chain.push(element.clone());
+            }
+            object = current.parent();
+        }
+        let Some(top) = chain.last().cloned() else { return Policy::Fail };
+        // Left over from a branch that was torn down since
+        if top.parent().is_none() {
+            return Policy::Ignore;
+        }
+        let branch = self.branches.lock().unwrap().iter().position(|b| b.elements.contains(&top));
+        // Anything failing in an output branch (its encoder, payloader, ...) takes the branch down
+        let category = match branch {
+            Some(_) => Category::Sink,
+            None => chain.iter().map(Category::of).find(|c| *c != Category::Other).unwrap_or(Category::Other),
+        };
+        let names: Vec<String> = chain.iter().map(|e| e.name().to_string()).collect();
+        match self.config.policy(&names, category) {
+            Policy::Restart => {
+                let now = Instant::now();
+                let restarts = self.restarts.entry(top.name().to_string()).or_default();
+                restarts.retain(|at| now.duration_since(*at) < RESTART_WINDOW);
+                if restarts.len() >= MAX_RESTARTS {
+                    eprintln!("Error: {} failed {} times within {}s, giving up", top.name(), MAX_RESTARTS, RESTART_WINDOW.as_secs());
+                    return Policy::Fail;
+                }
+                restarts.push(now);
+                if let Some(index) = branch {
+                    // Replaced by a fresh copy while the rest of the pipeline keeps running
+                    self.rebuilds += 1;
+                    let attempt = self.rebuilds;
+                    let branch = self.branches.lock().unwrap().remove(index);
+                    let names: Vec<String> = branch.elements.iter().map(|e| e.name().to_string()).collect();
+                    eprintln!("Rebuilding output branch: {}", names.join(" ! "));
+                    let templates: Vec<Template> = branch.elements.iter().filter_map(Template::of).collect();
+                    tear_down(&self.bin(), &branch.tee, &branch.elements);
+                    self.pending.push(Pending { tee: branch.tee, templates, attempt, delay: 0, due: now });
+                    self.rebuild_due();
+                    return Policy::Restart;
+                }
+                eprintln!("Restarting {} {}", category.name(), top.name());
+                let _ = top.set_state(gstreamer::State::Null);
+                if top.sync_state_with_parent().is_err() {
+                    eprintln!("Error: Could not restart {}", top.name());
+                    return Policy::Fail;
+                }
+                Policy::Restart
+            }
+            policy => policy,
+        }
+    }
+}
+
+type Branches = Arc<Mutex<Vec<Branch>>>;
+
+/// An output branch: the elements from a tee's src pad down to a sink, upstream first.
+struct Branch {
+    tee: gstreamer::Element,
+    elements: Vec<gstreamer::Element>,
+    /// Set when one of the elements posted an error; buffers are dropped at the branch entry from then on
+    failed: Arc<AtomicBool>,
+}
+
+impl Branch {
+    fn guard(tee: gstreamer::Element, elements: Vec<gstreamer::Element>) -> Branch {
+        let failed = Arc::new(AtomicBool::new(false));
+        let dropping = failed.clone();
+        if let Some(pad) = elements.first().and_then(|e| e.sink_pads().into_iter().next()) {
+            pad.add_probe(gstreamer::PadProbeType::BUFFER | gstreamer::PadProbeType::BUFFER_LIST, move |_pad, _info| {
+                if dropping.load(Ordering::Relaxed) {
+                    gstreamer::PadProbeReturn::Drop
+                } else {
+                    gstreamer::PadProbeReturn::Ok
+                }
+            });
+        }
+        Branch { tee, elements, failed }
+    }
+
+    /// Whether `object` is one of the branch's elements.
+    fn contains(&self, object: &gstreamer::Object) -> bool {
+        self.elements.iter().any(|e| e.upcast_ref::<gstreamer::Object>() == object)
+    }
+}
+
+/// The elements from `tee_pad` to the sink it ends in, if the path is a plain chain.
+fn follow(tee_pad: &gstreamer::Pad) -> Option<Vec<gstreamer::Element>> {
+    let mut elements = Vec::new();
+    let mut pad = tee_pad.peer()?;
+    loop {
+        let element = pad.parent_element()?;
+        if element.factory().is_some_and(|f| f.name() == "tee") || elements.len() >= MAX_BRANCH_ELEMENTS {
+            return None;
+        }
+        elements.push(element.clone());
+        match element.src_pads().as_slice() {
+            [] => return Some(elements),
+            [src] => pad = src.peer()?,
+            _ => return None,
+        }
+    }
+}
+
+/// What it takes to create an element again: its factory, name and property values.
+struct Template {
+    factory: gstreamer::ElementFactory,
+    name: String,
+    properties: Vec<(String, glib::Value)>,
+}
+
+impl Template {
+    fn of(element: &gstreamer::Element) -> Option<Template> {
+        let properties = element
+            .list_properties()
+            .iter()
+            .filter(|p| p.flags().contains(glib::ParamFlags::READWRITE) && !matches!(p.name(), "name" | "parent"))
+            .map(|p| (p.name().to_string(), element.property_value(p.name())))
+            .collect();
+        Some(Template { factory: element.factory()?, name: element.name().to_string(), properties })
+    }
+
+    /// A fresh element with the same settings. A file an output was writing is kept: the
+    /// new one writes `<name>_<attempt>.<ext>` next to it.
+    fn create(&self, attempt: usize) -> Result<gstreamer::Element, glib::BoolError> {
+        let mut builder = self.factory.create().name(self.name.as_str());
+        for (name, value) in &self.properties {
+            let value = match (name.as_str(), value.get::<String>()) {
+                ("location", Ok(location)) if self.factory.name() == "filesink" => {
+                    let file = match location.rsplit_once('.') {
+                        Some((stem, ext)) if !ext.contains('/') => format!("{}_{}.{}", stem, attempt, ext),
+                        _ => format!("{}_{}", location, attempt),
+                    };
+                    file.to_value()
+                }
+                _ => value.clone(),
+            };
+            builder = builder.property(name, value);
+        }
+        builder.build()
+    }
+}
+
+/// Unlinks a branch from its tee and removes its elements.
+fn tear_down(bin: &gstreamer::Bin, tee: &gstreamer::Element, elements: &[gstreamer::Element]) {
+    let entry = elements.first().and_then(|e| e.sink_pads().into_iter().next());
+    if let Some(tee_pad) = entry.as_ref().and_then(|pad| pad.peer()) {
+        if let Some(entry) = &entry {
+            let _ = tee_pad.unlink(entry);
+        }
+        tee.release_request_pad(&tee_pad);
+    }
+    for element in elements.iter().rev() {
+        let _ = element.set_state(gstreamer::State::Null);
+        let _ = bin.remove(element);
+    }
+}
+
+/// Builds the branch again from `templates` on a new pad of `tee` and starts it.
+fn build(
+    bin: &gstreamer::Bin,
+    tee: &gstreamer::Element,
+    templates: &[Template],
+    attempt: usize,
+) -> Result<Vec<gstreamer::Element>, String> {
+    let elements = templates.iter().map(|t| t.create(attempt)).collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+    let refs: Vec<&gstreamer::Element> = elements.iter().collect();
+    bin.add_many(&refs).map_err(|e| e.to_string())?;
+    let linked = gstreamer::Element::link_many(&refs).map_err(|e| e.to_string()).and_then(|_| {
+        let tee_pad = tee.request_pad_simple("src_%u").ok_or("tee gave no pad")?;
+        let linked = match elements[0].sink_pads().into_iter().next() {
+            Some(entry) => tee_pad.link(&entry).map(|_| ()).map_err(|e| format!("{:?}", e)),
+            None => Err("branch has no sink pad".to_string()),
+        };
+        if linked.is_err() {
+            tee.release_request_pad(&tee_pad);
+        }
+        linked
+    });
+    // Sink first, so nothing pushes into an element that isn't running yet
+    let started = linked.and_then(|_| {
+        elements.iter().rev().try_for_each(|e| {
+            e.sync_state_with_parent().map_err(|_| format!("{} did not start", e.name()))
+        })
+    });
+    match started {
+        Ok(()) => Ok(elements),
+        Err(e) => {
+            tear_down(bin, tee, &elements);
+            Err(e)
+        }
+    }
+}
+
+/// A torn-down branch waiting to be built again.
+struct Pending {
+    tee: gstreamer::Element,
+    templates: Vec<Template>,
+    attempt: usize,
+    /// Seconds waited before this attempt, doubled after each failure
+    delay: u32,
+    due: Instant,
+}
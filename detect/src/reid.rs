@@ -0,0 +1,206 @@
+use crate::events::{Event, Events};
+use crate::json::Value;
+use gstreamer::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Local tracks not seen for this long lose their global ID assignment
+const TRACK_TIMEOUT_SECONDS: f64 = 10.0;
+
+/// Cross-camera re-identification: a secondary nvinfer runs a ReID model on every tracked
+/// object, and the embeddings link tracks across the sources of this process to one global ID.
+pub struct ReidConfig {
+    /// nvinfer config of the embedding model (network-type=100, operates on the PGIE objects)
+    pub model_config: String,
+    pub unique_id: u32,
+    /// Cosine similarity at or above which two tracks are the same identity
+    pub threshold: f32,
+    /// Seconds an identity is remembered after it was last seen
+    pub max_age: f64,
+}
+
+impl ReidConfig {
+    /// Reads REID_CONFIG / REID_UNIQUE_ID / REID_THRESHOLD / REID_MAX_AGE.
+    pub fn from_env() -> Option<ReidConfig> {
+        let model_config = env::var("REID_CONFIG").ok()?;
+        let unique_id = env::var("REID_UNIQUE_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(3);
+        let threshold = env::var("REID_THRESHOLD").ok().and_then(|v| v.parse().ok()).unwrap_or(0.6);
+        let max_age = env::var("REID_MAX_AGE").ok().and_then(|v| v.parse().ok()).unwrap_or(600.0);
+        Some(ReidConfig { model_config, unique_id, threshold, max_age })
+    }
+
+    pub fn stage(&self) -> String {
+        format!(
+            "nvinfer name=reid process-mode=2 config-file-path={} unique-id={} output-tensor-meta=true",
+            self.model_config, self.unique_id
+        )
+    }
+}
+
+struct Identity {
+    id: u64,
+    /// Running average of the track embeddings, L2-normalized
+    embedding: Vec<f32>,
+    source_id: u32,
+    last_seen: f64,
+}
+
+/// Gallery of identities seen on any source.
+struct ReidMatcher {
+    config: ReidConfig,
+    events: Events,
+    identities: Vec<Identity>,
+    // (source, local track ID) -> (global ID, last seen)
+    tracks: HashMap<(u32, u64), (u64, f64)>,
+    next_id: u64,
+}
+
+/// L2-normalized copy, so dot products are cosine similarities.
+pub fn normalized(v: &[f32]) -> Vec<f32> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm <= f32::EPSILON {
+        return v.to_vec();
+    }
+    v.iter().map(|x| x / norm).collect()
+}
+
+/// Cosine similarity of two normalized embeddings.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+impl ReidMatcher {
+    fn global_id(&self, source_id: u32, track_id: u64) -> Option<u64> {
+        self.tracks.get(&(source_id, track_id)).map(|(id, _)| *id)
+    }
+
+    /// Assigns (or refreshes) the global ID of a local track from its latest embedding.
+    fn on_embedding(
+        &mut self,
+        source_id: u32,
+        frame_num: i32,
+        now: f64,
+        track_id: u64,
+        label: &str,
+        embedding: &[f32],
+    ) -> u64 {
+        let embedding = normalized(embedding);
+
+        if let Some((id, last_seen)) = self.tracks.get_mut(&(source_id, track_id)) {
+            *last_seen = now;
+            let id = *id;
+            if let Some(identity) = self.identities.iter_mut().find(|i| i.id == id) {
+                if identity.embedding.len() == embedding.len() {
+                    let blended: Vec<f32> = identity
+                        .embedding
+                        .iter()
+                        .zip(&embedding)
+                        .map(|(old, new)| 0.9 * old + 0.1 * new)
+                        .collect();
+                    identity.embedding = normalized(&blended);
+                }
+                identity.source_id = source_id;
+                identity.last_seen = now;
+            }
+            return id;
+        }
+
+        // Identities still visible on this source belong to other tracks here
+        let active: Vec<u64> = self
+            .tracks
+            .iter()
+            .filter(|((source, _), (_, seen))| *source == source_id && now - seen < 1.0)
+            .map(|(_, (id, _))| *id)
+            .collect();
+        let best = self
+            .identities
+            .iter()
+            .filter(|i| i.embedding.len() == embedding.len() && !active.contains(&i.id))
+            .map(|i| (i, cosine(&i.embedding, &embedding)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        let mut event = Event::new("track_identified", source_id, frame_num)
+            .with("track_id", track_id)
+            .with("label", label);
+        let id = match best {
+            Some((identity, similarity)) if similarity >= self.config.threshold => {
+                event = event
+                    .with("matched", true)
+                    .with("similarity", similarity)
+                    .with("previous_source_id", identity.source_id as u64);
+                identity.id
+            }
+            _ => {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.identities.push(Identity { id, embedding: embedding.clone(), source_id, last_seen: now });
+                event = event.with("matched", false);
+                id
+            }
+        };
+        self.tracks.insert((source_id, track_id), (id, now));
+        let vector = Value::Array(embedding.iter().map(|v| Value::from(*v)).collect());
+        self.events.lock().unwrap().emit(event.with("global_id", id).with("embedding", vector));
+        id
+    }
+
+    fn expire(&mut self, now: f64) {
+        let max_age = self.config.max_age;
+        self.identities.retain(|i| now - i.last_seen <= max_age);
+        self.tracks.retain(|_, (_, seen)| now - *seen <= TRACK_TIMEOUT_SECONDS);
+    }
+}
+
+/// Matches the ReID embeddings of every tracked object after the reid nvinfer and adds
+/// the global ID to the object's OSD label. Needs TRACKER_CONFIG.
+pub fn attach(bin: &gstreamer::Element, config: ReidConfig, events: Events) {
+    let reid = match bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("reid")) {
+        Some(reid) => reid,
+        None => {
+            eprintln!("Warning: reid nvinfer not found, re-identification disabled");
+            return;
+        }
+    };
+    let src_pad = reid.static_pad("src").expect("nvinfer has a src pad");
+    let unique_id = config.unique_id;
+    // Wall-clock time, since PTS of different sources aren't comparable
+    let started = Instant::now();
+    let matcher = Mutex::new(ReidMatcher {
+        config,
+        events,
+        identities: Vec::new(),
+        tracks: HashMap::new(),
+        next_id: 1,
+    });
+
+    src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            let mut matcher = matcher.lock().unwrap();
+            let now = started.elapsed().as_secs_f64();
+            for frame in batch.frames() {
+                let (source_id, frame_num) = (frame.source_id, frame.frame_num);
+                for obj in frame.objects() {
+                    let Some(track_id) = obj.track_id() else { continue };
+                    let label = obj.label();
+                    let embedding = obj
+                        .tensor_meta(unique_id)
+                        .and_then(|t| t.float_output(0))
+                        .map(|(_, v)| v.to_vec());
+                    let id = match embedding {
+                        Some(embedding) => {
+                            Some(matcher.on_embedding(source_id, frame_num, now, track_id, &label, &embedding))
+                        }
+                        None => matcher.global_id(source_id, track_id),
+                    };
+                    if let Some(id) = id {
+                        obj.append_display_text(&format!(" G{}", id));
+                    }
+                }
+            }
+            matcher.expire(now);
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
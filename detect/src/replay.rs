@@ -0,0 +1,114 @@
+use crate::detections::{FrameDetections, FrameObserver};
+use crate::json::Value;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Replay mode for debugging analytics on recorded files: the pipeline starts paused and
+/// advances only when stepped from the control API, batches wait for every source and
+/// no frame is ever dropped, so the tracker sees exactly the same input on every run.
+pub struct ReplayConfig {
+    /// Directory for the per-frame metadata dump (replay_src<N>.jsonl)
+    pub dump_dir: Option<String>,
+}
+
+impl ReplayConfig {
+    /// Reads REPLAY=true / REPLAY_DUMP_DIR. Exits when an input isn't a local file, since
+    /// live sources can't be replayed deterministically.
+    pub fn from_env(devices: &[String]) -> Option<ReplayConfig> {
+        if env::var("REPLAY").map(|v| v != "true").unwrap_or(true) {
+            return None;
+        }
+        let is_file = |d: &&String| d.ends_with(".mp4") || d.ends_with(".avi") || d.ends_with(".mkv");
+        if let Some(device) = devices.iter().find(|d| !is_file(d)) {
+            eprintln!("Error: REPLAY needs local video files, got '{}'", device);
+            std::process::exit(2);
+        }
+        let dump_dir = env::var("REPLAY_DUMP_DIR").ok();
+        Some(ReplayConfig { dump_dir })
+    }
+}
+
+/// Writes every frame's metadata as one JSON line per frame and source. Wall-clock
+/// timestamps are left out, so dumps of two runs over the same files diff cleanly.
+pub struct MetadataDump {
+    dir: String,
+    labels: Vec<String>,
+    files: HashMap<u32, BufWriter<File>>,
+}
+
+impl MetadataDump {
+    pub fn new(dir: &str, labels: Vec<String>) -> io::Result<MetadataDump> {
+        std::fs::create_dir_all(dir)?;
+        Ok(MetadataDump { dir: dir.trim_end_matches('/').to_string(), labels, files: HashMap::new() })
+    }
+
+    fn frame_value(&self, frame: &FrameDetections) -> Value {
+        let objects = frame
+            .objects
+            .iter()
+            .map(|det| {
+                let label = self.labels.get(det.class_id as usize).cloned().unwrap_or_else(|| det.class_id.to_string());
+                let mut entries = vec![
+                    ("class_id".to_string(), Value::from(det.class_id as i64)),
+                    ("label".to_string(), Value::from(label)),
+                    ("confidence".to_string(), Value::from(det.confidence)),
+                    ("track_id".to_string(), det.track_id.map(Value::from).unwrap_or(Value::Null)),
+                    (
+                        "bbox".to_string(),
+                        Value::Array([det.left, det.top, det.width, det.height].into_iter().map(Value::from).collect()),
+                    ),
+                ];
+                if !det.keypoints.is_empty() {
+                    let keypoints = det
+                        .keypoints
+                        .iter()
+                        .map(|k| Value::Array(k.iter().copied().map(Value::from).collect()))
+                        .collect();
+                    entries.push(("keypoints".to_string(), Value::Array(keypoints)));
+                }
+                Value::Object(entries)
+            })
+            .collect();
+        Value::Object(vec![
+            ("source_id".to_string(), Value::from(frame.source_id as u64)),
+            ("frame_num".to_string(), Value::from(frame.frame_num as i64)),
+            ("pts".to_string(), if frame.pts == u64::MAX { Value::Null } else { Value::from(frame.pts) }),
+            ("source_size".to_string(), Value::Array(vec![Value::from(frame.source_width as u64), Value::from(frame.source_height as u64)])),
+            ("pipeline_size".to_string(), Value::Array(vec![Value::from(frame.pipeline_width as u64), Value::from(frame.pipeline_height as u64)])),
+            ("objects".to_string(), Value::Array(objects)),
+        ])
+    }
+}
+
+impl FrameObserver for MetadataDump {
+    fn on_frame(&mut self, frame: &FrameDetections) {
+        let line = self.frame_value(frame).to_json();
+        let file = match self.files.entry(frame.source_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let path = format!("{}/replay_src{}.jsonl", self.dir, frame.source_id);
+                match File::create(&path) {
+                    Ok(file) => entry.insert(BufWriter::new(file)),
+                    Err(e) => {
+                        eprintln!("Warning: Could not create {}: {}", path, e);
+                        return;
+                    }
+                }
+            }
+        };
+        if let Err(e) = writeln!(file, "{}", line) {
+            eprintln!("Warning: Failed to write replay metadata: {}", e);
+        }
+        // Flushed per frame: while stepping, the dump should show the frame just processed
+        let _ = file.flush();
+    }
+
+    fn finish(&mut self) {
+        for file in self.files.values_mut() {
+            let _ = file.flush();
+        }
+    }
+}
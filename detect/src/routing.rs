@@ -0,0 +1,173 @@
+use crate::events::Event;
+use crate::json::Value;
+use std::env;
+
+/// How urgent an event is. Levels are ordered: a route receives its own level and above.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    fn parse(value: &str) -> Option<Severity> {
+        match value.trim().to_lowercase().as_str() {
+            "info" => Some(Severity::Info),
+            "warning" => Some(Severity::Warning),
+            "critical" => Some(Severity::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+
+    /// The severity the rules gave `event`; info when it has none.
+    pub fn of(event: &Event) -> Severity {
+        event
+            .fields
+            .iter()
+            .find_map(|(key, value)| match value {
+                Value::String(level) if key == "severity" => Severity::parse(level),
+                _ => None,
+            })
+            .unwrap_or(Severity::Info)
+    }
+}
+
+/// One EVENT_RULES entry: conditions an event must all meet, and the severity it then gets.
+#[derive(Default)]
+struct Rule {
+    kinds: Vec<String>,
+    /// Matched against the event's `label`
+    classes: Vec<String>,
+    zones: Vec<String>,
+    sources: Vec<u32>,
+    min_confidence: Option<f64>,
+    /// Local hours [start, end), wrapping past midnight when start > end
+    hours: Option<(u32, u32)>,
+    severity: Option<Severity>,
+}
+
+fn list(value: &str) -> Vec<String> {
+    value.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect()
+}
+
+impl Rule {
+    fn parse(spec: &str) -> Result<Rule, String> {
+        let mut rule = Rule::default();
+        for token in spec.split_whitespace() {
+            let Some((key, value)) = token.split_once('=') else {
+                return Err(format!("expected key=value, got '{}'", token));
+            };
+            match key {
+                "kind" => rule.kinds = list(value),
+                "class" => rule.classes = list(value),
+                "zone" => rule.zones = list(value),
+                "source" => {
+                    rule.sources = list(value)
+                        .iter()
+                        .map(|s| s.parse::<u32>().map_err(|_| format!("invalid source '{}'", s)))
+                        .collect::<Result<_, _>>()?
+                }
+                "min_confidence" => {
+                    rule.min_confidence = Some(value.parse().map_err(|_| format!("invalid min_confidence '{}'", value))?)
+                }
+                "hours" => {
+                    let hours = value
+                        .split_once('-')
+                        .and_then(|(start, end)| Some((start.trim().parse::<u32>().ok()?, end.trim().parse::<u32>().ok()?)))
+                        .filter(|(start, end)| *start < 24 && *end <= 24 && start != end);
+                    rule.hours = Some(hours.ok_or_else(|| format!("invalid hours '{}' (expected <start>-<end>, e.g. 22-6)", value))?);
+                }
+                "severity" => {
+                    rule.severity =
+                        Some(Severity::parse(value).ok_or_else(|| format!("invalid severity '{}' (info, warning or critical)", value))?)
+                }
+                _ => return Err(format!("unknown key '{}'", key)),
+            }
+        }
+        if rule.severity.is_none() {
+            return Err("no severity=".to_string());
+        }
+        Ok(rule)
+    }
+
+    fn matches(&self, event: &Event, hour: impl Fn() -> u32) -> bool {
+        let field = |name: &str| event.fields.iter().find(|(key, _)| key == name).map(|(_, value)| value);
+        let text = |name: &str| match field(name) {
+            Some(Value::String(s)) => Some(s.as_str()),
+            _ => None,
+        };
+        let any = |wanted: &[String], value: Option<&str>| wanted.is_empty() || value.is_some_and(|v| wanted.iter().any(|w| w == v));
+        any(&self.kinds, Some(&event.kind))
+            && any(&self.classes, text("label"))
+            && any(&self.zones, text("zone"))
+            && (self.sources.is_empty() || self.sources.contains(&event.source_id))
+            && self.min_confidence.is_none_or(|min| field("confidence").and_then(Value::as_f64).is_some_and(|c| c >= min))
+            && self.hours.is_none_or(|(start, end)| {
+                let hour = hour();
+                if start < end { (start..end).contains(&hour) } else { hour >= start || hour < end }
+            })
+    }
+}
+
+/// Severity rules for events (EVENT_RULES). Every matching rule is applied and the highest
+/// severity wins, so a broad rule can set a baseline that narrower ones escalate.
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Rules {
+    /// Reads EVENT_RULES: `;`-separated rules of space-separated `key=value` conditions
+    /// (kind, class, zone, source, min_confidence, hours) plus the `severity` they assign,
+    /// e.g. `kind=speeding severity=warning; kind=speeding class=truck hours=22-6 severity=critical`.
+    /// None when unset.
+    pub fn from_env() -> Option<Rules> {
+        let spec = env::var("EVENT_RULES").ok().filter(|v| !v.trim().is_empty())?;
+        let mut rules = Vec::new();
+        for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            match Rule::parse(entry) {
+                Ok(rule) => rules.push(rule),
+                Err(e) => {
+                    eprintln!("Error: Invalid EVENT_RULES rule '{}': {}", entry, e);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Some(Rules { rules })
+    }
+
+    /// Highest severity among the rules `event` matches; info when none does.
+    pub fn severity(&self, event: &Event) -> Severity {
+        // Local time, only looked up when a rule asks for it
+        let hour = || glib::DateTime::now_local().map(|now| now.hour() as u32).unwrap_or(0);
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(event, hour))
+            .filter_map(|rule| rule.severity)
+            .max()
+            .unwrap_or(Severity::Info)
+    }
+}
+
+/// Reads EVENT_ROUTES: `;`-separated `<severity>:<webhook or MQTT URL>` entries. Each
+/// destination gets the events of its severity and above.
+pub fn routes_from_env() -> Vec<(Severity, String)> {
+    let Ok(spec) = env::var("EVENT_ROUTES") else { return Vec::new() };
+    let mut routes = Vec::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((severity, url)) = entry.split_once(':').and_then(|(s, url)| Some((Severity::parse(s)?, url.trim()))) else {
+            eprintln!("Error: Invalid EVENT_ROUTES entry '{}' (expected <info|warning|critical>:<url>)", entry);
+            std::process::exit(2);
+        };
+        routes.push((severity, url.to_string()));
+    }
+    routes
+}
@@ -0,0 +1,131 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Sample clips: name, what's in it, URL. All from Intel's IoT DevKit sample-videos
+/// repository, licensed CC BY 4.0 (https://github.com/intel-iot-devkit/sample-videos).
+pub const SAMPLES: &[(&str, &str, &str)] = &[
+    (
+        "people",
+        "People walking through a hallway",
+        "https://github.com/intel-iot-devkit/sample-videos/raw/master/people-detection.mp4",
+    ),
+    (
+        "traffic",
+        "Cars on a road",
+        "https://github.com/intel-iot-devkit/sample-videos/raw/master/car-detection.mp4",
+    ),
+    (
+        "street",
+        "People, bicycles and cars on a street",
+        "https://github.com/intel-iot-devkit/sample-videos/raw/master/person-bicycle-car-detection.mp4",
+    ),
+    (
+        "faces",
+        "People walking towards the camera",
+        "https://github.com/intel-iot-devkit/sample-videos/raw/master/face-demographics-walking.mp4",
+    ),
+];
+
+/// Where samples are stored (SAMPLES_DIR, default: samples).
+fn samples_dir() -> String {
+    env::var("SAMPLES_DIR").unwrap_or_else(|_| "samples".to_string())
+}
+
+fn sample_path(dir: &str, name: &str) -> String {
+    format!("{}/{}.mp4", dir.trim_end_matches('/'), name)
+}
+
+/// Resolves a `sample:<name>` input to its downloaded file; other inputs are returned as is.
+pub fn resolve(device: &str) -> String {
+    let Some(name) = device.strip_prefix("sample:") else {
+        return device.to_string();
+    };
+    if !SAMPLES.iter().any(|(n, _, _)| *n == name) {
+        eprintln!("Error: Unknown sample '{}' (see `detect samples list`)", name);
+        std::process::exit(2);
+    }
+    let path = sample_path(&samples_dir(), name);
+    if !Path::new(&path).is_file() {
+        eprintln!("Error: Sample '{}' is not downloaded yet - run `detect samples fetch {}`", name, name);
+        std::process::exit(2);
+    }
+    // Absolute, since file inputs become file:// URIs
+    fs::canonicalize(&path).map(|p| p.to_string_lossy().to_string()).unwrap_or(path)
+}
+
+/// `detect samples [list | fetch [name...]]`: lists the sample clips or downloads them
+/// (all of them by default) into SAMPLES_DIR with curl.
+pub fn command(args: &[String]) -> ! {
+    let dir = samples_dir();
+    match args.first().map(String::as_str) {
+        Some("list") => {
+            for (name, description, _) in SAMPLES {
+                let state = if Path::new(&sample_path(&dir, name)).is_file() { "downloaded" } else { "not downloaded" };
+                println!("  sample:{:<10} {} ({})", name, description, state);
+            }
+            println!("Clips from intel-iot-devkit/sample-videos, licensed CC BY 4.0");
+            std::process::exit(0);
+        }
+        None | Some("fetch") => {}
+        Some(other) => {
+            eprintln!("Error: Unknown samples command '{}'", other);
+            eprintln!("Usage: detect samples [list | fetch [name...]]");
+            std::process::exit(2);
+        }
+    }
+
+    let names: Vec<&str> = if args.len() > 1 {
+        args[1..].iter().map(String::as_str).collect()
+    } else {
+        SAMPLES.iter().map(|(name, _, _)| *name).collect()
+    };
+    if let Some(unknown) = names.iter().find(|n| !SAMPLES.iter().any(|(s, _, _)| s == *n)) {
+        eprintln!("Error: Unknown sample '{}' (see `detect samples list`)", unknown);
+        std::process::exit(2);
+    }
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("Error: Could not create SAMPLES_DIR {}: {}", dir, e);
+        std::process::exit(2);
+    }
+
+    let mut failed = 0;
+    for name in &names {
+        let path = sample_path(&dir, name);
+        if Path::new(&path).is_file() {
+            println!("✓ sample:{} already downloaded ({})", name, path);
+            continue;
+        }
+        let (_, _, url) = SAMPLES.iter().find(|(s, _, _)| s == name).expect("sample was checked above");
+        println!("Downloading sample:{} from {}", name, url);
+        // Downloaded under a temporary name, so an interrupted download isn't taken for a sample
+        let partial = format!("{}.part", path);
+        let status = Command::new("curl").args(["-fL", "--retry", "2", "-o", &partial, url]).status();
+        match status {
+            Ok(status) if status.success() => match fs::rename(&partial, &path) {
+                Ok(()) => println!("✓ sample:{} -> {}", name, path),
+                Err(e) => {
+                    eprintln!("Error: Could not move sample:{} to {}: {}", name, path, e);
+                    let _ = fs::remove_file(&partial);
+                    failed += 1;
+                }
+            },
+            Ok(status) => {
+                eprintln!("Error: Downloading sample:{} failed (curl exited with {})", name, status);
+                let _ = fs::remove_file(&partial);
+                failed += 1;
+            }
+            Err(e) => {
+                eprintln!("Error: Failed to run curl: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    println!();
+    println!("Run detect on a sample with e.g. GST_DEVICE=sample:{} ./target/release/detect", names[0]);
+    std::process::exit(0);
+}
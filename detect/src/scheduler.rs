@@ -0,0 +1,181 @@
+use crate::metrics::Metrics;
+use gstreamer::prelude::*;
+use std::env;
+use std::fs;
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How often the GPU load is measured and the intervals adjusted
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Load this far under the budget gives frames back
+const HYSTERESIS: f64 = 15.0;
+/// Jetson's GPU load in tenths of a percent; desktop GPUs are asked through nvidia-smi
+const JETSON_LOAD: &str = "/sys/devices/gpu.0/load";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    Normal,
+    /// Never skipped
+    High,
+}
+
+impl Priority {
+    fn parse(value: &str) -> Option<Priority> {
+        match value.trim().to_lowercase().as_str() {
+            "high" => Some(Priority::High),
+            "" | "normal" => Some(Priority::Normal),
+            "low" => Some(Priority::Low),
+            _ => None,
+        }
+    }
+}
+
+/// Frames each input sends into the batch, traded against GPU load: while the GPU is busier
+/// than GPU_BUDGET, the lowest-priority inputs only send every 2nd, 3rd, ... frame (up to
+/// SOURCE_MAX_INTERVAL), and once it has room again they get their frames back, highest
+/// priority first. Skipped frames aren't inferred, tracked or drawn.
+pub struct SchedulerConfig {
+    /// Per GST_DEVICE position
+    pub priorities: Vec<Priority>,
+    /// GPU utilization (%) to stay under
+    pub budget: f64,
+    pub max_interval: u32,
+}
+
+impl SchedulerConfig {
+    /// Reads SOURCE_PRIORITY (`high`, `normal` or `low` per input, comma-separated), GPU_BUDGET
+    /// (default 85) and SOURCE_MAX_INTERVAL (default 5); None without SOURCE_PRIORITY.
+    pub fn from_env(sources: usize) -> Option<SchedulerConfig> {
+        let spec = env::var("SOURCE_PRIORITY").ok().filter(|v| !v.trim().is_empty())?;
+        let mut priorities = vec![Priority::Normal; sources];
+        for (index, value) in spec.split(',').enumerate() {
+            let Some(priority) = Priority::parse(value) else {
+                eprintln!("Error: Invalid SOURCE_PRIORITY entry '{}' (expected high, normal or low)", value.trim());
+                std::process::exit(2);
+            };
+            match priorities.get_mut(index) {
+                Some(slot) => *slot = priority,
+                None => eprintln!("Warning: SOURCE_PRIORITY has more entries than GST_DEVICE inputs, extra ignored"),
+            }
+        }
+        let number = |key: &str, default: f64| {
+            env::var(key).ok().map_or(default, |v| match v.trim().parse::<f64>() {
+                Ok(n) if n > 0.0 => n,
+                _ => {
+                    eprintln!("Error: {} must be a positive number, got '{}'", key, v);
+                    std::process::exit(2);
+                }
+            })
+        };
+        Some(SchedulerConfig {
+            priorities,
+            budget: number("GPU_BUDGET", 85.0).min(100.0),
+            max_interval: number("SOURCE_MAX_INTERVAL", 5.0).max(1.0) as u32,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let names: Vec<String> = self.priorities.iter().map(|p| format!("{:?}", p).to_lowercase()).collect();
+        names.join(", ")
+    }
+}
+
+/// Current GPU utilization in percent, None when there is no way to measure it.
+fn gpu_load() -> Option<f64> {
+    if let Ok(load) = fs::read_to_string(JETSON_LOAD) {
+        return load.trim().parse::<f64>().ok().map(|l| l / 10.0);
+    }
+    let gpu = env::var("GPU_ID").unwrap_or_else(|_| "0".to_string());
+    let output = Command::new("nvidia-smi")
+        .args(["-i", &gpu, "--query-gpu=utilization.gpu", "--format=csv,noheader,nounits"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    String::from_utf8_lossy(&output.stdout).lines().next()?.trim().parse().ok()
+}
+
+/// One step of the schedule: over budget, the lowest priority with room skips one more frame;
+/// well under it, the highest priority that skips any takes one back. Returns whether
+/// anything changed.
+fn adjust(intervals: &mut [u32], priorities: &[Priority], load: f64, budget: f64, max_interval: u32) -> bool {
+    let mut levels: Vec<Priority> = priorities.iter().copied().filter(|p| *p != Priority::High).collect();
+    levels.sort();
+    levels.dedup();
+    let step = |intervals: &mut [u32], level: Priority, delta: i32| {
+        for (interval, _) in intervals.iter_mut().zip(priorities).filter(|(_, p)| **p == level) {
+            *interval = (*interval as i32 + delta).clamp(1, max_interval as i32) as u32;
+        }
+    };
+    let of = |intervals: &[u32], level: Priority| {
+        intervals.iter().zip(priorities).find(|(_, p)| **p == level).map_or(1, |(i, _)| *i)
+    };
+    if load > budget {
+        if let Some(level) = levels.iter().copied().find(|l| of(intervals, *l) < max_interval) {
+            step(intervals, level, 1);
+            return true;
+        }
+    } else if load < budget - HYSTERESIS {
+        if let Some(level) = levels.iter().rev().copied().find(|l| of(intervals, *l) > 1) {
+            step(intervals, level, -1);
+            return true;
+        }
+    }
+    false
+}
+
+/// Lets every n-th frame of each input into nvstreammux, and re-plans the intervals from the
+/// measured GPU load every CHECK_INTERVAL. Load changes of any kind, such as an input coming
+/// back after a stall, are followed the same way.
+pub fn attach(pipeline: &gstreamer::Element, config: SchedulerConfig, metrics: Metrics) {
+    let bin = pipeline.downcast_ref::<gstreamer::Bin>().expect("pipeline is a bin");
+    let Some(mux) = bin.by_name("m") else { return };
+    let sources = config.priorities.len();
+    let intervals: Arc<Vec<AtomicU32>> = Arc::new((0..sources).map(|_| AtomicU32::new(1)).collect());
+    for index in 0..sources {
+        let Some(pad) = mux.static_pad(&format!("sink_{}", index)) else { continue };
+        let intervals = intervals.clone();
+        let frames = AtomicU64::new(0);
+        pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, _info| {
+            let interval = intervals[index].load(Ordering::Relaxed).max(1) as u64;
+            if frames.fetch_add(1, Ordering::Relaxed).is_multiple_of(interval) {
+                gstreamer::PadProbeReturn::Ok
+            } else {
+                gstreamer::PadProbeReturn::Drop
+            }
+        });
+    }
+
+    metrics.lock().unwrap().describe("detect_source_interval", "gauge", "Every how many frames each input is inferred");
+    thread::spawn(move || {
+        let mut current = vec![1; sources];
+        let mut measured = false;
+        loop {
+            {
+                let mut metrics = metrics.lock().unwrap();
+                for (index, interval) in current.iter().enumerate() {
+                    metrics.set("detect_source_interval", &[("source", index.to_string().as_str())], *interval as f64);
+                }
+            }
+            thread::sleep(CHECK_INTERVAL);
+            let Some(load) = gpu_load() else {
+                if !measured {
+                    eprintln!("Warning: GPU load can't be measured (no nvidia-smi or {}), SOURCE_PRIORITY has no effect", JETSON_LOAD);
+                    return;
+                }
+                continue;
+            };
+            measured = true;
+            if adjust(&mut current, &config.priorities, load, config.budget, config.max_interval) {
+                let plan: Vec<String> = current.iter().enumerate().map(|(i, n)| format!("{}: 1/{}", i, n)).collect();
+                println!("GPU at {:.0}% (budget {:.0}%), inferring inputs {}", load, config.budget, plan.join(", "));
+                for (atomic, interval) in intervals.iter().zip(&current) {
+                    atomic.store(*interval, Ordering::Relaxed);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,206 @@
+use crate::clips::Clips;
+use crate::events::{Event, Events};
+use crate::json::Value;
+use crate::snapshot::Snapshots;
+use gstreamer::prelude::*;
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use std::env;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Operations one run of the script may take before it is stopped, so a runaway loop costs
+/// a frame's worth of time instead of hanging the pipeline
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// Where `osd_text` lines start, below the operator banner
+const TEXT_TOP: u32 = 64;
+const TEXT_LINE: u32 = 28;
+
+/// The frame the script is running for, and what it asked to be drawn on it.
+#[derive(Default)]
+struct Context {
+    source_id: u32,
+    frame_num: i32,
+    texts: Vec<String>,
+    /// Object index and text appended to its label
+    labels: Vec<(usize, String)>,
+}
+
+fn json(value: &Dynamic) -> Value {
+    if let Ok(n) = value.as_int() {
+        Value::from(n)
+    } else if let Ok(n) = value.as_float() {
+        Value::from(n)
+    } else if let Ok(b) = value.as_bool() {
+        Value::from(b)
+    } else {
+        Value::from(value.to_string())
+    }
+}
+
+/// The engine with the functions scripts can call. Snapshots go to SCRIPT_SNAPSHOT_DIR
+/// (default `snapshots`).
+fn engine(context: &Arc<Mutex<Context>>, events: Events, clips: Option<Clips>, snapshots: Snapshots) -> Engine {
+    let snapshot_dir = env::var("SCRIPT_SNAPSHOT_DIR").unwrap_or_else(|_| "snapshots".to_string());
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.on_print(|text| println!("Script: {}", text));
+
+    let emit = {
+        let context = context.clone();
+        move |kind: &str, fields: Map| {
+            let (source_id, frame_num) = {
+                let context = context.lock().unwrap();
+                (context.source_id, context.frame_num)
+            };
+            let mut event = Event::new(kind, source_id, frame_num);
+            for (key, value) in &fields {
+                event = event.with(key, json(value));
+            }
+            events.lock().unwrap().emit(event);
+        }
+    };
+    let emit_kind = emit.clone();
+    engine.register_fn("emit", move |kind: &str| emit_kind(kind, Map::new()));
+    engine.register_fn("emit", emit);
+    let texts = context.clone();
+    engine.register_fn("osd_text", move |text: &str| texts.lock().unwrap().texts.push(text.to_string()));
+    let labels = context.clone();
+    engine.register_fn("object_text", move |index: i64, text: &str| {
+        labels.lock().unwrap().labels.push((index.max(0) as usize, text.to_string()));
+    });
+    let clip_context = context.clone();
+    engine.register_fn("clip", move || -> bool {
+        let source_id = clip_context.lock().unwrap().source_id;
+        clips.as_ref().and_then(|clips| clips.trigger(source_id)).is_some()
+    });
+    let snapshot_context = context.clone();
+    engine.register_fn("snapshot", move || -> String {
+        let (source_id, frame_num) = {
+            let context = snapshot_context.lock().unwrap();
+            (context.source_id, context.frame_num)
+        };
+        let jpeg = match snapshots.latest(Some(source_id as usize)) {
+            Ok(jpeg) => jpeg,
+            Err((_, message)) => {
+                eprintln!("Warning: Script snapshot failed: {}", message);
+                return String::new();
+            }
+        };
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        let path = format!("{}/source{}_{}_{}.jpg", snapshot_dir, source_id, frame_num, millis);
+        match fs::create_dir_all(&snapshot_dir).and_then(|_| fs::write(&path, jpeg)) {
+            Ok(()) => path,
+            Err(e) => {
+                eprintln!("Warning: Could not save script snapshot {}: {}", path, e);
+                String::new()
+            }
+        }
+    });
+    engine
+}
+
+/// Site-specific logic without forking the crate: a Rhai script (SCRIPT) run for every frame
+/// just before the OSD. It sees `frame` (`source_id`, `frame`, `objects` with `class_id`,
+/// `label`, `confidence`, `track_id` and the box) and a `state` map kept between frames, and
+/// can call `emit(kind[, fields])`, `osd_text(text)`, `object_text(index, text)`, `clip()`
+/// (needs CLIP_DIR) and `snapshot()` (needs SNAPSHOT_INTERVAL).
+pub struct Script {
+    path: String,
+    ast: AST,
+}
+
+impl Script {
+    /// Reads and compiles SCRIPT; None when unset.
+    pub fn from_env() -> Option<Script> {
+        let path = env::var("SCRIPT").ok().filter(|v| !v.trim().is_empty())?;
+        match Engine::new().compile_file(path.clone().into()) {
+            Ok(ast) => Some(Script { path, ast }),
+            Err(e) => {
+                eprintln!("Error: Could not load SCRIPT {}: {}", path, e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    pub fn describe(&self) -> &str {
+        &self.path
+    }
+
+    /// Runs the script for every frame on nvdsosd's sink pad and draws what it asked for.
+    /// A failing run is reported and the frame passes unchanged.
+    pub fn attach(
+        self,
+        pipeline: &gstreamer::Element,
+        labels: Vec<String>,
+        events: Events,
+        clips: Option<Clips>,
+        snapshots: Snapshots,
+    ) {
+        let Some(pad) = pipeline
+            .downcast_ref::<gstreamer::Bin>()
+            .and_then(|b| b.by_name("osd"))
+            .and_then(|osd| osd.static_pad("sink"))
+        else {
+            eprintln!("Warning: nvdsosd element not found, SCRIPT not run");
+            return;
+        };
+        let Script { path, ast } = self;
+        let context = Arc::new(Mutex::new(Context::default()));
+        let engine = engine(&context, events, clips, snapshots);
+        let mut scope = Scope::new();
+        scope.push("state", Map::new());
+        let scope = Mutex::new(scope);
+        pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+            let Some(batch) = nvds::BatchMeta::from_probe(info) else { return gstreamer::PadProbeReturn::Ok };
+            let mut scope = scope.lock().unwrap();
+            for frame in batch.frames() {
+                let objects: Array = frame
+                    .objects()
+                    .map(|obj| {
+                        let mut object = Map::new();
+                        object.insert("class_id".into(), Dynamic::from(obj.class_id as i64));
+                        let label = labels.get(obj.class_id as usize).cloned().unwrap_or_else(|| obj.label());
+                        object.insert("label".into(), Dynamic::from(label));
+                        object.insert("confidence".into(), Dynamic::from(obj.confidence as f64));
+                        object.insert("track_id".into(), obj.track_id().map_or(Dynamic::UNIT, |id| Dynamic::from(id as i64)));
+                        object.insert("left".into(), Dynamic::from(obj.rect_params.left as f64));
+                        object.insert("top".into(), Dynamic::from(obj.rect_params.top as f64));
+                        object.insert("width".into(), Dynamic::from(obj.rect_params.width as f64));
+                        object.insert("height".into(), Dynamic::from(obj.rect_params.height as f64));
+                        Dynamic::from_map(object)
+                    })
+                    .collect();
+                let mut info = Map::new();
+                info.insert("source_id".into(), Dynamic::from(frame.source_id as i64));
+                info.insert("frame".into(), Dynamic::from(frame.frame_num as i64));
+                info.insert("objects".into(), Dynamic::from_array(objects));
+                *context.lock().unwrap() =
+                    Context { source_id: frame.source_id, frame_num: frame.frame_num, ..Default::default() };
+
+                // Variables the script declares don't outlive the frame; `state` does
+                let base = scope.len();
+                scope.push("frame", info);
+                if let Err(e) = engine.run_ast_with_scope(&mut scope, &ast) {
+                    eprintln!("Warning: SCRIPT {} failed on source {} frame {}: {}", path, frame.source_id, frame.frame_num, e);
+                }
+                scope.rewind(base);
+
+                let Context { texts, labels: object_texts, .. } = std::mem::take(&mut *context.lock().unwrap());
+                for (index, obj) in frame.objects().enumerate() {
+                    for (_, text) in object_texts.iter().filter(|(i, _)| *i == index) {
+                        obj.append_display_text(&format!(" {}", text));
+                    }
+                }
+                for batch in texts.chunks(nvds::MAX_ELEMENTS_IN_DISPLAY_META) {
+                    let Some(display) = frame.add_display_meta() else { break };
+                    for (line, (params, text)) in display.text_params.iter_mut().zip(batch).enumerate() {
+                        params.set(text, 16, TEXT_TOP + line as u32 * TEXT_LINE, 16);
+                    }
+                    display.num_labels = batch.len() as u32;
+                }
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+    }
+}
@@ -0,0 +1,170 @@
+use crate::compare::{self, Agreement};
+use crate::detections::{Detection, FrameDetections, FrameObserver};
+use crate::json::Value;
+use gstreamer::prelude::*;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// gie-unique-id of the shadow model
+pub const SHADOW_UNIQUE_ID: u32 = 31;
+/// How often the agreement statistics are printed and rewritten
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A candidate model run silently next to MODEL_CONFIG before rolling it out. It infers
+/// right after pgie on the same frames; its objects are logged and then removed, so the
+/// OSD, tracker, events and every other output only ever see the primary model's.
+pub struct ShadowConfig {
+    /// nvinfer config of the shadow model (SHADOW_MODEL)
+    pub model_config: String,
+    /// JSON lines with the shadow model's detections (SHADOW_LOG)
+    pub log_path: String,
+    /// Agreement statistics as JSON (SHADOW_REPORT)
+    pub report_path: Option<String>,
+}
+
+impl ShadowConfig {
+    /// Reads SHADOW_MODEL, SHADOW_LOG (default shadow.jsonl) and SHADOW_REPORT; None without
+    /// SHADOW_MODEL.
+    pub fn from_env() -> Option<ShadowConfig> {
+        let model_config = env::var("SHADOW_MODEL").ok().filter(|v| !v.trim().is_empty())?;
+        if !Path::new(&model_config).exists() {
+            eprintln!("Error: SHADOW_MODEL config {} not found", model_config);
+            std::process::exit(2);
+        }
+        Some(ShadowConfig {
+            model_config,
+            log_path: env::var("SHADOW_LOG").unwrap_or_else(|_| "shadow.jsonl".to_string()),
+            report_path: env::var("SHADOW_REPORT").ok().filter(|v| !v.trim().is_empty()),
+        })
+    }
+
+    /// The shadow model's nvinfer, placed after pgie.
+    pub fn stage(&self) -> String {
+        format!(
+            "nvinfer name=shadow_model config-file-path={} unique-id={}",
+            self.model_config, SHADOW_UNIQUE_ID
+        )
+    }
+}
+
+fn object_json(det: &Detection, labels: &[String]) -> Value {
+    Value::Object(vec![
+        ("class_id".to_string(), Value::from(det.class_id as i64)),
+        ("label".to_string(), Value::from(labels.get(det.class_id as usize).map_or("", |l| l.as_str()))),
+        ("confidence".to_string(), Value::from(det.confidence)),
+        ("left".to_string(), Value::from(det.left)),
+        ("top".to_string(), Value::from(det.top)),
+        ("width".to_string(), Value::from(det.width)),
+        ("height".to_string(), Value::from(det.height)),
+    ])
+}
+
+/// The running shadow deployment; also a frame observer so the final statistics are
+/// written at shutdown.
+#[derive(Clone)]
+pub struct Shadow {
+    config: Arc<ShadowConfig>,
+    labels: Arc<Vec<String>>,
+    agreement: Arc<Mutex<Agreement>>,
+}
+
+impl Shadow {
+    pub fn new(config: ShadowConfig, labels: Vec<String>) -> Shadow {
+        Shadow { config: Arc::new(config), labels: Arc::new(labels), agreement: Arc::default() }
+    }
+
+    fn report(&self) {
+        let agreement = self.agreement.lock().unwrap();
+        println!("Shadow model {} against the primary:", self.config.model_config);
+        for line in agreement.summary(&self.labels) {
+            println!("  {}", line);
+        }
+        if let Some(path) = &self.config.report_path {
+            if let Err(e) = fs::write(path, agreement.to_json(&self.labels).to_json()) {
+                eprintln!("Warning: Failed to write shadow report {}: {}", path, e);
+            }
+        }
+    }
+
+    /// Logs and removes the shadow model's objects after its nvinfer, matching them against
+    /// the primary's on the same frame, and reports every REPORT_INTERVAL.
+    pub fn attach(&self, pipeline: &gstreamer::Element) {
+        let Some(shadow) = pipeline.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("shadow_model")) else {
+            eprintln!("Warning: shadow_model element not found, shadow mode disabled");
+            return;
+        };
+        let log = match OpenOptions::new().create(true).append(true).open(&self.config.log_path) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                eprintln!("Warning: Could not open SHADOW_LOG {}: {}. Shadow detections are not logged.", self.config.log_path, e);
+                None
+            }
+        };
+        let log_path = self.config.log_path.clone();
+        let labels = self.labels.clone();
+        let agreement = self.agreement.clone();
+        let pad = shadow.static_pad("src").expect("nvinfer has a src pad");
+        pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+            if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+                let timestamp_ms =
+                    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+                let mut lines = String::new();
+                for frame in batch.frames() {
+                    let (mut primary, mut shadowed) = (Vec::new(), Vec::new());
+                    frame.retain_objects(|obj| match obj.unique_component_id as u32 {
+                        SHADOW_UNIQUE_ID => {
+                            shadowed.push(compare::detection(obj));
+                            false
+                        }
+                        // An A/B candidate's objects are neither model's
+                        compare::CANDIDATE_UNIQUE_ID => true,
+                        _ => {
+                            primary.push(compare::detection(obj));
+                            true
+                        }
+                    });
+                    agreement.lock().unwrap().add(&primary, &shadowed);
+                    if !shadowed.is_empty() {
+                        let line = Value::Object(vec![
+                            ("source_id".to_string(), Value::from(frame.source_id as u64)),
+                            ("frame".to_string(), Value::from(frame.frame_num as i64)),
+                            ("timestamp_ms".to_string(), Value::from(timestamp_ms)),
+                            ("objects".to_string(), Value::Array(shadowed.iter().map(|d| object_json(d, &labels)).collect())),
+                        ]);
+                        lines.push_str(&line.to_json());
+                        lines.push('\n');
+                    }
+                }
+                // Flushed per batch so `tail -f` works
+                if let Some(mut log) = log.as_ref().filter(|_| !lines.is_empty()) {
+                    if let Err(e) = log.write_all(lines.as_bytes()).and_then(|_| log.flush()) {
+                        eprintln!("Warning: Failed to write shadow detections to {}: {}", log_path, e);
+                    }
+                }
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+
+        let shadow = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(REPORT_INTERVAL);
+            shadow.report();
+        });
+    }
+}
+
+impl FrameObserver for Shadow {
+    fn on_frame(&mut self, _frame: &FrameDetections) {}
+
+    fn finish(&mut self) {
+        self.report();
+        if let Some(path) = &self.config.report_path {
+            println!("✓ Wrote shadow report: {}", path);
+        }
+    }
+}
@@ -0,0 +1,685 @@
+use crate::access::RtspAccess;
+use crate::idle;
+use std::env;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    H264,
+    H265,
+}
+
+impl Codec {
+    fn parse(name: &str) -> Option<Codec> {
+        match name.to_lowercase().as_str() {
+            "h264" | "avc" => Some(Codec::H264),
+            "h265" | "hevc" => Some(Codec::H265),
+            _ => None,
+        }
+    }
+
+    fn encoder(&self) -> &'static str {
+        match self {
+            Codec::H264 => "nvv4l2h264enc",
+            Codec::H265 => "nvv4l2h265enc",
+        }
+    }
+
+    /// CPU encoder for branches beyond the GPU's NVENC sessions
+    fn software_encoder(&self) -> &'static str {
+        match self {
+            Codec::H264 => "x264enc",
+            Codec::H265 => "x265enc",
+        }
+    }
+
+    fn parser(&self) -> &'static str {
+        match self {
+            Codec::H264 => "h264parse",
+            Codec::H265 => "h265parse",
+        }
+    }
+
+    pub fn payloader(&self) -> &'static str {
+        match self {
+            Codec::H264 => "rtph264pay",
+            Codec::H265 => "rtph265pay",
+        }
+    }
+
+    /// RTP `encoding-name` caps field for this codec.
+    pub fn encoding_name(&self) -> &'static str {
+        match self {
+            Codec::H264 => "H264",
+            Codec::H265 => "H265",
+        }
+    }
+}
+
+/// Encoder tuning, from a sink's `tune` option or ENCODER_TUNING (set by the pipeline profiles).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tuning {
+    /// Fastest preset, no lookahead: frames leave the encoder as soon as possible
+    Latency,
+    /// Slowest preset tuned for quality, at the cost of encoder delay and GPU time
+    Quality,
+}
+
+impl Tuning {
+    fn parse(name: &str) -> Option<Tuning> {
+        match name {
+            "latency" => Some(Tuning::Latency),
+            "quality" => Some(Tuning::Quality),
+            _ => None,
+        }
+    }
+
+    /// ENCODER_TUNING; validated in sinks_from_env.
+    pub fn from_env() -> Option<Tuning> {
+        env::var("ENCODER_TUNING").ok().and_then(|v| Tuning::parse(&v))
+    }
+
+    /// Encoder properties. Jetson's encoders take a preset level, dGPU's NVENC a preset and tuning info.
+    pub fn properties(&self) -> &'static str {
+        match (self, cfg!(target_arch = "aarch64")) {
+            (Tuning::Latency, true) => "preset-level=1 maxperf-enable=true",
+            (Tuning::Quality, true) => "preset-level=4",
+            (Tuning::Latency, false) => "preset-id=1 tuning-info-id=3",
+            (Tuning::Quality, false) => "preset-id=7 tuning-info-id=1",
+        }
+    }
+}
+
+/// Encoder settings for one output branch. Each branch converts and encodes on its
+/// own, so e.g. a 1080p recording and a 480p preview don't share one encode.
+#[derive(Clone, Debug)]
+pub struct EncodeSettings {
+    pub codec: Codec,
+    pub bitrate: String,
+    pub width: Option<String>,
+    pub height: Option<String>,
+    pub iframeinterval: Option<String>,
+    pub tuning: Option<Tuning>,
+    /// Encode on the CPU (x264enc/x265enc) instead of NVENC
+    pub software: bool,
+    /// Encoder element name, for changing its bitrate while playing
+    pub name: Option<String>,
+}
+
+impl EncodeSettings {
+    pub fn new(codec: Codec, bitrate: &str) -> EncodeSettings {
+        EncodeSettings {
+            codec,
+            bitrate: bitrate.to_string(),
+            width: None,
+            height: None,
+            iframeinterval: None,
+            tuning: None,
+            software: false,
+            name: None,
+        }
+    }
+
+    /// GPU convert/scale to I420, encode and parse: ends on the parser's src pad.
+    pub fn chain(&self) -> String {
+        let name = self.name.as_ref().map(|n| format!(" name={}", n)).unwrap_or_default();
+        if self.software {
+            return self.software_chain(&name);
+        }
+        let mut encoder = format!(
+            "nvvideoconvert ! {} ! {}{} bitrate={} insert-sps-pps=true",
+            scaled_caps("video/x-raw(memory:NVMM),format=I420", &self.width, &self.height),
+            self.codec.encoder(),
+            name,
+            self.bitrate
+        );
+        if let Some(interval) = &self.iframeinterval {
+            encoder.push_str(&format!(" iframeinterval={}", interval));
+        }
+        if let Some(tuning) = self.tuning {
+            encoder.push_str(&format!(" {}", tuning.properties()));
+        }
+        encoder.push_str(&format!(" ! {}", self.codec.parser()));
+        encoder
+    }
+
+    /// Like `chain`, downloading the frames to system memory for x264enc/x265enc, which take
+    /// the bitrate in kbit/s and a speed preset instead of the NVENC tuning.
+    fn software_chain(&self, name: &str) -> String {
+        let kbps = match self.bitrate.parse::<u64>() {
+            Ok(bitrate) => (bitrate / 1000).max(1).to_string(),
+            Err(_) => self.bitrate.clone(),
+        };
+        let preset = match self.tuning {
+            Some(Tuning::Latency) => "ultrafast",
+            Some(Tuning::Quality) => "medium",
+            None => "veryfast",
+        };
+        let mut encoder = format!(
+            "nvvideoconvert ! {} ! {}{} bitrate={} tune=zerolatency speed-preset={}",
+            scaled_caps("video/x-raw,format=I420", &self.width, &self.height),
+            self.codec.software_encoder(),
+            name,
+            kbps,
+            preset
+        );
+        if let Some(interval) = &self.iframeinterval {
+            encoder.push_str(&format!(" key-int-max={}", interval));
+        }
+        encoder.push_str(&format!(" ! {}", self.codec.parser()));
+        encoder
+    }
+}
+
+fn scaled_caps(base: &str, width: &Option<String>, height: &Option<String>) -> String {
+    match (width, height) {
+        (Some(w), Some(h)) => format!("{},width={},height={}", base, w, h),
+        _ => base.to_string(),
+    }
+}
+
+/// How RTSP clients may receive a mount's RTP. The payloader MTU is a branch option
+/// instead, since packets are cut in the main pipeline.
+#[derive(Clone, Debug)]
+pub struct RtspTransport {
+    pub tcp: bool,
+    pub udp: bool,
+    pub multicast: bool,
+    /// Server-side ports handed out for unicast UDP, e.g. to match a firewall rule
+    pub rtp_ports: Option<(u16, u16)>,
+    pub multicast_range: Option<(String, String)>,
+    pub ttl: u8,
+    /// Network interface multicast is sent on, for hosts on several networks
+    pub multicast_iface: Option<String>,
+}
+
+fn parse_range(key: &str, value: &str) -> Result<(String, String), String> {
+    match value.split_once('-') {
+        Some((min, max)) => Ok((min.trim().to_string(), max.trim().to_string())),
+        None => Err(format!("{} must be a range like min-max, got '{}'", key, value)),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SinkKind {
+    Display,
+    Rtsp,
+    File,
+    Hls,
+    AppSink,
+    FakeSink,
+    Ndi,
+}
+
+impl SinkKind {
+    fn parse(name: &str) -> Option<SinkKind> {
+        match name {
+            "display" => Some(SinkKind::Display),
+            "rtsp" => Some(SinkKind::Rtsp),
+            "file" => Some(SinkKind::File),
+            "hls" => Some(SinkKind::Hls),
+            "appsink" => Some(SinkKind::AppSink),
+            "fakesink" => Some(SinkKind::FakeSink),
+            "ndi" => Some(SinkKind::Ndi),
+            _ => None,
+        }
+    }
+}
+
+/// One output of the sink graph: a kind plus `key=value` options.
+#[derive(Clone, Debug)]
+pub struct SinkSpec {
+    pub kind: SinkKind,
+    options: Vec<(String, String)>,
+}
+
+impl SinkSpec {
+    fn new(kind: SinkKind) -> SinkSpec {
+        SinkSpec { kind, options: Vec::new() }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    fn get_or<'a>(&'a self, key: &str, default: &'a str) -> &'a str {
+        self.get(key).unwrap_or(default)
+    }
+
+    /// Options prefixed with `queue-` are passed to this branch's queue,
+    /// e.g. `queue-leaky=downstream queue-max-size-buffers=2`, after the SINK_QUEUE
+    /// properties every branch gets.
+    ///
+    /// `fps` caps the rate of this branch only, so e.g. an X11 display can update at 10 fps
+    /// while a recording keeps every frame. It drops buffers on the NVMM side, before any
+    /// conversion or copy happens in the branch.
+    fn queue(&self) -> String {
+        let mut queue = "queue".to_string();
+        if let Some(properties) = env::var("SINK_QUEUE").ok().filter(|p| !p.trim().is_empty()) {
+            queue.push_str(&format!(" {}", properties.trim()));
+        }
+        for (key, value) in &self.options {
+            if let Some(property) = key.strip_prefix("queue-") {
+                queue.push_str(&format!(" {}={}", property, value));
+            }
+        }
+        if let Some(fps) = self.get("fps") {
+            queue.push_str(&format!(" ! videorate drop-only=true max-rate={}", fps));
+        }
+        queue
+    }
+
+    /// Whether this sink shows the OSD boxes and labels; `osd=false` tees it off before nvdsosd,
+    /// e.g. a clean recording next to an annotated preview. Validated in parse_sinks.
+    pub fn osd(&self) -> bool {
+        self.get("osd") != Some("false")
+    }
+
+    /// Whether this sink's branch has a video encoder.
+    pub fn encodes(&self) -> bool {
+        matches!(self.kind, SinkKind::Rtsp | SinkKind::File | SinkKind::Hls)
+    }
+
+    /// Whether this sink's options keep it on NVENC or move it to the CPU encoder.
+    pub fn encoder_choice(&self) -> Option<&str> {
+        self.get("encoder")
+    }
+
+    /// Moves this sink's encode to the CPU, e.g. when NVENC has no session left for it.
+    pub fn use_software_encoder(&mut self) {
+        self.options.retain(|(key, _)| key != "encoder");
+        self.options.push(("encoder".to_string(), "software".to_string()));
+    }
+
+    pub fn codec(&self) -> Codec {
+        // Validated in parse_sinks
+        self.get("codec").and_then(Codec::parse).unwrap_or(Codec::H264)
+    }
+
+    fn encoder(&self, default_bitrate: &str) -> String {
+        // Adaptive streams start at the top of their range unless a bitrate is given
+        let (bitrate, name) = match self.abr_range() {
+            Some((_, max)) => {
+                let bitrate = self.get("bitrate").map(str::to_string).unwrap_or_else(|| max.to_string());
+                (bitrate, Some(self.encoder_name()))
+            }
+            None => (self.get_or("bitrate", default_bitrate).to_string(), None),
+        };
+        EncodeSettings {
+            codec: self.codec(),
+            bitrate,
+            width: self.get("width").map(str::to_string),
+            height: self.get("height").map(str::to_string),
+            iframeinterval: self.get("iframeinterval").map(str::to_string),
+            tuning: self.get("tune").and_then(Tuning::parse).or_else(Tuning::from_env),
+            software: self.get("encoder") == Some("software"),
+            name,
+        }
+        .chain()
+    }
+
+    /// Bitrate range of an rtsp sink with `abr=<min>-<max>` (bits/s); validated in parse_sinks.
+    pub fn abr_range(&self) -> Option<(u32, u32)> {
+        let (min, max) = self.get("abr")?.split_once('-')?;
+        Some((min.trim().parse().ok()?, max.trim().parse().ok()?))
+    }
+
+    /// Name of this sink's encoder element.
+    pub fn encoder_name(&self) -> String {
+        format!("abr_enc{}", self.rtsp_udp_port())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let name = format!("{:?}", self.kind).to_lowercase();
+        if let Some(codec) = self.get("codec") {
+            let codec = Codec::parse(codec)
+                .ok_or_else(|| format!("sink '{}': unknown codec '{}' (expected h264 or h265)", name, codec))?;
+            if codec == Codec::H265 && self.kind == SinkKind::Hls {
+                return Err("sink 'hls': hlssink2 only supports h264".to_string());
+            }
+        }
+        if let Some(tune) = self.get("tune") {
+            if !self.encodes() {
+                return Err(format!("sink '{}': tune only applies to encoding sinks", name));
+            }
+            Tuning::parse(tune).ok_or_else(|| format!("sink '{}': tune must be latency or quality, got '{}'", name, tune))?;
+        }
+        if let Some(encoder) = self.get("encoder") {
+            if !self.encodes() {
+                return Err(format!("sink '{}': encoder only applies to encoding sinks", name));
+            }
+            if encoder != "hardware" && encoder != "software" {
+                return Err(format!("sink '{}': encoder must be hardware or software, got '{}'", name, encoder));
+            }
+        }
+        if self.kind == SinkKind::Rtsp {
+            self.rtsp_transport().map_err(|e| format!("sink 'rtsp': {}", e))?;
+            self.rtsp_access().map_err(|e| format!("sink 'rtsp': {}", e))?;
+            if let Some(linger) = self.get_or_env("idle-linger", "RTSP_IDLE_LINGER") {
+                linger
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| format!("sink 'rtsp': idle-linger must be a number of seconds, got '{}'", linger))?;
+            }
+        }
+        if let Some(abr) = self.get("abr") {
+            if self.kind != SinkKind::Rtsp {
+                return Err(format!("sink '{}': abr only applies to rtsp sinks", name));
+            }
+            match self.abr_range() {
+                Some((min, max)) if min > 0 && min <= max => {}
+                _ => return Err(format!("sink 'rtsp': abr must be a bitrate range like 500000-4000000, got '{}'", abr)),
+            }
+        }
+        for key in ["width", "height", "bitrate", "mtu", "fps"] {
+            if let Some(value) = self.get(key) {
+                if value.parse::<u32>().map(|v| v == 0).unwrap_or(true) {
+                    return Err(format!("sink '{}': {} must be a positive integer, got '{}'", name, key, value));
+                }
+            }
+        }
+        if let Some(idle) = self.get("idle") {
+            if idle != "pause" {
+                return Err(format!("sink '{}': idle must be pause, got '{}'", name, idle));
+            }
+        }
+        if let Some(osd) = self.get("osd") {
+            if osd != "true" && osd != "false" {
+                return Err(format!("sink '{}': osd must be true or false, got '{}'", name, osd));
+            }
+        }
+        if self.get("width").is_some() != self.get("height").is_some() {
+            return Err(format!("sink '{}': width and height must be set together", name));
+        }
+        Ok(())
+    }
+
+    pub fn rtsp_port(&self) -> String {
+        self.get("port")
+            .map(|p| p.to_string())
+            .or_else(|| env::var("RTSP_OUTPUT_PORT").ok())
+            .unwrap_or_else(|| "8555".to_string())
+    }
+
+    pub fn rtsp_mount(&self) -> &str {
+        self.get_or("mount", "/ds-detect")
+    }
+
+    fn get_or_env(&self, key: &str, var: &str) -> Option<String> {
+        self.get(key).map(str::to_string).or_else(|| env::var(var).ok())
+    }
+
+    /// Transport options, from sink options or RTSP_PROTOCOLS / RTSP_RTP_PORTS /
+    /// RTSP_MULTICAST / RTSP_MULTICAST_TTL / RTSP_MULTICAST_IFACE. `protocols=tcp` forces
+    /// interleaved TCP, which is what most clients behind NAT need; `protocols=udp-mcast`
+    /// sends one multicast stream that every client joins.
+    pub fn rtsp_transport(&self) -> Result<RtspTransport, String> {
+        let mut transport = RtspTransport {
+            tcp: true,
+            udp: true,
+            multicast: true,
+            rtp_ports: None,
+            multicast_range: None,
+            ttl: 16,
+            multicast_iface: None,
+        };
+        if let Some(protocols) = self.get_or_env("protocols", "RTSP_PROTOCOLS") {
+            transport.tcp = false;
+            transport.udp = false;
+            transport.multicast = false;
+            for protocol in protocols.split(',').map(str::trim) {
+                match protocol {
+                    "tcp" => transport.tcp = true,
+                    "udp" => transport.udp = true,
+                    "udp-mcast" | "multicast" => transport.multicast = true,
+                    _ => return Err(format!("unknown protocol '{}' (expected tcp, udp or udp-mcast)", protocol)),
+                }
+            }
+        }
+        if let Some(ports) = self.get_or_env("rtp-ports", "RTSP_RTP_PORTS") {
+            let (min, max) = parse_range("rtp-ports", &ports)?;
+            match (min.parse::<u16>(), max.parse::<u16>()) {
+                (Ok(min), Ok(max)) if min <= max => transport.rtp_ports = Some((min, max)),
+                _ => return Err(format!("rtp-ports must be two port numbers like 50000-50100, got '{}'", ports)),
+            }
+        }
+        let multicast_only = transport.multicast && !transport.tcp && !transport.udp;
+        match self.get_or_env("multicast", "RTSP_MULTICAST") {
+            Some(range) if range != "auto" => {
+                let (min, max) = if range.contains('-') {
+                    parse_range("multicast", &range)?
+                } else {
+                    (range.clone(), range.clone())
+                };
+                for address in [&min, &max] {
+                    match address.parse::<std::net::Ipv4Addr>() {
+                        Ok(ip) if ip.is_multicast() => {}
+                        _ => return Err(format!("multicast address '{}' is not an IPv4 multicast address", address)),
+                    }
+                }
+                transport.multicast_range = Some((min, max));
+            }
+            // `multicast=auto`, or multicast as the only protocol: a group of the sink's own in
+            // the organization-local scope, so mounts never share one
+            Some(_) => transport.multicast_range = Some(self.multicast_group()),
+            None if multicast_only => transport.multicast_range = Some(self.multicast_group()),
+            None => {}
+        }
+        if let Some(ttl) = self.get_or_env("ttl", "RTSP_MULTICAST_TTL") {
+            transport.ttl = ttl.parse().map_err(|_| format!("ttl must be 0-255, got '{}'", ttl))?;
+        }
+        transport.multicast_iface = self.get_or_env("multicast-iface", "RTSP_MULTICAST_IFACE");
+        if transport.multicast_range.is_some() && !transport.multicast {
+            return Err("multicast needs udp-mcast in protocols".to_string());
+        }
+        if !(transport.tcp || transport.udp || transport.multicast) {
+            return Err("protocols must enable at least one transport".to_string());
+        }
+        Ok(transport)
+    }
+
+    /// Access control of an rtsp sink's mount, from the `allow`, `deny`, `max-clients` and
+    /// `client-bitrate` options or RTSP_ALLOW / RTSP_DENY / RTSP_MAX_CLIENTS / RTSP_CLIENT_BITRATE.
+    pub fn rtsp_access(&self) -> Result<RtspAccess, String> {
+        RtspAccess::parse(
+            self.get_or_env("allow", "RTSP_ALLOW"),
+            self.get_or_env("deny", "RTSP_DENY"),
+            self.get_or_env("max-clients", "RTSP_MAX_CLIENTS"),
+            self.get_or_env("client-bitrate", "RTSP_CLIENT_BITRATE"),
+        )
+    }
+
+    /// Seconds an rtsp sink keeps encoding after its last viewer leaves (`idle-linger` or
+    /// RTSP_IDLE_LINGER); None encodes all the time. Validated in parse_sinks.
+    pub fn idle_linger(&self) -> Option<u32> {
+        if self.kind != SinkKind::Rtsp {
+            return None;
+        }
+        self.get_or_env("idle-linger", "RTSP_IDLE_LINGER").and_then(|v| v.trim().parse().ok())
+    }
+
+    /// Valve at the start of the branch that stops the encode while nobody watches.
+    pub fn idle_valve_name(&self) -> String {
+        format!("rtsp_idle{}", self.rtsp_udp_port())
+    }
+
+    /// 239.255.x.y from the sink's `udp-port`, which is unique per rtsp sink.
+    fn multicast_group(&self) -> (String, String) {
+        let port: u16 = self.rtsp_udp_port().parse().unwrap_or(5400);
+        let group = format!("239.255.{}.{}", port >> 8, port & 0xff);
+        (group.clone(), group)
+    }
+
+    /// Localhost port the encoded RTP is sent to. The RTSP server re-serves it from
+    /// there, so clients never cause the inference pipeline to be relaunched.
+    pub fn rtsp_udp_port(&self) -> String {
+        self.get_or("udp-port", "5400").to_string()
+    }
+
+    /// Renders this sink as a branch starting with its own queue, meant to hang off a tee pad.
+    pub fn branch(&self, index: usize) -> String {
+        let mut queue = self.queue();
+        // `idle=pause`: closed while idle mode (IDLE_AFTER) is on
+        if self.get("idle") == Some("pause") {
+            queue.push_str(&format!(" ! valve name={}{} drop=false", idle::IDLE_VALVE_PREFIX, index));
+        }
+        match self.kind {
+            SinkKind::Display => {
+                let scale = match (self.get("width"), self.get("height")) {
+                    (Some(w), Some(h)) => format!("nvvideoconvert ! video/x-raw(memory:NVMM),width={},height={} ! ", w, h),
+                    _ => String::new(),
+                };
+                format!(
+                    "{} ! {}nvvideoconvert ! {} sync={}",
+                    queue,
+                    scale,
+                    self.get_or("sink", "ximagesink"),
+                    self.get_or_env("sync", "SINK_SYNC").unwrap_or_else(|| "false".to_string())
+                )
+            }
+            SinkKind::Rtsp => format!(
+                "{}{} ! {} ! {} config-interval=1 pt=96 mtu={} ! \
+                 udpsink host=127.0.0.1 port={} sync=false async=false",
+                queue,
+                // Closed until the first viewer plays the mount
+                self.idle_linger().map(|_| format!(" ! valve name={} drop=true", self.idle_valve_name())).unwrap_or_default(),
+                self.encoder("4000000"),
+                self.codec().payloader(),
+                self.get_or_env("mtu", "RTSP_MTU").unwrap_or_else(|| "1400".to_string()),
+                self.rtsp_udp_port()
+            ),
+            SinkKind::File => {
+                let location = self
+                    .get("location")
+                    .map(|l| l.to_string())
+                    .unwrap_or_else(|| format!("/recordings/detect_sink{}.mp4", index));
+                // mkv survives abrupt termination; mp4 is fragmented for the same reason
+                let mux = if location.ends_with(".mkv") { "matroskamux" } else { "mp4mux fragment-duration=1000" };
+                format!(
+                    "{} ! {} ! {} ! filesink location={} sync=false",
+                    queue,
+                    self.encoder("8000000"),
+                    mux,
+                    location
+                )
+            }
+            SinkKind::Hls => {
+                let dir = self.get_or("dir", "/tmp/hls").trim_end_matches('/').to_string();
+                format!(
+                    "{} ! {} ! hlssink2 location={}/segment%05d.ts playlist-location={}/playlist.m3u8 \
+                     target-duration={} max-files={}",
+                    queue,
+                    self.encoder("4000000"),
+                    dir,
+                    dir,
+                    self.get_or("target-duration", "2"),
+                    self.get_or("max-files", "10")
+                )
+            }
+            SinkKind::AppSink => format!(
+                "{} ! nvvideoconvert ! {} ! \
+                 appsink name={} emit-signals=false sync=false max-buffers=1 drop=true",
+                queue,
+                scaled_caps(
+                    &format!("video/x-raw,format={}", self.get_or("format", "RGBA")),
+                    &self.get("width").map(str::to_string),
+                    &self.get("height").map(str::to_string)
+                ),
+                self.get("name").map(|n| n.to_string()).unwrap_or_else(|| format!("appsink{}", index))
+            ),
+            SinkKind::FakeSink => format!(
+                "{} ! fakesink sync={}",
+                queue,
+                self.get_or_env("sync", "SINK_SYNC").unwrap_or_else(|| "false".to_string())
+            ),
+            // UYVY is NDI's native format, so ndisink sends frames without converting them again
+            SinkKind::Ndi => format!(
+                "{} ! nvvideoconvert ! {} ! ndisink ndi-name={}",
+                queue,
+                scaled_caps(
+                    "video/x-raw,format=UYVY",
+                    &self.get("width").map(str::to_string),
+                    &self.get("height").map(str::to_string)
+                ),
+                self.get_or("name", "ds-detect")
+            ),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut out = format!("{:?}", self.kind).to_lowercase();
+        for (key, value) in &self.options {
+            out.push_str(&format!(" {}={}", key, value));
+        }
+        out
+    }
+}
+
+/// Parses a sink graph: entries separated by `;`, each a kind followed by
+/// space-separated options, e.g. `display; rtsp bitrate=2000000; file location=/rec/out.mp4`.
+pub fn parse_sinks(spec: &str) -> Result<Vec<SinkSpec>, String> {
+    let mut sinks = Vec::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let mut words = entry.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let kind = SinkKind::parse(name).ok_or_else(|| {
+            format!("unknown sink '{}' (expected display, rtsp, file, hls, appsink, fakesink or ndi)", name)
+        })?;
+        let mut sink = SinkSpec::new(kind);
+        for word in words {
+            let (key, value) = word
+                .split_once('=')
+                .ok_or_else(|| format!("sink '{}': option '{}' must be key=value", name, word))?;
+            sink.options.push((key.to_string(), value.to_string()));
+        }
+        sink.validate()?;
+        sinks.push(sink);
+    }
+
+    if sinks.is_empty() {
+        return Err("no sinks configured".to_string());
+    }
+
+    // Each rtsp sink needs its own loopback port and a distinct port/mount pair
+    let mut next_udp_port = 5400;
+    let mut seen = Vec::new();
+    for sink in sinks.iter_mut().filter(|s| s.kind == SinkKind::Rtsp) {
+        if sink.get("udp-port").is_none() {
+            sink.options.push(("udp-port".to_string(), next_udp_port.to_string()));
+            next_udp_port += 1;
+        }
+        let key = (sink.rtsp_port(), sink.rtsp_mount().to_string(), sink.rtsp_udp_port());
+        if seen.iter().any(|(port, mount, _): &(String, String, String)| *port == key.0 && *mount == key.1) {
+            return Err(format!("two rtsp sinks use rtsp://:{}{}", key.0, key.1));
+        }
+        if seen.iter().any(|(_, _, udp)| *udp == key.2) {
+            return Err(format!("two rtsp sinks use udp-port {}", key.2));
+        }
+        seen.push(key);
+    }
+    Ok(sinks)
+}
+
+/// Reads SINKS, falling back to the legacy RTSP_OUTPUT / SHOW_DISPLAY switches.
+pub fn sinks_from_env() -> Result<Vec<SinkSpec>, String> {
+    if let Ok(tuning) = env::var("ENCODER_TUNING") {
+        if Tuning::parse(&tuning).is_none() {
+            return Err(format!("ENCODER_TUNING must be latency or quality, got '{}'", tuning));
+        }
+    }
+    if let Ok(spec) = env::var("SINKS") {
+        return parse_sinks(&spec).map_err(|e| format!("SINKS: {}", e));
+    }
+
+    let show_display = env::var("SHOW_DISPLAY").unwrap_or_else(|_| "true".to_string()) == "true";
+    let mut sinks = Vec::new();
+    if env::var("RTSP_OUTPUT").is_ok() {
+        sinks.push(SinkSpec::new(SinkKind::Rtsp));
+    }
+    if show_display {
+        sinks.push(SinkSpec::new(SinkKind::Display));
+    }
+    if sinks.is_empty() {
+        sinks.push(SinkSpec::new(SinkKind::FakeSink));
+    }
+    Ok(sinks)
+}
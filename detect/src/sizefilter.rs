@@ -0,0 +1,140 @@
+use gstreamer::prelude::*;
+use std::env;
+
+/// Box limits for one class (or every class without a rule of its own). Sizes are in
+/// nvstreammux pixels, aspect is width / height.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SizeRule {
+    /// None for the `*` rule
+    pub class_id: Option<i32>,
+    pub min_width: Option<f32>,
+    pub max_width: Option<f32>,
+    pub min_height: Option<f32>,
+    pub max_height: Option<f32>,
+    pub min_area: Option<f32>,
+    pub max_area: Option<f32>,
+    pub min_aspect: Option<f32>,
+    pub max_aspect: Option<f32>,
+}
+
+impl SizeRule {
+    fn allows(&self, width: f32, height: f32) -> bool {
+        let within = |value: f32, min: Option<f32>, max: Option<f32>| {
+            min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+        };
+        let aspect = if height > 0.0 { width / height } else { 0.0 };
+        within(width, self.min_width, self.max_width)
+            && within(height, self.min_height, self.max_height)
+            && within(width * height, self.min_area, self.max_area)
+            && within(aspect, self.min_aspect, self.max_aspect)
+    }
+}
+
+/// Drops detections whose box is too small, too large or oddly shaped for their class,
+/// right after nvinfer, so tiny false positives and frame-sized boxes never reach the
+/// tracker, the OSD or the event outputs.
+pub struct SizeFilterConfig {
+    pub rules: Vec<SizeRule>,
+}
+
+impl SizeFilterConfig {
+    /// Reads SIZE_FILTERS: `;`-separated `<label or *>:<key>=<value>,...` entries with the
+    /// keys min_width, max_width, min_height, max_height, min_area, max_area,
+    /// min_aspect, max_aspect, e.g. `person:min_height=40,max_aspect=1.2;*:min_area=200`.
+    pub fn from_env(labels: &[String]) -> Option<SizeFilterConfig> {
+        let spec = env::var("SIZE_FILTERS").ok().filter(|v| !v.trim().is_empty())?;
+        let rules: Result<Vec<SizeRule>, String> = spec
+            .split(';')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| parse_rule(entry, labels))
+            .collect();
+        match rules {
+            Ok(rules) => Some(SizeFilterConfig { rules }),
+            Err(e) => {
+                eprintln!("Error: Invalid SIZE_FILTERS: {}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    /// The class's own rule, else the `*` rule.
+    fn rule(&self, class_id: i32) -> Option<&SizeRule> {
+        self.rules
+            .iter()
+            .find(|r| r.class_id == Some(class_id))
+            .or_else(|| self.rules.iter().find(|r| r.class_id.is_none()))
+    }
+
+    /// Human-readable summary, e.g. "2 rules (person, *)".
+    pub fn describe(&self, labels: &[String]) -> String {
+        let names: Vec<String> = self
+            .rules
+            .iter()
+            .map(|r| match r.class_id {
+                Some(id) => labels.get(id as usize).cloned().unwrap_or_else(|| id.to_string()),
+                None => "*".to_string(),
+            })
+            .collect();
+        format!("{} rule{} ({})", self.rules.len(), if self.rules.len() == 1 { "" } else { "s" }, names.join(", "))
+    }
+}
+
+fn parse_rule(entry: &str, labels: &[String]) -> Result<SizeRule, String> {
+    let (class, limits) = entry.split_once(':').ok_or_else(|| format!("'{}' has no <label>: prefix", entry))?;
+    let class = class.trim();
+    let class_id = if class == "*" {
+        None
+    } else {
+        let id = class
+            .parse::<i32>()
+            .ok()
+            .or_else(|| labels.iter().position(|l| l == class).map(|i| i as i32))
+            .ok_or_else(|| format!("unknown label '{}'", class))?;
+        Some(id)
+    };
+    let mut rule = SizeRule { class_id, ..Default::default() };
+    for limit in limits.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+        let (key, value) = limit.split_once('=').ok_or_else(|| format!("'{}' is not key=value", limit))?;
+        let value: f32 = value
+            .trim()
+            .parse()
+            .ok()
+            .filter(|v: &f32| *v >= 0.0)
+            .ok_or_else(|| format!("'{}' needs a non-negative number", limit))?;
+        let field = match key.trim() {
+            "min_width" => &mut rule.min_width,
+            "max_width" => &mut rule.max_width,
+            "min_height" => &mut rule.min_height,
+            "max_height" => &mut rule.max_height,
+            "min_area" => &mut rule.min_area,
+            "max_area" => &mut rule.max_area,
+            "min_aspect" => &mut rule.min_aspect,
+            "max_aspect" => &mut rule.max_aspect,
+            other => return Err(format!("unknown key '{}'", other)),
+        };
+        *field = Some(value);
+    }
+    Ok(rule)
+}
+
+/// Removes the objects outside their class's limits on nvinfer's (`pgie`) src pad.
+pub fn attach(bin: &gstreamer::Element, config: SizeFilterConfig) {
+    let Some(pgie) = bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("pgie")) else {
+        eprintln!("Warning: nvinfer not found, size filters disabled");
+        return;
+    };
+    let src_pad = pgie.static_pad("src").expect("nvinfer has a src pad");
+    src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            for frame in batch.frames() {
+                frame.retain_objects(|obj| {
+                    config
+                        .rule(obj.class_id)
+                        .is_none_or(|rule| rule.allows(obj.rect_params.width, obj.rect_params.height))
+                });
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
@@ -0,0 +1,178 @@
+use gstreamer::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+/// Hysteresis on tracked detections: a track must be seen in `min_frames` consecutive
+/// frames before anything downstream sees it, and a confirmed track that drops out is
+/// still drawn for `hold_frames` frames. Cuts flicker in the annotated output and
+/// one-frame false positives in the events.
+pub struct SmoothingConfig {
+    pub min_frames: u32,
+    pub hold_frames: u32,
+}
+
+impl SmoothingConfig {
+    /// Reads DETECTION_MIN_FRAMES (default 1) / DETECTION_HOLD_FRAMES (default 0); None when
+    /// both are at their defaults.
+    pub fn from_env() -> Option<SmoothingConfig> {
+        let number = |key: &str, default: u32| {
+            env::var(key)
+                .ok()
+                .map(|v| {
+                    v.parse().unwrap_or_else(|_| {
+                        eprintln!("Warning: Invalid {} '{}' (expected a frame count), using {}", key, v, default);
+                        default
+                    })
+                })
+                .unwrap_or(default)
+        };
+        let min_frames = number("DETECTION_MIN_FRAMES", 1).max(1);
+        let hold_frames = number("DETECTION_HOLD_FRAMES", 0);
+        if min_frames == 1 && hold_frames == 0 {
+            return None;
+        }
+        Some(SmoothingConfig { min_frames, hold_frames })
+    }
+}
+
+/// The tracks a frame shows only because they are held, attached to the frame as user
+/// meta so later probes (trajectories) follow the boxes nvdsosd actually draws.
+#[derive(Debug, Default, PartialEq)]
+pub struct HeldTracks(pub Vec<(u64, (f32, f32, f32, f32))>);
+
+impl nvds::UserMeta for HeldTracks {
+    const DESCRIPTOR: &'static str = "DETECT.HELD_TRACKS";
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.0.len() * 24);
+        for &(track_id, (left, top, width, height)) in &self.0 {
+            bytes.extend_from_slice(&track_id.to_le_bytes());
+            for value in [left, top, width, height] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<HeldTracks> {
+        if !bytes.len().is_multiple_of(24) {
+            return None;
+        }
+        let held = bytes
+            .chunks_exact(24)
+            .map(|entry| {
+                let float = |at: usize| f32::from_le_bytes(entry[at..at + 4].try_into().unwrap());
+                let track_id = u64::from_le_bytes(entry[..8].try_into().unwrap());
+                (track_id, (float(8), float(12), float(16), float(20)))
+            })
+            .collect();
+        Some(HeldTracks(held))
+    }
+}
+
+struct Track {
+    /// Consecutive frames seen, up to the last one
+    streak: u32,
+    last_frame: i32,
+    confirmed: bool,
+    /// Box and border color from the last frame, for drawing it while held
+    rect: (f32, f32, f32, f32),
+    border_width: u32,
+    color: nvds::NvOSD_ColorParams,
+}
+
+struct Smoother {
+    config: SmoothingConfig,
+    tracks: HashMap<(u32, u64), Track>,
+}
+
+impl Smoother {
+    fn on_frame(&mut self, frame: &mut nvds::NvDsFrameMeta) {
+        let (source_id, frame_num) = (frame.source_id, frame.frame_num);
+        let min_frames = self.config.min_frames;
+        let tracks = &mut self.tracks;
+        frame.retain_objects(|obj| {
+            // Untracked objects can't be followed over frames
+            let Some(id) = obj.track_id() else { return true };
+            let r = &obj.rect_params;
+            let track = tracks.entry((source_id, id)).or_insert(Track {
+                streak: 0,
+                last_frame: frame_num - 1,
+                confirmed: false,
+                rect: (0.0, 0.0, 0.0, 0.0),
+                border_width: 0,
+                color: r.border_color,
+            });
+            track.streak = if track.last_frame == frame_num - 1 { track.streak + 1 } else { 1 };
+            track.last_frame = frame_num;
+            track.rect = (r.left, r.top, r.width, r.height);
+            track.border_width = r.border_width;
+            track.color = r.border_color;
+            track.confirmed |= track.streak >= min_frames;
+            track.confirmed
+        });
+
+        // Confirmed tracks that just dropped out keep their last box for a while
+        let hold = self.config.hold_frames as i32;
+        let held: Vec<(u64, &Track)> = self
+            .tracks
+            .iter()
+            .filter(|((source, _), t)| *source == source_id && t.confirmed && t.last_frame < frame_num)
+            .filter(|(_, t)| frame_num - t.last_frame <= hold)
+            .map(|((_, id), t)| (*id, t))
+            .collect();
+        if !held.is_empty() {
+            frame.attach_user_meta(&HeldTracks(held.iter().map(|(id, t)| (*id, t.rect)).collect()));
+        }
+        for batch in held.chunks(nvds::MAX_ELEMENTS_IN_DISPLAY_META) {
+            let Some(display) = frame.add_display_meta() else { break };
+            for (rect, (_, track)) in display.rect_params.iter_mut().zip(batch) {
+                let (left, top, width, height) = track.rect;
+                rect.left = left;
+                rect.top = top;
+                rect.width = width;
+                rect.height = height;
+                rect.border_width = track.border_width.max(1);
+                rect.border_color = track.color;
+            }
+            display.num_rects = batch.len() as u32;
+        }
+        self.tracks.retain(|(source, _), t| *source != source_id || frame_num - t.last_frame <= hold.max(1));
+    }
+}
+
+/// Applies the hysteresis on the tracker's src pad, before analytics, OSD and events.
+pub fn attach(bin: &gstreamer::Element, config: SmoothingConfig) {
+    let Some(tracker) = bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("tracker")) else {
+        eprintln!("Warning: DETECTION_MIN_FRAMES/DETECTION_HOLD_FRAMES need TRACKER_CONFIG, smoothing disabled");
+        return;
+    };
+    let src_pad = tracker.static_pad("src").expect("nvtracker has a src pad");
+    let smoother = Mutex::new(Smoother { config, tracks: HashMap::new() });
+    src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            let mut smoother = smoother.lock().unwrap();
+            for frame in batch.frames() {
+                smoother.on_frame(frame);
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nvds::UserMeta;
+
+    #[test]
+    fn held_tracks_round_trip_through_bytes() {
+        let held = HeldTracks(vec![(7, (10.0, 20.5, 30.0, 40.25)), (u64::MAX, (0.0, -1.0, 2.0, 3.0))]);
+        let bytes = held.to_bytes();
+        assert_eq!(bytes.len(), 48);
+        assert_eq!(HeldTracks::from_bytes(&bytes), Some(held));
+        assert_eq!(HeldTracks::from_bytes(&[]), Some(HeldTracks::default()));
+        assert_eq!(HeldTracks::from_bytes(&bytes[..30]), None);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::launch;
+use gstreamer::prelude::*;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Latest annotated frame as JPEG, refreshed every SNAPSHOT_INTERVAL seconds, for
+/// `GET /snapshot.jpg` on CONTROL_PORT.
+pub struct SnapshotConfig {
+    pub interval: Duration,
+    /// Output frame size, which the tiles of a multi-source run divide up
+    width: u32,
+    height: u32,
+    sources: usize,
+}
+
+impl SnapshotConfig {
+    /// Reads SNAPSHOT_INTERVAL; None when unset.
+    pub fn from_env(sources: usize, width: u32, height: u32) -> Option<SnapshotConfig> {
+        let value = env::var("SNAPSHOT_INTERVAL").ok()?;
+        let interval = match value.trim().parse::<f64>() {
+            Ok(seconds) if seconds > 0.0 => Duration::from_secs_f64(seconds),
+            _ => {
+                eprintln!("Error: SNAPSHOT_INTERVAL must be a positive number of seconds, got '{}'", value);
+                std::process::exit(2);
+            }
+        };
+        if env::var("CONTROL_PORT").is_err() {
+            eprintln!("Warning: SNAPSHOT_INTERVAL is set but CONTROL_PORT isn't, snapshots are not served");
+        }
+        Some(SnapshotConfig { interval, width, height, sources })
+    }
+
+    /// Tiles of the output frame to encode on their own: one per source when the tiler
+    /// composites several, as `left:top:width:height` for nvvideoconvert's src-crop.
+    fn tiles(&self) -> Vec<String> {
+        if self.sources < 2 {
+            return Vec::new();
+        }
+        let (columns, rows) = launch::tile_grid(self.sources);
+        let (width, height) = (self.width / columns as u32, self.height / rows as u32);
+        (0..self.sources)
+            .map(|index| {
+                let (column, row) = ((index % columns) as u32, (index / columns) as u32);
+                format!("{}:{}:{}:{}", column * width, row * height, width, height)
+            })
+            .collect()
+    }
+
+    /// Post-OSD branches encoding the whole output frame (`snapshot_q`) and, with several
+    /// sources, each source's tile (`snapshot_q0`, `snapshot_q1`, ...). Their queues only let
+    /// a frame through once per interval, and only ever hold one.
+    pub fn branches(&self) -> Vec<String> {
+        let branch = |suffix: &str, crop: String| {
+            format!(
+                "queue name=snapshot_q{} leaky=downstream max-size-buffers=1 ! nvvideoconvert{} ! \
+                 video/x-raw,format=I420 ! jpegenc quality=85 ! fakesink name=snapshot{} sync=false async=false",
+                suffix, crop, suffix
+            )
+        };
+        let mut branches = vec![branch("", String::new())];
+        for (index, tile) in self.tiles().into_iter().enumerate() {
+            branches.push(branch(&index.to_string(), format!(" src-crop={}", tile)));
+        }
+        branches
+    }
+}
+
+/// The JPEGs the snapshot branches produced last: the whole frame, then one per source.
+#[derive(Clone, Default)]
+pub struct Snapshots {
+    latest: Arc<Mutex<Vec<Option<Vec<u8>>>>>,
+}
+
+impl Snapshots {
+    /// Rate-limits the snapshot branches of `pipeline` and keeps what their encoders put out.
+    pub fn attach(&self, pipeline: &gstreamer::Element, config: &SnapshotConfig) {
+        let Some(bin) = pipeline.downcast_ref::<gstreamer::Bin>() else { return };
+        let tiles = if config.sources < 2 { 0 } else { config.sources };
+        let suffixes: Vec<String> = std::iter::once(String::new()).chain((0..tiles).map(|i| i.to_string())).collect();
+        *self.latest.lock().unwrap() = vec![None; suffixes.len()];
+        for (slot, suffix) in suffixes.iter().enumerate() {
+            let (Some(queue), Some(sink)) =
+                (bin.by_name(&format!("snapshot_q{}", suffix)), bin.by_name(&format!("snapshot{}", suffix)))
+            else {
+                eprintln!("Warning: snapshot branch {} not found, its snapshots are disabled", suffix);
+                continue;
+            };
+            let interval = config.interval;
+            let last = Mutex::new(None::<Instant>);
+            queue.static_pad("src").expect("queue has a src pad").add_probe(
+                gstreamer::PadProbeType::BUFFER,
+                move |_pad, _info| {
+                    let mut last = last.lock().unwrap();
+                    if last.is_some_and(|at| at.elapsed() < interval) {
+                        return gstreamer::PadProbeReturn::Drop;
+                    }
+                    *last = Some(Instant::now());
+                    gstreamer::PadProbeReturn::Ok
+                },
+            );
+            let latest = self.latest.clone();
+            sink.static_pad("sink").expect("fakesink has a sink pad").add_probe(
+                gstreamer::PadProbeType::BUFFER,
+                move |_pad, info| {
+                    if let Some(gstreamer::PadProbeData::Buffer(buffer)) = &info.data {
+                        if let Ok(map) = buffer.map_readable() {
+                            latest.lock().unwrap()[slot] = Some(map.to_vec());
+                        }
+                    }
+                    gstreamer::PadProbeReturn::Ok
+                },
+            );
+        }
+    }
+
+    /// Latest JPEG of the whole output or of one source's tile (a single source's tile is
+    /// the whole output), or the HTTP status and message to answer with instead.
+    pub fn latest(&self, source: Option<usize>) -> Result<Vec<u8>, (&'static str, String)> {
+        let latest = self.latest.lock().unwrap();
+        if latest.is_empty() {
+            return Err(("404 Not Found", "error: snapshots are off (set SNAPSHOT_INTERVAL)".to_string()));
+        }
+        let slot = match source {
+            None => 0,
+            Some(0) if latest.len() == 1 => 0,
+            Some(index) if index + 1 < latest.len() => index + 1,
+            Some(index) => return Err(("404 Not Found", format!("error: no source {}", index))),
+        };
+        latest[slot].clone().ok_or(("503 Service Unavailable", "error: no frame yet".to_string()))
+    }
+}
@@ -0,0 +1,106 @@
+use gstreamer::prelude::*;
+use std::env;
+
+/// gie-unique-id of the first per-source model; the rest follow
+const FIRST_UNIQUE_ID: u32 = 20;
+
+/// A primary model that only some inputs are detected with.
+pub struct SourceModel {
+    pub model_config: String,
+    /// Positions in GST_DEVICE
+    pub sources: Vec<u32>,
+    pub unique_id: u32,
+}
+
+/// Different primary models for different inputs, e.g. PeopleNet on the indoor cameras
+/// and TrafficCamNet on the street camera. Each model is an nvinfer right after `pgie`;
+/// a probe after the last one keeps every model's objects only on its own sources, and
+/// pgie's (MODEL_CONFIG) only on the sources without a model of their own. Every nvinfer
+/// still runs on the whole batch, so each extra model costs a full inference pass.
+pub struct SourceModelConfig {
+    pub models: Vec<SourceModel>,
+}
+
+impl SourceModelConfig {
+    /// Reads SOURCE_MODELS: `;`-separated `<source,...>:<nvinfer config>` entries, e.g.
+    /// `0,1:/models/peoplenet.txt;2:/models/trafficcamnet.txt`.
+    pub fn from_env(source_count: usize) -> Option<SourceModelConfig> {
+        let spec = env::var("SOURCE_MODELS").ok().filter(|v| !v.trim().is_empty())?;
+        let mut models: Vec<SourceModel> = Vec::new();
+        for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((sources, model_config)) = entry.split_once(':') else {
+                eprintln!("Error: Invalid SOURCE_MODELS entry '{}' (expected <source,...>:<config>)", entry);
+                std::process::exit(2);
+            };
+            let mut ids = Vec::new();
+            for source in sources.split(',').map(str::trim) {
+                match source.parse::<u32>() {
+                    Ok(id) if (id as usize) < source_count => ids.push(id),
+                    _ => {
+                        eprintln!(
+                            "Error: Invalid source '{}' in SOURCE_MODELS (expected 0..{} for the GST_DEVICE inputs)",
+                            source,
+                            source_count.saturating_sub(1)
+                        );
+                        std::process::exit(2);
+                    }
+                }
+            }
+            if let Some(taken) = ids.iter().find(|id| models.iter().any(|m| m.sources.contains(id))) {
+                eprintln!("Error: Source {} has more than one model in SOURCE_MODELS", taken);
+                std::process::exit(2);
+            }
+            models.push(SourceModel {
+                model_config: model_config.trim().to_string(),
+                sources: ids,
+                unique_id: FIRST_UNIQUE_ID + models.len() as u32,
+            });
+        }
+        if models.is_empty() {
+            return None;
+        }
+        Some(SourceModelConfig { models })
+    }
+
+    /// The per-source nvinfer elements, in pipeline order.
+    pub fn stages(&self) -> Vec<String> {
+        self.models
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                format!(
+                    "nvinfer name=source_model{} config-file-path={} unique-id={}",
+                    i, m.model_config, m.unique_id
+                )
+            })
+            .collect()
+    }
+
+    /// Keeps each object only if it came from the model assigned to its frame's source.
+    fn keeps(&self, source_id: u32, component_id: i32) -> bool {
+        match self.models.iter().find(|m| m.unique_id as i32 == component_id) {
+            Some(model) => model.sources.contains(&source_id),
+            // pgie (or anything else upstream) covers the sources nobody else claimed
+            None => !self.models.iter().any(|m| m.sources.contains(&source_id)),
+        }
+    }
+}
+
+/// Sorts the objects per source on the last per-source nvinfer's src pad.
+pub fn attach(bin: &gstreamer::Element, config: SourceModelConfig) {
+    let name = format!("source_model{}", config.models.len() - 1);
+    let Some(last) = bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name(&name)) else {
+        eprintln!("Warning: {} element not found, per-source models disabled", name);
+        return;
+    };
+    let src_pad = last.static_pad("src").expect("nvinfer has a src pad");
+    src_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            for frame in batch.frames() {
+                let source_id = frame.source_id;
+                frame.retain_objects(|obj| config.keeps(source_id, obj.unique_component_id));
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
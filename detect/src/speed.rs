@@ -0,0 +1,166 @@
+use crate::calibration::{self, Calibration};
+use crate::detections::{FrameDetections, FrameObserver};
+use crate::events::{Event, Events};
+use std::collections::{HashMap, VecDeque};
+use std::env;
+
+/// A track's frames are dropped after this many frames without a detection
+const TRACK_TIMEOUT_FRAMES: i32 = 60;
+
+pub struct SpeedConfig {
+    pub calibration: Calibration,
+    /// km/h above which a "speeding" event fires, once per track
+    pub limit_kmh: Option<f64>,
+    /// Frames between the two positions a speed is measured over
+    pub window: usize,
+    /// Exponential smoothing factor for new measurements (0-1, higher reacts faster)
+    pub smoothing: f64,
+}
+
+impl SpeedConfig {
+    /// Enabled by CALIBRATION; reads SPEED_LIMIT, SPEED_WINDOW and SPEED_SMOOTHING.
+    pub fn from_env() -> Option<SpeedConfig> {
+        let calibration = calibration::calibration_from_env()?;
+        let limit_kmh = env::var("SPEED_LIMIT").ok().and_then(|v| v.parse().ok());
+        let window = env::var("SPEED_WINDOW")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v >= 2)
+            .unwrap_or(10);
+        let smoothing = env::var("SPEED_SMOOTHING")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0.0 && *v <= 1.0)
+            .unwrap_or(0.3);
+        Some(SpeedConfig { calibration, limit_kmh, window, smoothing })
+    }
+}
+
+struct TrackSpeed {
+    class_id: i32,
+    last_frame: i32,
+    /// (seconds, ground x, ground y)
+    positions: VecDeque<(f64, f64, f64)>,
+    smoothed: Option<f64>,
+    max: f64,
+    sum: f64,
+    samples: u32,
+    alerted: bool,
+}
+
+/// Real-world speed of tracked objects from the bottom center of their boxes
+/// projected onto the calibrated ground plane.
+pub struct SpeedEstimator {
+    config: SpeedConfig,
+    labels: Vec<String>,
+    events: Events,
+    tracks: HashMap<(u32, u64), TrackSpeed>,
+}
+
+impl SpeedEstimator {
+    pub fn new(config: SpeedConfig, labels: Vec<String>, events: Events) -> SpeedEstimator {
+        SpeedEstimator { config, labels, events, tracks: HashMap::new() }
+    }
+
+    fn label(&self, class_id: i32) -> String {
+        self.labels
+            .get(class_id as usize)
+            .cloned()
+            .unwrap_or_else(|| format!("class{}", class_id))
+    }
+
+    /// Summary event for a track that ended (lost by the tracker or end of stream).
+    fn track_ended(&self, source_id: u32, track_id: u64, track: &TrackSpeed) {
+        if track.samples == 0 {
+            return;
+        }
+        let event = Event::new("track_speed", source_id, track.last_frame)
+            .with("track_id", track_id)
+            .with("label", self.label(track.class_id))
+            .with("max_speed_kmh", track.max)
+            .with("mean_speed_kmh", track.sum / track.samples as f64);
+        self.events.lock().unwrap().emit(event);
+    }
+}
+
+impl FrameObserver for SpeedEstimator {
+    fn on_frame(&mut self, frame: &FrameDetections) {
+        let seconds = frame.seconds();
+        let (width, height) = (frame.pipeline_width.max(1) as f64, frame.pipeline_height.max(1) as f64);
+
+        for det in &frame.objects {
+            let Some(track_id) = det.track_id else { continue };
+            let x = (det.left + det.width / 2.0) as f64 / width;
+            let y = (det.top + det.height) as f64 / height;
+            let Some((gx, gy)) = self.config.calibration.to_ground(x, y) else { continue };
+
+            let track = self.tracks.entry((frame.source_id, track_id)).or_insert_with(|| TrackSpeed {
+                class_id: det.class_id,
+                last_frame: frame.frame_num,
+                positions: VecDeque::new(),
+                smoothed: None,
+                max: 0.0,
+                sum: 0.0,
+                samples: 0,
+                alerted: false,
+            });
+            track.last_frame = frame.frame_num;
+            track.positions.push_back((seconds, gx, gy));
+            if track.positions.len() > self.config.window {
+                track.positions.pop_front();
+            }
+            if track.positions.len() < self.config.window {
+                continue;
+            }
+
+            let (t0, x0, y0) = track.positions[0];
+            let (t1, x1, y1) = track.positions[track.positions.len() - 1];
+            if t1 <= t0 {
+                continue;
+            }
+            let kmh = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt() / (t1 - t0) * 3.6;
+            let speed = match track.smoothed {
+                Some(previous) => previous + self.config.smoothing * (kmh - previous),
+                None => kmh,
+            };
+            track.smoothed = Some(speed);
+            track.max = track.max.max(speed);
+            track.sum += speed;
+            track.samples += 1;
+
+            if let Some(limit) = self.config.limit_kmh {
+                if speed > limit && !track.alerted {
+                    track.alerted = true;
+                    let label = self.label(det.class_id);
+                    let event = Event::new("speeding", frame.source_id, frame.frame_num)
+                        .with("track_id", track_id)
+                        .with("label", label)
+                        .with("speed_kmh", speed)
+                        .with("limit_kmh", limit);
+                    self.events.lock().unwrap().emit(event);
+                }
+            }
+        }
+
+        let lost: Vec<(u32, u64)> = self
+            .tracks
+            .iter()
+            .filter(|((source, _), track)| {
+                *source == frame.source_id && frame.frame_num - track.last_frame > TRACK_TIMEOUT_FRAMES
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        for key in lost {
+            if let Some(track) = self.tracks.remove(&key) {
+                self.track_ended(key.0, key.1, &track);
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        for ((source_id, track_id), track) in &self.tracks {
+            self.track_ended(*source_id, *track_id, track);
+        }
+        self.tracks.clear();
+    }
+}
@@ -0,0 +1,189 @@
+use crate::detections::{FrameDetections, FrameObserver};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+/// Runtime state persisted across restarts as a `key=value` file.
+/// Keys are namespaced by feature, e.g. `tracker.next_id` or `count.person`.
+#[derive(Debug, Default)]
+pub struct RuntimeState {
+    values: BTreeMap<String, String>,
+}
+
+impl RuntimeState {
+    /// Loads `path`; a missing file is a fresh start, not an error.
+    pub fn load(path: &str) -> RuntimeState {
+        let mut state = RuntimeState::default();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return state,
+            Err(e) => {
+                eprintln!("Warning: Could not read state file {}: {}. Starting fresh.", path, e);
+                return state;
+            }
+        };
+        for line in contents.lines() {
+            if let Some((key, value)) = line.split_once('=') {
+                state.values.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+        state
+    }
+
+    /// Writes to a temporary file first so a crash mid-write never truncates the state.
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut out = String::new();
+        for (key, value) in &self.values {
+            out.push_str(&format!("{}={}\n", key, value));
+        }
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, out)?;
+        fs::rename(&tmp_path, path)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    pub fn get_u64(&self, key: &str) -> u64 {
+        self.get(key).and_then(|v| v.parse().ok()).unwrap_or(0)
+    }
+
+    pub fn set(&mut self, key: &str, value: impl ToString) {
+        self.values.insert(key.to_string(), value.to_string());
+    }
+}
+
+pub type SharedState = Arc<Mutex<RuntimeState>>;
+
+pub struct StateConfig {
+    pub path: String,
+    pub interval_secs: u32,
+}
+
+impl StateConfig {
+    /// Reads STATE_FILE / STATE_SAVE_INTERVAL. Returns None when persistence is disabled.
+    pub fn from_env() -> Option<StateConfig> {
+        let path = env::var("STATE_FILE").ok().filter(|p| !p.trim().is_empty())?;
+        let interval_secs = env::var("STATE_SAVE_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(10);
+        Some(StateConfig { path, interval_secs })
+    }
+}
+
+/// Keeps the tracker and counting entries of the state up to date.
+///
+/// nvtracker restarts its IDs at 0, so IDs seen by the other observers are offset
+/// by the saved `tracker.next_id`; a track ID is never reused across restarts.
+pub struct StateRecorder {
+    state: SharedState,
+    path: String,
+    labels: Vec<String>,
+    seen_tracks: HashSet<u64>,
+}
+
+impl StateRecorder {
+    pub fn new(state: SharedState, path: &str, labels: Vec<String>) -> StateRecorder {
+        StateRecorder {
+            state,
+            path: path.to_string(),
+            labels,
+            seen_tracks: HashSet::new(),
+        }
+    }
+}
+
+impl FrameObserver for StateRecorder {
+    fn on_frame(&mut self, frame: &FrameDetections) {
+        let mut state = self.state.lock().unwrap();
+        let frames = state.get_u64("frames") + 1;
+        state.set("frames", frames);
+        for det in &frame.objects {
+            // Totals count tracks, not per-frame boxes; without a tracker there's nothing stable to count
+            let Some(track_id) = det.track_id else { continue };
+            if !self.seen_tracks.insert(track_id) {
+                continue;
+            }
+            if track_id >= state.get_u64("tracker.next_id") {
+                state.set("tracker.next_id", track_id + 1);
+            }
+            let label = self
+                .labels
+                .get(det.class_id as usize)
+                .cloned()
+                .unwrap_or_else(|| format!("class{}", det.class_id));
+            let key = format!("count.{}", label);
+            let total = state.get_u64(&key) + 1;
+            state.set(&key, total);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Err(e) = self.state.lock().unwrap().save(&self.path) {
+            eprintln!("Warning: Failed to save state to {}: {}", self.path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn temp_path(name: &str) -> String {
+        let dir = env::temp_dir().join(format!("detect-state-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("state").to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn saved_state_loads_back() {
+        let path = temp_path("roundtrip");
+        let mut state = RuntimeState::default();
+        state.set("tracker.next_id", 42);
+        state.set("control.paused", true);
+        state.set("control.classes", "0,2");
+        state.save(&path).unwrap();
+
+        let loaded = RuntimeState::load(&path);
+        assert_eq!(loaded.get_u64("tracker.next_id"), 42);
+        assert_eq!(loaded.get("control.paused"), Some("true"));
+        assert_eq!(loaded.get("control.classes"), Some("0,2"));
+        assert_eq!(loaded.get_u64("frames"), 0);
+    }
+
+    #[test]
+    fn save_replaces_the_file_through_a_temporary() {
+        let path = temp_path("rename");
+        fs::write(&path, "count.person=1\n").unwrap();
+        let mut state = RuntimeState::load(&path);
+        state.set("count.person", 2);
+        state.save(&path).unwrap();
+
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "count.person=2\n");
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let path = temp_path("malformed");
+        fs::write(&path, "garbage\n\n tracker.next_id = 7 \nsource=rtsp://cam/stream?a=b\ncount.car=many\n").unwrap();
+        let state = RuntimeState::load(&path);
+        assert_eq!(state.get_u64("tracker.next_id"), 7);
+        // Only the first `=` separates key and value
+        assert_eq!(state.get("source"), Some("rtsp://cam/stream?a=b"));
+        assert_eq!(state.get("count.car"), Some("many"));
+        assert_eq!(state.get_u64("count.car"), 0);
+        assert_eq!(state.get("garbage"), None);
+    }
+
+    #[test]
+    fn a_missing_file_is_a_fresh_start() {
+        let state = RuntimeState::load(&temp_path("missing"));
+        assert_eq!(state.get("source"), None);
+    }
+}
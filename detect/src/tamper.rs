@@ -0,0 +1,225 @@
+use crate::events::{Event, Events};
+use crate::metrics::Metrics;
+use crate::processing::{self, MappedFrame};
+use std::collections::HashMap;
+use std::env;
+use std::time::{Duration, Instant};
+
+const TAMPER_ELEMENT: &str = "tamper_frames";
+
+/// Sampled points per frame: a coarse grid of luma values
+const GRID_COLS: u32 = 64;
+const GRID_ROWS: u32 = 36;
+
+/// Luma difference (0-255) at which a grid point counts as changed
+const CHANGED_LEVEL: f32 = 40.0;
+
+/// Luma standard deviation below which the view counts as covered (lens blocked, sprayed)
+const OCCLUDED_DEVIATION: f32 = 10.0;
+
+/// How quickly the reference follows gradual changes (lighting, parked cars) per sampled frame
+const LEARN_RATE: f32 = 0.01;
+
+/// Only every n-th frame of a source is sampled
+const FRAME_STRIDE: u64 = 5;
+
+pub struct TamperConfig {
+    /// How long a condition must last before it is an alert
+    pub duration: Duration,
+    /// Fraction of the grid that must differ from the reference for a scene change
+    pub scene_change: f32,
+    /// Sharpness, as a fraction of the reference's, below which the view counts as defocused
+    pub blur: f32,
+    /// Startup period the reference is learned over before anything is reported
+    pub learn: Duration,
+}
+
+impl TamperConfig {
+    /// Enabled by TAMPER_DETECT=true; reads TAMPER_SECONDS, TAMPER_SCENE_CHANGE, TAMPER_BLUR and TAMPER_LEARN_SECONDS.
+    pub fn from_env() -> Option<TamperConfig> {
+        if env::var("TAMPER_DETECT").map(|v| v != "true").unwrap_or(true) {
+            return None;
+        }
+        let number = |name: &str, default: f32| env::var(name).ok().and_then(|v| v.parse::<f32>().ok()).filter(|v| *v > 0.0).unwrap_or(default);
+        Some(TamperConfig {
+            duration: Duration::from_secs_f32(number("TAMPER_SECONDS", 3.0)),
+            scene_change: number("TAMPER_SCENE_CHANGE", 0.6).min(1.0),
+            blur: number("TAMPER_BLUR", 0.35).min(1.0),
+            learn: Duration::from_secs_f32(number("TAMPER_LEARN_SECONDS", 10.0)),
+        })
+    }
+
+    /// Mapped RGBA frames, for the image statistics.
+    pub fn stage(&self) -> String {
+        processing::mappable_stage(TAMPER_ELEMENT)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Tamper {
+    /// The whole view changed at once: camera turned, moved or swapped
+    Moved,
+    /// A uniform image: lens covered or sprayed
+    Blocked,
+    /// Edges faded: lens defocused, fogged or smeared
+    Defocused,
+}
+
+impl Tamper {
+    fn name(&self) -> &'static str {
+        match self {
+            Tamper::Moved => "moved",
+            Tamper::Blocked => "blocked",
+            Tamper::Defocused => "defocused",
+        }
+    }
+}
+
+/// Statistics of one sampled frame.
+struct Sample {
+    luma: Vec<f32>,
+    deviation: f32,
+    /// Mean fine-scale gradient around the grid points
+    sharpness: f32,
+}
+
+fn luma(pixels: &[u8], i: usize) -> f32 {
+    0.299 * pixels[i] as f32 + 0.587 * pixels[i + 1] as f32 + 0.114 * pixels[i + 2] as f32
+}
+
+fn sample(frame: &MappedFrame) -> Sample {
+    let (width, height, pitch) = (frame.width as usize, frame.height as usize, frame.pitch as usize);
+    let mut values = Vec::with_capacity((GRID_COLS * GRID_ROWS) as usize);
+    let mut sharpness = 0.0;
+    for row in 0..GRID_ROWS as usize {
+        let y = ((row * 2 + 1) * height / (GRID_ROWS as usize * 2)).min(height.saturating_sub(2));
+        for col in 0..GRID_COLS as usize {
+            let x = ((col * 2 + 1) * width / (GRID_COLS as usize * 2)).min(width.saturating_sub(2));
+            let i = y * pitch + x * 4;
+            let center = luma(frame.pixels, i);
+            // Neighbouring pixels at full resolution: blur flattens these first
+            sharpness += (luma(frame.pixels, i + 4) - center).abs() + (luma(frame.pixels, i + pitch) - center).abs();
+            values.push(center);
+        }
+    }
+    let n = values.len().max(1) as f32;
+    let mean = values.iter().sum::<f32>() / n;
+    let deviation = (values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n).sqrt();
+    Sample { luma: values, deviation, sharpness: sharpness / n }
+}
+
+struct SourceState {
+    started: Instant,
+    frames: u64,
+    reference: Vec<f32>,
+    sharpness: f32,
+    /// Since when each current condition has held
+    since: HashMap<Tamper, Instant>,
+    /// Conditions alerted and not cleared yet
+    active: Vec<Tamper>,
+}
+
+/// Compares every source's subsampled frames against a slowly learned reference view and
+/// raises `tamper` alerts when most of the view changes at once (moved), the image becomes
+/// uniform (blocked) or loses its fine detail (defocused) for TAMPER_SECONDS. Blocked and
+/// defocused end with `tamper_cleared`; after a move the new view becomes the reference.
+struct TamperDetector {
+    config: TamperConfig,
+    events: Events,
+    metrics: Metrics,
+    sources: HashMap<u32, SourceState>,
+}
+
+impl TamperDetector {
+    fn process(&mut self, frame: &mut MappedFrame) {
+        if frame.width < 4 || frame.height < 4 {
+            return;
+        }
+        let source_id = frame.meta.source_id;
+        let frame_num = frame.meta.frame_num;
+        let now = Instant::now();
+        let state = self.sources.entry(source_id).or_insert_with(|| SourceState {
+            started: now,
+            frames: 0,
+            reference: Vec::new(),
+            sharpness: 0.0,
+            since: HashMap::new(),
+            active: Vec::new(),
+        });
+        state.frames += 1;
+        if state.frames % FRAME_STRIDE != 1 {
+            return;
+        }
+        let sample = sample(frame);
+        if state.reference.is_empty() {
+            state.reference = sample.luma;
+            state.sharpness = sample.sharpness;
+            return;
+        }
+        let learning = now.duration_since(state.started) < self.config.learn;
+
+        let changed = sample.luma.iter().zip(&state.reference).filter(|(v, r)| (*v - *r).abs() > CHANGED_LEVEL).count();
+        let changed = changed as f32 / sample.luma.len() as f32;
+        let mut conditions = Vec::new();
+        if sample.deviation < OCCLUDED_DEVIATION {
+            conditions.push(Tamper::Blocked);
+        } else if changed >= self.config.scene_change {
+            conditions.push(Tamper::Moved);
+        }
+        if state.sharpness > 0.0 && sample.sharpness < state.sharpness * self.config.blur && !conditions.contains(&Tamper::Blocked) {
+            conditions.push(Tamper::Defocused);
+        }
+        if learning {
+            conditions.clear();
+        }
+        state.since.retain(|tamper, _| conditions.contains(tamper));
+        for tamper in &conditions {
+            state.since.entry(*tamper).or_insert(now);
+        }
+
+        let source = source_id.to_string();
+        for tamper in [Tamper::Moved, Tamper::Blocked, Tamper::Defocused] {
+            let held = state.since.get(&tamper).is_some_and(|at| now.duration_since(*at) >= self.config.duration);
+            let was = state.active.contains(&tamper);
+            if held && !was {
+                let event = Event::new("tamper", source_id, frame_num)
+                    .with("kind", tamper.name())
+                    .with("changed", changed as f64)
+                    .with("sharpness", (sample.sharpness / state.sharpness.max(f32::EPSILON)) as f64);
+                self.events.lock().unwrap().emit(event);
+                state.active.push(tamper);
+            } else if !held && was && !state.since.contains_key(&tamper) {
+                self.events
+                    .lock()
+                    .unwrap()
+                    .emit(Event::new("tamper_cleared", source_id, frame_num).with("kind", tamper.name()));
+                state.active.retain(|t| *t != tamper);
+            }
+            let labels = [("source", source.as_str()), ("kind", tamper.name())];
+            self.metrics.lock().unwrap().set("detect_tamper", &labels, if state.active.contains(&tamper) { 1.0 } else { 0.0 });
+        }
+
+        if state.active.contains(&Tamper::Moved) {
+            // The camera points somewhere else now: that view is the new normal
+            state.reference = sample.luma;
+            state.sharpness = sample.sharpness;
+            state.since.clear();
+            state.active.retain(|t| *t != Tamper::Moved);
+        } else if state.active.is_empty() && state.since.is_empty() {
+            for (reference, value) in state.reference.iter_mut().zip(&sample.luma) {
+                *reference += (value - *reference) * LEARN_RATE;
+            }
+            state.sharpness += (sample.sharpness - state.sharpness) * LEARN_RATE;
+        }
+    }
+}
+
+/// Runs the tamper checks on the mapped frames of the tamper stage.
+pub fn attach(bin: &gstreamer::Element, config: TamperConfig, events: Events, metrics: Metrics) {
+    metrics
+        .lock()
+        .unwrap()
+        .describe("detect_tamper", "gauge", "1 while a tamper condition (moved, blocked, defocused) is active");
+    let mut detector = TamperDetector { config, events, metrics, sources: HashMap::new() };
+    processing::attach_at(bin, TAMPER_ELEMENT, Box::new(move |frame: &mut MappedFrame| detector.process(frame)));
+}
@@ -0,0 +1,241 @@
+use crate::events::{Event, Events};
+use gstreamer::prelude::*;
+use std::env;
+use std::sync::Mutex;
+
+/// Weight of the previous frame's range when auto-ranging, so the image doesn't flicker
+const RANGE_SMOOTHING: f32 = 0.9;
+
+/// 16-bit grayscale (Y16) thermal camera input. Raw values are mapped to temperatures
+/// with a linear calibration, stretched to the full range and colored with a palette
+/// before the frames enter nvstreammux.
+pub struct ThermalConfig {
+    /// Temperature = raw * scale + offset (FLIR radiometric: 0.01 / -273.15 for °C)
+    pub scale: f32,
+    pub offset: f32,
+    /// Fixed raw range mapped to black..white; auto-ranged per frame when unset
+    pub range: Option<(u16, u16)>,
+    pub palette: Palette,
+    /// Temperature above which pixels count as a hot spot
+    pub alert_temp: Option<f32>,
+    /// Hot pixels needed for an alert
+    pub alert_pixels: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Palette {
+    WhiteHot,
+    BlackHot,
+    Iron,
+    Rainbow,
+}
+
+impl Palette {
+    fn parse(name: &str) -> Option<Palette> {
+        match name.to_lowercase().as_str() {
+            "whitehot" | "white" | "gray" => Some(Palette::WhiteHot),
+            "blackhot" | "black" => Some(Palette::BlackHot),
+            "iron" => Some(Palette::Iron),
+            "rainbow" => Some(Palette::Rainbow),
+            _ => None,
+        }
+    }
+
+    /// 256-entry RGB lookup table, interpolated between the palette's color stops.
+    fn table(self) -> Vec<[u8; 3]> {
+        let stops: &[(f32, [f32; 3])] = match self {
+            Palette::WhiteHot => &[(0.0, [0.0, 0.0, 0.0]), (1.0, [255.0, 255.0, 255.0])],
+            Palette::BlackHot => &[(0.0, [255.0, 255.0, 255.0]), (1.0, [0.0, 0.0, 0.0])],
+            Palette::Iron => &[
+                (0.0, [0.0, 0.0, 0.0]),
+                (0.25, [90.0, 0.0, 140.0]),
+                (0.5, [220.0, 40.0, 60.0]),
+                (0.75, [255.0, 160.0, 0.0]),
+                (1.0, [255.0, 255.0, 220.0]),
+            ],
+            Palette::Rainbow => &[
+                (0.0, [0.0, 0.0, 255.0]),
+                (0.25, [0.0, 255.0, 255.0]),
+                (0.5, [0.0, 255.0, 0.0]),
+                (0.75, [255.0, 255.0, 0.0]),
+                (1.0, [255.0, 0.0, 0.0]),
+            ],
+        };
+        (0..256)
+            .map(|i| {
+                let t = i as f32 / 255.0;
+                let upper = stops.iter().position(|(at, _)| *at >= t).unwrap_or(stops.len() - 1).max(1);
+                let ((t0, c0), (t1, c1)) = (stops[upper - 1], stops[upper]);
+                let f = ((t - t0) / (t1 - t0)).clamp(0.0, 1.0);
+                [0, 1, 2].map(|c| (c0[c] + (c1[c] - c0[c]) * f) as u8)
+            })
+            .collect()
+    }
+}
+
+impl ThermalConfig {
+    /// Reads THERMAL=true / THERMAL_SCALE / THERMAL_OFFSET / THERMAL_RANGE ("min,max" raw)
+    /// / THERMAL_PALETTE / THERMAL_ALERT_TEMP / THERMAL_ALERT_PIXELS.
+    pub fn from_env() -> Option<ThermalConfig> {
+        if env::var("THERMAL").map(|v| v != "true").unwrap_or(true) {
+            return None;
+        }
+        let scale = env::var("THERMAL_SCALE").ok().and_then(|v| v.parse().ok()).unwrap_or(0.01);
+        let offset = env::var("THERMAL_OFFSET").ok().and_then(|v| v.parse().ok()).unwrap_or(-273.15);
+        let range = env::var("THERMAL_RANGE").ok().and_then(|v| {
+            let parsed = v
+                .split_once(',')
+                .and_then(|(min, max)| Some((min.trim().parse::<u16>().ok()?, max.trim().parse::<u16>().ok()?)))
+                .filter(|(min, max)| min < max);
+            if parsed.is_none() {
+                eprintln!("Warning: Invalid THERMAL_RANGE '{}' (expected min,max raw values), auto-ranging", v);
+            }
+            parsed
+        });
+        let palette = match env::var("THERMAL_PALETTE") {
+            Ok(name) => Palette::parse(&name).unwrap_or_else(|| {
+                eprintln!("Warning: Unknown THERMAL_PALETTE '{}' (expected whitehot, blackhot, iron or rainbow), using iron", name);
+                Palette::Iron
+            }),
+            Err(_) => Palette::Iron,
+        };
+        let alert_temp = env::var("THERMAL_ALERT_TEMP").ok().and_then(|v| v.parse().ok());
+        let alert_pixels = env::var("THERMAL_ALERT_PIXELS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(1);
+        Some(ThermalConfig { scale, offset, range, palette, alert_temp, alert_pixels })
+    }
+
+    /// v4l2src for source `index` up to RGBA frames: the Y16 frames are normalized in place,
+    /// converted and then colored by the probes on the two identities.
+    pub fn source_element(&self, device: &str, index: usize) -> String {
+        format!(
+            "v4l2src device={} ! video/x-raw,format=GRAY16_LE ! identity name=thermal_raw{} silent=true ! \
+             videoconvert ! video/x-raw,format=RGBA ! identity name=thermal_color{} silent=true",
+            device, index, index
+        )
+    }
+}
+
+struct ThermalSource {
+    source_id: u32,
+    range: Option<(f32, f32)>,
+    hot: bool,
+    frame_num: i32,
+}
+
+impl ThermalSource {
+    /// Checks the hot spot threshold, then stretches the raw values to 0..65535.
+    fn process_raw(&mut self, config: &ThermalConfig, width: usize, data: &mut [u8], events: &Events) {
+        let frame_num = self.frame_num;
+        self.frame_num += 1;
+        let count = data.len() / 2;
+        let raw = |i: usize| u16::from_le_bytes([data[2 * i], data[2 * i + 1]]);
+
+        let (mut min, mut max, mut max_at) = (u16::MAX, 0u16, 0usize);
+        for i in 0..count {
+            let value = raw(i);
+            min = min.min(value);
+            if value > max {
+                max = value;
+                max_at = i;
+            }
+        }
+
+        if let Some(alert_temp) = config.alert_temp {
+            let hot_pixels = (0..count).filter(|&i| raw(i) as f32 * config.scale + config.offset > alert_temp).count();
+            let hot = hot_pixels >= config.alert_pixels;
+            if hot != self.hot {
+                self.hot = hot;
+                let height = (count / width.max(1)).max(1);
+                let kind = if hot { "thermal_hot_spot" } else { "thermal_hot_spot_cleared" };
+                events.lock().unwrap().emit(
+                    Event::new(kind, self.source_id, frame_num)
+                        .with("max_temp", max as f32 * config.scale + config.offset)
+                        .with("hot_pixels", hot_pixels as u64)
+                        .with("x", (max_at % width.max(1)) as f32 / width.max(1) as f32)
+                        .with("y", (max_at / width.max(1)) as f32 / height as f32)
+                        .with("threshold", alert_temp),
+                );
+            }
+        }
+
+        let (low, high) = match config.range {
+            Some((low, high)) => (low as f32, high as f32),
+            None => {
+                let (low, high) = match self.range {
+                    Some((low, high)) => (
+                        low * RANGE_SMOOTHING + min as f32 * (1.0 - RANGE_SMOOTHING),
+                        high * RANGE_SMOOTHING + max as f32 * (1.0 - RANGE_SMOOTHING),
+                    ),
+                    None => (min as f32, max as f32),
+                };
+                self.range = Some((low, high));
+                (low, high)
+            }
+        };
+        let span = (high - low).max(1.0);
+        for pixel in data.chunks_exact_mut(2) {
+            let value = u16::from_le_bytes([pixel[0], pixel[1]]) as f32;
+            let stretched = (((value - low) / span).clamp(0.0, 1.0) * 65535.0) as u16;
+            pixel.copy_from_slice(&stretched.to_le_bytes());
+        }
+    }
+}
+
+fn frame_width(pad: &gstreamer::Pad) -> Option<usize> {
+    let caps = pad.current_caps()?;
+    caps.structure(0)?.get::<i32>("width").ok().map(|w| w as usize)
+}
+
+/// Hooks the normalization and palette probes of every thermal source.
+pub fn attach(bin: &gstreamer::Element, config: ThermalConfig, source_count: usize, events: Events) {
+    let Some(bin) = bin.downcast_ref::<gstreamer::Bin>() else { return };
+    let config = std::sync::Arc::new(config);
+    let table = std::sync::Arc::new(config.palette.table());
+    for index in 0..source_count {
+        let (Some(raw), Some(color)) = (
+            bin.by_name(&format!("thermal_raw{}", index)),
+            bin.by_name(&format!("thermal_color{}", index)),
+        ) else {
+            continue;
+        };
+
+        let source = Mutex::new(ThermalSource { source_id: index as u32, range: None, hot: false, frame_num: 0 });
+        let (raw_config, raw_events) = (config.clone(), events.clone());
+        raw.static_pad("src").expect("identity has a src pad").add_probe(
+            gstreamer::PadProbeType::BUFFER,
+            move |pad, info| {
+                let width = frame_width(pad).unwrap_or(1);
+                if let Some(gstreamer::PadProbeData::Buffer(buffer)) = &mut info.data {
+                    if let Ok(mut map) = buffer.make_mut().map_writable() {
+                        source.lock().unwrap().process_raw(&raw_config, width, map.as_mut_slice(), &raw_events);
+                    }
+                }
+                gstreamer::PadProbeReturn::Ok
+            },
+        );
+
+        // With white-hot the gray RGBA frames are already right
+        if config.palette == Palette::WhiteHot {
+            continue;
+        }
+        let table = table.clone();
+        color.static_pad("src").expect("identity has a src pad").add_probe(
+            gstreamer::PadProbeType::BUFFER,
+            move |_pad, info| {
+                if let Some(gstreamer::PadProbeData::Buffer(buffer)) = &mut info.data {
+                    if let Ok(mut map) = buffer.make_mut().map_writable() {
+                        for pixel in map.as_mut_slice().chunks_exact_mut(4) {
+                            let rgb = table[pixel[0] as usize];
+                            pixel[..3].copy_from_slice(&rgb);
+                        }
+                    }
+                }
+                gstreamer::PadProbeReturn::Ok
+            },
+        );
+    }
+}
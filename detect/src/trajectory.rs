@@ -0,0 +1,141 @@
+use crate::smoothing::HeldTracks;
+use gstreamer::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::Mutex;
+
+/// Fading trail behind each tracked object, drawn by nvdsosd.
+pub struct TrajectoryConfig {
+    /// Points kept per track (one per frame)
+    pub length: usize,
+    pub line_width: u32,
+}
+
+impl TrajectoryConfig {
+    /// Reads TRAJECTORIES=true / TRAJECTORY_LENGTH / TRAJECTORY_WIDTH.
+    pub fn from_env() -> Option<TrajectoryConfig> {
+        if env::var("TRAJECTORIES").map(|v| v != "true").unwrap_or(true) {
+            return None;
+        }
+        let length = env::var("TRAJECTORY_LENGTH")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v >= 2)
+            .unwrap_or(30);
+        let line_width = env::var("TRAJECTORY_WIDTH").ok().and_then(|v| v.parse().ok()).unwrap_or(3);
+        Some(TrajectoryConfig { length, line_width })
+    }
+}
+
+struct Track {
+    last_frame: i32,
+    points: VecDeque<(f32, f32)>,
+}
+
+/// Ring buffer of recent positions keyed by (source, track ID).
+struct TrajectoryHistory {
+    config: TrajectoryConfig,
+    tracks: HashMap<(u32, u64), Track>,
+}
+
+impl TrajectoryHistory {
+    fn update(&mut self, source_id: u32, frame_num: i32, objects: &[(u64, f32, f32)]) {
+        for &(track_id, x, y) in objects {
+            let track = self.tracks.entry((source_id, track_id)).or_insert_with(|| Track {
+                last_frame: frame_num,
+                points: VecDeque::new(),
+            });
+            track.last_frame = frame_num;
+            track.points.push_back((x, y));
+            if track.points.len() > self.config.length {
+                track.points.pop_front();
+            }
+        }
+        // Tracks the tracker dropped fade out once they'd have scrolled off anyway
+        let length = self.config.length as i32;
+        self.tracks
+            .retain(|(source, _), track| *source != source_id || frame_num - track.last_frame <= length);
+    }
+
+    fn draw(&self, source_id: u32, frame: &mut nvds::NvDsFrameMeta) {
+        let mut lines = Vec::new();
+        for ((source, track_id), track) in &self.tracks {
+            if *source != source_id || track.points.len() < 2 {
+                continue;
+            }
+            let color = palette(*track_id);
+            let n = track.points.len();
+            for (i, (a, b)) in track.points.iter().zip(track.points.iter().skip(1)).enumerate() {
+                // Older segments are more transparent
+                let alpha = (i + 1) as f64 / (n - 1) as f64;
+                lines.push((*a, *b, nvds::NvOSD_ColorParams { alpha, ..color }));
+            }
+        }
+        for batch in lines.chunks(nvds::MAX_ELEMENTS_IN_DISPLAY_META) {
+            let Some(display) = frame.add_display_meta() else { return };
+            for (line, ((x1, y1), (x2, y2), color)) in display.line_params.iter_mut().zip(batch) {
+                line.x1 = x1.max(0.0) as u32;
+                line.y1 = y1.max(0.0) as u32;
+                line.x2 = x2.max(0.0) as u32;
+                line.y2 = y2.max(0.0) as u32;
+                line.line_width = self.config.line_width;
+                line.line_color = *color;
+            }
+            display.num_lines = batch.len() as u32;
+        }
+    }
+}
+
+/// A fixed color per track ID so neighbouring trails can be told apart.
+fn palette(track_id: u64) -> nvds::NvOSD_ColorParams {
+    const COLORS: [(f64, f64, f64); 6] = [
+        (0.0, 1.0, 0.0),
+        (1.0, 0.6, 0.0),
+        (0.0, 0.8, 1.0),
+        (1.0, 0.0, 1.0),
+        (1.0, 1.0, 0.0),
+        (1.0, 0.2, 0.2),
+    ];
+    let (red, green, blue) = COLORS[(track_id % COLORS.len() as u64) as usize];
+    nvds::NvOSD_ColorParams { red, green, blue, alpha: 1.0 }
+}
+
+/// Records the bottom center of every tracked box and adds trail lines to the frame
+/// before nvdsosd draws it, including the boxes detection smoothing holds. Untracked
+/// objects (no TRACKER_CONFIG) have no trail.
+pub fn attach(bin: &gstreamer::Element, config: TrajectoryConfig) {
+    let osd = match bin.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("osd")) {
+        Some(osd) => osd,
+        None => {
+            eprintln!("Warning: nvdsosd element not found, trajectories disabled");
+            return;
+        }
+    };
+    let sink_pad = osd.static_pad("sink").expect("nvdsosd has a sink pad");
+    let history = Mutex::new(TrajectoryHistory { config, tracks: HashMap::new() });
+
+    sink_pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, info| {
+        if let Some(batch) = nvds::BatchMeta::from_probe(info) {
+            let mut history = history.lock().unwrap();
+            for frame in batch.frames() {
+                let mut objects: Vec<(u64, f32, f32)> = frame
+                    .objects()
+                    .filter_map(|obj| {
+                        let rect = &obj.rect_params;
+                        obj.track_id().map(|id| (id, rect.left + rect.width / 2.0, rect.top + rect.height))
+                    })
+                    .collect();
+                // Boxes the smoother holds are still drawn, so their trails keep up with them
+                for HeldTracks(held) in frame.read_user_meta::<HeldTracks>() {
+                    objects.extend(held.into_iter().map(|(id, (left, top, width, height))| {
+                        (id, left + width / 2.0, top + height)
+                    }));
+                }
+                let (source_id, frame_num) = (frame.source_id, frame.frame_num);
+                history.update(source_id, frame_num, &objects);
+                history.draw(source_id, frame);
+            }
+        }
+        gstreamer::PadProbeReturn::Ok
+    });
+}
@@ -0,0 +1,198 @@
+use crate::metrics::Metrics;
+use crate::sinks::SinkSpec;
+use glib::translate::ToGlibPtr;
+use gstreamer::prelude::*;
+use gstreamer_rtsp_server::prelude::*;
+use gstreamer_rtsp_server::{ffi, gst_rtsp, RTSPClient, RTSPContext, RTSPServer};
+use std::ffi::CStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// One client playing one mount.
+struct Viewer {
+    /// The RTSPClient's address, which identifies it while connected
+    client: usize,
+    /// `<port><mount>`, e.g. `8555/ds-detect`
+    mount: String,
+    address: String,
+}
+
+/// The encode branch of an rtsp sink with `idle-linger`, stopped at its valve while nobody watches.
+struct Gate {
+    mount: String,
+    valve: gstreamer::Element,
+    linger: Duration,
+    idle_since: Option<Instant>,
+}
+
+/// RTSP clients per mount across every server, for the `viewers` command and
+/// `detect_rtsp_clients`, and the idle gates of the mounts that stop encoding without them.
+#[derive(Clone, Default)]
+pub struct Viewers {
+    /// Every served mount as `<port><mount>`
+    mounts: Arc<Mutex<Vec<String>>>,
+    viewers: Arc<Mutex<Vec<Viewer>>>,
+    gates: Arc<Mutex<Vec<Gate>>>,
+}
+
+/// Address of the client's RTSP connection ("?" before it has one).
+pub fn client_address(client: &RTSPClient) -> String {
+    // SAFETY: the client holds its connection for as long as the client lives; the IP
+    // string belongs to the connection and is copied right away
+    unsafe {
+        let connection = ffi::gst_rtsp_client_get_connection(client.to_glib_none().0);
+        if connection.is_null() {
+            return "?".to_string();
+        }
+        let ip = gst_rtsp::ffi::gst_rtsp_connection_get_ip(connection);
+        if ip.is_null() {
+            return "?".to_string();
+        }
+        CStr::from_ptr(ip).to_string_lossy().into_owned()
+    }
+}
+
+/// Path of the request being handled, e.g. `/ds-detect/stream=0`.
+pub fn request_path(ctx: &RTSPContext) -> Option<String> {
+    // SAFETY: the context and its URL are valid for the duration of the request signal
+    unsafe {
+        let ctx: *mut ffi::GstRTSPContext = ctx.to_glib_none().0;
+        let uri = (*ctx).uri;
+        if uri.is_null() || (*uri).abspath.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr((*uri).abspath).to_string_lossy().into_owned())
+    }
+}
+
+impl Viewers {
+    /// Follows the clients of `server` on `port`, which serves `sinks`. Sinks with an idle
+    /// linger start with their encode branch stopped; the first PLAY starts it.
+    pub fn track(&self, server: &RTSPServer, port: &str, sinks: &[&SinkSpec], pipeline: &gstreamer::Element) {
+        let bin = pipeline.downcast_ref::<gstreamer::Bin>();
+        for sink in sinks {
+            self.mounts.lock().unwrap().push(format!("{}{}", port, sink.rtsp_mount()));
+            let Some(linger) = sink.idle_linger() else { continue };
+            let Some(valve) = bin.and_then(|b| b.by_name(&sink.idle_valve_name())) else {
+                eprintln!("Warning: Idle valve for rtsp sink {} not found, it keeps encoding", sink.rtsp_mount());
+                continue;
+            };
+            self.gates.lock().unwrap().push(Gate {
+                mount: format!("{}{}", port, sink.rtsp_mount()),
+                valve,
+                linger: Duration::from_secs(linger as u64),
+                idle_since: Some(Instant::now()),
+            });
+        }
+        let mounts: Vec<String> = sinks.iter().map(|s| s.rtsp_mount().to_string()).collect();
+        let port = port.to_string();
+        let viewers = self.clone();
+        server.connect_client_connected(move |_server, client| {
+            let id = client.as_ptr() as usize;
+            let (play, mounts, port) = (viewers.clone(), mounts.clone(), port.clone());
+            client.connect_play_request(move |client, ctx| {
+                let Some(path) = request_path(ctx) else { return };
+                // Aggregate control paths carry the stream after the mount
+                let Some(mount) = mounts.iter().filter(|m| path.starts_with(m.as_str())).max_by_key(|m| m.len()) else {
+                    return;
+                };
+                play.play(id, &format!("{}{}", port, mount), client_address(client));
+            });
+            let teardown = viewers.clone();
+            client.connect_teardown_request(move |_client, _ctx| teardown.leave(id));
+            let closed = viewers.clone();
+            client.connect_closed(move |_client| closed.leave(id));
+        });
+    }
+
+    fn play(&self, client: usize, mount: &str, address: String) {
+        let mut viewers = self.viewers.lock().unwrap();
+        if !viewers.iter().any(|v| v.client == client && v.mount == mount) {
+            println!("RTSP viewer {} on {}", address, mount);
+            viewers.push(Viewer { client, mount: mount.to_string(), address });
+        }
+        for gate in self.gates.lock().unwrap().iter_mut().filter(|g| g.mount == mount) {
+            gate.idle_since = None;
+            if gate.valve.property::<bool>("drop") {
+                gate.valve.set_property("drop", false);
+                println!("RTSP {}: viewer connected, encoding resumed", mount);
+            }
+        }
+    }
+
+    fn leave(&self, client: usize) {
+        self.viewers.lock().unwrap().retain(|v| v.client != client);
+    }
+
+    /// Stops the encode of every gated mount that has had no viewers for its linger, sets
+    /// `detect_rtsp_clients{mount}` and expires the sessions of clients that vanished
+    /// without a TEARDOWN, once a second.
+    pub fn start(&self, servers: &[RTSPServer], metrics: Metrics) {
+        metrics.lock().unwrap().describe("detect_rtsp_clients", "gauge", "RTSP clients playing each mount");
+        let pools: Vec<_> = servers.iter().filter_map(|s| s.session_pool()).collect();
+        let viewers = self.clone();
+        glib::timeout_add_seconds(1, move || {
+            for pool in &pools {
+                pool.cleanup();
+            }
+            let counts = viewers.counts();
+            let mut metrics = metrics.lock().unwrap();
+            for (mount, count) in &counts {
+                metrics.set("detect_rtsp_clients", &[("mount", mount)], *count as f64);
+            }
+            for gate in viewers.gates.lock().unwrap().iter_mut() {
+                if counts.iter().any(|(mount, count)| *mount == gate.mount && *count > 0) {
+                    gate.idle_since = None;
+                    continue;
+                }
+                let idle_since = *gate.idle_since.get_or_insert_with(Instant::now);
+                if idle_since.elapsed() >= gate.linger && !gate.valve.property::<bool>("drop") {
+                    gate.valve.set_property("drop", true);
+                    println!("RTSP {}: no viewers for {}s, encoding stopped", gate.mount, gate.linger.as_secs());
+                }
+            }
+            glib::Continue(true)
+        });
+    }
+
+    /// Clients playing `mount` (`<port><mount>`).
+    pub fn count(&self, mount: &str) -> usize {
+        self.viewers.lock().unwrap().iter().filter(|v| v.mount == mount).count()
+    }
+
+    /// Whether a client from `address` already plays `mount`, e.g. one that is re-sending PLAY.
+    pub fn is_watching(&self, mount: &str, address: &str) -> bool {
+        self.viewers.lock().unwrap().iter().any(|v| v.mount == mount && v.address == address)
+    }
+
+    /// Clients per served mount.
+    fn counts(&self) -> Vec<(String, usize)> {
+        let viewers = self.viewers.lock().unwrap();
+        let mounts = self.mounts.lock().unwrap();
+        mounts.iter().map(|mount| (mount.clone(), viewers.iter().filter(|v| v.mount == *mount).count())).collect()
+    }
+
+    /// `8555/ds-detect: 2 (10.0.0.5, 10.0.0.7), 8555/preview: 0 (encoding stopped)`
+    pub fn describe(&self) -> String {
+        let counts = self.counts();
+        if counts.is_empty() {
+            return "no rtsp mounts".to_string();
+        }
+        let viewers = self.viewers.lock().unwrap();
+        let gates = self.gates.lock().unwrap();
+        let mounts: Vec<String> = counts
+            .iter()
+            .map(|(mount, count)| {
+                let addresses: Vec<&str> =
+                    viewers.iter().filter(|v| v.mount == *mount).map(|v| v.address.as_str()).collect();
+                let stopped = gates.iter().any(|g| g.mount == *mount && g.valve.property::<bool>("drop"));
+                match (addresses.is_empty(), stopped) {
+                    (true, true) => format!("{}: 0 (encoding stopped)", mount),
+                    (true, false) => format!("{}: 0", mount),
+                    (false, _) => format!("{}: {} ({})", mount, count, addresses.join(", ")),
+                }
+            })
+            .collect();
+        mounts.join(", ")
+    }
+}
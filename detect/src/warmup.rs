@@ -0,0 +1,176 @@
+use crate::doctor;
+use crate::enginecache::EngineCache;
+use crate::metrics::Metrics;
+use gstreamer::prelude::*;
+use std::env;
+use std::fs;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the wait for the first inference is reported
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What nvinfer does before its first inference for `model_config`: loading a serialized
+/// engine, or building one from the ONNX file when the engine doesn't exist yet.
+fn engine_status(model_config: &str) -> String {
+    let properties = fs::read_to_string(model_config).map(|c| doctor::infer_properties(&c)).unwrap_or_default();
+    let get = |key: &str| {
+        properties.iter().find(|(k, _)| k == key).map(|(_, v)| doctor::resolve(model_config, v))
+    };
+    match (get("model-engine-file"), get("onnx-file")) {
+        (Some(engine), _) if engine.exists() => format!("loading TensorRT engine {}", engine.display()),
+        (engine, Some(onnx)) => format!(
+            "building the TensorRT engine{} from {} (first run, can take minutes)",
+            engine.map(|e: PathBuf| format!(" {}", e.display())).unwrap_or_default(),
+            onnx.display()
+        ),
+        _ => "loading the model".to_string(),
+    }
+}
+
+/// Sends `state` (e.g. `READY=1`) to systemd when run as a Type=notify service.
+fn notify(state: &str) {
+    let Ok(path) = env::var("NOTIFY_SOCKET") else { return };
+    let Ok(socket) = UnixDatagram::unbound() else { return };
+    let sent = match path.strip_prefix('@') {
+        // Abstract socket namespace
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(name).and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr))
+        }
+        None => socket.send_to(state.as_bytes(), &path),
+    };
+    if let Err(e) = sent {
+        eprintln!("Warning: Could not notify systemd on {}: {}", path, e);
+    }
+}
+
+/// Whether the pipeline has finished its first inference. Until then the engine is still
+/// being built or loaded and nothing is detected, so `GET /ready` on METRICS_PORT answers
+/// 503, `detect_ready` is 0 and systemd hasn't been told READY=1.
+#[derive(Clone, Default)]
+pub struct Readiness {
+    ready: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    /// Reports what the engine is doing every PROGRESS_INTERVAL until the first batch leaves
+    /// pgie, then marks the service ready.
+    pub fn attach(&self, pipeline: &gstreamer::Element, model_config: &str, metrics: Metrics) {
+        {
+            let mut metrics = metrics.lock().unwrap();
+            metrics.describe("detect_ready", "gauge", "1 once the first inference has completed");
+            metrics.set("detect_ready", &[], 0.0);
+        }
+        let started = Instant::now();
+        let status = engine_status(model_config);
+        println!("Warming up: {}", status);
+        notify(&format!("STATUS=Warming up: {}", status));
+
+        let Some(pad) = pipeline
+            .downcast_ref::<gstreamer::Bin>()
+            .and_then(|b| b.by_name("pgie"))
+            .and_then(|pgie| pgie.static_pad("src"))
+        else {
+            eprintln!("Warning: pgie not found, marking the service ready right away");
+            self.ready.store(true, Ordering::Relaxed);
+            metrics.lock().unwrap().set("detect_ready", &[], 1.0);
+            notify("READY=1");
+            return;
+        };
+        let ready = self.ready.clone();
+        let ready_metrics = metrics.clone();
+        pad.add_probe(gstreamer::PadProbeType::BUFFER, move |_pad, _info| {
+            if !ready.swap(true, Ordering::Relaxed) {
+                println!("✓ First inference done after {:.1}s, ready", started.elapsed().as_secs_f64());
+                ready_metrics.lock().unwrap().set("detect_ready", &[], 1.0);
+                notify("READY=1\nSTATUS=Running");
+            }
+            gstreamer::PadProbeReturn::Remove
+        });
+
+        let ready = self.ready.clone();
+        thread::spawn(move || loop {
+            thread::sleep(PROGRESS_INTERVAL);
+            if ready.load(Ordering::Relaxed) {
+                return;
+            }
+            let waited = started.elapsed().as_secs();
+            println!("Still warming up after {}s: {}", waited, status);
+            notify(&format!("STATUS=Warming up for {}s: {}", waited, status));
+        });
+    }
+}
+
+/// `detect warmup [config...]`: builds (or loads) the TensorRT engine of each nvinfer config,
+/// MODEL_CONFIG by default, by running one test frame through it, so the first real start
+/// doesn't have to, e.g. in a container image build or a deployment's init step. With
+/// ENGINE_CACHE_DIR set the engines are built into (or loaded from) the cache.
+pub fn command(args: &[String], model_config: &str) -> ! {
+    if cfg!(not(feature = "deepstream")) {
+        println!("Built without the deepstream feature: there are no engines to warm up");
+        std::process::exit(0);
+    }
+    let configs: Vec<String> = if args.is_empty() { vec![model_config.to_string()] } else { args.to_vec() };
+    let cache = EngineCache::from_env();
+    for original in &configs {
+        if fs::metadata(original).is_err() {
+            eprintln!("Error: nvinfer config {} not found", original);
+            std::process::exit(2);
+        }
+        let cached = cache.as_ref().and_then(|cache| cache.prepare(original, original));
+        let config = cached.as_ref().map_or(original, |cached| &cached.config);
+        let started = Instant::now();
+        println!("{}: {}", original, engine_status(config));
+        let description = format!(
+            "videotestsrc num-buffers=1 ! nvvideoconvert ! video/x-raw(memory:NVMM),format=NV12 ! m.sink_0 \
+             nvstreammux name=m batch-size=1 width=1280 height=720 ! nvinfer config-file-path={} ! fakesink",
+            config
+        );
+        let pipeline = match gstreamer::parse_launch(&description) {
+            Ok(pipeline) => pipeline,
+            Err(e) => {
+                eprintln!("Error: Could not create the warm-up pipeline: {}", e);
+                std::process::exit(1);
+            }
+        };
+        if pipeline.set_state(gstreamer::State::Playing).is_err() {
+            eprintln!("Error: {} failed to start (see the nvinfer output above)", config);
+            std::process::exit(1);
+        }
+        let bus = pipeline.bus().expect("Pipeline should have a bus");
+        let mut failed = false;
+        loop {
+            let Some(msg) = bus.timed_pop(gstreamer::ClockTime::from_seconds(PROGRESS_INTERVAL.as_secs())) else {
+                println!("  still working after {}s...", started.elapsed().as_secs());
+                continue;
+            };
+            match msg.view() {
+                gstreamer::MessageView::Eos(..) => break,
+                gstreamer::MessageView::Error(err) => {
+                    eprintln!("Error: {} ({:?})", err.error(), err.debug());
+                    failed = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let _ = pipeline.set_state(gstreamer::State::Null);
+        if failed {
+            std::process::exit(1);
+        }
+        if let (Some(cache), Some(cached)) = (&cache, &cached) {
+            cache.store(cached);
+        }
+        println!("✓ {} ready after {:.1}s ({})", original, started.elapsed().as_secs_f64(), engine_status(config));
+    }
+    std::process::exit(0);
+}
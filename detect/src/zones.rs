@@ -0,0 +1,110 @@
+use std::env;
+
+/// A named region of interest. Points are normalized (0-1) so zones don't depend on
+/// the muxer resolution.
+#[derive(Clone, Debug)]
+pub struct Zone {
+    pub name: String,
+    pub points: Vec<(f32, f32)>,
+}
+
+impl Zone {
+    /// Whole-frame zone, used by analytics when no ZONES are configured.
+    pub fn full_frame() -> Zone {
+        Zone {
+            name: "frame".to_string(),
+            points: vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+        }
+    }
+
+    /// Even-odd ray casting; `x`/`y` are normalized.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        let mut inside = false;
+        let n = self.points.len();
+        for i in 0..n {
+            let (xi, yi) = self.points[i];
+            let (xj, yj) = self.points[(i + n - 1) % n];
+            if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// Top-left corner of the bounding box, normalized; where overlay text goes.
+    pub fn origin(&self) -> (f32, f32) {
+        let x = self.points.iter().map(|p| p.0).fold(1.0, f32::min);
+        let y = self.points.iter().map(|p| p.1).fold(1.0, f32::min);
+        (x, y)
+    }
+}
+
+/// For each zone, the indices of the grid cells whose centers lie inside it, for a
+/// row-major grid covering the frame (flow vectors, density maps).
+pub struct GridCells {
+    pub grid: (usize, usize),
+    pub cells: Vec<Vec<usize>>,
+}
+
+impl GridCells {
+    pub fn new(zones: &[Zone], rows: usize, cols: usize) -> GridCells {
+        let cells = zones
+            .iter()
+            .map(|zone| {
+                (0..rows * cols)
+                    .filter(|i| {
+                        let x = ((i % cols) as f32 + 0.5) / cols as f32;
+                        let y = ((i / cols) as f32 + 0.5) / rows as f32;
+                        zone.contains(x, y)
+                    })
+                    .collect()
+            })
+            .collect();
+        GridCells { grid: (rows, cols), cells }
+    }
+
+    /// Cached cells for a `rows` x `cols` grid, rebuilt when the grid size changes.
+    pub fn for_grid<'a>(cache: &'a mut Option<GridCells>, zones: &[Zone], rows: usize, cols: usize) -> &'a [Vec<usize>] {
+        if cache.as_ref().map(|c| c.grid != (rows, cols)).unwrap_or(true) {
+            *cache = Some(GridCells::new(zones, rows, cols));
+        }
+        &cache.as_ref().unwrap().cells
+    }
+}
+
+/// Parses `name:x1,y1,x2,y2,...` entries separated by `;`, with normalized coordinates.
+pub fn parse_zones(spec: &str) -> Result<Vec<Zone>, String> {
+    let mut zones = Vec::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, coords) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("zone '{}' must be name:x1,y1,x2,y2,...", entry))?;
+        let values = coords
+            .split(',')
+            .map(|v| v.trim().parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|_| format!("zone '{}': coordinates must be numbers", name))?;
+        if values.len() < 6 || values.len() % 2 != 0 {
+            return Err(format!("zone '{}': needs at least 3 x,y points", name));
+        }
+        if values.iter().any(|v| !(0.0..=1.0).contains(v)) {
+            return Err(format!("zone '{}': coordinates are normalized and must be within 0-1", name));
+        }
+        zones.push(Zone {
+            name: name.trim().to_string(),
+            points: values.chunks(2).map(|p| (p[0], p[1])).collect(),
+        });
+    }
+    Ok(zones)
+}
+
+/// Reads ZONES; exits on a malformed spec like the other startup configuration.
+pub fn zones_from_env() -> Vec<Zone> {
+    match env::var("ZONES") {
+        Ok(spec) => parse_zones(&spec).unwrap_or_else(|e| {
+            eprintln!("Error: ZONES: {}", e);
+            std::process::exit(2);
+        }),
+        Err(_) => Vec::new(),
+    }
+}
@@ -0,0 +1,68 @@
+fn main() {
+    #[cfg(feature = "deepstream")]
+    deepstream();
+}
+
+/// Links the DeepStream metadata libraries and generates bindings for the metadata API from
+/// the headers of the same install, so the layouts always match the libraries linked.
+#[cfg(feature = "deepstream")]
+fn deepstream() {
+    use std::env;
+    use std::path::{Path, PathBuf};
+
+    let deepstream_dir = env::var("DEEPSTREAM_DIR").unwrap_or_else(|_| "/opt/nvidia/deepstream/deepstream".to_string());
+    println!("cargo:rerun-if-env-changed=DEEPSTREAM_DIR");
+    // libnvdsgst_meta / libnvds_meta live in the DeepStream install, not on the default linker path
+    println!("cargo:rustc-link-search=native={}/lib", deepstream_dir);
+    println!("cargo:rustc-link-lib=nvds_meta");
+    println!("cargo:rustc-link-lib=nvdsgst_meta");
+
+    let includes = Path::new(&deepstream_dir).join("sources/includes");
+    if !includes.join("nvdsmeta.h").exists() {
+        panic!(
+            "DeepStream headers not found in {} (set DEEPSTREAM_DIR, or build with --no-default-features)",
+            includes.display()
+        );
+    }
+    // nvdsmeta.h and gstnvdsinfer.h include glib and GStreamer headers
+    let gstreamer = pkg_config::Config::new()
+        .cargo_metadata(false)
+        .probe("gstreamer-1.0")
+        .expect("pkg-config could not find gstreamer-1.0");
+
+    let mut builder = bindgen::Builder::default()
+        .header_contents(
+            "nvds_wrapper.h",
+            "#include <gstnvdsmeta.h>\n#include <nvds_opticalflow_meta.h>\n#include <gstnvdsinfer.h>\n",
+        )
+        .clang_arg(format!("-I{}", includes.display()))
+        .clang_args(gstreamer.include_paths.iter().map(|path| format!("-I{}", path.display())))
+        .allowlist_type(
+            "NvDs(Base|Batch|Frame|Object|Classifier|Display|User|OpticalFlow)Meta|NvDsLabelInfo|NvDsMetaType\
+             |NvOFFlowVector|NvDsInfer(TensorMeta|LayerInfo|Dims|NetworkInfo|DataType)",
+        )
+        .allowlist_function(
+            "gst_buffer_get_nvds_batch_meta|nvds_acquire_(display|user)_meta_from_pool|nvds_add_(display|user)_meta_to_frame\
+             |nvds_add_user_meta_to_obj|nvds_remove_obj_meta_from_frame|nvds_get_user_meta_type",
+        )
+        // glib and GStreamer structs come from their -sys crates, so they stay interchangeable
+        .blocklist_type("_?G[A-Z].*")
+        .raw_line("use glib::ffi::*;")
+        .raw_line("use gstreamer::ffi::GstBuffer;")
+        .prepend_enum_name(false)
+        .derive_default(true)
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()));
+    // nvdsinfer.h pulls in CUDA types on some releases
+    let cuda_include = Path::new(&env::var("CUDA_HOME").unwrap_or_else(|_| "/usr/local/cuda".to_string())).join("include");
+    println!("cargo:rerun-if-env-changed=CUDA_HOME");
+    if cuda_include.exists() {
+        builder = builder.clang_arg(format!("-I{}", cuda_include.display()));
+    }
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("nvds_sys.rs");
+    builder
+        .generate()
+        .expect("could not generate DeepStream bindings (bindgen needs libclang, e.g. apt install libclang-dev)")
+        .write_to_file(&out)
+        .expect("could not write DeepStream bindings");
+}
@@ -0,0 +1,533 @@
+// Safe access to the parts of DeepStream's NvDsMeta API the probes use, shared by the
+// detect and record binaries. The raw declarations are generated with bindgen from the
+// headers of the DeepStream install being linked (see build.rs), so layouts can't drift
+// from the libraries; builds without the deepstream feature use hand-written stand-ins
+// with the same names (standin.rs). Probes start from `BatchMeta::from_probe`; the frames,
+// objects and everything reached from them borrow from the probe's buffer.
+
+use glib::ffi::{gpointer, GList};
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_float, c_int};
+
+/// Generated from the DeepStream headers by build.rs.
+#[cfg(feature = "deepstream")]
+#[allow(
+    non_camel_case_types,
+    non_snake_case,
+    non_upper_case_globals,
+    dead_code,
+    unused_imports,
+    clippy::all
+)]
+mod sys {
+    include!(concat!(env!("OUT_DIR"), "/nvds_sys.rs"));
+}
+
+#[cfg(not(feature = "deepstream"))]
+#[allow(non_camel_case_types, non_snake_case)]
+#[path = "standin.rs"]
+mod sys;
+
+mod usermeta;
+
+pub use sys::{
+    gst_buffer_get_nvds_batch_meta, nvds_acquire_display_meta_from_pool, nvds_acquire_user_meta_from_pool,
+    nvds_add_display_meta_to_frame, nvds_add_user_meta_to_frame, nvds_add_user_meta_to_obj, nvds_get_user_meta_type,
+    nvds_remove_obj_meta_from_frame, NvBbox_Coords, NvDsBaseMeta, NvDsBatchMeta, NvDsClassifierMeta, NvDsComp_BboxInfo,
+    NvDsDisplayMeta, NvDsFrameMeta, NvDsInferDataType, NvDsInferDims, NvDsInferLayerInfo, NvDsInferNetworkInfo,
+    NvDsInferTensorMeta, NvDsLabelInfo, NvDsMetaType, NvDsObjectMeta, NvDsOpticalFlowMeta, NvDsUserMeta, NvOFFlowVector,
+    NvOSD_ArrowParams, NvOSD_CircleParams, NvOSD_ColorParams, NvOSD_FontParams, NvOSD_LineParams, NvOSD_MaskParams,
+    NvOSD_RectParams, NvOSD_TextParams, NVDSINFER_TENSOR_OUTPUT_META, NVDS_GST_CUSTOM_META, NVDS_OPTICAL_FLOW_META,
+    NVDS_RESERVED_META, NVDS_START_USER_META,
+};
+pub use usermeta::UserMeta;
+
+pub const MAX_USER_FIELDS: usize = 4;
+pub const MAX_RESERVED_FIELDS: usize = 4;
+pub const MAX_LABEL_SIZE: usize = 128;
+pub const MAX_ELEMENTS_IN_DISPLAY_META: usize = 16;
+pub const NVDS_INFER_MAX_DIMS: usize = 8;
+
+/// object_id value nvinfer assigns when no tracker is present.
+pub const UNTRACKED_OBJECT_ID: u64 = u64::MAX;
+
+/// NvDsInferDataType value of 32-bit float layers
+pub const NVDS_INFER_FLOAT: NvDsInferDataType = 0;
+
+impl NvOSD_TextParams {
+    /// White label on a translucent black box at (`x`, `y`). The text is copied with
+    /// g_strdup because DeepStream frees it with the display meta.
+    pub fn set(&mut self, text: &str, x: u32, y: u32, font_size: u32) {
+        let text = std::ffi::CString::new(text.replace('\0', "")).unwrap_or_default();
+        // SAFETY: g_strdup copies the NUL-terminated string
+        self.display_text = unsafe { glib::ffi::g_strdup(text.as_ptr()) };
+        self.x_offset = x;
+        self.y_offset = y;
+        self.font_params.font_name = c"Serif".as_ptr() as *mut c_char;
+        self.font_params.font_size = font_size;
+        self.font_params.font_color = NvOSD_ColorParams { red: 1.0, green: 1.0, blue: 1.0, alpha: 1.0 };
+        self.set_bg_clr = 1;
+        self.text_bg_clr = NvOSD_ColorParams { red: 0.0, green: 0.0, blue: 0.0, alpha: 0.6 };
+    }
+}
+
+impl NvDsInferTensorMeta {
+    pub fn layers(&self) -> &[NvDsInferLayerInfo] {
+        if self.output_layers_info.is_null() {
+            return &[];
+        }
+        // SAFETY: nvinfer fills num_output_layers entries
+        unsafe { std::slice::from_raw_parts(self.output_layers_info, self.num_output_layers as usize) }
+    }
+
+    /// Host copy of output layer `index` as floats, with its dims.
+    pub fn float_output(&self, index: usize) -> Option<(&NvDsInferDims, &[f32])> {
+        let layer = self.layers().get(index)?;
+        if layer.dataType != NVDS_INFER_FLOAT || self.out_buf_ptrs_host.is_null() {
+            return None;
+        }
+        // SAFETY: out_buf_ptrs_host has one host buffer per output layer, numElements long
+        unsafe {
+            let data = *self.out_buf_ptrs_host.add(index) as *const f32;
+            if data.is_null() {
+                return None;
+            }
+            let dims = layer.infer_dims();
+            Some((dims, std::slice::from_raw_parts(data, dims.numElements as usize)))
+        }
+    }
+}
+
+impl NvDsInferLayerInfo {
+    /// `inferDims`, which shares an anonymous union with the deprecated `dims`.
+    pub fn infer_dims(&self) -> &NvDsInferDims {
+        // SAFETY: both union members are NvDsInferDims
+        unsafe { &self.__bindgen_anon_1.inferDims }
+    }
+
+    pub fn name(&self) -> String {
+        if self.layerName.is_null() {
+            return String::new();
+        }
+        // SAFETY: layer names are NUL-terminated strings owned by nvinfer
+        unsafe { std::ffi::CStr::from_ptr(self.layerName) }.to_string_lossy().into_owned()
+    }
+}
+
+/// Iterates the `data` pointers of a GList as typed references.
+pub struct GListIter<'a, T> {
+    node: *mut GList,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> GListIter<'a, T> {
+    /// # Safety
+    /// `list` must be null or a valid GList whose data pointers point to `T`,
+    /// and the list must stay alive and unmodified for `'a`.
+    pub unsafe fn new(list: *mut GList) -> Self {
+        GListIter { node: list, _marker: PhantomData }
+    }
+}
+
+impl<'a, T> Iterator for GListIter<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.node.is_null() {
+            // SAFETY: upheld by the contract of `GListIter::new`
+            unsafe {
+                let data = (*self.node).data as *mut T;
+                self.node = (*self.node).next;
+                if !data.is_null() {
+                    return Some(&mut *data);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The batch nvstreammux attached to a buffer: its frames, their objects and display meta.
+pub type BatchMeta = NvDsBatchMeta;
+pub type FrameMeta = NvDsFrameMeta;
+pub type ObjectMeta = NvDsObjectMeta;
+pub type Frames<'a> = GListIter<'a, FrameMeta>;
+pub type Objects<'a> = GListIter<'a, ObjectMeta>;
+
+impl BatchMeta {
+    /// The batch meta of the buffer passing a pad probe, if it carries one. It borrows the
+    /// probe info mutably, so it can't outlive the buffer and a probe can't hold it twice.
+    ///
+    /// DeepStream mutates metadata in place even on buffers that aren't writable; the
+    /// remaining rule is that probes on parallel branches after a tee must not both edit
+    /// the same batch.
+    pub fn from_probe<'a>(info: &'a mut gstreamer::PadProbeInfo) -> Option<&'a mut BatchMeta> {
+        probe_buffer(info)?.1
+    }
+}
+
+/// The buffer passing a pad probe and its batch meta, for probes that also read the buffer
+/// itself (timestamps, NVMM surfaces). Same borrowing rules as `BatchMeta::from_probe`.
+pub fn probe_buffer<'a>(
+    info: &'a mut gstreamer::PadProbeInfo,
+) -> Option<(&'a gstreamer::BufferRef, Option<&'a mut BatchMeta>)> {
+    let Some(gstreamer::PadProbeData::Buffer(buffer)) = &info.data else { return None };
+    // SAFETY: the buffer is owned by `info`, which stays mutably borrowed for 'a; the batch
+    // meta is separate from the buffer's memory, which is all the BufferRef can reach
+    let batch = unsafe { batch_meta(buffer.as_ptr() as *mut gstreamer::ffi::GstBuffer) };
+    Some((buffer, batch))
+}
+
+/// # Safety
+/// No other reference to the same batch meta may be alive, and `buffer` must stay alive
+/// for `'a`; `probe_buffer` ties both to the probe info.
+unsafe fn batch_meta<'a>(buffer: *mut gstreamer::ffi::GstBuffer) -> Option<&'a mut NvDsBatchMeta> {
+    gst_buffer_get_nvds_batch_meta(buffer).as_mut()
+}
+
+impl NvDsBatchMeta {
+    pub fn frames(&mut self) -> Frames<'_> {
+        // SAFETY: frame_meta_list is owned by the batch meta
+        unsafe { GListIter::new(self.frame_meta_list) }
+    }
+}
+
+impl NvDsFrameMeta {
+    pub fn objects(&mut self) -> Objects<'_> {
+        // SAFETY: obj_meta_list is owned by the frame meta
+        unsafe { GListIter::new(self.obj_meta_list) }
+    }
+
+    /// Removes every object `keep` returns false for (they go back to the batch pool, so
+    /// nothing downstream sees them) and returns how many were removed.
+    pub fn retain_objects(&mut self, mut keep: impl FnMut(&NvDsObjectMeta) -> bool) -> usize {
+        // Collected first: removing unlinks the node the iterator would step from
+        let removed: Vec<*mut NvDsObjectMeta> = self
+            .objects()
+            .filter(|obj| !keep(obj))
+            .map(|obj| obj as *mut NvDsObjectMeta)
+            .collect();
+        for obj in &removed {
+            // SAFETY: every pointer is an object of this frame, removed exactly once
+            unsafe { nvds_remove_obj_meta_from_frame(self, *obj) };
+        }
+        removed.len()
+    }
+
+    pub fn user_meta(&mut self) -> GListIter<'_, NvDsUserMeta> {
+        // SAFETY: frame_user_meta_list is owned by the frame meta
+        unsafe { GListIter::new(self.frame_user_meta_list) }
+    }
+
+    /// Takes an empty display meta from the batch pool and attaches it to this frame;
+    /// whatever is filled in before the buffer reaches nvdsosd gets drawn.
+    pub fn add_display_meta(&mut self) -> Option<&mut NvDsDisplayMeta> {
+        // SAFETY: batch_meta is the batch this frame belongs to; the pool is locked internally
+        // and the display meta is owned by the frame once added
+        unsafe {
+            let display = nvds_acquire_display_meta_from_pool(self.base_meta.batch_meta);
+            if display.is_null() {
+                return None;
+            }
+            nvds_add_display_meta_to_frame(self, display);
+            display.as_mut()
+        }
+    }
+
+    /// Output tensors of the primary-mode nvinfer with gie-unique-id `unique_id`.
+    pub fn tensor_meta(&mut self, unique_id: u32) -> Option<&NvDsInferTensorMeta> {
+        find_tensor_meta(self.user_meta(), unique_id)
+    }
+
+    /// The nvof flow grid attached to this frame, as (rows, cols, vectors).
+    pub fn optical_flow(&mut self) -> Option<(usize, usize, &[NvOFFlowVector])> {
+        let meta = self
+            .user_meta()
+            .find(|m| m.base_meta.meta_type == NVDS_OPTICAL_FLOW_META)?;
+        // SAFETY: nvof stores an NvDsOpticalFlowMeta whose data holds rows * cols vectors
+        unsafe {
+            let flow = (meta.user_meta_data as *const NvDsOpticalFlowMeta).as_ref()?;
+            if flow.data.is_null() {
+                return None;
+            }
+            let (rows, cols) = (flow.rows as usize, flow.cols as usize);
+            let vectors = std::slice::from_raw_parts(flow.data as *const NvOFFlowVector, rows * cols);
+            Some((rows, cols, vectors))
+        }
+    }
+}
+
+fn find_tensor_meta<'a>(
+    mut user_meta: GListIter<'a, NvDsUserMeta>,
+    unique_id: u32,
+) -> Option<&'a NvDsInferTensorMeta> {
+    user_meta
+        .find(|m| {
+            m.base_meta.meta_type == NVDSINFER_TENSOR_OUTPUT_META
+                // SAFETY: tensor output user meta holds an NvDsInferTensorMeta
+                && unsafe { (*(m.user_meta_data as *const NvDsInferTensorMeta)).unique_id } == unique_id
+        })
+        // SAFETY: as above
+        .and_then(|m| unsafe { (m.user_meta_data as *const NvDsInferTensorMeta).as_ref() })
+}
+
+impl NvDsLabelInfo {
+    /// Long results are stored in pResult_label instead of the inline array.
+    pub fn label(&self) -> String {
+        let ptr = if self.pResult_label.is_null() { self.result_label.as_ptr() } else { self.pResult_label };
+        // SAFETY: both are NUL-terminated; result_label is fixed-size and zero-initialized
+        unsafe { std::ffi::CStr::from_ptr(ptr) }.to_string_lossy().into_owned()
+    }
+}
+
+impl NvDsObjectMeta {
+    pub fn user_meta(&mut self) -> GListIter<'_, NvDsUserMeta> {
+        // SAFETY: obj_user_meta_list is owned by the object meta
+        unsafe { GListIter::new(self.obj_user_meta_list) }
+    }
+
+    /// Output tensors of the secondary-mode nvinfer with gie-unique-id `unique_id`.
+    pub fn tensor_meta(&mut self, unique_id: u32) -> Option<&NvDsInferTensorMeta> {
+        find_tensor_meta(self.user_meta(), unique_id)
+    }
+
+    pub fn classifiers(&mut self) -> GListIter<'_, NvDsClassifierMeta> {
+        // SAFETY: classifier_meta_list is owned by the object meta
+        unsafe { GListIter::new(self.classifier_meta_list) }
+    }
+
+    /// Result label and probability the classifier with gie-unique-id `unique_id` gave this
+    /// object, e.g. the plate text of an LPR model.
+    pub fn classifier_result(&mut self, unique_id: u32) -> Option<(String, f32)> {
+        let classifier = self.classifiers().find(|c| c.unique_component_id == unique_id as c_int)?;
+        // SAFETY: label_info_list is owned by the classifier meta
+        let label = unsafe { GListIter::<NvDsLabelInfo>::new(classifier.label_info_list) }.next()?;
+        Some((label.label(), label.result_prob))
+    }
+
+    /// Every label the classifier with gie-unique-id `unique_id` attached, for multi-label
+    /// classifiers (e.g. "helmet", "vest").
+    pub fn classifier_labels(&mut self, unique_id: u32) -> Vec<String> {
+        let mut labels = Vec::new();
+        for classifier in self.classifiers().filter(|c| c.unique_component_id == unique_id as c_int) {
+            // SAFETY: label_info_list is owned by the classifier meta
+            labels.extend(unsafe { GListIter::<NvDsLabelInfo>::new(classifier.label_info_list) }.map(|l| l.label()));
+        }
+        labels
+    }
+
+    /// Keypoints of pose models whose parser stores them in mask_params as (x, y, confidence)
+    /// triples (YOLOv8-pose with the DeepStream-Yolo-Pose parser does).
+    pub fn keypoints(&self) -> &[f32] {
+        let mask = &self.mask_params;
+        // Real masks have a size; keypoint parsers leave it at 0
+        if mask.data.is_null() || mask.width != 0 || mask.height != 0 {
+            return &[];
+        }
+        let len = mask.size as usize / std::mem::size_of::<c_float>();
+        // SAFETY: size is the byte length of the array at data
+        unsafe { std::slice::from_raw_parts(mask.data, len) }
+    }
+
+    /// Appends to the label nvdsosd draws above the box.
+    pub fn append_display_text(&mut self, suffix: &str) {
+        let current = if self.text_params.display_text.is_null() {
+            String::new()
+        } else {
+            // SAFETY: display_text is a NUL-terminated string allocated by DeepStream
+            unsafe { std::ffi::CStr::from_ptr(self.text_params.display_text) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        let text = std::ffi::CString::new(format!("{}{}", current, suffix).replace('\0', "")).unwrap_or_default();
+        // SAFETY: the old text was g_malloc'd; its replacement is freed by DeepStream the same way
+        unsafe {
+            glib::ffi::g_free(self.text_params.display_text as gpointer);
+            self.text_params.display_text = glib::ffi::g_strdup(text.as_ptr());
+        }
+    }
+
+    /// Renames the object: its `obj_label`, and the label nvdsosd draws when that starts
+    /// with the old name.
+    pub fn set_label(&mut self, label: &str) {
+        let old = self.label();
+        let bytes = label.as_bytes();
+        let len = bytes.len().min(MAX_LABEL_SIZE - 1);
+        for (slot, byte) in self.obj_label.iter_mut().zip(bytes[..len].iter().chain(std::iter::repeat(&0))) {
+            *slot = *byte as c_char;
+        }
+        if self.text_params.display_text.is_null() {
+            return;
+        }
+        // SAFETY: display_text is a NUL-terminated string allocated by DeepStream
+        let current = unsafe { std::ffi::CStr::from_ptr(self.text_params.display_text) }.to_string_lossy().into_owned();
+        let Some(rest) = current.strip_prefix(old.as_str()).filter(|_| !old.is_empty()) else { return };
+        let text = std::ffi::CString::new(format!("{}{}", label, rest).replace('\0', "")).unwrap_or_default();
+        // SAFETY: the old text was g_malloc'd; its replacement is freed by DeepStream the same way
+        unsafe {
+            glib::ffi::g_free(self.text_params.display_text as gpointer);
+            self.text_params.display_text = glib::ffi::g_strdup(text.as_ptr());
+        }
+    }
+
+    pub fn label(&self) -> String {
+        // SAFETY: obj_label is a fixed-size inline array
+        let bytes = unsafe {
+            std::slice::from_raw_parts(self.obj_label.as_ptr() as *const u8, MAX_LABEL_SIZE)
+        };
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(MAX_LABEL_SIZE);
+        String::from_utf8_lossy(&bytes[..end]).into_owned()
+    }
+
+    pub fn track_id(&self) -> Option<u64> {
+        if self.object_id == UNTRACKED_OBJECT_ID {
+            None
+        } else {
+            Some(self.object_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    /// Links `items` into a GList the way DeepStream does; the nodes are leaked for the test.
+    fn list<T>(items: Vec<*mut T>) -> *mut GList {
+        let mut head: *mut GList = std::ptr::null_mut();
+        for data in items.into_iter().rev() {
+            let node = Box::into_raw(Box::new(GList { data: data as gpointer, next: head, prev: std::ptr::null_mut() }));
+            if !head.is_null() {
+                // SAFETY: head was allocated just above
+                unsafe { (*head).prev = node };
+            }
+            head = node;
+        }
+        head
+    }
+
+    fn zeroed<T>() -> Box<T> {
+        // SAFETY: the meta structs are plain C data for which all zeroes is a valid value
+        Box::new(unsafe { std::mem::zeroed() })
+    }
+
+    fn object(class_id: c_int, label: &str) -> Box<ObjectMeta> {
+        let mut obj = zeroed::<ObjectMeta>();
+        obj.class_id = class_id;
+        obj.object_id = UNTRACKED_OBJECT_ID;
+        obj.set_label(label);
+        obj
+    }
+
+    fn display_text(obj: &ObjectMeta) -> String {
+        // SAFETY: set by the object methods, NUL-terminated
+        unsafe { CStr::from_ptr(obj.text_params.display_text) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn frames_and_objects_follow_the_lists_and_skip_empty_nodes() {
+        let person = Box::into_raw(object(0, "person"));
+        let car = Box::into_raw(object(2, "car"));
+        let mut frame = zeroed::<FrameMeta>();
+        frame.source_id = 3;
+        frame.obj_meta_list = list(vec![person, std::ptr::null_mut(), car]);
+        let mut batch = zeroed::<BatchMeta>();
+        batch.frame_meta_list = list(vec![Box::into_raw(frame)]);
+
+        let frames: Vec<_> = batch.frames().map(|f| f.source_id).collect();
+        assert_eq!(frames, [3]);
+        let frame = batch.frames().next().unwrap();
+        let objects: Vec<_> = frame.objects().map(|o| (o.class_id, o.label())).collect();
+        assert_eq!(objects, [(0, "person".to_string()), (2, "car".to_string())]);
+    }
+
+    #[test]
+    fn set_label_renames_the_drawn_label_too() {
+        let mut obj = object(0, "pedestrian");
+        let text = std::ffi::CString::new("pedestrian 0.91").unwrap();
+        // SAFETY: text is NUL-terminated; g_strdup allocates the copy DeepStream would free
+        obj.text_params.display_text = unsafe { glib::ffi::g_strdup(text.as_ptr()) };
+
+        obj.set_label("person");
+        assert_eq!(obj.label(), "person");
+        assert_eq!(display_text(&obj), "person 0.91");
+
+        obj.append_display_text(" #7");
+        assert_eq!(display_text(&obj), "person 0.91 #7");
+    }
+
+    #[test]
+    fn long_labels_are_truncated_to_the_inline_array() {
+        let obj = object(0, &"x".repeat(MAX_LABEL_SIZE * 2));
+        assert_eq!(obj.label().len(), MAX_LABEL_SIZE - 1);
+    }
+
+    #[test]
+    fn untracked_objects_have_no_track_id() {
+        let mut obj = object(0, "person");
+        assert_eq!(obj.track_id(), None);
+        obj.object_id = 42;
+        assert_eq!(obj.track_id(), Some(42));
+    }
+
+    #[test]
+    fn keypoints_come_only_from_maskless_params() {
+        let mut points = [0.5f32, 0.25, 0.9, 0.75, 0.5, 0.8];
+        let mut obj = object(0, "person");
+        assert!(obj.keypoints().is_empty());
+        obj.mask_params.data = points.as_mut_ptr();
+        obj.mask_params.size = std::mem::size_of_val(&points) as std::os::raw::c_uint;
+        assert_eq!(obj.keypoints(), points);
+        // A real segmentation mask has a size
+        obj.mask_params.width = 2;
+        obj.mask_params.height = 3;
+        assert!(obj.keypoints().is_empty());
+    }
+
+    #[test]
+    fn classifier_results_are_read_per_component() {
+        let mut label = zeroed::<NvDsLabelInfo>();
+        for (slot, byte) in label.result_label.iter_mut().zip(b"ABC123") {
+            *slot = *byte as c_char;
+        }
+        label.result_prob = 0.8;
+        let mut classifier = zeroed::<NvDsClassifierMeta>();
+        classifier.unique_component_id = 5;
+        classifier.label_info_list = list(vec![Box::into_raw(label)]);
+        let mut obj = object(2, "car");
+        obj.classifier_meta_list = list(vec![Box::into_raw(classifier)]);
+
+        assert_eq!(obj.classifier_result(5), Some(("ABC123".to_string(), 0.8)));
+        assert_eq!(obj.classifier_result(6), None);
+        assert_eq!(obj.classifier_labels(5), ["ABC123"]);
+    }
+
+    #[test]
+    fn user_meta_types_start_past_the_reserved_ranges() {
+        // nvdsmeta.h: NVDS_START_USER_META = NVDS_GST_CUSTOM_META + 4096 + 1
+        assert_eq!(NVDS_START_USER_META, 8193);
+        let descriptor = std::ffi::CString::new("TEST.RANGE").unwrap();
+        // SAFETY: descriptor is NUL-terminated
+        let meta_type = unsafe { nvds_get_user_meta_type(descriptor.as_ptr() as *mut _) };
+        assert!(meta_type > NVDS_START_USER_META);
+        assert!(meta_type > NVDS_GST_CUSTOM_META + 4096);
+    }
+
+    #[test]
+    fn a_buffer_without_batch_meta_has_none() {
+        gstreamer::init().unwrap();
+        let mut info = gstreamer::PadProbeInfo {
+            mask: gstreamer::PadProbeType::BUFFER,
+            id: None,
+            offset: 0,
+            size: 0,
+            data: Some(gstreamer::PadProbeData::Buffer(gstreamer::Buffer::new())),
+            flow_res: Ok(gstreamer::FlowSuccess::Ok),
+        };
+        assert!(BatchMeta::from_probe(&mut info).is_none());
+        let (buffer, batch) = probe_buffer(&mut info).unwrap();
+        assert_eq!(buffer.size(), 0);
+        assert!(batch.is_none());
+    }
+}
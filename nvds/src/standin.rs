@@ -0,0 +1,346 @@
+// Stand-ins for the generated bindings in builds without the deepstream feature: the
+// subset of nvdsmeta.h / nvll_osd_struct.h / gstnvdsinfer.h the safe layer uses, with
+// the C names bindgen gives them, so the same code compiles against either. Software
+// pipelines never attach batch meta, so the functions find none and the pools are empty.
+
+use crate::{MAX_ELEMENTS_IN_DISPLAY_META, MAX_LABEL_SIZE, MAX_RESERVED_FIELDS, MAX_USER_FIELDS, NVDS_INFER_MAX_DIMS};
+use glib::ffi::{gboolean, gpointer, GList, GRecMutex};
+use std::os::raw::{c_char, c_float, c_int, c_short, c_uint, c_void};
+
+pub type NvDsMetaType = c_int;
+
+pub const NVDS_OPTICAL_FLOW_META: NvDsMetaType = 10;
+pub const NVDSINFER_TENSOR_OUTPUT_META: NvDsMetaType = 12;
+pub const NVDS_RESERVED_META: NvDsMetaType = 4095;
+pub const NVDS_GST_CUSTOM_META: NvDsMetaType = 4096;
+pub const NVDS_START_USER_META: NvDsMetaType = NVDS_GST_CUSTOM_META + 4096 + 1;
+
+pub type NvDsMetaCopyFunc = Option<unsafe extern "C" fn(data: gpointer, user_data: gpointer) -> gpointer>;
+pub type NvDsMetaReleaseFunc = Option<unsafe extern "C" fn(data: gpointer, user_data: gpointer)>;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct NvDsBaseMeta {
+    pub batch_meta: *mut NvDsBatchMeta,
+    pub meta_type: NvDsMetaType,
+    pub uContext: *mut c_void,
+    pub copy_func: NvDsMetaCopyFunc,
+    pub release_func: NvDsMetaReleaseFunc,
+}
+
+#[repr(C)]
+pub struct NvDsBatchMeta {
+    pub base_meta: NvDsBaseMeta,
+    pub max_frames_in_batch: c_uint,
+    pub num_frames_in_batch: c_uint,
+    pub frame_meta_pool: gpointer,
+    pub obj_meta_pool: gpointer,
+    pub classifier_meta_pool: gpointer,
+    pub display_meta_pool: gpointer,
+    pub user_meta_pool: gpointer,
+    pub label_info_meta_pool: gpointer,
+    pub frame_meta_list: *mut GList,
+    pub batch_user_meta_list: *mut GList,
+    pub meta_mutex: GRecMutex,
+    pub misc_batch_info: [i64; MAX_USER_FIELDS],
+    pub reserved: [i64; MAX_RESERVED_FIELDS],
+}
+
+#[repr(C)]
+pub struct NvDsFrameMeta {
+    pub base_meta: NvDsBaseMeta,
+    pub pad_index: c_uint,
+    pub batch_id: c_uint,
+    pub frame_num: c_int,
+    pub buf_pts: u64,
+    pub ntp_timestamp: u64,
+    pub source_id: c_uint,
+    pub num_surfaces_per_frame: c_int,
+    pub source_frame_width: c_uint,
+    pub source_frame_height: c_uint,
+    pub surface_type: c_uint,
+    pub surface_index: c_uint,
+    pub num_obj_meta: c_uint,
+    pub bInferDone: gboolean,
+    pub obj_meta_list: *mut GList,
+    pub display_meta_list: *mut GList,
+    pub frame_user_meta_list: *mut GList,
+    pub misc_frame_info: [i64; MAX_USER_FIELDS],
+    pub pipeline_width: c_uint,
+    pub pipeline_height: c_uint,
+    pub reserved: [i64; MAX_RESERVED_FIELDS],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NvBbox_Coords {
+    pub left: c_float,
+    pub top: c_float,
+    pub width: c_float,
+    pub height: c_float,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NvDsComp_BboxInfo {
+    pub org_bbox_coords: NvBbox_Coords,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NvOSD_ColorParams {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    pub alpha: f64,
+}
+
+#[repr(C)]
+pub struct NvOSD_RectParams {
+    pub left: c_float,
+    pub top: c_float,
+    pub width: c_float,
+    pub height: c_float,
+    pub border_width: c_uint,
+    pub border_color: NvOSD_ColorParams,
+    pub has_bg_color: c_uint,
+    pub reserved: c_uint,
+    pub bg_color: NvOSD_ColorParams,
+    pub has_color_info: c_int,
+    pub color_id: c_int,
+}
+
+#[repr(C)]
+pub struct NvOSD_MaskParams {
+    pub data: *mut c_float,
+    pub size: c_uint,
+    pub threshold: c_float,
+    pub width: c_uint,
+    pub height: c_uint,
+}
+
+#[repr(C)]
+pub struct NvOSD_FontParams {
+    pub font_name: *mut c_char,
+    pub font_size: c_uint,
+    pub font_color: NvOSD_ColorParams,
+}
+
+#[repr(C)]
+pub struct NvOSD_TextParams {
+    pub display_text: *mut c_char,
+    pub x_offset: c_uint,
+    pub y_offset: c_uint,
+    pub font_params: NvOSD_FontParams,
+    pub set_bg_clr: c_int,
+    pub text_bg_clr: NvOSD_ColorParams,
+}
+
+#[repr(C)]
+pub struct NvOSD_LineParams {
+    pub x1: c_uint,
+    pub y1: c_uint,
+    pub x2: c_uint,
+    pub y2: c_uint,
+    pub line_width: c_uint,
+    pub line_color: NvOSD_ColorParams,
+}
+
+pub type NvOSD_Arrow_Head_Direction = c_uint;
+
+#[repr(C)]
+pub struct NvOSD_ArrowParams {
+    pub x1: c_uint,
+    pub y1: c_uint,
+    pub x2: c_uint,
+    pub y2: c_uint,
+    pub arrow_width: c_uint,
+    pub arrow_head: NvOSD_Arrow_Head_Direction,
+    pub arrow_color: NvOSD_ColorParams,
+    pub reserved: c_uint,
+}
+
+#[repr(C)]
+pub struct NvOSD_CircleParams {
+    pub xc: c_uint,
+    pub yc: c_uint,
+    pub radius: c_uint,
+    pub circle_color: NvOSD_ColorParams,
+    pub has_bg_color: c_uint,
+    pub bg_color: NvOSD_ColorParams,
+    pub reserved: c_uint,
+}
+
+#[repr(C)]
+pub struct NvDsDisplayMeta {
+    pub base_meta: NvDsBaseMeta,
+    pub num_rects: c_uint,
+    pub num_labels: c_uint,
+    pub num_lines: c_uint,
+    pub num_arrows: c_uint,
+    pub num_circles: c_uint,
+    pub rect_params: [NvOSD_RectParams; MAX_ELEMENTS_IN_DISPLAY_META],
+    pub text_params: [NvOSD_TextParams; MAX_ELEMENTS_IN_DISPLAY_META],
+    pub line_params: [NvOSD_LineParams; MAX_ELEMENTS_IN_DISPLAY_META],
+    pub arrow_params: [NvOSD_ArrowParams; MAX_ELEMENTS_IN_DISPLAY_META],
+    pub circle_params: [NvOSD_CircleParams; MAX_ELEMENTS_IN_DISPLAY_META],
+    pub misc_osd_data: [i64; MAX_USER_FIELDS],
+    pub reserved: [i64; MAX_RESERVED_FIELDS],
+}
+
+#[repr(C)]
+pub struct NvDsObjectMeta {
+    pub base_meta: NvDsBaseMeta,
+    pub parent: *mut NvDsObjectMeta,
+    pub unique_component_id: c_int,
+    pub class_id: c_int,
+    pub object_id: u64,
+    pub detector_bbox_info: NvDsComp_BboxInfo,
+    pub tracker_bbox_info: NvDsComp_BboxInfo,
+    pub confidence: c_float,
+    pub tracker_confidence: c_float,
+    pub rect_params: NvOSD_RectParams,
+    pub mask_params: NvOSD_MaskParams,
+    pub text_params: NvOSD_TextParams,
+    pub obj_label: [c_char; MAX_LABEL_SIZE],
+    pub classifier_meta_list: *mut GList,
+    pub obj_user_meta_list: *mut GList,
+    pub misc_obj_info: [i64; MAX_USER_FIELDS],
+    pub reserved: [i64; MAX_RESERVED_FIELDS],
+}
+
+#[repr(C)]
+pub struct NvDsClassifierMeta {
+    pub base_meta: NvDsBaseMeta,
+    pub num_labels: c_uint,
+    pub unique_component_id: c_int,
+    pub label_info_list: *mut GList,
+    pub classifier_type: *const c_char,
+}
+
+#[repr(C)]
+pub struct NvDsLabelInfo {
+    pub base_meta: NvDsBaseMeta,
+    pub num_classes: c_uint,
+    pub result_label: [c_char; MAX_LABEL_SIZE],
+    pub pResult_label: *mut c_char,
+    pub result_class_id: c_uint,
+    pub label_id: c_uint,
+    pub result_prob: c_float,
+}
+
+#[repr(C)]
+pub struct NvDsUserMeta {
+    pub base_meta: NvDsBaseMeta,
+    pub user_meta_data: *mut c_void,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NvOFFlowVector {
+    pub flowx: c_short,
+    pub flowy: c_short,
+}
+
+#[repr(C)]
+pub struct NvDsOpticalFlowMeta {
+    pub rows: c_uint,
+    pub cols: c_uint,
+    pub mv_size: c_uint,
+    pub frame_num: u64,
+    pub data: *mut c_void,
+    pub priv_: *mut c_void,
+    pub reserved: [*mut c_void; 4],
+}
+
+pub type NvDsInferDataType = c_uint;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NvDsInferDims {
+    pub numDims: c_uint,
+    pub d: [c_uint; NVDS_INFER_MAX_DIMS],
+    pub numElements: c_uint,
+}
+
+/// `inferDims` and its deprecated alias `dims`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union NvDsInferLayerInfo__bindgen_ty_1 {
+    pub inferDims: NvDsInferDims,
+    pub dims: NvDsInferDims,
+}
+
+#[repr(C)]
+pub struct NvDsInferLayerInfo {
+    pub dataType: NvDsInferDataType,
+    pub __bindgen_anon_1: NvDsInferLayerInfo__bindgen_ty_1,
+    pub bindingIndex: c_int,
+    pub layerName: *const c_char,
+    pub buffer: *mut c_void,
+    pub isInput: c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NvDsInferNetworkInfo {
+    pub width: c_uint,
+    pub height: c_uint,
+    pub channels: c_uint,
+}
+
+/// Only the leading fields; the struct is only read through pointers.
+#[repr(C)]
+pub struct NvDsInferTensorMeta {
+    pub unique_id: c_uint,
+    pub num_output_layers: c_uint,
+    pub output_layers_info: *mut NvDsInferLayerInfo,
+    pub out_buf_ptrs_host: *mut *mut c_void,
+    pub out_buf_ptrs_dev: *mut *mut c_void,
+    pub gpu_id: c_int,
+    pub priv_data: *mut c_void,
+    pub network_info: NvDsInferNetworkInfo,
+}
+
+/// # Safety
+/// Always safe; kept unsafe to match the DeepStream signature.
+pub unsafe fn gst_buffer_get_nvds_batch_meta(_buffer: *mut gstreamer::ffi::GstBuffer) -> *mut NvDsBatchMeta {
+    std::ptr::null_mut()
+}
+
+/// # Safety
+/// Always safe; kept unsafe to match the DeepStream signature.
+pub unsafe fn nvds_acquire_display_meta_from_pool(_batch_meta: *mut NvDsBatchMeta) -> *mut NvDsDisplayMeta {
+    std::ptr::null_mut()
+}
+
+/// # Safety
+/// Always safe; kept unsafe to match the DeepStream signature.
+pub unsafe fn nvds_add_display_meta_to_frame(_frame_meta: *mut NvDsFrameMeta, _display_meta: *mut NvDsDisplayMeta) {}
+
+/// # Safety
+/// Always safe; kept unsafe to match the DeepStream signature.
+pub unsafe fn nvds_remove_obj_meta_from_frame(_frame_meta: *mut NvDsFrameMeta, _obj_meta: *mut NvDsObjectMeta) {}
+
+/// # Safety
+/// Always safe; kept unsafe to match the DeepStream signature.
+pub unsafe fn nvds_acquire_user_meta_from_pool(_batch_meta: *mut NvDsBatchMeta) -> *mut NvDsUserMeta {
+    std::ptr::null_mut()
+}
+
+/// # Safety
+/// Always safe; kept unsafe to match the DeepStream signature.
+pub unsafe fn nvds_add_user_meta_to_frame(_frame_meta: *mut NvDsFrameMeta, _user_meta: *mut NvDsUserMeta) {}
+
+/// # Safety
+/// Always safe; kept unsafe to match the DeepStream signature.
+pub unsafe fn nvds_add_user_meta_to_obj(_obj_meta: *mut NvDsObjectMeta, _user_meta: *mut NvDsUserMeta) {}
+
+/// Distinct per descriptor, like DeepStream's, so metadata built by hand (e.g. in tests)
+/// still reads back.
+///
+/// # Safety
+/// `meta_descriptor` must be a NUL-terminated string.
+pub unsafe fn nvds_get_user_meta_type(meta_descriptor: *mut c_char) -> NvDsMetaType {
+    NVDS_START_USER_META + glib::ffi::g_quark_from_string(meta_descriptor) as NvDsMetaType
+}
@@ -0,0 +1,205 @@
+// Custom NvDsUserMeta carrying serialized Rust values, so one probe can hand results to
+// probes further down the pipeline (or to another binary reading the same buffers)
+// through DeepStream's own metadata instead of side channels keyed by frame number.
+// The payload is a boxed byte vector; copy_func and release_func let DeepStream duplicate
+// and free it when metadata is copied or the batch goes back to its pool.
+
+use crate::{
+    nvds_acquire_user_meta_from_pool, nvds_add_user_meta_to_frame, nvds_add_user_meta_to_obj, nvds_get_user_meta_type,
+    GListIter, NvDsBatchMeta, NvDsFrameMeta, NvDsMetaType, NvDsObjectMeta, NvDsUserMeta,
+};
+use glib::ffi::gpointer;
+use std::ffi::CString;
+
+/// A value that can travel as user meta. DESCRIPTOR names its meta type and must be
+/// unique to this layout, e.g. `DETECT.LINE_CROSSING`; bytes written by `to_bytes` are
+/// only ever handed to the same type's `from_bytes`.
+pub trait UserMeta: Sized {
+    const DESCRIPTOR: &'static str;
+
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// None when the bytes don't parse; such entries are skipped on read.
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+fn meta_type<T: UserMeta>() -> NvDsMetaType {
+    let descriptor = CString::new(T::DESCRIPTOR).expect("meta descriptor has no NUL bytes");
+    // SAFETY: descriptor is NUL-terminated; DeepStream only reads it
+    unsafe { nvds_get_user_meta_type(descriptor.as_ptr() as *mut _) }
+}
+
+/// copy_func: a deep copy of the payload for a duplicated user meta.
+unsafe extern "C" fn copy_bytes(data: gpointer, _user_data: gpointer) -> gpointer {
+    let meta = &*(data as *const NvDsUserMeta);
+    let bytes = &*(meta.user_meta_data as *const Vec<u8>);
+    Box::into_raw(Box::new(bytes.clone())) as gpointer
+}
+
+/// release_func: frees the payload when the user meta goes back to the pool.
+unsafe extern "C" fn release_bytes(data: gpointer, _user_data: gpointer) {
+    let meta = &mut *(data as *mut NvDsUserMeta);
+    if !meta.user_meta_data.is_null() {
+        drop(Box::from_raw(meta.user_meta_data as *mut Vec<u8>));
+        meta.user_meta_data = std::ptr::null_mut();
+    }
+}
+
+/// Fills a user meta fresh from the pool with `value`.
+fn fill<T: UserMeta>(meta: &mut NvDsUserMeta, value: &T) {
+    meta.user_meta_data = Box::into_raw(Box::new(value.to_bytes())) as gpointer;
+    meta.base_meta.meta_type = meta_type::<T>();
+    meta.base_meta.copy_func = Some(copy_bytes);
+    meta.base_meta.release_func = Some(release_bytes);
+}
+
+fn acquire<T: UserMeta>(batch: *mut NvDsBatchMeta, value: &T) -> Option<*mut NvDsUserMeta> {
+    // SAFETY: batch is the batch meta the frame or object belongs to; the pool is locked
+    // internally and the user meta is ours until it is added
+    let meta = unsafe { nvds_acquire_user_meta_from_pool(batch).as_mut()? };
+    fill(meta, value);
+    Some(meta)
+}
+
+fn read<T: UserMeta>(user_meta: GListIter<'_, NvDsUserMeta>) -> Vec<T> {
+    let wanted = meta_type::<T>();
+    user_meta
+        .filter(|m| m.base_meta.meta_type == wanted && !m.user_meta_data.is_null())
+        // SAFETY: user meta of T's type always holds the payload `fill` stored
+        .filter_map(|m| T::from_bytes(unsafe { &*(m.user_meta_data as *const Vec<u8>) }))
+        .collect()
+}
+
+impl NvDsFrameMeta {
+    /// Attaches `value` to this frame for probes further downstream. False when there is no
+    /// batch pool to take the meta from (software pipelines).
+    pub fn attach_user_meta<T: UserMeta>(&mut self, value: &T) -> bool {
+        let Some(meta) = acquire(self.base_meta.batch_meta, value) else { return false };
+        // SAFETY: meta came from this frame's batch pool and is owned by the frame once added
+        unsafe { nvds_add_user_meta_to_frame(self, meta) };
+        true
+    }
+
+    /// Every `T` attached to this frame upstream, in the order they were attached.
+    pub fn read_user_meta<T: UserMeta>(&mut self) -> Vec<T> {
+        read(self.user_meta())
+    }
+}
+
+impl NvDsObjectMeta {
+    /// Attaches `value` to this object for probes further downstream. False when there is
+    /// no batch pool to take the meta from (software pipelines).
+    pub fn attach_user_meta<T: UserMeta>(&mut self, value: &T) -> bool {
+        let Some(meta) = acquire(self.base_meta.batch_meta, value) else { return false };
+        // SAFETY: meta came from this object's batch pool and is owned by the object once added
+        unsafe { nvds_add_user_meta_to_obj(self, meta) };
+        true
+    }
+
+    /// Every `T` attached to this object upstream, in the order they were attached.
+    pub fn read_user_meta<T: UserMeta>(&mut self) -> Vec<T> {
+        read(self.user_meta())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glib::ffi::GList;
+
+    #[derive(Debug, PartialEq)]
+    struct Crossing {
+        line: u8,
+        inbound: bool,
+    }
+
+    impl UserMeta for Crossing {
+        const DESCRIPTOR: &'static str = "TEST.CROSSING";
+
+        fn to_bytes(&self) -> Vec<u8> {
+            vec![self.line, self.inbound as u8]
+        }
+
+        fn from_bytes(bytes: &[u8]) -> Option<Crossing> {
+            match bytes {
+                [line, inbound] => Some(Crossing { line: *line, inbound: *inbound != 0 }),
+                _ => None,
+            }
+        }
+    }
+
+    struct Note(String);
+
+    impl UserMeta for Note {
+        const DESCRIPTOR: &'static str = "TEST.NOTE";
+
+        fn to_bytes(&self) -> Vec<u8> {
+            self.0.as_bytes().to_vec()
+        }
+
+        fn from_bytes(bytes: &[u8]) -> Option<Note> {
+            String::from_utf8(bytes.to_vec()).ok().map(Note)
+        }
+    }
+
+    fn user_meta() -> Box<NvDsUserMeta> {
+        // SAFETY: NvDsUserMeta is plain C data for which all zeroes is a valid value
+        Box::new(unsafe { std::mem::zeroed() })
+    }
+
+    fn filled<T: UserMeta>(value: &T) -> *mut NvDsUserMeta {
+        let mut meta = user_meta();
+        fill(&mut meta, value);
+        Box::into_raw(meta)
+    }
+
+    /// A GList over `items`, leaked for the test.
+    fn list(items: Vec<*mut NvDsUserMeta>) -> *mut GList {
+        items.into_iter().rev().fold(std::ptr::null_mut(), |next, data| {
+            Box::into_raw(Box::new(GList { data: data as gpointer, next, prev: std::ptr::null_mut() }))
+        })
+    }
+
+    #[test]
+    fn values_read_back_by_type_in_attach_order() {
+        let mut tensor = user_meta();
+        tensor.base_meta.meta_type = crate::NVDSINFER_TENSOR_OUTPUT_META;
+        // SAFETY: the frame meta is plain C data for which all zeroes is a valid value
+        let mut frame: Box<NvDsFrameMeta> = Box::new(unsafe { std::mem::zeroed() });
+        frame.frame_user_meta_list = list(vec![
+            filled(&Crossing { line: 1, inbound: true }),
+            Box::into_raw(tensor),
+            filled(&Note("hello".to_string())),
+            filled(&Crossing { line: 2, inbound: false }),
+        ]);
+
+        assert_eq!(
+            frame.read_user_meta::<Crossing>(),
+            [Crossing { line: 1, inbound: true }, Crossing { line: 2, inbound: false }]
+        );
+        let notes: Vec<String> = frame.read_user_meta::<Note>().into_iter().map(|n| n.0).collect();
+        assert_eq!(notes, ["hello"]);
+    }
+
+    #[test]
+    fn copies_are_deep_and_release_frees_the_payload() {
+        let original = filled(&Crossing { line: 7, inbound: true });
+        // SAFETY: original was filled above; the copy is placed in a user meta of its own
+        unsafe {
+            let data = ((*original).base_meta.copy_func.unwrap())(original as gpointer, std::ptr::null_mut());
+            assert_ne!(data, (*original).user_meta_data);
+            let copy = Box::into_raw(user_meta());
+            (*copy).user_meta_data = data;
+            (*copy).base_meta.meta_type = (*original).base_meta.meta_type;
+            (*copy).base_meta.release_func = (*original).base_meta.release_func;
+
+            let release = (*original).base_meta.release_func.unwrap();
+            release(original as gpointer, std::ptr::null_mut());
+            assert!((*original).user_meta_data.is_null());
+            let read = read::<Crossing>(GListIter::new(list(vec![copy])));
+            assert_eq!(read, [Crossing { line: 7, inbound: true }]);
+            release(copy as gpointer, std::ptr::null_mut());
+            assert!((*copy).user_meta_data.is_null());
+        }
+    }
+}
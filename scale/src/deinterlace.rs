@@ -0,0 +1,47 @@
+use crate::probe::StreamInfo;
+use std::env;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Deinterlace inputs whose caps say they are interlaced
+    Auto,
+    On,
+    Off,
+}
+
+impl Mode {
+    /// Reads DEINTERLACE=auto|on|off (default auto).
+    pub fn from_env() -> Mode {
+        match env::var("DEINTERLACE").as_deref() {
+            Err(_) | Ok("auto") => Mode::Auto,
+            Ok("on") | Ok("true") => Mode::On,
+            Ok("off") | Ok("false") => Mode::Off,
+            Ok(other) => {
+                eprintln!("Error: Invalid DEINTERLACE '{}' (expected auto, on or off)", other);
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+/// Deinterlacing stage for one decoded input, placed before scaling so fields aren't
+/// scaled into combing. nvdeinterlace keeps frames on the GPU where it is installed;
+/// otherwise they take a round trip through the CPU `deinterlace` element.
+pub fn stage(mode: Mode, info: Option<&StreamInfo>, device: &str) -> Option<String> {
+    let deinterlace_mode = match mode {
+        Mode::Off => return None,
+        // Only frames flagged as interlaced are touched, so mixed content stays sharp
+        Mode::Auto if info.map(|i| i.interlaced).unwrap_or(false) => "auto",
+        Mode::Auto => return None,
+        Mode::On => "interlaced",
+    };
+    println!("Deinterlacing {}", device);
+    if gstreamer::ElementFactory::find("nvdeinterlace").is_some() {
+        Some("nvvideoconvert ! video/x-raw(memory:NVMM) ! nvdeinterlace".to_string())
+    } else {
+        Some(format!(
+            "nvvideoconvert ! video/x-raw ! deinterlace mode={} ! nvvideoconvert ! video/x-raw(memory:NVMM)",
+            deinterlace_mode
+        ))
+    }
+}
@@ -0,0 +1,73 @@
+use std::env;
+
+/// Pixel formats nvvideoconvert can produce for strict downstream consumers
+const FORMATS: &[&str] = &["NV12", "I420", "RGBA", "BGRx"];
+
+/// Output pixel format and, for YUV formats, the color range and matrix, applied where
+/// the frames are scaled.
+pub struct OutputFormat {
+    pub format: String,
+    /// Caps colorimetry (range:matrix:transfer:primaries) when range or matrix is set
+    pub colorimetry: Option<String>,
+}
+
+impl OutputFormat {
+    /// Reads OUTPUT_FORMAT (NV12, I420, RGBA or BGRx), OUTPUT_RANGE (full or limited)
+    /// and OUTPUT_MATRIX (bt601, bt709 or bt2020).
+    pub fn from_env() -> Option<OutputFormat> {
+        let format = env::var("OUTPUT_FORMAT").ok();
+        let range = env::var("OUTPUT_RANGE").ok();
+        let matrix = env::var("OUTPUT_MATRIX").ok();
+        if format.is_none() && range.is_none() && matrix.is_none() {
+            return None;
+        }
+        let format = format.unwrap_or_else(|| "NV12".to_string());
+        let Some(format) = FORMATS.iter().find(|f| f.eq_ignore_ascii_case(&format)) else {
+            eprintln!("Error: Invalid OUTPUT_FORMAT '{}' (expected {})", format, FORMATS.join(", "));
+            std::process::exit(2);
+        };
+        let rgb = format.starts_with("RGB") || format.starts_with("BGR");
+        if rgb && (range.is_some() || matrix.is_some()) {
+            eprintln!("Warning: OUTPUT_RANGE/OUTPUT_MATRIX only apply to YUV formats, ignoring them for {}", format);
+            return Some(OutputFormat { format: format.to_string(), colorimetry: None });
+        }
+
+        // GstVideoColorimetry enums: range 1 = 0-255, 2 = 16-235; matrix, transfer and
+        // primaries per standard
+        let range_code = match range.as_deref() {
+            None | Some("limited") => 2,
+            Some("full") => 1,
+            Some(other) => {
+                eprintln!("Error: Invalid OUTPUT_RANGE '{}' (expected full or limited)", other);
+                std::process::exit(2);
+            }
+        };
+        let (matrix_code, transfer, primaries) = match matrix.as_deref() {
+            None | Some("bt709") => (3, 5, 1),
+            Some("bt601") => (4, 16, 4),
+            Some("bt2020") => (6, 13, 7),
+            Some(other) => {
+                eprintln!("Error: Invalid OUTPUT_MATRIX '{}' (expected bt601, bt709 or bt2020)", other);
+                std::process::exit(2);
+            }
+        };
+        let colorimetry = (range.is_some() || matrix.is_some())
+            .then(|| format!("{}:{}:{}:{}", range_code, matrix_code, transfer, primaries));
+        Some(OutputFormat { format: format.to_string(), colorimetry })
+    }
+
+    /// Extra caps fields for the converted frames.
+    pub fn caps(&self) -> String {
+        match &self.colorimetry {
+            Some(colorimetry) => format!(",format={},colorimetry=(string){}", self.format, colorimetry),
+            None => format!(",format={}", self.format),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.colorimetry {
+            Some(colorimetry) => format!("{} (colorimetry {})", self.format, colorimetry),
+            None => self.format.clone(),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::probe::StreamInfo;
+use gstreamer::prelude::*;
+use std::env;
+
+/// Limited-range 10-bit luma: black and nominal peak codes
+const BLACK: f64 = 64.0;
+const WHITE: f64 = 940.0;
+
+/// SDR reference white the tone curve maps to, in nits
+const SDR_WHITE: f64 = 100.0;
+
+/// 10-bit output: P010 surfaces end to end and HEVC Main10 encoding, with the input's
+/// colorimetry carried through, plus optional tone mapping for the display.
+pub struct HdrConfig {
+    pub colorimetry: Option<String>,
+    pub tonemap: bool,
+    /// Brightest highlight the tone curve keeps detail for, in nits
+    pub peak_nits: f64,
+}
+
+impl HdrConfig {
+    /// Reads BIT_DEPTH (8, 10 or auto: 10 when the input is; default 8), TONEMAP=true
+    /// and TONEMAP_PEAK (nits, default 1000). `input` is the probed first input.
+    pub fn from_env(input: Option<&StreamInfo>) -> Option<HdrConfig> {
+        let ten_bit = match env::var("BIT_DEPTH").as_deref() {
+            Err(_) | Ok("8") => false,
+            Ok("10") => true,
+            Ok("auto") => input.map(|i| i.ten_bit).unwrap_or(false),
+            Ok(other) => {
+                eprintln!("Error: Invalid BIT_DEPTH '{}' (expected 8, 10 or auto)", other);
+                std::process::exit(2);
+            }
+        };
+        if !ten_bit {
+            return None;
+        }
+        let peak_nits = env::var("TONEMAP_PEAK")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|p| *p > SDR_WHITE)
+            .unwrap_or(1000.0);
+        Some(HdrConfig {
+            colorimetry: input.and_then(|i| i.colorimetry.clone()),
+            tonemap: env::var("TONEMAP").map(|v| v == "true").unwrap_or(false),
+            peak_nits,
+        })
+    }
+
+    /// Extra caps fields for the scaled NVMM frames.
+    pub fn caps(&self) -> String {
+        match &self.colorimetry {
+            Some(colorimetry) => format!(",format=P010_10LE,colorimetry={}", colorimetry),
+            None => ",format=P010_10LE".to_string(),
+        }
+    }
+
+    /// HEVC Main10 encoder for P010 NVMM frames
+    pub fn encoder(bitrate: u32) -> String {
+        format!(
+            "nvvideoconvert ! video/x-raw(memory:NVMM),format=P010_10LE ! \
+             nvv4l2h265enc profile=1 bitrate={} insert-sps-pps=true ! h265parse",
+            bitrate
+        )
+    }
+
+    /// Display branch: frames come down to system memory as P010, the `tonemap` probe
+    /// maps their luma to SDR, and videoconvert makes them 8-bit for the window.
+    pub fn display_sink(&self) -> String {
+        if self.tonemap {
+            "nvvideoconvert ! video/x-raw,format=P010_10LE ! identity name=tonemap ! videoconvert ! \
+             ximagesink sync=false"
+                .to_string()
+        } else {
+            "nvvideoconvert ! ximagesink sync=false".to_string()
+        }
+    }
+
+    /// Installs the tone-mapping probe on the display branch's `tonemap` element.
+    pub fn attach_tonemap(&self, pipeline: &gstreamer::Element) {
+        if !self.tonemap {
+            return;
+        }
+        let Some(element) = pipeline.downcast_ref::<gstreamer::Bin>().and_then(|b| b.by_name("tonemap")) else {
+            eprintln!("Warning: tonemap element not found, display is not tone mapped");
+            return;
+        };
+        let lut = tone_curve(self.colorimetry.as_deref().unwrap_or(""), self.peak_nits);
+        let pad = element.static_pad("src").expect("identity has a src pad");
+        pad.add_probe(gstreamer::PadProbeType::BUFFER, move |pad, info| {
+            let Some(gstreamer::PadProbeData::Buffer(buffer)) = info.data.as_mut() else {
+                return gstreamer::PadProbeReturn::Ok;
+            };
+            let Some((width, height)) = pad.current_caps().and_then(|caps| {
+                let s = caps.structure(0)?;
+                Some((s.get::<i32>("width").ok()? as usize, s.get::<i32>("height").ok()? as usize))
+            }) else {
+                return gstreamer::PadProbeReturn::Ok;
+            };
+            let buffer = buffer.make_mut();
+            let Ok(mut map) = buffer.map_writable() else {
+                return gstreamer::PadProbeReturn::Ok;
+            };
+            // P010: the Y plane comes first with GStreamer's default stride (two bytes per
+            // pixel, rounded up to 4), each sample in the top 10 bits of a 16-bit word
+            let stride = (width * 2).div_ceil(4) * 4;
+            let data = map.as_mut_slice();
+            let luma_len = (stride * height).min(data.len());
+            for word in data[..luma_len].chunks_exact_mut(2) {
+                let sample = u16::from_le_bytes([word[0], word[1]]) >> 6;
+                word.copy_from_slice(&(lut[sample as usize] << 6).to_le_bytes());
+            }
+            gstreamer::PadProbeReturn::Ok
+        });
+        println!("Tone mapping the display to SDR (peak {} nits)", self.peak_nits);
+    }
+}
+
+/// Luma lookup table from HDR (PQ or HLG per `colorimetry`) to SDR gamma, both as
+/// limited-range 10-bit codes. Highlights are compressed with an extended Reinhard
+/// curve so `peak_nits` lands on SDR white. Other colorimetries pass through.
+fn tone_curve(colorimetry: &str, peak_nits: f64) -> Vec<u16> {
+    let pq = colorimetry.contains("pq") || colorimetry.contains("2084");
+    let hlg = colorimetry.contains("hlg") || colorimetry.contains("b67");
+    let white = peak_nits / SDR_WHITE;
+    (0..1024u16)
+        .map(|code| {
+            if !pq && !hlg {
+                return code;
+            }
+            let signal = ((code as f64 - BLACK) / (WHITE - BLACK)).clamp(0.0, 1.0);
+            let nits = if pq { pq_eotf(signal) } else { hlg_eotf(signal, peak_nits) };
+            let x = nits / SDR_WHITE;
+            let mapped = (x * (1.0 + x / (white * white)) / (1.0 + x)).clamp(0.0, 1.0);
+            let sdr = mapped.powf(1.0 / 2.4);
+            (BLACK + sdr * (WHITE - BLACK)).round() as u16
+        })
+        .collect()
+}
+
+/// SMPTE ST 2084 (PQ): signal 0-1 to absolute nits
+fn pq_eotf(signal: f64) -> f64 {
+    let (m1, m2) = (2610.0 / 16384.0, 2523.0 / 4096.0 * 128.0);
+    let (c1, c2, c3) = (3424.0 / 4096.0, 2413.0 / 4096.0 * 32.0, 2392.0 / 4096.0 * 32.0);
+    let p = signal.powf(1.0 / m2);
+    10000.0 * ((p - c1).max(0.0) / (c2 - c3 * p)).powf(1.0 / m1)
+}
+
+/// ARIB STD-B67 (HLG): inverse OETF then the reference OOTF (system gamma 1.2)
+fn hlg_eotf(signal: f64, peak_nits: f64) -> f64 {
+    let (a, b, c) = (0.17883277, 0.28466892, 0.55991073);
+    let scene = if signal <= 0.5 { signal * signal / 3.0 } else { (((signal - c) / a).exp() + b) / 12.0 };
+    peak_nits * scene.powf(1.2)
+}
@@ -3,6 +3,15 @@ use gstreamer_rtsp_server::prelude::*;
 use std::env;
 use std::path::Path;
 
+mod deinterlace;
+mod format;
+mod hdr;
+mod mosaic;
+mod passthrough;
+mod probe;
+mod transcode;
+mod watermark;
+
 fn setup_rtsp_server(pipeline_str: &str, port: &str, mount_point: &str) -> gstreamer_rtsp_server::RTSPServer {
     let server = gstreamer_rtsp_server::RTSPServer::new();
     server.set_address("0.0.0.0");
@@ -38,6 +47,29 @@ fn setup_rtsp_server(pipeline_str: &str, port: &str, mount_point: &str) -> gstre
     server
 }
 
+/// Source element for `device`, up to decoded frames. The test pattern is generated at its
+/// own native size (TEST_SOURCE_WIDTH/TEST_SOURCE_HEIGHT, default 1280x720), so it is scaled
+/// by the same nvvideoconvert as every other input.
+fn source_element(device: &str) -> String {
+    if device.starts_with("rtsp://") || device.starts_with("http://") {
+        // Network stream (RTSP, HTTP), decoded in hardware
+        format!("nvurisrcbin uri={}", device)
+    } else if device.ends_with(".mp4") || device.ends_with(".avi") || device.ends_with(".mkv") {
+        // Video file with hardware decoding
+        format!("nvurisrcbin uri=file://{}", device)
+    } else if Path::new(device).exists() && device.starts_with("/dev/video") {
+        // Local camera device
+        format!("v4l2src device={}", device)
+    } else {
+        // Fallback to test pattern
+        println!("Using test video source (no camera/stream specified)");
+        let test_pattern = "0"; // SMPTE color bars
+        let test_width = env::var("TEST_SOURCE_WIDTH").unwrap_or_else(|_| "1280".to_string());
+        let test_height = env::var("TEST_SOURCE_HEIGHT").unwrap_or_else(|_| "720".to_string());
+        format!("videotestsrc pattern={} ! video/x-raw,width={},height={}", test_pattern, test_width, test_height)
+    }
+}
+
 fn main() {
     // Initialize GStreamer
     gstreamer::init().expect("Failed to initialize GStreamer");
@@ -47,25 +79,171 @@ fn main() {
         .or_else(|_| env::var("GST_DEVICE"))
         .unwrap_or_else(|_| "test".to_string());
 
+    // Several comma-separated inputs are composited into one mosaic
+    let devices: Vec<String> = device.split(',').map(|d| d.trim().to_string()).filter(|d| !d.is_empty()).collect();
+
     // Output dimensions for scaling (optional)
     let output_width = env::var("OUTPUT_WIDTH").unwrap_or_else(|_| "1920".to_string());
     let output_height = env::var("OUTPUT_HEIGHT").unwrap_or_else(|_| "1080".to_string());
-    
+
+    // Inputs are probed (codec, size, interlacing) once, for deinterlacing and passthrough
+    let deinterlace_mode = deinterlace::Mode::from_env();
+    let passthrough_mode = env::var("PASSTHROUGH").unwrap_or_default();
+    let needs_probe = deinterlace_mode == deinterlace::Mode::Auto
+        || matches!(passthrough_mode.as_str(), "auto" | "true")
+        || env::var("BIT_DEPTH").is_ok_and(|v| v != "8");
+    let probes: Vec<Option<probe::StreamInfo>> =
+        devices.iter().map(|d| if needs_probe { probe::probe(d) } else { None }).collect();
+    let hdr = hdr::HdrConfig::from_env(probes.first().and_then(|p| p.as_ref()));
+    let output_format = format::OutputFormat::from_env();
+    if hdr.is_some() && output_format.is_some() {
+        eprintln!("Error: OUTPUT_FORMAT/OUTPUT_RANGE/OUTPUT_MATRIX can't be combined with 10-bit output");
+        std::process::exit(2);
+    }
+    let conversion_caps = match (&hdr, &output_format) {
+        (Some(hdr), _) => hdr.caps(),
+        (_, Some(format)) => format.caps(),
+        _ => String::new(),
+    };
+
+    let sources: Vec<String> = devices
+        .iter()
+        .zip(&probes)
+        .map(|(d, info)| {
+            let source = source_element(d);
+            match deinterlace::stage(deinterlace_mode, info.as_ref(), d) {
+                Some(stage) => format!("{} ! {}", source, stage),
+                None => source,
+            }
+        })
+        .collect();
+    let source = if sources.len() > 1 {
+        let (Ok(width), Ok(height)) = (output_width.parse::<u32>(), output_height.parse::<u32>()) else {
+            eprintln!("Error: The mosaic needs numeric OUTPUT_WIDTH/OUTPUT_HEIGHT");
+            std::process::exit(2);
+        };
+        mosaic::source(&sources, width, height)
+    } else {
+        sources.into_iter().next().unwrap_or_else(|| source_element(&device))
+    };
+
     // RTSP output configuration
     let rtsp_output = env::var("RTSP_OUTPUT").is_ok();
     let rtsp_output_port = env::var("RTSP_OUTPUT_PORT").unwrap_or_else(|_| "8557".to_string());
     let show_display = env::var("SHOW_DISPLAY").unwrap_or_else(|_| "true".to_string()) == "true";
 
+    // Burned-in text/logo; needs decoded frames, so it rules out passthrough
+    let watermark = watermark::Watermark::from_env();
+    let watermark_stage = watermark.as_ref().map(|w| format!("{} ! ", w.stage())).unwrap_or_default();
+    if hdr.is_some() && (devices.len() > 1 || watermark.is_some()) {
+        eprintln!("Warning: The mosaic and watermark blend 8-bit RGBA, so 10-bit output only carries 8-bit detail");
+    }
+
+    // RENDITIONS turns scale into a transcoder: one decode, one encoder per rendition
+    if let Ok(spec) = env::var("RENDITIONS") {
+        let renditions = transcode::parse_renditions(&spec).unwrap_or_else(|e| {
+            eprintln!("Error: Invalid RENDITIONS: {}", e);
+            std::process::exit(2);
+        });
+        let hls_dir = env::var("HLS_DIR").ok();
+        if hls_dir.is_some() && renditions.iter().any(|r| r.codec != probe::Codec::H264) {
+            eprintln!("Error: HLS_DIR needs h264 renditions (hlssink2 only muxes H.264)");
+            std::process::exit(2);
+        }
+        if !rtsp_output && hls_dir.is_none() {
+            eprintln!("Warning: RENDITIONS without RTSP_OUTPUT or HLS_DIR encodes into fakesinks");
+        }
+        let udp_port = env::var("RENDITION_UDP_PORT").ok().and_then(|v| v.parse().ok()).unwrap_or(5600);
+        // Renditions share one decode, so the watermark goes on before they are scaled
+        let source = match &watermark {
+            Some(watermark) => format!("{} ! nvvideoconvert ! video/x-raw(memory:NVMM) ! {}", source, watermark.stage()),
+            None => source,
+        };
+        transcode::run(
+            &source,
+            &renditions,
+            rtsp_output.then_some(rtsp_output_port.as_str()),
+            udp_port,
+            hls_dir.as_deref(),
+            hdr.as_ref(),
+        );
+        return;
+    }
+
+    // Passthrough restreams RTSP input without a decode/encode cycle: PASSTHROUGH=auto
+    // when the input already has the output size, PASSTHROUGH=true whatever its size
+    let passthrough = match passthrough_mode.as_str() {
+        "auto" | "true" if watermark.is_some() => {
+            eprintln!("Warning: PASSTHROUGH can't burn in a watermark, decoding instead");
+            None
+        }
+        "auto" | "true" if rtsp_output && devices.len() == 1 && device.starts_with("rtsp://") => match &probes[0] {
+            Some(info @ probe::StreamInfo { codec: Some(codec), .. })
+                if passthrough_mode == "true"
+                    || (info.width.to_string() == output_width && info.height.to_string() == output_height) =>
+            {
+                Some((*codec, info.width, info.height))
+            }
+            Some(info) if info.codec.is_some() => {
+                println!(
+                    "Passthrough: input is {}x{}, scaling to {}x{} instead",
+                    info.width, info.height, output_width, output_height
+                );
+                None
+            }
+            _ => {
+                eprintln!("Warning: Could not read an H.264/H.265 stream from the input, decoding it instead");
+                None
+            }
+        },
+        "auto" | "true" => {
+            eprintln!("Warning: PASSTHROUGH needs a single rtsp:// input and RTSP_OUTPUT, decoding instead");
+            None
+        }
+        "" | "false" => None,
+        other => {
+            eprintln!("Error: Invalid PASSTHROUGH '{}' (expected auto, true or false)", other);
+            std::process::exit(2);
+        }
+    };
+
     // Build pipeline with scaling
     // All pipelines use DeepStream's hardware-accelerated elements for GPU processing
     // Optimized: tee before encoding to avoid unnecessary decode/re-encode cycle
     
     // Determine output sink based on configuration
-    let output_sink = if rtsp_output {
+    let output_sink = if let (true, Some(_)) = (rtsp_output, &hdr) {
+        // 10-bit RTSP output as HEVC Main10
+        format!("{} ! rtph265pay name=pay0 pt=96", hdr::HdrConfig::encoder(4000000))
+    } else if rtsp_output {
         // RTSP output with H.264 encoding
         "nvvideoconvert ! video/x-raw(memory:NVMM),format=I420 ! \
          nvv4l2h264enc bitrate=4000000 insert-sps-pps=true ! \
          h264parse ! rtph264pay name=pay0 pt=96".to_string()
+    } else if let (Ok(path), Some(_)) = (env::var("OUTPUT_FILE"), &hdr) {
+        format!(
+            "{} ! mp4mux fragment-duration=1000 ! filesink location={}",
+            hdr::HdrConfig::encoder(8000000),
+            path
+        )
+    } else if let Ok(path) = env::var("OUTPUT_FILE") {
+        // File output; fragmented MP4 stays playable if scale is stopped without EOS
+        format!(
+            "nvvideoconvert ! video/x-raw(memory:NVMM),format=I420 ! \
+             nvv4l2h264enc bitrate=8000000 ! h264parse ! mp4mux fragment-duration=1000 ! \
+             filesink location={}",
+            path
+        )
+    } else if let Ok(socket) = env::var("OUTPUT_SHM") {
+        // Raw frames in shared memory, e.g. for OpenCV: shmsrc socket-path=... ! video/x-raw,... ! appsink
+        format!(
+            "nvvideoconvert ! video/x-raw{} ! shmsink socket-path={} sync=false wait-for-connection=false shm-size={}",
+            conversion_caps,
+            socket,
+            output_width.parse::<u64>().unwrap_or(1920) * output_height.parse::<u64>().unwrap_or(1080) * 4 * 8
+        )
+    } else if let (true, Some(hdr)) = (show_display, &hdr) {
+        hdr.display_sink()
     } else if show_display {
         // Local display only
         "nvvideoconvert ! ximagesink sync=false".to_string()
@@ -74,56 +252,70 @@ fn main() {
         "fakesink".to_string()
     };
     
-    let pipeline_str = if device.starts_with("rtsp://") || device.starts_with("http://") {
-        // Network stream (RTSP, HTTP) - scale and output
-        format!(
-            "nvurisrcbin uri={} ! \
-             nvvideoconvert interpolation-method=5 ! \
-             video/x-raw(memory:NVMM),width={},height={} ! \
-             {}",
-            device, output_width, output_height, output_sink
-        )
-    } else if device.ends_with(".mp4") || device.ends_with(".avi") || device.ends_with(".mkv") {
-        // Video file with hardware decoding and scaling
-        format!(
-            "nvurisrcbin uri=file://{} ! \
-             nvvideoconvert interpolation-method=5 ! \
-             video/x-raw(memory:NVMM),width={},height={} ! \
-             {}",
-            device, output_width, output_height, output_sink
-        )
-    } else if Path::new(&device).exists() && device.starts_with("/dev/video") {
-        // Local camera device with hardware processing and scaling
-        format!(
-            "v4l2src device={} ! \
-             nvvideoconvert interpolation-method=5 ! \
-             video/x-raw(memory:NVMM),width={},height={} ! \
-             {}",
-            device, output_width, output_height, output_sink
-        )
+    let pipeline_str = if let Some((codec, _, _)) = passthrough {
+        // Compressed input straight to the RTSP server's payloader
+        passthrough::pipeline(&device, codec)
     } else {
-        // Fallback to test pattern with hardware processing and scaling
-        println!("Using test video source (no camera/stream specified)");
-        let test_pattern = "0"; // SMPTE color bars
         format!(
-            "videotestsrc pattern={} ! video/x-raw,width={},height={} ! \
-             nvvideoconvert interpolation-method=5 ! video/x-raw(memory:NVMM) ! \
-             {}",
-            test_pattern, output_width, output_height, output_sink
+            "{} ! \
+             nvvideoconvert interpolation-method=5 ! \
+             video/x-raw(memory:NVMM),width={},height={}{} ! \
+             {}{}",
+            source,
+            output_width,
+            output_height,
+            conversion_caps,
+            watermark_stage,
+            output_sink
         )
     };
 
     println!("DeepStream GPU-Accelerated Scaling Pipeline");
-    println!("  Input: {}", device);
-    println!("  Output dimensions: {}x{}", output_width, output_height);
+    if devices.len() > 1 {
+        println!("  Mosaic: {} inputs", devices.len());
+        for (d, (x, y, w, h)) in devices.iter().zip(mosaic::layout(devices.len(), output_width.parse().unwrap_or(0), output_height.parse().unwrap_or(0))) {
+            println!("    {} at {},{} ({}x{})", d, x, y, w, h);
+        }
+    } else {
+        println!("  Input: {}", device);
+    }
+    match passthrough {
+        Some((codec, width, height)) => {
+            println!("  Passthrough: {} {}x{} restreamed without decoding", codec.name(), width, height)
+        }
+        None => println!("  Output dimensions: {}x{}", output_width, output_height),
+    }
     println!("  Display: {}", if show_display { "enabled" } else { "disabled" });
+    if let Some(hdr) = &hdr {
+        println!(
+            "  Bit depth: 10 (P010, HEVC Main10), colorimetry {}{}",
+            hdr.colorimetry.as_deref().unwrap_or("unknown"),
+            if hdr.tonemap { ", display tone mapped to SDR" } else { "" }
+        );
+    }
+    if let Some(format) = &output_format {
+        println!("  Output format: {}", format.describe());
+    }
+    if let Ok(socket) = env::var("OUTPUT_SHM") {
+        if !rtsp_output && env::var("OUTPUT_FILE").is_err() {
+            println!("  Shared memory: {} (caps video/x-raw,width={},height={}{})", socket, output_width, output_height, conversion_caps);
+        }
+    }
+    if let Some(watermark) = &watermark {
+        println!("  Watermark: {}", watermark.describe());
+    }
+    if let (false, Ok(path)) = (rtsp_output, env::var("OUTPUT_FILE")) {
+        println!("  File: {}", path);
+    }
     if rtsp_output {
         println!("  RTSP Stream: rtsp://localhost:{}/ds-scale", rtsp_output_port);
     }
     println!("  Pipeline: {}", pipeline_str);
     println!();
-    println!("Note: Video will be STRETCHED to fit {}x{} exactly", output_width, output_height);
-    println!("      To maintain aspect ratio, use matching dimensions");
+    if passthrough.is_none() {
+        println!("Note: Video will be STRETCHED to fit {}x{} exactly", output_width, output_height);
+        println!("      To maintain aspect ratio, use matching dimensions");
+    }
     
     // Handle RTSP server if RTSP output is enabled
     if rtsp_output {
@@ -138,7 +330,7 @@ fn main() {
         let server = setup_rtsp_server(&pipeline_str, &rtsp_output_port, "/ds-scale");
         
         // Attach server to main context
-        server.attach(None);
+        server.attach(None).expect("Failed to attach RTSP server");
         
         println!("RTSP server started on port {}", rtsp_output_port);
         println!("Server bound to 0.0.0.0:{}", rtsp_output_port);
@@ -157,6 +349,9 @@ fn main() {
     let pipeline = pipeline
         .downcast::<gstreamer::Pipeline>()
         .expect("Expected a gstreamer::Pipeline");
+    if let Some(hdr) = &hdr {
+        hdr.attach_tonemap(pipeline.upcast_ref());
+    }
 
     let bus = pipeline
         .bus()
@@ -0,0 +1,38 @@
+/// Grid cell of one input in the mosaic: x, y, width, height in output pixels.
+pub type Cell = (u32, u32, u32, u32);
+
+/// Near-square grid for `count` inputs filling `width`x`height` row by row, e.g. 2x2
+/// for four inputs and 3x2 for five or six. Cells are equal; unused ones stay empty.
+pub fn layout(count: usize, width: u32, height: u32) -> Vec<Cell> {
+    let columns = (count as f64).sqrt().ceil().max(1.0) as u32;
+    let rows = (count as u32).div_ceil(columns).max(1);
+    let cell_width = width / columns;
+    let cell_height = height / rows;
+    (0..count as u32)
+        .map(|i| ((i % columns) * cell_width, (i / columns) * cell_height, cell_width, cell_height))
+        .collect()
+}
+
+/// Composites every source into one `width`x`height` frame with nvcompositor. The result
+/// ends in NVMM frames, so it takes the place of a single decoded source.
+pub fn source(sources: &[String], width: u32, height: u32) -> String {
+    let mut out = String::new();
+    let mut compositor = "nvcompositor name=comp".to_string();
+    for (index, (source, (x, y, w, h))) in sources.iter().zip(layout(sources.len(), width, height)).enumerate() {
+        // nvcompositor blends RGBA surfaces
+        out.push_str(&format!(
+            "{} ! nvvideoconvert ! video/x-raw(memory:NVMM),format=RGBA ! queue ! comp.sink_{} ",
+            source, index
+        ));
+        compositor.push_str(&format!(
+            " sink_{i}::xpos={} sink_{i}::ypos={} sink_{i}::width={} sink_{i}::height={}",
+            x,
+            y,
+            w,
+            h,
+            i = index
+        ));
+    }
+    out.push_str(&format!("{} ! video/x-raw(memory:NVMM),width={},height={}", compositor, width, height));
+    out
+}
@@ -0,0 +1,11 @@
+use crate::probe::Codec;
+
+/// Restreams the input's compressed video as-is: depayload, reparse (so SPS/PPS go out
+/// with every keyframe for late joiners) and payload again as pay0 for the RTSP server.
+pub fn pipeline(uri: &str, codec: Codec) -> String {
+    let name = codec.name();
+    format!(
+        "rtspsrc location={} ! rtp{}depay ! {}parse config-interval=-1 ! rtp{}pay name=pay0 pt=96",
+        uri, name, name, name
+    )
+}
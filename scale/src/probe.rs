@@ -0,0 +1,131 @@
+use gstreamer::prelude::*;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the input's first caps when probing (cameras can be slow to answer)
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Video codec of a compressed input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    H264,
+    H265,
+}
+
+impl Codec {
+    pub fn parse(name: &str) -> Option<Codec> {
+        match name.to_lowercase().as_str() {
+            "h264" => Some(Codec::H264),
+            "h265" | "hevc" => Some(Codec::H265),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Codec::H264 => "h264",
+            Codec::H265 => "h265",
+        }
+    }
+
+    /// RTP encoding name, as in the RTSP server's udpsrc caps
+    pub fn encoding_name(&self) -> &'static str {
+        match self {
+            Codec::H264 => "H264",
+            Codec::H265 => "H265",
+        }
+    }
+}
+
+/// What the input stream carries, read from its caps before decoding.
+pub struct StreamInfo {
+    /// None for raw (camera) video or codecs other than H.264/H.265
+    pub codec: Option<Codec>,
+    pub width: i32,
+    pub height: i32,
+    /// Fields rather than progressive frames, e.g. from analog encoders
+    pub interlaced: bool,
+    /// 10-bit samples (HEVC Main10, P010 cameras)
+    pub ten_bit: bool,
+    /// Caps colorimetry, e.g. bt2100-pq for HDR10
+    pub colorimetry: Option<String>,
+}
+
+/// Reads the first video caps of `device` without decoding it: RTSP, HTTP and file inputs
+/// go through parsebin, so the caps come from the codec parser; cameras report raw caps.
+/// None for the test pattern, on errors or on timeout.
+pub fn probe(device: &str) -> Option<StreamInfo> {
+    let description = if device.starts_with("rtsp://") {
+        format!("rtspsrc location={} ! parsebin name=probe_parse", device)
+    } else if device.starts_with("http://") {
+        format!("souphttpsrc location={} ! parsebin name=probe_parse", device)
+    } else if device.ends_with(".mp4") || device.ends_with(".avi") || device.ends_with(".mkv") {
+        format!("filesrc location={} ! parsebin name=probe_parse", device)
+    } else if Path::new(device).exists() && device.starts_with("/dev/video") {
+        format!("v4l2src device={} ! fakesink sync=false", device)
+    } else {
+        return None;
+    };
+    let pipeline = gstreamer::parse_launch(&description).ok()?;
+    let bin = pipeline.clone().downcast::<gstreamer::Bin>().ok()?;
+    // Every stream parsebin finds (audio too) ends in its own fakesink
+    if let Some(parse) = bin.by_name("probe_parse") {
+        let bin = bin.downgrade();
+        parse.connect_pad_added(move |_, pad| {
+            let Some(bin) = bin.upgrade() else { return };
+            let Ok(sink) = gstreamer::ElementFactory::make("fakesink").property("sync", false).build() else {
+                return;
+            };
+            if bin.add(&sink).is_ok() {
+                let _ = sink.sync_state_with_parent();
+                let _ = pad.link(&sink.static_pad("sink").unwrap());
+            }
+        });
+    }
+    let bus = pipeline.bus()?;
+    if pipeline.set_state(gstreamer::State::Playing).is_err() {
+        return None;
+    }
+
+    let started = Instant::now();
+    let mut info = None;
+    while info.is_none() && started.elapsed() < PROBE_TIMEOUT {
+        info = bin.iterate_sinks().into_iter().flatten().find_map(|sink| {
+            let caps = sink.static_pad("sink")?.current_caps()?;
+            stream_info(caps.structure(0)?)
+        });
+        if let Some(msg) = bus.timed_pop_filtered(
+            gstreamer::ClockTime::from_mseconds(100),
+            &[gstreamer::MessageType::Error, gstreamer::MessageType::Eos],
+        ) {
+            if let gstreamer::MessageView::Error(err) = msg.view() {
+                eprintln!("Warning: Could not probe {}: {}", device, err.error());
+            }
+            break;
+        }
+    }
+    let _ = pipeline.set_state(gstreamer::State::Null);
+    info
+}
+
+fn stream_info(structure: &gstreamer::StructureRef) -> Option<StreamInfo> {
+    let codec = match structure.name().as_str() {
+        "video/x-h264" => Some(Codec::H264),
+        "video/x-h265" => Some(Codec::H265),
+        name if name.starts_with("video/") || name.starts_with("image/") => None,
+        _ => return None,
+    };
+    let interlaced = structure.get::<String>("interlace-mode").map(|m| m != "progressive").unwrap_or(false);
+    // Parsers report the profile (main-10, high-10); raw video the sample format
+    let profile = structure.get::<String>("profile").unwrap_or_default();
+    let format = structure.get::<String>("format").unwrap_or_default();
+    let ten_bit = profile.contains("10") || format.contains("10");
+    Some(StreamInfo {
+        codec,
+        width: structure.get("width").ok()?,
+        height: structure.get("height").ok()?,
+        interlaced,
+        ten_bit,
+        colorimetry: structure.get::<String>("colorimetry").ok(),
+    })
+}
@@ -0,0 +1,233 @@
+use crate::hdr::HdrConfig;
+use crate::probe::Codec;
+use gstreamer::prelude::*;
+use gstreamer_rtsp_server::prelude::*;
+use std::fs;
+use std::io;
+
+/// One output of the ladder: its own size, bitrate and codec, all encoded from one decode.
+pub struct Rendition {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// Bits per second
+    pub bitrate: u32,
+    pub codec: Codec,
+}
+
+/// Parses `name:WIDTHxHEIGHT:bitrate[:codec]` entries separated by `;`, e.g.
+/// `1080p:1920x1080:6M; 720p:1280x720:3M:h265`. Bitrates take a k or M suffix; codec
+/// defaults to h264.
+pub fn parse_renditions(spec: &str) -> Result<Vec<Rendition>, String> {
+    let mut renditions: Vec<Rendition> = Vec::new();
+    for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let parts: Vec<&str> = entry.split(':').map(str::trim).collect();
+        if parts.len() < 3 || parts.len() > 4 {
+            return Err(format!("'{}': expected name:WIDTHxHEIGHT:bitrate[:codec]", entry));
+        }
+        let name = parts[0].to_string();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("'{}': name may only use letters, digits, '-' and '_'", entry));
+        }
+        if renditions.iter().any(|r| r.name == name) {
+            return Err(format!("'{}': rendition {} is listed twice", entry, name));
+        }
+        let (width, height) = parts[1]
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)))
+            .filter(|(w, h)| *w > 0 && *h > 0)
+            .ok_or_else(|| format!("'{}': invalid size '{}' (expected e.g. 1280x720)", entry, parts[1]))?;
+        let bitrate = parse_bitrate(parts[2])
+            .ok_or_else(|| format!("'{}': invalid bitrate '{}' (expected e.g. 3000000, 3000k or 3M)", entry, parts[2]))?;
+        let codec = match parts.get(3) {
+            Some(name) => Codec::parse(name).ok_or_else(|| format!("'{}': unknown codec '{}' (h264 or h265)", entry, name))?,
+            None => Codec::H264,
+        };
+        renditions.push(Rendition { name, width, height, bitrate, codec });
+    }
+    if renditions.is_empty() {
+        return Err("no renditions given".to_string());
+    }
+    Ok(renditions)
+}
+
+fn parse_bitrate(value: &str) -> Option<u32> {
+    let lower = value.to_lowercase();
+    let (number, scale) = if let Some(n) = lower.strip_suffix('m') {
+        (n, 1_000_000.0)
+    } else if let Some(n) = lower.strip_suffix('k') {
+        (n, 1_000.0)
+    } else {
+        (lower.as_str(), 1.0)
+    };
+    let bits = number.parse::<f64>().ok()? * scale;
+    (bits >= 1.0 && bits <= u32::MAX as f64).then_some(bits as u32)
+}
+
+/// Decodes `source` once and tees the frames into one scaler + encoder per rendition.
+/// Each encoded rendition is sent as RTP to its local UDP port for the RTSP server
+/// (`rtsp_port` + index) and/or written as an HLS variant under `hls_dir`. With `hdr`,
+/// h265 renditions stay 10-bit (HEVC Main10); h264 ones are converted to 8-bit.
+pub fn pipeline(
+    source: &str,
+    renditions: &[Rendition],
+    rtsp_port: Option<u16>,
+    hls_dir: Option<&str>,
+    hdr: Option<&HdrConfig>,
+) -> String {
+    let mut out = format!("{} ! nvvideoconvert ! video/x-raw(memory:NVMM) ! tee name=t", source);
+    for (index, rendition) in renditions.iter().enumerate() {
+        let codec = rendition.codec.name();
+        let (format, profile) = match hdr {
+            Some(hdr) if rendition.codec == Codec::H265 => (hdr.caps(), " profile=1"),
+            _ => (",format=I420".to_string(), ""),
+        };
+        out.push_str(&format!(
+            " t. ! queue ! nvvideoconvert interpolation-method=5 ! \
+             video/x-raw(memory:NVMM),width={},height={}{} ! \
+             nvv4l2{}enc{} bitrate={} insert-sps-pps=true ! {}parse config-interval=-1 ! tee name=r{}",
+            rendition.width, rendition.height, format, codec, profile, rendition.bitrate, codec, index
+        ));
+        let mut outputs = 0;
+        if let Some(port) = rtsp_port {
+            out.push_str(&format!(
+                " r{}. ! queue ! rtp{}pay pt=96 config-interval=1 ! udpsink host=127.0.0.1 port={} sync=false async=false",
+                index,
+                codec,
+                port + index as u16
+            ));
+            outputs += 1;
+        }
+        if let Some(dir) = hls_dir {
+            let dir = format!("{}/{}", dir.trim_end_matches('/'), rendition.name);
+            out.push_str(&format!(
+                " r{}. ! queue ! hlssink2 location={}/segment%05d.ts playlist-location={}/playlist.m3u8 \
+                 target-duration=2 max-files=10",
+                index, dir, dir
+            ));
+            outputs += 1;
+        }
+        if outputs == 0 {
+            out.push_str(&format!(" r{}. ! queue ! fakesink", index));
+        }
+    }
+    out
+}
+
+/// RTSP server launch line re-serving a rendition's RTP from its local UDP `port`.
+pub fn rtsp_launch(rendition: &Rendition, port: u16) -> String {
+    format!(
+        "( udpsrc name=pay0 port={} buffer-size=524288 \
+         caps=\"application/x-rtp, media=video, clock-rate=90000, encoding-name={}, payload=96\" )",
+        port,
+        rendition.codec.encoding_name()
+    )
+}
+
+/// Creates the variant directories and the master playlist players pick a rendition from.
+pub fn write_master_playlist(dir: &str, renditions: &[Rendition]) -> io::Result<String> {
+    let dir = dir.trim_end_matches('/');
+    let mut playlist = "#EXTM3U\n#EXT-X-VERSION:3\n".to_string();
+    for rendition in renditions {
+        fs::create_dir_all(format!("{}/{}", dir, rendition.name))?;
+        playlist.push_str(&format!(
+            "#EXT-X-STREAM-INF:BANDWIDTH={},RESOLUTION={}x{}\n{}/playlist.m3u8\n",
+            rendition.bitrate, rendition.width, rendition.height, rendition.name
+        ));
+    }
+    let path = format!("{}/master.m3u8", dir);
+    fs::write(&path, playlist)?;
+    Ok(path)
+}
+
+/// Runs the ladder until EOS or an error: the transcoding pipeline itself, plus an RTSP
+/// server on `rtsp_port` with one mount per rendition (/ds-scale/<name>).
+pub fn run(
+    source: &str,
+    renditions: &[Rendition],
+    rtsp_port: Option<&str>,
+    udp_port: u16,
+    hls_dir: Option<&str>,
+    hdr: Option<&HdrConfig>,
+) {
+    let pipeline_str = pipeline(source, renditions, rtsp_port.map(|_| udp_port), hls_dir, hdr);
+    println!("DeepStream GPU Transcoder");
+    for (index, rendition) in renditions.iter().enumerate() {
+        println!(
+            "  Rendition {}: {}x{} {} at {} bit/s",
+            rendition.name,
+            rendition.width,
+            rendition.height,
+            rendition.codec.name(),
+            rendition.bitrate
+        );
+        if let Some(port) = rtsp_port {
+            println!("    RTSP: rtsp://localhost:{}/ds-scale/{} (udp {})", port, rendition.name, udp_port + index as u16);
+        }
+    }
+    if let Some(dir) = hls_dir {
+        match write_master_playlist(dir, renditions) {
+            Ok(path) => println!("  HLS master playlist: {}", path),
+            Err(e) => {
+                eprintln!("Error: Could not write the HLS master playlist in {}: {}", dir, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    println!("  Pipeline: {}", pipeline_str);
+    println!();
+
+    // The server only re-serves RTP the pipeline sends to localhost, so every client
+    // shares the single decode
+    let _server = rtsp_port.map(|port| {
+        let server = gstreamer_rtsp_server::RTSPServer::new();
+        server.set_address("0.0.0.0");
+        server.set_service(port);
+        let mounts = server.mount_points().unwrap();
+        for (index, rendition) in renditions.iter().enumerate() {
+            let factory = gstreamer_rtsp_server::RTSPMediaFactory::new();
+            factory.set_launch(&rtsp_launch(rendition, udp_port + index as u16));
+            factory.set_shared(true);
+            mounts.add_factory(&format!("/ds-scale/{}", rendition.name), factory);
+        }
+        server.attach(None).expect("Failed to attach RTSP server");
+        println!("RTSP server started on port {}", port);
+        server
+    });
+
+    let pipeline = gstreamer::parse_launch(&pipeline_str).expect("Failed to create pipeline");
+    let bus = pipeline.bus().expect("Pipeline without bus. Shouldn't happen.");
+    let main_loop = glib::MainLoop::new(None, false);
+    let loop_ref = main_loop.clone();
+    let _watch = bus
+        .add_watch(move |_, msg| {
+            use gstreamer::MessageView;
+
+            match msg.view() {
+                MessageView::Eos(..) => {
+                    println!("End-Of-Stream reached.");
+                    loop_ref.quit();
+                }
+                MessageView::Error(err) => {
+                    eprintln!(
+                        "Error from {:?}: {} ({:?})",
+                        err.src().map(|s| s.path_string()),
+                        err.error(),
+                        err.debug()
+                    );
+                    loop_ref.quit();
+                }
+                _ => (),
+            }
+            glib::Continue(true)
+        })
+        .expect("Failed to add bus watch");
+
+    pipeline
+        .set_state(gstreamer::State::Playing)
+        .expect("Unable to set the pipeline to the `Playing` state");
+    main_loop.run();
+    pipeline
+        .set_state(gstreamer::State::Null)
+        .expect("Unable to set the pipeline to the `Null` state");
+}
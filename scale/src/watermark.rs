@@ -0,0 +1,147 @@
+use std::env;
+use std::path::Path;
+
+/// Margin between a watermark and the frame edge, in pixels
+const MARGIN: i32 = 16;
+
+#[derive(Clone, Copy)]
+enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    fn parse(key: &str, default: Corner) -> Corner {
+        match env::var(key).as_deref() {
+            Err(_) => default,
+            Ok("top-left") => Corner::TopLeft,
+            Ok("top-right") => Corner::TopRight,
+            Ok("bottom-left") => Corner::BottomLeft,
+            Ok("bottom-right") => Corner::BottomRight,
+            Ok(other) => {
+                eprintln!(
+                    "Error: Invalid {} '{}' (expected top-left, top-right, bottom-left or bottom-right)",
+                    key, other
+                );
+                std::process::exit(2);
+            }
+        }
+    }
+
+    /// textoverlay/clockoverlay halignment and valignment
+    fn alignment(&self) -> (&'static str, &'static str) {
+        match self {
+            Corner::TopLeft => ("left", "top"),
+            Corner::TopRight => ("right", "top"),
+            Corner::BottomLeft => ("left", "bottom"),
+            Corner::BottomRight => ("right", "bottom"),
+        }
+    }
+
+    /// gdkpixbufoverlay offsets; negative values count from the right/bottom edge
+    fn offsets(&self) -> (i32, i32) {
+        match self {
+            Corner::TopLeft => (MARGIN, MARGIN),
+            Corner::TopRight => (-MARGIN, MARGIN),
+            Corner::BottomLeft => (MARGIN, -MARGIN),
+            Corner::BottomRight => (-MARGIN, -MARGIN),
+        }
+    }
+}
+
+/// Text (camera name, optionally with the wall-clock time) and/or a PNG logo burned
+/// into the output frames.
+pub struct Watermark {
+    text: Option<String>,
+    timestamp: bool,
+    text_corner: Corner,
+    logo: Option<String>,
+    logo_corner: Corner,
+    opacity: f64,
+}
+
+impl Watermark {
+    /// Reads WATERMARK_TEXT / WATERMARK_TIMESTAMP / WATERMARK_TEXT_POSITION (default
+    /// top-left), WATERMARK_LOGO / WATERMARK_LOGO_POSITION (default bottom-right) and
+    /// WATERMARK_OPACITY (0-1, default 0.8).
+    pub fn from_env() -> Option<Watermark> {
+        let text = env::var("WATERMARK_TEXT").ok().filter(|t| !t.is_empty());
+        let timestamp = env::var("WATERMARK_TIMESTAMP").map(|v| v == "true").unwrap_or(false);
+        let logo = env::var("WATERMARK_LOGO").ok().filter(|l| !l.is_empty());
+        if text.is_none() && !timestamp && logo.is_none() {
+            return None;
+        }
+        if let Some(logo) = &logo {
+            if !Path::new(logo).is_file() {
+                eprintln!("Error: WATERMARK_LOGO {} does not exist", logo);
+                std::process::exit(2);
+            }
+        }
+        let opacity = match env::var("WATERMARK_OPACITY") {
+            Err(_) => 0.8,
+            Ok(v) => match v.parse::<f64>() {
+                Ok(o) if (0.0..=1.0).contains(&o) => o,
+                _ => {
+                    eprintln!("Error: Invalid WATERMARK_OPACITY '{}' (expected 0 to 1)", v);
+                    std::process::exit(2);
+                }
+            },
+        };
+        Some(Watermark {
+            text,
+            timestamp,
+            text_corner: Corner::parse("WATERMARK_TEXT_POSITION", Corner::TopLeft),
+            logo,
+            logo_corner: Corner::parse("WATERMARK_LOGO_POSITION", Corner::BottomRight),
+            opacity,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(text) = &self.text {
+            parts.push(format!("text '{}'", text));
+        }
+        if self.timestamp {
+            parts.push("timestamp".to_string());
+        }
+        if let Some(logo) = &self.logo {
+            parts.push(format!("logo {}", logo));
+        }
+        format!("{} at opacity {}", parts.join(", "), self.opacity)
+    }
+
+    /// Overlay stage for NVMM frames. The overlays draw on system memory, so frames are
+    /// converted to RGBA for them and back to NVMM afterwards.
+    pub fn stage(&self) -> String {
+        let mut out = "nvvideoconvert ! video/x-raw,format=RGBA".to_string();
+        if self.text.is_some() || self.timestamp {
+            let (halign, valign) = self.text_corner.alignment();
+            let text = self.text.clone().unwrap_or_default();
+            // Text color is ARGB; white with the watermark's alpha
+            let color = ((self.opacity * 255.0).round() as u32) << 24 | 0x00ff_ffff;
+            let element = if self.timestamp {
+                let text = if text.is_empty() { text } else { format!("{}  ", text) };
+                format!("clockoverlay time-format=\"%Y-%m-%d %H:%M:%S\" text=\"{}\"", text.replace('"', "'"))
+            } else {
+                format!("textoverlay text=\"{}\"", text.replace('"', "'"))
+            };
+            out.push_str(&format!(
+                " ! {} halignment={} valignment={} shaded-background=false color={} font-desc=\"Sans 18\" \
+                 xpad={} ypad={}",
+                element, halign, valign, color, MARGIN, MARGIN
+            ));
+        }
+        if let Some(logo) = &self.logo {
+            let (x, y) = self.logo_corner.offsets();
+            out.push_str(&format!(
+                " ! gdkpixbufoverlay location={} offset-x={} offset-y={} alpha={}",
+                logo, x, y, self.opacity
+            ));
+        }
+        out.push_str(" ! nvvideoconvert ! video/x-raw(memory:NVMM)");
+        out
+    }
+}
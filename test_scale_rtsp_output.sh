@@ -43,6 +43,7 @@
 #
 # NOTES:
 #   - Input video is STRETCHED to exact dimensions (not aspect-ratio preserving)
+#   - PASSTHROUGH=auto restreams without decoding when the input already has WIDTHxHEIGHT
 #   - To maintain aspect ratio, calculate matching dimensions
 #   - Requires DeepStream 8.0+ container with GPU support
 #   - Port must be unique (not conflicting with other RTSP servers)
@@ -109,5 +110,6 @@ docker run --rm -it \
   -e RTSP_OUTPUT=true \
   -e RTSP_OUTPUT_PORT="$RTSP_OUTPUT_PORT" \
   -e SHOW_DISPLAY=false \
+  -e PASSTHROUGH="${PASSTHROUGH:-}" \
   deepstream-rust-builder:latest \
   /workdir/target/release/scale